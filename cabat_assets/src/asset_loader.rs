@@ -14,6 +14,14 @@ pub trait AssetTypeLoader: 'static + Send + Sync {
     fn load(&self, all_storages: AllStoragesView, path: &Path) -> crate::Result<Self::AssetType>;
     fn extensions(&self) -> &[&str];
 
+    /// Load without access to the world, so the asset can be loaded on a worker
+    /// thread with [crate::asset_storage::AssetStorage::load_file_background].
+    /// Loaders that need the world return `None`.
+    #[inline]
+    fn load_detached(&self, _path: &Path) -> Option<crate::Result<Self::AssetType>> {
+        None
+    }
+
     #[inline]
     fn type_name(&self) -> &str {
         std::any::type_name::<Self::AssetType>()
@@ -28,6 +36,7 @@ pub trait AssetLoaderOuter: 'static + Send + Sync {
         all_storages: AllStoragesView,
         path: &Path,
     ) -> Result<LoadedAsset, AssetLoadError>;
+    fn load_detached(&self, path: &Path) -> Option<Result<LoadedAsset, AssetLoadError>>;
     fn extensions(&self) -> &[&str];
 
     fn type_name(&self) -> &str;
@@ -49,6 +58,14 @@ where
         }
     }
 
+    #[inline]
+    fn load_detached(&self, path: &Path) -> Option<Result<LoadedAsset, AssetLoadError>> {
+        L::load_detached(&self, path).map(|result| match result {
+            Ok(asset) => Ok(asset.into()),
+            Err(e) => Err(AssetLoadError::Other(e)),
+        })
+    }
+
     #[inline]
     fn extensions(&self) -> &[&str] {
         L::extensions(&self)
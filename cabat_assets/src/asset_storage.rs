@@ -2,20 +2,24 @@
 
 use std::{
     any::TypeId,
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     fmt::{self, Debug, Display},
     hash::BuildHasherDefault,
-    path::PathBuf,
+    marker::PhantomData,
+    path::{Path, PathBuf},
     sync::Arc,
+    time::Instant,
 };
 
+use cabat_runner::task_pool::TaskPool;
 use crossbeam::channel::TryRecvError;
 use rustc_hash::FxHasher;
 use shipyard::{AllStoragesView, Unique};
 
 use crate::{
-    asset_loader::{AssetLoaderOuter, AssetTypeLoader},
+    asset_loader::{AssetLoaderOuter, AssetTypeLoader, LoadedAsset},
     handle::{Handle, HandleId},
+    stats::{AssetChange, AssetChangeKind, AssetStats, AssetTypeStats},
     Asset,
 };
 
@@ -25,6 +29,9 @@ pub(crate) type Sender = crossbeam::channel::Sender<ReferenceCountSignal>;
 pub(crate) type Receiver = crossbeam::channel::Receiver<ReferenceCountSignal>;
 pub(crate) type Hasher = BuildHasherDefault<FxHasher>;
 
+// Number of loads and unloads kept for the stats
+const RECENT_CHANGES: usize = 32;
+
 //====================================================================
 
 pub(crate) enum ReferenceCountSignal {
@@ -46,6 +53,8 @@ pub enum AssetLoadError {
     InvalidExtension,
     NoLoaderForType(String, String), // Type Name, Ext
     InvalidCastType(String, String), // Type 1, Type 2
+    NotDetached(String),             // Type Name
+    TaskFailed(PathBuf),
 
     #[error(transparent)]
     Other(#[from] anyhow::Error),
@@ -75,6 +84,16 @@ impl Debug for AssetLoadError {
                 type_id, type_id1
             )),
 
+            AssetLoadError::NotDetached(type_name) => f.write_fmt(format_args!(
+                "Loader for type '{}' needs the world and can't load in the background",
+                type_name
+            )),
+
+            AssetLoadError::TaskFailed(path_buf) => f.write_fmt(format_args!(
+                "Background load of '{:?}' stopped without a result",
+                path_buf
+            )),
+
             AssetLoadError::Other(e) => f.write_fmt(format_args!("{}", e)),
         }
     }
@@ -91,16 +110,37 @@ impl Display for AssetLoadError {
 struct InnerStorage {
     current_id: HandleId,
     // asset_type: TypeId,
+    type_name: &'static str,
     loaded_assets: HashMap<HandleId, Arc<dyn Asset>, Hasher>,
     handle_count: HashMap<HandleId, u32, Hasher>,
+    paths: HashMap<HandleId, PathBuf, Hasher>,
 }
 impl InnerStorage {
     fn new<A: Asset>() -> Self {
         Self {
             current_id: HandleId::from_id::<A>(0),
             // asset_type: std::any::TypeId::of::<A>(),
+            type_name: std::any::type_name::<A>(),
             loaded_assets: HashMap::default(),
             handle_count: HashMap::default(),
+            paths: HashMap::default(),
+        }
+    }
+
+    fn stats(&self) -> AssetTypeStats {
+        let (cpu_memory, gpu_memory) = self
+            .loaded_assets
+            .values()
+            .fold((0, 0), |(cpu, gpu), asset| {
+                (cpu + asset.cpu_memory(), gpu + asset.gpu_memory())
+            });
+
+        AssetTypeStats {
+            type_name: self.type_name,
+            count: self.loaded_assets.len(),
+            handles: self.handle_count.values().sum(),
+            cpu_memory,
+            gpu_memory,
         }
     }
 
@@ -129,6 +169,7 @@ pub struct AssetStorage {
     storages: HashMap<TypeId, InnerStorage, Hasher>,
 
     removed_assets: Vec<HandleId>,
+    recent_changes: VecDeque<AssetChange>,
 }
 
 impl Default for AssetStorage {
@@ -150,6 +191,7 @@ impl Default for AssetStorage {
             storages: HashMap::default(),
 
             removed_assets: Vec::new(),
+            recent_changes: VecDeque::with_capacity(RECENT_CHANGES),
         }
     }
 }
@@ -160,14 +202,65 @@ impl AssetStorage {
         Self::default()
     }
 
+    /// Directory that asset paths are relative to.
+    #[inline]
+    pub fn load_path(&self) -> &Path {
+        &self.load_path
+    }
+
+    #[inline]
+    pub fn set_load_path(&mut self, path: impl Into<PathBuf>) {
+        self.load_path = path.into();
+    }
+
     pub(crate) fn register_loader<L: AssetTypeLoader>(&mut self, loader: L) {
         let type_id = std::any::TypeId::of::<L::AssetType>();
         self.asset_loaders.insert(type_id, Arc::new(loader));
     }
+
+    /// Counts and estimated memory of the stored assets, by type.
+    pub fn stats(&self) -> AssetStats {
+        let mut types = self
+            .storages
+            .values()
+            .map(InnerStorage::stats)
+            .collect::<Vec<_>>();
+
+        types.sort_by_key(|stats| stats.type_name);
+
+        AssetStats {
+            types,
+            recent: self.recent_changes.iter().cloned().collect(),
+        }
+    }
+
+    fn record_change(&mut self, change: AssetChange) {
+        if self.recent_changes.len() == RECENT_CHANGES {
+            self.recent_changes.pop_front();
+        }
+
+        self.recent_changes.push_back(change);
+    }
 }
 
 //====================================================================
 
+/// Asset being loaded on a worker thread, from [AssetStorage::load_file_background].
+pub struct PendingAsset<A: Asset> {
+    path: PathBuf,
+    receiver: crossbeam::channel::Receiver<Result<LoadedAsset, AssetLoadError>>,
+    phantom: PhantomData<A>,
+}
+
+impl<A: Asset> PendingAsset<A> {
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+//--------------------------------------------------
+
 impl AssetStorage {
     pub fn load_file<'a, A>(
         &mut self,
@@ -177,7 +270,70 @@ impl AssetStorage {
     where
         A: Asset,
     {
-        let path = self.load_path.join(path.into());
+        let (path, loader) = self.find_loader::<A>(path.into())?;
+        let loaded_asset = loader.load(all_storages, path.as_path())?;
+
+        self.insert_loaded(loaded_asset, path)
+    }
+
+    /// Load the file on a [TaskPool] worker, for loaders that support
+    /// [AssetTypeLoader::load_detached]. Poll the returned [PendingAsset] with
+    /// [AssetStorage::poll_pending] to get its handle once loaded.
+    pub fn load_file_background<A>(
+        &self,
+        task_pool: &TaskPool,
+        path: impl Into<PathBuf>,
+    ) -> Result<PendingAsset<A>, AssetLoadError>
+    where
+        A: Asset,
+    {
+        let (path, loader) = self.find_loader::<A>(path.into())?;
+        let (sender, receiver) = crossbeam::channel::bounded(1);
+
+        let task_path = path.clone();
+        task_pool.spawn(move || {
+            let loaded_asset = loader.load_detached(&task_path).unwrap_or_else(|| {
+                Err(AssetLoadError::NotDetached(loader.type_name().to_string()))
+            });
+
+            sender.send(loaded_asset).ok();
+        });
+
+        Ok(PendingAsset {
+            path,
+            receiver,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Handle to the asset if its background load has finished. Returns `None`
+    /// while it's still loading.
+    pub fn poll_pending<A>(
+        &mut self,
+        pending: &PendingAsset<A>,
+    ) -> Option<Result<Handle<A>, AssetLoadError>>
+    where
+        A: Asset,
+    {
+        let loaded_asset = match pending.receiver.try_recv() {
+            Ok(loaded_asset) => loaded_asset,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => {
+                return Some(Err(AssetLoadError::TaskFailed(pending.path.clone())))
+            }
+        };
+
+        Some(
+            loaded_asset
+                .and_then(|loaded_asset| self.insert_loaded(loaded_asset, pending.path.clone())),
+        )
+    }
+
+    fn find_loader<A: Asset>(
+        &self,
+        path: PathBuf,
+    ) -> Result<(PathBuf, Arc<dyn AssetLoaderOuter>), AssetLoadError> {
+        let path = self.load_path.join(path);
         let type_id = std::any::TypeId::of::<A>();
         let type_name = std::any::type_name::<A>();
 
@@ -200,33 +356,46 @@ impl AssetStorage {
         let ext = path.extension().ok_or(AssetLoadError::InvalidExtension)?;
 
         //--------------------------------------------------
-        // Load asset
+        // Find loader
 
         let loader = self
             .asset_loaders
             .iter()
             .find(|(id, val)| **id == type_id && val.extensions().contains(&ext.to_str().unwrap()));
 
-        let (_, loader) = match loader {
-            Some(loader) => loader,
-            None => {
-                return Err(AssetLoadError::NoLoaderForType(
-                    type_name.to_string(),
-                    format!("{:?}", ext),
-                ))
-            }
-        };
-
-        let loaded_asset = loader.load(all_storages, path.as_path())?;
-
-        //--------------------------------------------------
-        // Convert data and create handle
+        match loader {
+            Some((_, loader)) => Ok((path, loader.clone())),
+            None => Err(AssetLoadError::NoLoaderForType(
+                type_name.to_string(),
+                format!("{:?}", ext),
+            )),
+        }
+    }
 
+    // Convert data and create handle
+    fn insert_loaded<A: Asset>(
+        &mut self,
+        loaded_asset: LoadedAsset,
+        path: PathBuf,
+    ) -> Result<Handle<A>, AssetLoadError> {
         let data: Box<A> = loaded_asset.data.downcast().map_err(|_| {
-            AssetLoadError::InvalidCastType(loaded_asset.type_name, type_name.to_string())
+            AssetLoadError::InvalidCastType(
+                loaded_asset.type_name,
+                std::any::type_name::<A>().to_string(),
+            )
         })?;
 
-        let data = Arc::new(*data);
+        Ok(self.insert_asset_inner(*data, Some(path)))
+    }
+
+    #[inline]
+    pub fn insert_asset<A: Asset>(&mut self, asset: A) -> Handle<A> {
+        self.insert_asset_inner(asset, None)
+    }
+
+    fn insert_asset_inner<A: Asset>(&mut self, asset: A, path: Option<PathBuf>) -> Handle<A> {
+        let type_id = std::any::TypeId::of::<A>();
+        let data = Arc::new(asset);
 
         let storage = self
             .storages
@@ -234,11 +403,20 @@ impl AssetStorage {
             .or_insert(InnerStorage::new::<A>());
 
         let handle_id = storage.insert_data(data.clone());
-        let handle = Handle::new(handle_id, self.sender.clone(), data);
 
-        Ok(handle)
+        if let Some(path) = &path {
+            storage.paths.insert(handle_id, path.clone());
+        }
 
-        //--------------------------------------------------
+        self.record_change(AssetChange {
+            kind: AssetChangeKind::Loaded,
+            id: handle_id,
+            type_name: std::any::type_name::<A>(),
+            path,
+            time: Instant::now(),
+        });
+
+        Handle::new(handle_id, self.sender.clone(), data)
     }
 
     pub fn get_storage<A: Asset>(&self) -> Option<&HashMap<HandleId, Arc<dyn Asset>, Hasher>> {
@@ -313,17 +491,30 @@ impl AssetStorage {
         }
 
         // Remove pending assets
-        self.removed_assets.iter().for_each(|handle_id| {
-            let storage = match self.storages.get_mut(&handle_id.get_type_id()) {
-                Some(storage) => storage,
-                None => unimplemented!(),
-            };
-
-            storage.loaded_assets.remove(&handle_id);
-            storage.handle_count.remove(&handle_id);
-
-            // TODO - Asset path removal
-        });
+        let time = Instant::now();
+
+        std::mem::take(&mut self.removed_assets)
+            .into_iter()
+            .for_each(|handle_id| {
+                let storage = match self.storages.get_mut(&handle_id.get_type_id()) {
+                    Some(storage) => storage,
+                    None => unimplemented!(),
+                };
+
+                storage.loaded_assets.remove(&handle_id);
+                storage.handle_count.remove(&handle_id);
+
+                let change = AssetChange {
+                    kind: AssetChangeKind::Unloaded,
+                    id: handle_id,
+                    type_name: storage.type_name,
+                    path: storage.paths.remove(&handle_id),
+                    time,
+                };
+
+                self.record_change(change);
+                self.removed_assets.push(handle_id);
+            });
     }
 }
 
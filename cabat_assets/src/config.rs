@@ -0,0 +1,360 @@
+//====================================================================
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use cabat_shipyard::{prelude::*, GetWorld};
+use serde::de::DeserializeOwned;
+use shipyard::{AllStorages, AllStoragesView, Unique};
+
+use crate::{asset_loader::AssetTypeLoader, Asset, RegisterAssetLoader};
+
+//====================================================================
+
+// How often the config file is checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+//====================================================================
+
+/// Loads a config file at startup into the settings uniques registered with
+/// [RegisterConfigSection::register_config_section]. While watching, the file
+/// is reapplied whenever it changes on disk and a [ConfigReloadedEvent] is sent.
+///
+/// ```toml
+/// [renderer]
+/// present_mode = "Fifo"
+/// ```
+pub struct ConfigPlugin {
+    path: PathBuf,
+    watch: bool,
+}
+
+impl ConfigPlugin {
+    /// Path to a `.toml` or `.ron` file, relative to the working directory.
+    #[inline]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            watch: true,
+        }
+    }
+
+    #[inline]
+    pub fn with_watch(mut self, watch: bool) -> Self {
+        self.watch = watch;
+        self
+    }
+}
+
+impl Plugin for ConfigPlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        let mut file = ConfigFile {
+            path: self.path,
+            watch: self.watch,
+            modified: None,
+            last_check: Instant::now(),
+            config: None,
+        };
+
+        file.reload();
+
+        // Sections registered by plugins built before this one
+        if let Some(config) = &file.config {
+            builder.get_world().run(|all_storages: AllStoragesView| {
+                if let Ok(registry) = all_storages.borrow::<Res<ConfigRegistry>>() {
+                    registry.apply_all(&all_storages, config);
+                }
+            });
+        }
+
+        builder
+            .insert(file)
+            .register_loader(ConfigLoader)
+            .add_workload_first(Stages::First, sys_watch_config);
+    }
+}
+
+//====================================================================
+
+/// Settings file split into named sections. Each section is deserialized into
+/// whichever type was registered for it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    sections: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Asset for Config {}
+
+impl Config {
+    pub fn from_toml(text: &str) -> crate::Result<Self> {
+        Ok(Self {
+            sections: toml::from_str(text)?,
+        })
+    }
+
+    pub fn from_ron(text: &str) -> crate::Result<Self> {
+        Ok(Self {
+            sections: ron::from_str(text)?,
+        })
+    }
+
+    /// Parse a file, choosing the format from its extension.
+    pub fn load(path: &Path) -> crate::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml(&text),
+            Some("ron") => Self::from_ron(&text),
+            _ => Err(anyhow::anyhow!(
+                "Unsupported config format for file {:?}",
+                path
+            )),
+        }
+    }
+
+    #[inline]
+    pub fn has_section(&self, name: &str) -> bool {
+        self.sections.contains_key(name)
+    }
+
+    #[inline]
+    pub fn sections(&self) -> impl Iterator<Item = &str> {
+        self.sections.keys().map(String::as_str)
+    }
+
+    /// Deserialize a section, if the config has it.
+    pub fn get<T: DeserializeOwned>(&self, name: &str) -> Option<crate::Result<T>> {
+        self.sections
+            .get(name)
+            .map(|section| Ok(serde_json::from_value(section.clone())?))
+    }
+}
+
+//--------------------------------------------------
+
+pub struct ConfigLoader;
+
+impl AssetTypeLoader for ConfigLoader {
+    type AssetType = Config;
+
+    fn load(&self, _all_storages: AllStoragesView, path: &Path) -> crate::Result<Self::AssetType> {
+        Config::load(path)
+    }
+
+    #[inline]
+    fn load_detached(&self, path: &Path) -> Option<crate::Result<Self::AssetType>> {
+        Some(Config::load(path))
+    }
+
+    #[inline]
+    fn extensions(&self) -> &[&str] {
+        &["toml", "ron"]
+    }
+}
+
+//====================================================================
+
+type ApplyFn = Box<dyn Fn(&AllStorages, &Config) -> crate::Result<()> + Send + Sync>;
+
+/// Types config sections are deserialized into, by section name.
+#[derive(Unique, Default)]
+pub struct ConfigRegistry {
+    sections: HashMap<String, ApplyFn>,
+}
+
+impl ConfigRegistry {
+    /// Deserialize the section into a `T` unique whenever the config is
+    /// loaded, replacing the current one. Fields missing from the file keep
+    /// their defaults when `T` uses `#[serde(default)]`.
+    pub fn register<T>(&mut self, name: &str)
+    where
+        T: Unique + Send + Sync + DeserializeOwned,
+    {
+        let section = name.to_string();
+
+        self.sections.insert(
+            name.to_string(),
+            Box::new(move |all_storages, config| {
+                let value = match config.get::<T>(&section) {
+                    Some(value) => value?,
+                    None => return Ok(()),
+                };
+
+                match all_storages.borrow::<ResMut<T>>() {
+                    Ok(mut current) => *current = value,
+                    Err(_) => all_storages.add_unique(value),
+                }
+
+                Ok(())
+            }),
+        );
+    }
+
+    fn apply(&self, all_storages: &AllStorages, config: &Config, name: &str) {
+        if let Some(apply) = self.sections.get(name) {
+            if let Err(e) = apply(all_storages, config) {
+                log::warn!("Failed to apply config section '{}': {}", name, e);
+            }
+        }
+    }
+
+    // Returns the sections that were applied
+    fn apply_all(&self, all_storages: &AllStorages, config: &Config) -> Vec<String> {
+        config
+            .sections()
+            .filter(|name| self.sections.contains_key(*name))
+            .map(|name| {
+                self.apply(all_storages, config, name);
+                name.to_string()
+            })
+            .collect()
+    }
+}
+
+//--------------------------------------------------
+
+pub trait RegisterConfigSection {
+    /// Register a settings unique with the [ConfigRegistry]. Applied straight
+    /// away if a config has already been loaded.
+    fn register_config_section<T>(&self, name: &str) -> &Self
+    where
+        T: Unique + Send + Sync + DeserializeOwned;
+}
+
+impl<W: GetWorld> RegisterConfigSection for W {
+    fn register_config_section<T>(&self, name: &str) -> &Self
+    where
+        T: Unique + Send + Sync + DeserializeOwned,
+    {
+        self.get_world().run(|all_storages: AllStoragesView| {
+            match all_storages.borrow::<ResMut<ConfigRegistry>>() {
+                Ok(mut registry) => registry.register::<T>(name),
+
+                Err(shipyard::error::GetStorage::MissingStorage { .. }) => {
+                    let mut registry = ConfigRegistry::default();
+                    registry.register::<T>(name);
+                    all_storages.add_unique(registry);
+                }
+
+                Err(_) => unimplemented!(),
+            }
+
+            if let Ok(file) = all_storages.borrow::<Res<ConfigFile>>() {
+                if let Some(config) = &file.config {
+                    let registry = all_storages.borrow::<Res<ConfigRegistry>>().unwrap();
+                    registry.apply(&all_storages, config, name);
+                }
+            }
+        });
+
+        self
+    }
+}
+
+//====================================================================
+
+/// Config file loaded by the [ConfigPlugin].
+#[derive(Unique)]
+pub struct ConfigFile {
+    path: PathBuf,
+    watch: bool,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+    config: Option<Config>,
+}
+
+impl ConfigFile {
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Last successfully loaded config.
+    #[inline]
+    pub fn config(&self) -> Option<&Config> {
+        self.config.as_ref()
+    }
+
+    #[inline]
+    pub fn watching(&self) -> bool {
+        self.watch
+    }
+
+    #[inline]
+    pub fn set_watch(&mut self, watch: bool) {
+        self.watch = watch;
+    }
+
+    #[inline]
+    fn modified_time(&self) -> Option<SystemTime> {
+        std::fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    // Keeps the previous config when the file can't be parsed
+    fn reload(&mut self) -> bool {
+        self.modified = self.modified_time();
+
+        match Config::load(&self.path) {
+            Ok(config) => {
+                log::info!("Loaded config from {:?}", self.path);
+                self.config = Some(config);
+                true
+            }
+            Err(e) => {
+                log::warn!("Failed to load config from {:?}: {}", self.path, e);
+                false
+            }
+        }
+    }
+}
+
+//--------------------------------------------------
+
+/// Sent after the config file changed on disk and was reapplied. Settings that
+/// are only read during setup need to be re-applied by their owners.
+#[derive(Event, Debug, Clone)]
+pub struct ConfigReloadedEvent {
+    /// Registered sections found in the new config.
+    pub sections: Vec<String>,
+}
+
+//====================================================================
+
+fn sys_watch_config(all_storages: AllStoragesView) {
+    let config = {
+        let mut file = all_storages.borrow::<ResMut<ConfigFile>>().unwrap();
+
+        if !file.watch || file.last_check.elapsed() < WATCH_INTERVAL {
+            return;
+        }
+
+        file.last_check = Instant::now();
+
+        let modified = file.modified_time();
+        if modified.is_none() || modified == file.modified {
+            return;
+        }
+
+        match file.reload() {
+            true => file.config.clone().unwrap(),
+            false => return,
+        }
+    };
+
+    let sections = match all_storages.borrow::<Res<ConfigRegistry>>() {
+        Ok(registry) => registry.apply_all(&all_storages, &config),
+        Err(_) => Vec::new(),
+    };
+
+    all_storages
+        .borrow::<ResMut<EventHandler>>()
+        .unwrap()
+        .add_event(ConfigReloadedEvent { sections });
+}
+
+//====================================================================
@@ -1,5 +1,6 @@
 //====================================================================
 
+use cabat_common::LaunchConfig;
 use cabat_shipyard::{prelude::*, GetWorld, UniqueTools};
 use downcast_rs::DowncastSync;
 
@@ -7,14 +8,31 @@ use crate::{asset_loader::AssetTypeLoader, asset_storage::AssetStorage};
 
 pub mod asset_loader;
 pub mod asset_storage;
+pub mod config;
 pub mod handle;
 pub mod loaders;
+pub mod stats;
 
 pub use anyhow::Result;
+pub use config::{
+    Config, ConfigPlugin, ConfigRegistry, ConfigReloadedEvent, RegisterConfigSection,
+};
 
 //====================================================================
 
-pub trait Asset: Send + Sync + DowncastSync {}
+pub use cabat_proc::Asset;
+pub trait Asset: Send + Sync + DowncastSync {
+    /// Estimated bytes of cpu memory used by the asset, reported in
+    /// [stats::AssetStats]. Assets owning heap data should include it.
+    fn cpu_memory(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+
+    /// Estimated bytes of gpu memory used by the asset.
+    fn gpu_memory(&self) -> usize {
+        0
+    }
+}
 
 //====================================================================
 
@@ -22,8 +40,16 @@ pub struct AssetStoragePlugin;
 
 impl Plugin for AssetStoragePlugin {
     fn build(self, builder: &WorkloadBuilder) {
+        let mut asset_storage = AssetStorage::new();
+
+        if let Ok(config) = builder.get_world().borrow::<Res<LaunchConfig>>() {
+            if let Some(path) = &config.asset_path {
+                asset_storage.set_load_path(path.clone());
+            }
+        }
+
         builder
-            .insert_default::<AssetStorage>()
+            .insert(asset_storage)
             .register_loader(loaders::TextLoader)
             .add_workload(Stages::Last, sys_update_storage);
     }
@@ -4,7 +4,12 @@ use crate::{asset_loader::AssetTypeLoader, Asset};
 
 //====================================================================
 
-impl Asset for String {}
+impl Asset for String {
+    #[inline]
+    fn cpu_memory(&self) -> usize {
+        std::mem::size_of::<Self>() + self.capacity()
+    }
+}
 
 pub struct TextLoader;
 
@@ -19,6 +24,10 @@ impl AssetTypeLoader for TextLoader {
         Ok(std::fs::read_to_string(path)?)
     }
 
+    fn load_detached(&self, path: &std::path::Path) -> Option<anyhow::Result<Self::AssetType>> {
+        Some(std::fs::read_to_string(path).map_err(Into::into))
+    }
+
     fn extensions(&self) -> &[&str] {
         &["txt"]
     }
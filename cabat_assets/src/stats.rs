@@ -0,0 +1,97 @@
+//====================================================================
+
+use std::{path::PathBuf, time::Instant};
+
+use crate::handle::HandleId;
+
+//====================================================================
+
+/// Snapshot of what the [crate::asset_storage::AssetStorage] is holding, from
+/// [crate::asset_storage::AssetStorage::stats].
+#[derive(Debug, Clone, Default)]
+pub struct AssetStats {
+    /// One entry per asset type that has been stored, sorted by type name.
+    pub types: Vec<AssetTypeStats>,
+    /// Assets loaded or unloaded most recently, oldest first.
+    pub recent: Vec<AssetChange>,
+}
+
+impl AssetStats {
+    #[inline]
+    pub fn asset_count(&self) -> usize {
+        self.types.iter().map(|stats| stats.count).sum()
+    }
+
+    #[inline]
+    pub fn cpu_memory(&self) -> usize {
+        self.types.iter().map(|stats| stats.cpu_memory).sum()
+    }
+
+    #[inline]
+    pub fn gpu_memory(&self) -> usize {
+        self.types.iter().map(|stats| stats.gpu_memory).sum()
+    }
+}
+
+//--------------------------------------------------
+
+#[derive(Debug, Clone)]
+pub struct AssetTypeStats {
+    pub type_name: &'static str,
+    pub count: usize,
+    /// Live handles across every asset of the type.
+    pub handles: u32,
+    /// Estimated bytes, see [crate::Asset::cpu_memory].
+    pub cpu_memory: usize,
+    /// Estimated bytes, see [crate::Asset::gpu_memory].
+    pub gpu_memory: usize,
+}
+
+impl AssetTypeStats {
+    /// Type name without its module path.
+    #[inline]
+    pub fn short_name(&self) -> &'static str {
+        self.type_name.rsplit("::").next().unwrap_or(self.type_name)
+    }
+}
+
+//--------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetChangeKind {
+    Loaded,
+    /// Removed after its last handle was dropped.
+    Unloaded,
+}
+
+#[derive(Debug, Clone)]
+pub struct AssetChange {
+    pub kind: AssetChangeKind,
+    pub id: HandleId,
+    pub type_name: &'static str,
+    /// File the asset was loaded from, if it came from one.
+    pub path: Option<PathBuf>,
+    pub time: Instant,
+}
+
+//====================================================================
+
+/// Format a byte count with a binary unit, e.g. `1.5 MiB`.
+pub fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+
+    while value >= 1024. && unit < UNITS.len() - 1 {
+        value /= 1024.;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{} {}", bytes, UNITS[0]),
+        _ => format!("{:.1} {}", value, UNITS[unit]),
+    }
+}
+
+//====================================================================
@@ -0,0 +1,38 @@
+//====================================================================
+
+use cabat_assets::RegisterAssetLoader;
+use cabat_shipyard::{prelude::*, UniqueTools};
+use shipyard::AllStoragesView;
+
+mod manager;
+mod source;
+mod spatial;
+
+pub use manager::{AudioManager, PlaybackSettings, SoundId};
+pub use source::{AudioLoader, AudioSource};
+pub use spatial::{AudioEmitter, AudioListener};
+
+//====================================================================
+
+pub struct AudioPlugin;
+
+impl Plugin for AudioPlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        builder
+            .register_loader(AudioLoader)
+            .insert_default::<AudioListener>()
+            .add_workload(Stages::Setup, sys_setup_audio)
+            .add_workload_last(Stages::Update, spatial::sys_update_spatial_audio)
+            .add_workload(Stages::Last, sys_clean_finished_sounds);
+    }
+}
+
+fn sys_setup_audio(all_storages: AllStoragesView) {
+    all_storages.add_unique(AudioManager::new());
+}
+
+fn sys_clean_finished_sounds(mut manager: ResMut<AudioManager>) {
+    manager.clean_finished();
+}
+
+//====================================================================
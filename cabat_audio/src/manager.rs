@@ -0,0 +1,356 @@
+//====================================================================
+
+use std::sync::mpsc::{self, Sender};
+
+use rodio::{OutputStream, OutputStreamHandle, Sink, Source, SpatialSink};
+use rustc_hash::FxHashMap;
+use shipyard::Unique;
+
+use crate::AudioSource;
+
+//====================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SoundId(u64);
+
+#[derive(Debug, Clone, Copy)]
+pub struct PlaybackSettings {
+    pub volume: f32,
+    pub speed: f32,
+    pub looping: bool,
+    pub paused: bool,
+}
+
+impl Default for PlaybackSettings {
+    #[inline]
+    fn default() -> Self {
+        Self::ONCE
+    }
+}
+
+impl PlaybackSettings {
+    pub const ONCE: Self = Self {
+        volume: 1.,
+        speed: 1.,
+        looping: false,
+        paused: false,
+    };
+
+    pub const LOOP: Self = Self {
+        looping: true,
+        ..Self::ONCE
+    };
+
+    #[inline]
+    pub fn with_volume(mut self, volume: f32) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    #[inline]
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    #[inline]
+    pub fn paused(mut self) -> Self {
+        self.paused = true;
+        self
+    }
+}
+
+//====================================================================
+
+enum SoundSink {
+    Flat(Sink),
+    Spatial(SpatialSink),
+}
+
+// Sink and SpatialSink share their controls but not a trait
+macro_rules! sink_call {
+    ($sink:expr, $method:ident($($arg:expr),*)) => {
+        match $sink {
+            SoundSink::Flat(sink) => sink.$method($($arg),*),
+            SoundSink::Spatial(sink) => sink.$method($($arg),*),
+        }
+    };
+}
+
+struct Sound {
+    sink: SoundSink,
+    volume: f32,
+}
+
+impl Sound {
+    fn new<S>(sink: SoundSink, source: S, settings: &PlaybackSettings, master_volume: f32) -> Self
+    where
+        S: Source + Send + 'static,
+        S::Item: rodio::Sample + Send,
+        f32: rodio::cpal::FromSample<S::Item>,
+    {
+        sink_call!(&sink, append(source));
+        sink_call!(&sink, set_volume(settings.volume * master_volume));
+        sink_call!(&sink, set_speed(settings.speed));
+
+        if settings.paused {
+            sink_call!(&sink, pause());
+        }
+
+        Self {
+            sink,
+            volume: settings.volume,
+        }
+    }
+}
+
+/// Owns the audio output and every sound currently playing. Each sound gets its
+/// own sink so it can be controlled independently.
+#[derive(Unique)]
+pub struct AudioManager {
+    // OutputStream isn't Send, so it lives on its own thread until the manager is dropped
+    _stream_thread: Option<Sender<()>>,
+    handle: Option<OutputStreamHandle>,
+
+    sounds: FxHashMap<SoundId, Sound>,
+    next_id: u64,
+    master_volume: f32,
+
+    left_ear: [f32; 3],
+    right_ear: [f32; 3],
+}
+
+impl Default for AudioManager {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioManager {
+    pub fn new() -> Self {
+        let (stream_thread, handle) = match Self::spawn_stream() {
+            Some((stream_thread, handle)) => (Some(stream_thread), Some(handle)),
+            None => (None, None),
+        };
+
+        Self {
+            _stream_thread: stream_thread,
+            handle,
+            sounds: FxHashMap::default(),
+            next_id: 0,
+            master_volume: 1.,
+
+            left_ear: [-0.1, 0., 0.],
+            right_ear: [0.1, 0., 0.],
+        }
+    }
+
+    fn spawn_stream() -> Option<(Sender<()>, OutputStreamHandle)> {
+        let (shutdown_sender, shutdown_receiver) = mpsc::channel::<()>();
+        let (handle_sender, handle_receiver) = mpsc::channel();
+
+        let spawned = std::thread::Builder::new()
+            .name("Audio Output".into())
+            .spawn(move || {
+                let (_stream, handle) = match OutputStream::try_default() {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        log::warn!("Unable to open audio output device: {}", e);
+                        return;
+                    }
+                };
+
+                if handle_sender.send(handle).is_err() {
+                    return;
+                }
+
+                // Returns once the sender is dropped along with the manager
+                let _ = shutdown_receiver.recv();
+            });
+
+        if let Err(e) = spawned {
+            log::warn!("Unable to spawn audio thread: {}", e);
+            return None;
+        }
+
+        let handle = handle_receiver.recv().ok()?;
+        log::info!("Audio output stream started");
+
+        Some((shutdown_sender, handle))
+    }
+
+    /// Whether an output device is available. Playing sounds is a no-op when there isn't one.
+    #[inline]
+    pub fn has_output(&self) -> bool {
+        self.handle.is_some()
+    }
+
+    pub fn play(&mut self, source: &AudioSource, settings: PlaybackSettings) -> Option<SoundId> {
+        let sink = match Sink::try_new(self.handle.as_ref()?) {
+            Ok(sink) => sink,
+            Err(e) => {
+                log::warn!("Unable to create audio sink: {}", e);
+                return None;
+            }
+        };
+
+        self.add_sound(SoundSink::Flat(sink), source, settings)
+    }
+
+    /// Play a sound positioned in the world. Its volume and panning follow the
+    /// [crate::AudioListener] and any [crate::AudioEmitter] referencing it.
+    pub fn play_spatial(
+        &mut self,
+        source: &AudioSource,
+        settings: PlaybackSettings,
+        position: [f32; 3],
+    ) -> Option<SoundId> {
+        let sink = match SpatialSink::try_new(
+            self.handle.as_ref()?,
+            position,
+            self.left_ear,
+            self.right_ear,
+        ) {
+            Ok(sink) => sink,
+            Err(e) => {
+                log::warn!("Unable to create spatial audio sink: {}", e);
+                return None;
+            }
+        };
+
+        self.add_sound(SoundSink::Spatial(sink), source, settings)
+    }
+
+    fn add_sound(
+        &mut self,
+        sink: SoundSink,
+        source: &AudioSource,
+        settings: PlaybackSettings,
+    ) -> Option<SoundId> {
+        let decoder = match source.decoder() {
+            Ok(decoder) => decoder,
+            Err(e) => {
+                log::warn!("Unable to decode audio source: {}", e);
+                return None;
+            }
+        };
+
+        let sound = match settings.looping {
+            true => Sound::new(
+                sink,
+                decoder.buffered().repeat_infinite(),
+                &settings,
+                self.master_volume,
+            ),
+            false => Sound::new(sink, decoder, &settings, self.master_volume),
+        };
+
+        let id = SoundId(self.next_id);
+        self.next_id += 1;
+
+        self.sounds.insert(id, sound);
+
+        Some(id)
+    }
+
+    #[inline]
+    pub fn stop(&mut self, id: SoundId) {
+        if let Some(sound) = self.sounds.remove(&id) {
+            sink_call!(&sound.sink, stop());
+        }
+    }
+
+    pub fn stop_all(&mut self) {
+        self.sounds
+            .drain()
+            .for_each(|(_, sound)| sink_call!(&sound.sink, stop()));
+    }
+
+    #[inline]
+    pub fn pause(&self, id: SoundId) {
+        if let Some(sound) = self.sounds.get(&id) {
+            sink_call!(&sound.sink, pause());
+        }
+    }
+
+    #[inline]
+    pub fn resume(&self, id: SoundId) {
+        if let Some(sound) = self.sounds.get(&id) {
+            sink_call!(&sound.sink, play());
+        }
+    }
+
+    #[inline]
+    pub fn is_playing(&self, id: SoundId) -> bool {
+        match self.sounds.get(&id) {
+            Some(sound) => {
+                !sink_call!(&sound.sink, is_paused()) && !sink_call!(&sound.sink, empty())
+            }
+            None => false,
+        }
+    }
+
+    #[inline]
+    pub fn volume(&self, id: SoundId) -> Option<f32> {
+        self.sounds.get(&id).map(|sound| sound.volume)
+    }
+
+    pub fn set_volume(&mut self, id: SoundId, volume: f32) {
+        let master_volume = self.master_volume;
+
+        if let Some(sound) = self.sounds.get_mut(&id) {
+            sound.volume = volume;
+            sink_call!(&sound.sink, set_volume(volume * master_volume));
+        }
+    }
+
+    #[inline]
+    pub fn set_speed(&self, id: SoundId, speed: f32) {
+        if let Some(sound) = self.sounds.get(&id) {
+            sink_call!(&sound.sink, set_speed(speed));
+        }
+    }
+
+    #[inline]
+    pub fn master_volume(&self) -> f32 {
+        self.master_volume
+    }
+
+    pub fn set_master_volume(&mut self, master_volume: f32) {
+        self.master_volume = master_volume;
+
+        self.sounds
+            .values()
+            .for_each(|sound| sink_call!(&sound.sink, set_volume(sound.volume * master_volume)));
+    }
+
+    pub fn set_emitter_position(&self, id: SoundId, position: [f32; 3]) {
+        if let Some(Sound {
+            sink: SoundSink::Spatial(sink),
+            ..
+        }) = self.sounds.get(&id)
+        {
+            sink.set_emitter_position(position);
+        }
+    }
+
+    pub(crate) fn set_listener_ears(&mut self, left_ear: [f32; 3], right_ear: [f32; 3]) {
+        self.left_ear = left_ear;
+        self.right_ear = right_ear;
+
+        self.sounds.values().for_each(|sound| {
+            if let SoundSink::Spatial(sink) = &sound.sink {
+                sink.set_left_ear_position(left_ear);
+                sink.set_right_ear_position(right_ear);
+            }
+        });
+    }
+
+    pub(crate) fn clean_finished(&mut self) {
+        self.sounds
+            .retain(|_, sound| !sink_call!(&sound.sink, empty()));
+    }
+}
+
+//====================================================================
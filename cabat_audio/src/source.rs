@@ -0,0 +1,83 @@
+//====================================================================
+
+use std::{io::Cursor, sync::Arc};
+
+use cabat_assets::{asset_loader::AssetTypeLoader, Asset};
+
+//====================================================================
+
+/// Encoded audio file kept in memory. Decoded each time it is played.
+#[derive(Clone)]
+pub struct AudioSource {
+    bytes: Arc<[u8]>,
+}
+
+impl Asset for AudioSource {
+    #[inline]
+    fn cpu_memory(&self) -> usize {
+        std::mem::size_of::<Self>() + self.bytes.len()
+    }
+}
+
+impl AudioSource {
+    #[inline]
+    pub fn from_bytes(bytes: impl Into<Arc<[u8]>>) -> Self {
+        Self {
+            bytes: bytes.into(),
+        }
+    }
+
+    #[inline]
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    #[inline]
+    pub(crate) fn decoder(
+        &self,
+    ) -> Result<rodio::Decoder<Cursor<Arc<[u8]>>>, rodio::decoder::DecoderError> {
+        rodio::Decoder::new(Cursor::new(self.bytes.clone()))
+    }
+}
+
+//====================================================================
+
+pub struct AudioLoader;
+
+impl AssetTypeLoader for AudioLoader {
+    type AssetType = AudioSource;
+
+    #[inline]
+    fn load(
+        &self,
+        _all_storages: shipyard::AllStoragesView,
+        path: &std::path::Path,
+    ) -> cabat_assets::Result<Self::AssetType> {
+        read_source(path)
+    }
+
+    #[inline]
+    fn load_detached(
+        &self,
+        path: &std::path::Path,
+    ) -> Option<cabat_assets::Result<Self::AssetType>> {
+        Some(read_source(path))
+    }
+
+    #[inline]
+    fn extensions(&self) -> &[&str] {
+        &["wav", "ogg", "mp3", "flac"]
+    }
+}
+
+fn read_source(path: &std::path::Path) -> cabat_assets::Result<AudioSource> {
+    let bytes = std::fs::read(path)?;
+    let source = AudioSource::from_bytes(bytes);
+
+    // Catch unsupported files on load rather than on first play
+    source.decoder()?;
+
+    Ok(source)
+}
+
+//====================================================================
@@ -0,0 +1,105 @@
+//====================================================================
+
+use cabat_shipyard::prelude::*;
+use cabat_spatial::Transform;
+use shipyard::{Component, EntityId, Get, IntoIter, Unique, View};
+
+use crate::{AudioManager, SoundId};
+
+//====================================================================
+
+/// Positions a sound started with [AudioManager::play_spatial] at the entity's [Transform].
+#[derive(Component)]
+pub struct AudioEmitter {
+    sound: SoundId,
+}
+
+impl AudioEmitter {
+    #[inline]
+    pub fn new(sound: SoundId) -> Self {
+        Self { sound }
+    }
+
+    #[inline]
+    pub fn sound(&self) -> SoundId {
+        self.sound
+    }
+}
+
+//====================================================================
+
+/// Where spatial sounds are heard from. Either follows an entity with a
+/// [Transform] or is set manually, for example from the camera each frame.
+#[derive(Unique)]
+pub struct AudioListener {
+    pub translation: glam::Vec3,
+    pub rotation: glam::Quat,
+    pub ear_distance: f32,
+    pub follow: Option<EntityId>,
+}
+
+impl Default for AudioListener {
+    fn default() -> Self {
+        Self {
+            translation: glam::Vec3::ZERO,
+            rotation: glam::Quat::IDENTITY,
+            ear_distance: 0.2,
+            follow: None,
+        }
+    }
+}
+
+impl AudioListener {
+    #[inline]
+    pub fn set_transform(&mut self, translation: glam::Vec3, rotation: glam::Quat) {
+        self.translation = translation;
+        self.rotation = rotation;
+    }
+
+    #[inline]
+    pub fn follow(&mut self, entity: EntityId) {
+        self.follow = Some(entity);
+    }
+
+    fn ears(&self) -> ([f32; 3], [f32; 3]) {
+        let offset = self.rotation * glam::Vec3::X * (self.ear_distance / 2.);
+
+        (
+            (self.translation - offset).to_array(),
+            (self.translation + offset).to_array(),
+        )
+    }
+}
+
+//====================================================================
+
+pub(crate) fn sys_update_spatial_audio(
+    mut manager: ResMut<AudioManager>,
+    mut listener: ResMut<AudioListener>,
+    v_emitter: View<AudioEmitter>,
+    v_transform: View<Transform>,
+) {
+    if let Some(entity) = listener.follow {
+        match v_transform.get(entity) {
+            Ok(transform) => {
+                listener.translation = transform.translation;
+                listener.rotation = transform.rotation;
+            }
+            Err(_) => {
+                log::warn!("Audio listener entity no longer has a transform");
+                listener.follow = None;
+            }
+        }
+    }
+
+    let (left_ear, right_ear) = listener.ears();
+    manager.set_listener_ears(left_ear, right_ear);
+
+    (&v_emitter, &v_transform)
+        .iter()
+        .for_each(|(emitter, transform)| {
+            manager.set_emitter_position(emitter.sound, transform.translation.to_array());
+        });
+}
+
+//====================================================================
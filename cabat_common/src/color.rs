@@ -0,0 +1,181 @@
+//====================================================================
+
+use std::{error::Error, fmt::Display};
+
+//====================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColorParseError {
+    InvalidLength(usize),
+    InvalidDigit(String),
+}
+
+impl Error for ColorParseError {}
+
+impl Display for ColorParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColorParseError::InvalidLength(length) => write!(
+                f,
+                "Hex color must have 3, 4, 6 or 8 digits but has {}",
+                length
+            ),
+            ColorParseError::InvalidDigit(hex) => {
+                write!(f, "Hex color '{}' contains invalid digits", hex)
+            }
+        }
+    }
+}
+
+//====================================================================
+
+#[inline]
+pub fn srgb_to_linear(value: f32) -> f32 {
+    match value <= 0.04045 {
+        true => value / 12.92,
+        false => ((value + 0.055) / 1.055).powf(2.4),
+    }
+}
+
+#[inline]
+pub fn linear_to_srgb(value: f32) -> f32 {
+    match value <= 0.0031308 {
+        true => value * 12.92,
+        false => 1.055 * value.powf(1. / 2.4) - 0.055,
+    }
+}
+
+//====================================================================
+
+/// RGBA color stored in linear space, which is what shaders writing to an sRGB
+/// surface expect. Use the `srgb` constructors for colors picked from images,
+/// color pickers or hex codes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Default for Color {
+    #[inline]
+    fn default() -> Self {
+        Self::WHITE
+    }
+}
+
+impl Color {
+    pub const WHITE: Self = Self::linear(1., 1., 1., 1.);
+    pub const BLACK: Self = Self::linear(0., 0., 0., 1.);
+    pub const RED: Self = Self::linear(1., 0., 0., 1.);
+    pub const GREEN: Self = Self::linear(0., 1., 0., 1.);
+    pub const BLUE: Self = Self::linear(0., 0., 1., 1.);
+    pub const TRANSPARENT: Self = Self::linear(0., 0., 0., 0.);
+
+    #[inline]
+    pub const fn linear(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    #[inline]
+    pub fn srgb(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self {
+            r: srgb_to_linear(r),
+            g: srgb_to_linear(g),
+            b: srgb_to_linear(b),
+            a,
+        }
+    }
+
+    #[inline]
+    pub fn srgb_u8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self::srgb(
+            r as f32 / 255.,
+            g as f32 / 255.,
+            b as f32 / 255.,
+            a as f32 / 255.,
+        )
+    }
+
+    /// Parse an sRGB hex code such as `#ff8800`, `ff8800cc` or `#f80`.
+    pub fn hex(hex: &str) -> Result<Self, ColorParseError> {
+        let digits = hex.trim().trim_start_matches('#');
+
+        let invalid = || ColorParseError::InvalidDigit(hex.to_string());
+
+        let parse = |index: usize, width: usize| -> Result<u8, ColorParseError> {
+            let value =
+                u8::from_str_radix(digits.get(index..index + width).ok_or_else(invalid)?, 16)
+                    .map_err(|_| invalid())?;
+
+            // Expand shorthand digits (f => ff)
+            Ok(match width {
+                1 => value * 17,
+                _ => value,
+            })
+        };
+
+        let [r, g, b, a] = match digits.len() {
+            3 => [parse(0, 1)?, parse(1, 1)?, parse(2, 1)?, 255],
+            4 => [parse(0, 1)?, parse(1, 1)?, parse(2, 1)?, parse(3, 1)?],
+            6 => [parse(0, 2)?, parse(2, 2)?, parse(4, 2)?, 255],
+            8 => [parse(0, 2)?, parse(2, 2)?, parse(4, 2)?, parse(6, 2)?],
+            length => return Err(ColorParseError::InvalidLength(length)),
+        };
+
+        Ok(Self::srgb_u8(r, g, b, a))
+    }
+
+    #[inline]
+    pub fn with_alpha(mut self, a: f32) -> Self {
+        self.a = a;
+        self
+    }
+
+    #[inline]
+    pub fn to_array(&self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    #[inline]
+    pub fn to_srgb_array(&self) -> [f32; 4] {
+        [
+            linear_to_srgb(self.r),
+            linear_to_srgb(self.g),
+            linear_to_srgb(self.b),
+            self.a,
+        ]
+    }
+
+    #[inline]
+    pub fn to_srgb_u8(&self) -> [u8; 4] {
+        self.to_srgb_array()
+            .map(|value| (value.clamp(0., 1.) * 255.).round() as u8)
+    }
+}
+
+//--------------------------------------------------
+
+impl From<[f32; 4]> for Color {
+    #[inline]
+    fn from(value: [f32; 4]) -> Self {
+        Self::linear(value[0], value[1], value[2], value[3])
+    }
+}
+
+impl From<Color> for [f32; 4] {
+    #[inline]
+    fn from(value: Color) -> Self {
+        value.to_array()
+    }
+}
+
+impl From<Color> for [f64; 4] {
+    #[inline]
+    fn from(value: Color) -> Self {
+        value.to_array().map(|value| value as f64)
+    }
+}
+
+//====================================================================
@@ -0,0 +1,159 @@
+//====================================================================
+
+use std::{fmt, path::PathBuf, str::FromStr};
+
+use shipyard::Unique;
+
+use crate::Size;
+
+//====================================================================
+
+// Used for whichever dimension isn't given on the command line
+const DEFAULT_WINDOW_SIZE: Size<u32> = Size {
+    width: 800,
+    height: 600,
+};
+
+//====================================================================
+
+/// Options for launching the app, inserted as a unique before any plugins are
+/// built. Usually parsed from the command line with [LaunchConfig::from_env]:
+///
+/// ```text
+/// --width <px> --height <px>   or   --size <width>x<height>
+/// --fullscreen
+/// --assets <path>
+/// --headless
+/// --log <off|error|warn|info|debug|trace>
+/// ```
+///
+/// Values can also be given as `--flag=value`. Arguments that aren't recognised
+/// are kept in [LaunchConfig::extra] for the app to handle.
+#[derive(Unique, Debug, Clone, Default, PartialEq)]
+pub struct LaunchConfig {
+    /// Initial inner size of the window in physical pixels.
+    pub window_size: Option<Size<u32>>,
+    /// Start in borderless fullscreen on the current monitor.
+    pub fullscreen: bool,
+    /// Directory assets are loaded from, in place of `res` in the working directory.
+    pub asset_path: Option<PathBuf>,
+    /// Run without creating a window or event loop.
+    pub headless: bool,
+    /// Maximum level of log records passed on to the logger.
+    pub log_level: Option<log::LevelFilter>,
+    pub extra: Vec<String>,
+}
+
+impl LaunchConfig {
+    /// Parse the arguments the process was started with.
+    #[inline]
+    pub fn from_env() -> Result<Self, LaunchConfigError> {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    /// Parse arguments, not including the program name.
+    pub fn parse<I, S>(args: I) -> Result<Self, LaunchConfigError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut config = Self::default();
+        let mut args = args.into_iter().map(Into::into);
+
+        let mut width = None;
+        let mut height = None;
+
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => {
+                    (flag.to_string(), Some(value.to_string()))
+                }
+                _ => (arg.clone(), None),
+            };
+
+            match flag.as_str() {
+                "--width" => width = Some(parse_value(&flag, value(&flag, inline, &mut args)?)?),
+                "--height" => height = Some(parse_value(&flag, value(&flag, inline, &mut args)?)?),
+                "--size" => {
+                    let size = value(&flag, inline, &mut args)?;
+
+                    let (w, h) = size
+                        .split_once('x')
+                        .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                        .ok_or_else(|| LaunchConfigError::InvalidValue {
+                            flag: flag.clone(),
+                            value: size.clone(),
+                        })?;
+
+                    width = Some(w);
+                    height = Some(h);
+                }
+                "--fullscreen" => config.fullscreen = parse_switch(&flag, inline)?,
+                "--assets" => config.asset_path = Some(value(&flag, inline, &mut args)?.into()),
+                "--headless" => config.headless = parse_switch(&flag, inline)?,
+                "--log" => {
+                    config.log_level = Some(parse_value(&flag, value(&flag, inline, &mut args)?)?)
+                }
+                _ => config.extra.push(arg),
+            }
+        }
+
+        if width.is_some() || height.is_some() {
+            config.window_size = Some(Size::new(
+                width.unwrap_or(DEFAULT_WINDOW_SIZE.width),
+                height.unwrap_or(DEFAULT_WINDOW_SIZE.height),
+            ));
+        }
+
+        Ok(config)
+    }
+}
+
+// Value given inline or as the next argument
+fn value(
+    flag: &str,
+    inline: Option<String>,
+    args: &mut impl Iterator<Item = String>,
+) -> Result<String, LaunchConfigError> {
+    inline
+        .or_else(|| args.next())
+        .ok_or_else(|| LaunchConfigError::MissingValue(flag.to_string()))
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: String) -> Result<T, LaunchConfigError> {
+    value.parse().map_err(|_| LaunchConfigError::InvalidValue {
+        flag: flag.to_string(),
+        value,
+    })
+}
+
+// Switches are on when given without a value
+fn parse_switch(flag: &str, inline: Option<String>) -> Result<bool, LaunchConfigError> {
+    match inline {
+        Some(value) => parse_value(flag, value),
+        None => Ok(true),
+    }
+}
+
+//--------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LaunchConfigError {
+    MissingValue(String),
+    InvalidValue { flag: String, value: String },
+}
+
+impl fmt::Display for LaunchConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LaunchConfigError::MissingValue(flag) => write!(f, "Missing value for '{}'", flag),
+            LaunchConfigError::InvalidValue { flag, value } => {
+                write!(f, "Invalid value '{}' for '{}'", value, flag)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LaunchConfigError {}
+
+//====================================================================
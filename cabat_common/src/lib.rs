@@ -6,11 +6,18 @@ use cabat_shipyard::Event;
 use shipyard::Unique;
 use window_handles::WindowHandle;
 
+mod color;
+mod launch;
+mod ui;
 mod window_handles;
 
+pub use color::{linear_to_srgb, srgb_to_linear, Color, ColorParseError};
+pub use launch::{LaunchConfig, LaunchConfigError};
+pub use ui::{Anchor, UiPosition, UiVal};
+
 //====================================================================
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Size<T> {
     pub width: T,
     pub height: T,
@@ -115,3 +122,35 @@ impl WindowResizeEvent {
 }
 
 //====================================================================
+
+#[derive(Unique)]
+pub struct WindowScale(f32);
+
+impl WindowScale {
+    #[inline]
+    pub fn new(scale_factor: f32) -> Self {
+        Self(scale_factor)
+    }
+
+    #[inline]
+    pub fn scale_factor(&self) -> f32 {
+        self.0
+    }
+}
+
+#[derive(Event)]
+pub struct ScaleFactorChangedEvent(f32);
+
+impl ScaleFactorChangedEvent {
+    #[inline]
+    pub fn new(scale_factor: f32) -> Self {
+        Self(scale_factor)
+    }
+
+    #[inline]
+    pub fn scale_factor(&self) -> f32 {
+        self.0
+    }
+}
+
+//====================================================================
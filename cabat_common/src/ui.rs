@@ -0,0 +1,129 @@
+//====================================================================
+
+use crate::Size;
+
+//====================================================================
+
+/// A screen space length, either in logical pixels or as a percentage of the
+/// containing extent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UiVal {
+    Px(f32),
+    Percent(f32),
+}
+
+impl Default for UiVal {
+    #[inline]
+    fn default() -> Self {
+        Self::Px(0.)
+    }
+}
+
+impl From<f32> for UiVal {
+    #[inline]
+    fn from(value: f32) -> Self {
+        Self::Px(value)
+    }
+}
+
+impl UiVal {
+    /// Resolve into physical pixels. Pixel values are multiplied by the scale factor.
+    #[inline]
+    pub fn resolve(&self, extent: f32, scale_factor: f32) -> f32 {
+        match self {
+            UiVal::Px(value) => value * scale_factor,
+            UiVal::Percent(percent) => extent * percent / 100.,
+        }
+    }
+}
+
+//====================================================================
+
+/// Point of the parent (usually the window) an element is positioned relative to.
+/// The same point of the element is aligned to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Anchor {
+    #[default]
+    TopLeft,
+    TopCenter,
+    TopRight,
+    CenterLeft,
+    Center,
+    CenterRight,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Normalized anchor point, with (0, 0) being the top left.
+    pub fn fraction(&self) -> (f32, f32) {
+        match self {
+            Anchor::TopLeft => (0., 0.),
+            Anchor::TopCenter => (0.5, 0.),
+            Anchor::TopRight => (1., 0.),
+            Anchor::CenterLeft => (0., 0.5),
+            Anchor::Center => (0.5, 0.5),
+            Anchor::CenterRight => (1., 0.5),
+            Anchor::BottomLeft => (0., 1.),
+            Anchor::BottomCenter => (0.5, 1.),
+            Anchor::BottomRight => (1., 1.),
+        }
+    }
+
+    // Offsets point inwards from the edge the element is anchored to
+    #[inline]
+    fn direction(fraction: f32) -> f32 {
+        match fraction >= 1. {
+            true => -1.,
+            false => 1.,
+        }
+    }
+}
+
+//--------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct UiPosition {
+    pub anchor: Anchor,
+    pub x: UiVal,
+    pub y: UiVal,
+}
+
+impl From<(f32, f32)> for UiPosition {
+    #[inline]
+    fn from(value: (f32, f32)) -> Self {
+        Self {
+            anchor: Anchor::TopLeft,
+            x: UiVal::Px(value.0),
+            y: UiVal::Px(value.1),
+        }
+    }
+}
+
+impl UiPosition {
+    #[inline]
+    pub fn new(anchor: Anchor, x: impl Into<UiVal>, y: impl Into<UiVal>) -> Self {
+        Self {
+            anchor,
+            x: x.into(),
+            y: y.into(),
+        }
+    }
+
+    /// Top left corner, in physical pixels, of an element with the given size
+    /// placed inside the parent.
+    pub fn resolve(&self, parent: Size<f32>, element: Size<f32>, scale_factor: f32) -> (f32, f32) {
+        let (fraction_x, fraction_y) = self.anchor.fraction();
+
+        let x = parent.width * fraction_x - element.width * fraction_x
+            + self.x.resolve(parent.width, scale_factor) * Anchor::direction(fraction_x);
+
+        let y = parent.height * fraction_y - element.height * fraction_y
+            + self.y.resolve(parent.height, scale_factor) * Anchor::direction(fraction_y);
+
+        (x, y)
+    }
+}
+
+//====================================================================
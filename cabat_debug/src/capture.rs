@@ -0,0 +1,188 @@
+//====================================================================
+
+use std::sync::Mutex;
+
+use cabat_runner::tools::{Input, KeyCode};
+use cabat_shipyard::{prelude::*, Event};
+use renderdoc::{RenderDoc, V141};
+use shipyard::{IntoWorkload, Unique, WorkloadModificator};
+
+//====================================================================
+
+/// Captures single frames with RenderDoc, either when
+/// [FrameCaptureSettings::capture_key] is pressed or a [CaptureNextFrame]
+/// event is sent. Requires the `renderdoc` feature.
+///
+/// RenderDoc has to be attached before the renderer creates its device, so
+/// the app should be launched from RenderDoc (or with `renderdoccmd capture`).
+/// Otherwise capture requests are ignored with a warning.
+pub struct FrameCapturePlugin;
+
+impl Plugin for FrameCapturePlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        builder.get_or_insert(FrameCaptureSettings::default);
+
+        builder
+            .insert(FrameCapture::load())
+            .describe_order(Stages::Render, &sys_start_capture, &["setup_encoder"], &[])
+            .describe_order(Stages::Render, &sys_end_capture, &[], &["submit_encoder"])
+            .add_workload_pre(Stages::Update, sys_capture_key)
+            .add_event::<CaptureNextFrame>(sys_request_capture.into_workload())
+            .add_workload_first(
+                Stages::Render,
+                sys_start_capture
+                    .into_workload()
+                    .before_all("setup_encoder"),
+            )
+            .add_workload_last(
+                Stages::Render,
+                sys_end_capture.into_workload().after_all("submit_encoder"),
+            );
+    }
+}
+
+//====================================================================
+
+/// Insert before adding the [FrameCapturePlugin] to configure it.
+#[derive(Unique, Debug, Clone)]
+pub struct FrameCaptureSettings {
+    /// Set to `None` to only capture through [CaptureNextFrame].
+    pub capture_key: Option<KeyCode>,
+}
+
+impl Default for FrameCaptureSettings {
+    fn default() -> Self {
+        Self {
+            // RenderDoc's own overlay already captures on F12
+            capture_key: Some(KeyCode::F9),
+        }
+    }
+}
+
+/// Send to capture the next rendered frame.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CaptureNextFrame;
+
+//--------------------------------------------------
+
+/// Connection to RenderDoc, if the app was launched with it attached.
+#[derive(Unique)]
+pub struct FrameCapture {
+    renderdoc: Option<Mutex<RenderDoc<V141>>>,
+    requested: bool,
+    capturing: bool,
+}
+
+impl FrameCapture {
+    fn load() -> Self {
+        let renderdoc = match RenderDoc::new() {
+            Ok(renderdoc) => {
+                log::info!("RenderDoc attached, frame captures available");
+                Some(Mutex::new(renderdoc))
+            }
+            Err(e) => {
+                log::debug!("RenderDoc not attached: {}", e);
+                None
+            }
+        };
+
+        Self {
+            renderdoc,
+            requested: false,
+            capturing: false,
+        }
+    }
+
+    #[inline]
+    pub fn is_available(&self) -> bool {
+        self.renderdoc.is_some()
+    }
+
+    /// Whether a capture has been requested and not yet finished.
+    #[inline]
+    pub fn is_pending(&self) -> bool {
+        self.requested || self.capturing
+    }
+
+    /// Capture the next rendered frame. Requests made while waiting on a
+    /// capture are merged into it.
+    pub fn capture_next_frame(&mut self) {
+        match self.renderdoc {
+            Some(_) => self.requested = true,
+            None => log::warn!("Unable to capture frame - RenderDoc isn't attached"),
+        }
+    }
+
+    /// Number of frames captured in this session, including those triggered
+    /// from RenderDoc itself.
+    pub fn capture_count(&self) -> u32 {
+        match &self.renderdoc {
+            Some(renderdoc) => renderdoc.lock().unwrap().get_num_captures(),
+            None => 0,
+        }
+    }
+}
+
+//====================================================================
+
+fn sys_capture_key(
+    keys: Res<Input<KeyCode>>,
+    settings: Res<FrameCaptureSettings>,
+    mut capture: ResMut<FrameCapture>,
+) {
+    if let Some(key) = settings.capture_key {
+        if keys.just_pressed(key) {
+            capture.capture_next_frame();
+        }
+    }
+}
+
+fn sys_request_capture(mut capture: ResMut<FrameCapture>) {
+    capture.capture_next_frame();
+}
+
+// Null device and window pointers capture whichever is active, as wgpu
+// doesn't expose its native handles.
+fn sys_start_capture(mut capture: ResMut<FrameCapture>) {
+    if !capture.requested {
+        return;
+    }
+
+    let capture = &mut *capture;
+    if let Some(renderdoc) = &mut capture.renderdoc {
+        log::info!("Capturing frame");
+
+        renderdoc
+            .get_mut()
+            .unwrap()
+            .start_frame_capture(std::ptr::null(), std::ptr::null());
+
+        capture.requested = false;
+        capture.capturing = true;
+    }
+}
+
+// Runs after the frame is presented so the capture includes it
+fn sys_end_capture(mut capture: ResMut<FrameCapture>) {
+    if !capture.capturing {
+        return;
+    }
+
+    let capture = &mut *capture;
+    capture.capturing = false;
+
+    let renderdoc = match &mut capture.renderdoc {
+        Some(renderdoc) => renderdoc.get_mut().unwrap(),
+        None => return,
+    };
+
+    renderdoc.end_frame_capture(std::ptr::null(), std::ptr::null());
+
+    let index = renderdoc.get_num_captures().saturating_sub(1);
+    match renderdoc.get_capture(index) {
+        Some((path, _)) => log::info!("Frame captured to '{}'", path.display()),
+        None => log::warn!("Frame capture finished without RenderDoc saving it"),
+    }
+}
+
+//====================================================================
@@ -0,0 +1,529 @@
+//====================================================================
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    str::FromStr,
+    sync::Arc,
+};
+
+use cabat_common::{Anchor, UiPosition, UiVal};
+use cabat_renderer::text::{Color, Metrics, Text2dBuffer, Text2dBufferDescriptor, TextFontSystem};
+use cabat_runner::{
+    clipboard::Clipboard,
+    tools::{Input, InputLayer, KeyCode, KeyboardText},
+};
+use cabat_shipyard::{prelude::*, GetWorld, UniqueTools};
+use shipyard::{AllStorages, AllStoragesViewMut, Component, IntoIter, Unique, ViewMut};
+
+//====================================================================
+
+/// Toggleable overlay for typing commands registered with
+/// [AddConsoleCommand::add_console_command] or [Console::register]. Opened
+/// with [ConsoleSettings::toggle_key].
+///
+/// `help` lists the registered commands and `clear` empties the console log.
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        builder.get_or_insert(ConsoleSettings::default);
+        // Commands may have been added by plugins built before this one
+        builder.get_or_insert(Console::default);
+
+        builder
+            // Text components are created during the renderer setup
+            .add_workload_post(Stages::Setup, sys_setup_console)
+            .add_workload_pre(Stages::Update, sys_console_input)
+            .add_workload(Stages::Update, sys_run_console_commands)
+            .add_workload_post(Stages::Update, sys_update_console);
+    }
+}
+
+//====================================================================
+
+/// Insert before adding the [ConsolePlugin] to configure it.
+#[derive(Unique, Debug, Clone)]
+pub struct ConsoleSettings {
+    pub visible: bool,
+    pub toggle_key: KeyCode,
+    /// Number of log lines shown above the input line.
+    pub visible_lines: usize,
+}
+
+impl Default for ConsoleSettings {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            toggle_key: KeyCode::Backquote,
+            visible_lines: 16,
+        }
+    }
+}
+
+//--------------------------------------------------
+
+/// Result of a console command. Ok text is printed as is, errors are prefixed
+/// in the console log. Empty text prints nothing.
+pub type CommandResult = Result<String, String>;
+
+type CommandFn = dyn Fn(&AllStorages, &ConsoleArgs) -> CommandResult + Send + Sync;
+
+struct ConsoleCommand {
+    help: String,
+    run: Arc<CommandFn>,
+}
+
+/// Arguments following a command's name. Words are split on whitespace and
+/// double quotes group words into a single argument.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConsoleArgs(Vec<String>);
+
+impl ConsoleArgs {
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.0.get(index).map(String::as_str)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(String::as_str)
+    }
+
+    /// Parse an argument, with an error message fit for printing when it is
+    /// missing or invalid.
+    pub fn parse<T: FromStr>(&self, index: usize) -> Result<T, String> {
+        let arg = self
+            .get(index)
+            .ok_or_else(|| format!("Missing argument {}", index + 1))?;
+
+        arg.parse()
+            .map_err(|_| format!("Invalid argument {}: '{}'", index + 1, arg))
+    }
+
+    /// Parse an argument, falling back to a default when it isn't given.
+    pub fn parse_or<T: FromStr>(&self, index: usize, default: T) -> Result<T, String> {
+        match self.get(index) {
+            Some(_) => self.parse(index),
+            None => Ok(default),
+        }
+    }
+
+    fn tokenize(line: &str) -> Vec<String> {
+        let mut tokens = Vec::new();
+        let mut current = String::new();
+        let mut in_token = false;
+        let mut quoted = false;
+
+        line.chars().for_each(|c| match c {
+            '"' => {
+                quoted = !quoted;
+                in_token = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_token = true;
+            }
+        });
+
+        if in_token {
+            tokens.push(current);
+        }
+
+        tokens
+    }
+}
+
+//--------------------------------------------------
+
+/// Command registry, log and input line of the developer console.
+#[derive(Unique)]
+pub struct Console {
+    commands: BTreeMap<String, ConsoleCommand>,
+
+    lines: VecDeque<String>,
+    capacity: usize,
+
+    input: String,
+    history: Vec<String>,
+    // Entry of the history being browsed, if any
+    history_index: Option<usize>,
+
+    // Submitted lines waiting for the commands to be run
+    pending: Vec<String>,
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Self::new(128)
+    }
+}
+
+impl Console {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            commands: BTreeMap::new(),
+
+            lines: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+
+            input: String::new(),
+            history: Vec::new(),
+            history_index: None,
+
+            pending: Vec::new(),
+        }
+    }
+
+    /// Add a command, replacing any with the same name. Commands run with access
+    /// to the whole world, after input is handled in [Stages::Update].
+    pub fn register<F>(&mut self, name: impl Into<String>, help: impl Into<String>, command: F)
+    where
+        F: Fn(&AllStorages, &ConsoleArgs) -> CommandResult + Send + Sync + 'static,
+    {
+        self.commands.insert(
+            name.into(),
+            ConsoleCommand {
+                help: help.into(),
+                run: Arc::new(command),
+            },
+        );
+    }
+
+    #[inline]
+    pub fn unregister(&mut self, name: &str) {
+        self.commands.remove(name);
+    }
+
+    #[inline]
+    pub fn has_command(&self, name: &str) -> bool {
+        self.commands.contains_key(name)
+    }
+
+    /// Queue a line to be run as if it had been typed in.
+    #[inline]
+    pub fn submit(&mut self, line: impl Into<String>) {
+        self.pending.push(line.into());
+    }
+
+    pub fn print(&mut self, text: impl AsRef<str>) {
+        text.as_ref().lines().for_each(|line| {
+            if self.lines.len() == self.capacity {
+                self.lines.pop_front();
+            }
+            self.lines.push_back(line.to_string());
+        });
+    }
+
+    /// Logged lines, oldest first.
+    #[inline]
+    pub fn lines(&self) -> impl DoubleEndedIterator<Item = &str> + ExactSizeIterator {
+        self.lines.iter().map(String::as_str)
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    #[inline]
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    //--------------------------------------------------
+
+    fn submit_input(&mut self) {
+        let line = std::mem::take(&mut self.input);
+        self.history_index = None;
+
+        if line.trim().is_empty() {
+            return;
+        }
+
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+
+        self.pending.push(line);
+    }
+
+    fn browse_history(&mut self, back: bool) {
+        let index = match (self.history_index, back) {
+            (None, true) => self.history.len().checked_sub(1),
+            (None, false) => None,
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) => Some(index + 1).filter(|index| *index < self.history.len()),
+        };
+
+        self.history_index = index;
+        self.input = index
+            .map(|index| self.history[index].clone())
+            .unwrap_or_default();
+    }
+
+    // Complete the command name when only one matches what has been typed
+    fn complete(&mut self) {
+        if self.input.contains(char::is_whitespace) {
+            return;
+        }
+
+        let mut matches = self
+            .commands
+            .keys()
+            .map(String::as_str)
+            .chain(["clear", "help"])
+            .filter(|name| name.starts_with(self.input.as_str()));
+
+        let completed = match (matches.next(), matches.next()) {
+            (Some(name), None) => format!("{} ", name),
+            _ => return,
+        };
+
+        self.input = completed;
+    }
+
+    fn help(&mut self) {
+        let help = self
+            .commands
+            .iter()
+            .map(|(name, command)| format!("  {} - {}", name, command.help))
+            .collect::<Vec<_>>();
+
+        self.print("Commands:");
+        self.print("  clear - Clear the console");
+        self.print("  help - List commands");
+        help.into_iter().for_each(|line| self.print(line));
+    }
+}
+
+//--------------------------------------------------
+
+pub trait AddConsoleCommand {
+    /// Register a [Console] command while building the app.
+    fn add_console_command<F>(&self, name: &str, help: &str, command: F) -> &Self
+    where
+        F: Fn(&AllStorages, &ConsoleArgs) -> CommandResult + Send + Sync + 'static;
+}
+
+impl AddConsoleCommand for WorkloadBuilder<'_> {
+    fn add_console_command<F>(&self, name: &str, help: &str, command: F) -> &Self
+    where
+        F: Fn(&AllStorages, &ConsoleArgs) -> CommandResult + Send + Sync + 'static,
+    {
+        self.log(format!("Adding console command '{}'", name));
+
+        match self.get_world().get_unique::<&mut Console>() {
+            Ok(mut console) => console.register(name, help, command),
+
+            Err(shipyard::error::GetStorage::MissingStorage { .. }) => {
+                let mut console = Console::default();
+                console.register(name, help, command);
+                self.get_world().add_unique(console);
+            }
+
+            Err(_) => unimplemented!(),
+        }
+
+        self
+    }
+}
+
+#[derive(Component, Default)]
+pub(crate) struct ConsoleText {
+    // Last text given to the buffer, so it's only reshaped when it changes
+    shown: String,
+}
+
+//====================================================================
+
+fn sys_setup_console(mut all_storages: AllStoragesViewMut) {
+    let buffer = {
+        let mut font_system = all_storages.borrow::<ResMut<TextFontSystem>>().unwrap();
+
+        Text2dBuffer::new(
+            font_system.inner_mut(),
+            &Text2dBufferDescriptor {
+                metrics: Metrics::new(14., 18.),
+                bounds_bottom: 600,
+                position: UiPosition::new(Anchor::BottomLeft, 8., 8.),
+                width: Some(UiVal::Px(800.)),
+                color: Color::rgb(230, 230, 230),
+                ..Default::default()
+            },
+        )
+    };
+
+    all_storages.add_entity((buffer, ConsoleText::default()));
+}
+
+fn sys_console_input(
+    mut keys: ResMut<Input<KeyCode>>,
+    keyboard: Res<KeyboardText>,
+    mut clipboard: NonSendResMut<Clipboard>,
+    mut settings: ResMut<ConsoleSettings>,
+    mut console: ResMut<Console>,
+) {
+    // The toggle key usually types a character too, so skip text on that frame
+    if keys.just_pressed(settings.toggle_key) {
+        settings.visible = !settings.visible;
+        keys.consume(settings.toggle_key, InputLayer::CONSOLE);
+        return;
+    }
+
+    if !settings.visible {
+        return;
+    }
+
+    // Nothing else reacts to keys while the console is open
+    keys.consume_all(InputLayer::CONSOLE);
+
+    if keys.just_pressed(KeyCode::Escape) {
+        settings.visible = false;
+        return;
+    }
+
+    let ctrl = keys.pressed(KeyCode::ControlLeft) || keys.pressed(KeyCode::ControlRight);
+
+    match ctrl {
+        false => console.input.push_str(keyboard.as_str()),
+
+        // Copy and paste the whole input line
+        true => {
+            if keys.just_pressed(KeyCode::KeyC) && !console.input.is_empty() {
+                clipboard.set_text(console.input.clone());
+            }
+
+            if keys.just_pressed(KeyCode::KeyV) {
+                if let Some(text) = clipboard.get_text() {
+                    let line = text.lines().next().unwrap_or_default();
+                    console
+                        .input
+                        .extend(line.chars().filter(|c| !c.is_control()));
+                }
+            }
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Backspace) {
+        console.input.pop();
+    }
+
+    if keys.just_pressed(KeyCode::Tab) {
+        console.complete();
+    }
+
+    if keys.just_pressed(KeyCode::ArrowUp) {
+        console.browse_history(true);
+    }
+
+    if keys.just_pressed(KeyCode::ArrowDown) {
+        console.browse_history(false);
+    }
+
+    if keys.just_pressed(KeyCode::Enter) || keys.just_pressed(KeyCode::NumpadEnter) {
+        console.submit_input();
+    }
+}
+
+// Commands need the whole world, so the console is only borrowed between them.
+// They can change anything, so nothing else runs alongside them.
+fn sys_run_console_commands(all_storages: AllStoragesViewMut) {
+    let pending = match all_storages.borrow::<ResMut<Console>>() {
+        Ok(mut console) => std::mem::take(&mut console.pending),
+        Err(_) => return,
+    };
+
+    pending.into_iter().for_each(|line| {
+        let mut tokens = ConsoleArgs::tokenize(&line).into_iter();
+        let name = match tokens.next() {
+            Some(name) => name,
+            None => return,
+        };
+        let args = ConsoleArgs(tokens.collect());
+
+        let command = {
+            let mut console = all_storages.borrow::<ResMut<Console>>().unwrap();
+            console.print(format!("> {}", line));
+
+            match name.as_str() {
+                "clear" => {
+                    console.clear();
+                    return;
+                }
+                "help" => {
+                    console.help();
+                    return;
+                }
+                _ => {}
+            }
+
+            match console.commands.get(&name) {
+                Some(command) => command.run.clone(),
+                None => {
+                    console.print(format!(
+                        "Unknown command '{}'. Type 'help' for a list",
+                        name
+                    ));
+                    return;
+                }
+            }
+        };
+
+        let result = command(&all_storages, &args);
+
+        let mut console = all_storages.borrow::<ResMut<Console>>().unwrap();
+        match result {
+            Ok(output) => console.print(output),
+            Err(error) => console.print(format!("Error: {}", error)),
+        }
+    });
+}
+
+fn sys_update_console(
+    settings: Res<ConsoleSettings>,
+    console: Res<Console>,
+    mut font_system: ResMut<TextFontSystem>,
+    mut vm_console: ViewMut<ConsoleText>,
+    mut vm_text: ViewMut<Text2dBuffer>,
+) {
+    let text = match settings.visible {
+        true => {
+            let skip = console.lines.len().saturating_sub(settings.visible_lines);
+            let input = format!("> {}_", console.input);
+
+            console
+                .lines()
+                .skip(skip)
+                .chain([input.as_str()])
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        false => String::new(),
+    };
+
+    (&mut vm_console, &mut vm_text)
+        .iter()
+        .filter(|(console_text, _)| console_text.shown != text)
+        .for_each(|(console_text, buffer)| {
+            buffer.set_text(font_system.inner_mut(), &text);
+            console_text.shown = text.clone();
+        });
+}
+
+//====================================================================
@@ -0,0 +1,90 @@
+//====================================================================
+
+use std::{backtrace::Backtrace, panic::PanicHookInfo, sync::Once};
+
+use cabat_shipyard::{prelude::*, FrameContext};
+
+//====================================================================
+
+static INSTALL_HOOK: Once = Once::new();
+
+//====================================================================
+
+/// Installs a panic hook that logs what the world was doing when a system
+/// panicked (the system, stage, frame and recently dispatched events). The
+/// previously installed hook still runs first, and the panic unwinds as usual.
+///
+/// The system is the one [FrameContext] recorded as running on the panicking
+/// thread. Panics outside of a recorded system fall back to the first `sys_*`
+/// function found in a backtrace, which needs debug symbols.
+pub struct DiagnosticsPlugin;
+
+impl Plugin for DiagnosticsPlugin {
+    fn build(self, _builder: &WorkloadBuilder) {
+        INSTALL_HOOK.call_once(|| {
+            let previous = std::panic::take_hook();
+
+            std::panic::set_hook(Box::new(move |info| {
+                previous(info);
+                report_panic(info);
+            }));
+        });
+    }
+}
+
+//====================================================================
+
+fn report_panic(info: &PanicHookInfo) {
+    let thread = std::thread::current();
+    let thread = thread.name().unwrap_or("unnamed");
+
+    let context = match FrameContext::current() {
+        Some(context) => context,
+        None => {
+            log::error!("Panic on thread '{}' inside the runner: {}", thread, info);
+            return;
+        }
+    };
+
+    let stage = match context.stage {
+        Some(stage) => format!("{:?}", stage),
+        None => "none".into(),
+    };
+
+    let system = match context.system {
+        Some(system) => system.to_string(),
+        None => panicking_system(&Backtrace::force_capture()).unwrap_or_else(|| "unknown".into()),
+    };
+
+    let events = match context.recent_events.is_empty() {
+        true => "none".into(),
+        false => context.recent_events.join(", "),
+    };
+
+    log::error!(
+        "System panicked on thread '{}'\n\tSystem: {}\n\tStage: {}\n\tFrame: {}\n\tRecent events: {}\n\t{}",
+        thread,
+        system,
+        stage,
+        context.frame,
+        events,
+        info
+    );
+}
+
+// Innermost function following the repo's `sys_` naming for systems
+fn panicking_system(backtrace: &Backtrace) -> Option<String> {
+    backtrace
+        .to_string()
+        .lines()
+        .map(str::trim)
+        .filter_map(|line| line.split_once(": ").map(|(_, symbol)| symbol))
+        .find(|symbol| {
+            symbol
+                .split("::")
+                .any(|segment| segment.starts_with("sys_"))
+        })
+        .map(|symbol| symbol.to_string())
+}
+
+//====================================================================
@@ -0,0 +1,56 @@
+//====================================================================
+
+use cabat_shipyard::{prelude::*, UniqueTools};
+
+#[cfg(feature = "renderdoc")]
+mod capture;
+mod console;
+mod diagnostics;
+mod logger;
+mod overlay;
+mod stats;
+
+#[cfg(feature = "renderdoc")]
+pub use capture::{CaptureNextFrame, FrameCapture, FrameCapturePlugin, FrameCaptureSettings};
+pub use console::{
+    AddConsoleCommand, CommandResult, Console, ConsoleArgs, ConsolePlugin, ConsoleSettings,
+};
+pub use diagnostics::DiagnosticsPlugin;
+pub use logger::{LogCapture, Logger};
+pub use overlay::DebugOverlaySettings;
+pub use stats::{FrameSpikeEvent, FrameStats};
+
+//====================================================================
+
+/// On screen overlay showing frame and stage timings, entity and draw call
+/// counts and recent log lines. Toggled with [DebugOverlaySettings::toggle_key].
+///
+/// Warnings and errors are only collected from the [log] crate when
+/// [LogCapture] is set as the global logger.
+pub struct DebugOverlayPlugin;
+
+impl Plugin for DebugOverlayPlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        builder.get_or_insert(DebugOverlaySettings::default);
+
+        builder
+            .insert_default::<FrameStats>()
+            .insert_default::<Logger>()
+            // Text components are created during the renderer setup
+            .add_workload_post(Stages::Setup, overlay::sys_setup_overlay)
+            .add_workload_post(
+                Stages::First,
+                (
+                    stats::sys_update_frame_stats,
+                    logger::sys_collect_captured_logs,
+                ),
+            )
+            .add_workload_pre(Stages::Update, overlay::sys_toggle_overlay)
+            .add_workload_post(
+                Stages::Update,
+                (overlay::sys_update_overlay, overlay::sys_update_stage_bar),
+            );
+    }
+}
+
+//====================================================================
@@ -0,0 +1,160 @@
+//====================================================================
+
+use std::{collections::VecDeque, sync::Mutex};
+
+use cabat_shipyard::prelude::*;
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use shipyard::Unique;
+
+//====================================================================
+
+// Records waiting to be moved into the Logger. Bounded in case the overlay isn't running.
+static CAPTURED: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+const CAPTURED_LIMIT: usize = 256;
+
+//====================================================================
+
+/// Recent messages shown in the debug overlay. Oldest lines are dropped once
+/// the capacity is reached.
+#[derive(Unique, Debug)]
+pub struct Logger {
+    lines: VecDeque<String>,
+    capacity: usize,
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Self::new(32)
+    }
+}
+
+impl Logger {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn add_log(&mut self, line: impl Into<String>) {
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line.into());
+    }
+
+    /// Logged lines, oldest first.
+    #[inline]
+    pub fn lines(&self) -> impl DoubleEndedIterator<Item = &str> + ExactSizeIterator {
+        self.lines.iter().map(String::as_str)
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+}
+
+//====================================================================
+
+/// Global [log] implementation that keeps warnings and errors from the whole app
+/// for the debug overlay, forwarding every record to an optional inner logger.
+///
+/// ```ignore
+/// let inner = env_logger::Builder::new()
+///     .filter_module("cabat", log::LevelFilter::Trace)
+///     .build();
+///
+/// LogCapture::new().with_inner(inner).init().unwrap();
+/// ```
+pub struct LogCapture {
+    inner: Option<Box<dyn Log>>,
+    level: Level,
+}
+
+impl Default for LogCapture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LogCapture {
+    pub fn new() -> Self {
+        Self {
+            inner: None,
+            level: Level::Warn,
+        }
+    }
+
+    #[inline]
+    pub fn with_inner(mut self, inner: impl Log + 'static) -> Self {
+        self.inner = Some(Box::new(inner));
+        self
+    }
+
+    /// Least severe level that is captured. Defaults to [Level::Warn].
+    #[inline]
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Set as the global logger. Fails if a logger has already been set.
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        // The inner logger does its own filtering
+        let max_level = match self.inner {
+            Some(_) => LevelFilter::Trace,
+            None => self.level.to_level_filter(),
+        };
+
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(max_level);
+
+        Ok(())
+    }
+}
+
+impl Log for LogCapture {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+            || self
+                .inner
+                .as_ref()
+                .map(|inner| inner.enabled(metadata))
+                .unwrap_or(false)
+    }
+
+    fn log(&self, record: &Record) {
+        if record.level() <= self.level {
+            let line = format!("[{} {}] {}", record.level(), record.target(), record.args());
+
+            if let Ok(mut captured) = CAPTURED.lock() {
+                if captured.len() == CAPTURED_LIMIT {
+                    captured.pop_front();
+                }
+                captured.push_back(line);
+            }
+        }
+
+        if let Some(inner) = &self.inner {
+            inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(inner) = &self.inner {
+            inner.flush();
+        }
+    }
+}
+
+pub(crate) fn sys_collect_captured_logs(mut logger: ResMut<Logger>) {
+    let lines = match CAPTURED.lock() {
+        Ok(mut captured) => captured.drain(..).collect::<Vec<_>>(),
+        Err(_) => return,
+    };
+
+    lines.into_iter().for_each(|line| logger.add_log(line));
+}
+
+//====================================================================
@@ -0,0 +1,476 @@
+//====================================================================
+
+use std::fmt::Write;
+
+use cabat_assets::{
+    asset_storage::AssetStorage,
+    stats::{format_bytes, AssetChangeKind},
+};
+use cabat_common::{Anchor, UiPosition, UiVal};
+use cabat_renderer::{
+    default_assets::DefaultRendererAssets,
+    gizmo::GizmoSettings,
+    nine_slice::{NineSlice, NineSliceMargins},
+    shared::SortKey,
+    text::{Color, Metrics, Text2dBuffer, Text2dBufferDescriptor, TextFontSystem},
+    RenderStats,
+};
+use cabat_runner::tools::{Input, KeyCode};
+use cabat_shipyard::{prelude::*, EntityLabel, EventTrace, FrameTimings, Name, StageTimings};
+use shipyard::{
+    AllStoragesViewMut, Component, EntitiesView, IntoIter, IntoWithId, Unique, View, ViewMut,
+};
+
+use crate::{logger::Logger, stats::FrameStats};
+
+//====================================================================
+
+const GRAPH_BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const GRAPH_SAMPLES: usize = 60;
+
+// Stage timing bar size in logical pixels
+const STAGE_BAR_WIDTH: f32 = 300.;
+const STAGE_BAR_HEIGHT: f32 = 12.;
+
+// Bar segment colors, in the order of FrameTimings::STAGES
+const STAGE_COLORS: [(u8, u8, u8); 5] = [
+    (160, 160, 160),
+    (170, 110, 230),
+    (70, 140, 240),
+    (240, 150, 50),
+    (90, 200, 110),
+];
+
+/// Insert before adding the [crate::DebugOverlayPlugin] to configure what it shows.
+#[derive(Unique, Debug, Clone)]
+pub struct DebugOverlaySettings {
+    pub visible: bool,
+    pub toggle_key: KeyCode,
+
+    pub show_fps: bool,
+    pub show_frame_graph: bool,
+    pub show_entities: bool,
+    /// Number of [Name]d entities listed under the entity count. Zero hides the list.
+    pub named_entities: usize,
+    pub show_draw_calls: bool,
+    /// Average time spent in each stage and substage, with a bar in the top right
+    /// corner splitting the frame between first (grey), fixed update (purple),
+    /// update (blue), render (orange) and last (green).
+    pub show_stage_timings: bool,
+    /// Names of events dispatched this frame.
+    pub show_events: bool,
+    /// Asset counts and estimated memory by type.
+    pub show_assets: bool,
+    /// Number of recent asset loads and unloads shown. Zero hides them.
+    pub asset_changes: usize,
+    /// Number of recent log lines shown. Zero hides the log.
+    pub log_lines: usize,
+
+    /// Toggle [GizmoSettings::camera_frustums] while the overlay is visible.
+    pub frustum_gizmos_key: KeyCode,
+    /// Toggle [GizmoSettings::bounds] while the overlay is visible.
+    pub bounds_gizmos_key: KeyCode,
+    /// Toggle [GizmoSettings::lights] while the overlay is visible.
+    pub light_gizmos_key: KeyCode,
+}
+
+impl Default for DebugOverlaySettings {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            toggle_key: KeyCode::F3,
+
+            show_fps: true,
+            show_frame_graph: true,
+            show_entities: true,
+            named_entities: 0,
+            show_draw_calls: true,
+            show_stage_timings: true,
+            show_events: true,
+            show_assets: true,
+            asset_changes: 4,
+            log_lines: 8,
+
+            frustum_gizmos_key: KeyCode::F5,
+            bounds_gizmos_key: KeyCode::F6,
+            light_gizmos_key: KeyCode::F7,
+        }
+    }
+}
+
+/// Text buffer the overlay is written to, tracking whether it still holds text
+/// so hiding the overlay only clears it once.
+#[derive(Component)]
+pub(crate) struct DebugOverlayText {
+    shown: bool,
+}
+
+/// Segment of the stage timing bar, holding its index into [FrameTimings::STAGES].
+#[derive(Component)]
+pub(crate) struct StageTimingBar(usize);
+
+//====================================================================
+
+pub(crate) fn sys_setup_overlay(mut all_storages: AllStoragesViewMut) {
+    let buffer = {
+        let mut font_system = all_storages.borrow::<ResMut<TextFontSystem>>().unwrap();
+
+        Text2dBuffer::new(
+            font_system.inner_mut(),
+            &Text2dBufferDescriptor {
+                metrics: Metrics::new(14., 18.),
+                bounds_bottom: 600,
+                position: UiPosition::new(Anchor::TopLeft, 8., 8.),
+                width: Some(UiVal::Px(600.)),
+                color: Color::rgb(230, 230, 230),
+                ..Default::default()
+            },
+        )
+    };
+
+    all_storages.add_entity((buffer, DebugOverlayText { shown: false }));
+
+    // Only drawn with the nine slice plugin
+    let texture = match all_storages.borrow::<Res<DefaultRendererAssets>>() {
+        Ok(assets) => assets.white_texture.clone(),
+        Err(_) => return,
+    };
+
+    STAGE_COLORS
+        .iter()
+        .enumerate()
+        .for_each(|(index, (r, g, b))| {
+            let panel = NineSlice::new(
+                texture.clone(),
+                NineSliceMargins::default(),
+                UiPosition::new(Anchor::TopRight, 8., 8.),
+                0.,
+                STAGE_BAR_HEIGHT,
+            );
+
+            all_storages.add_entity((
+                NineSlice {
+                    color: cabat_common::Color::srgb_u8(*r, *g, *b, 220),
+                    sort_key: SortKey::layer(i32::MAX),
+                    visible: false,
+                    ..panel
+                },
+                StageTimingBar(index),
+            ));
+        });
+}
+
+pub(crate) fn sys_toggle_overlay(
+    keys: Res<Input<KeyCode>>,
+    mut settings: ResMut<DebugOverlaySettings>,
+    gizmo_settings: Option<ResMut<GizmoSettings>>,
+) {
+    if keys.just_pressed(settings.toggle_key) {
+        settings.visible = !settings.visible;
+    }
+
+    // Only present with the gizmo plugin
+    let mut gizmo_settings = match (settings.visible, gizmo_settings) {
+        (true, Some(gizmo_settings)) => gizmo_settings,
+        _ => return,
+    };
+
+    if keys.just_pressed(settings.frustum_gizmos_key) {
+        gizmo_settings.camera_frustums = !gizmo_settings.camera_frustums;
+    }
+
+    if keys.just_pressed(settings.bounds_gizmos_key) {
+        gizmo_settings.bounds = !gizmo_settings.bounds;
+    }
+
+    if keys.just_pressed(settings.light_gizmos_key) {
+        gizmo_settings.lights = !gizmo_settings.lights;
+    }
+}
+
+pub(crate) fn sys_update_overlay(
+    settings: Res<DebugOverlaySettings>,
+    frame_stats: Res<FrameStats>,
+    render_stats: Res<RenderStats>,
+    logger: Res<Logger>,
+    event_trace: Res<EventTrace>,
+    stage_timings: Res<StageTimings>,
+    gizmo_settings: Option<Res<GizmoSettings>>,
+    asset_storage: Option<Res<AssetStorage>>,
+    mut font_system: ResMut<TextFontSystem>,
+    entities: EntitiesView,
+
+    v_name: View<Name>,
+    mut vm_overlay: ViewMut<DebugOverlayText>,
+    mut vm_text: ViewMut<Text2dBuffer>,
+) {
+    if !settings.visible {
+        (&mut vm_overlay, &mut vm_text)
+            .iter()
+            .filter(|(overlay, _)| overlay.shown)
+            .for_each(|(overlay, buffer)| {
+                buffer.set_text(font_system.inner_mut(), "");
+                overlay.shown = false;
+            });
+
+        return;
+    }
+
+    let mut text = String::new();
+
+    write_stats(
+        &mut text,
+        &settings,
+        &frame_stats,
+        &render_stats,
+        &event_trace,
+        &entities,
+        &v_name,
+    );
+
+    if settings.show_stage_timings {
+        write_stage_timings(&mut text, &stage_timings.average());
+    }
+
+    if let Some(gizmo_settings) = gizmo_settings {
+        write_gizmos(&mut text, &settings, &gizmo_settings);
+    }
+
+    if let Some(asset_storage) = asset_storage {
+        write_assets(&mut text, &settings, &asset_storage);
+    }
+
+    write_log(&mut text, &settings, &logger);
+
+    (&mut vm_overlay, &mut vm_text)
+        .iter()
+        .for_each(|(overlay, buffer)| {
+            buffer.set_text(font_system.inner_mut(), &text);
+            overlay.shown = true;
+        });
+}
+
+// Segments are anchored to the right, so are placed from the last stage back
+pub(crate) fn sys_update_stage_bar(
+    settings: Res<DebugOverlaySettings>,
+    stage_timings: Res<StageTimings>,
+    mut vm_nine_slice: ViewMut<NineSlice>,
+    v_bar: View<StageTimingBar>,
+) {
+    let visible = settings.visible && settings.show_stage_timings;
+    let timings = stage_timings.average();
+    let total = timings.total().as_secs_f32().max(f32::EPSILON);
+
+    let mut segments = (&v_bar, &mut vm_nine_slice).iter().collect::<Vec<_>>();
+    segments.sort_by_key(|(bar, _)| std::cmp::Reverse(bar.0));
+
+    let mut offset = 8.;
+
+    segments.into_iter().for_each(|(bar, panel)| {
+        let stage = FrameTimings::STAGES[bar.0];
+        let width = timings.stage(stage).as_secs_f32() / total * STAGE_BAR_WIDTH;
+
+        panel.position = UiPosition::new(Anchor::TopRight, offset, 8.);
+        panel.width = UiVal::Px(width);
+        panel.visible = visible && width > 0.;
+
+        offset += width;
+    });
+}
+
+fn write_stats(
+    text: &mut String,
+    settings: &DebugOverlaySettings,
+    frame_stats: &FrameStats,
+    render_stats: &RenderStats,
+    event_trace: &EventTrace,
+    entities: &EntitiesView,
+    v_name: &View<Name>,
+) {
+    if settings.show_fps {
+        writeln!(
+            text,
+            "FPS: {:.1} ({:.2} ms avg, {:.2} ms p99, {:.2} ms max)",
+            frame_stats.fps(),
+            frame_stats.average_frame_time() * 1000.,
+            frame_stats.p99() * 1000.,
+            frame_stats.max_frame_time() * 1000.,
+        )
+        .unwrap();
+    }
+
+    if settings.show_frame_graph {
+        let max = frame_stats.max_frame_time().max(f32::EPSILON);
+        let skip = frame_stats
+            .frame_times()
+            .len()
+            .saturating_sub(GRAPH_SAMPLES);
+
+        frame_stats.frame_times().skip(skip).for_each(|frame_time| {
+            let index = (frame_time / max * (GRAPH_BARS.len() - 1) as f32).round() as usize;
+            text.push(GRAPH_BARS[index.min(GRAPH_BARS.len() - 1)]);
+        });
+        text.push('\n');
+    }
+
+    if settings.show_entities {
+        writeln!(text, "Entities: {}", entities.iter().count()).unwrap();
+    }
+
+    if settings.named_entities > 0 {
+        v_name
+            .iter()
+            .with_id()
+            .take(settings.named_entities)
+            .for_each(|(id, _)| writeln!(text, "  {}", EntityLabel::new(v_name, id)).unwrap());
+    }
+
+    if settings.show_draw_calls {
+        let frame = render_stats.last_frame();
+
+        writeln!(
+            text,
+            "Draw calls: {} ({} instances, {} pipelines)",
+            frame.draw_calls, frame.instances, frame.pipeline_switches
+        )
+        .unwrap();
+
+        if frame.buffer_allocations > 0 || frame.texture_allocations > 0 {
+            writeln!(
+                text,
+                "Allocated: {} buffers ({}), {} textures ({})",
+                frame.buffer_allocations,
+                format_bytes(frame.buffer_bytes as usize),
+                frame.texture_allocations,
+                format_bytes(frame.texture_bytes as usize)
+            )
+            .unwrap();
+        }
+    }
+
+    if settings.show_events {
+        let events = event_trace
+            .current_frame()
+            .map(|name| name.rsplit("::").next().unwrap_or(name))
+            .collect::<Vec<_>>();
+
+        if !events.is_empty() {
+            writeln!(text, "Events: {}", events.join(", ")).unwrap();
+        }
+    }
+}
+
+fn write_stage_timings(text: &mut String, timings: &FrameTimings) {
+    let ms = |duration: std::time::Duration| duration.as_secs_f32() * 1000.;
+
+    writeln!(text, "Stages: {:.2} ms", ms(timings.total())).unwrap();
+
+    FrameTimings::STAGES.iter().for_each(|stage| {
+        let substages = FrameTimings::SUBSTAGES
+            .iter()
+            .filter_map(|substage| {
+                let duration = timings.substage(*stage, *substage);
+                (!duration.is_zero()).then(|| format!("{:?} {:.2}", substage, ms(duration)))
+            })
+            .collect::<Vec<_>>();
+
+        writeln!(
+            text,
+            "  {:?}: {:.2} ms [{}]",
+            stage,
+            ms(timings.stage(*stage)),
+            substages.join(", ")
+        )
+        .unwrap();
+    });
+}
+
+fn write_gizmos(
+    text: &mut String,
+    settings: &DebugOverlaySettings,
+    gizmo_settings: &GizmoSettings,
+) {
+    if !gizmo_settings.enabled {
+        return;
+    }
+
+    let state = |on: bool| if on { "on" } else { "off" };
+
+    writeln!(
+        text,
+        "Gizmos: frustums {} [{:?}], bounds {} [{:?}], lights {} [{:?}]",
+        state(gizmo_settings.camera_frustums),
+        settings.frustum_gizmos_key,
+        state(gizmo_settings.bounds),
+        settings.bounds_gizmos_key,
+        state(gizmo_settings.lights),
+        settings.light_gizmos_key,
+    )
+    .unwrap();
+}
+
+fn write_assets(text: &mut String, settings: &DebugOverlaySettings, asset_storage: &AssetStorage) {
+    if !settings.show_assets && settings.asset_changes == 0 {
+        return;
+    }
+
+    let stats = asset_storage.stats();
+
+    if settings.show_assets {
+        writeln!(
+            text,
+            "Assets: {} ({} cpu, {} gpu)",
+            stats.asset_count(),
+            format_bytes(stats.cpu_memory()),
+            format_bytes(stats.gpu_memory()),
+        )
+        .unwrap();
+
+        stats.types.iter().for_each(|type_stats| {
+            writeln!(
+                text,
+                "  {}: {} ({} handles, {} cpu, {} gpu)",
+                type_stats.short_name(),
+                type_stats.count,
+                type_stats.handles,
+                format_bytes(type_stats.cpu_memory),
+                format_bytes(type_stats.gpu_memory),
+            )
+            .unwrap()
+        });
+    }
+
+    let skip = stats.recent.len().saturating_sub(settings.asset_changes);
+
+    stats.recent.iter().skip(skip).for_each(|change| {
+        let sign = match change.kind {
+            AssetChangeKind::Loaded => '+',
+            AssetChangeKind::Unloaded => '-',
+        };
+        let name = change
+            .type_name
+            .rsplit("::")
+            .next()
+            .unwrap_or(change.type_name);
+
+        match &change.path {
+            Some(path) => writeln!(text, "  {} {} {:?}", sign, name, path),
+            None => writeln!(text, "  {} {} ({})", sign, name, change.id),
+        }
+        .unwrap()
+    });
+}
+
+fn write_log(text: &mut String, settings: &DebugOverlaySettings, logger: &Logger) {
+    if settings.log_lines > 0 && logger.lines().len() > 0 {
+        text.push('\n');
+
+        let skip = logger.lines().len().saturating_sub(settings.log_lines);
+        logger.lines().skip(skip).for_each(|line| {
+            text.push_str(line);
+            text.push('\n');
+        });
+    }
+}
+
+//====================================================================
@@ -0,0 +1,176 @@
+//====================================================================
+
+use std::collections::VecDeque;
+
+use cabat_runner::tools::Time;
+use cabat_shipyard::prelude::*;
+use shipyard::Unique;
+
+//====================================================================
+
+// How often the displayed fps is recalculated, in seconds
+const FPS_INTERVAL: f32 = 0.5;
+
+// Frames needed before the average is trusted for spike detection
+const SPIKE_WARMUP_FRAMES: usize = 10;
+
+/// Rolling frame time history with summary statistics. Times are in seconds.
+#[derive(Unique, Debug)]
+pub struct FrameStats {
+    frame_times: VecDeque<f32>,
+    history: usize,
+
+    fps: f32,
+    interval_frames: u32,
+    interval_elapsed: f32,
+
+    /// A frame is a spike when it takes this many times longer than the average.
+    pub spike_factor: f32,
+    /// Frames faster than this are never reported as spikes.
+    pub spike_min_time: f32,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self::new(120)
+    }
+}
+
+impl FrameStats {
+    pub fn new(history: usize) -> Self {
+        Self {
+            frame_times: VecDeque::with_capacity(history),
+            history: history.max(1),
+
+            fps: 0.,
+            interval_frames: 0,
+            interval_elapsed: 0.,
+
+            spike_factor: 2.,
+            spike_min_time: 1. / 60.,
+        }
+    }
+
+    #[inline]
+    pub fn fps(&self) -> f32 {
+        self.fps
+    }
+
+    /// Duration of the last frame.
+    #[inline]
+    pub fn frame_time(&self) -> f32 {
+        self.frame_times.back().copied().unwrap_or_default()
+    }
+
+    pub fn average_frame_time(&self) -> f32 {
+        match self.frame_times.len() {
+            0 => 0.,
+            len => self.frame_times.iter().sum::<f32>() / len as f32,
+        }
+    }
+
+    pub fn min_frame_time(&self) -> f32 {
+        match self.frame_times.is_empty() {
+            true => 0.,
+            false => self.frame_times.iter().copied().fold(f32::MAX, f32::min),
+        }
+    }
+
+    pub fn max_frame_time(&self) -> f32 {
+        self.frame_times.iter().copied().fold(0., f32::max)
+    }
+
+    /// Frame time that the given fraction (0 to 1) of frames in the history are
+    /// at or below.
+    pub fn percentile(&self, fraction: f32) -> f32 {
+        if self.frame_times.is_empty() {
+            return 0.;
+        }
+
+        let mut sorted = self.frame_times.iter().copied().collect::<Vec<_>>();
+        sorted.sort_by(f32::total_cmp);
+
+        let index = ((sorted.len() - 1) as f32 * fraction.clamp(0., 1.)).round() as usize;
+        sorted[index]
+    }
+
+    #[inline]
+    pub fn p95(&self) -> f32 {
+        self.percentile(0.95)
+    }
+
+    #[inline]
+    pub fn p99(&self) -> f32 {
+        self.percentile(0.99)
+    }
+
+    /// Frame times, oldest first.
+    #[inline]
+    pub fn frame_times(&self) -> impl Iterator<Item = f32> + '_ {
+        self.frame_times.iter().copied()
+    }
+
+    #[inline]
+    pub fn history(&self) -> usize {
+        self.history
+    }
+
+    pub fn set_history(&mut self, history: usize) {
+        self.history = history.max(1);
+
+        while self.frame_times.len() > self.history {
+            self.frame_times.pop_front();
+        }
+    }
+
+    fn is_spike(&self, delta: f32) -> bool {
+        self.frame_times.len() >= SPIKE_WARMUP_FRAMES
+            && delta >= self.spike_min_time
+            && delta > self.average_frame_time() * self.spike_factor
+    }
+
+    fn push(&mut self, delta: f32) {
+        if self.frame_times.len() == self.history {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(delta);
+
+        self.interval_frames += 1;
+        self.interval_elapsed += delta;
+
+        if self.interval_elapsed >= FPS_INTERVAL {
+            self.fps = self.interval_frames as f32 / self.interval_elapsed;
+            self.interval_frames = 0;
+            self.interval_elapsed = 0.;
+        }
+    }
+}
+
+//--------------------------------------------------
+
+/// A frame took much longer than the recent average.
+#[derive(Event, Debug, Clone)]
+pub struct FrameSpikeEvent {
+    pub frame_time: f32,
+    pub average_frame_time: f32,
+}
+
+pub(crate) fn sys_update_frame_stats(
+    time: Res<Time>,
+    mut stats: ResMut<FrameStats>,
+    mut event_handler: ResMut<EventHandler>,
+) {
+    let delta = time.delta_seconds();
+
+    // Compare against the average before this frame is included
+    if stats.is_spike(delta) {
+        event_handler.add_event(FrameSpikeEvent {
+            frame_time: delta,
+            average_frame_time: stats.average_frame_time(),
+        });
+    }
+
+    stats.push(delta);
+}
+
+//====================================================================
@@ -0,0 +1,118 @@
+//====================================================================
+
+use std::net::{Ipv4Addr, SocketAddr};
+
+use cabat_shipyard::{prelude::*, UniqueTools};
+use shipyard::{IntoWorkload, Unique};
+
+mod message;
+mod replication;
+mod socket;
+
+pub use message::{MessageReceivedEvent, NetMessage, NetSender, RegisterNetMessage};
+pub use replication::{
+    Interpolate, Interpolated, RegisterReplicated, Replicated, ReplicatedEntities,
+    ReplicationMessage, ReplicationPlugin, ReplicationSettings, ServerEntity,
+};
+pub use socket::{NetSocket, PeerId};
+
+//====================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetRole {
+    /// Accepts any number of clients.
+    Server { bind: SocketAddr },
+    /// Connects to a single server, seen as [PeerId::SERVER].
+    Client { server: SocketAddr },
+}
+
+/// Insert before adding the [NetPlugin] to configure the connection. Without
+/// it the plugin runs a server on `127.0.0.1:7777`, only reachable locally.
+#[derive(Unique, Debug, Clone)]
+pub struct NetSettings {
+    pub role: NetRole,
+    /// Seconds without hearing from a peer before it is disconnected.
+    pub timeout: f32,
+    /// Seconds between keep alive packets when nothing else is sent.
+    pub heartbeat: f32,
+}
+
+impl NetSettings {
+    pub fn server(bind: SocketAddr) -> Self {
+        Self {
+            role: NetRole::Server { bind },
+            timeout: 10.,
+            heartbeat: 1.,
+        }
+    }
+
+    pub fn client(server: SocketAddr) -> Self {
+        Self {
+            role: NetRole::Client { server },
+            timeout: 10.,
+            heartbeat: 1.,
+        }
+    }
+}
+
+//--------------------------------------------------
+
+#[derive(Event, Debug, Clone)]
+pub struct PeerConnectedEvent(pub Vec<PeerId>);
+
+#[derive(Event, Debug, Clone)]
+pub struct PeerDisconnectedEvent(pub Vec<PeerId>);
+
+//====================================================================
+
+/// Lightweight UDP transport. Message types registered with
+/// [RegisterNetMessage::register_net_message] are received as
+/// [MessageReceivedEvent]s and sent through the [NetSender].
+///
+/// Messages are unreliable and unordered, like the underlying datagrams.
+///
+/// If the socket can't be opened the error is logged and nothing is sent or
+/// received. Check for the [NetSocket] unique to tell if networking is up.
+pub struct NetPlugin;
+
+impl Plugin for NetPlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        let socket = {
+            let settings = builder.get_or_insert(|| {
+                let settings = NetSettings::server((Ipv4Addr::LOCALHOST, 7777).into());
+                log::info!(
+                    "No net settings inserted, defaulting to {:?}",
+                    settings.role
+                );
+                settings
+            });
+
+            match NetSocket::new(&settings) {
+                Ok(socket) => Some(socket),
+                Err(e) => {
+                    log::error!(
+                        "Unable to open network socket for {:?}, networking is disabled: {}",
+                        settings.role,
+                        e
+                    );
+                    None
+                }
+            }
+        };
+
+        if let Some(socket) = socket {
+            builder.insert(socket);
+        }
+
+        builder
+            .insert_default::<NetSender>()
+            .insert_default::<message::MessageRegistry>()
+            .add_workload_first(Stages::First, message::sys_receive_messages)
+            .add_workload_last(
+                Stages::Last,
+                (message::sys_send_messages, socket::sys_update_peers).into_sequential_workload(),
+            );
+    }
+}
+
+//====================================================================
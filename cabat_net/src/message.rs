@@ -0,0 +1,231 @@
+//====================================================================
+
+use std::{collections::HashMap, hash::BuildHasherDefault};
+
+use cabat_shipyard::{prelude::*, GetWorld, UniqueTools};
+use rustc_hash::FxHasher;
+use serde::{de::DeserializeOwned, Serialize};
+use shipyard::{Unique, UniqueViewMut};
+
+use crate::socket::{NetSocket, PeerId, HEARTBEAT_ID, MAX_PACKET_SIZE};
+
+//====================================================================
+
+pub trait NetMessage: Serialize + DeserializeOwned + Send + Sync + 'static {}
+impl<T> NetMessage for T where T: Serialize + DeserializeOwned + Send + Sync + 'static {}
+
+/// Messages of type `M` received since the last frame, with the peer that sent them.
+#[derive(Event, Debug, Clone)]
+pub struct MessageReceivedEvent<M: NetMessage>(pub Vec<(PeerId, M)>);
+
+impl<M: NetMessage> MessageReceivedEvent<M> {
+    #[inline]
+    pub fn messages(&self) -> &[(PeerId, M)] {
+        &self.0
+    }
+}
+
+//--------------------------------------------------
+
+type Dispatch = fn(&mut EventHandler, Vec<(PeerId, &[u8])>);
+
+struct MessageRegistration {
+    name: &'static str,
+    dispatch: Dispatch,
+}
+
+#[derive(Unique, Default)]
+pub(crate) struct MessageRegistry {
+    messages: HashMap<u32, MessageRegistration, BuildHasherDefault<FxHasher>>,
+}
+
+// FNV-1a
+pub(crate) fn name_hash(name: &str) -> u32 {
+    name.bytes().fold(0x811c9dc5_u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    })
+}
+
+// Ids are derived from the type name so clients and servers built from the
+// same code agree without sharing a registration order.
+fn message_id<M: NetMessage>() -> u32 {
+    match name_hash(std::any::type_name::<M>()) {
+        HEARTBEAT_ID => 1,
+        id => id,
+    }
+}
+
+fn dispatch<M: NetMessage>(event_handler: &mut EventHandler, packets: Vec<(PeerId, &[u8])>) {
+    let messages = packets
+        .into_iter()
+        .filter_map(|(peer, payload)| match bincode::deserialize::<M>(payload) {
+            Ok(message) => Some((peer, message)),
+            Err(e) => {
+                log::warn!(
+                    "Unable to decode '{}' from {:?}: {}",
+                    std::any::type_name::<M>(),
+                    peer,
+                    e
+                );
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if !messages.is_empty() {
+        event_handler.add_event(MessageReceivedEvent(messages));
+    }
+}
+
+pub trait RegisterNetMessage {
+    /// Allow `M` to be sent and received. Received messages arrive as a
+    /// [MessageReceivedEvent] - subscribe with `add_event::<MessageReceivedEvent<M>>`.
+    fn register_net_message<M: NetMessage>(&self) -> &Self;
+}
+
+impl RegisterNetMessage for WorkloadBuilder<'_> {
+    fn register_net_message<M: NetMessage>(&self) -> &Self {
+        let name = std::any::type_name::<M>();
+        self.log(format!("Registering net message '{}'", name));
+
+        self.get_or_insert(MessageRegistry::default);
+
+        {
+            let mut registry = self
+                .get_world()
+                .borrow::<UniqueViewMut<MessageRegistry>>()
+                .unwrap();
+
+            let id = message_id::<M>();
+
+            if let Some(existing) = registry.messages.get(&id) {
+                if existing.name != name {
+                    panic!(
+                        "Net messages '{}' and '{}' have the same id",
+                        existing.name, name
+                    );
+                }
+            }
+
+            registry.messages.insert(
+                id,
+                MessageRegistration {
+                    name,
+                    dispatch: dispatch::<M>,
+                },
+            );
+        }
+
+        self
+    }
+}
+
+//====================================================================
+
+enum Target {
+    Peer(PeerId),
+    All,
+}
+
+/// Outgoing messages. Sent at the end of the frame.
+#[derive(Unique, Default)]
+pub struct NetSender {
+    queue: Vec<(Target, Vec<u8>)>,
+}
+
+impl NetSender {
+    pub fn send<M: NetMessage>(&mut self, peer: PeerId, message: &M) {
+        if let Some(packet) = Self::encode(message) {
+            self.queue.push((Target::Peer(peer), packet));
+        }
+    }
+
+    /// Send to every connected peer. For clients this is only the server.
+    pub fn broadcast<M: NetMessage>(&mut self, message: &M) {
+        if let Some(packet) = Self::encode(message) {
+            self.queue.push((Target::All, packet));
+        }
+    }
+
+    fn encode<M: NetMessage>(message: &M) -> Option<Vec<u8>> {
+        let mut packet = message_id::<M>().to_le_bytes().to_vec();
+
+        if let Err(e) = bincode::serialize_into(&mut packet, message) {
+            log::error!("Unable to encode '{}': {}", std::any::type_name::<M>(), e);
+            return None;
+        }
+
+        if packet.len() > MAX_PACKET_SIZE {
+            log::error!(
+                "Message '{}' is {} bytes - larger than the {} byte packet limit",
+                std::any::type_name::<M>(),
+                packet.len(),
+                MAX_PACKET_SIZE
+            );
+            return None;
+        }
+
+        Some(packet)
+    }
+}
+
+//====================================================================
+
+pub(crate) fn sys_receive_messages(
+    socket: Option<ResMut<NetSocket>>,
+    registry: Res<MessageRegistry>,
+    mut event_handler: ResMut<EventHandler>,
+) {
+    // Not present if the socket couldn't be opened
+    let mut socket = match socket {
+        Some(socket) => socket,
+        None => return,
+    };
+
+    let packets = socket.receive();
+
+    let mut by_message =
+        HashMap::<u32, Vec<(PeerId, &[u8])>, BuildHasherDefault<FxHasher>>::default();
+
+    packets.iter().for_each(|(peer, packet)| {
+        if packet.len() < 4 {
+            return;
+        }
+
+        let (id, payload) = packet.split_at(4);
+        let id = u32::from_le_bytes(id.try_into().unwrap());
+
+        if id != HEARTBEAT_ID {
+            by_message.entry(id).or_default().push((*peer, payload));
+        }
+    });
+
+    by_message
+        .into_iter()
+        .for_each(|(id, packets)| match registry.messages.get(&id) {
+            Some(registration) => (registration.dispatch)(&mut event_handler, packets),
+            None => log::warn!("Received unregistered net message id {}", id),
+        });
+}
+
+pub(crate) fn sys_send_messages(socket: Option<ResMut<NetSocket>>, mut sender: ResMut<NetSender>) {
+    // Messages are dropped rather than queued up without a socket
+    let mut socket = match socket {
+        Some(socket) => socket,
+        None => {
+            sender.queue.clear();
+            return;
+        }
+    };
+
+    let peers = socket.peers().collect::<Vec<_>>();
+
+    std::mem::take(&mut sender.queue)
+        .into_iter()
+        .for_each(|(target, packet)| match target {
+            Target::Peer(peer) => socket.send(peer, &packet),
+            Target::All => peers.iter().for_each(|peer| socket.send(*peer, &packet)),
+        });
+}
+
+//====================================================================
@@ -0,0 +1,663 @@
+//====================================================================
+
+use std::{
+    collections::{HashMap, HashSet},
+    hash::BuildHasherDefault,
+};
+
+use cabat_runner::tools::Time;
+use cabat_shipyard::{prelude::*, GetWorld, UniqueTools};
+use cabat_spatial::Transform;
+use rustc_hash::FxHasher;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use shipyard::{
+    AllStorages, AllStoragesView, AllStoragesViewMut, Component, EntitiesView, EntitiesViewMut,
+    EntityId, Get, IntoIter, IntoWithId, IntoWorkload, Unique, UniqueViewMut, View, ViewMut,
+};
+
+use crate::{
+    message::{name_hash, MessageReceivedEvent, NetSender, RegisterNetMessage},
+    socket::{NetSocket, PeerId},
+    PeerConnectedEvent,
+};
+
+//====================================================================
+
+type FastHashMap<K, V> = HashMap<K, V, BuildHasherDefault<FxHasher>>;
+type FastHashSet<K> = HashSet<K, BuildHasherDefault<FxHasher>>;
+
+// Leaves room for the message header and encoding overhead
+const CHUNK_SIZE: usize = 1000;
+// Rough per entry overhead of an encoded update - entity id plus length prefix
+const ENTRY_OVERHEAD: usize = 16;
+
+//====================================================================
+
+/// Entities with this component have their replicated components sent to
+/// clients by the server.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Replicated;
+
+/// Added to entities spawned on clients for replicated server entities.
+/// Holds the entity's id on the server.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ServerEntity(pub u64);
+
+/// Insert before adding the [ReplicationPlugin]. Clients and servers
+/// should use the same settings.
+#[derive(Unique, Debug, Clone)]
+pub struct ReplicationSettings {
+    /// Snapshots sent per second.
+    pub rate: f32,
+    /// Seconds between resending unchanged state, covering for lost packets.
+    pub full_sync: f32,
+}
+
+impl Default for ReplicationSettings {
+    fn default() -> Self {
+        Self {
+            rate: 20.,
+            full_sync: 2.,
+        }
+    }
+}
+
+impl ReplicationSettings {
+    #[inline]
+    pub fn interval(&self) -> f32 {
+        1. / self.rate.max(0.001)
+    }
+}
+
+//--------------------------------------------------
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ReplicationMessage {
+    Update {
+        tick: u32,
+        component: u32,
+        entries: Vec<(u64, Vec<u8>)>,
+    },
+    Despawn {
+        tick: u32,
+        entities: Vec<u64>,
+    },
+}
+
+//====================================================================
+
+/// Components that blend from their previous value to the latest snapshot
+/// on clients, instead of snapping to it.
+pub trait Interpolate: Clone {
+    fn interpolate(&self, target: &Self, s: f32) -> Self;
+}
+
+impl Interpolate for Transform {
+    fn interpolate(&self, target: &Self, s: f32) -> Self {
+        let mut transform = self.clone();
+        transform.lerp(target, s);
+        transform
+    }
+}
+
+/// Interpolation state of a replicated `T` on a client.
+pub struct Interpolated<T> {
+    from: T,
+    to: T,
+    elapsed: f32,
+    duration: f32,
+}
+
+impl<T: Send + Sync + 'static> Component for Interpolated<T> {
+    type Tracking = shipyard::track::Untracked;
+}
+
+impl<T: Interpolate> Interpolated<T> {
+    fn new(from: T, to: T, duration: f32) -> Self {
+        Self {
+            from,
+            to,
+            elapsed: 0.,
+            duration,
+        }
+    }
+
+    #[inline]
+    pub fn target(&self) -> &T {
+        &self.to
+    }
+}
+
+//====================================================================
+
+type Capture = fn(&AllStorages) -> Vec<(u64, Vec<u8>)>;
+type Apply = fn(&AllStorages, Vec<(EntityId, Vec<u8>)>, f32);
+
+struct ReplicatedComponent {
+    name: &'static str,
+    capture: Capture,
+    apply: Apply,
+}
+
+#[derive(Unique, Default)]
+pub(crate) struct ReplicationRegistry {
+    components: FastHashMap<u32, ReplicatedComponent>,
+}
+
+fn capture<T: Component + Serialize>(all_storages: &AllStorages) -> Vec<(u64, Vec<u8>)> {
+    all_storages.run(|v_replicated: View<Replicated>, v_component: View<T>| {
+        (&v_replicated, &v_component)
+            .iter()
+            .with_id()
+            .filter_map(|(id, (_, component))| match bincode::serialize(component) {
+                Ok(bytes) => Some((id.inner(), bytes)),
+                Err(e) => {
+                    log::error!(
+                        "Unable to encode replicated '{}': {}",
+                        std::any::type_name::<T>(),
+                        e
+                    );
+                    None
+                }
+            })
+            .collect()
+    })
+}
+
+fn decode<T: DeserializeOwned>(
+    entries: Vec<(EntityId, Vec<u8>)>,
+) -> impl Iterator<Item = (EntityId, T)> {
+    entries
+        .into_iter()
+        .filter_map(|(id, bytes)| match bincode::deserialize::<T>(&bytes) {
+            Ok(component) => Some((id, component)),
+            Err(e) => {
+                log::warn!(
+                    "Unable to decode replicated '{}': {}",
+                    std::any::type_name::<T>(),
+                    e
+                );
+                None
+            }
+        })
+}
+
+fn apply<T: Component + DeserializeOwned + Send + Sync>(
+    all_storages: &AllStorages,
+    entries: Vec<(EntityId, Vec<u8>)>,
+    _interval: f32,
+) {
+    all_storages.run(|entities: EntitiesView, mut vm_component: ViewMut<T>| {
+        decode::<T>(entries).for_each(|(id, component)| {
+            entities.add_component(id, &mut vm_component, component);
+        });
+    });
+}
+
+fn apply_interpolated<T: Component + Interpolate + DeserializeOwned + Send + Sync>(
+    all_storages: &AllStorages,
+    entries: Vec<(EntityId, Vec<u8>)>,
+    interval: f32,
+) {
+    all_storages.run(
+        |entities: EntitiesView,
+         mut vm_component: ViewMut<T>,
+         mut vm_interpolated: ViewMut<Interpolated<T>>| {
+            decode::<T>(entries).for_each(|(id, target)| {
+                let current = (&vm_component).get(id).ok().cloned();
+
+                match current {
+                    Some(current) => entities.add_component(
+                        id,
+                        &mut vm_interpolated,
+                        Interpolated::new(current, target, interval),
+                    ),
+
+                    // Nothing to blend from yet
+                    None => entities.add_component(
+                        id,
+                        (&mut vm_component, &mut vm_interpolated),
+                        (
+                            target.clone(),
+                            Interpolated::new(target.clone(), target, interval),
+                        ),
+                    ),
+                }
+            });
+        },
+    );
+}
+
+fn sys_interpolate<T: Component + Interpolate + Send + Sync>(
+    time: Res<Time>,
+    mut vm_interpolated: ViewMut<Interpolated<T>>,
+    mut vm_component: ViewMut<T>,
+) {
+    let delta = time.delta_seconds();
+
+    (&mut vm_interpolated, &mut vm_component)
+        .iter()
+        .for_each(|(interpolated, component)| {
+            if interpolated.elapsed >= interpolated.duration {
+                return;
+            }
+
+            interpolated.elapsed = (interpolated.elapsed + delta).min(interpolated.duration);
+
+            let s = match interpolated.duration > 0. {
+                true => interpolated.elapsed / interpolated.duration,
+                false => 1.,
+            };
+
+            *component = interpolated.from.interpolate(&interpolated.to, s);
+        });
+}
+
+//--------------------------------------------------
+
+pub trait RegisterReplicated {
+    /// Send `T` on [Replicated] entities from the server to clients. Clients
+    /// replace their copy whenever a new snapshot arrives.
+    fn replicate_component<T>(&self) -> &Self
+    where
+        T: Component + Serialize + DeserializeOwned + Send + Sync;
+
+    /// Like [RegisterReplicated::replicate_component], but clients blend
+    /// towards each new snapshot over the snapshot interval.
+    fn replicate_component_interpolated<T>(&self) -> &Self
+    where
+        T: Component + Interpolate + Serialize + DeserializeOwned + Send + Sync;
+}
+
+impl WorkloadBuilder<'_> {
+    fn register_replicated(&self, name: &'static str, capture: Capture, apply: Apply) {
+        self.log(format!("Replicating component '{}'", name));
+
+        self.get_or_insert(ReplicationRegistry::default);
+
+        let mut registry = self
+            .get_world()
+            .borrow::<UniqueViewMut<ReplicationRegistry>>()
+            .unwrap();
+
+        let id = name_hash(name);
+
+        if let Some(existing) = registry.components.get(&id) {
+            if existing.name != name {
+                panic!(
+                    "Replicated components '{}' and '{}' have the same id",
+                    existing.name, name
+                );
+            }
+        }
+
+        registry.components.insert(
+            id,
+            ReplicatedComponent {
+                name,
+                capture,
+                apply,
+            },
+        );
+    }
+}
+
+impl RegisterReplicated for WorkloadBuilder<'_> {
+    fn replicate_component<T>(&self) -> &Self
+    where
+        T: Component + Serialize + DeserializeOwned + Send + Sync,
+    {
+        self.register_replicated(std::any::type_name::<T>(), capture::<T>, apply::<T>);
+        self
+    }
+
+    fn replicate_component_interpolated<T>(&self) -> &Self
+    where
+        T: Component + Interpolate + Serialize + DeserializeOwned + Send + Sync,
+    {
+        self.register_replicated(
+            std::any::type_name::<T>(),
+            capture::<T>,
+            apply_interpolated::<T>,
+        );
+
+        self.add_workload_pre(Stages::Update, sys_interpolate::<T>)
+    }
+}
+
+//====================================================================
+
+#[derive(Unique, Default)]
+struct ReplicationServer {
+    tick: u32,
+    timer: f32,
+    since_full_sync: f32,
+
+    entities: FastHashSet<u64>,
+    last_sent: FastHashMap<(u32, u64), Vec<u8>>,
+    // Despawns are resent for a while in case the first packet is lost
+    despawned: Vec<(u64, u32)>,
+}
+
+/// Maps server entities to the local entities replicating them on a client.
+#[derive(Unique, Default)]
+pub struct ReplicatedEntities {
+    entities: FastHashMap<u64, EntityId>,
+    // Tick each despawn was first heard of, kept while the server may resend it
+    despawned: FastHashMap<u64, u32>,
+    ticks: FastHashMap<(u32, u64), u32>,
+    latest_tick: u32,
+}
+
+impl ReplicatedEntities {
+    #[inline]
+    pub fn get(&self, server: ServerEntity) -> Option<EntityId> {
+        self.entities.get(&server.0).copied()
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (ServerEntity, EntityId)> + '_ {
+        self.entities
+            .iter()
+            .map(|(server, local)| (ServerEntity(*server), *local))
+    }
+}
+
+//====================================================================
+
+/// Replicates components registered with [RegisterReplicated] from the
+/// server to its clients. Add after the [crate::NetPlugin].
+///
+/// The server sends the components that changed since the last snapshot at a
+/// fixed rate, along with entities that are no longer [Replicated]. Clients
+/// spawn an entity with a [ServerEntity] the first time they hear of one.
+pub struct ReplicationPlugin;
+
+impl Plugin for ReplicationPlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        builder.get_or_insert(ReplicationSettings::default);
+        builder.get_or_insert(ReplicationRegistry::default);
+
+        builder
+            .register_net_message::<ReplicationMessage>()
+            .insert_default::<ReplicationServer>()
+            .insert_default::<ReplicatedEntities>()
+            .add_workload(Stages::Last, sys_replicate_entities)
+            .add_event::<MessageReceivedEvent<ReplicationMessage>>(
+                sys_apply_replication.into_workload(),
+            );
+    }
+}
+
+//--------------------------------------------------
+
+fn chunk_entries(entries: Vec<(u64, Vec<u8>)>, name: &str) -> Vec<Vec<(u64, Vec<u8>)>> {
+    let mut chunks = vec![Vec::new()];
+    let mut size = 0;
+
+    entries.into_iter().for_each(|(id, bytes)| {
+        let entry_size = bytes.len() + ENTRY_OVERHEAD;
+
+        if entry_size > CHUNK_SIZE {
+            log::error!(
+                "Replicated '{}' is {} bytes - too large to send",
+                name,
+                bytes.len()
+            );
+            return;
+        }
+
+        if size + entry_size > CHUNK_SIZE {
+            chunks.push(Vec::new());
+            size = 0;
+        }
+
+        size += entry_size;
+        chunks.last_mut().unwrap().push((id, bytes));
+    });
+
+    chunks.retain(|chunk| !chunk.is_empty());
+    chunks
+}
+
+fn sys_replicate_entities(all_storages: AllStoragesView) {
+    match all_storages.borrow::<Res<NetSocket>>() {
+        Ok(socket) if socket.is_server() => {}
+        _ => return,
+    }
+
+    let settings = all_storages.borrow::<Res<ReplicationSettings>>().unwrap();
+    let mut server = all_storages.borrow::<ResMut<ReplicationServer>>().unwrap();
+
+    // New peers need the full state
+    let peer_connected = all_storages
+        .borrow::<Res<EventHandler>>()
+        .unwrap()
+        .get_event::<PeerConnectedEvent>()
+        .is_some();
+
+    if peer_connected {
+        server.last_sent.clear();
+    }
+
+    let interval = settings.interval();
+    server.timer += all_storages.borrow::<Res<Time>>().unwrap().delta_seconds();
+
+    if server.timer < interval {
+        return;
+    }
+
+    // Don't try to catch up on missed snapshots after a long frame
+    server.timer = (server.timer - interval).min(interval);
+    server.tick += 1;
+
+    server.since_full_sync += interval;
+    if server.since_full_sync >= settings.full_sync {
+        server.since_full_sync = 0.;
+        server.last_sent.clear();
+    }
+
+    let tick = server.tick;
+    let mut sender = all_storages.borrow::<ResMut<NetSender>>().unwrap();
+
+    //--------------------------------------------------
+
+    let current = all_storages.run(|v_replicated: View<Replicated>| {
+        v_replicated
+            .iter()
+            .with_id()
+            .map(|(id, _)| id.inner())
+            .collect::<FastHashSet<_>>()
+    });
+
+    let removed = server
+        .entities
+        .difference(&current)
+        .copied()
+        .collect::<Vec<_>>();
+
+    if !removed.is_empty() {
+        let resend = settings.rate.ceil() as u32;
+
+        server
+            .last_sent
+            .retain(|(_, entity), _| !removed.contains(entity));
+        server
+            .despawned
+            .extend(removed.into_iter().map(|entity| (entity, resend)));
+    }
+
+    server.entities = current;
+
+    if !server.despawned.is_empty() {
+        server
+            .despawned
+            .chunks(CHUNK_SIZE / ENTRY_OVERHEAD)
+            .for_each(|chunk| {
+                sender.broadcast(&ReplicationMessage::Despawn {
+                    tick,
+                    entities: chunk.iter().map(|(entity, _)| *entity).collect(),
+                })
+            });
+
+        server.despawned.retain_mut(|(_, remaining)| {
+            *remaining -= 1;
+            *remaining > 0
+        });
+    }
+
+    //--------------------------------------------------
+
+    let registry = all_storages.borrow::<Res<ReplicationRegistry>>().unwrap();
+
+    registry
+        .components
+        .iter()
+        .for_each(|(component, registration)| {
+            let changed = (registration.capture)(&all_storages)
+                .into_iter()
+                .filter(|(entity, bytes)| {
+                    server.last_sent.get(&(*component, *entity)) != Some(bytes)
+                })
+                .collect::<Vec<_>>();
+
+            changed.iter().for_each(|(entity, bytes)| {
+                server
+                    .last_sent
+                    .insert((*component, *entity), bytes.clone());
+            });
+
+            chunk_entries(changed, registration.name)
+                .into_iter()
+                .for_each(|entries| {
+                    sender.broadcast(&ReplicationMessage::Update {
+                        tick,
+                        component: *component,
+                        entries,
+                    })
+                });
+        });
+}
+
+//--------------------------------------------------
+
+fn sys_apply_replication(mut all_storages: AllStoragesViewMut) {
+    match all_storages.borrow::<Res<NetSocket>>() {
+        Ok(socket) if !socket.is_server() => {}
+        _ => return,
+    }
+
+    let messages = match all_storages
+        .borrow::<Res<EventHandler>>()
+        .unwrap()
+        .get_event::<MessageReceivedEvent<ReplicationMessage>>()
+    {
+        Some(event) => event.0.clone(),
+        None => return,
+    };
+
+    let (interval, resend) = {
+        let settings = all_storages.borrow::<Res<ReplicationSettings>>().unwrap();
+        (settings.interval(), settings.rate.ceil() as u32)
+    };
+
+    let mut to_delete = Vec::new();
+
+    {
+        let mut replicated = all_storages.borrow::<ResMut<ReplicatedEntities>>().unwrap();
+
+        let mut updates = Vec::new();
+
+        messages
+            .into_iter()
+            .filter(|(peer, _)| *peer == PeerId::SERVER)
+            .for_each(|(_, message)| match message {
+                ReplicationMessage::Despawn { tick, entities } => {
+                    replicated.latest_tick = replicated.latest_tick.max(tick);
+
+                    entities.into_iter().for_each(|entity| {
+                        replicated.despawned.entry(entity).or_insert(tick);
+
+                        if let Some(local) = replicated.entities.remove(&entity) {
+                            to_delete.push((entity, local));
+                        }
+                    });
+                }
+
+                ReplicationMessage::Update {
+                    tick,
+                    component,
+                    entries,
+                } => {
+                    replicated.latest_tick = replicated.latest_tick.max(tick);
+
+                    let entries = entries
+                        .into_iter()
+                        .filter_map(|(entity, bytes)| {
+                            if replicated.despawned.contains_key(&entity) {
+                                return None;
+                            }
+
+                            // Skip snapshots that arrived out of order
+                            let last_tick =
+                                replicated.ticks.entry((component, entity)).or_default();
+                            if tick < *last_tick {
+                                return None;
+                            }
+                            *last_tick = tick;
+
+                            let local = match replicated.entities.get(&entity) {
+                                Some(local) => *local,
+                                None => {
+                                    let local = all_storages.run(
+                                        |mut entities: EntitiesViewMut,
+                                         mut vm_server: ViewMut<ServerEntity>| {
+                                            entities
+                                                .add_entity(&mut vm_server, ServerEntity(entity))
+                                        },
+                                    );
+                                    replicated.entities.insert(entity, local);
+                                    local
+                                }
+                            };
+
+                            Some((local, bytes))
+                        })
+                        .collect::<Vec<_>>();
+
+                    updates.push((component, entries));
+                }
+            });
+
+        if !to_delete.is_empty() {
+            replicated
+                .ticks
+                .retain(|(_, entity), _| !to_delete.iter().any(|(removed, _)| removed == entity));
+        }
+
+        // The server stops mentioning an entity once it has resent its despawn
+        // for a full window, so older despawns can be forgotten
+        let latest_tick = replicated.latest_tick;
+        replicated
+            .despawned
+            .retain(|_, seen| latest_tick.saturating_sub(*seen) <= resend);
+
+        let registry = all_storages.borrow::<Res<ReplicationRegistry>>().unwrap();
+
+        updates.into_iter().for_each(|(component, entries)| {
+            match registry.components.get(&component) {
+                Some(registration) => (registration.apply)(&all_storages, entries, interval),
+                None => log::warn!(
+                    "Received unregistered replicated component id {}",
+                    component
+                ),
+            }
+        });
+    }
+
+    to_delete.into_iter().for_each(|(_, local)| {
+        all_storages.delete_entity(local);
+    });
+}
+
+//====================================================================
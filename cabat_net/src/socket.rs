@@ -0,0 +1,262 @@
+//====================================================================
+
+use std::{
+    collections::HashMap,
+    hash::BuildHasherDefault,
+    io::ErrorKind,
+    net::{SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use cabat_shipyard::prelude::*;
+use rustc_hash::FxHasher;
+use serde::{Deserialize, Serialize};
+use shipyard::Unique;
+
+use crate::{NetRole, NetSettings, PeerConnectedEvent, PeerDisconnectedEvent};
+
+//====================================================================
+
+// Largest payload that fits in a single datagram without fragmenting on most networks
+pub(crate) const MAX_PACKET_SIZE: usize = 1200;
+
+// Message id reserved for keep alive packets
+pub(crate) const HEARTBEAT_ID: u32 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PeerId(pub u32);
+
+impl PeerId {
+    /// The server, as seen by a client.
+    pub const SERVER: PeerId = PeerId(0);
+}
+
+struct Peer {
+    addr: SocketAddr,
+    last_received: Instant,
+    last_sent: Instant,
+}
+
+//====================================================================
+
+/// Socket and known peers. Clients have the server as their only peer.
+#[derive(Unique)]
+pub struct NetSocket {
+    socket: UdpSocket,
+    role: NetRole,
+    timeout: Duration,
+    heartbeat: Duration,
+
+    peers: HashMap<PeerId, Peer, BuildHasherDefault<FxHasher>>,
+    next_peer: u32,
+    server_replied: bool,
+
+    connected: Vec<PeerId>,
+}
+
+impl NetSocket {
+    pub(crate) fn new(settings: &NetSettings) -> std::io::Result<Self> {
+        let bind: SocketAddr = match settings.role {
+            NetRole::Server { bind } => bind,
+            NetRole::Client { .. } => ([0, 0, 0, 0], 0).into(),
+        };
+
+        let socket = UdpSocket::bind(bind)?;
+        socket.set_nonblocking(true)?;
+
+        log::info!(
+            "Opened {:?} socket on {}",
+            settings.role,
+            socket.local_addr()?
+        );
+
+        let mut net_socket = Self {
+            socket,
+            role: settings.role,
+            timeout: Duration::from_secs_f32(settings.timeout),
+            heartbeat: Duration::from_secs_f32(settings.heartbeat),
+
+            peers: HashMap::default(),
+            next_peer: 1,
+            server_replied: false,
+
+            connected: Vec::new(),
+        };
+
+        // Clients know their only peer up front. It's reported as connected
+        // once the server first replies.
+        if let NetRole::Client { server } = settings.role {
+            net_socket.peers.insert(
+                PeerId::SERVER,
+                Peer {
+                    addr: server,
+                    last_received: Instant::now(),
+                    last_sent: Instant::now() - net_socket.heartbeat,
+                },
+            );
+        }
+
+        Ok(net_socket)
+    }
+
+    #[inline]
+    pub fn role(&self) -> NetRole {
+        self.role
+    }
+
+    #[inline]
+    pub fn is_server(&self) -> bool {
+        matches!(self.role, NetRole::Server { .. })
+    }
+
+    pub fn peers(&self) -> impl Iterator<Item = PeerId> + '_ {
+        self.peers.keys().copied()
+    }
+
+    pub fn peer_addr(&self, peer: PeerId) -> Option<SocketAddr> {
+        self.peers.get(&peer).map(|peer| peer.addr)
+    }
+
+    fn peer_by_addr(&mut self, addr: SocketAddr) -> Option<PeerId> {
+        if let Some((id, _)) = self.peers.iter().find(|(_, peer)| peer.addr == addr) {
+            return Some(*id);
+        }
+
+        // Only servers accept packets from unknown addresses
+        if !self.is_server() {
+            return None;
+        }
+
+        let id = PeerId(self.next_peer);
+        self.next_peer += 1;
+
+        self.peers.insert(
+            id,
+            Peer {
+                addr,
+                last_received: Instant::now(),
+                last_sent: Instant::now(),
+            },
+        );
+        self.connected.push(id);
+
+        log::info!("Peer {:?} connected from {}", id, addr);
+
+        Some(id)
+    }
+
+    /// Read every waiting datagram, returning the sending peer and packet.
+    pub(crate) fn receive(&mut self) -> Vec<(PeerId, Vec<u8>)> {
+        let mut packets = Vec::new();
+        let mut buffer = [0; MAX_PACKET_SIZE];
+
+        loop {
+            let (len, addr) = match self.socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                // Windows reports an unreachable peer on the next read
+                Err(e) if e.kind() == ErrorKind::ConnectionReset => continue,
+                Err(e) => {
+                    log::error!("Network receive error: {}", e);
+                    break;
+                }
+            };
+
+            let id = match self.peer_by_addr(addr) {
+                Some(id) => id,
+                None => continue,
+            };
+
+            // Clients are connected once the server first replies
+            if !self.is_server() && !self.server_replied {
+                self.server_replied = true;
+                self.connected.push(id);
+
+                log::info!("Connected to server at {}", addr);
+            }
+
+            if let Some(peer) = self.peers.get_mut(&id) {
+                peer.last_received = Instant::now();
+            }
+            packets.push((id, buffer[..len].to_vec()));
+        }
+
+        packets
+    }
+
+    pub(crate) fn send(&mut self, peer: PeerId, packet: &[u8]) {
+        let peer = match self.peers.get_mut(&peer) {
+            Some(peer) => peer,
+            None => return,
+        };
+
+        if let Err(e) = self.socket.send_to(packet, peer.addr) {
+            log::warn!("Failed to send packet to {}: {}", peer.addr, e);
+        }
+        peer.last_sent = Instant::now();
+    }
+}
+
+//====================================================================
+
+// Drop peers that have gone quiet and keep the rest alive
+pub(crate) fn sys_update_peers(
+    socket: Option<ResMut<NetSocket>>,
+    mut event_handler: ResMut<EventHandler>,
+) {
+    let mut socket = match socket {
+        Some(socket) => socket,
+        None => return,
+    };
+
+    let timeout = socket.timeout;
+    let heartbeat = socket.heartbeat;
+
+    let disconnected = socket
+        .peers
+        .iter()
+        .filter(|(_, peer)| peer.last_received.elapsed() > timeout)
+        .map(|(id, _)| *id)
+        .collect::<Vec<_>>();
+
+    match socket.is_server() {
+        true => {
+            disconnected.iter().for_each(|id| {
+                log::info!("Peer {:?} timed out", id);
+                socket.peers.remove(id);
+            });
+
+            if !disconnected.is_empty() {
+                event_handler.add_event(PeerDisconnectedEvent(disconnected));
+            }
+        }
+
+        // Clients keep the server as a peer and keep trying to reach it
+        false => {
+            if !disconnected.is_empty() && socket.server_replied {
+                log::info!("Lost connection to server");
+
+                socket.server_replied = false;
+                event_handler.add_event(PeerDisconnectedEvent(disconnected));
+            }
+        }
+    }
+
+    let quiet = socket
+        .peers
+        .iter()
+        .filter(|(_, peer)| peer.last_sent.elapsed() >= heartbeat)
+        .map(|(id, _)| *id)
+        .collect::<Vec<_>>();
+
+    quiet
+        .into_iter()
+        .for_each(|id| socket.send(id, &HEARTBEAT_ID.to_le_bytes()));
+
+    let connected = std::mem::take(&mut socket.connected);
+    if !connected.is_empty() {
+        event_handler.add_event(PeerConnectedEvent(connected));
+    }
+}
+
+//====================================================================
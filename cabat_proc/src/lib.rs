@@ -1,13 +1,82 @@
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, DeriveInput};
+use syn::{parse_macro_input, Data, DeriveInput, Fields};
 
 // Event derive
 #[proc_macro_derive(Event)]
 pub fn derive(input: TokenStream) -> TokenStream {
-    let DeriveInput { ident, .. } = parse_macro_input!(input);
+    let DeriveInput {
+        ident, generics, ..
+    } = parse_macro_input!(input);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let output = quote! {
+        impl #impl_generics Event for #ident #ty_generics #where_clause {}
+    };
+
+    output.into()
+}
+
+// Asset derive
+#[proc_macro_derive(Asset)]
+pub fn derive_asset(input: TokenStream) -> TokenStream {
+    let DeriveInput {
+        ident, generics, ..
+    } = parse_macro_input!(input);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let output = quote! {
+        impl #impl_generics Asset for #ident #ty_generics #where_clause {}
+    };
+
+    output.into()
+}
+
+// Newtype inner()/inner_mut() accessors
+#[proc_macro_derive(WrappedUnique)]
+pub fn derive_wrapped_unique(input: TokenStream) -> TokenStream {
+    let DeriveInput {
+        ident,
+        generics,
+        data,
+        ..
+    } = parse_macro_input!(input);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let inner_type = match data {
+        Data::Struct(data) => match data.fields {
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                fields.unnamed.into_iter().next().map(|field| field.ty)
+            }
+            _ => None,
+        },
+        _ => None,
+    };
+
+    let inner_type = match inner_type {
+        Some(inner_type) => inner_type,
+        None => {
+            return syn::Error::new_spanned(
+                ident,
+                "WrappedUnique can only be derived for tuple structs with a single field",
+            )
+            .to_compile_error()
+            .into()
+        }
+    };
+
     let output = quote! {
-        impl Event for #ident {}
+        impl #impl_generics #ident #ty_generics #where_clause {
+            #[inline]
+            pub fn inner(&self) -> &#inner_type {
+                &self.0
+            }
+
+            #[inline]
+            pub fn inner_mut(&mut self) -> &mut #inner_type {
+                &mut self.0
+            }
+        }
     };
 
     output.into()
@@ -0,0 +1,114 @@
+//====================================================================
+// Uniforms
+
+struct Camera {
+    projection: mat4x4<f32>,
+    position: vec3<f32>,
+    exposure: f32,
+    tonemapping: u32,
+}
+
+struct DecalView {
+    inverse_view_projection: mat4x4<f32>,
+    // x, y, width, height in pixels
+    viewport: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+@group(1) @binding(0) var texture: texture_2d<f32>;
+@group(1) @binding(1) var texture_sampler: sampler;
+
+@group(2) @binding(0) var depth_texture: texture_depth_2d;
+
+@group(3) @binding(0) var<uniform> view: DecalView;
+
+
+//====================================================================
+
+struct VertexIn {
+    // Vertex
+    @location(0) vertex_position: vec3<f32>,
+
+    // Instance
+    @location(4) transform_1: vec4<f32>,
+    @location(5) transform_2: vec4<f32>,
+    @location(6) transform_3: vec4<f32>,
+    @location(7) transform_4: vec4<f32>,
+    @location(8) inverse_1: vec4<f32>,
+    @location(9) inverse_2: vec4<f32>,
+    @location(10) inverse_3: vec4<f32>,
+    @location(11) inverse_4: vec4<f32>,
+    @location(12) color: vec4<f32>,
+    @location(13) fade: f32,
+}
+
+struct VertexOut {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) @interpolate(flat) inverse_1: vec4<f32>,
+    @location(1) @interpolate(flat) inverse_2: vec4<f32>,
+    @location(2) @interpolate(flat) inverse_3: vec4<f32>,
+    @location(3) @interpolate(flat) inverse_4: vec4<f32>,
+    @location(4) @interpolate(flat) color: vec4<f32>,
+    @location(5) @interpolate(flat) fade: f32,
+}
+
+//====================================================================
+
+@vertex
+fn vs_main(in: VertexIn) -> VertexOut {
+    var out: VertexOut;
+
+    let transform = mat4x4<f32>(
+        in.transform_1,
+        in.transform_2,
+        in.transform_3,
+        in.transform_4,
+    );
+
+    out.clip_position = camera.projection * transform * vec4<f32>(in.vertex_position, 1.);
+
+    out.inverse_1 = in.inverse_1;
+    out.inverse_2 = in.inverse_2;
+    out.inverse_3 = in.inverse_3;
+    out.inverse_4 = in.inverse_4;
+    out.color = in.color;
+    out.fade = in.fade;
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    let inverse = mat4x4<f32>(
+        in.inverse_1,
+        in.inverse_2,
+        in.inverse_3,
+        in.inverse_4,
+    );
+
+    // Rebuild the world position of whatever is already drawn under this pixel
+    let depth = textureLoad(depth_texture, vec2<i32>(in.clip_position.xy), 0);
+    let ndc = vec2<f32>(
+        (in.clip_position.x - view.viewport.x) / view.viewport.z * 2. - 1.,
+        1. - (in.clip_position.y - view.viewport.y) / view.viewport.w * 2.,
+    );
+
+    let world = view.inverse_view_projection * vec4<f32>(ndc, depth, 1.);
+    let local = (inverse * vec4<f32>(world.xyz / world.w, 1.)).xyz;
+
+    // Projected along -Y, so x and z become the uvs
+    let uv = vec2<f32>(local.x + 0.5, 0.5 - local.z);
+    var color = textureSampleLevel(texture, texture_sampler, uv, 0.) * in.color;
+
+    if any(abs(local) > vec3<f32>(0.5)) {
+        discard;
+    }
+
+    let edge = 1. - abs(local.y) * 2.;
+    color.a *= clamp(edge / max(in.fade, 0.0001), 0., 1.);
+
+    return vec4<f32>(tonemap(color.rgb), color.a);
+}
+
+//====================================================================
@@ -0,0 +1,28 @@
+//====================================================================
+
+// Fade towards the fog color based on distance from the camera. Appended to
+// shaders declaring the `camera` and `environment` uniforms, see
+// cabat_renderer::environment::FOG_SHADER
+fn apply_fog(color: vec3<f32>, world_position: vec3<f32>) -> vec3<f32> {
+    let distance = length(world_position - camera.position);
+
+    var visibility = 1.;
+    switch environment.fog_mode {
+        case 1u: {
+            visibility = exp(-environment.fog_params.x * distance);
+        }
+        case 2u: {
+            visibility = clamp(
+                (environment.fog_params.z - distance)
+                    / (environment.fog_params.z - environment.fog_params.y),
+                0.,
+                1.,
+            );
+        }
+        default: {}
+    }
+
+    return mix(environment.fog_color, color, visibility);
+}
+
+//====================================================================
@@ -0,0 +1,114 @@
+//====================================================================
+// Bindings
+
+struct PointLight {
+    position: vec3<f32>,
+    range: f32,
+    color: vec3<f32>,
+}
+
+struct LightView {
+    inverse_view_projection: mat4x4<f32>,
+    // x, y, width, height in pixels
+    viewport: vec4<f32>,
+    tiles_x: u32,
+    tiles_y: u32,
+    tile_offset: u32,
+    light_count: u32,
+}
+
+@group(0) @binding(0) var<storage, read> lights: array<PointLight>;
+@group(0) @binding(1) var<storage, read> views: array<LightView>;
+// Per tile, a light count followed by the indices of the lights touching it
+@group(0) @binding(2) var<storage, read_write> tiles: array<u32>;
+
+
+//====================================================================
+
+const TILE_SIZE: f32 = 16.;
+const MAX_LIGHTS_PER_TILE: u32 = 63u;
+
+// One invocation per tile, testing every light against the tile's frustum
+@compute @workgroup_size(8, 8)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let view = views[id.z];
+
+    if id.x >= view.tiles_x || id.y >= view.tiles_y {
+        return;
+    }
+
+    // Tile bounds in normalized device coordinates
+    let min_pixel = vec2<f32>(id.xy) * TILE_SIZE;
+    let max_pixel = min(min_pixel + TILE_SIZE, view.viewport.zw);
+
+    let left = min_pixel.x / view.viewport.z * 2. - 1.;
+    let right = max_pixel.x / view.viewport.z * 2. - 1.;
+    let top = 1. - min_pixel.y / view.viewport.w * 2.;
+    let bottom = 1. - max_pixel.y / view.viewport.w * 2.;
+
+    var corners: array<vec3<f32>, 8>;
+    corners[0] = unproject(view, vec3<f32>(left, bottom, 0.));
+    corners[1] = unproject(view, vec3<f32>(right, bottom, 0.));
+    corners[2] = unproject(view, vec3<f32>(left, top, 0.));
+    corners[3] = unproject(view, vec3<f32>(right, top, 0.));
+    corners[4] = unproject(view, vec3<f32>(left, bottom, 1.));
+    corners[5] = unproject(view, vec3<f32>(right, bottom, 1.));
+    corners[6] = unproject(view, vec3<f32>(left, top, 1.));
+    corners[7] = unproject(view, vec3<f32>(right, top, 1.));
+
+    var center = vec3<f32>(0.);
+    for (var index = 0; index < 8; index++) {
+        center += corners[index];
+    }
+    center /= 8.;
+
+    // Left, right, bottom, top, near, far
+    var planes: array<vec4<f32>, 6>;
+    planes[0] = plane(corners[0], corners[2], corners[4], center);
+    planes[1] = plane(corners[1], corners[3], corners[5], center);
+    planes[2] = plane(corners[0], corners[1], corners[4], center);
+    planes[3] = plane(corners[2], corners[3], corners[6], center);
+    planes[4] = plane(corners[0], corners[1], corners[2], center);
+    planes[5] = plane(corners[4], corners[5], corners[6], center);
+
+    let tile = (view.tile_offset + id.y * view.tiles_x + id.x) * (MAX_LIGHTS_PER_TILE + 1u);
+    var count = 0u;
+
+    for (var index = 0u; index < view.light_count && count < MAX_LIGHTS_PER_TILE; index++) {
+        let light = lights[index];
+
+        var inside = true;
+        for (var side = 0; side < 6; side++) {
+            if dot(planes[side].xyz, light.position) + planes[side].w < -light.range {
+                inside = false;
+                break;
+            }
+        }
+
+        if inside {
+            tiles[tile + 1u + count] = index;
+            count++;
+        }
+    }
+
+    tiles[tile] = count;
+}
+
+//====================================================================
+
+fn unproject(view: LightView, ndc: vec3<f32>) -> vec3<f32> {
+    let world = view.inverse_view_projection * vec4<f32>(ndc, 1.);
+    return world.xyz / world.w;
+}
+
+// Plane through three points, facing towards the inside point
+fn plane(a: vec3<f32>, b: vec3<f32>, c: vec3<f32>, inside: vec3<f32>) -> vec4<f32> {
+    var normal = normalize(cross(b - a, c - a));
+    if dot(normal, inside - a) < 0. {
+        normal = -normal;
+    }
+
+    return vec4<f32>(normal, -dot(normal, a));
+}
+
+//====================================================================
@@ -0,0 +1,85 @@
+//====================================================================
+// Uniforms
+
+struct Screen {
+    size: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> screen: Screen;
+
+@group(1) @binding(0) var texture: texture_2d<f32>;
+@group(1) @binding(1) var texture_sampler: sampler;
+
+
+//====================================================================
+
+struct VertexIn {
+    // Vertex - corner of the 4x4 slice grid
+    @location(0) grid: vec2<f32>,
+
+    // Instance
+    @location(1) position: vec2<f32>,
+    @location(2) size: vec2<f32>,
+    @location(3) border: vec4<f32>,
+    @location(4) uv_border: vec4<f32>,
+    @location(5) color: vec4<f32>,
+}
+
+struct VertexOut {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+//====================================================================
+
+// Offset along one axis of the slice grid. 0 and 3 are the outer edges,
+// 1 and 2 the inner edges of the border.
+fn slice_offset(index: f32, size: f32, start: f32, end: f32) -> f32 {
+    if index < 0.5 {
+        return 0.;
+    }
+    if index < 1.5 {
+        return start;
+    }
+    if index < 2.5 {
+        return size - end;
+    }
+    return size;
+}
+
+@vertex
+fn vs_main(in: VertexIn) -> VertexOut {
+    var out: VertexOut;
+
+    // Border is left, right, top, bottom
+    let offset = vec2<f32>(
+        slice_offset(in.grid.x, in.size.x, in.border.x, in.border.y),
+        slice_offset(in.grid.y, in.size.y, in.border.z, in.border.w),
+    );
+
+    let pixel = in.position + offset;
+    let ndc = vec2<f32>(
+        pixel.x / screen.size.x * 2. - 1.,
+        1. - pixel.y / screen.size.y * 2.,
+    );
+
+    out.clip_position = vec4<f32>(ndc, 0., 1.);
+
+    out.uv = vec2<f32>(
+        slice_offset(in.grid.x, 1., in.uv_border.x, in.uv_border.y),
+        slice_offset(in.grid.y, 1., in.uv_border.z, in.uv_border.w),
+    );
+    out.color = in.color;
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    let tex_color = textureSample(texture, texture_sampler, in.uv);
+
+    return tex_color * in.color;
+}
+
+//====================================================================
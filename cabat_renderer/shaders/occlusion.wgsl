@@ -0,0 +1,55 @@
+//====================================================================
+// Uniforms
+
+struct Camera {
+    projection: mat4x4<f32>,
+    position: vec3<f32>,
+    exposure: f32,
+    tonemapping: u32,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+
+//====================================================================
+
+struct VertexIn {
+    // Vertex
+    @location(0) vertex_position: vec3<f32>,
+
+    // Instance
+    @location(4) transform_1: vec4<f32>,
+    @location(5) transform_2: vec4<f32>,
+    @location(6) transform_3: vec4<f32>,
+    @location(7) transform_4: vec4<f32>,
+}
+
+struct VertexOut {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+//====================================================================
+
+@vertex
+fn vs_main(in: VertexIn) -> VertexOut {
+    var out: VertexOut;
+
+    let transform = mat4x4<f32>(
+        in.transform_1,
+        in.transform_2,
+        in.transform_3,
+        in.transform_4,
+    );
+
+    out.clip_position = camera.projection * transform * vec4<f32>(in.vertex_position, 1.);
+
+    return out;
+}
+
+// Nothing is written, only the samples passing the depth test are counted
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    return vec4<f32>(0.);
+}
+
+//====================================================================
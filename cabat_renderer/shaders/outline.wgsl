@@ -0,0 +1,64 @@
+//====================================================================
+// Uniforms
+
+struct Outline {
+    width: f32,
+}
+
+@group(0) @binding(0) var mask: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> outline: Outline;
+
+
+//====================================================================
+
+// Single triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2. - 1., 0., 1.);
+}
+
+// Color pixels outside of the mask using the closest masked pixel within the
+// outline width
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let center = vec2<i32>(position.xy);
+
+    if textureLoad(mask, center, 0).a > 0. {
+        discard;
+    }
+
+    let size = vec2<i32>(textureDimensions(mask));
+    let width = i32(ceil(outline.width));
+
+    var color = vec4<f32>(0.);
+    var closest = outline.width + 1.;
+
+    for (var y = -width; y <= width; y++) {
+        for (var x = -width; x <= width; x++) {
+            let coords = center + vec2<i32>(x, y);
+            if any(coords < vec2<i32>(0)) || any(coords >= size) {
+                continue;
+            }
+
+            let distance = length(vec2<f32>(f32(x), f32(y)));
+            if distance > outline.width || distance >= closest {
+                continue;
+            }
+
+            let sample = textureLoad(mask, coords, 0);
+            if sample.a > 0. {
+                color = sample;
+                closest = distance;
+            }
+        }
+    }
+
+    if color.a <= 0. {
+        discard;
+    }
+
+    return color;
+}
+
+//====================================================================
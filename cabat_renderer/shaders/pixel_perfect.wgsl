@@ -0,0 +1,33 @@
+//====================================================================
+// Uniforms
+
+@group(0) @binding(0) var scene: texture_2d<f32>;
+@group(0) @binding(1) var scene_sampler: sampler;
+
+
+//====================================================================
+
+struct VertexOut {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// Single triangle covering the whole viewport
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOut {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: VertexOut;
+    out.clip_position = vec4<f32>(uv * 2. - 1., 0., 1.);
+    // Texture coordinates start at the top left
+    out.uv = vec2<f32>(uv.x, 1. - uv.y);
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    return textureSample(scene, scene_sampler, in.uv);
+}
+
+//====================================================================
@@ -0,0 +1,74 @@
+//====================================================================
+// Point lights, see cabat_renderer::lights::Lights
+
+struct PointLight {
+    position: vec3<f32>,
+    range: f32,
+    color: vec3<f32>,
+}
+
+struct LightView {
+    inverse_view_projection: mat4x4<f32>,
+    // x, y, width, height in pixels
+    viewport: vec4<f32>,
+    tiles_x: u32,
+    tiles_y: u32,
+    tile_offset: u32,
+    light_count: u32,
+}
+
+@group(3) @binding(0) var<storage, read> lights: array<PointLight>;
+@group(3) @binding(1) var<storage, read> light_views: array<LightView>;
+@group(3) @binding(2) var<storage, read> light_tiles: array<u32>;
+
+//====================================================================
+
+const LIGHT_TILE_SIZE: f32 = 16.;
+const MAX_LIGHTS_PER_TILE: u32 = 63u;
+
+// Light from the point lights binned into this pixel's tile
+fn point_lights(pixel: vec2<f32>, world_position: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    // Find the camera drawing this pixel from its viewport
+    var view_index = -1;
+    for (var index = 0; index < i32(arrayLength(&light_views)); index++) {
+        let viewport = light_views[index].viewport;
+        if all(pixel >= viewport.xy) && all(pixel < viewport.xy + viewport.zw) {
+            view_index = index;
+        }
+    }
+
+    if view_index < 0 {
+        return vec3<f32>(0.);
+    }
+
+    // Tiles aren't culled on frames without lights
+    let view = light_views[view_index];
+    if view.light_count == 0u {
+        return vec3<f32>(0.);
+    }
+
+    let tile_coords = vec2<u32>((pixel - view.viewport.xy) / LIGHT_TILE_SIZE);
+    let tile = (view.tile_offset + tile_coords.y * view.tiles_x + tile_coords.x)
+        * (MAX_LIGHTS_PER_TILE + 1u);
+
+    var total = vec3<f32>(0.);
+
+    let count = light_tiles[tile];
+    for (var index = 0u; index < count; index++) {
+        let light = lights[light_tiles[tile + 1u + index]];
+
+        let offset = light.position - world_position;
+        let distance = length(offset);
+
+        // Inverse square falloff, smoothly windowed to zero at the light's range
+        let fade = clamp(1. - pow(distance / light.range, 4.), 0., 1.);
+        let attenuation = fade * fade / (distance * distance + 1.);
+
+        let diffuse = max(dot(normal, offset / max(distance, 0.0001)), 0.);
+        total += light.color * diffuse * attenuation;
+    }
+
+    return total;
+}
+
+//====================================================================
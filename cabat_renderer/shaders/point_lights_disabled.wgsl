@@ -0,0 +1,8 @@
+//====================================================================
+
+// Point lights aren't supported on this device, see cabat_renderer::lights::Lights
+fn point_lights(pixel: vec2<f32>, world_position: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(0.);
+}
+
+//====================================================================
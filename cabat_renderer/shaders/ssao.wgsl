@@ -0,0 +1,144 @@
+//====================================================================
+// Uniforms
+
+struct SsaoView {
+    view_projection: mat4x4<f32>,
+    inverse_view_projection: mat4x4<f32>,
+    // x, y, width, height in pixels
+    viewport: vec4<f32>,
+    radius: f32,
+    intensity: f32,
+    bias: f32,
+    samples: u32,
+}
+
+@group(0) @binding(0) var depth_texture: texture_depth_2d;
+
+@group(1) @binding(0) var<uniform> view: SsaoView;
+
+
+//====================================================================
+
+const TAU: f32 = 6.28318530718;
+const GOLDEN_ANGLE: f32 = 2.39996322973;
+
+// Single triangle covering the whole viewport
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2. - 1., 0., 1.);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(position.xy);
+    let depth = textureLoad(depth_texture, pixel, 0);
+
+    // Nothing was drawn here
+    if depth >= 1. {
+        return vec4<f32>(1.);
+    }
+
+    let origin = world_position(position.xy, depth);
+    let normal = world_normal(pixel, origin);
+
+    // Any basis around the normal works as samples are rotated per pixel anyway
+    var up = vec3<f32>(0., 1., 0.);
+    if abs(normal.y) > 0.99 {
+        up = vec3<f32>(1., 0., 0.);
+    }
+    let tangent = normalize(cross(up, normal));
+    let bitangent = cross(normal, tangent);
+
+    // Rotation repeating every 4x4 pixels, which the composite blur averages out
+    let noise = f32((pixel.x & 3) + (pixel.y & 3) * 4) / 16.;
+
+    var occlusion = 0.;
+
+    for (var index = 0u; index < view.samples; index++) {
+        // Spiral over the hemisphere, clustered towards the origin
+        let t = (f32(index) + 0.5) / f32(view.samples);
+        let angle = f32(index) * GOLDEN_ANGLE + noise * TAU;
+        let height = 1. - t;
+        let spread = sqrt(1. - height * height);
+        let scale = mix(0.1, 1., t * t) * view.radius;
+
+        let offset = tangent * cos(angle) * spread + bitangent * sin(angle) * spread + normal * height;
+        let sample_position = origin + offset * scale;
+
+        let clip = view.view_projection * vec4<f32>(sample_position, 1.);
+        if clip.w <= 0. {
+            continue;
+        }
+
+        let ndc = clip.xyz / clip.w;
+        let sample_pixel = view.viewport.xy
+            + vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5) * view.viewport.zw;
+
+        if any(sample_pixel < view.viewport.xy) || any(sample_pixel >= view.viewport.xy + view.viewport.zw) {
+            continue;
+        }
+
+        let scene_depth = textureLoad(depth_texture, vec2<i32>(sample_pixel), 0);
+        if scene_depth >= ndc.z {
+            continue;
+        }
+
+        // Whatever is in front of the sample only counts if it's above the
+        // surface and close enough to plausibly cover it
+        let occluder = world_position(sample_pixel, scene_depth) - origin;
+        let occluder_distance = length(occluder);
+
+        if dot(occluder, normal) > view.bias {
+            occlusion += smoothstep(0., 1., view.radius / max(occluder_distance, 0.0001));
+        }
+    }
+
+    let visibility = clamp(1. - occlusion / f32(view.samples) * view.intensity, 0., 1.);
+    return vec4<f32>(visibility, visibility, visibility, 1.);
+}
+
+//====================================================================
+
+// Rebuild the world position of a pixel from its depth
+fn world_position(pixel: vec2<f32>, depth: f32) -> vec3<f32> {
+    let ndc = vec2<f32>(
+        (pixel.x - view.viewport.x) / view.viewport.z * 2. - 1.,
+        1. - (pixel.y - view.viewport.y) / view.viewport.w * 2.,
+    );
+
+    let world = view.inverse_view_projection * vec4<f32>(ndc, depth, 1.);
+    return world.xyz / world.w;
+}
+
+// Normal from neighbouring positions, using whichever neighbour on each axis is
+// closest so edges don't bleed into the background
+fn world_normal(pixel: vec2<i32>, origin: vec3<f32>) -> vec3<f32> {
+    let right = neighbour(pixel + vec2<i32>(1, 0)) - origin;
+    let left = origin - neighbour(pixel - vec2<i32>(1, 0));
+    let down = neighbour(pixel + vec2<i32>(0, 1)) - origin;
+    let up = origin - neighbour(pixel - vec2<i32>(0, 1));
+
+    var horizontal = right;
+    if dot(left, left) < dot(right, right) {
+        horizontal = left;
+    }
+
+    var vertical = down;
+    if dot(up, up) < dot(down, down) {
+        vertical = up;
+    }
+
+    // Screen y points down, so this faces the camera
+    return normalize(cross(vertical, horizontal));
+}
+
+fn neighbour(pixel: vec2<i32>) -> vec3<f32> {
+    let size = vec2<i32>(textureDimensions(depth_texture));
+    let clamped = clamp(pixel, vec2<i32>(0), size - 1);
+
+    let depth = textureLoad(depth_texture, clamped, 0);
+    return world_position(vec2<f32>(clamped) + 0.5, depth);
+}
+
+//====================================================================
@@ -0,0 +1,36 @@
+//====================================================================
+// Uniforms
+
+@group(0) @binding(0) var occlusion: texture_2d<f32>;
+
+
+//====================================================================
+
+// Single triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2. - 1., 0., 1.);
+}
+
+// Average the 4x4 block around the pixel, matching the size of the noise
+// pattern used when sampling. Blended by multiplying with the scene.
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let center = vec2<i32>(position.xy);
+    let size = vec2<i32>(textureDimensions(occlusion));
+
+    var total = 0.;
+
+    for (var y = -2; y < 2; y++) {
+        for (var x = -2; x < 2; x++) {
+            let coords = clamp(center + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+            total += textureLoad(occlusion, coords, 0).r;
+        }
+    }
+
+    let visibility = total / 16.;
+    return vec4<f32>(visibility, visibility, visibility, 1.);
+}
+
+//====================================================================
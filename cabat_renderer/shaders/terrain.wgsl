@@ -0,0 +1,92 @@
+//====================================================================
+// Uniforms
+
+struct Camera {
+    projection: mat4x4<f32>,
+    position: vec3<f32>,
+    exposure: f32,
+    tonemapping: u32,
+}
+
+struct Terrain {
+    transform: mat4x4<f32>,
+    sun_direction: vec3<f32>,
+    layer_tiling: vec2<f32>,
+}
+
+struct Environment {
+    ambient: vec4<f32>,
+    fog_color: vec3<f32>,
+    fog_mode: u32,
+    fog_params: vec3<f32>,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+@group(1) @binding(0) var<uniform> terrain: Terrain;
+@group(1) @binding(1) var splat_map: texture_2d<f32>;
+@group(1) @binding(2) var layer_0: texture_2d<f32>;
+@group(1) @binding(3) var layer_1: texture_2d<f32>;
+@group(1) @binding(4) var layer_2: texture_2d<f32>;
+@group(1) @binding(5) var layer_3: texture_2d<f32>;
+@group(1) @binding(6) var terrain_sampler: sampler;
+
+@group(2) @binding(0) var<uniform> environment: Environment;
+
+// Point lights are bound to group 3, see point_lights.wgsl
+
+
+//====================================================================
+
+struct VertexIn {
+    @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+}
+
+struct VertexOut {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) world_position: vec3<f32>,
+}
+
+//====================================================================
+
+@vertex
+fn vs_main(in: VertexIn) -> VertexOut {
+    var out: VertexOut;
+
+    let world_position = terrain.transform * vec4<f32>(in.position, 1.);
+
+    out.clip_position = camera.projection * world_position;
+    out.world_position = world_position.xyz;
+
+    out.uv = in.uv;
+    out.normal = (terrain.transform * vec4<f32>(in.normal, 0.)).xyz;
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    let splat = textureSample(splat_map, terrain_sampler, in.uv);
+    let weights = splat / max(splat.r + splat.g + splat.b + splat.a, 0.0001);
+
+    let layer_uv = in.uv * terrain.layer_tiling;
+
+    let color =
+        textureSample(layer_0, terrain_sampler, layer_uv) * weights.r
+        + textureSample(layer_1, terrain_sampler, layer_uv) * weights.g
+        + textureSample(layer_2, terrain_sampler, layer_uv) * weights.b
+        + textureSample(layer_3, terrain_sampler, layer_uv) * weights.a;
+
+    let diffuse = max(dot(normalize(in.normal), -normalize(terrain.sun_direction)), 0.);
+    let ambient = environment.ambient.rgb * environment.ambient.a;
+    let light = ambient + diffuse * (1. - environment.ambient.a)
+        + point_lights(in.clip_position.xy, in.world_position, normalize(in.normal));
+
+    return vec4<f32>(tonemap(apply_fog(color.rgb * light, in.world_position)), 1.);
+}
+
+//====================================================================
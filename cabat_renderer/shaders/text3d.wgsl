@@ -4,12 +4,21 @@
 struct Camera {
     projection: mat4x4<f32>,
     position: vec3<f32>,
+    exposure: f32,
+    tonemapping: u32,
 }
 
 struct Instance {
     transform: mat4x4<f32>,
 }
 
+struct Environment {
+    ambient: vec4<f32>,
+    fog_color: vec3<f32>,
+    fog_mode: u32,
+    fog_params: vec3<f32>,
+}
+
 @group(0) @binding(0) var<uniform> camera: Camera;
 
 @group(1) @binding(0) var atlas_texture: texture_2d<f32>;
@@ -17,6 +26,8 @@ struct Instance {
 
 @group(2) @binding(0) var<uniform> instance: Instance;
 
+@group(3) @binding(0) var<uniform> environment: Environment;
+
 
 //====================================================================
 
@@ -29,12 +40,16 @@ struct VertexIn {
     @location(2) uv_start: vec2<f32>,
     @location(3) uv_end: vec2<f32>,
     @location(4) color: u32,
+    @location(5) depth: f32,
+    @location(6) edge: f32,
 }
 
 struct VertexOut {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) uv: vec2<f32>,
     @location(1) color: vec4<f32>,
+    @location(2) edge: f32,
+    @location(3) world_position: vec3<f32>,
 }
 
 //====================================================================
@@ -75,10 +90,10 @@ fn vs_main(in: VertexIn) -> VertexOut {
     
     vertex_pos = vertex_pos * in.glyph_size + in.glyph_pos;
 
-    out.clip_position =
-        camera.projection
-        * instance.transform
-        * vec4<f32>(vertex_pos, 1., 1.);
+    let world_position = instance.transform * vec4<f32>(vertex_pos, 1. + in.depth, 1.);
+
+    out.clip_position = camera.projection * world_position;
+    out.world_position = world_position.xyz;
 
     out.color = vec4<f32>(
         f32((in.color & 0x00ff0000u) >> 16u) / 255.,
@@ -87,6 +102,8 @@ fn vs_main(in: VertexIn) -> VertexOut {
         f32((in.color & 0xff000000u) >> 24u) / 255.,
     );
 
+    out.edge = in.edge;
+
     return out;
 }
 
@@ -94,8 +111,23 @@ fn vs_main(in: VertexIn) -> VertexOut {
 fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
     let tex_color = textureSample(atlas_texture, atlas_texture_sampler, in.uv);
     
-    return vec4<f32>(in.color.xyz, in.color.w * tex_color.x);
+    let color = tonemap(apply_fog(in.color.xyz * environment.ambient.rgb, in.world_position));
+
+    return vec4<f32>(color, in.color.w * tex_color.x);
 }
 
-//====================================================================
+// Atlas holds distance fields - 0.5 is the glyph edge
+@fragment
+fn fs_sdf(in: VertexOut) -> @location(0) vec4<f32> {
+    let distance = textureSample(atlas_texture, atlas_texture_sampler, in.uv).x;
+
+    // Keep the edge about a pixel wide on screen at any scale
+    let smoothing = max(fwidth(distance) * 0.5, 0.001);
+    let alpha = smoothstep(in.edge - smoothing, in.edge + smoothing, distance);
+
+    let color = tonemap(apply_fog(in.color.xyz * environment.ambient.rgb, in.world_position));
 
+    return vec4<f32>(color, in.color.w * alpha);
+}
+
+//====================================================================
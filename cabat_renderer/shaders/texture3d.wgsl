@@ -4,6 +4,15 @@
 struct Camera {
     projection: mat4x4<f32>,
     position: vec3<f32>,
+    exposure: f32,
+    tonemapping: u32,
+}
+
+struct Environment {
+    ambient: vec4<f32>,
+    fog_color: vec3<f32>,
+    fog_mode: u32,
+    fog_params: vec3<f32>,
 }
 
 @group(0) @binding(0) var<uniform> camera: Camera;
@@ -11,6 +20,8 @@ struct Camera {
 @group(1) @binding(0) var texture: texture_2d<f32>;
 @group(1) @binding(1) var texture_sampler: sampler;
 
+@group(2) @binding(0) var<uniform> environment: Environment;
+
 
 //====================================================================
 
@@ -26,12 +37,15 @@ struct VertexIn {
     @location(5) transform_3: vec4<f32>,
     @location(6) transform_4: vec4<f32>,
     @location(7) color: vec4<f32>,
+    @location(8) alpha_cutoff: f32,
 }
 
 struct VertexOut {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) uv: vec2<f32>,
     @location(1) color: vec4<f32>,
+    @location(2) alpha_cutoff: f32,
+    @location(3) world_position: vec3<f32>,
 }
 
 //====================================================================
@@ -48,24 +62,30 @@ fn vs_main(in: VertexIn) -> VertexOut {
     );
 
     let vertex_pos = in.vertex_position * in.size;
+    let world_position = transform * vec4<f32>(vertex_pos, 1., 1.);
 
-    out.clip_position =
-        camera.projection
-        * transform
-        * vec4<f32>(vertex_pos, 1., 1.);
+    out.clip_position = camera.projection * world_position;
+    out.world_position = world_position.xyz;
 
     out.uv = in.uv;
     out.color = in.color;
+    out.alpha_cutoff = in.alpha_cutoff;
 
     return out;
 }
 
 @fragment
 fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
-    let tex_color = textureSample(texture, texture_sampler, in.uv);
-    
-    return tex_color * in.color;
+    let color = textureSample(texture, texture_sampler, in.uv) * in.color;
+
+    if color.a < in.alpha_cutoff {
+        discard;
+    }
+
+    // Sprites are unlit, so only take on the ambient color
+    let lit = color.rgb * environment.ambient.rgb;
+
+    return vec4<f32>(tonemap(apply_fog(lit, in.world_position)), color.a);
 }
 
 //====================================================================
-
@@ -0,0 +1,94 @@
+//====================================================================
+// Uniforms
+
+struct Camera {
+    projection: mat4x4<f32>,
+    position: vec3<f32>,
+    exposure: f32,
+    tonemapping: u32,
+}
+
+struct Environment {
+    ambient: vec4<f32>,
+    fog_color: vec3<f32>,
+    fog_mode: u32,
+    fog_params: vec3<f32>,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+@group(1) @binding(0) var texture: texture_2d_array<f32>;
+@group(1) @binding(1) var texture_sampler: sampler;
+
+@group(2) @binding(0) var<uniform> environment: Environment;
+
+
+//====================================================================
+
+struct VertexIn {
+    // Vertex
+    @location(0) vertex_position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+
+    // Instance
+    @location(2) size: vec2<f32>,
+    @location(3) transform_1: vec4<f32>,
+    @location(4) transform_2: vec4<f32>,
+    @location(5) transform_3: vec4<f32>,
+    @location(6) transform_4: vec4<f32>,
+    @location(7) color: vec4<f32>,
+    @location(8) alpha_cutoff: f32,
+    @location(9) layer: u32,
+}
+
+struct VertexOut {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) alpha_cutoff: f32,
+    @location(3) world_position: vec3<f32>,
+    @location(4) @interpolate(flat) layer: u32,
+}
+
+//====================================================================
+
+@vertex
+fn vs_main(in: VertexIn) -> VertexOut {
+    var out: VertexOut;
+    
+    let transform = mat4x4<f32>(
+        in.transform_1,
+        in.transform_2,
+        in.transform_3,
+        in.transform_4,
+    );
+
+    let vertex_pos = in.vertex_position * in.size;
+    let world_position = transform * vec4<f32>(vertex_pos, 1., 1.);
+
+    out.clip_position = camera.projection * world_position;
+    out.world_position = world_position.xyz;
+
+    out.uv = in.uv;
+    out.color = in.color;
+    out.alpha_cutoff = in.alpha_cutoff;
+    out.layer = in.layer;
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    let color = textureSample(texture, texture_sampler, in.uv, in.layer) * in.color;
+
+    if color.a < in.alpha_cutoff {
+        discard;
+    }
+
+    // Sprites are unlit, so only take on the ambient color
+    let lit = color.rgb * environment.ambient.rgb;
+
+    return vec4<f32>(tonemap(apply_fog(lit, in.world_position)), color.a);
+}
+
+//====================================================================
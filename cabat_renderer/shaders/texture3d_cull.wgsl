@@ -0,0 +1,69 @@
+//====================================================================
+// Bindings
+
+// Texture3dInstanceRaw, flattened as its matrix isn't 16 byte aligned
+struct Instance {
+    // size (2), transform (16), color (4), alpha cutoff (1)
+    data: array<f32, 23>,
+}
+
+struct View {
+    planes: array<vec4<f32>, 6>,
+}
+
+struct Params {
+    count: u32,
+    capacity: u32,
+}
+
+struct DrawArgs {
+    index_count: u32,
+    instance_count: atomic<u32>,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+@group(0) @binding(0) var<storage, read> views: array<View>;
+
+@group(1) @binding(0) var<uniform> params: Params;
+@group(1) @binding(1) var<storage, read> instances: array<Instance>;
+@group(1) @binding(2) var<storage, read_write> culled: array<Instance>;
+@group(1) @binding(3) var<storage, read_write> args: array<DrawArgs>;
+
+
+//====================================================================
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    let view = id.y;
+
+    if index >= params.count {
+        return;
+    }
+
+    let instance = instances[index];
+
+    // Bounding sphere of the scaled quad, same as the cpu culling
+    let size = vec2<f32>(instance.data[0], instance.data[1]);
+    let x_axis = vec3<f32>(instance.data[2], instance.data[3], instance.data[4]);
+    let y_axis = vec3<f32>(instance.data[6], instance.data[7], instance.data[8]);
+    let z_axis = vec3<f32>(instance.data[10], instance.data[11], instance.data[12]);
+    let center = vec3<f32>(instance.data[14], instance.data[15], instance.data[16]);
+
+    let scale = max(length(x_axis), max(length(y_axis), length(z_axis)));
+    let radius = length(size) / 2. * scale;
+
+    for (var plane = 0u; plane < 6u; plane++) {
+        let normal = views[view].planes[plane];
+        if dot(normal.xyz, center) + normal.w < -radius {
+            return;
+        }
+    }
+
+    let slot = atomicAdd(&args[view].instance_count, 1u);
+    culled[view * params.capacity + slot] = instance;
+}
+
+//====================================================================
@@ -0,0 +1,31 @@
+//====================================================================
+
+// Apply the camera's exposure and tonemapping curve. Appended to shaders
+// declaring the `camera` uniform, see cabat_renderer::camera::TONEMAP_SHADER
+fn tonemap(color: vec3<f32>) -> vec3<f32> {
+    let exposed = color * camera.exposure;
+
+    var mapped = exposed;
+    switch camera.tonemapping {
+        case 1u: {
+            mapped = exposed / (exposed + vec3<f32>(1.));
+        }
+        case 2u: {
+            let a = 2.51;
+            let b = 0.03;
+            let c = 2.43;
+            let d = 0.59;
+            let e = 0.14;
+            mapped = clamp(
+                (exposed * (a * exposed + b)) / (exposed * (c * exposed + d) + e),
+                vec3<f32>(0.),
+                vec3<f32>(1.),
+            );
+        }
+        default: {}
+    }
+
+    return mapped;
+}
+
+//====================================================================
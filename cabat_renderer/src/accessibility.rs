@@ -0,0 +1,165 @@
+//====================================================================
+
+use cabat_common::Color;
+use cabat_shipyard::{prelude::*, Event};
+use serde::Deserialize;
+use shipyard::Unique;
+
+//====================================================================
+
+/// Player facing readability settings. Can be modified at any point, in which
+/// case an [AccessibilityChangedEvent] is triggered at the start of the next
+/// frame. Loaded from the `accessibility` section of the config file when
+/// using the ConfigPlugin.
+///
+/// Screen space text is drawn at `text_scale` on top of the window scale
+/// factor, wrapping within the same area rather than growing it. While
+/// `high_contrast` is set, text and UI panels are drawn with the [HighContrastPalette].
+#[derive(Unique, Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct AccessibilitySettings {
+    pub text_scale: f32,
+    pub high_contrast: bool,
+    #[serde(skip)]
+    pub palette: HighContrastPalette,
+}
+
+impl Default for AccessibilitySettings {
+    fn default() -> Self {
+        Self {
+            text_scale: 1.,
+            high_contrast: false,
+            palette: HighContrastPalette::default(),
+        }
+    }
+}
+
+impl AccessibilitySettings {
+    pub const MIN_TEXT_SCALE: f32 = 0.5;
+    pub const MAX_TEXT_SCALE: f32 = 4.;
+
+    #[inline]
+    pub fn with_text_scale(mut self, text_scale: f32) -> Self {
+        self.text_scale = text_scale;
+        self
+    }
+
+    #[inline]
+    pub fn with_high_contrast(mut self, high_contrast: bool) -> Self {
+        self.high_contrast = high_contrast;
+        self
+    }
+
+    #[inline]
+    pub fn with_palette(mut self, palette: HighContrastPalette) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    /// Text scale clamped to a usable range.
+    #[inline]
+    pub fn effective_text_scale(&self) -> f32 {
+        match self.text_scale.is_finite() {
+            true => self
+                .text_scale
+                .clamp(Self::MIN_TEXT_SCALE, Self::MAX_TEXT_SCALE),
+            false => 1.,
+        }
+    }
+
+    /// Palette to draw with, if high contrast is enabled.
+    #[inline]
+    pub fn contrast_palette(&self) -> Option<&HighContrastPalette> {
+        match self.high_contrast {
+            true => Some(&self.palette),
+            false => None,
+        }
+    }
+}
+
+//--------------------------------------------------
+
+/// Colors replacing the usual text and panel colors in high contrast mode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HighContrastPalette {
+    pub text: Color,
+    /// Used for UI panels and text outlines.
+    pub background: Color,
+    /// Used for highlights such as text selections.
+    pub accent: Color,
+}
+
+impl Default for HighContrastPalette {
+    fn default() -> Self {
+        Self {
+            text: Color::WHITE,
+            background: Color::BLACK,
+            accent: Color::srgb_u8(255, 214, 0, 255),
+        }
+    }
+}
+
+impl HighContrastPalette {
+    #[inline]
+    pub fn text_color(&self) -> cosmic_text::Color {
+        to_text_color(self.text)
+    }
+
+    #[inline]
+    pub fn background_text_color(&self) -> cosmic_text::Color {
+        to_text_color(self.background)
+    }
+}
+
+#[inline]
+fn to_text_color(color: Color) -> cosmic_text::Color {
+    let [r, g, b, a] = color.to_srgb_u8();
+    cosmic_text::Color::rgba(r, g, b, a)
+}
+
+//--------------------------------------------------
+
+/// Sent when the [AccessibilitySettings] change, so layouts depending on them
+/// can be recomputed.
+#[derive(Event, Debug, Clone)]
+pub struct AccessibilityChangedEvent(AccessibilitySettings);
+
+impl AccessibilityChangedEvent {
+    #[inline]
+    pub fn settings(&self) -> &AccessibilitySettings {
+        &self.0
+    }
+}
+
+// Settings as of the last change event
+#[derive(Unique, Default)]
+pub(crate) struct AppliedAccessibility(Option<AccessibilitySettings>);
+
+//====================================================================
+
+pub(crate) fn sys_apply_accessibility(
+    settings: Res<AccessibilitySettings>,
+    mut applied: ResMut<AppliedAccessibility>,
+    mut event_handler: ResMut<EventHandler>,
+) {
+    match &applied.0 {
+        Some(previous) if previous == &*settings => return,
+        // First frame sets the baseline, nothing has been laid out with older settings
+        None => {
+            applied.0 = Some(settings.clone());
+            return;
+        }
+        Some(_) => {}
+    }
+
+    log::info!(
+        "Accessibility settings changed - text scale {}, high contrast {}",
+        settings.effective_text_scale(),
+        settings.high_contrast
+    );
+
+    applied.0 = Some(settings.clone());
+    event_handler.add_event(AccessibilityChangedEvent(settings.clone()));
+}
+
+//====================================================================
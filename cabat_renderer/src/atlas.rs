@@ -0,0 +1,293 @@
+//====================================================================
+
+use std::{collections::HashMap, hash::BuildHasherDefault};
+
+use cabat_assets::{asset_storage::AssetStorage, handle::HandleId};
+use cabat_common::Size;
+use etagere::{euclid::Size2D, AllocId, BucketedAtlasAllocator};
+use rustc_hash::FxHasher;
+
+use crate::{
+    render_tools,
+    shared::BindGroupLayoutRegistry,
+    texture::{RawTexture, Texture},
+};
+
+//====================================================================
+
+/// Where an image was packed in a [RuntimeAtlas].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasRect {
+    /// Page of the atlas holding the image, see [RuntimeAtlas::bind_group].
+    pub page: usize,
+    pub uv_start: [f32; 2],
+    pub uv_end: [f32; 2],
+    /// Size of the image in pixels.
+    pub size: Size<u32>,
+}
+
+struct AtlasEntry {
+    alloc_id: AllocId,
+    rect: AtlasRect,
+}
+
+struct AtlasPage {
+    packer: BucketedAtlasAllocator,
+    texture: RawTexture,
+    bind_group: wgpu::BindGroup,
+}
+
+//====================================================================
+
+/// Packs many small loaded textures into shared pages, so anything drawing
+/// them can use one bind group per page and pick its image through the uv
+/// rect of its [AtlasRect].
+///
+/// Uses the same allocator as the [TextAtlas](crate::text::atlas::TextAtlas).
+/// A new page is added whenever the existing ones are full, so rects stay
+/// valid until their texture is unloaded and removed with
+/// [RuntimeAtlas::trim]. Page bind groups use the shared texture layout from
+/// the [BindGroupLayoutRegistry].
+pub struct RuntimeAtlas {
+    label: String,
+    page_size: Size<u32>,
+    padding: u32,
+
+    pages: Vec<AtlasPage>,
+    entries: HashMap<HandleId, AtlasEntry, BuildHasherDefault<FxHasher>>,
+}
+
+impl RuntimeAtlas {
+    /// Textures must be in this format to be packed.
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    /// Images are kept a pixel apart by default so filtering doesn't pull in
+    /// their neighbours.
+    pub fn new(label: &str, page_size: Size<u32>) -> Self {
+        Self {
+            label: label.to_string(),
+            page_size: Size::new(page_size.width.max(1), page_size.height.max(1)),
+            padding: 1,
+
+            pages: Vec::new(),
+            entries: HashMap::default(),
+        }
+    }
+
+    #[inline]
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    fn create_page(&self, device: &wgpu::Device, layouts: &BindGroupLayoutRegistry) -> AtlasPage {
+        let label = format!("{} Atlas Page {}", self.label, self.pages.len());
+
+        let texture = render_tools::create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label: Some(&label),
+                size: wgpu::Extent3d {
+                    width: self.page_size.width,
+                    height: self.page_size.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: Self::FORMAT,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(&label),
+            ..Default::default()
+        });
+
+        let texture = RawTexture {
+            texture,
+            view,
+            sampler,
+        };
+
+        let bind_group = layouts.create_bind_group(device, &texture, Some(&label));
+
+        AtlasPage {
+            packer: BucketedAtlasAllocator::new(Size2D::new(
+                self.page_size.width as i32,
+                self.page_size.height as i32,
+            )),
+            texture,
+            bind_group,
+        }
+    }
+
+    #[inline]
+    pub fn page_size(&self) -> Size<u32> {
+        self.page_size
+    }
+
+    #[inline]
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Number of images packed.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Bind group of a page, with the page at binding 0 and its sampler at
+    /// binding 1.
+    #[inline]
+    pub fn bind_group(&self, page: usize) -> Option<&wgpu::BindGroup> {
+        self.pages.get(page).map(|page| &page.bind_group)
+    }
+
+    /// Rect of an already packed texture.
+    #[inline]
+    pub fn get(&self, id: HandleId) -> Option<AtlasRect> {
+        self.entries.get(&id).map(|entry| entry.rect)
+    }
+
+    /// Whether a texture has the format and size to be packed.
+    pub fn accepts(&self, texture: &Texture) -> bool {
+        let raw = &texture.raw().texture;
+
+        raw.format() == Self::FORMAT
+            && raw.depth_or_array_layers() == 1
+            && raw.width() + self.padding * 2 <= self.page_size.width
+            && raw.height() + self.padding * 2 <= self.page_size.height
+    }
+
+    /// Rect of the texture, packing it in if it isn't already. Returns `None`
+    /// if the texture isn't loaded or can't be packed.
+    pub fn insert(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        layouts: &BindGroupLayoutRegistry,
+        storage: &AssetStorage,
+        id: HandleId,
+    ) -> Option<AtlasRect> {
+        if let Some(entry) = self.entries.get(&id) {
+            return Some(entry.rect);
+        }
+
+        let texture = storage.get_asset::<Texture>(id)?;
+        if !self.accepts(texture) {
+            return None;
+        }
+
+        let raw = &texture.raw().texture;
+        let size = Size::new(raw.width(), raw.height());
+
+        let alloc_size = etagere::Size::new(
+            (size.width + self.padding * 2).max(1) as i32,
+            (size.height + self.padding * 2).max(1) as i32,
+        );
+
+        // Earlier pages may have space freed up by trimming
+        let found = self
+            .pages
+            .iter_mut()
+            .enumerate()
+            .find_map(|(index, page)| Some((index, page.packer.allocate(alloc_size)?)));
+
+        let (page, allocation) = match found {
+            Some(found) => found,
+            None => {
+                log::trace!("Adding page {} to '{}' atlas", self.pages.len(), self.label);
+
+                let mut page = self.create_page(device, layouts);
+                let allocation = page.packer.allocate(alloc_size)?;
+                self.pages.push(page);
+
+                (self.pages.len() - 1, allocation)
+            }
+        };
+
+        let x = allocation.rectangle.min.x as u32 + self.padding;
+        let y = allocation.rectangle.min.y as u32 + self.padding;
+
+        encoder.copy_texture_to_texture(
+            raw.as_image_copy(),
+            wgpu::ImageCopyTexture {
+                texture: &self.pages[page].texture.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        let page_width = self.page_size.width as f32;
+        let page_height = self.page_size.height as f32;
+
+        let rect = AtlasRect {
+            page,
+            uv_start: [x as f32 / page_width, y as f32 / page_height],
+            uv_end: [
+                (x + size.width) as f32 / page_width,
+                (y + size.height) as f32 / page_height,
+            ],
+            size,
+        };
+
+        self.entries.insert(
+            id,
+            AtlasEntry {
+                alloc_id: allocation.id,
+                rect,
+            },
+        );
+
+        Some(rect)
+    }
+
+    /// Free the space of a packed texture. Its rect may be handed to another
+    /// image afterwards.
+    pub fn remove(&mut self, id: HandleId) {
+        if let Some(entry) = self.entries.remove(&id) {
+            self.pages[entry.rect.page]
+                .packer
+                .deallocate(entry.alloc_id);
+        }
+    }
+
+    /// Free the space of any textures that have been unloaded.
+    pub fn trim(&mut self, storage: &AssetStorage) {
+        let pages = &mut self.pages;
+
+        self.entries.retain(|id, entry| {
+            let loaded = storage.get_asset::<Texture>(*id).is_some();
+            if !loaded {
+                pages[entry.rect.page].packer.deallocate(entry.alloc_id);
+            }
+            loaded
+        });
+    }
+
+    /// Estimated bytes used by every page.
+    pub fn gpu_memory(&self) -> usize {
+        self.pages
+            .iter()
+            .map(|page| page.texture.gpu_memory())
+            .sum()
+    }
+}
+
+//====================================================================
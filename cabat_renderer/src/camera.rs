@@ -1,71 +1,277 @@
 //====================================================================
 
-use shipyard::Unique;
-use wgpu::util::DeviceExt;
+use std::sync::RwLock;
+
+use cabat_common::{Size, WindowSize};
+use cabat_spatial::Ray;
+use shipyard::{Component, IntoIter, IntoWithId, Unique, View};
+
+use crate::{render_tools, shared::BindGroupLayoutRegistry, visibility::RenderLayers};
 
 //====================================================================
 
+/// `tonemap(color: vec3<f32>) -> vec3<f32>`, applying the camera's exposure and
+/// tonemapping. Appended to the source of shaders declaring the `camera` uniform.
+pub const TONEMAP_SHADER: &str = include_str!("../shaders/tonemap.wgsl");
+
+/// Camera the main pass is drawn with, along with the area of the surface it
+/// covers. The area is the full surface unless letterboxed by a
+/// [FixedAspect](crate::letterbox::FixedAspect).
 #[derive(Unique)]
-pub struct MainCamera(pub Camera);
+pub struct MainCamera(pub Camera, Viewport);
 
 impl MainCamera {
     #[inline]
-    pub fn new<C: CameraUniform>(device: &wgpu::Device, camera: &C) -> Self {
-        Self(Camera::new(device, camera))
+    pub fn new<C: CameraUniform>(
+        device: &wgpu::Device,
+        layouts: &BindGroupLayoutRegistry,
+        camera: &C,
+    ) -> Self {
+        Self(Camera::new(device, layouts, camera), Viewport::FULL)
     }
 
     #[inline]
-    pub fn update_camera<C: CameraUniform>(&self, queue: &wgpu::Queue, camera: &C) {
-        self.0.update_camera(queue, camera);
+    pub fn viewport(&self) -> Viewport {
+        self.1
     }
 
     #[inline]
-    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
-        self.0.bind_group_layout()
+    pub(crate) fn set_viewport(&mut self, viewport: Viewport) {
+        self.1 = viewport;
+    }
+
+    #[inline]
+    pub fn update_camera<C: CameraUniform>(&self, queue: &wgpu::Queue, camera: &C) {
+        self.0.update_camera(queue, camera);
     }
 
     #[inline]
     pub fn bind_group(&self) -> &wgpu::BindGroup {
         self.0.bind_group()
     }
+
+    #[inline]
+    pub fn frustum(&self) -> Frustum {
+        self.0.frustum()
+    }
 }
 
 // TODO - Create MainUiCamera (orthographic projection)
 
 //====================================================================
 
+/// Area of the surface a camera draws into, in normalized (0 to 1) coordinates
+/// starting from the top left corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for Viewport {
+    #[inline]
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+impl Viewport {
+    pub const FULL: Self = Self {
+        x: 0.,
+        y: 0.,
+        width: 1.,
+        height: 1.,
+    };
+
+    #[inline]
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn to_pixels(&self, size: Size<u32>) -> [f32; 4] {
+        let width = size.width as f32;
+        let height = size.height as f32;
+
+        let x = (self.x * width).clamp(0., width);
+        let y = (self.y * height).clamp(0., height);
+
+        [
+            x,
+            y,
+            (self.width * width).clamp(0., width - x),
+            (self.height * height).clamp(0., height - y),
+        ]
+    }
+
+    /// This viewport placed inside of another.
+    #[inline]
+    pub fn within(&self, outer: Viewport) -> Self {
+        Self {
+            x: outer.x + self.x * outer.width,
+            y: outer.y + self.y * outer.height,
+            width: self.width * outer.width,
+            height: self.height * outer.height,
+        }
+    }
+
+    /// Position relative to the viewport's top left corner, from a position on
+    /// a surface of the given size.
+    #[inline]
+    pub fn local_position(&self, screen_pos: glam::Vec2, size: Size<u32>) -> glam::Vec2 {
+        let [x, y, _, _] = self.to_pixels(size);
+        screen_pos - glam::vec2(x, y)
+    }
+
+    #[inline]
+    pub fn aspect(&self, size: Size<u32>) -> f32 {
+        let [_, _, width, height] = self.to_pixels(size);
+        width / height.max(1.)
+    }
+
+    /// Set the pass's viewport and scissor rect. wgpu rejects empty rects, so
+    /// viewports smaller than a pixel still cover one.
+    pub fn apply(&self, pass: &mut wgpu::RenderPass, size: Size<u32>) {
+        let [x, y, width, height] = self.to_pixels(size);
+
+        let x = (x as u32).min(size.width.saturating_sub(1));
+        let y = (y as u32).min(size.height.saturating_sub(1));
+        let width = (width as u32).clamp(1, (size.width - x).max(1));
+        let height = (height as u32).clamp(1, (size.height - y).max(1));
+
+        pass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0., 1.);
+        pass.set_scissor_rect(x, y, width, height);
+    }
+}
+
+//--------------------------------------------------
+
+/// Additional camera drawing the scene into part of the surface (split-screen etc).
+/// When any active scene cameras exist they replace the [MainCamera] in the main pass.
+/// Cameras share the main depth texture so viewports should not overlap.
+#[derive(Component)]
+pub struct SceneCamera {
+    camera: Camera,
+    pub viewport: Viewport,
+    pub order: i32,
+    pub active: bool,
+}
+
+impl SceneCamera {
+    pub fn new<C: CameraUniform>(
+        device: &wgpu::Device,
+        layouts: &BindGroupLayoutRegistry,
+        camera: &C,
+        viewport: Viewport,
+    ) -> Self {
+        Self {
+            camera: Camera::new(device, layouts, camera),
+            viewport,
+            order: 0,
+            active: true,
+        }
+    }
+
+    #[inline]
+    pub fn with_order(mut self, order: i32) -> Self {
+        self.order = order;
+        self
+    }
+
+    #[inline]
+    pub fn update_camera<C: CameraUniform>(&self, queue: &wgpu::Queue, camera: &C) {
+        self.camera.update_camera(queue, camera);
+    }
+
+    #[inline]
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+}
+
+//--------------------------------------------------
+
+pub struct CameraView<'a> {
+    pub bind_group: &'a wgpu::BindGroup,
+    pub viewport: Viewport,
+    pub view_projection: glam::Mat4,
+    pub frustum: Frustum,
+    pub layers: RenderLayers,
+}
+
+/// Cameras to draw the main pass with, in order. Falls back to the [MainCamera]
+/// if there are no active scene cameras.
+pub fn main_pass_cameras<'a>(
+    main_camera: &'a MainCamera,
+    v_cameras: &'a View<SceneCamera>,
+    v_layers: &View<RenderLayers>,
+) -> Vec<CameraView<'a>> {
+    let mut cameras = v_cameras
+        .iter()
+        .with_id()
+        .filter(|(_, camera)| camera.active)
+        .collect::<Vec<_>>();
+
+    if cameras.is_empty() {
+        return vec![CameraView {
+            bind_group: main_camera.bind_group(),
+            viewport: main_camera.viewport(),
+            view_projection: main_camera.0.view_projection(),
+            frustum: main_camera.frustum(),
+            layers: RenderLayers::default(),
+        }];
+    }
+
+    cameras.sort_by_key(|(_, camera)| camera.order);
+
+    cameras
+        .into_iter()
+        .map(|(id, camera)| CameraView {
+            bind_group: camera.camera.bind_group(),
+            viewport: camera.viewport.within(main_camera.viewport()),
+            view_projection: camera.camera.view_projection(),
+            frustum: camera.camera.frustum(),
+            layers: RenderLayers::of(v_layers, id),
+        })
+        .collect()
+}
+
+//====================================================================
+
 pub struct Camera {
     camera_buffer: wgpu::Buffer,
-    camera_bind_group_layout: wgpu::BindGroupLayout,
     camera_bind_group: wgpu::BindGroup,
+
+    // Cpu copy of the last uploaded projection, used for culling
+    view_projection: RwLock<glam::Mat4>,
 }
 
 impl Camera {
-    pub fn new<C: CameraUniform>(device: &wgpu::Device, camera: &C) -> Self {
-        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Camera buffer"),
-            contents: bytemuck::cast_slice(&[camera.into_uniform()]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
-        let camera_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Camera Bind Group Layout"),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
-            });
+    pub fn new<C: CameraUniform>(
+        device: &wgpu::Device,
+        layouts: &BindGroupLayoutRegistry,
+        camera: &C,
+    ) -> Self {
+        let uniform = camera.into_uniform();
+
+        let camera_buffer = render_tools::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Camera buffer"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        );
 
         let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Camera Bind Group"),
-            layout: &camera_bind_group_layout,
+            layout: layouts.camera(),
             entries: &[wgpu::BindGroupEntry {
                 binding: 0,
                 resource: wgpu::BindingResource::Buffer(camera_buffer.as_entire_buffer_binding()),
@@ -74,23 +280,28 @@ impl Camera {
 
         Self {
             camera_buffer,
-            camera_bind_group_layout,
             camera_bind_group,
+
+            view_projection: RwLock::new(uniform.view_projection()),
         }
     }
 
     #[inline]
     pub fn update_camera<C: CameraUniform>(&self, queue: &wgpu::Queue, camera: &C) {
-        queue.write_buffer(
-            &self.camera_buffer,
-            0,
-            bytemuck::cast_slice(&[camera.into_uniform()]),
-        );
+        let uniform = camera.into_uniform();
+
+        queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[uniform]));
+        *self.view_projection.write().unwrap() = uniform.view_projection();
     }
 
     #[inline]
-    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
-        &self.camera_bind_group_layout
+    pub fn view_projection(&self) -> glam::Mat4 {
+        *self.view_projection.read().unwrap()
+    }
+
+    #[inline]
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_view_projection(self.view_projection())
     }
 
     #[inline]
@@ -99,27 +310,215 @@ impl Camera {
     }
 }
 
+impl CameraProjection for Camera {
+    #[inline]
+    fn view_projection(&self) -> glam::Mat4 {
+        Camera::view_projection(self)
+    }
+}
+
 //====================================================================
 
 pub trait CameraUniform {
     fn into_uniform(&self) -> CameraUniformRaw;
 }
 
+//--------------------------------------------------
+
+/// Mapping between world space and screen pixels through a camera. Screen
+/// positions start from the top left corner, matching the mouse position. For
+/// cameras drawing into a [Viewport], use the viewport's size in pixels and
+/// positions relative to its corner.
+pub trait CameraProjection {
+    fn view_projection(&self) -> glam::Mat4;
+
+    /// Screen position of a point in the world, or `None` if it's behind the camera.
+    fn project(&self, world_pos: glam::Vec3, screen_size: Size<u32>) -> Option<glam::Vec2> {
+        let clip = self.view_projection() * world_pos.extend(1.);
+
+        if clip.w <= f32::EPSILON {
+            return None;
+        }
+
+        let ndc = clip.truncate() / clip.w;
+
+        Some(glam::vec2(
+            (ndc.x + 1.) / 2. * screen_size.width as f32,
+            (1. - ndc.y) / 2. * screen_size.height as f32,
+        ))
+    }
+
+    /// Point in the world under a screen position, at a depth from 0 (near
+    /// plane) to 1 (far plane).
+    fn unproject(&self, screen_pos: glam::Vec2, depth: f32, screen_size: Size<u32>) -> glam::Vec3 {
+        let ndc = glam::vec3(
+            screen_pos.x / (screen_size.width as f32).max(1.) * 2. - 1.,
+            1. - screen_pos.y / (screen_size.height as f32).max(1.) * 2.,
+            depth,
+        );
+
+        self.view_projection().inverse().project_point3(ndc)
+    }
+
+    /// Ray from the near plane through a screen position, for picking.
+    fn screen_ray(&self, screen_pos: glam::Vec2, screen_size: Size<u32>) -> Ray {
+        let near = self.unproject(screen_pos, 0., screen_size);
+        let far = self.unproject(screen_pos, 1., screen_size);
+
+        Ray::new(near, far - near)
+    }
+
+    /// [CameraProjection::project] for a camera covering the whole window.
+    #[inline]
+    fn project_to_window(&self, world_pos: glam::Vec3, window: &WindowSize) -> Option<glam::Vec2> {
+        self.project(world_pos, window.size())
+    }
+
+    /// [CameraProjection::screen_ray] for a camera covering the whole window.
+    #[inline]
+    fn window_ray(&self, screen_pos: glam::Vec2, window: &WindowSize) -> Ray {
+        self.screen_ray(screen_pos, window.size())
+    }
+}
+
+/// Curve mapping high dynamic range colors into the displayable range, applied by
+/// the 3d shaders after exposure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Tonemapping {
+    /// Colors above 1 are clipped.
+    #[default]
+    None,
+    Reinhard,
+    /// Fitted ACES filmic curve.
+    Aces,
+}
+
+impl Tonemapping {
+    #[inline]
+    fn raw(&self) -> u32 {
+        match self {
+            Tonemapping::None => 0,
+            Tonemapping::Reinhard => 1,
+            Tonemapping::Aces => 2,
+        }
+    }
+}
+
+//--------------------------------------------------
+
 #[repr(C)]
 #[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
 pub struct CameraUniformRaw {
     view_projection: [f32; 16],
     camera_position: [f32; 3],
-    _padding: u32,
+    exposure: f32,
+    tonemapping: u32,
+    _padding: [u32; 3],
 }
 impl CameraUniformRaw {
     pub fn new(view_projection: [f32; 16], camera_position: [f32; 3]) -> Self {
         Self {
             view_projection,
             camera_position,
-            _padding: 0,
+            exposure: 1.,
+            tonemapping: Tonemapping::None.raw(),
+            _padding: [0; 3],
         }
     }
+
+    #[inline]
+    pub fn with_exposure(mut self, exposure: f32, tonemapping: Tonemapping) -> Self {
+        self.exposure = exposure;
+        self.tonemapping = tonemapping.raw();
+        self
+    }
+
+    #[inline]
+    pub fn view_projection(&self) -> glam::Mat4 {
+        glam::Mat4::from_cols_array(&self.view_projection)
+    }
+}
+
+//--------------------------------------------------
+
+/// Clipping planes of a camera, pointing inwards.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    planes: [glam::Vec4; 6],
+    bounds: Option<(glam::Vec3, glam::Vec3)>,
+}
+
+impl Frustum {
+    /// Extract the planes from a view projection matrix with a 0 to 1 depth range.
+    pub fn from_view_projection(view_projection: glam::Mat4) -> Self {
+        let [row_x, row_y, row_z, row_w] = [0, 1, 2, 3].map(|row| view_projection.row(row));
+
+        let planes = [
+            row_w + row_x,
+            row_w - row_x,
+            row_w + row_y,
+            row_w - row_y,
+            row_z,
+            row_w - row_z,
+        ]
+        .map(|plane| plane / plane.truncate().length().max(f32::EPSILON));
+
+        // Corners of clip space, which may be at infinity for infinite far planes
+        let inverse = view_projection.inverse();
+        let corners = (0..8).map(|corner| {
+            inverse.project_point3(glam::Vec3::new(
+                if corner & 1 == 0 { -1. } else { 1. },
+                if corner & 2 == 0 { -1. } else { 1. },
+                if corner & 4 == 0 { 0. } else { 1. },
+            ))
+        });
+
+        let bounds = corners.clone().all(|corner| corner.is_finite()).then(|| {
+            corners.fold((glam::Vec3::MAX, glam::Vec3::MIN), |(min, max), corner| {
+                (min.min(corner), max.max(corner))
+            })
+        });
+
+        Self { planes, bounds }
+    }
+
+    /// World space box around the frustum as `(min, max)`, or None if it
+    /// reaches infinitely far.
+    #[inline]
+    pub fn aabb(&self) -> Option<(glam::Vec3, glam::Vec3)> {
+        self.bounds
+    }
+
+    /// Planes as `(normal, distance)`, in left, right, bottom, top, near, far order.
+    #[inline]
+    pub fn planes(&self) -> &[glam::Vec4; 6] {
+        &self.planes
+    }
+
+    #[inline]
+    pub fn contains_point(&self, point: glam::Vec3) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.truncate().dot(point) + plane.w >= 0.)
+    }
+
+    /// Whether any part of the axis aligned box could be visible.
+    pub fn intersects_aabb(&self, min: glam::Vec3, max: glam::Vec3) -> bool {
+        self.planes.iter().all(|plane| {
+            let normal = plane.truncate();
+
+            // Corner furthest along the plane normal
+            let corner = glam::Vec3::select(normal.cmpge(glam::Vec3::ZERO), max, min);
+            normal.dot(corner) + plane.w >= 0.
+        })
+    }
+
+    #[inline]
+    pub fn intersects_sphere(&self, center: glam::Vec3, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.truncate().dot(center) + plane.w >= -radius)
+    }
 }
 
 //--------------------------------------------------
@@ -135,6 +534,10 @@ pub struct OrthographicCamera {
 
     pub translation: glam::Vec3,
     pub rotation: glam::Quat,
+
+    /// Multiplier applied to colors before tonemapping.
+    pub exposure: f32,
+    pub tonemapping: Tonemapping,
 }
 
 impl Default for OrthographicCamera {
@@ -149,6 +552,9 @@ impl Default for OrthographicCamera {
 
             translation: glam::Vec3::ZERO,
             rotation: glam::Quat::IDENTITY,
+
+            exposure: 1.,
+            tonemapping: Tonemapping::None,
         }
     }
 }
@@ -156,6 +562,14 @@ impl Default for OrthographicCamera {
 impl CameraUniform for OrthographicCamera {
     fn into_uniform(&self) -> CameraUniformRaw {
         CameraUniformRaw::new(self.get_projection(), self.translation.into())
+            .with_exposure(self.exposure, self.tonemapping)
+    }
+}
+
+impl CameraProjection for OrthographicCamera {
+    #[inline]
+    fn view_projection(&self) -> glam::Mat4 {
+        glam::Mat4::from_cols_array(&self.get_projection())
     }
 }
 
@@ -226,6 +640,10 @@ pub struct PerspectiveCamera {
 
     pub translation: glam::Vec3,
     pub rotation: glam::Quat,
+
+    /// Multiplier applied to colors before tonemapping.
+    pub exposure: f32,
+    pub tonemapping: Tonemapping,
 }
 
 impl Default for PerspectiveCamera {
@@ -239,6 +657,9 @@ impl Default for PerspectiveCamera {
 
             translation: glam::Vec3::ZERO,
             rotation: glam::Quat::IDENTITY,
+
+            exposure: 1.,
+            tonemapping: Tonemapping::None,
         }
     }
 }
@@ -246,6 +667,14 @@ impl Default for PerspectiveCamera {
 impl CameraUniform for PerspectiveCamera {
     fn into_uniform(&self) -> CameraUniformRaw {
         CameraUniformRaw::new(self.get_projection(), self.translation.into())
+            .with_exposure(self.exposure, self.tonemapping)
+    }
+}
+
+impl CameraProjection for PerspectiveCamera {
+    #[inline]
+    fn view_projection(&self) -> glam::Mat4 {
+        glam::Mat4::from_cols_array(&self.get_projection())
     }
 }
 
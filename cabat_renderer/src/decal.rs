@@ -0,0 +1,423 @@
+//====================================================================
+
+use std::{
+    collections::{HashMap, HashSet},
+    hash::BuildHasherDefault,
+};
+
+use cabat_assets::{
+    asset_storage::AssetStorage,
+    handle::{Handle, HandleId},
+};
+use cabat_common::Color;
+use cabat_shipyard::prelude::*;
+use cabat_spatial::Transform;
+use rustc_hash::FxHasher;
+use shipyard::{
+    AllStoragesView, Component, IntoIter, IntoWithId, IntoWorkload, SystemModificator, Unique, View,
+};
+
+use crate::{
+    camera::{self, MainCamera, SceneCamera},
+    mesh::{Mesh, MeshData, MeshVertex},
+    render_graph::{resources, AddRenderPass, RenderGraphNode},
+    render_tools::{self, DynamicUniformBuffer, FaceCulling, InstanceBuffer},
+    settings::SurfaceFormatChangedEvent,
+    shared::BindGroupLayoutRegistry,
+    texture::{DepthTexture, Texture},
+    visibility::{self, RenderLayers, Visibility},
+    Device, DrawCounts, Queue, RenderEncoder, RenderPassDesc, RenderStats, SurfaceConfig, Vertex,
+};
+
+//====================================================================
+
+pub struct DecalPlugin;
+
+impl Plugin for DecalPlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        builder
+            .add_workload_pre(Stages::Setup, sys_setup_decal_renderer)
+            .add_workload_last(Stages::Update, sys_prep_decals)
+            .add_workload_pre(
+                Stages::Render,
+                sys_prep_decal_views.skip_if_missing_unique::<RenderEncoder>(),
+            )
+            // Needs the finished depth buffer so runs once the main pass has ended
+            .add_render_pass(
+                RenderGraphNode::new("decals").writes(resources::SCENE),
+                sys_render_decals,
+            )
+            .add_event::<SurfaceFormatChangedEvent>(sys_setup_decal_renderer.into_workload());
+    }
+}
+
+//====================================================================
+
+/// Projects a texture along the entity's local -Y axis onto whatever geometry is
+/// inside its box, e.g. bullet holes, blob shadows and stains.
+///
+/// Decals are drawn after the main pass, on top of any transparent sprites or
+/// text inside their box.
+#[derive(Component)]
+pub struct Decal {
+    pub texture: Handle<Texture>,
+    /// Box the decal is projected through, scaled by the entity's transform.
+    pub size: glam::Vec3,
+    pub color: Color,
+    /// Portion of the box's height faded out towards its top and bottom, 0 for a
+    /// hard cut.
+    pub fade: f32,
+}
+
+impl Decal {
+    #[inline]
+    pub fn new(texture: Handle<Texture>, size: glam::Vec3) -> Self {
+        Self {
+            texture,
+            size,
+            color: Color::WHITE,
+            fade: 0.25,
+        }
+    }
+
+    #[inline]
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    #[inline]
+    pub fn with_fade(mut self, fade: f32) -> Self {
+        self.fade = fade;
+        self
+    }
+}
+
+//====================================================================
+
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Debug)]
+pub struct DecalInstanceRaw {
+    pub transform: [f32; 16],
+    pub inverse_transform: [f32; 16],
+    pub color: [f32; 4],
+    pub fade: f32,
+}
+
+impl Vertex for DecalInstanceRaw {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 10] = wgpu::vertex_attr_array![
+            4 => Float32x4,
+            5 => Float32x4,
+            6 => Float32x4,
+            7 => Float32x4,
+            8 => Float32x4,
+            9 => Float32x4,
+            10 => Float32x4,
+            11 => Float32x4,
+            12 => Float32x4,
+            13 => Float32,
+        ];
+
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<DecalInstanceRaw>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &VERTEX_ATTRIBUTES,
+        }
+    }
+}
+
+// Per camera data used to rebuild world positions from the depth buffer
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+struct DecalViewRaw {
+    inverse_view_projection: [f32; 16],
+    // x, y, width, height in pixels
+    viewport: [f32; 4],
+}
+
+//====================================================================
+
+#[derive(Unique)]
+pub struct DecalRenderer {
+    pipeline: wgpu::RenderPipeline,
+    cube: Mesh,
+
+    depth_bind_group_layout: wgpu::BindGroupLayout,
+    depth_bind_group: wgpu::BindGroup,
+
+    view_bind_group_layout: wgpu::BindGroupLayout,
+    views: DynamicUniformBuffer<DecalViewRaw>,
+    // Offsets into views, in main pass camera order
+    view_offsets: Vec<u32>,
+
+    instances: HashMap<
+        (HandleId, RenderLayers),
+        InstanceBuffer<DecalInstanceRaw>,
+        BuildHasherDefault<FxHasher>,
+    >,
+}
+
+impl DecalRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        layouts: &BindGroupLayoutRegistry,
+        depth: &DepthTexture,
+    ) -> Self {
+        let depth_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Decal Depth Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                }],
+            });
+
+        let depth_bind_group =
+            Self::create_depth_bind_group(device, &depth_bind_group_layout, depth);
+
+        let view_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Decal View Bind Group Layout"),
+                entries: &[DynamicUniformBuffer::<DecalViewRaw>::bgl_entry(
+                    0,
+                    wgpu::ShaderStages::FRAGMENT,
+                )],
+            });
+
+        let views = DynamicUniformBuffer::new(device, &view_bind_group_layout, "Decal View");
+
+        let pipeline = render_tools::create_pipeline(
+            device,
+            config,
+            "Decal Pipeline",
+            &[
+                layouts.camera(),
+                layouts.texture(),
+                &depth_bind_group_layout,
+                &view_bind_group_layout,
+            ],
+            &[MeshVertex::desc(), DecalInstanceRaw::desc()],
+            &format!(
+                "{}\n{}",
+                include_str!("../shaders/decal.wgsl"),
+                camera::TONEMAP_SHADER
+            ),
+            render_tools::RenderPipelineDescriptor {
+                fragment_targets: Some(&[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::all(),
+                })]),
+                ..Default::default()
+            }
+            // Back faces still cover the box when the camera is inside it
+            .with_face_culling(FaceCulling::FRONT),
+        );
+
+        Self {
+            pipeline,
+            cube: Mesh::new(device, &MeshData::cube(), "Decal Cube"),
+
+            depth_bind_group_layout,
+            depth_bind_group,
+
+            view_bind_group_layout,
+            views,
+            view_offsets: Vec::new(),
+
+            instances: HashMap::default(),
+        }
+    }
+
+    fn create_depth_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        depth: &DepthTexture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Decal Depth Bind Group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&depth.main_texture().view),
+            }],
+        })
+    }
+
+    /// Draw the decals on any of the camera's layers.
+    pub fn render(
+        &self,
+        pass: &mut wgpu::RenderPass,
+        camera_bind_group: &wgpu::BindGroup,
+        view_index: usize,
+        camera_layers: RenderLayers,
+        storage: &AssetStorage,
+    ) -> DrawCounts {
+        let offset = match self.view_offsets.get(view_index) {
+            Some(offset) => *offset,
+            None => return DrawCounts::default(),
+        };
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, camera_bind_group, &[]);
+        pass.set_bind_group(2, &self.depth_bind_group, &[]);
+        pass.set_bind_group(3, self.views.bind_group(), &[offset]);
+
+        self.instances
+            .iter()
+            .filter(|((_, layers), instances)| {
+                !instances.is_empty() && layers.intersects(&camera_layers)
+            })
+            .fold(DrawCounts::pipeline(), |counts, ((id, _), instances)| {
+                let texture = match storage.get_asset::<Texture>(*id) {
+                    Some(texture) => texture,
+                    None => return counts,
+                };
+
+                pass.set_bind_group(1, texture.binding(), &[]);
+                pass.set_vertex_buffer(1, instances.buffer().slice(..));
+                self.cube.draw(pass, 0, 0..instances.count());
+
+                counts.draw(instances.count())
+            })
+    }
+}
+
+//====================================================================
+
+fn sys_setup_decal_renderer(
+    all_storages: AllStoragesView,
+    device: Res<Device>,
+    config: Res<SurfaceConfig>,
+    layouts: Res<BindGroupLayoutRegistry>,
+    depth: Res<DepthTexture>,
+) {
+    let renderer = DecalRenderer::new(device.inner(), config.inner(), &layouts, &depth);
+    all_storages.add_unique(renderer);
+}
+
+fn sys_prep_decals(
+    device: Res<Device>,
+    queue: Res<Queue>,
+    depth: Res<DepthTexture>,
+    mut renderer: ResMut<DecalRenderer>,
+
+    v_decal: View<Decal>,
+    v_transform: View<Transform>,
+    v_visibility: View<Visibility>,
+    v_layers: View<RenderLayers>,
+) {
+    if depth.is_modified() {
+        renderer.depth_bind_group = DecalRenderer::create_depth_bind_group(
+            device.inner(),
+            &renderer.depth_bind_group_layout,
+            &depth,
+        );
+    }
+
+    let instances = (&v_transform, &v_decal)
+        .iter()
+        .with_id()
+        .filter(|(id, _)| visibility::is_visible(&v_visibility, *id))
+        .fold(HashMap::new(), |mut acc, (id, (transform, decal))| {
+            let matrix = glam::Mat4::from_cols_array(&transform.to_array())
+                * glam::Mat4::from_scale(decal.size);
+
+            let instance = DecalInstanceRaw {
+                transform: matrix.to_cols_array(),
+                inverse_transform: matrix.inverse().to_cols_array(),
+                color: decal.color.into(),
+                fade: decal.fade.clamp(0., 1.),
+            };
+
+            acc.entry((decal.texture.id(), RenderLayers::of(&v_layers, id)))
+                .or_insert(Vec::new())
+                .push(instance);
+
+            acc
+        });
+
+    let mut previous = renderer.instances.keys().copied().collect::<HashSet<_>>();
+
+    instances.into_iter().for_each(|(key, raw)| {
+        previous.remove(&key);
+
+        renderer
+            .instances
+            .entry(key)
+            .or_insert_with(|| InstanceBuffer::new(device.inner(), "Decal"))
+            .update(device.inner(), queue.inner(), raw.as_slice());
+    });
+
+    previous.into_iter().for_each(|to_remove| {
+        renderer.instances.remove(&to_remove);
+    });
+}
+
+fn sys_prep_decal_views(
+    device: Res<Device>,
+    queue: Res<Queue>,
+    tools: Res<RenderEncoder>,
+    mut renderer: ResMut<DecalRenderer>,
+
+    camera: Res<MainCamera>,
+    v_cameras: View<SceneCamera>,
+    v_layers: View<RenderLayers>,
+) {
+    let renderer = &mut *renderer;
+    renderer.views.clear();
+
+    renderer.view_offsets = camera::main_pass_cameras(&camera, &v_cameras, &v_layers)
+        .into_iter()
+        .map(|view| {
+            renderer.views.push(&DecalViewRaw {
+                inverse_view_projection: view.view_projection.inverse().to_cols_array(),
+                viewport: view.viewport.to_pixels(tools.scene_size()),
+            })
+        })
+        .collect();
+
+    renderer.views.upload(
+        device.inner(),
+        queue.inner(),
+        &renderer.view_bind_group_layout,
+    );
+}
+
+fn sys_render_decals(
+    mut tools: ResMut<RenderEncoder>,
+    renderer: Res<DecalRenderer>,
+    storage: Res<AssetStorage>,
+    stats: Res<RenderStats>,
+
+    camera: Res<MainCamera>,
+    v_cameras: View<SceneCamera>,
+    v_layers: View<RenderLayers>,
+) {
+    if renderer.instances.is_empty() {
+        return;
+    }
+
+    let size = tools.scene_size();
+    let mut pass = tools.begin_render_pass(RenderPassDesc::none());
+
+    camera::main_pass_cameras(&camera, &v_cameras, &v_layers)
+        .into_iter()
+        .enumerate()
+        .for_each(|(index, view)| {
+            view.viewport.apply(&mut pass, size);
+
+            let counts = renderer.render(&mut pass, view.bind_group, index, view.layers, &storage);
+            stats.record(counts);
+        });
+}
+
+//====================================================================
@@ -0,0 +1,94 @@
+//====================================================================
+
+use cabat_assets::{asset_storage::AssetStorage, handle::Handle};
+use cabat_shipyard::{Res, ResMut};
+use shipyard::{AllStoragesView, Unique};
+
+use crate::{
+    mesh::{Mesh, MeshData},
+    shared::BindGroupLayoutRegistry,
+    texture::{RawTexture, Texture},
+    Device, Queue,
+};
+
+//====================================================================
+
+/// Generated assets available without any files on disk.
+#[derive(Unique)]
+pub struct DefaultRendererAssets {
+    pub white_texture: Handle<Texture>,
+    pub black_texture: Handle<Texture>,
+    /// Flat tangent space normal map.
+    pub normal_texture: Handle<Texture>,
+
+    pub quad: Handle<Mesh>,
+    pub plane: Handle<Mesh>,
+    pub cube: Handle<Mesh>,
+    pub sphere: Handle<Mesh>,
+}
+
+//====================================================================
+
+pub(crate) fn sys_setup_default_assets(
+    all_storages: AllStoragesView,
+    device: Res<Device>,
+    queue: Res<Queue>,
+    layouts: Res<BindGroupLayoutRegistry>,
+    mut storage: ResMut<AssetStorage>,
+) {
+    let device = device.inner();
+    let queue = queue.inner();
+
+    let mut texture = |color: [u8; 3], format: wgpu::TextureFormat, label: &str| {
+        let mut rgb = image::RgbImage::new(1, 1);
+        rgb.put_pixel(0, 0, image::Rgb(color));
+
+        let raw = RawTexture::from_image_with_format(
+            device,
+            queue,
+            &image::DynamicImage::from(rgb),
+            format,
+            Some(label),
+            None,
+        );
+
+        storage.insert_asset(layouts.load_texture(device, raw, Some(label)))
+    };
+
+    let white_texture = texture(
+        [255, 255, 255],
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+        "Default White Texture",
+    );
+    let black_texture = texture(
+        [0, 0, 0],
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+        "Default Black Texture",
+    );
+    let normal_texture = texture(
+        [128, 128, 255],
+        wgpu::TextureFormat::Rgba8Unorm,
+        "Default Normal Texture",
+    );
+
+    let mut mesh =
+        |data: MeshData, label: &str| storage.insert_asset(Mesh::new(device, &data, label));
+
+    let quad = mesh(MeshData::quad(), "Default Quad");
+    let plane = mesh(MeshData::plane(1., 1), "Default Plane");
+    let cube = mesh(MeshData::cube(), "Default Cube");
+    let sphere = mesh(MeshData::uv_sphere(0.5, 32, 16), "Default Sphere");
+
+    all_storages.add_unique(DefaultRendererAssets {
+        white_texture,
+        black_texture,
+        normal_texture,
+
+        quad,
+        plane,
+        cube,
+        sphere,
+    });
+}
+
+//====================================================================
@@ -0,0 +1,165 @@
+//====================================================================
+
+use cabat_common::Color;
+use cabat_shipyard::{Res, UniqueTools};
+use shipyard::{AllStoragesView, Unique};
+
+use crate::{render_tools, shared::BindGroupLayoutRegistry, Device, Queue};
+
+//====================================================================
+
+/// `apply_fog(color: vec3<f32>, world_position: vec3<f32>) -> vec3<f32>`.
+/// Appended to the source of shaders declaring the `camera` and `environment`
+/// uniforms, along with the [TONEMAP_SHADER](crate::camera::TONEMAP_SHADER).
+pub const FOG_SHADER: &str = include_str!("../shaders/fog.wgsl");
+
+/// How fog thickens with distance from the camera.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FogMode {
+    /// Fades smoothly, never fully reaching the fog color.
+    Exponential { density: f32 },
+    /// Fully clear before `start` and fully fogged past `end`.
+    Linear { start: f32, end: f32 },
+}
+
+/// Scene wide lighting and fog, shared by the 3d shaders. Can be inserted before
+/// setup or modified at any point.
+#[derive(Unique, Debug, Clone)]
+pub struct EnvironmentSettings {
+    /// Tints unlit sprites and 3d text.
+    pub ambient_color: Color,
+    /// How much of the ambient color lights surfaces facing away from any light.
+    pub ambient_strength: f32,
+
+    pub fog_color: Color,
+    /// No fog when `None`.
+    pub fog: Option<FogMode>,
+}
+
+impl Default for EnvironmentSettings {
+    fn default() -> Self {
+        Self {
+            ambient_color: Color::WHITE,
+            ambient_strength: 0.3,
+
+            fog_color: Color::linear(0.2, 0.2, 0.2, 1.),
+            fog: None,
+        }
+    }
+}
+
+impl EnvironmentSettings {
+    fn to_raw(&self) -> EnvironmentUniformRaw {
+        let [r, g, b, _] = self.ambient_color.to_array();
+        let [fog_r, fog_g, fog_b, _] = self.fog_color.to_array();
+
+        let (mode, fog_params) = match self.fog {
+            None => (0, [0.; 3]),
+            Some(FogMode::Exponential { density }) => (1, [density, 0., 0.]),
+            Some(FogMode::Linear { start, end }) => (2, [0., start, end.max(start + 0.0001)]),
+        };
+
+        EnvironmentUniformRaw {
+            ambient: [r, g, b, self.ambient_strength],
+            fog_color: [fog_r, fog_g, fog_b],
+            fog_mode: mode,
+            fog_params,
+            _padding: 0.,
+        }
+    }
+}
+
+//--------------------------------------------------
+
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+struct EnvironmentUniformRaw {
+    // Color in rgb, strength in a
+    ambient: [f32; 4],
+    fog_color: [f32; 3],
+    // 0 = none, 1 = exponential, 2 = linear
+    fog_mode: u32,
+    // Density, start, end
+    fog_params: [f32; 3],
+    _padding: f32,
+}
+
+//====================================================================
+
+/// Gpu side of the [EnvironmentSettings], bound by pipelines that use them.
+#[derive(Unique)]
+pub struct Environment {
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Environment {
+    pub fn new(
+        device: &wgpu::Device,
+        layouts: &BindGroupLayoutRegistry,
+        settings: &EnvironmentSettings,
+    ) -> Self {
+        let uniform_buffer = render_tools::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Environment Uniform Buffer"),
+                contents: bytemuck::bytes_of(&settings.to_raw()),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        );
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Environment Bind Group"),
+            layout: layouts.environment(),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    #[inline]
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    fn update(&self, queue: &wgpu::Queue, settings: &EnvironmentSettings) {
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::bytes_of(&settings.to_raw()),
+        );
+    }
+}
+
+//====================================================================
+
+pub(crate) fn sys_setup_environment(
+    all_storages: AllStoragesView,
+    device: Res<Device>,
+    layouts: Res<BindGroupLayoutRegistry>,
+) {
+    let environment = {
+        let settings = all_storages.get_or_insert(EnvironmentSettings::default);
+        Environment::new(device.inner(), &layouts, &settings)
+    };
+
+    all_storages.add_unique(environment);
+}
+
+pub(crate) fn sys_sync_environment(
+    queue: Res<Queue>,
+    settings: Res<EnvironmentSettings>,
+    environment: Res<Environment>,
+) {
+    if settings.is_modified() {
+        environment.update(queue.inner(), &settings);
+    }
+}
+
+//====================================================================
@@ -0,0 +1,460 @@
+//====================================================================
+
+use cabat_common::Color;
+use cabat_shipyard::{prelude::*, UniqueTools};
+use cabat_spatial::{Bounds, Transform};
+use shipyard::{AllStoragesView, IntoIter, IntoWorkload, Unique, View};
+
+use crate::{
+    camera::{self, MainCamera, SceneCamera},
+    lights::PointLight,
+    render_graph::{resources, AddRenderPass, RenderGraphNode},
+    render_tools::{self, InstanceBuffer},
+    settings::SurfaceFormatChangedEvent,
+    shared::BindGroupLayoutRegistry,
+    terrain::{Terrain, TerrainLighting},
+    visibility::RenderLayers,
+    Device, DrawCounts, Queue, RenderEncoder, RenderPassDesc, RenderStats, SurfaceConfig, Vertex,
+};
+
+//====================================================================
+
+/// Debug lines drawn on top of the scene. Anything can be drawn through the
+/// [Gizmos] unique, and [GizmoSettings] turns on the built in visualizers for
+/// camera frustums, [Bounds] and lights.
+pub struct GizmoPlugin;
+
+impl Plugin for GizmoPlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        builder.get_or_insert(GizmoSettings::default);
+
+        builder
+            .insert_default::<Gizmos>()
+            .add_workload_pre(Stages::Setup, sys_setup_gizmo_renderer)
+            .add_workload_post(
+                Stages::Update,
+                (
+                    sys_draw_camera_gizmos,
+                    sys_draw_bounds_gizmos,
+                    sys_draw_light_gizmos,
+                ),
+            )
+            .add_workload_last(Stages::Update, sys_prep_gizmos)
+            .add_render_pass(
+                RenderGraphNode::new("gizmos").writes(resources::SCENE),
+                sys_render_gizmos,
+            )
+            .add_event::<SurfaceFormatChangedEvent>(sys_setup_gizmo_renderer.into_workload());
+    }
+}
+
+//====================================================================
+
+/// Insert before adding the [GizmoPlugin] to configure it. Gizmos are only
+/// drawn in debug builds unless enabled.
+#[derive(Unique, Debug, Clone)]
+pub struct GizmoSettings {
+    pub enabled: bool,
+
+    /// Outline the frustum of every [SceneCamera], and of the [MainCamera] when
+    /// scene cameras are replacing it.
+    pub camera_frustums: bool,
+    pub frustum_color: Color,
+    /// Frustums are cut off at this distance from the near plane.
+    pub max_frustum_depth: f32,
+
+    /// Outline the [Bounds] of every entity with a [Transform].
+    pub bounds: bool,
+    pub bounds_color: Color,
+
+    /// Draw the range of every [PointLight] in its color, and an arrow along
+    /// the [TerrainLighting::sun_direction] above every [Terrain].
+    pub lights: bool,
+    pub sun_color: Color,
+}
+
+impl Default for GizmoSettings {
+    fn default() -> Self {
+        Self {
+            enabled: cfg!(debug_assertions),
+
+            camera_frustums: true,
+            frustum_color: Color::linear(1., 1., 0., 1.),
+            max_frustum_depth: 50.,
+
+            bounds: false,
+            bounds_color: Color::linear(0., 1., 0.4, 1.),
+
+            lights: false,
+            sun_color: Color::linear(1., 0.8, 0.2, 1.),
+        }
+    }
+}
+
+//--------------------------------------------------
+
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Debug)]
+pub struct GizmoVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+impl Vertex for GizmoVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![
+                0 => Float32x3, 1 => Float32x4
+        ];
+
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<GizmoVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &VERTEX_ATTRIBUTES,
+        }
+    }
+}
+
+//--------------------------------------------------
+
+// Line segments making up a circle
+const CIRCLE_SEGMENTS: usize = 32;
+
+// Corner pairs making up the edges of a box, with corners ordered by their bits
+// as x, y, z from least significant
+const BOX_EDGES: [(usize, usize); 12] = [
+    (0, 1),
+    (2, 3),
+    (4, 5),
+    (6, 7),
+    (0, 2),
+    (1, 3),
+    (4, 6),
+    (5, 7),
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+];
+
+/// Lines to draw this frame, in world space. Cleared once they've been
+/// uploaded at the end of [Stages::Update].
+#[derive(Unique, Debug, Default)]
+pub struct Gizmos {
+    vertices: Vec<GizmoVertex>,
+}
+
+impl Gizmos {
+    #[inline]
+    pub fn line(&mut self, start: glam::Vec3, end: glam::Vec3, color: Color) {
+        let color = color.into();
+
+        self.vertices.extend([
+            GizmoVertex {
+                position: start.into(),
+                color,
+            },
+            GizmoVertex {
+                position: end.into(),
+                color,
+            },
+        ]);
+    }
+
+    /// Box from its eight corners, ordered by x, then y, then z, each from low to high.
+    pub fn cuboid(&mut self, corners: [glam::Vec3; 8], color: Color) {
+        BOX_EDGES
+            .iter()
+            .for_each(|(a, b)| self.line(corners[*a], corners[*b], color));
+    }
+
+    #[inline]
+    pub fn aabb(&mut self, min: glam::Vec3, max: glam::Vec3, color: Color) {
+        self.cuboid(
+            box_corners(|x, y, z| glam::Vec3::new(x, y, z), min, max),
+            color,
+        );
+    }
+
+    /// Line with a head at its end.
+    pub fn arrow(&mut self, start: glam::Vec3, end: glam::Vec3, color: Color) {
+        self.line(start, end, color);
+
+        let direction = end - start;
+        let length = direction.length();
+        if length <= f32::EPSILON {
+            return;
+        }
+
+        let direction = direction / length;
+        let (side, up) = direction.any_orthonormal_pair();
+        let head = length * 0.2;
+        let base = end - direction * head;
+
+        [side, -side, up, -up]
+            .into_iter()
+            .for_each(|offset| self.line(end, base + offset * head * 0.5, color));
+    }
+
+    /// Circle facing along `normal`.
+    pub fn circle(&mut self, center: glam::Vec3, normal: glam::Vec3, radius: f32, color: Color) {
+        let (x, y) = normal.normalize_or(glam::Vec3::Y).any_orthonormal_pair();
+
+        let point = |index: usize| {
+            let angle = index as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+            center + (x * angle.cos() + y * angle.sin()) * radius
+        };
+
+        (0..CIRCLE_SEGMENTS).for_each(|index| self.line(point(index), point(index + 1), color));
+    }
+
+    /// Circles around each axis.
+    #[inline]
+    pub fn sphere(&mut self, center: glam::Vec3, radius: f32, color: Color) {
+        [glam::Vec3::X, glam::Vec3::Y, glam::Vec3::Z]
+            .into_iter()
+            .for_each(|normal| self.circle(center, normal, radius, color));
+    }
+
+    /// Outline of a camera's view, with far corners pulled in to `max_depth`
+    /// from the near plane.
+    pub fn frustum(&mut self, view_projection: glam::Mat4, max_depth: f32, color: Color) {
+        let inverse = view_projection.inverse();
+
+        // Camera clip space has a 0 to 1 depth range
+        let mut corners = box_corners(
+            |x, y, z| inverse.project_point3(glam::Vec3::new(x, y, z)),
+            glam::Vec3::new(-1., -1., 0.),
+            glam::Vec3::ONE,
+        );
+
+        (0..4).for_each(|index| {
+            let near = corners[index];
+            let far = &mut corners[index + 4];
+
+            if near.distance(*far) > max_depth {
+                *far = near + (*far - near).normalize() * max_depth;
+            }
+        });
+
+        self.cuboid(corners, color);
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+}
+
+#[inline]
+fn box_corners(
+    corner: impl Fn(f32, f32, f32) -> glam::Vec3,
+    min: glam::Vec3,
+    max: glam::Vec3,
+) -> [glam::Vec3; 8] {
+    std::array::from_fn(|index| {
+        let pick = |bit: usize, min: f32, max: f32| match index & bit != 0 {
+            true => max,
+            false => min,
+        };
+
+        corner(
+            pick(1, min.x, max.x),
+            pick(2, min.y, max.y),
+            pick(4, min.z, max.z),
+        )
+    })
+}
+
+//====================================================================
+
+#[derive(Unique)]
+pub struct GizmoRenderer {
+    pipeline: wgpu::RenderPipeline,
+    vertices: InstanceBuffer<GizmoVertex>,
+}
+
+impl GizmoRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        layouts: &BindGroupLayoutRegistry,
+    ) -> Self {
+        let pipeline = render_tools::create_pipeline(
+            device,
+            config,
+            "Gizmo Pipeline",
+            &[layouts.camera()],
+            &[GizmoVertex::desc()],
+            include_str!("../shaders/gizmo.wgsl"),
+            render_tools::RenderPipelineDescriptor {
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::LineList,
+                    ..Default::default()
+                },
+                fragment_targets: Some(&[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::all(),
+                })]),
+                ..Default::default()
+            },
+        );
+
+        Self {
+            pipeline,
+            vertices: InstanceBuffer::new(device, "Gizmo"),
+        }
+    }
+}
+
+//====================================================================
+
+fn sys_setup_gizmo_renderer(
+    all_storages: AllStoragesView,
+    device: Res<Device>,
+    config: Res<SurfaceConfig>,
+    layouts: Res<BindGroupLayoutRegistry>,
+) {
+    let renderer = GizmoRenderer::new(device.inner(), config.inner(), &layouts);
+    all_storages.add_unique(renderer);
+}
+
+fn sys_draw_camera_gizmos(
+    settings: Res<GizmoSettings>,
+    main_camera: Res<MainCamera>,
+    mut gizmos: ResMut<Gizmos>,
+    v_cameras: View<SceneCamera>,
+) {
+    if !settings.enabled || !settings.camera_frustums {
+        return;
+    }
+
+    let mut draw = |view_projection| {
+        gizmos.frustum(
+            view_projection,
+            settings.max_frustum_depth,
+            settings.frustum_color,
+        )
+    };
+
+    // The main camera is only drawn through when there are no active scene cameras
+    if v_cameras.iter().any(|camera| camera.active) {
+        draw(main_camera.0.view_projection());
+    }
+
+    v_cameras
+        .iter()
+        .for_each(|camera| draw(camera.camera().view_projection()));
+}
+
+fn sys_draw_bounds_gizmos(
+    settings: Res<GizmoSettings>,
+    mut gizmos: ResMut<Gizmos>,
+    v_bounds: View<Bounds>,
+    v_transform: View<Transform>,
+) {
+    if !settings.enabled || !settings.bounds {
+        return;
+    }
+
+    (&v_bounds, &v_transform)
+        .iter()
+        .for_each(|(bounds, transform)| {
+            let corners = box_corners(
+                |x, y, z| {
+                    transform.translation
+                        + transform.rotation * (glam::Vec3::new(x, y, z) * transform.scale)
+                },
+                bounds.offset - bounds.half_extents,
+                bounds.offset + bounds.half_extents,
+            );
+
+            gizmos.cuboid(corners, settings.bounds_color);
+        });
+}
+
+fn sys_draw_light_gizmos(
+    settings: Res<GizmoSettings>,
+    lighting: Option<Res<TerrainLighting>>,
+    mut gizmos: ResMut<Gizmos>,
+    v_light: View<PointLight>,
+    v_terrain: View<Terrain>,
+    v_transform: View<Transform>,
+) {
+    if !settings.enabled || !settings.lights {
+        return;
+    }
+
+    (&v_light, &v_transform)
+        .iter()
+        .filter(|(light, _)| light.range > 0.)
+        .for_each(|(light, transform)| {
+            gizmos.sphere(transform.translation, light.range, light.color);
+        });
+
+    // Directional light has no position, so is drawn coming down onto each terrain
+    let sun_direction = match lighting {
+        Some(lighting) => lighting.sun_direction.normalize_or_zero(),
+        None => return,
+    };
+
+    (&v_terrain, &v_transform)
+        .iter()
+        .for_each(|(terrain, transform)| {
+            let size = terrain.size() * transform.scale;
+            let end = transform.translation + glam::Vec3::Y * size.y;
+            let length = size.x.max(size.z) * 0.25;
+
+            gizmos.arrow(end - sun_direction * length, end, settings.sun_color);
+        });
+}
+
+fn sys_prep_gizmos(
+    device: Res<Device>,
+    queue: Res<Queue>,
+    settings: Res<GizmoSettings>,
+    mut renderer: ResMut<GizmoRenderer>,
+    mut gizmos: ResMut<Gizmos>,
+) {
+    match settings.enabled {
+        true => renderer
+            .vertices
+            .update(device.inner(), queue.inner(), &gizmos.vertices),
+        false => renderer.vertices.clear(),
+    }
+
+    gizmos.clear();
+}
+
+fn sys_render_gizmos(
+    mut tools: ResMut<RenderEncoder>,
+    renderer: Res<GizmoRenderer>,
+    stats: Res<RenderStats>,
+
+    camera: Res<MainCamera>,
+    v_cameras: View<SceneCamera>,
+    v_layers: View<RenderLayers>,
+) {
+    if renderer.vertices.is_empty() {
+        return;
+    }
+
+    let size = tools.scene_size();
+    let mut pass = tools.begin_render_pass(RenderPassDesc::none());
+
+    pass.set_pipeline(&renderer.pipeline);
+    pass.set_vertex_buffer(0, renderer.vertices.buffer().slice(..));
+    stats.record(DrawCounts::pipeline());
+
+    camera::main_pass_cameras(&camera, &v_cameras, &v_layers)
+        .into_iter()
+        .for_each(|view| {
+            view.viewport.apply(&mut pass, size);
+
+            pass.set_bind_group(0, view.bind_group, &[]);
+            pass.draw(0..renderer.vertices.count(), 0..1);
+            stats.record(DrawCounts::default().draw(1));
+        });
+}
+
+//====================================================================
@@ -0,0 +1,412 @@
+//====================================================================
+
+use std::marker::PhantomData;
+
+use crate::{camera::Frustum, render_tools, settings::GpuInfo};
+
+//====================================================================
+
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+struct CullViewRaw {
+    planes: [[f32; 4]; 6],
+}
+
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+struct CullParamsRaw {
+    count: u32,
+    capacity: u32,
+    _padding: [u32; 2],
+}
+
+// Matches wgpu's indexed indirect layout, instance count is filled in by the compute pass
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+struct DrawIndexedArgsRaw {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+const DRAW_ARGS_SIZE: wgpu::BufferAddress = std::mem::size_of::<DrawIndexedArgsRaw>() as u64;
+
+//====================================================================
+
+/// Compute pipeline testing instances against every camera's frustum and
+/// compacting the survivors into one instance range per camera, along with the
+/// indirect draw arguments to draw them.
+///
+/// The shader decides the instance layout, so each instance type has its own
+/// culler. Requires compute shader and indirect draw support, see
+/// [IndirectCuller::is_supported].
+pub struct IndirectCuller {
+    pipeline: wgpu::ComputePipeline,
+    batch_layout: wgpu::BindGroupLayout,
+
+    views_layout: wgpu::BindGroupLayout,
+    views_buffer: wgpu::Buffer,
+    views_bind_group: wgpu::BindGroup,
+    views_capacity: u32,
+    views: u32,
+}
+
+impl IndirectCuller {
+    const WORKGROUP_SIZE: u32 = 64;
+
+    /// Whether the device can run the culling pass and draw its output.
+    pub fn is_supported(info: &GpuInfo) -> bool {
+        info.downlevel.flags.contains(
+            wgpu::DownlevelFlags::COMPUTE_SHADERS | wgpu::DownlevelFlags::INDIRECT_EXECUTION,
+        )
+    }
+
+    pub fn new(device: &wgpu::Device, label: &str, shader_module_data: &str) -> Self {
+        let views_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(&format!("{} Views Bind Group Layout", label)),
+            entries: &[render_tools::bgl_storage_entry(
+                0,
+                wgpu::ShaderStages::COMPUTE,
+                true,
+            )],
+        });
+
+        let batch_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(&format!("{} Batch Bind Group Layout", label)),
+            entries: &[
+                render_tools::bgl_uniform_entry(0, wgpu::ShaderStages::COMPUTE),
+                render_tools::bgl_storage_entry(1, wgpu::ShaderStages::COMPUTE, true),
+                render_tools::bgl_storage_entry(2, wgpu::ShaderStages::COMPUTE, false),
+                render_tools::bgl_storage_entry(3, wgpu::ShaderStages::COMPUTE, false),
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&format!("{} Pipeline Layout", label)),
+            bind_group_layouts: &[&views_layout, &batch_layout],
+            push_constant_ranges: &[],
+        });
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&format!("{} Shader Module", label)),
+            source: wgpu::ShaderSource::Wgsl(shader_module_data.into()),
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(&format!("{} Pipeline", label)),
+            layout: Some(&layout),
+            module: &module,
+            entry_point: "cs_main",
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        let (views_buffer, views_bind_group) = Self::create_views(device, &views_layout, 4);
+
+        Self {
+            pipeline,
+            batch_layout,
+
+            views_layout,
+            views_buffer,
+            views_bind_group,
+            views_capacity: 4,
+            views: 0,
+        }
+    }
+
+    fn create_views(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        capacity: u32,
+    ) -> (wgpu::Buffer, wgpu::BindGroup) {
+        let buffer = render_tools::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("Indirect Cull Views Buffer"),
+                size: capacity as u64 * std::mem::size_of::<CullViewRaw>() as u64,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Indirect Cull Views Bind Group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        (buffer, bind_group)
+    }
+
+    /// Number of cameras instances are culled for this frame.
+    #[inline]
+    pub fn views(&self) -> u32 {
+        self.views
+    }
+
+    /// Upload the frustums to cull against. Instances are compacted separately
+    /// for each frustum, in the given order.
+    pub fn update_views(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frustums: &[Frustum],
+    ) {
+        self.views = frustums.len() as u32;
+
+        if self.views > self.views_capacity {
+            let capacity = self.views.next_power_of_two();
+            (self.views_buffer, self.views_bind_group) =
+                Self::create_views(device, &self.views_layout, capacity);
+            self.views_capacity = capacity;
+        }
+
+        if frustums.is_empty() {
+            return;
+        }
+
+        let raw = frustums
+            .iter()
+            .map(|frustum| CullViewRaw {
+                planes: frustum.planes().map(|plane| plane.to_array()),
+            })
+            .collect::<Vec<_>>();
+
+        queue.write_buffer(&self.views_buffer, 0, bytemuck::cast_slice(&raw));
+    }
+
+    /// Record the culling of every batch. Batches must be prepared for the
+    /// current views beforehand.
+    pub fn dispatch<'a, T: bytemuck::Pod + 'a>(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        batches: impl IntoIterator<Item = &'a IndirectBatch<T>>,
+    ) {
+        if self.views == 0 {
+            return;
+        }
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Indirect Cull Pass"),
+            timestamp_writes: None,
+        });
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.views_bind_group, &[]);
+
+        batches
+            .into_iter()
+            .filter(|batch| batch.count > 0)
+            .for_each(|batch| {
+                let bind_group = match &batch.bind_group {
+                    Some(bind_group) => bind_group,
+                    None => return,
+                };
+
+                pass.set_bind_group(1, bind_group, &[]);
+                pass.dispatch_workgroups(batch.count.div_ceil(Self::WORKGROUP_SIZE), self.views, 1);
+            });
+    }
+}
+
+//====================================================================
+
+/// Instances uploaded to a storage buffer and culled on the gpu. After culling,
+/// each camera draws its own compacted range with [Self::draw].
+pub struct IndirectBatch<T: bytemuck::Pod> {
+    label: String,
+    source: wgpu::Buffer,
+    params: wgpu::Buffer,
+    count: u32,
+    capacity: u32,
+
+    // Sized for `capacity` instances per view
+    output: Option<(wgpu::Buffer, wgpu::Buffer)>,
+    output_views: u32,
+    bind_group: Option<wgpu::BindGroup>,
+
+    phantom: PhantomData<T>,
+}
+
+impl<T: bytemuck::Pod> IndirectBatch<T> {
+    const MIN_CAPACITY: u32 = 64;
+    const STRIDE: wgpu::BufferAddress = std::mem::size_of::<T>() as u64;
+
+    pub fn new(device: &wgpu::Device, label: &str) -> Self {
+        let params = render_tools::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some(&format!("{} Indirect Params Buffer", label)),
+                size: std::mem::size_of::<CullParamsRaw>() as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+
+        Self {
+            label: label.to_string(),
+            source: Self::create_source(device, label, Self::MIN_CAPACITY),
+            params,
+            count: 0,
+            capacity: Self::MIN_CAPACITY,
+
+            output: None,
+            output_views: 0,
+            bind_group: None,
+
+            phantom: PhantomData,
+        }
+    }
+
+    fn create_source(device: &wgpu::Device, label: &str, capacity: u32) -> wgpu::Buffer {
+        render_tools::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some(&format!("{} Indirect Source Buffer", label)),
+                size: capacity as u64 * Self::STRIDE,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        )
+    }
+
+    #[inline]
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Replace the instances to be culled, growing if required.
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, data: &[T]) {
+        self.count = data.len() as u32;
+
+        if self.count > self.capacity {
+            let capacity = self.count.next_power_of_two();
+
+            log::trace!(
+                "Growing '{}' indirect batch from {} to {}",
+                self.label,
+                self.capacity,
+                capacity
+            );
+
+            self.source = Self::create_source(device, &self.label, capacity);
+            self.capacity = capacity;
+            self.output = None;
+            self.bind_group = None;
+        }
+
+        if !data.is_empty() {
+            queue.write_buffer(&self.source, 0, bytemuck::cast_slice(data));
+        }
+
+        let params = CullParamsRaw {
+            count: self.count,
+            capacity: self.capacity,
+            _padding: [0; 2],
+        };
+        queue.write_buffer(&self.params, 0, bytemuck::bytes_of(&params));
+    }
+
+    /// Size the output for the culler's views and reset the draw arguments.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        culler: &IndirectCuller,
+        index_count: u32,
+    ) {
+        let views = culler.views().max(1);
+
+        if self.output.is_none() || views > self.output_views {
+            let instances = render_tools::create_buffer(
+                device,
+                &wgpu::BufferDescriptor {
+                    label: Some(&format!("{} Indirect Instance Buffer", self.label)),
+                    size: self.capacity as u64 * views as u64 * Self::STRIDE,
+                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
+                    mapped_at_creation: false,
+                },
+            );
+
+            let args = render_tools::create_buffer(
+                device,
+                &wgpu::BufferDescriptor {
+                    label: Some(&format!("{} Indirect Args Buffer", self.label)),
+                    size: views as u64 * DRAW_ARGS_SIZE,
+                    usage: wgpu::BufferUsages::STORAGE
+                        | wgpu::BufferUsages::INDIRECT
+                        | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                },
+            );
+
+            self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(&format!("{} Indirect Bind Group", self.label)),
+                layout: &culler.batch_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: self.params.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: self.source.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: instances.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: args.as_entire_binding(),
+                    },
+                ],
+            }));
+
+            self.output = Some((instances, args));
+            self.output_views = views;
+        }
+
+        let (_, args) = self.output.as_ref().unwrap();
+
+        let reset = (0..views)
+            .map(|_| DrawIndexedArgsRaw {
+                index_count,
+                instance_count: 0,
+                first_index: 0,
+                base_vertex: 0,
+                first_instance: 0,
+            })
+            .collect::<Vec<_>>();
+
+        queue.write_buffer(args, 0, bytemuck::cast_slice(&reset));
+    }
+
+    /// Draw the instances that survived culling for the given view. The caller
+    /// binds everything but the instance buffer.
+    pub fn draw(&self, pass: &mut wgpu::RenderPass, slot: u32, view: u32) {
+        let (instances, args) = match &self.output {
+            Some(output) if view < self.output_views && self.count > 0 => output,
+            _ => return,
+        };
+
+        let offset = view as u64 * self.capacity as u64 * Self::STRIDE;
+        let size = self.capacity as u64 * Self::STRIDE;
+
+        pass.set_vertex_buffer(slot, instances.slice(offset..offset + size));
+        pass.draw_indexed_indirect(args, view as u64 * DRAW_ARGS_SIZE);
+    }
+}
+
+//====================================================================
@@ -0,0 +1,237 @@
+//====================================================================
+
+use cabat_common::{Color, Size};
+use cabat_shipyard::{prelude::*, UniqueTools};
+use shipyard::{AllStoragesView, IntoWorkload, SystemModificator, Unique};
+
+use crate::{
+    camera::{MainCamera, Viewport},
+    render_graph::{resources, AddRenderPass, RenderGraphNode},
+    render_tools::{self, InstanceBuffer},
+    settings::SurfaceFormatChangedEvent,
+    Device, DrawCounts, Queue, RenderEncoder, RenderPassDesc, RenderStats, SurfaceConfig, Vertex,
+};
+
+//====================================================================
+
+/// Keeps the main pass at a fixed aspect ratio, centered in the surface with
+/// bars filling the rest. [SceneCamera](crate::camera::SceneCamera) viewports
+/// are placed inside the letterboxed area. Add after the
+/// [CoreRendererPlugin](crate::CoreRendererPlugin).
+pub struct LetterboxPlugin;
+
+impl Plugin for LetterboxPlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        builder.get_or_insert(FixedAspect::default);
+
+        builder
+            .add_workload_pre(Stages::Setup, sys_setup_letterbox_renderer)
+            .add_workload_pre(
+                Stages::Render,
+                sys_update_letterbox.skip_if_missing_unique::<RenderEncoder>(),
+            )
+            .add_render_pass(
+                RenderGraphNode::new("letterbox").writes(resources::SCENE),
+                sys_render_letterbox,
+            )
+            .add_event::<SurfaceFormatChangedEvent>(sys_setup_letterbox_renderer.into_workload());
+    }
+}
+
+//====================================================================
+
+/// Insert before adding the [LetterboxPlugin] to configure it. Can be changed
+/// at any time.
+#[derive(Unique, Debug, Clone)]
+pub struct FixedAspect {
+    /// Width divided by height. Cameras should use the same aspect ratio.
+    /// Letterboxing is turned off when `None`.
+    pub aspect: Option<f32>,
+    pub bar_color: Color,
+}
+
+impl Default for FixedAspect {
+    fn default() -> Self {
+        Self {
+            aspect: Some(16. / 9.),
+            bar_color: Color::BLACK,
+        }
+    }
+}
+
+impl FixedAspect {
+    #[inline]
+    pub fn new(aspect: f32) -> Self {
+        Self {
+            aspect: Some(aspect),
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn with_bar_color(mut self, bar_color: Color) -> Self {
+        self.bar_color = bar_color;
+        self
+    }
+
+    /// Largest area of a surface with the aspect ratio, snapped to whole pixels.
+    pub fn viewport(&self, size: Size<u32>) -> Viewport {
+        let aspect = match self.aspect {
+            Some(aspect) if aspect > 0. && size.width > 0 && size.height > 0 => aspect,
+            _ => return Viewport::FULL,
+        };
+
+        let width = size.width as f32;
+        let height = size.height as f32;
+
+        let (inner_width, inner_height) = match width / height > aspect {
+            // Pillarbox
+            true => ((height * aspect).round(), height),
+            // Letterbox
+            false => (width, (width / aspect).round()),
+        };
+
+        let x = ((width - inner_width) / 2.).floor();
+        let y = ((height - inner_height) / 2.).floor();
+
+        Viewport::new(
+            x / width,
+            y / height,
+            inner_width / width,
+            inner_height / height,
+        )
+    }
+}
+
+//====================================================================
+
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Debug)]
+pub struct LetterboxVertex {
+    position: [f32; 2],
+    color: [f32; 4],
+}
+
+impl Vertex for LetterboxVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![
+                0 => Float32x2, 1 => Float32x4
+        ];
+
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<LetterboxVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &VERTEX_ATTRIBUTES,
+        }
+    }
+}
+
+#[derive(Unique)]
+pub struct LetterboxRenderer {
+    pipeline: wgpu::RenderPipeline,
+    bars: InstanceBuffer<LetterboxVertex>,
+}
+
+impl LetterboxRenderer {
+    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+        let pipeline = render_tools::create_pipeline(
+            device,
+            config,
+            "Letterbox Pipeline",
+            &[],
+            &[LetterboxVertex::desc()],
+            include_str!("../shaders/letterbox.wgsl"),
+            render_tools::RenderPipelineDescriptor::default(),
+        );
+
+        Self {
+            pipeline,
+            bars: InstanceBuffer::new(device, "Letterbox"),
+        }
+    }
+}
+
+// Triangles covering the parts of the surface outside of the viewport, in clip space
+fn bar_vertices(viewport: Viewport, color: Color) -> Vec<LetterboxVertex> {
+    let color = color.into();
+
+    let left = viewport.x;
+    let right = viewport.x + viewport.width;
+    let top = viewport.y;
+    let bottom = viewport.y + viewport.height;
+
+    [
+        (0., 0., left, 1.),
+        (right, 0., 1., 1.),
+        (left, 0., right, top),
+        (left, bottom, right, 1.),
+    ]
+    .into_iter()
+    .filter(|(x0, y0, x1, y1)| x1 > x0 && y1 > y0)
+    .flat_map(|(x0, y0, x1, y1)| {
+        // Normalized coordinates start at the top left
+        let corner = |x: f32, y: f32| LetterboxVertex {
+            position: [x * 2. - 1., 1. - y * 2.],
+            color,
+        };
+
+        [
+            corner(x0, y0),
+            corner(x0, y1),
+            corner(x1, y1),
+            corner(x0, y0),
+            corner(x1, y1),
+            corner(x1, y0),
+        ]
+    })
+    .collect()
+}
+
+//====================================================================
+
+fn sys_setup_letterbox_renderer(
+    all_storages: AllStoragesView,
+    device: Res<Device>,
+    config: Res<SurfaceConfig>,
+) {
+    let renderer = LetterboxRenderer::new(device.inner(), config.inner());
+    all_storages.add_unique(renderer);
+}
+
+fn sys_update_letterbox(
+    device: Res<Device>,
+    queue: Res<Queue>,
+    tools: Res<RenderEncoder>,
+    settings: Res<FixedAspect>,
+    mut camera: ResMut<MainCamera>,
+    mut renderer: ResMut<LetterboxRenderer>,
+) {
+    // The scene may be drawn at a fixed resolution rather than the surface's
+    let viewport = settings.viewport(tools.scene_size());
+    camera.set_viewport(viewport);
+
+    let vertices = bar_vertices(viewport, settings.bar_color);
+    renderer
+        .bars
+        .update(device.inner(), queue.inner(), &vertices);
+}
+
+fn sys_render_letterbox(
+    mut tools: ResMut<RenderEncoder>,
+    renderer: Res<LetterboxRenderer>,
+    stats: Res<RenderStats>,
+) {
+    if renderer.bars.is_empty() {
+        return;
+    }
+
+    let mut pass = tools.begin_render_pass(RenderPassDesc::none());
+
+    pass.set_pipeline(&renderer.pipeline);
+    pass.set_vertex_buffer(0, renderer.bars.buffer().slice(..));
+    pass.draw(0..renderer.bars.count(), 0..1);
+
+    stats.record(DrawCounts::pipeline().draw(1));
+}
+
+//====================================================================
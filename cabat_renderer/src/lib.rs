@@ -1,29 +1,66 @@
 //====================================================================
 
-use cabat_assets::RegisterAssetLoader;
-use cabat_common::{Size, WindowRaw, WindowResizeEvent, WindowSize};
-use cabat_shipyard::{prelude::*, UniqueTools};
+use std::{
+    ops::{Add, AddAssign},
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
+};
+
+use cabat_assets::{RegisterAssetLoader, RegisterConfigSection};
+use cabat_common::{Color, Size, WindowRaw, WindowResizeEvent, WindowSize};
+use cabat_shipyard::{prelude::*, trace_span, PluginGroupBuilder, UniqueTools, WrappedUnique};
 use loader::TextureLoader;
-use pollster::FutureExt;
-use shared::SharedPipelineResources;
+use render_graph::{resources, AddRenderPass, RenderGraphNode};
+use settings::{GpuInfo, RendererSettings, SurfaceFormatChangedEvent, SurfaceFormats};
+use shared::BindGroupLayoutRegistry;
 use shipyard::{AllStoragesView, IntoWorkload, SystemModificator, Unique, WorkloadModificator};
 use texture::DepthTexture;
 
+pub mod accessibility;
+pub mod atlas;
 pub mod camera;
+pub mod decal;
+pub mod default_assets;
+pub mod environment;
+pub mod gizmo;
+pub mod indirect;
+pub mod letterbox;
+pub mod lights;
 pub mod loader;
+pub mod mesh;
+pub mod nine_slice;
+pub mod occlusion;
+pub mod outline;
+pub mod pixel_perfect;
+pub mod procedural;
+pub mod reflection_probe;
+pub mod render_graph;
+pub mod render_target;
 pub mod render_tools;
+pub mod settings;
 pub mod shared;
+pub mod ssao;
+pub mod terrain;
 pub mod text;
 pub mod texture;
 pub mod texture3d_renderer;
+pub mod texture_array;
+#[cfg(feature = "video")]
+pub mod video;
+pub mod visibility;
 
 //====================================================================
 
 pub mod plugins {
     pub use crate::{
-        text::Text2dPlugin, text::Text3dPlugin, texture3d_renderer::Texture3dPlugin,
-        CoreRendererPlugin,
+        decal::DecalPlugin, gizmo::GizmoPlugin, letterbox::LetterboxPlugin,
+        nine_slice::NineSlicePlugin, occlusion::OcclusionCullingPlugin, outline::OutlinePlugin,
+        pixel_perfect::PixelPerfectPlugin, reflection_probe::ReflectionProbePlugin,
+        ssao::SsaoPlugin, terrain::TerrainPlugin, text::Text2dPlugin, text::Text3dPlugin,
+        texture3d_renderer::Texture3dPlugin, CoreRendererPlugin,
     };
+
+    #[cfg(feature = "video")]
+    pub use crate::video::VideoPlugin;
 }
 
 pub mod crates {
@@ -34,13 +71,15 @@ pub mod crates {
 
 pub struct FullRendererPlugin;
 
-impl Plugin for FullRendererPlugin {
-    fn build(self, builder: &WorkloadBuilder) {
-        builder
-            .add_plugin(CoreRendererPlugin)
-            .add_plugin(plugins::Texture3dPlugin)
-            .add_plugin(plugins::Text2dPlugin)
-            .add_plugin(plugins::Text3dPlugin);
+impl PluginGroup for FullRendererPlugin {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(CoreRendererPlugin)
+            .add(plugins::Texture3dPlugin)
+            // Panels are drawn underneath screen space text
+            .add(plugins::NineSlicePlugin)
+            .add(plugins::Text2dPlugin)
+            .add(plugins::Text3dPlugin)
     }
 }
 
@@ -50,33 +89,88 @@ pub struct CoreRendererPlugin;
 
 impl Plugin for CoreRendererPlugin {
     fn build(self, builder: &WorkloadBuilder) {
+        builder.get_or_insert(accessibility::AccessibilitySettings::default);
+
+        let setup_systems = (
+            sys_setup_renderer_components,
+            sys_setup_misc,
+            environment::sys_setup_environment,
+            lights::sys_setup_lights,
+            default_assets::sys_setup_default_assets,
+            texture::sys_setup_depth_texture,
+        );
+        let encoder_systems = (sys_reset_render_stats, sys_setup_encoder);
+
         builder
+            .describe_tag(Stages::Setup, "renderer_setup", &setup_systems)
+            .describe_tag(Stages::Render, "setup_encoder", &encoder_systems)
+            .describe_tag(Stages::Render, "submit_encoder", &sys_submit_encoder)
             .register_loader(TextureLoader)
+            .register_config_section::<RendererSettings>("renderer")
             .add_workload_first(
                 Stages::Setup,
-                (
-                    sys_setup_renderer_components,
-                    sys_setup_misc,
-                    texture::sys_setup_depth_texture,
-                )
+                setup_systems
                     .into_sequential_workload()
                     .tag("renderer_setup"),
             )
+            .add_workload(Stages::First, sys_apply_renderer_settings)
+            .register_config_section::<accessibility::AccessibilitySettings>("accessibility")
+            .insert_default::<accessibility::AppliedAccessibility>()
+            .add_workload(Stages::First, accessibility::sys_apply_accessibility)
+            .insert_default::<RenderStats>()
+            .insert_default::<render_tools::BufferPool>()
+            .transient_unique::<RenderEncoder>()
+            .transient_unique::<RenderPass>()
+            .add_workload_first(
+                Stages::Render,
+                encoder_systems
+                    .into_sequential_workload()
+                    .tag("setup_encoder"),
+            )
+            .add_workload_first(Stages::Render, environment::sys_sync_environment)
+            .add_render_pass(
+                RenderGraphNode::new("clear_render_targets").writes(resources::RENDER_TARGETS),
+                render_target::sys_clear_render_targets,
+            )
+            .add_workload_last(Stages::Update, lights::sys_prep_lights)
             .add_workload_pre(
                 Stages::Render,
-                (sys_setup_encoder, sys_setup_render_pass).into_sequential_workload(),
+                lights::sys_prep_light_views.skip_if_missing_unique::<RenderEncoder>(),
+            )
+            .add_render_pass(
+                RenderGraphNode::new("light_culling").writes(resources::LIGHTS),
+                lights::sys_cull_lights,
+            )
+            .add_render_pass(
+                RenderGraphNode::new("main_pass_begin")
+                    .reads(resources::RENDER_TARGETS)
+                    .reads(resources::LIGHTS)
+                    .writes(resources::MAIN_PASS)
+                    .without_debug_group(),
+                sys_setup_render_pass,
+            )
+            .add_render_pass(
+                RenderGraphNode::new("main_pass_end")
+                    .reads(resources::MAIN_PASS)
+                    .writes(resources::SURFACE)
+                    .writes(resources::SCENE)
+                    .without_debug_group(),
+                sys_finish_main_render_pass,
             )
-            .add_workload_post(Stages::Render, sys_finish_main_render_pass)
             .add_workload_last(
                 Stages::Render,
                 (sys_submit_encoder).into_workload().tag("submit_encoder"),
             )
+            .add_workload(Stages::Last, render_tools::sys_recycle_buffer_pool)
             .add_event::<WindowResizeEvent>(
                 (
                     sys_resize,
                     texture::sys_resize_depth_texture.skip_if_missing_unique::<DepthTexture>(),
                 )
                     .into_workload(),
+            )
+            .add_event::<SurfaceFormatChangedEvent>(
+                render_target::sys_recreate_render_targets.into_workload(),
             );
     }
 }
@@ -89,39 +183,21 @@ pub trait Vertex: bytemuck::Pod {
 
 //====================================================================
 
-#[derive(Unique)]
+#[derive(Unique, WrappedUnique)]
 pub struct Device(wgpu::Device);
-impl Device {
-    #[inline]
-    pub fn inner(&self) -> &wgpu::Device {
-        &self.0
-    }
-}
 
-#[derive(Unique)]
+#[derive(Unique, WrappedUnique)]
 pub struct Queue(wgpu::Queue);
-impl Queue {
-    #[inline]
-    pub fn inner(&self) -> &wgpu::Queue {
-        &self.0
-    }
-}
 
-#[derive(Unique)]
+#[derive(Unique, WrappedUnique)]
 pub struct Surface(wgpu::Surface<'static>);
-impl Surface {
-    #[inline]
-    pub fn inner(&self) -> &wgpu::Surface {
-        &self.0
-    }
-}
 
-#[derive(Unique)]
+#[derive(Unique, WrappedUnique)]
 pub struct SurfaceConfig(wgpu::SurfaceConfiguration);
 impl SurfaceConfig {
     #[inline]
-    pub fn inner(&self) -> &wgpu::SurfaceConfiguration {
-        &self.0
+    pub fn size(&self) -> Size<u32> {
+        Size::new(self.0.width, self.0.height)
     }
 
     pub fn resize(&mut self, size: Size<u32>) {
@@ -158,6 +234,14 @@ impl ClearColor {
     }
 }
 
+impl From<Color> for ClearColor {
+    #[inline]
+    fn from(value: Color) -> Self {
+        let [r, g, b, a] = value.into();
+        Self { r, g, b, a }
+    }
+}
+
 //====================================================================
 
 fn sys_setup_renderer_components(all_storages: AllStoragesView, window: Res<WindowRaw>) {
@@ -165,44 +249,39 @@ fn sys_setup_renderer_components(all_storages: AllStoragesView, window: Res<Wind
 
     let size = window.size();
 
-    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-        backends: wgpu::Backends::PRIMARY,
-        ..Default::default()
-    });
+    let gpu_settings = all_storages
+        .get_or_insert(settings::GpuSettings::default)
+        .clone();
+
+    let instance = settings::request_instance(&gpu_settings);
 
     let surface = instance.create_surface(window.arc().clone()).unwrap();
 
-    let adapter = instance
-        .request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::default(),
-            force_fallback_adapter: false,
-            compatible_surface: Some(&surface),
-        })
-        .block_on()
-        .unwrap();
+    let adapter = settings::request_adapter(&instance, &surface, &gpu_settings);
 
     log::debug!("Chosen device adapter: {:#?}", adapter.get_info());
 
-    let (device, queue) = adapter
-        .request_device(&wgpu::DeviceDescriptor::default(), None)
-        .block_on()
-        .unwrap();
+    let (device, queue, gpu_info) = settings::request_device(&adapter, &gpu_settings);
 
     let surface_capabilities = surface.get_capabilities(&adapter);
+    let surface_formats = SurfaceFormats(surface_capabilities.formats);
+
+    let (surface_format, present_mode) = {
+        let settings = all_storages.get_or_insert(RendererSettings::default);
+        (
+            settings.choose_format(&surface_formats),
+            settings.present_mode,
+        )
+    };
 
-    let surface_format = surface_capabilities
-        .formats
-        .iter()
-        .find(|format| format.is_srgb())
-        .copied()
-        .unwrap_or(surface_capabilities.formats[0]);
+    log::info!("Using surface format {:?}", surface_format);
 
     let config = wgpu::SurfaceConfiguration {
         usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
         format: surface_format,
         width: size.width,
         height: size.height,
-        present_mode: wgpu::PresentMode::AutoNoVsync,
+        present_mode,
         desired_maximum_frame_latency: 2,
         alpha_mode: surface_capabilities.alpha_modes[0],
         view_formats: vec![],
@@ -214,21 +293,53 @@ fn sys_setup_renderer_components(all_storages: AllStoragesView, window: Res<Wind
         .insert(Device(device))
         .insert(Queue(queue))
         .insert(Surface(surface))
-        .insert(SurfaceConfig(config));
+        .insert(SurfaceConfig(config))
+        .insert(surface_formats)
+        .insert(gpu_info);
 }
 
-fn sys_setup_misc(all_storages: AllStoragesView, device: Res<Device>) {
+fn sys_setup_misc(all_storages: AllStoragesView, device: Res<Device>, gpu_info: Res<GpuInfo>) {
+    let layouts = BindGroupLayoutRegistry::new(device.inner(), &gpu_info);
+    let main_camera = camera::MainCamera::new(
+        device.inner(),
+        &layouts,
+        &camera::PerspectiveCamera::default(),
+    );
+
     all_storages
-        .insert(SharedPipelineResources::new(device.inner()))
+        .insert(layouts)
         .insert(ClearColor::default())
-        .insert(camera::MainCamera(camera::Camera::new(
-            device.inner(),
-            &camera::PerspectiveCamera::default(),
-        )));
+        .insert(main_camera);
 }
 
 //====================================================================
 
+fn sys_apply_renderer_settings(
+    device: Res<Device>,
+    surface: Res<Surface>,
+    mut config: ResMut<SurfaceConfig>,
+    settings: Res<RendererSettings>,
+    surface_formats: Res<SurfaceFormats>,
+    mut event_handler: ResMut<EventHandler>,
+) {
+    let format = settings.choose_format(&surface_formats);
+
+    if format == config.0.format && settings.present_mode == config.0.present_mode {
+        return;
+    }
+
+    config.0.present_mode = settings.present_mode;
+
+    if format != config.0.format {
+        log::info!("Surface format changed to {:?}", format);
+
+        config.0.format = format;
+        event_handler.add_event(SurfaceFormatChangedEvent(format));
+    }
+
+    surface.inner().configure(device.inner(), config.inner());
+}
+
 fn sys_resize(
     device: Res<Device>,
     surface: Res<Surface>,
@@ -244,17 +355,25 @@ fn sys_resize(
 #[derive(Unique)]
 pub struct RenderPass {
     pass: wgpu::RenderPass<'static>,
+    size: Size<u32>,
 }
 
 impl RenderPass {
     pub fn pass(&mut self) -> &mut wgpu::RenderPass<'static> {
         &mut self.pass
     }
+
+    /// Size of the texture the main pass draws into.
+    #[inline]
+    pub fn size(&self) -> Size<u32> {
+        self.size
+    }
 }
 
 pub struct RenderPassDesc<'a> {
     pub use_depth: Option<&'a wgpu::TextureView>,
     pub clear_color: Option<[f64; 4]>,
+    pub occlusion_query_set: Option<&'a wgpu::QuerySet>,
 }
 
 impl RenderPassDesc<'_> {
@@ -262,6 +381,7 @@ impl RenderPassDesc<'_> {
         Self {
             use_depth: None,
             clear_color: None,
+            occlusion_query_set: None,
         }
     }
 }
@@ -271,6 +391,7 @@ impl Default for RenderPassDesc<'_> {
         Self {
             use_depth: None,
             clear_color: Some([0.2, 0.2, 0.2, 1.]),
+            occlusion_query_set: None,
         }
     }
 }
@@ -280,10 +401,24 @@ pub struct RenderEncoder {
     surface_texture: wgpu::SurfaceTexture,
     surface_view: wgpu::TextureView,
     encoder: wgpu::CommandEncoder,
+
+    // Drawn into in place of the surface until the scene is finished
+    scene_target: Option<(wgpu::TextureView, Size<u32>)>,
+    // Where each open debug group was pushed, innermost last
+    debug_groups: Vec<DebugGroupTarget>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DebugGroupTarget {
+    Encoder,
+    MainPass,
 }
 
 impl RenderEncoder {
     fn new(device: &wgpu::Device, surface: &wgpu::Surface) -> Result<Self, wgpu::SurfaceError> {
+        // Can block for a while waiting on vsync
+        trace_span!("acquire_surface");
+
         let (surface_texture, surface_view) = match surface.get_current_texture() {
             Ok(texture) => {
                 let view = texture
@@ -302,14 +437,89 @@ impl RenderEncoder {
             surface_texture,
             surface_view,
             encoder,
+            scene_target: None,
+            debug_groups: Vec::new(),
         })
     }
 
-    fn finish(self, queue: &wgpu::Queue) {
-        queue.submit(Some(self.encoder.finish()));
+    fn finish(mut self, queue: &wgpu::Queue) {
+        // Close any groups left open by a pass that opened the main pass
+        self.debug_groups
+            .iter()
+            .filter(|target| **target == DebugGroupTarget::Encoder)
+            .for_each(|_| self.encoder.pop_debug_group());
+
+        {
+            trace_span!("submit");
+            queue.submit(Some(self.encoder.finish()));
+        }
+
+        trace_span!("present");
         self.surface_texture.present();
     }
 
+    #[inline]
+    pub fn encoder(&mut self) -> &mut wgpu::CommandEncoder {
+        &mut self.encoder
+    }
+
+    /// Start a named group of commands, shown in graphics debuggers such as
+    /// RenderDoc. Recorded into the main pass while it is open.
+    pub fn push_debug_group(&mut self, pass: Option<&mut RenderPass>, label: &str) {
+        match pass {
+            Some(pass) => {
+                pass.pass.push_debug_group(label);
+                self.debug_groups.push(DebugGroupTarget::MainPass);
+            }
+            None => {
+                self.encoder.push_debug_group(label);
+                self.debug_groups.push(DebugGroupTarget::Encoder);
+            }
+        }
+    }
+
+    /// End the most recent debug group.
+    pub fn pop_debug_group(&mut self, pass: Option<&mut RenderPass>) {
+        match (self.debug_groups.last(), pass) {
+            (Some(DebugGroupTarget::MainPass), Some(pass)) => pass.pass.pop_debug_group(),
+            (Some(DebugGroupTarget::Encoder), None) => self.encoder.pop_debug_group(),
+
+            // The encoder can't be used while the main pass is open, so the group
+            // is left for the encoder to close when it finishes
+            (Some(DebugGroupTarget::Encoder), Some(_)) => return,
+            (Some(DebugGroupTarget::MainPass), None) => {
+                log::warn!("Main pass closed with a debug group still open");
+            }
+            (None, _) => return,
+        }
+
+        self.debug_groups.pop();
+    }
+
+    /// Size of the texture render passes currently draw into. Smaller than the
+    /// surface while the scene is drawn at a fixed resolution.
+    pub fn scene_size(&self) -> Size<u32> {
+        match &self.scene_target {
+            Some((_, size)) => *size,
+            None => {
+                let size = self.surface_texture.texture.size();
+                Size::new(size.width, size.height)
+            }
+        }
+    }
+
+    /// Draw render passes into another texture instead of the surface.
+    #[inline]
+    pub fn set_scene_target(&mut self, view: wgpu::TextureView, size: Size<u32>) {
+        self.scene_target = Some((view, size));
+    }
+
+    /// Go back to drawing into the surface, returning the previous scene target.
+    #[inline]
+    pub fn take_scene_target(&mut self) -> Option<(wgpu::TextureView, Size<u32>)> {
+        self.scene_target.take()
+    }
+
     pub fn begin_render_pass(&mut self, desc: RenderPassDesc) -> wgpu::RenderPass {
         // Clear the current depth buffer and use it.
         let depth_stencil_attachment = match desc.use_depth {
@@ -337,7 +547,10 @@ impl RenderEncoder {
         let render_pass = self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Render Tools Basic Render Pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &self.surface_view,
+                view: match &self.scene_target {
+                    Some((view, _)) => view,
+                    None => &self.surface_view,
+                },
                 resolve_target: None,
                 ops: wgpu::Operations {
                     load,
@@ -346,17 +559,166 @@ impl RenderEncoder {
             })],
             depth_stencil_attachment,
             timestamp_writes: None,
-            occlusion_query_set: None,
+            occlusion_query_set: desc.occlusion_query_set,
         });
 
         render_pass
     }
 }
 
-fn sys_setup_encoder(all_storages: AllStoragesView, device: Res<Device>, surface: Res<Surface>) {
+//--------------------------------------------------
+
+// Resources are created wherever a device is at hand, so allocations are
+// counted globally and collected into the RenderStats each frame
+static BUFFER_ALLOCATIONS: AtomicU32 = AtomicU32::new(0);
+static BUFFER_BYTES: AtomicU64 = AtomicU64::new(0);
+static TEXTURE_ALLOCATIONS: AtomicU32 = AtomicU32::new(0);
+static TEXTURE_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// Work done by the built in renderers, collected over each frame.
+#[derive(Unique, Default)]
+pub struct RenderStats {
+    draw_calls: AtomicU32,
+    instances: AtomicU32,
+    pipeline_switches: AtomicU32,
+    last_frame: RenderFrameStats,
+}
+
+impl RenderStats {
+    /// Renderers only get shared access during the render graph.
+    #[inline]
+    pub fn record(&self, counts: DrawCounts) {
+        self.draw_calls.fetch_add(counts.draws, Ordering::Relaxed);
+        self.instances
+            .fetch_add(counts.instances, Ordering::Relaxed);
+        self.pipeline_switches
+            .fetch_add(counts.pipeline_switches, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn record_buffer_allocation(size: wgpu::BufferAddress) {
+        BUFFER_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        BUFFER_BYTES.fetch_add(size, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn record_texture_allocation(size: u64) {
+        TEXTURE_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        TEXTURE_BYTES.fetch_add(size, Ordering::Relaxed);
+    }
+
+    /// Draw calls issued over the last full frame.
+    #[inline]
+    pub fn draw_calls(&self) -> u32 {
+        self.last_frame.draw_calls
+    }
+
+    /// Everything counted over the last full frame.
+    #[inline]
+    pub fn last_frame(&self) -> &RenderFrameStats {
+        &self.last_frame
+    }
+}
+
+//--------------------------------------------------
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderFrameStats {
+    pub draw_calls: u32,
+    pub instances: u32,
+    pub pipeline_switches: u32,
+    /// Gpu buffers created, including ones grown to fit more data.
+    pub buffer_allocations: u32,
+    pub buffer_bytes: u64,
+    pub texture_allocations: u32,
+    /// Estimated, see [texture::texture_memory].
+    pub texture_bytes: u64,
+}
+
+/// Draw calls, instances and pipeline switches made by a renderer. Added up
+/// while drawing and passed to [RenderStats::record].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DrawCounts {
+    pub draws: u32,
+    pub instances: u32,
+    pub pipeline_switches: u32,
+}
+
+impl DrawCounts {
+    #[inline]
+    pub fn pipeline() -> Self {
+        Self {
+            pipeline_switches: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Add a draw call of the given number of instances.
+    #[inline]
+    pub fn draw(mut self, instances: u32) -> Self {
+        self.draws += 1;
+        self.instances += instances;
+        self
+    }
+}
+
+impl Add for DrawCounts {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            draws: self.draws + rhs.draws,
+            instances: self.instances + rhs.instances,
+            pipeline_switches: self.pipeline_switches + rhs.pipeline_switches,
+        }
+    }
+}
+
+impl AddAssign for DrawCounts {
+    #[inline]
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+fn sys_reset_render_stats(mut stats: ResMut<RenderStats>) {
+    stats.last_frame = RenderFrameStats {
+        draw_calls: std::mem::take(stats.draw_calls.get_mut()),
+        instances: std::mem::take(stats.instances.get_mut()),
+        pipeline_switches: std::mem::take(stats.pipeline_switches.get_mut()),
+        buffer_allocations: BUFFER_ALLOCATIONS.swap(0, Ordering::Relaxed),
+        buffer_bytes: BUFFER_BYTES.swap(0, Ordering::Relaxed),
+        texture_allocations: TEXTURE_ALLOCATIONS.swap(0, Ordering::Relaxed),
+        texture_bytes: TEXTURE_BYTES.swap(0, Ordering::Relaxed),
+    };
+}
+
+// Systems needing the encoder are skipped for frames where it can't be created
+fn sys_setup_encoder(
+    all_storages: AllStoragesView,
+    device: Res<Device>,
+    surface: Res<Surface>,
+    config: Res<SurfaceConfig>,
+) {
     let encoder = match RenderEncoder::new(device.inner(), surface.inner()) {
         Ok(encoder) => encoder,
-        Err(_) => todo!(),
+
+        // Common after restoring a minimized window or moving between displays
+        Err(e @ (wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated)) => {
+            log::warn!("Surface {:?} - reconfiguring and skipping frame", e);
+            surface.inner().configure(device.inner(), config.inner());
+            return;
+        }
+
+        Err(wgpu::SurfaceError::Timeout) => {
+            log::warn!("Timed out acquiring surface texture - skipping frame");
+            return;
+        }
+
+        Err(wgpu::SurfaceError::OutOfMemory) => {
+            panic!("Out of memory acquiring surface texture")
+        }
     };
 
     all_storages.add_unique(encoder);
@@ -368,23 +730,38 @@ fn sys_setup_render_pass(
     clear_color: Res<ClearColor>,
     depth: Res<DepthTexture>,
 ) {
+    // Only present with the occlusion plugin
+    let queries = all_storages
+        .borrow::<Res<occlusion::OcclusionQueries>>()
+        .ok();
+
+    let size = tools.scene_size();
+
+    tools.push_debug_group(None, "main_pass");
+
     let pass = tools
         .begin_render_pass(RenderPassDesc {
             use_depth: Some(&depth.main_texture().view),
             clear_color: Some(clear_color.to_array()),
+            occlusion_query_set: queries.as_ref().and_then(|queries| queries.query_set()),
         })
         .forget_lifetime();
 
-    all_storages.add_unique(RenderPass { pass });
+    all_storages.add_unique(RenderPass { pass, size });
 }
 
 fn sys_finish_main_render_pass(all_storages: AllStoragesView) {
     all_storages.remove_unique::<RenderPass>().ok();
+
+    if let Ok(mut tools) = all_storages.borrow::<ResMut<RenderEncoder>>() {
+        tools.pop_debug_group(None);
+    }
 }
 
 fn sys_submit_encoder(all_storages: AllStoragesView, queue: Res<Queue>) {
-    let encoder = all_storages.remove_unique::<RenderEncoder>().unwrap();
-    encoder.finish(queue.inner());
+    if let Ok(encoder) = all_storages.remove_unique::<RenderEncoder>() {
+        encoder.finish(queue.inner());
+    }
 }
 
 //====================================================================
@@ -0,0 +1,581 @@
+//====================================================================
+
+use cabat_common::Color;
+use cabat_shipyard::prelude::*;
+use cabat_spatial::Transform;
+use shipyard::{AllStoragesView, Component, IntoIter, IntoWithId, Unique, View};
+
+use crate::{
+    camera::{self, MainCamera, SceneCamera},
+    render_tools,
+    settings::GpuInfo,
+    shared::BindGroupLayoutRegistry,
+    visibility::{self, RenderLayers, Visibility},
+    Device, Queue, RenderEncoder,
+};
+
+//====================================================================
+
+/// Light shining in every direction from the entity's [Transform], fading out
+/// to nothing at its range.
+///
+/// Lights are binned into screen tiles by a compute pass before the main pass,
+/// so each pixel only shades the lights that can reach it.
+#[derive(Component, Debug, Clone)]
+pub struct PointLight {
+    pub color: Color,
+    pub intensity: f32,
+    /// Distance the light reaches, in world units.
+    pub range: f32,
+}
+
+impl Default for PointLight {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+            intensity: 1.,
+            range: 10.,
+        }
+    }
+}
+
+impl PointLight {
+    #[inline]
+    pub fn new(color: Color, range: f32) -> Self {
+        Self {
+            color,
+            range,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+}
+
+//--------------------------------------------------
+
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+struct PointLightRaw {
+    position: [f32; 3],
+    range: f32,
+    // Color multiplied by intensity
+    color: [f32; 3],
+    _padding: f32,
+}
+
+// Per camera tile grid. Unused entries are zeroed so their viewport never
+// contains a pixel.
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+struct LightViewRaw {
+    inverse_view_projection: [f32; 16],
+    // x, y, width, height in pixels
+    viewport: [f32; 4],
+    tiles_x: u32,
+    tiles_y: u32,
+    // First tile of this view in the tile buffer
+    tile_offset: u32,
+    light_count: u32,
+}
+
+//====================================================================
+
+/// Gpu side of every [PointLight], bound by pipelines that shade with them.
+///
+/// Each tile holds a count followed by up to [Lights::MAX_LIGHTS_PER_TILE]
+/// light indices. Lights past that are dropped from the tile.
+///
+/// Culling needs compute shaders and storage buffers in the fragment stage.
+/// Devices without them bind an empty uniform in place of the lights and
+/// point lights aren't drawn, see [Lights::is_enabled].
+#[derive(Unique)]
+pub struct Lights(LightsBackend);
+
+enum LightsBackend {
+    Tiled(TiledLights),
+    Disabled { bind_group: wgpu::BindGroup },
+}
+
+impl Lights {
+    /// Size of a tile in pixels.
+    pub const TILE_SIZE: u32 = 16;
+    pub const MAX_LIGHTS_PER_TILE: u32 = 63;
+
+    const TILE_STRIDE: u64 = (Self::MAX_LIGHTS_PER_TILE as u64 + 1) * 4;
+    const WORKGROUP_SIZE: u32 = 8;
+    const INITIAL_TILES: u32 = 1024;
+
+    /// Whether the device can cull and shade point lights. The culling pass
+    /// and fragment shaders each bind three storage buffers.
+    pub fn is_supported(info: &GpuInfo) -> bool {
+        info.downlevel
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+            && info.limits.max_storage_buffers_per_shader_stage >= 3
+            && info.limits.max_storage_buffer_binding_size as u64
+                >= Self::INITIAL_TILES as u64 * Self::TILE_STRIDE
+    }
+
+    pub fn new(device: &wgpu::Device, layouts: &BindGroupLayoutRegistry, info: &GpuInfo) -> Self {
+        if Self::is_supported(info) {
+            return Self(LightsBackend::Tiled(TiledLights::new(device, layouts)));
+        }
+
+        log::warn!(
+            "Device doesn't support compute shaders or storage buffers - point lights are disabled"
+        );
+
+        let buffer = render_tools::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("Disabled Lights Buffer"),
+                size: 16,
+                usage: wgpu::BufferUsages::UNIFORM,
+                mapped_at_creation: false,
+            },
+        );
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Disabled Lights Bind Group"),
+            layout: layouts.lights(),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Self(LightsBackend::Disabled { bind_group })
+    }
+
+    /// Bind group layout entries matching what [Lights::new] creates.
+    pub(crate) fn layout_entries(info: &GpuInfo) -> Vec<wgpu::BindGroupLayoutEntry> {
+        match Self::is_supported(info) {
+            true => vec![
+                render_tools::bgl_storage_entry(0, wgpu::ShaderStages::FRAGMENT, true),
+                render_tools::bgl_storage_entry(1, wgpu::ShaderStages::FRAGMENT, true),
+                render_tools::bgl_storage_entry(2, wgpu::ShaderStages::FRAGMENT, true),
+            ],
+            false => vec![render_tools::bgl_uniform_entry(
+                0,
+                wgpu::ShaderStages::FRAGMENT,
+            )],
+        }
+    }
+
+    /// Whether point lights are culled and drawn on this device.
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        matches!(self.0, LightsBackend::Tiled(_))
+    }
+
+    #[inline]
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        match &self.0 {
+            LightsBackend::Tiled(tiled) => &tiled.bind_group,
+            LightsBackend::Disabled { bind_group } => bind_group,
+        }
+    }
+
+    #[inline]
+    pub fn light_count(&self) -> u32 {
+        match &self.0 {
+            LightsBackend::Tiled(tiled) => tiled.light_count,
+            LightsBackend::Disabled { .. } => 0,
+        }
+    }
+
+    /// WGSL declaring the lights bindings (group 3) and a
+    /// `point_lights(pixel, world_position, normal) -> vec3<f32>` function.
+    /// Appended to the source of shaders that bind the lights layout.
+    #[inline]
+    pub fn shader_source(&self) -> &'static str {
+        match self.0 {
+            LightsBackend::Tiled(_) => include_str!("../shaders/point_lights.wgsl"),
+            LightsBackend::Disabled { .. } => {
+                include_str!("../shaders/point_lights_disabled.wgsl")
+            }
+        }
+    }
+}
+
+//--------------------------------------------------
+
+struct TiledLights {
+    culling_pipeline: wgpu::ComputePipeline,
+    culling_layout: wgpu::BindGroupLayout,
+    culling_bind_group: wgpu::BindGroup,
+    bind_group: wgpu::BindGroup,
+
+    lights: wgpu::Buffer,
+    lights_capacity: u32,
+    light_count: u32,
+
+    views: wgpu::Buffer,
+    views_capacity: u32,
+    // Tile grid size of each view this frame
+    view_tiles: Vec<(u32, u32)>,
+
+    tiles: wgpu::Buffer,
+    tiles_capacity: u32,
+}
+
+impl TiledLights {
+    fn new(device: &wgpu::Device, layouts: &BindGroupLayoutRegistry) -> Self {
+        let culling_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Light Culling Bind Group Layout"),
+            entries: &[
+                render_tools::bgl_storage_entry(0, wgpu::ShaderStages::COMPUTE, true),
+                render_tools::bgl_storage_entry(1, wgpu::ShaderStages::COMPUTE, true),
+                render_tools::bgl_storage_entry(2, wgpu::ShaderStages::COMPUTE, false),
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Light Culling Pipeline Layout"),
+            bind_group_layouts: &[&culling_layout],
+            push_constant_ranges: &[],
+        });
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Light Culling Shader Module"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/light_culling.wgsl").into()),
+        });
+
+        let culling_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Light Culling Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &module,
+            entry_point: "cs_main",
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        let lights = Self::create_buffer::<PointLightRaw>(device, "Point Lights", 16);
+        let views = Self::create_buffer::<LightViewRaw>(device, "Light Views", 4);
+        let tiles = Self::create_tiles(device, Lights::INITIAL_TILES);
+
+        let (culling_bind_group, bind_group) =
+            Self::create_bind_groups(device, layouts, &culling_layout, &lights, &views, &tiles);
+
+        Self {
+            culling_pipeline,
+            culling_layout,
+            culling_bind_group,
+            bind_group,
+
+            lights,
+            lights_capacity: 16,
+            light_count: 0,
+
+            views,
+            views_capacity: 4,
+            view_tiles: Vec::new(),
+
+            tiles,
+            tiles_capacity: Lights::INITIAL_TILES,
+        }
+    }
+
+    fn create_buffer<T>(device: &wgpu::Device, label: &str, capacity: u32) -> wgpu::Buffer {
+        render_tools::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some(&format!("{} Buffer", label)),
+                size: capacity as u64 * std::mem::size_of::<T>() as u64,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        )
+    }
+
+    fn create_tiles(device: &wgpu::Device, capacity: u32) -> wgpu::Buffer {
+        render_tools::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("Light Tiles Buffer"),
+                size: capacity as u64 * Lights::TILE_STRIDE,
+                usage: wgpu::BufferUsages::STORAGE,
+                mapped_at_creation: false,
+            },
+        )
+    }
+
+    fn create_bind_groups(
+        device: &wgpu::Device,
+        layouts: &BindGroupLayoutRegistry,
+        culling_layout: &wgpu::BindGroupLayout,
+        lights: &wgpu::Buffer,
+        views: &wgpu::Buffer,
+        tiles: &wgpu::Buffer,
+    ) -> (wgpu::BindGroup, wgpu::BindGroup) {
+        let entries = [
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: lights.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: views.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: tiles.as_entire_binding(),
+            },
+        ];
+
+        let culling_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Light Culling Bind Group"),
+            layout: culling_layout,
+            entries: &entries,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Lights Bind Group"),
+            layout: layouts.lights(),
+            entries: &entries,
+        });
+
+        (culling_bind_group, bind_group)
+    }
+
+    fn rebuild_bind_groups(&mut self, device: &wgpu::Device, layouts: &BindGroupLayoutRegistry) {
+        (self.culling_bind_group, self.bind_group) = Self::create_bind_groups(
+            device,
+            layouts,
+            &self.culling_layout,
+            &self.lights,
+            &self.views,
+            &self.tiles,
+        );
+    }
+
+    fn update_lights(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layouts: &BindGroupLayoutRegistry,
+        lights: &[PointLightRaw],
+    ) {
+        self.light_count = lights.len() as u32;
+
+        if self.light_count > self.lights_capacity {
+            let capacity = self.light_count.next_power_of_two();
+
+            log::trace!(
+                "Growing point light buffer from {} to {}",
+                self.lights_capacity,
+                capacity
+            );
+
+            self.lights = Self::create_buffer::<PointLightRaw>(device, "Point Lights", capacity);
+            self.lights_capacity = capacity;
+            self.rebuild_bind_groups(device, layouts);
+        }
+
+        if !lights.is_empty() {
+            queue.write_buffer(&self.lights, 0, bytemuck::cast_slice(lights));
+        }
+    }
+
+    fn update_views(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layouts: &BindGroupLayoutRegistry,
+        viewports: &[[f32; 4]],
+        inverse_view_projections: &[glam::Mat4],
+    ) {
+        self.view_tiles = viewports
+            .iter()
+            .map(|[_, _, width, height]| {
+                (
+                    (*width as u32).div_ceil(Lights::TILE_SIZE),
+                    (*height as u32).div_ceil(Lights::TILE_SIZE),
+                )
+            })
+            .collect();
+
+        let view_count = viewports.len() as u32;
+        let tile_count = self.view_tiles.iter().map(|(x, y)| x * y).sum::<u32>();
+
+        let mut rebuild = false;
+
+        if view_count > self.views_capacity {
+            self.views_capacity = view_count.next_power_of_two();
+            self.views =
+                Self::create_buffer::<LightViewRaw>(device, "Light Views", self.views_capacity);
+            rebuild = true;
+        }
+
+        if tile_count > self.tiles_capacity {
+            self.tiles_capacity = tile_count.next_power_of_two();
+            self.tiles = Self::create_tiles(device, self.tiles_capacity);
+            rebuild = true;
+        }
+
+        if rebuild {
+            self.rebuild_bind_groups(device, layouts);
+        }
+
+        let mut tile_offset = 0;
+
+        let mut raw = viewports
+            .iter()
+            .zip(inverse_view_projections)
+            .zip(&self.view_tiles)
+            .map(|((viewport, inverse), (tiles_x, tiles_y))| {
+                let view = LightViewRaw {
+                    inverse_view_projection: inverse.to_cols_array(),
+                    viewport: *viewport,
+                    tiles_x: *tiles_x,
+                    tiles_y: *tiles_y,
+                    tile_offset,
+                    light_count: self.light_count,
+                };
+
+                tile_offset += tiles_x * tiles_y;
+                view
+            })
+            .collect::<Vec<_>>();
+
+        // Clear out views left over from previous frames
+        raw.resize(self.views_capacity as usize, bytemuck::Zeroable::zeroed());
+
+        queue.write_buffer(&self.views, 0, bytemuck::cast_slice(&raw));
+    }
+
+    /// Record the compute pass binning lights into each view's tiles.
+    fn dispatch(&self, encoder: &mut wgpu::CommandEncoder) {
+        // Views are written with no lights, so stale tiles aren't read
+        if self.light_count == 0 {
+            return;
+        }
+
+        let (tiles_x, tiles_y) = self
+            .view_tiles
+            .iter()
+            .fold((0, 0), |(x, y), (tiles_x, tiles_y)| {
+                (x.max(*tiles_x), y.max(*tiles_y))
+            });
+
+        if tiles_x == 0 || tiles_y == 0 {
+            return;
+        }
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Light Culling Pass"),
+            timestamp_writes: None,
+        });
+
+        pass.set_pipeline(&self.culling_pipeline);
+        pass.set_bind_group(0, &self.culling_bind_group, &[]);
+
+        // Views with smaller grids skip the extra tiles in the shader
+        pass.dispatch_workgroups(
+            tiles_x.div_ceil(Lights::WORKGROUP_SIZE),
+            tiles_y.div_ceil(Lights::WORKGROUP_SIZE),
+            self.view_tiles.len() as u32,
+        );
+    }
+}
+
+//====================================================================
+
+pub(crate) fn sys_setup_lights(
+    all_storages: AllStoragesView,
+    device: Res<Device>,
+    layouts: Res<BindGroupLayoutRegistry>,
+    gpu_info: Res<GpuInfo>,
+) {
+    let lights = Lights::new(device.inner(), &layouts, &gpu_info);
+    all_storages.add_unique(lights);
+}
+
+pub(crate) fn sys_prep_lights(
+    device: Res<Device>,
+    queue: Res<Queue>,
+    layouts: Res<BindGroupLayoutRegistry>,
+    mut lights: ResMut<Lights>,
+
+    v_light: View<PointLight>,
+    v_transform: View<Transform>,
+    v_visibility: View<Visibility>,
+) {
+    let lights = match &mut lights.0 {
+        LightsBackend::Tiled(tiled) => tiled,
+        LightsBackend::Disabled { .. } => return,
+    };
+
+    let raw = (&v_light, &v_transform)
+        .iter()
+        .with_id()
+        .filter(|(id, (light, _))| light.range > 0. && visibility::is_visible(&v_visibility, *id))
+        .map(|(_, (light, transform))| {
+            let [r, g, b, _] = light.color.to_array();
+
+            PointLightRaw {
+                position: transform.translation.to_array(),
+                range: light.range,
+                color: [
+                    r * light.intensity,
+                    g * light.intensity,
+                    b * light.intensity,
+                ],
+                _padding: 0.,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    lights.update_lights(device.inner(), queue.inner(), &layouts, &raw);
+}
+
+pub(crate) fn sys_prep_light_views(
+    device: Res<Device>,
+    queue: Res<Queue>,
+    tools: Res<RenderEncoder>,
+    layouts: Res<BindGroupLayoutRegistry>,
+    mut lights: ResMut<Lights>,
+
+    camera: Res<MainCamera>,
+    v_cameras: View<SceneCamera>,
+    v_layers: View<RenderLayers>,
+) {
+    let lights = match &mut lights.0 {
+        LightsBackend::Tiled(tiled) => tiled,
+        LightsBackend::Disabled { .. } => return,
+    };
+
+    let size = tools.scene_size();
+
+    let (viewports, inverses): (Vec<_>, Vec<_>) =
+        camera::main_pass_cameras(&camera, &v_cameras, &v_layers)
+            .into_iter()
+            .map(|view| {
+                (
+                    view.viewport.to_pixels(size),
+                    view.view_projection.inverse(),
+                )
+            })
+            .unzip();
+
+    lights.update_views(
+        device.inner(),
+        queue.inner(),
+        &layouts,
+        &viewports,
+        &inverses,
+    );
+}
+
+pub(crate) fn sys_cull_lights(mut tools: ResMut<RenderEncoder>, lights: Res<Lights>) {
+    if let LightsBackend::Tiled(tiled) = &lights.0 {
+        tiled.dispatch(tools.encoder());
+    }
+}
+
+//====================================================================
@@ -4,7 +4,8 @@ use cabat_assets::asset_loader::AssetTypeLoader;
 use cabat_shipyard::Res;
 
 use crate::{
-    shared::SharedPipelineResources,
+    procedural::ProceduralTexture,
+    shared::BindGroupLayoutRegistry,
     texture::{RawTexture, Texture},
     Device, Queue,
 };
@@ -26,25 +27,36 @@ impl AssetTypeLoader for TextureLoader {
             None => "Loaded Texture",
         };
 
-        let image_reader = image::ImageReader::open(&path)?;
-        let image = image_reader.decode()?;
-
         let device = all_storages.borrow::<Res<Device>>()?;
         let queue = all_storages.borrow::<Res<Queue>>()?;
+        let layouts = all_storages.borrow::<Res<BindGroupLayoutRegistry>>()?;
+
+        // Generated from a descriptor rather than read from an image
+        if path.extension().is_some_and(|ext| ext == "texgen") {
+            let descriptor = ProceduralTexture::from_ron(&std::fs::read_to_string(path)?)?;
+
+            return Ok(descriptor.create_texture(
+                device.inner(),
+                queue.inner(),
+                &layouts,
+                Some(name),
+            ));
+        }
+
+        let image_reader = image::ImageReader::open(&path)?;
+        let image = image_reader.decode()?;
 
         let raw_texture =
             RawTexture::from_image(device.inner(), queue.inner(), &image, Some(&name), None);
 
-        let shared = all_storages.borrow::<Res<SharedPipelineResources>>()?;
-
-        let texture = shared.load_texture(device.inner(), raw_texture, Some(&name));
+        let texture = layouts.load_texture(device.inner(), raw_texture, Some(&name));
 
         Ok(texture)
     }
 
     #[inline]
     fn extensions(&self) -> &[&str] {
-        &["png", "jpg"]
+        &["png", "jpg", "texgen"]
     }
 }
 
@@ -0,0 +1,490 @@
+//====================================================================
+
+use std::{collections::HashMap, f32::consts::PI, hash::BuildHasherDefault};
+
+use cabat_assets::Asset;
+use glam::{Vec2, Vec3};
+use rustc_hash::FxHasher;
+
+use crate::{render_tools, Vertex};
+
+//====================================================================
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, bytemuck::Zeroable, bytemuck::Pod)]
+pub struct MeshVertex {
+    pub pos: [f32; 3],
+    pub uv: [f32; 2],
+    pub normal: [f32; 3],
+    /// Tangent along +u, with the sign of the bitangent in w. Filled in by
+    /// [MeshData::generate_tangents].
+    pub tangent: [f32; 4],
+}
+
+impl Vertex for MeshVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+                0 => Float32x3, 1 => Float32x2, 2 => Float32x3, 3 => Float32x4
+        ];
+
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<MeshVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &VERTEX_ATTRIBUTES,
+        }
+    }
+}
+
+impl MeshVertex {
+    #[inline]
+    pub fn new(pos: Vec3, uv: Vec2, normal: Vec3) -> Self {
+        Self {
+            pos: pos.to_array(),
+            uv: uv.to_array(),
+            normal: normal.to_array(),
+            tangent: [0.; 4],
+        }
+    }
+}
+
+//====================================================================
+
+/// Mesh geometry on the cpu side. Triangles are counter-clockwise when facing
+/// the front.
+#[derive(Clone, Debug, Default)]
+pub struct MeshData {
+    pub vertices: Vec<MeshVertex>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    /// Unit quad on the XY plane, facing +Z.
+    pub fn quad() -> Self {
+        let mut data = Self::default();
+        data.push_face(Vec3::ZERO, Vec3::X, Vec3::Y);
+        data.generate_tangents();
+        data
+    }
+
+    /// Square plane on the XZ plane, facing +Y, split into a grid of
+    /// `subdivisions` by `subdivisions` cells.
+    pub fn plane(size: f32, subdivisions: u32) -> Self {
+        let cells = subdivisions.max(1);
+        let row = cells + 1;
+        let half = size / 2.;
+
+        let vertices = (0..row)
+            .flat_map(|z| (0..row).map(move |x| (x, z)))
+            .map(|(x, z)| {
+                let uv = Vec2::new(x as f32 / cells as f32, z as f32 / cells as f32);
+                let pos = Vec3::new(uv.x * size - half, 0., uv.y * size - half);
+                MeshVertex::new(pos, uv, Vec3::Y)
+            })
+            .collect();
+
+        let indices = (0..cells)
+            .flat_map(|z| (0..cells).map(move |x| (x, z)))
+            .flat_map(|(x, z)| {
+                let a = z * row + x;
+                let b = a + 1;
+                let c = a + row;
+                let d = c + 1;
+
+                [a, c, d, a, d, b]
+            })
+            .collect();
+
+        let mut data = Self { vertices, indices };
+        data.generate_tangents();
+        data
+    }
+
+    /// Unit cube centered on the origin. Each face has its own vertices so normals
+    /// and uvs stay flat.
+    pub fn cube() -> Self {
+        let mut data = Self::default();
+
+        [
+            (Vec3::Z, Vec3::X, Vec3::Y),
+            (Vec3::NEG_Z, Vec3::NEG_X, Vec3::Y),
+            (Vec3::X, Vec3::NEG_Z, Vec3::Y),
+            (Vec3::NEG_X, Vec3::Z, Vec3::Y),
+            (Vec3::Y, Vec3::X, Vec3::NEG_Z),
+            (Vec3::NEG_Y, Vec3::X, Vec3::Z),
+        ]
+        .into_iter()
+        .for_each(|(normal, u, v)| data.push_face(normal * 0.5, u, v));
+
+        data.generate_tangents();
+        data
+    }
+
+    /// Sphere made of `sectors` slices around the Y axis and `stacks` rings from
+    /// top to bottom.
+    pub fn uv_sphere(radius: f32, sectors: u32, stacks: u32) -> Self {
+        let sectors = sectors.max(3);
+        let stacks = stacks.max(2);
+        let row = sectors + 1;
+
+        let vertices = (0..=stacks)
+            .flat_map(|stack| (0..=sectors).map(move |sector| (stack, sector)))
+            .map(|(stack, sector)| {
+                let uv = Vec2::new(sector as f32 / sectors as f32, stack as f32 / stacks as f32);
+
+                let phi = PI * uv.y;
+                let theta = 2. * PI * uv.x;
+
+                let normal =
+                    Vec3::new(phi.sin() * theta.cos(), phi.cos(), -phi.sin() * theta.sin());
+
+                MeshVertex::new(normal * radius, uv, normal)
+            })
+            .collect();
+
+        let mut indices = Vec::new();
+
+        (0..stacks).for_each(|stack| {
+            (0..sectors).for_each(|sector| {
+                let k1 = stack * row + sector;
+                let k2 = k1 + row;
+
+                // Skip triangles that collapse into the poles
+                if stack != 0 {
+                    indices.extend([k1, k2, k1 + 1]);
+                }
+
+                if stack != stacks - 1 {
+                    indices.extend([k1 + 1, k2, k2 + 1]);
+                }
+            });
+        });
+
+        let mut data = Self { vertices, indices };
+        data.generate_tangents();
+        data
+    }
+
+    /// Generate tangents from the uvs, for sampling tangent space normal maps.
+    /// Tangents are averaged between triangles sharing a vertex, then made
+    /// orthogonal to its normal.
+    pub fn generate_tangents(&mut self) {
+        let mut tangents = vec![Vec3::ZERO; self.vertices.len()];
+        let mut bitangents = vec![Vec3::ZERO; self.vertices.len()];
+
+        self.indices.chunks_exact(3).for_each(|triangle| {
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| index as usize);
+            let [va, vb, vc] = [a, b, c].map(|index| &self.vertices[index]);
+
+            let edge1 = Vec3::from(vb.pos) - Vec3::from(va.pos);
+            let edge2 = Vec3::from(vc.pos) - Vec3::from(va.pos);
+            let duv1 = Vec2::from(vb.uv) - Vec2::from(va.uv);
+            let duv2 = Vec2::from(vc.uv) - Vec2::from(va.uv);
+
+            let det = duv1.x * duv2.y - duv2.x * duv1.y;
+
+            // Triangle has no uv area to derive a direction from
+            if det.abs() <= f32::EPSILON {
+                return;
+            }
+
+            let r = 1. / det;
+            let tangent = (edge1 * duv2.y - edge2 * duv1.y) * r;
+            let bitangent = (edge2 * duv1.x - edge1 * duv2.x) * r;
+
+            [a, b, c].into_iter().for_each(|index| {
+                tangents[index] += tangent;
+                bitangents[index] += bitangent;
+            });
+        });
+
+        self.vertices
+            .iter_mut()
+            .zip(tangents.into_iter().zip(bitangents))
+            .for_each(|(vertex, (tangent, bitangent))| {
+                let normal = Vec3::from(vertex.normal);
+
+                // Gram-Schmidt, falling back to any perpendicular direction
+                let tangent = (tangent - normal * normal.dot(tangent))
+                    .try_normalize()
+                    .unwrap_or_else(|| normal.any_orthogonal_vector().normalize_or_zero());
+
+                let handedness = match normal.cross(tangent).dot(bitangent) < 0. {
+                    true => -1.,
+                    false => 1.,
+                };
+
+                vertex.tangent = tangent.extend(handedness).to_array();
+            });
+    }
+
+    // Push a unit square centered on 'center', spanned by the 'u' and 'v' axes.
+    // The face points towards u x v.
+    fn push_face(&mut self, center: Vec3, u: Vec3, v: Vec3) {
+        let normal = u.cross(v);
+        let start = self.vertices.len() as u32;
+
+        self.vertices.extend([
+            MeshVertex::new(center - u * 0.5 + v * 0.5, Vec2::new(0., 0.), normal),
+            MeshVertex::new(center - u * 0.5 - v * 0.5, Vec2::new(0., 1.), normal),
+            MeshVertex::new(center + u * 0.5 + v * 0.5, Vec2::new(1., 0.), normal),
+            MeshVertex::new(center + u * 0.5 - v * 0.5, Vec2::new(1., 1.), normal),
+        ]);
+
+        self.indices
+            .extend([0, 1, 3, 0, 3, 2].into_iter().map(|index| start + index));
+    }
+}
+
+//====================================================================
+
+/// Accumulates geometry for procedural meshes. Vertices added without a normal
+/// get smooth normals generated from the surrounding triangles when built.
+#[derive(Clone, Debug, Default)]
+pub struct MeshBuilder {
+    positions: Vec<Vec3>,
+    uvs: Vec<Vec2>,
+    normals: Vec<Option<Vec3>>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(vertices: usize, indices: usize) -> Self {
+        Self {
+            positions: Vec::with_capacity(vertices),
+            uvs: Vec::with_capacity(vertices),
+            normals: Vec::with_capacity(vertices),
+            indices: Vec::with_capacity(indices),
+        }
+    }
+
+    #[inline]
+    pub fn vertex_count(&self) -> u32 {
+        self.positions.len() as u32
+    }
+
+    #[inline]
+    pub fn index_count(&self) -> u32 {
+        self.indices.len() as u32
+    }
+
+    /// Add a vertex that will have its normal generated, returning its index.
+    #[inline]
+    pub fn vertex(&mut self, pos: Vec3, uv: Vec2) -> u32 {
+        self.push_vertex(pos, uv, None)
+    }
+
+    /// Add a vertex with an explicit normal, returning its index.
+    #[inline]
+    pub fn vertex_with_normal(&mut self, pos: Vec3, uv: Vec2, normal: Vec3) -> u32 {
+        self.push_vertex(pos, uv, Some(normal))
+    }
+
+    fn push_vertex(&mut self, pos: Vec3, uv: Vec2, normal: Option<Vec3>) -> u32 {
+        let index = self.vertex_count();
+
+        self.positions.push(pos);
+        self.uvs.push(uv);
+        self.normals.push(normal);
+
+        index
+    }
+
+    /// Add a counter-clockwise triangle.
+    #[inline]
+    pub fn triangle(&mut self, a: u32, b: u32, c: u32) -> &mut Self {
+        self.indices.extend([a, b, c]);
+        self
+    }
+
+    /// Add a counter-clockwise quad, split along a to c.
+    #[inline]
+    pub fn quad(&mut self, a: u32, b: u32, c: u32, d: u32) -> &mut Self {
+        self.indices.extend([a, b, c, a, c, d]);
+        self
+    }
+
+    /// Append existing mesh data, keeping its normals.
+    pub fn extend(&mut self, data: &MeshData) -> &mut Self {
+        let start = self.vertex_count();
+
+        data.vertices.iter().for_each(|vertex| {
+            self.push_vertex(
+                vertex.pos.into(),
+                vertex.uv.into(),
+                Some(vertex.normal.into()),
+            );
+        });
+
+        self.indices
+            .extend(data.indices.iter().map(|index| start + index));
+
+        self
+    }
+
+    /// Discard all normals so every vertex gets a generated one.
+    pub fn clear_normals(&mut self) -> &mut Self {
+        self.normals.iter_mut().for_each(|normal| *normal = None);
+        self
+    }
+
+    /// Merge vertices that share the same position, uv and normal. Vertices without
+    /// a normal are merged by position and uv, so their generated normals end up
+    /// smooth across the shared edge.
+    pub fn deduplicate(&mut self) -> &mut Self {
+        let mut unique: HashMap<_, _, BuildHasherDefault<FxHasher>> = HashMap::default();
+        let mut remap = Vec::with_capacity(self.positions.len());
+
+        let mut positions = Vec::new();
+        let mut uvs = Vec::new();
+        let mut normals = Vec::new();
+
+        (0..self.positions.len()).for_each(|index| {
+            let (pos, uv, normal) = (self.positions[index], self.uvs[index], self.normals[index]);
+
+            let key = (
+                pos.to_array().map(f32::to_bits),
+                uv.to_array().map(f32::to_bits),
+                normal.map(|normal| normal.to_array().map(f32::to_bits)),
+            );
+
+            let new_index = *unique.entry(key).or_insert_with(|| {
+                positions.push(pos);
+                uvs.push(uv);
+                normals.push(normal);
+                positions.len() as u32 - 1
+            });
+
+            remap.push(new_index);
+        });
+
+        log::trace!(
+            "Mesh builder deduplicated {} vertices into {}",
+            self.positions.len(),
+            positions.len()
+        );
+
+        self.indices
+            .iter_mut()
+            .for_each(|index| *index = remap[*index as usize]);
+
+        self.positions = positions;
+        self.uvs = uvs;
+        self.normals = normals;
+
+        self
+    }
+
+    // Area weighted average of the normals of every triangle using the vertex
+    fn generate_normals(&self) -> Vec<Vec3> {
+        let mut generated = vec![Vec3::ZERO; self.positions.len()];
+
+        self.indices.chunks_exact(3).for_each(|triangle| {
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| index as usize);
+
+            let face = (self.positions[b] - self.positions[a])
+                .cross(self.positions[c] - self.positions[a]);
+
+            generated[a] += face;
+            generated[b] += face;
+            generated[c] += face;
+        });
+
+        generated
+            .into_iter()
+            .map(|normal| normal.normalize_or_zero())
+            .collect()
+    }
+
+    pub fn build(&self) -> MeshData {
+        let generated = match self.normals.iter().any(Option::is_none) {
+            true => self.generate_normals(),
+            false => Vec::new(),
+        };
+
+        let vertices = (0..self.positions.len())
+            .map(|index| {
+                let normal = self.normals[index].unwrap_or_else(|| generated[index]);
+                MeshVertex::new(self.positions[index], self.uvs[index], normal)
+            })
+            .collect();
+
+        let mut data = MeshData {
+            vertices,
+            indices: self.indices.clone(),
+        };
+
+        data.generate_tangents();
+        data
+    }
+
+    #[inline]
+    pub fn build_mesh(&self, device: &wgpu::Device, label: &str) -> Mesh {
+        Mesh::new(device, &self.build(), label)
+    }
+}
+
+//====================================================================
+
+/// Mesh uploaded to the gpu.
+pub struct Mesh {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_count: u32,
+}
+
+impl Asset for Mesh {
+    #[inline]
+    fn gpu_memory(&self) -> usize {
+        (self.vertex_buffer.size() + self.index_buffer.size()) as usize
+    }
+}
+
+impl Mesh {
+    pub fn new(device: &wgpu::Device, data: &MeshData, label: &str) -> Self {
+        let vertex_buffer = render_tools::vertex_buffer(device, label, &data.vertices);
+
+        let index_buffer = render_tools::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{} Index Buffer", label)),
+                contents: bytemuck::cast_slice(&data.indices),
+                usage: wgpu::BufferUsages::INDEX,
+            },
+        );
+
+        Self {
+            vertex_buffer,
+            index_buffer,
+            index_count: data.indices.len() as u32,
+        }
+    }
+
+    #[inline]
+    pub fn vertex_buffer(&self) -> &wgpu::Buffer {
+        &self.vertex_buffer
+    }
+
+    #[inline]
+    pub fn index_buffer(&self) -> &wgpu::Buffer {
+        &self.index_buffer
+    }
+
+    #[inline]
+    pub fn index_count(&self) -> u32 {
+        self.index_count
+    }
+
+    /// Bind the mesh buffers to the given slot and draw the given instances.
+    pub fn draw(&self, pass: &mut wgpu::RenderPass, slot: u32, instances: std::ops::Range<u32>) {
+        pass.set_vertex_buffer(slot, self.vertex_buffer.slice(..));
+        pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        pass.draw_indexed(0..self.index_count, 0, instances);
+    }
+}
+
+//====================================================================
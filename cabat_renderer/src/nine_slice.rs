@@ -0,0 +1,477 @@
+//====================================================================
+
+use cabat_assets::{
+    asset_storage::AssetStorage,
+    handle::{Handle, HandleId},
+};
+use cabat_common::{Color, Size, UiPosition, UiVal, WindowResizeEvent, WindowScale, WindowSize};
+use cabat_shipyard::prelude::*;
+use shipyard::{
+    AllStoragesView, Component, IntoIter, IntoWithId, IntoWorkload, SystemModificator, Unique, View,
+};
+
+use crate::{
+    render_graph::{resources, AddRenderPass, RenderGraphNode},
+    render_tools::{self, InstanceBuffer},
+    settings::SurfaceFormatChangedEvent,
+    shared::{self, BindGroupLayoutRegistry, SortKey},
+    texture::Texture,
+    Device, DrawCounts, Queue, RenderEncoder, RenderPassDesc, RenderStats, SurfaceConfig, Vertex,
+};
+
+//====================================================================
+
+pub struct NineSlicePlugin;
+
+impl Plugin for NineSlicePlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        builder
+            .add_workload_pre(Stages::Setup, sys_setup_nine_slice_renderer)
+            .add_workload_last(Stages::Update, sys_prep_nine_slice)
+            .add_render_pass(
+                RenderGraphNode::new("nine_slice")
+                    .reads(resources::SCENE)
+                    .reads(resources::SCENE_OUTPUT)
+                    .writes(resources::SURFACE),
+                sys_render_nine_slice.skip_if_missing_unique::<RenderEncoder>(),
+            )
+            .add_event::<WindowResizeEvent>(sys_resize_nine_slice.into_workload())
+            .add_event::<SurfaceFormatChangedEvent>(
+                sys_rebuild_nine_slice_pipeline.into_workload(),
+            );
+    }
+}
+
+//====================================================================
+
+/// Border sizes of a nine-slice texture, in texture pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct NineSliceMargins {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+impl NineSliceMargins {
+    #[inline]
+    pub fn new(left: f32, right: f32, top: f32, bottom: f32) -> Self {
+        Self {
+            left,
+            right,
+            top,
+            bottom,
+        }
+    }
+
+    #[inline]
+    pub fn uniform(margin: f32) -> Self {
+        Self::new(margin, margin, margin, margin)
+    }
+
+    #[inline]
+    fn to_array(&self) -> [f32; 4] {
+        [self.left, self.right, self.top, self.bottom]
+    }
+}
+
+//--------------------------------------------------
+
+/// Screen space panel drawn from a texture split into nine regions. Corners keep
+/// their size, edges stretch along one axis and the center stretches along both.
+#[derive(Component)]
+pub struct NineSlice {
+    pub texture: Handle<Texture>,
+    pub margins: NineSliceMargins,
+
+    pub position: UiPosition,
+    pub width: UiVal,
+    pub height: UiVal,
+
+    /// Scale of the border regions, applied on top of the window scale factor.
+    pub border_scale: f32,
+    pub color: Color,
+    /// Panels with a higher sort key are drawn on top.
+    pub sort_key: SortKey,
+    pub visible: bool,
+}
+
+impl NineSlice {
+    pub fn new(
+        texture: Handle<Texture>,
+        margins: NineSliceMargins,
+        position: UiPosition,
+        width: impl Into<UiVal>,
+        height: impl Into<UiVal>,
+    ) -> Self {
+        Self {
+            texture,
+            margins,
+            position,
+            width: width.into(),
+            height: height.into(),
+            border_scale: 1.,
+            color: Color::WHITE,
+            sort_key: SortKey::default(),
+            visible: true,
+        }
+    }
+
+    /// Size of the panel in physical pixels.
+    #[inline]
+    pub fn screen_size(&self, window: Size<f32>, scale_factor: f32) -> Size<f32> {
+        Size::new(
+            self.width.resolve(window.width, scale_factor),
+            self.height.resolve(window.height, scale_factor),
+        )
+    }
+
+    fn to_instance(&self, window: Size<f32>, scale_factor: f32) -> NineSliceInstanceRaw {
+        let size = self.screen_size(window, scale_factor);
+        let (x, y) = self.position.resolve(window, size, scale_factor);
+
+        let texture_size = self.texture.inner().raw().texture.size();
+        let [left, right, top, bottom] = self.margins.to_array();
+
+        let uv_border = [
+            left / texture_size.width as f32,
+            right / texture_size.width as f32,
+            top / texture_size.height as f32,
+            bottom / texture_size.height as f32,
+        ];
+
+        // Shrink the borders when the panel is too small to fit them
+        let border_scale = self.border_scale * scale_factor;
+        let fit_x = (size.width / ((left + right) * border_scale)).min(1.);
+        let fit_y = (size.height / ((top + bottom) * border_scale)).min(1.);
+
+        let border = [
+            left * border_scale * fit_x,
+            right * border_scale * fit_x,
+            top * border_scale * fit_y,
+            bottom * border_scale * fit_y,
+        ];
+
+        NineSliceInstanceRaw {
+            position: [x, y],
+            size: [size.width, size.height],
+            border,
+            uv_border,
+            color: self.color.into(),
+        }
+    }
+}
+
+//====================================================================
+
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Debug)]
+struct NineSliceVertex {
+    grid: [f32; 2],
+}
+
+impl Vertex for NineSliceVertex {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![
+            0 => Float32x2
+        ];
+
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<NineSliceVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &VERTEX_ATTRIBUTES,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Debug)]
+pub struct NineSliceInstanceRaw {
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    pub border: [f32; 4],
+    pub uv_border: [f32; 4],
+    pub color: [f32; 4],
+}
+
+impl Vertex for NineSliceInstanceRaw {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+            1 => Float32x2,
+            2 => Float32x2,
+            3 => Float32x4,
+            4 => Float32x4,
+            5 => Float32x4,
+        ];
+
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<NineSliceInstanceRaw>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &VERTEX_ATTRIBUTES,
+        }
+    }
+}
+
+// 4x4 grid of vertices, row by row from the top left
+fn slice_grid() -> ([NineSliceVertex; 16], [u16; 54]) {
+    let vertices = std::array::from_fn(|index| NineSliceVertex {
+        grid: [(index % 4) as f32, (index / 4) as f32],
+    });
+
+    let mut indices = [0; 54];
+
+    (0..9).for_each(|cell| {
+        let top_left = (cell / 3) * 4 + cell % 3;
+        let bottom_left = top_left + 4;
+
+        indices[cell as usize * 6..cell as usize * 6 + 6].copy_from_slice(&[
+            top_left,
+            bottom_left,
+            bottom_left + 1,
+            top_left,
+            bottom_left + 1,
+            top_left + 1,
+        ]);
+    });
+
+    (vertices, indices)
+}
+
+//====================================================================
+
+#[derive(Unique)]
+pub struct NineSliceRenderer {
+    pipeline: wgpu::RenderPipeline,
+
+    screen_buffer: wgpu::Buffer,
+    screen_bind_group_layout: wgpu::BindGroupLayout,
+    screen_bind_group: wgpu::BindGroup,
+
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_count: u32,
+
+    instances: InstanceBuffer<NineSliceInstanceRaw>,
+    // Consecutive instances sharing a texture, in draw order
+    batches: Vec<(HandleId, std::ops::Range<u32>)>,
+}
+
+impl NineSliceRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        layouts: &BindGroupLayoutRegistry,
+        size: Size<u32>,
+    ) -> Self {
+        let screen_buffer = render_tools::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Nine Slice Screen Buffer"),
+                contents: bytemuck::cast_slice(&Self::screen_data(size)),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        );
+
+        let screen_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Nine Slice Screen Bind Group Layout"),
+                entries: &[render_tools::bgl_uniform_entry(
+                    0,
+                    wgpu::ShaderStages::VERTEX,
+                )],
+            });
+
+        let screen_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Nine Slice Screen Bind Group"),
+            layout: &screen_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: screen_buffer.as_entire_binding(),
+            }],
+        });
+
+        let pipeline = Self::create_pipeline(device, config, layouts, &screen_bind_group_layout);
+
+        let (vertices, indices) = slice_grid();
+        let vertex_buffer = render_tools::vertex_buffer(device, "Nine Slice", &vertices);
+        let index_buffer = render_tools::index_buffer(device, "Nine Slice", &indices);
+
+        Self {
+            pipeline,
+
+            screen_buffer,
+            screen_bind_group_layout,
+            screen_bind_group,
+
+            vertex_buffer,
+            index_buffer,
+            index_count: indices.len() as u32,
+
+            instances: InstanceBuffer::new(device, "Nine Slice"),
+            batches: Vec::new(),
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        layouts: &BindGroupLayoutRegistry,
+        screen_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::RenderPipeline {
+        render_tools::create_pipeline(
+            device,
+            config,
+            "Nine Slice Pipeline",
+            &[screen_bind_group_layout, layouts.texture()],
+            &[NineSliceVertex::desc(), NineSliceInstanceRaw::desc()],
+            include_str!("../shaders/nine_slice.wgsl"),
+            render_tools::RenderPipelineDescriptor {
+                fragment_targets: Some(&[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::all(),
+                })]),
+                ..Default::default()
+            },
+        )
+    }
+
+    // Uniform buffers need to be 16 byte aligned
+    #[inline]
+    fn screen_data(size: Size<u32>) -> [f32; 4] {
+        [size.width as f32, size.height as f32, 0., 0.]
+    }
+
+    fn resize(&self, queue: &wgpu::Queue, size: Size<u32>) {
+        queue.write_buffer(
+            &self.screen_buffer,
+            0,
+            bytemuck::cast_slice(&Self::screen_data(size)),
+        );
+    }
+
+    pub fn render(&self, pass: &mut wgpu::RenderPass, storage: &AssetStorage) -> DrawCounts {
+        if self.batches.is_empty() {
+            return DrawCounts::default();
+        }
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.screen_bind_group, &[]);
+
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.set_vertex_buffer(1, self.instances.buffer().slice(..));
+        pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+
+        self.batches
+            .iter()
+            .fold(DrawCounts::pipeline(), |counts, (id, instances)| {
+                let texture = match storage.get_asset::<Texture>(*id) {
+                    Some(texture) => texture,
+                    None => return counts,
+                };
+
+                pass.set_bind_group(1, texture.binding(), &[]);
+                pass.draw_indexed(0..self.index_count, 0, instances.clone());
+
+                counts.draw(instances.len() as u32)
+            })
+    }
+}
+
+//====================================================================
+
+fn sys_setup_nine_slice_renderer(
+    all_storages: AllStoragesView,
+    device: Res<Device>,
+    config: Res<SurfaceConfig>,
+    layouts: Res<BindGroupLayoutRegistry>,
+    size: Res<WindowSize>,
+) {
+    all_storages.add_unique(NineSliceRenderer::new(
+        device.inner(),
+        config.inner(),
+        &layouts,
+        size.size(),
+    ));
+}
+
+fn sys_rebuild_nine_slice_pipeline(
+    device: Res<Device>,
+    config: Res<SurfaceConfig>,
+    layouts: Res<BindGroupLayoutRegistry>,
+    mut renderer: ResMut<NineSliceRenderer>,
+) {
+    renderer.pipeline = NineSliceRenderer::create_pipeline(
+        device.inner(),
+        config.inner(),
+        &layouts,
+        &renderer.screen_bind_group_layout,
+    );
+}
+
+fn sys_resize_nine_slice(
+    queue: Res<Queue>,
+    size: Res<WindowSize>,
+    renderer: Res<NineSliceRenderer>,
+) {
+    renderer.resize(queue.inner(), size.size());
+}
+
+fn sys_prep_nine_slice(
+    device: Res<Device>,
+    queue: Res<Queue>,
+    size: Res<WindowSize>,
+    scale: Res<WindowScale>,
+    mut renderer: ResMut<NineSliceRenderer>,
+    v_nine_slice: View<NineSlice>,
+) {
+    let window = Size::new(size.width_f32(), size.height_f32());
+
+    let mut panels = v_nine_slice
+        .iter()
+        .with_id()
+        .filter(|(_, panel)| panel.visible)
+        .map(|(id, panel)| (panel.sort_key, id, panel))
+        .collect::<Vec<_>>();
+    shared::sort_draw_order(&mut panels);
+
+    let mut batches: Vec<(HandleId, std::ops::Range<u32>)> = Vec::new();
+
+    let instances = panels
+        .into_iter()
+        .enumerate()
+        .map(|(index, (_, _, panel))| {
+            let index = index as u32;
+            let id = panel.texture.id();
+
+            match batches.last_mut() {
+                Some((last, range)) if *last == id => range.end = index + 1,
+                _ => batches.push((id, index..index + 1)),
+            }
+
+            panel.to_instance(window, scale.scale_factor())
+        })
+        .collect::<Vec<_>>();
+
+    renderer
+        .instances
+        .update(device.inner(), queue.inner(), &instances);
+    renderer.batches = batches;
+}
+
+fn sys_render_nine_slice(
+    mut tools: ResMut<RenderEncoder>,
+    renderer: Res<NineSliceRenderer>,
+    storage: Res<AssetStorage>,
+    stats: Res<RenderStats>,
+) {
+    if renderer.batches.is_empty() {
+        return;
+    }
+
+    let mut pass = tools.begin_render_pass(RenderPassDesc::none());
+    let counts = renderer.render(&mut pass, &storage);
+    stats.record(counts);
+}
+
+//====================================================================
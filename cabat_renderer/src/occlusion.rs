@@ -0,0 +1,492 @@
+//====================================================================
+
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Arc,
+};
+
+use cabat_shipyard::prelude::*;
+use cabat_spatial::Transform;
+use shipyard::{
+    AllStoragesView, Component, EntityId, Get, IntoIter, IntoWithId, IntoWorkload, Unique, View,
+    ViewMut,
+};
+
+use crate::{
+    camera::{self, MainCamera, SceneCamera},
+    mesh::{Mesh, MeshData, MeshVertex},
+    render_graph::{resources, AddRenderPass, RenderGraphNode},
+    render_tools::{self, FaceCulling, InstanceBuffer},
+    settings::SurfaceFormatChangedEvent,
+    shared::BindGroupLayoutRegistry,
+    texture::RawTexture,
+    visibility::{RenderLayers, Visibility},
+    Device, DrawCounts, Queue, RenderEncoder, RenderPass, RenderStats, SurfaceConfig, Vertex,
+};
+
+//====================================================================
+
+/// Hides entities fully behind other geometry using occlusion queries. Bounding
+/// boxes are tested against the depth buffer at the end of the main pass and the
+/// results are applied once they are read back, usually a frame or two later.
+///
+/// Meant for dense scenes where many entities are hidden by walls, testing costs
+/// a draw call per entity and camera.
+pub struct OcclusionCullingPlugin;
+
+impl Plugin for OcclusionCullingPlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        builder
+            .add_workload_pre(Stages::Setup, sys_setup_occlusion)
+            .add_workload_post(Stages::Update, sys_prep_occlusion)
+            .add_render_pass(
+                RenderGraphNode::new("occlusion_queries")
+                    .writes(resources::MAIN_PASS)
+                    .writes(resources::OCCLUSION),
+                sys_render_occlusion_queries,
+            )
+            // Reading the scene keeps the resolve after the main pass has ended
+            .add_render_pass(
+                RenderGraphNode::new("occlusion_resolve")
+                    .reads(resources::OCCLUSION)
+                    .reads(resources::SCENE),
+                sys_resolve_occlusion_queries,
+            )
+            .add_workload(Stages::Last, sys_map_occlusion_results)
+            .add_event::<SurfaceFormatChangedEvent>(sys_rebuild_occlusion_pipeline.into_workload());
+    }
+}
+
+//====================================================================
+
+/// Test this entity for occlusion using a box around its transform. Requires a
+/// [Visibility] to have any effect.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct OcclusionCulled {
+    /// Half the size of the box, scaled by the entity's transform.
+    pub half_extents: glam::Vec3,
+}
+
+impl OcclusionCulled {
+    #[inline]
+    pub fn new(half_extents: glam::Vec3) -> Self {
+        Self { half_extents }
+    }
+}
+
+//--------------------------------------------------
+
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+struct OcclusionInstanceRaw {
+    transform: [f32; 16],
+}
+
+impl Vertex for OcclusionInstanceRaw {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+            4 => Float32x4,
+            5 => Float32x4,
+            6 => Float32x4,
+            7 => Float32x4,
+        ];
+
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<OcclusionInstanceRaw>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &VERTEX_ATTRIBUTES,
+        }
+    }
+}
+
+//====================================================================
+
+// A single readback is kept in flight, new queries are only issued once the last
+// results have been read. Each step only happens once the previous one ran, so
+// a frame that skips the queries or resolve doesn't map stale results.
+enum Readback {
+    Idle,
+    // Instances uploaded, waiting for the main pass to draw them
+    Issued,
+    // Drawn, waiting to be resolved into the readback buffer
+    Queried,
+    // Resolve recorded, mapped once the frame has been submitted
+    Resolved,
+    Mapping(Arc<AtomicU8>),
+}
+
+// Progress of a map_async call, written by its callback
+const MAP_PENDING: u8 = 0;
+const MAP_DONE: u8 = 1;
+const MAP_FAILED: u8 = 2;
+
+#[derive(Unique)]
+pub struct OcclusionQueries {
+    pipeline: wgpu::RenderPipeline,
+    cube: Mesh,
+    instances: InstanceBuffer<OcclusionInstanceRaw>,
+
+    query_set: Option<wgpu::QuerySet>,
+    capacity: u32,
+    resolve_buffer: Option<wgpu::Buffer>,
+    readback_buffer: Option<wgpu::Buffer>,
+
+    // Entities being tested and the layers they are drawn on, in instance order
+    tested: Vec<(EntityId, RenderLayers)>,
+    views: u32,
+    readback: Readback,
+}
+
+impl OcclusionQueries {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        layouts: &BindGroupLayoutRegistry,
+    ) -> Self {
+        Self {
+            pipeline: Self::create_pipeline(device, config, layouts),
+            cube: Mesh::new(device, &MeshData::cube(), "Occlusion Cube"),
+            instances: InstanceBuffer::new(device, "Occlusion"),
+
+            query_set: None,
+            capacity: 0,
+            resolve_buffer: None,
+            readback_buffer: None,
+
+            tested: Vec::new(),
+            views: 0,
+            readback: Readback::Idle,
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        layouts: &BindGroupLayoutRegistry,
+    ) -> wgpu::RenderPipeline {
+        render_tools::create_pipeline(
+            device,
+            config,
+            "Occlusion Pipeline",
+            &[layouts.camera()],
+            &[MeshVertex::desc(), OcclusionInstanceRaw::desc()],
+            include_str!("../shaders/occlusion.wgsl"),
+            render_tools::RenderPipelineDescriptor {
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: RawTexture::DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                fragment_targets: Some(&[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::empty(),
+                })]),
+                ..Default::default()
+            }
+            // Boxes surrounding the camera still need to pass
+            .with_face_culling(FaceCulling::NONE),
+        )
+    }
+
+    #[inline]
+    pub fn query_set(&self) -> Option<&wgpu::QuerySet> {
+        match self.readback {
+            Readback::Issued => self.query_set.as_ref(),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn query_count(&self) -> u32 {
+        self.tested.len() as u32 * self.views
+    }
+
+    // Make sure the query set and buffers fit the queries issued this frame
+    fn reserve(&mut self, device: &wgpu::Device, count: u32) {
+        if count <= self.capacity {
+            return;
+        }
+
+        let capacity = count.next_power_of_two().max(64);
+        let size = capacity as wgpu::BufferAddress * std::mem::size_of::<u64>() as u64;
+
+        log::trace!(
+            "Growing occlusion queries from {} to {}",
+            self.capacity,
+            capacity
+        );
+
+        self.query_set = Some(device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Occlusion Query Set"),
+            ty: wgpu::QueryType::Occlusion,
+            count: capacity,
+        }));
+
+        self.resolve_buffer = Some(render_tools::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("Occlusion Resolve Buffer"),
+                size,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            },
+        ));
+
+        self.readback_buffer = Some(render_tools::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("Occlusion Readback Buffer"),
+                size,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        ));
+
+        self.capacity = capacity;
+    }
+
+    // Samples passed by each query, if the last readback has finished. A failed
+    // readback is dropped so new queries can be issued.
+    fn read_results(&mut self) -> Option<Vec<u64>> {
+        match &self.readback {
+            Readback::Mapping(state) => match state.load(Ordering::Acquire) {
+                MAP_DONE => {}
+                MAP_FAILED => {
+                    self.readback = Readback::Idle;
+                    return None;
+                }
+                _ => return None,
+            },
+            _ => return None,
+        }
+
+        let buffer = self.readback_buffer.as_ref()?;
+        let size = self.query_count() as wgpu::BufferAddress * std::mem::size_of::<u64>() as u64;
+
+        let results = {
+            let view = buffer.slice(..size).get_mapped_range();
+            bytemuck::cast_slice::<u8, u64>(&view).to_vec()
+        };
+
+        buffer.unmap();
+        self.readback = Readback::Idle;
+
+        Some(results)
+    }
+}
+
+//====================================================================
+
+fn sys_setup_occlusion(
+    all_storages: AllStoragesView,
+    device: Res<Device>,
+    config: Res<SurfaceConfig>,
+    layouts: Res<BindGroupLayoutRegistry>,
+) {
+    let queries = OcclusionQueries::new(device.inner(), config.inner(), &layouts);
+    all_storages.add_unique(queries);
+}
+
+fn sys_rebuild_occlusion_pipeline(
+    device: Res<Device>,
+    config: Res<SurfaceConfig>,
+    layouts: Res<BindGroupLayoutRegistry>,
+    mut queries: ResMut<OcclusionQueries>,
+) {
+    queries.pipeline = OcclusionQueries::create_pipeline(device.inner(), config.inner(), &layouts);
+}
+
+fn sys_prep_occlusion(
+    device: Res<Device>,
+    queue: Res<Queue>,
+    mut queries: ResMut<OcclusionQueries>,
+
+    camera: Res<MainCamera>,
+    v_cameras: View<SceneCamera>,
+    v_layers: View<RenderLayers>,
+    v_transform: View<Transform>,
+    v_occlusion: View<OcclusionCulled>,
+    mut vm_visibility: ViewMut<Visibility>,
+) {
+    device.inner().poll(wgpu::Maintain::Poll);
+
+    if let Some(results) = queries.read_results() {
+        let views = queries.views as usize;
+
+        queries
+            .tested
+            .iter()
+            .enumerate()
+            .for_each(|(index, (id, _))| {
+                let passed = results[index * views..(index + 1) * views]
+                    .iter()
+                    .any(|samples| *samples > 0);
+
+                if let Ok(visibility) = (&mut vm_visibility).get(*id) {
+                    visibility.set_occluded(!passed);
+                }
+            });
+    }
+
+    if !matches!(queries.readback, Readback::Idle) {
+        return;
+    }
+
+    // Entities no longer tested shouldn't stay hidden
+    (&mut vm_visibility)
+        .iter()
+        .with_id()
+        .filter(|(id, visibility)| visibility.occluded() && !v_occlusion.contains(*id))
+        .for_each(|(_, visibility)| visibility.set_occluded(false));
+
+    let (tested, raw): (Vec<_>, Vec<_>) = (&v_transform, &v_occlusion, &vm_visibility)
+        .iter()
+        .with_id()
+        .filter(|(_, (_, _, visibility))| visibility.visible)
+        .map(|(id, (transform, occlusion, _))| {
+            let matrix = glam::Mat4::from_cols_array(&transform.to_array())
+                * glam::Mat4::from_scale(occlusion.half_extents * 2.);
+
+            let raw = OcclusionInstanceRaw {
+                transform: matrix.to_cols_array(),
+            };
+
+            ((id, RenderLayers::of(&v_layers, id)), raw)
+        })
+        .unzip();
+
+    if tested.is_empty() {
+        queries.tested.clear();
+        return;
+    }
+
+    let views = camera::main_pass_cameras(&camera, &v_cameras, &v_layers).len() as u32;
+
+    queries.tested = tested;
+    queries.views = views;
+
+    let count = queries.query_count();
+    queries.reserve(device.inner(), count);
+    queries
+        .instances
+        .update(device.inner(), queue.inner(), raw.as_slice());
+
+    queries.readback = Readback::Issued;
+}
+
+fn sys_render_occlusion_queries(
+    pass: Option<ResMut<RenderPass>>,
+    mut queries: ResMut<OcclusionQueries>,
+    stats: Res<RenderStats>,
+
+    camera: Res<MainCamera>,
+    v_cameras: View<SceneCamera>,
+    v_layers: View<RenderLayers>,
+) {
+    let mut pass = match pass {
+        Some(pass) if queries.query_set().is_some() => pass,
+        _ => return,
+    };
+
+    let size = pass.size();
+    let pass = pass.pass();
+
+    pass.set_pipeline(&queries.pipeline);
+    pass.set_vertex_buffer(0, queries.cube.vertex_buffer().slice(..));
+    pass.set_vertex_buffer(1, queries.instances.buffer().slice(..));
+    pass.set_index_buffer(
+        queries.cube.index_buffer().slice(..),
+        wgpu::IndexFormat::Uint32,
+    );
+
+    let views = queries.views as usize;
+
+    let counts = camera::main_pass_cameras(&camera, &v_cameras, &v_layers)
+        .into_iter()
+        .take(views)
+        .enumerate()
+        .fold(DrawCounts::pipeline(), |counts, (view_index, view)| {
+            view.viewport.apply(pass, size);
+            pass.set_bind_group(0, view.bind_group, &[]);
+
+            queries
+                .tested
+                .iter()
+                .enumerate()
+                .fold(counts, |mut counts, (index, (_, layers))| {
+                    // Queries for cameras that don't draw the entity never pass
+                    pass.begin_occlusion_query((index * views + view_index) as u32);
+                    if layers.intersects(&view.layers) {
+                        let instance = index as u32;
+                        pass.draw_indexed(0..queries.cube.index_count(), 0, instance..instance + 1);
+                        counts = counts.draw(1);
+                    }
+                    pass.end_occlusion_query();
+
+                    counts
+                })
+        });
+
+    stats.record(counts);
+    queries.readback = Readback::Queried;
+}
+
+fn sys_resolve_occlusion_queries(
+    mut tools: ResMut<RenderEncoder>,
+    mut queries: ResMut<OcclusionQueries>,
+) {
+    if !matches!(queries.readback, Readback::Queried) {
+        return;
+    }
+
+    let (query_set, resolve, readback) = match (
+        &queries.query_set,
+        &queries.resolve_buffer,
+        &queries.readback_buffer,
+    ) {
+        (Some(query_set), Some(resolve), Some(readback)) => (query_set, resolve, readback),
+        _ => return,
+    };
+
+    let count = queries.query_count();
+    let size = count as wgpu::BufferAddress * std::mem::size_of::<u64>() as u64;
+
+    let encoder = tools.encoder();
+    encoder.resolve_query_set(query_set, 0..count, resolve, 0);
+    encoder.copy_buffer_to_buffer(resolve, 0, readback, 0, size);
+
+    queries.readback = Readback::Resolved;
+}
+
+// Queries are only readable once the frame has been submitted
+fn sys_map_occlusion_results(mut queries: ResMut<OcclusionQueries>) {
+    if !matches!(queries.readback, Readback::Resolved) {
+        return;
+    }
+
+    let buffer = match &queries.readback_buffer {
+        Some(buffer) => buffer,
+        None => return,
+    };
+
+    let size = queries.query_count() as wgpu::BufferAddress * std::mem::size_of::<u64>() as u64;
+    let state = Arc::new(AtomicU8::new(MAP_PENDING));
+
+    let callback_state = state.clone();
+    buffer
+        .slice(..size)
+        .map_async(wgpu::MapMode::Read, move |result| match result {
+            Ok(()) => callback_state.store(MAP_DONE, Ordering::Release),
+            Err(e) => {
+                log::warn!("Unable to read occlusion query results: {}", e);
+                callback_state.store(MAP_FAILED, Ordering::Release);
+            }
+        });
+
+    queries.readback = Readback::Mapping(state);
+}
+
+//====================================================================
@@ -0,0 +1,465 @@
+//====================================================================
+
+use std::{
+    collections::{HashMap, HashSet},
+    hash::BuildHasherDefault,
+};
+
+use cabat_assets::{asset_storage::AssetStorage, handle::HandleId};
+use cabat_common::{Color, Size, WindowSize};
+use cabat_shipyard::{prelude::*, UniqueTools};
+use cabat_spatial::Transform;
+use rustc_hash::FxHasher;
+use shipyard::{
+    AllStoragesView, Component, Get, IntoIter, IntoWithId, IntoWorkload, SystemModificator, Unique,
+    View,
+};
+
+use crate::{
+    camera::{self, MainCamera, SceneCamera},
+    default_assets::DefaultRendererAssets,
+    render_graph::{resources, AddRenderPass, RenderGraphNode},
+    render_tools::{self, InstanceBuffer},
+    settings::SurfaceFormatChangedEvent,
+    shared::{
+        BindGroupLayoutRegistry, TextureRectVertex, TEXTURE_RECT_INDEX_COUNT, TEXTURE_RECT_INDICES,
+        TEXTURE_RECT_VERTICES,
+    },
+    texture::{RawTexture, Texture},
+    texture3d_renderer::{AlphaCutout, Sprite, Texture3dInstanceRaw},
+    visibility::{self, RenderLayers, Visibility},
+    Device, DrawCounts, Queue, RenderEncoder, RenderPassDesc, RenderStats, SurfaceConfig, Vertex,
+};
+
+//====================================================================
+
+pub struct OutlinePlugin;
+
+impl Plugin for OutlinePlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        builder.get_or_insert(OutlineSettings::default);
+
+        builder
+            .add_workload_pre(Stages::Setup, sys_setup_outline_renderer)
+            .add_workload_last(Stages::Update, sys_prep_outlines)
+            .add_workload_pre(
+                Stages::Render,
+                sys_resize_outline_mask.skip_if_missing_unique::<RenderEncoder>(),
+            )
+            .add_render_pass(
+                RenderGraphNode::new("outline").writes(resources::SCENE),
+                sys_render_outlines,
+            )
+            .add_event::<SurfaceFormatChangedEvent>(sys_setup_outline_renderer.into_workload());
+    }
+}
+
+//====================================================================
+
+/// Draw an outline around a sprite, on top of anything in front of it. Useful for
+/// selection and highlighting.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Outlined {
+    pub color: Color,
+}
+
+impl Default for Outlined {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            color: Color::srgb(1., 0.6, 0., 1.),
+        }
+    }
+}
+
+impl Outlined {
+    #[inline]
+    pub fn new(color: Color) -> Self {
+        Self { color }
+    }
+}
+
+//--------------------------------------------------
+
+#[derive(Unique, Debug, Clone)]
+pub struct OutlineSettings {
+    /// Outline thickness in pixels, up to [OutlineSettings::MAX_WIDTH].
+    pub width: f32,
+}
+
+impl Default for OutlineSettings {
+    fn default() -> Self {
+        Self { width: 3. }
+    }
+}
+
+impl OutlineSettings {
+    // Each pixel searches the whole square around it, so keep this small
+    pub const MAX_WIDTH: f32 = 16.;
+}
+
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+struct OutlineUniformRaw {
+    width: f32,
+    _padding: [f32; 3],
+}
+
+impl OutlineUniformRaw {
+    fn new(settings: &OutlineSettings) -> Self {
+        Self {
+            width: settings.width.clamp(0., OutlineSettings::MAX_WIDTH),
+            _padding: [0.; 3],
+        }
+    }
+}
+
+//====================================================================
+
+#[derive(Unique)]
+pub struct OutlineRenderer {
+    mask_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_count: u32,
+
+    // Outlined sprites are drawn in their outline color, then edges of the mask
+    // are found when compositing
+    mask: RawTexture,
+    composite_bind_group_layout: wgpu::BindGroupLayout,
+    composite_bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+
+    instances: HashMap<
+        (HandleId, RenderLayers),
+        InstanceBuffer<Texture3dInstanceRaw>,
+        BuildHasherDefault<FxHasher>,
+    >,
+}
+
+impl OutlineRenderer {
+    const MASK_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        layouts: &BindGroupLayoutRegistry,
+        settings: &OutlineSettings,
+        size: Size<u32>,
+    ) -> Self {
+        let mask_pipeline = render_tools::create_pipeline(
+            device,
+            config,
+            "Outline Mask Pipeline",
+            &[layouts.camera(), layouts.texture()],
+            &[TextureRectVertex::desc(), Texture3dInstanceRaw::desc()],
+            include_str!("../shaders/outline_mask.wgsl"),
+            render_tools::RenderPipelineDescriptor {
+                fragment_targets: Some(&[Some(wgpu::ColorTargetState {
+                    format: Self::MASK_FORMAT,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::all(),
+                })]),
+                ..Default::default()
+            },
+        );
+
+        let composite_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Outline Composite Bind Group Layout"),
+                entries: &[
+                    render_tools::bgl_texture_entry(0),
+                    render_tools::bgl_uniform_entry(1, wgpu::ShaderStages::FRAGMENT),
+                ],
+            });
+
+        let composite_pipeline = render_tools::create_pipeline(
+            device,
+            config,
+            "Outline Composite Pipeline",
+            &[&composite_bind_group_layout],
+            &[],
+            include_str!("../shaders/outline.wgsl"),
+            render_tools::RenderPipelineDescriptor {
+                fragment_targets: Some(&[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::all(),
+                })]),
+                ..Default::default()
+            },
+        );
+
+        let vertex_buffer = render_tools::vertex_buffer(device, "Outline", &TEXTURE_RECT_VERTICES);
+        let index_buffer = render_tools::index_buffer(device, "Outline", &TEXTURE_RECT_INDICES);
+
+        let uniform_buffer = render_tools::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Outline Uniform Buffer"),
+                contents: bytemuck::bytes_of(&OutlineUniformRaw::new(settings)),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        );
+
+        let mask = Self::create_mask(device, size);
+        let composite_bind_group = Self::create_composite_bind_group(
+            device,
+            &composite_bind_group_layout,
+            &mask,
+            &uniform_buffer,
+        );
+
+        Self {
+            mask_pipeline,
+            composite_pipeline,
+
+            vertex_buffer,
+            index_buffer,
+            index_count: TEXTURE_RECT_INDEX_COUNT,
+
+            mask,
+            composite_bind_group_layout,
+            composite_bind_group,
+            uniform_buffer,
+
+            instances: HashMap::default(),
+        }
+    }
+
+    fn create_mask(device: &wgpu::Device, size: Size<u32>) -> RawTexture {
+        let size = Size::new(size.width.max(1), size.height.max(1));
+        RawTexture::create_render_target(device, size, Self::MASK_FORMAT, "Outline Mask")
+    }
+
+    fn create_composite_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        mask: &RawTexture,
+        uniform_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Outline Composite Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&mask.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, size: Size<u32>) {
+        self.mask = Self::create_mask(device, size);
+        self.composite_bind_group = Self::create_composite_bind_group(
+            device,
+            &self.composite_bind_group_layout,
+            &self.mask,
+            &self.uniform_buffer,
+        );
+    }
+
+    /// Draw outlined sprites on any of the camera's layers into the mask. Returns
+    /// the number of draw calls.
+    fn render_mask(
+        &self,
+        pass: &mut wgpu::RenderPass,
+        camera_bind_group: &wgpu::BindGroup,
+        camera_layers: RenderLayers,
+        storage: &AssetStorage,
+    ) -> DrawCounts {
+        pass.set_pipeline(&self.mask_pipeline);
+        pass.set_bind_group(0, camera_bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+
+        self.instances
+            .iter()
+            .filter(|((_, layers), instances)| {
+                !instances.is_empty() && layers.intersects(&camera_layers)
+            })
+            .fold(DrawCounts::pipeline(), |counts, ((id, _), instances)| {
+                let texture = match storage.get_asset::<Texture>(*id) {
+                    Some(texture) => texture,
+                    None => return counts,
+                };
+
+                pass.set_bind_group(1, texture.binding(), &[]);
+                pass.set_vertex_buffer(1, instances.buffer().slice(..));
+                pass.draw_indexed(0..self.index_count, 0, 0..instances.count());
+
+                counts.draw(instances.count())
+            })
+    }
+}
+
+//====================================================================
+
+fn sys_setup_outline_renderer(
+    all_storages: AllStoragesView,
+    device: Res<Device>,
+    config: Res<SurfaceConfig>,
+    layouts: Res<BindGroupLayoutRegistry>,
+    settings: Res<OutlineSettings>,
+    size: Res<WindowSize>,
+) {
+    let renderer = OutlineRenderer::new(
+        device.inner(),
+        config.inner(),
+        &layouts,
+        &settings,
+        size.size(),
+    );
+
+    all_storages.add_unique(renderer);
+}
+
+// The mask matches whatever the scene is being drawn into, which isn't always
+// the size of the window
+fn sys_resize_outline_mask(
+    device: Res<Device>,
+    tools: Res<RenderEncoder>,
+    mut renderer: ResMut<OutlineRenderer>,
+) {
+    let size = tools.scene_size();
+    let mask_size = renderer.mask.texture.size();
+
+    if mask_size.width != size.width.max(1) || mask_size.height != size.height.max(1) {
+        renderer.resize(device.inner(), size);
+    }
+}
+
+fn sys_prep_outlines(
+    device: Res<Device>,
+    queue: Res<Queue>,
+    settings: Res<OutlineSettings>,
+    defaults: Res<DefaultRendererAssets>,
+    mut renderer: ResMut<OutlineRenderer>,
+
+    v_outlined: View<Outlined>,
+    v_sprite: View<Sprite>,
+    v_transform: View<Transform>,
+    v_visibility: View<Visibility>,
+    v_layers: View<RenderLayers>,
+    v_cutout: View<AlphaCutout>,
+) {
+    if settings.is_modified() {
+        queue.inner().write_buffer(
+            &renderer.uniform_buffer,
+            0,
+            bytemuck::bytes_of(&OutlineUniformRaw::new(&settings)),
+        );
+    }
+
+    let instances = (&v_outlined, &v_transform, &v_sprite)
+        .iter()
+        .with_id()
+        .filter(|(id, _)| visibility::is_visible(&v_visibility, *id))
+        .fold(
+            HashMap::new(),
+            |mut acc, (id, (outlined, transform, sprite))| {
+                // Only the visible part of the sprite is outlined
+                let cutout = v_cutout.get(id).copied().unwrap_or_default();
+
+                let instance = Texture3dInstanceRaw {
+                    size: [sprite.width, sprite.height],
+                    transform: transform.to_array(),
+                    color: outlined.color.into(),
+                    alpha_cutoff: cutout.0,
+                };
+
+                let texture = match &sprite.texture {
+                    Some(texture) => texture.id(),
+                    None => defaults.white_texture.id(),
+                };
+
+                acc.entry((texture, RenderLayers::of(&v_layers, id)))
+                    .or_insert(Vec::new())
+                    .push(instance);
+
+                acc
+            },
+        );
+
+    let mut previous = renderer.instances.keys().copied().collect::<HashSet<_>>();
+
+    instances.into_iter().for_each(|(key, raw)| {
+        previous.remove(&key);
+
+        renderer
+            .instances
+            .entry(key)
+            .or_insert_with(|| InstanceBuffer::new(device.inner(), "Outline"))
+            .update(device.inner(), queue.inner(), raw.as_slice());
+    });
+
+    previous.into_iter().for_each(|to_remove| {
+        renderer.instances.remove(&to_remove);
+    });
+}
+
+fn sys_render_outlines(
+    mut tools: ResMut<RenderEncoder>,
+    renderer: Res<OutlineRenderer>,
+    storage: Res<AssetStorage>,
+    stats: Res<RenderStats>,
+
+    camera: Res<MainCamera>,
+    v_cameras: View<SceneCamera>,
+    v_layers: View<RenderLayers>,
+) {
+    if renderer
+        .instances
+        .values()
+        .all(|instances| instances.is_empty())
+    {
+        return;
+    }
+
+    let size = tools.scene_size();
+
+    {
+        let mut pass = tools
+            .encoder()
+            .begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Outline Mask Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &renderer.mask.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+        camera::main_pass_cameras(&camera, &v_cameras, &v_layers)
+            .into_iter()
+            .for_each(|view| {
+                view.viewport.apply(&mut pass, size);
+
+                let counts =
+                    renderer.render_mask(&mut pass, view.bind_group, view.layers, &storage);
+                stats.record(counts);
+            });
+    }
+
+    let mut pass = tools.begin_render_pass(RenderPassDesc::none());
+
+    pass.set_pipeline(&renderer.composite_pipeline);
+    pass.set_bind_group(0, &renderer.composite_bind_group, &[]);
+    pass.draw(0..3, 0..1);
+    stats.record(DrawCounts::pipeline().draw(1));
+}
+
+//====================================================================
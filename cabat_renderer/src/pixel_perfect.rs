@@ -0,0 +1,319 @@
+//====================================================================
+
+use cabat_common::{Color, Size, WindowSize};
+use cabat_shipyard::{prelude::*, UniqueTools};
+use shipyard::{AllStoragesView, IntoWorkload, Unique, WorkloadModificator};
+
+use crate::{
+    camera::Viewport,
+    render_graph::{resources, AddRenderPass, RenderGraphNode},
+    render_tools,
+    settings::SurfaceFormatChangedEvent,
+    shared::BindGroupLayoutRegistry,
+    texture::{DepthTexture, RawTexture},
+    Device, DrawCounts, RenderEncoder, RenderPassDesc, RenderStats, SurfaceConfig,
+};
+
+//====================================================================
+
+/// Draws the scene at a fixed low resolution and scales it up to the surface
+/// with nearest filtering. Screen space passes like text and nine slices are
+/// drawn afterwards at the full resolution. Add after the
+/// [CoreRendererPlugin](crate::CoreRendererPlugin).
+pub struct PixelPerfectPlugin;
+
+impl Plugin for PixelPerfectPlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        builder.get_or_insert(PixelPerfect::default);
+
+        builder
+            .describe_order(Stages::Render, &sys_redirect_scene, &[], &["setup_encoder"])
+            .add_workload_pre(Stages::Setup, sys_setup_pixel_perfect_renderer)
+            .add_workload_last(Stages::First, sys_resize_pixel_perfect_target)
+            .add_workload_first(
+                Stages::Render,
+                sys_redirect_scene
+                    .into_workload()
+                    .skip_if_missing_unique::<RenderEncoder>()
+                    .after_all("setup_encoder"),
+            )
+            .add_render_pass(
+                RenderGraphNode::new("pixel_perfect_upscale")
+                    .reads(resources::SCENE)
+                    .writes(resources::SCENE_OUTPUT),
+                sys_render_upscale,
+            )
+            .add_event::<SurfaceFormatChangedEvent>(
+                sys_setup_pixel_perfect_renderer.into_workload(),
+            );
+    }
+}
+
+//====================================================================
+
+/// Insert before adding the [PixelPerfectPlugin] to configure it. Can be
+/// changed at any time.
+///
+/// Cameras should use the aspect ratio of the resolution, and positions on the
+/// window can be mapped onto the scene with [PixelPerfect::scene_position].
+#[derive(Unique, Debug, Clone)]
+pub struct PixelPerfect {
+    /// Size of the texture the scene is drawn into.
+    pub resolution: Size<u32>,
+    /// Only scale the scene by whole numbers so every scene pixel covers the
+    /// same number of surface pixels. Ignored when the surface is smaller than
+    /// the resolution.
+    pub integer_scaling: bool,
+    /// Fills the surface around the scaled scene.
+    pub bar_color: Color,
+}
+
+impl Default for PixelPerfect {
+    fn default() -> Self {
+        Self {
+            resolution: Size::new(320, 180),
+            integer_scaling: true,
+            bar_color: Color::BLACK,
+        }
+    }
+}
+
+impl PixelPerfect {
+    #[inline]
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            resolution: Size::new(width, height),
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn with_integer_scaling(mut self, integer_scaling: bool) -> Self {
+        self.integer_scaling = integer_scaling;
+        self
+    }
+
+    #[inline]
+    pub fn with_bar_color(mut self, bar_color: Color) -> Self {
+        self.bar_color = bar_color;
+        self
+    }
+
+    // Resolution with both sides at least a pixel, as textures can't be empty
+    #[inline]
+    fn texture_size(&self) -> Size<u32> {
+        Size::new(self.resolution.width.max(1), self.resolution.height.max(1))
+    }
+
+    /// Area of a surface the scaled up scene covers, centered and snapped to
+    /// whole pixels.
+    pub fn viewport(&self, size: Size<u32>) -> Viewport {
+        if size.width == 0 || size.height == 0 {
+            return Viewport::FULL;
+        }
+
+        let resolution = self.texture_size();
+
+        let width = size.width as f32;
+        let height = size.height as f32;
+
+        let scale = (width / resolution.width as f32).min(height / resolution.height as f32);
+        let scale = match self.integer_scaling && scale >= 1. {
+            true => scale.floor(),
+            false => scale,
+        };
+
+        let inner_width = (resolution.width as f32 * scale).round();
+        let inner_height = (resolution.height as f32 * scale).round();
+
+        let x = ((width - inner_width) / 2.).floor();
+        let y = ((height - inner_height) / 2.).floor();
+
+        Viewport::new(
+            x / width,
+            y / height,
+            inner_width / width,
+            inner_height / height,
+        )
+    }
+
+    /// Position in scene pixels from a position on the window, or `None` if
+    /// it's outside of the scaled up scene.
+    pub fn scene_position(
+        &self,
+        screen_pos: glam::Vec2,
+        window_size: &WindowSize,
+    ) -> Option<glam::Vec2> {
+        let size = window_size.size();
+        let viewport = self.viewport(size);
+
+        let [_, _, width, height] = viewport.to_pixels(size);
+        let local = viewport.local_position(screen_pos, size);
+        if local.x < 0. || local.y < 0. || local.x >= width || local.y >= height {
+            return None;
+        }
+
+        let resolution = self.texture_size();
+
+        Some(glam::vec2(
+            local.x / width * resolution.width as f32,
+            local.y / height * resolution.height as f32,
+        ))
+    }
+}
+
+//====================================================================
+
+#[derive(Unique)]
+pub struct PixelPerfectRenderer {
+    pipeline: wgpu::RenderPipeline,
+    target: RawTexture,
+    bind_group: wgpu::BindGroup,
+}
+
+impl PixelPerfectRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        layouts: &BindGroupLayoutRegistry,
+        resolution: Size<u32>,
+    ) -> Self {
+        let pipeline = render_tools::create_pipeline(
+            device,
+            config,
+            "Pixel Perfect Pipeline",
+            &[layouts.texture()],
+            &[],
+            include_str!("../shaders/pixel_perfect.wgsl"),
+            render_tools::RenderPipelineDescriptor::default(),
+        );
+
+        let (target, bind_group) = Self::create_target(device, config, layouts, resolution);
+
+        Self {
+            pipeline,
+            target,
+            bind_group,
+        }
+    }
+
+    // Scene pipelines are built for the surface format, so the target uses it too
+    fn create_target(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        layouts: &BindGroupLayoutRegistry,
+        resolution: Size<u32>,
+    ) -> (RawTexture, wgpu::BindGroup) {
+        let mut target =
+            RawTexture::create_render_target(device, resolution, config.format, "Pixel Perfect");
+
+        target.sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Pixel Perfect Sampler"),
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let bind_group = layouts.create_bind_group(device, &target, Some("Pixel Perfect"));
+
+        (target, bind_group)
+    }
+
+    #[inline]
+    pub fn resolution(&self) -> Size<u32> {
+        let size = self.target.texture.size();
+        Size::new(size.width, size.height)
+    }
+
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        layouts: &BindGroupLayoutRegistry,
+        resolution: Size<u32>,
+    ) {
+        let (target, bind_group) = Self::create_target(device, config, layouts, resolution);
+
+        self.target = target;
+        self.bind_group = bind_group;
+    }
+}
+
+//====================================================================
+
+fn sys_setup_pixel_perfect_renderer(
+    all_storages: AllStoragesView,
+    device: Res<Device>,
+    config: Res<SurfaceConfig>,
+    layouts: Res<BindGroupLayoutRegistry>,
+    settings: Res<PixelPerfect>,
+    size: Res<WindowSize>,
+    mut depth: ResMut<DepthTexture>,
+) {
+    let resolution = settings.texture_size();
+
+    let renderer = PixelPerfectRenderer::new(device.inner(), config.inner(), &layouts, resolution);
+    all_storages.add_unique(renderer);
+
+    depth.set_fixed_size(device.inner(), Some(resolution), size.size());
+}
+
+// Runs before Update so the depth texture is already resized when other
+// plugins prepare anything depending on it.
+fn sys_resize_pixel_perfect_target(
+    device: Res<Device>,
+    config: Res<SurfaceConfig>,
+    layouts: Res<BindGroupLayoutRegistry>,
+    settings: Res<PixelPerfect>,
+    size: Res<WindowSize>,
+    mut renderer: ResMut<PixelPerfectRenderer>,
+    mut depth: ResMut<DepthTexture>,
+) {
+    let resolution = settings.texture_size();
+
+    if renderer.resolution() != resolution {
+        renderer.resize(device.inner(), config.inner(), &layouts, resolution);
+    }
+
+    if depth.fixed_size() != Some(resolution) {
+        depth.set_fixed_size(device.inner(), Some(resolution), size.size());
+    }
+}
+
+fn sys_redirect_scene(mut tools: ResMut<RenderEncoder>, renderer: Res<PixelPerfectRenderer>) {
+    let view = renderer
+        .target
+        .texture
+        .create_view(&wgpu::TextureViewDescriptor::default());
+
+    tools.set_scene_target(view, renderer.resolution());
+}
+
+fn sys_render_upscale(
+    mut tools: ResMut<RenderEncoder>,
+    renderer: Res<PixelPerfectRenderer>,
+    settings: Res<PixelPerfect>,
+    config: Res<SurfaceConfig>,
+    stats: Res<RenderStats>,
+) {
+    // Everything from here on is drawn at the surface's resolution
+    tools.take_scene_target();
+
+    let mut pass = tools.begin_render_pass(RenderPassDesc {
+        use_depth: None,
+        clear_color: Some(settings.bar_color.to_array().map(|value| value as f64)),
+        occlusion_query_set: None,
+    });
+
+    settings
+        .viewport(config.size())
+        .apply(&mut pass, config.size());
+
+    pass.set_pipeline(&renderer.pipeline);
+    pass.set_bind_group(0, &renderer.bind_group, &[]);
+    pass.draw(0..3, 0..1);
+
+    stats.record(DrawCounts::pipeline().draw(1));
+}
+
+//====================================================================
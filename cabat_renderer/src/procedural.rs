@@ -0,0 +1,306 @@
+//====================================================================
+
+use cabat_common::Color;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    shared::BindGroupLayoutRegistry,
+    texture::{RawTexture, Texture},
+};
+
+//====================================================================
+
+/// Describes a texture generated at load time instead of read from an image,
+/// for prototyping without shipping image files. Loaded through the asset
+/// storage from `.texgen` files holding the descriptor as ron, or created
+/// directly with [ProceduralTexture::create_texture].
+///
+/// ```ron
+/// (
+///     width: 256,
+///     height: 256,
+///     pattern: Noise(kind: Perlin, seed: 7, scale: 64., octaves: 4, low: "#203040", high: "#a0c0e0"),
+/// )
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProceduralTexture {
+    pub width: u32,
+    pub height: u32,
+    pub pattern: Pattern,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Pattern {
+    /// Fractal noise blended between two colors.
+    Noise {
+        kind: NoiseKind,
+        seed: u32,
+        /// Size in pixels of the largest features.
+        scale: f32,
+        /// Layers of finer detail added on top, each at twice the frequency
+        /// and half the strength of the last.
+        octaves: u32,
+        #[serde(with = "hex_color")]
+        low: Color,
+        #[serde(with = "hex_color")]
+        high: Color,
+    },
+    Gradient {
+        direction: GradientDirection,
+        #[serde(with = "hex_color")]
+        start: Color,
+        #[serde(with = "hex_color")]
+        end: Color,
+    },
+    Checkerboard {
+        /// Size in pixels of each square.
+        cell_size: u32,
+        #[serde(with = "hex_color")]
+        a: Color,
+        #[serde(with = "hex_color")]
+        b: Color,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NoiseKind {
+    Perlin,
+    Simplex,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GradientDirection {
+    /// Left to right.
+    Horizontal,
+    /// Top to bottom.
+    Vertical,
+    /// Center to the corners.
+    Radial,
+}
+
+// Colors are written as srgb hex codes in descriptor files
+mod hex_color {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        let [r, g, b, a] = color.to_srgb_u8();
+        serializer.serialize_str(&format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        Color::hex(&hex).map_err(|e| serde::de::Error::custom(format!("{:?}", e)))
+    }
+}
+
+//====================================================================
+
+impl ProceduralTexture {
+    #[inline]
+    pub fn new(width: u32, height: u32, pattern: Pattern) -> Self {
+        Self {
+            width,
+            height,
+            pattern,
+        }
+    }
+
+    /// Parse a descriptor from ron.
+    #[inline]
+    pub fn from_ron(text: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(text)
+    }
+
+    /// Pixels of the texture in srgb.
+    pub fn generate(&self) -> image::RgbaImage {
+        let width = self.width.max(1);
+        let height = self.height.max(1);
+
+        image::RgbaImage::from_fn(width, height, |x, y| {
+            // Sample pixel centers
+            let position = glam::vec2(x as f32 + 0.5, y as f32 + 0.5);
+            let size = glam::vec2(width as f32, height as f32);
+
+            image::Rgba(self.pattern.color(position, size).to_srgb_u8())
+        })
+    }
+
+    pub fn create_texture(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layouts: &BindGroupLayoutRegistry,
+        label: Option<&str>,
+    ) -> Texture {
+        let image = image::DynamicImage::ImageRgba8(self.generate());
+        let raw = RawTexture::from_image(device, queue, &image, label, None);
+
+        layouts.load_texture(device, raw, label)
+    }
+}
+
+impl Pattern {
+    fn color(&self, position: glam::Vec2, size: glam::Vec2) -> Color {
+        match self {
+            Pattern::Noise {
+                kind,
+                seed,
+                scale,
+                octaves,
+                low,
+                high,
+            } => {
+                let position = position / scale.max(f32::EPSILON);
+                let value = fractal_noise(*kind, *seed, position, (*octaves).max(1));
+
+                mix(*low, *high, value)
+            }
+
+            Pattern::Gradient {
+                direction,
+                start,
+                end,
+            } => {
+                let amount = match direction {
+                    GradientDirection::Horizontal => position.x / size.x,
+                    GradientDirection::Vertical => position.y / size.y,
+                    GradientDirection::Radial => {
+                        let half = size / 2.;
+                        (position - half).length() / half.length()
+                    }
+                };
+
+                mix(*start, *end, amount)
+            }
+
+            Pattern::Checkerboard { cell_size, a, b } => {
+                let cell = (position / (*cell_size).max(1) as f32).floor();
+
+                match (cell.x + cell.y).rem_euclid(2.) < 1. {
+                    true => *a,
+                    false => *b,
+                }
+            }
+        }
+    }
+}
+
+// Blended in linear space
+#[inline]
+fn mix(start: Color, end: Color, amount: f32) -> Color {
+    let start = glam::Vec4::from_array(start.to_array());
+    let end = glam::Vec4::from_array(end.to_array());
+
+    start.lerp(end, amount.clamp(0., 1.)).to_array().into()
+}
+
+//====================================================================
+
+/// Noise layered over several octaves, between 0 and 1.
+pub fn fractal_noise(kind: NoiseKind, seed: u32, position: glam::Vec2, octaves: u32) -> f32 {
+    let (total, max) = (0..octaves).fold((0., 0.), |(total, max), octave| {
+        let frequency = (1 << octave.min(16)) as f32;
+        let amplitude = 1. / frequency;
+
+        // Each octave gets its own seed so their features don't line up
+        let seed = seed.wrapping_add(octave.wrapping_mul(0x9e37_79b9));
+        let value = match kind {
+            NoiseKind::Perlin => perlin(seed, position * frequency),
+            NoiseKind::Simplex => simplex(seed, position * frequency),
+        };
+
+        (total + value * amplitude, max + amplitude)
+    });
+
+    (total / max * 0.5 + 0.5).clamp(0., 1.)
+}
+
+// Integer hash of a lattice point
+#[inline]
+fn hash(seed: u32, x: i32, y: i32) -> u32 {
+    let mut value =
+        seed ^ (x as u32).wrapping_mul(0x27d4_eb2d) ^ (y as u32).wrapping_mul(0x1656_67b1);
+
+    value ^= value >> 15;
+    value = value.wrapping_mul(0x85eb_ca6b);
+    value ^= value >> 13;
+    value = value.wrapping_mul(0xc2b2_ae35);
+    value ^ (value >> 16)
+}
+
+// One of eight evenly spread unit directions for a lattice point
+#[inline]
+fn gradient(seed: u32, x: i32, y: i32) -> glam::Vec2 {
+    const DIAGONAL: f32 = std::f32::consts::FRAC_1_SQRT_2;
+    const GRADIENTS: [glam::Vec2; 8] = [
+        glam::Vec2::new(1., 0.),
+        glam::Vec2::new(-1., 0.),
+        glam::Vec2::new(0., 1.),
+        glam::Vec2::new(0., -1.),
+        glam::Vec2::new(DIAGONAL, DIAGONAL),
+        glam::Vec2::new(-DIAGONAL, DIAGONAL),
+        glam::Vec2::new(DIAGONAL, -DIAGONAL),
+        glam::Vec2::new(-DIAGONAL, -DIAGONAL),
+    ];
+
+    GRADIENTS[(hash(seed, x, y) & 7) as usize]
+}
+
+/// Classic perlin noise, roughly between -1 and 1.
+pub fn perlin(seed: u32, position: glam::Vec2) -> f32 {
+    let cell = position.floor();
+    let local = position - cell;
+    let (x, y) = (cell.x as i32, cell.y as i32);
+
+    let corner = |offset_x: i32, offset_y: i32| {
+        let offset = glam::vec2(offset_x as f32, offset_y as f32);
+        gradient(seed, x + offset_x, y + offset_y).dot(local - offset)
+    };
+
+    let fade = |t: f32| t * t * t * (t * (t * 6. - 15.) + 10.);
+    let (u, v) = (fade(local.x), fade(local.y));
+
+    let top = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * u;
+    let bottom = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * u;
+
+    // Scaled up from the +-0.71 a unit gradient can reach
+    (top + (bottom - top) * v) * std::f32::consts::SQRT_2
+}
+
+/// Simplex noise, roughly between -1 and 1. Cheaper than perlin with fewer
+/// directional artifacts.
+pub fn simplex(seed: u32, position: glam::Vec2) -> f32 {
+    const SKEW: f32 = 0.366_025_42; // (sqrt(3) - 1) / 2
+    const UNSKEW: f32 = 0.211_324_87; // (3 - sqrt(3)) / 6
+
+    let skewed = (position + glam::Vec2::splat((position.x + position.y) * SKEW)).floor();
+    let origin = skewed - glam::Vec2::splat((skewed.x + skewed.y) * UNSKEW);
+    let first = position - origin;
+
+    // Which of the two triangles in the skewed cell the point is in
+    let step = match first.x > first.y {
+        true => glam::ivec2(1, 0),
+        false => glam::ivec2(0, 1),
+    };
+
+    let second = first - step.as_vec2() + glam::Vec2::splat(UNSKEW);
+    let third = first - glam::Vec2::ONE + glam::Vec2::splat(2. * UNSKEW);
+
+    let (x, y) = (skewed.x as i32, skewed.y as i32);
+
+    let contribution = |offset: glam::Vec2, corner: glam::IVec2| {
+        let falloff = 0.5 - offset.length_squared();
+        match falloff > 0. {
+            true => falloff.powi(4) * gradient(seed, x + corner.x, y + corner.y).dot(offset),
+            false => 0.,
+        }
+    };
+
+    let total = contribution(first, glam::IVec2::ZERO)
+        + contribution(second, step)
+        + contribution(third, glam::IVec2::ONE);
+
+    (total * 70.).clamp(-1., 1.)
+}
+
+//====================================================================
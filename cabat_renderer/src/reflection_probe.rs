@@ -0,0 +1,411 @@
+//====================================================================
+
+use std::collections::HashSet;
+
+use cabat_assets::asset_storage::AssetStorage;
+use cabat_common::Size;
+use cabat_shipyard::prelude::*;
+use cabat_spatial::Transform;
+use shipyard::{Component, EntityId, Get, IntoIter, IntoWithId, IntoWorkload, View, ViewMut};
+
+use crate::{
+    camera::PerspectiveCamera,
+    render_graph::{resources, AddRenderPass, RenderGraphNode},
+    render_target::{RenderTarget, RenderTargetDescriptor},
+    render_tools,
+    settings::SurfaceFormatChangedEvent,
+    shared::BindGroupLayoutRegistry,
+    visibility::RenderLayers,
+    Device, Queue, RenderEncoder, SurfaceConfig,
+};
+
+//====================================================================
+
+pub struct ReflectionProbePlugin;
+
+impl Plugin for ReflectionProbePlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        builder
+            // Before culling so active faces are counted as cameras
+            .add_workload_post(Stages::Update, sys_prep_reflection_probes)
+            .add_render_pass(
+                RenderGraphNode::new("reflection_probes").reads(resources::RENDER_TARGETS),
+                sys_capture_reflection_probes,
+            )
+            .add_event::<SurfaceFormatChangedEvent>(sys_recreate_probe_cubemaps.into_workload());
+    }
+}
+
+//====================================================================
+
+// Forward and up of each cubemap face, in layer order (+X, -X, +Y, -Y, +Z, -Z)
+const FACES: [(glam::Vec3, glam::Vec3); 6] = [
+    (glam::Vec3::X, glam::Vec3::Y),
+    (glam::Vec3::NEG_X, glam::Vec3::Y),
+    (glam::Vec3::Y, glam::Vec3::NEG_Z),
+    (glam::Vec3::NEG_Y, glam::Vec3::Z),
+    (glam::Vec3::Z, glam::Vec3::Y),
+    (glam::Vec3::NEG_Z, glam::Vec3::Y),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProbeRefresh {
+    /// Capture once the probe is created and again whenever requested.
+    #[default]
+    Once,
+    /// Capture every frame. Draws the scene six more times so keep these rare.
+    EveryFrame,
+}
+
+/// Captures the scene around its [Transform] into a small cubemap, for shaders
+/// wanting cheap reflections. Faces are drawn by the render target passes, using
+/// the probe's [RenderLayers].
+#[derive(Component)]
+pub struct ReflectionProbe {
+    pub resolution: u32,
+    pub z_near: f32,
+    pub z_far: f32,
+    pub clear_color: [f64; 4],
+    pub refresh: ProbeRefresh,
+
+    pending: bool,
+    captured: bool,
+    cubemap: Option<ProbeCubemap>,
+}
+
+impl Default for ReflectionProbe {
+    fn default() -> Self {
+        Self {
+            resolution: 128,
+            z_near: 0.1,
+            z_far: 1000.,
+            clear_color: [0.2, 0.2, 0.2, 1.],
+            refresh: ProbeRefresh::Once,
+
+            pending: true,
+            captured: false,
+            cubemap: None,
+        }
+    }
+}
+
+impl ReflectionProbe {
+    #[inline]
+    pub fn new(resolution: u32) -> Self {
+        Self {
+            resolution,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn with_refresh(mut self, refresh: ProbeRefresh) -> Self {
+        self.refresh = refresh;
+        self
+    }
+
+    /// Capture the scene again next frame.
+    #[inline]
+    pub fn request_capture(&mut self) {
+        self.pending = true;
+    }
+
+    /// Whether the cubemap has been drawn into at least once.
+    #[inline]
+    pub fn captured(&self) -> bool {
+        self.captured
+    }
+
+    /// Cubemap bound with the [LayoutKey::Cubemap](crate::shared::LayoutKey::Cubemap)
+    /// layout. Available after the probe's first update.
+    #[inline]
+    pub fn cubemap(&self) -> Option<&ProbeCubemap> {
+        self.cubemap.as_ref()
+    }
+
+    #[inline]
+    fn capturing(&self) -> bool {
+        self.pending || self.refresh == ProbeRefresh::EveryFrame
+    }
+
+    fn face_camera(&self, position: glam::Vec3, face: usize) -> PerspectiveCamera {
+        let (forward, up) = FACES[face];
+
+        PerspectiveCamera {
+            up,
+            aspect: 1.,
+            fovy: std::f32::consts::FRAC_PI_2,
+            z_near: self.z_near,
+            z_far: self.z_far,
+            translation: position,
+            rotation: glam::Quat::from_rotation_arc(glam::Vec3::Z, forward),
+            ..Default::default()
+        }
+    }
+}
+
+//--------------------------------------------------
+
+/// One face of a [ReflectionProbe], spawned alongside a [RenderTarget] that is
+/// only active while the probe captures.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct ReflectionProbeFace {
+    pub probe: EntityId,
+    pub face: u32,
+}
+
+//====================================================================
+
+pub struct ProbeCubemap {
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+}
+
+impl ProbeCubemap {
+    fn new(
+        device: &wgpu::Device,
+        layouts: &BindGroupLayoutRegistry,
+        format: wgpu::TextureFormat,
+        resolution: u32,
+    ) -> Self {
+        let texture = render_tools::create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label: Some("Reflection Probe Cubemap"),
+                size: wgpu::Extent3d {
+                    width: resolution,
+                    height: resolution,
+                    depth_or_array_layers: 6,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Reflection Probe Cubemap View"),
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Reflection Probe Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Reflection Probe Bind Group"),
+            layout: layouts.cubemap(),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        Self {
+            texture,
+            bind_group,
+        }
+    }
+
+    #[inline]
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    fn copy_face(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::Texture,
+        face: u32,
+        resolution: u32,
+    ) {
+        encoder.copy_texture_to_texture(
+            source.as_image_copy(),
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: 0,
+                    y: 0,
+                    z: face,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::Extent3d {
+                width: resolution,
+                height: resolution,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+//====================================================================
+
+fn sys_prep_reflection_probes(
+    device: Res<Device>,
+    queue: Res<Queue>,
+    config: Res<SurfaceConfig>,
+    layouts: Res<BindGroupLayoutRegistry>,
+    mut storage: ResMut<AssetStorage>,
+    mut commands: ResMut<Commands>,
+
+    mut vm_probe: ViewMut<ReflectionProbe>,
+    mut vm_target: ViewMut<RenderTarget>,
+    v_face: View<ReflectionProbeFace>,
+    v_transform: View<Transform>,
+    v_layers: View<RenderLayers>,
+) {
+    // New probes get their cubemap now and their faces once commands are applied
+    (&mut vm_probe)
+        .iter()
+        .with_id()
+        .filter(|(_, probe)| probe.cubemap.is_none())
+        .for_each(|(id, probe)| {
+            let resolution = probe.resolution.max(1);
+            probe.resolution = resolution;
+            probe.cubemap = Some(ProbeCubemap::new(
+                device.inner(),
+                &layouts,
+                config.inner().format,
+                resolution,
+            ));
+
+            let position = v_transform
+                .get(id)
+                .map(|transform| transform.translation)
+                .unwrap_or_default();
+
+            let layers = RenderLayers::of(&v_layers, id);
+
+            (0..FACES.len()).for_each(|face| {
+                let mut target = RenderTarget::new(
+                    device.inner(),
+                    config.inner(),
+                    &layouts,
+                    &mut storage,
+                    &probe.face_camera(position, face),
+                    &RenderTargetDescriptor {
+                        label: "Reflection Probe Face",
+                        size: Size::new(resolution, resolution),
+                        clear_color: probe.clear_color,
+                    },
+                );
+                target.active = false;
+
+                let face = ReflectionProbeFace {
+                    probe: id,
+                    face: face as u32,
+                };
+
+                commands.spawn((target, face, layers));
+            });
+        });
+
+    (&v_face, &mut vm_target)
+        .iter()
+        .with_id()
+        .for_each(|(id, (face, target))| {
+            let probe = match vm_probe.get(face.probe) {
+                Ok(probe) => probe,
+                Err(_) => {
+                    commands.despawn(id);
+                    return;
+                }
+            };
+
+            target.active = probe.capturing();
+            if !target.active {
+                return;
+            }
+
+            let position = v_transform
+                .get(face.probe)
+                .map(|transform| transform.translation)
+                .unwrap_or_default();
+
+            target.clear_color = probe.clear_color;
+            target.update_camera(
+                queue.inner(),
+                &probe.face_camera(position, face.face as usize),
+            );
+        });
+}
+
+fn sys_capture_reflection_probes(
+    mut tools: ResMut<RenderEncoder>,
+    mut vm_probe: ViewMut<ReflectionProbe>,
+    v_face: View<ReflectionProbeFace>,
+    v_target: View<RenderTarget>,
+) {
+    let mut captured = HashSet::new();
+
+    (&v_face, &v_target)
+        .iter()
+        .filter(|(_, target)| target.active)
+        .for_each(|(face, target)| {
+            let probe = match vm_probe.get(face.probe) {
+                Ok(probe) => probe,
+                Err(_) => return,
+            };
+
+            let cubemap = match &probe.cubemap {
+                Some(cubemap) => cubemap,
+                None => return,
+            };
+
+            cubemap.copy_face(
+                tools.encoder(),
+                &target.texture().inner().raw().texture,
+                face.face,
+                probe.resolution.min(target.size().width),
+            );
+
+            captured.insert(face.probe);
+        });
+
+    captured.into_iter().for_each(|id| {
+        if let Ok(mut probe) = (&mut vm_probe).get(id) {
+            probe.pending = false;
+            probe.captured = true;
+        }
+    });
+}
+
+// Faces are copied from render targets in the surface format, so cubemaps are
+// recreated alongside them and captured again
+fn sys_recreate_probe_cubemaps(
+    device: Res<Device>,
+    config: Res<SurfaceConfig>,
+    layouts: Res<BindGroupLayoutRegistry>,
+    mut vm_probe: ViewMut<ReflectionProbe>,
+) {
+    (&mut vm_probe)
+        .iter()
+        .filter(|probe| probe.cubemap.is_some())
+        .for_each(|probe| {
+            probe.cubemap = Some(ProbeCubemap::new(
+                device.inner(),
+                &layouts,
+                config.inner().format,
+                probe.resolution,
+            ));
+            probe.pending = true;
+            probe.captured = false;
+        });
+}
+
+//====================================================================
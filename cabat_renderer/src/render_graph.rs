@@ -0,0 +1,331 @@
+//====================================================================
+
+use std::{
+    any::TypeId,
+    collections::{hash_map::Entry, HashMap, VecDeque},
+};
+
+use cabat_shipyard::{GetWorld, Res, ResMut, Stages, WorkloadBuilder};
+use shipyard::{IntoWorkload, Unique, WorkloadModificator};
+
+use crate::{RenderEncoder, RenderPass};
+
+//====================================================================
+
+/// Resources shared between the built in render passes.
+pub mod resources {
+    /// Render targets owned by [crate::render_target::RenderTarget] cameras.
+    pub const RENDER_TARGETS: &str = "render_targets";
+    /// The main scene pass, open between its begin and end nodes.
+    pub const MAIN_PASS: &str = "main_pass";
+    /// The surface texture, once the main pass has finished with it.
+    pub const SURFACE: &str = "surface";
+    /// Passes drawing into the 3d scene outside of the main pass. Read by screen
+    /// space passes so they draw on top.
+    pub const SCENE: &str = "scene";
+    /// The finished scene once it's been copied out of a separate scene target,
+    /// such as by the [crate::pixel_perfect::PixelPerfectPlugin]. Read by screen
+    /// space passes so they aren't drawn into the scene target.
+    pub const SCENE_OUTPUT: &str = "scene_output";
+    /// Point lights binned into screen tiles, read by the main pass.
+    pub const LIGHTS: &str = "lights";
+    /// Occlusion queries drawn in the main pass, read once they're resolved.
+    pub const OCCLUSION: &str = "occlusion";
+}
+
+//====================================================================
+
+/// A render pass declared to the graph along with the resources it uses.
+///
+/// Writers of a resource run in registration order and every reader of a
+/// resource runs after all of its writers.
+///
+/// Each pass is wrapped in a debug group named after it, so its commands are
+/// grouped together in graphics debuggers such as RenderDoc. Passes are told
+/// apart by the type of their systems, so a pass reusing another's systems
+/// doesn't get its own group.
+#[derive(Debug, Clone)]
+pub struct RenderGraphNode {
+    name: &'static str,
+    reads: Vec<&'static str>,
+    writes: Vec<&'static str>,
+    debug_group: bool,
+}
+
+impl RenderGraphNode {
+    #[inline]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            reads: Vec::new(),
+            writes: Vec::new(),
+            debug_group: true,
+        }
+    }
+
+    /// Don't wrap the pass in a debug group. Needed for passes that open or
+    /// close the main pass, as a group can't span both.
+    #[inline]
+    pub fn without_debug_group(mut self) -> Self {
+        self.debug_group = false;
+        self
+    }
+
+    #[inline]
+    pub fn reads(mut self, resource: &'static str) -> Self {
+        self.reads.push(resource);
+        self
+    }
+
+    #[inline]
+    pub fn writes(mut self, resource: &'static str) -> Self {
+        self.writes.push(resource);
+        self
+    }
+
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    #[inline]
+    fn is_writer(&self, resource: &str) -> bool {
+        self.writes.iter().any(|write| *write == resource)
+    }
+
+    #[inline]
+    fn is_reader(&self, resource: &str) -> bool {
+        self.reads.iter().any(|read| *read == resource) && !self.is_writer(resource)
+    }
+}
+
+//--------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PassOrder {
+    Before,
+    After,
+}
+
+// Where a newly added node has to run relative to an existing one
+fn pass_order(existing: &RenderGraphNode, new: &RenderGraphNode) -> Option<PassOrder> {
+    let resources = existing.reads.iter().chain(existing.writes.iter());
+
+    let mut order = None;
+
+    for resource in resources {
+        let resource_order = if existing.is_writer(resource)
+            && (new.is_writer(resource) || new.is_reader(resource))
+        {
+            PassOrder::After
+        } else if existing.is_reader(resource) && new.is_writer(resource) {
+            PassOrder::Before
+        } else {
+            continue;
+        };
+
+        match order {
+            Some(previous) if previous != resource_order => {
+                log::warn!(
+                    "Render passes '{}' and '{}' have conflicting resource orders",
+                    existing.name,
+                    new.name
+                );
+            }
+            _ => order = Some(resource_order),
+        }
+    }
+
+    order
+}
+
+//====================================================================
+
+#[derive(Unique, Default)]
+pub struct RenderGraph {
+    nodes: Vec<RenderGraphNode>,
+    // Debug group names keyed by the type of the systems they wrap
+    debug_groups: HashMap<TypeId, &'static str>,
+}
+
+impl RenderGraph {
+    // Returns the ordering constraints of the new node, and whether it gets a debug group
+    fn add_node<Pass: 'static>(
+        &mut self,
+        node: RenderGraphNode,
+    ) -> (Vec<(PassOrder, &'static str)>, bool) {
+        let constraints = self
+            .nodes
+            .iter()
+            .filter_map(|existing| Some((pass_order(existing, &node)?, existing.name)))
+            .collect();
+
+        let debug_group = node.debug_group
+            && match self.debug_groups.entry(TypeId::of::<Pass>()) {
+                Entry::Vacant(entry) => {
+                    entry.insert(node.name);
+                    true
+                }
+                Entry::Occupied(entry) => {
+                    log::warn!(
+                        "Render pass '{}' reuses the systems of '{}' - it won't get its own debug group",
+                        node.name,
+                        entry.get()
+                    );
+                    false
+                }
+            };
+
+        self.nodes.push(node);
+        (constraints, debug_group)
+    }
+
+    #[inline]
+    pub fn nodes(&self) -> &[RenderGraphNode] {
+        &self.nodes
+    }
+
+    /// Order passes will execute in. Returns `None` if the declared resources form a cycle.
+    pub fn execution_order(&self) -> Option<Vec<&'static str>> {
+        let mut incoming = vec![0; self.nodes.len()];
+        let mut edges = vec![Vec::new(); self.nodes.len()];
+
+        self.nodes.iter().enumerate().for_each(|(new_index, new)| {
+            self.nodes[..new_index]
+                .iter()
+                .enumerate()
+                .for_each(|(index, existing)| {
+                    let (from, to) = match pass_order(existing, new) {
+                        Some(PassOrder::After) => (index, new_index),
+                        Some(PassOrder::Before) => (new_index, index),
+                        None => return,
+                    };
+
+                    edges[from].push(to);
+                    incoming[to] += 1;
+                });
+        });
+
+        let mut queue = incoming
+            .iter()
+            .enumerate()
+            .filter_map(|(index, count)| (*count == 0).then_some(index))
+            .collect::<VecDeque<_>>();
+
+        let mut order = Vec::with_capacity(self.nodes.len());
+
+        while let Some(index) = queue.pop_front() {
+            order.push(self.nodes[index].name);
+
+            edges[index].iter().for_each(|to| {
+                incoming[*to] -= 1;
+                if incoming[*to] == 0 {
+                    queue.push_back(*to);
+                }
+            });
+        }
+
+        match order.len() == self.nodes.len() {
+            true => Some(order),
+            false => None,
+        }
+    }
+}
+
+//====================================================================
+
+pub trait AddRenderPass {
+    fn add_render_pass<Views, R, Sys>(&self, node: RenderGraphNode, workload: Sys) -> &Self
+    where
+        Sys: IntoWorkload<Views, R> + 'static,
+        R: 'static;
+}
+
+impl AddRenderPass for WorkloadBuilder<'_> {
+    fn add_render_pass<Views, R, Sys>(&self, node: RenderGraphNode, workload: Sys) -> &Self
+    where
+        Sys: IntoWorkload<Views, R> + 'static,
+        R: 'static,
+    {
+        let name = node.name;
+
+        let (constraints, debug_group) = match self.get_world().get_unique::<&mut RenderGraph>() {
+            Ok(mut graph) => graph.add_node::<Sys>(node),
+
+            Err(shipyard::error::GetStorage::MissingStorage { .. }) => {
+                let mut graph = RenderGraph::default();
+                let constraints = graph.add_node::<Sys>(node);
+                self.get_world().add_unique(graph);
+                constraints
+            }
+
+            Err(_) => unimplemented!(),
+        };
+
+        self.log(format!("Adding render pass '{}'", name));
+
+        let ordered = |order: PassOrder| {
+            constraints
+                .iter()
+                .filter(|(other_order, _)| *other_order == order)
+                .map(|(_, other)| *other)
+                .collect::<Vec<_>>()
+        };
+
+        self.describe_tag(Stages::Render, name, &workload)
+            .describe_order(
+                Stages::Render,
+                &workload,
+                &ordered(PassOrder::Before),
+                &ordered(PassOrder::After),
+            );
+
+        let workload = match debug_group {
+            true => (
+                sys_push_debug_group::<Sys>.into_workload(),
+                workload.into_workload(),
+                sys_pop_debug_group::<Sys>.into_workload(),
+            )
+                .into_sequential_workload(),
+            false => workload.into_workload(),
+        };
+
+        // No encoder means the surface couldn't be acquired and the frame is skipped
+        let workload = constraints.into_iter().fold(
+            workload.skip_if_missing_unique::<RenderEncoder>().tag(name),
+            |workload, (order, other)| match order {
+                PassOrder::Before => workload.before_all(other),
+                PassOrder::After => workload.after_all(other),
+            },
+        );
+
+        self.add_workload(Stages::Render, workload)
+    }
+}
+
+//====================================================================
+
+// Generic over the type of the pass's systems, as shipyard tells systems apart
+// by type. The group name is looked up from the graph.
+fn sys_push_debug_group<Pass: 'static>(
+    graph: Res<RenderGraph>,
+    encoder: Option<ResMut<RenderEncoder>>,
+    mut pass: Option<ResMut<RenderPass>>,
+) {
+    let name = graph.debug_groups.get(&TypeId::of::<Pass>());
+
+    if let (Some(mut encoder), Some(name)) = (encoder, name) {
+        encoder.push_debug_group(pass.as_deref_mut(), name);
+    }
+}
+
+fn sys_pop_debug_group<Pass: 'static>(
+    encoder: Option<ResMut<RenderEncoder>>,
+    mut pass: Option<ResMut<RenderPass>>,
+) {
+    if let Some(mut encoder) = encoder {
+        encoder.pop_debug_group(pass.as_deref_mut());
+    }
+}
+
+//====================================================================
@@ -0,0 +1,227 @@
+//====================================================================
+
+use std::{
+    error::Error,
+    fmt::Display,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use cabat_assets::{
+    asset_storage::AssetStorage,
+    handle::{Handle, HandleId},
+};
+use cabat_common::Size;
+use cabat_shipyard::prelude::*;
+use shipyard::{Component, IntoIter, View, ViewMut};
+
+use crate::{
+    camera::{Camera, CameraUniform},
+    shared::BindGroupLayoutRegistry,
+    texture::{RawTexture, Texture},
+    Device, RenderEncoder, SurfaceConfig,
+};
+
+//====================================================================
+
+pub struct RenderTargetDescriptor<'a> {
+    pub label: &'a str,
+    pub size: Size<u32>,
+    pub clear_color: [f64; 4],
+}
+
+impl Default for RenderTargetDescriptor<'_> {
+    fn default() -> Self {
+        Self {
+            label: "Render Target",
+            size: Size::new(512, 512),
+            clear_color: [0.2, 0.2, 0.2, 1.],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderTargetError {
+    /// The target's own texture was bound while drawing into it.
+    SampledOwnTexture,
+}
+
+impl Error for RenderTargetError {}
+
+impl Display for RenderTargetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            RenderTargetError::SampledOwnTexture => {
+                write!(
+                    f,
+                    "Render target can't sample its own texture while drawn into"
+                )
+            }
+        }
+    }
+}
+
+//--------------------------------------------------
+
+/// Camera that renders the scene into an offscreen texture instead of the surface.
+/// The resulting texture is a regular asset and can be used by any sprite, other
+/// than sprites drawn by the same target, which are skipped with an error.
+///
+/// The texture is recreated when the surface format changes, getting a new
+/// handle, so anything drawing it should fetch [RenderTarget::texture] again.
+#[derive(Component)]
+pub struct RenderTarget {
+    label: String,
+    camera: Camera,
+    size: Size<u32>,
+    texture: Handle<Texture>,
+    depth_texture: RawTexture,
+    // Errors are logged once rather than every frame
+    reported: AtomicBool,
+
+    pub clear_color: [f64; 4],
+    pub active: bool,
+}
+
+impl RenderTarget {
+    pub fn new<C: CameraUniform>(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        layouts: &BindGroupLayoutRegistry,
+        storage: &mut AssetStorage,
+        camera: &C,
+        desc: &RenderTargetDescriptor,
+    ) -> Self {
+        // Use surface format so existing pipelines can draw into the target
+        let raw = RawTexture::create_render_target(device, desc.size, config.format, desc.label);
+        let texture = storage.insert_asset(layouts.load_texture(device, raw, Some(desc.label)));
+
+        let depth_texture = RawTexture::create_depth_texture(device, desc.size, desc.label);
+
+        Self {
+            label: desc.label.to_string(),
+            camera: Camera::new(device, layouts, camera),
+            size: desc.size,
+            texture,
+            depth_texture,
+            reported: AtomicBool::new(false),
+
+            clear_color: desc.clear_color,
+            active: true,
+        }
+    }
+
+    #[inline]
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    #[inline]
+    pub fn update_camera<C: CameraUniform>(&self, queue: &wgpu::Queue, camera: &C) {
+        self.camera.update_camera(queue, camera);
+    }
+
+    #[inline]
+    pub fn texture(&self) -> &Handle<Texture> {
+        &self.texture
+    }
+
+    #[inline]
+    pub fn size(&self) -> Size<u32> {
+        self.size
+    }
+
+    /// Check a texture can be bound while drawing into the target, which isn't
+    /// the case for the target's own texture.
+    #[inline]
+    pub fn check_sampled(&self, texture: HandleId) -> Result<(), RenderTargetError> {
+        match self.texture.id() == texture {
+            true => Err(RenderTargetError::SampledOwnTexture),
+            false => Ok(()),
+        }
+    }
+
+    pub(crate) fn report(&self, error: RenderTargetError) {
+        if !self.reported.swap(true, Ordering::Relaxed) {
+            log::error!("Skipped drawing into '{}': {}", self.label, error);
+        }
+    }
+
+    /// Replace the texture with one in the surface's current format.
+    pub fn recreate_texture(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        layouts: &BindGroupLayoutRegistry,
+        storage: &mut AssetStorage,
+    ) {
+        let raw = RawTexture::create_render_target(device, self.size, config.format, &self.label);
+        self.texture = storage.insert_asset(layouts.load_texture(device, raw, Some(&self.label)));
+    }
+
+    pub fn begin_render_pass<'a>(
+        &'a self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        clear: bool,
+    ) -> wgpu::RenderPass<'a> {
+        let (load, depth_load) = match clear {
+            true => {
+                let [r, g, b, a] = self.clear_color;
+                (
+                    wgpu::LoadOp::Clear(wgpu::Color { r, g, b, a }),
+                    wgpu::LoadOp::Clear(1.),
+                )
+            }
+            false => (wgpu::LoadOp::Load, wgpu::LoadOp::Load),
+        };
+
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Target Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.texture.inner().raw().view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: depth_load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        })
+    }
+}
+
+//====================================================================
+
+pub(crate) fn sys_clear_render_targets(
+    mut tools: ResMut<RenderEncoder>,
+    v_targets: View<RenderTarget>,
+) {
+    v_targets
+        .iter()
+        .filter(|target| target.active)
+        .for_each(|target| {
+            target.begin_render_pass(tools.encoder(), true);
+        });
+}
+
+pub(crate) fn sys_recreate_render_targets(
+    device: Res<Device>,
+    config: Res<SurfaceConfig>,
+    layouts: Res<BindGroupLayoutRegistry>,
+    mut storage: ResMut<AssetStorage>,
+    mut vm_targets: ViewMut<RenderTarget>,
+) {
+    (&mut vm_targets).iter().for_each(|target| {
+        target.recreate_texture(device.inner(), config.inner(), &layouts, &mut storage);
+    });
+}
+
+//====================================================================
@@ -1,10 +1,19 @@
 //====================================================================
 
-use std::num::NonZeroU32;
+use std::{
+    collections::HashMap, hash::BuildHasherDefault, marker::PhantomData, num::NonZeroU32,
+    panic::Location, path::Path, sync::Arc,
+};
 
+use cabat_shipyard::ResMut;
+use rustc_hash::FxHasher;
+use shipyard::{Component, EntityId, Get, Unique, View};
 use wgpu::util::DeviceExt;
 
-use crate::{texture::RawTexture, Vertex};
+use crate::{
+    texture::{self, RawTexture},
+    RenderStats, Vertex,
+};
 
 //====================================================================
 
@@ -15,6 +24,7 @@ pub struct RenderPipelineDescriptor<'a> {
     pub fragment_targets: Option<&'a [Option<wgpu::ColorTargetState>]>,
     pub multiview: Option<NonZeroU32>,
     pub cache: Option<&'a wgpu::PipelineCache>,
+    pub fragment_entry: &'a str,
 }
 
 impl<'a> Default for RenderPipelineDescriptor<'a> {
@@ -26,6 +36,7 @@ impl<'a> Default for RenderPipelineDescriptor<'a> {
             fragment_targets: None,
             multiview: None,
             cache: None,
+            fragment_entry: "fs_main",
         }
     }
 }
@@ -43,12 +54,66 @@ impl RenderPipelineDescriptor<'_> {
         self
     }
 
-    pub fn with_backface_culling(mut self) -> Self {
-        self.primitive.cull_mode = Some(wgpu::Face::Back);
+    pub fn with_backface_culling(self) -> Self {
+        self.with_face_culling(FaceCulling::BACK)
+    }
+
+    pub fn with_face_culling(mut self, culling: FaceCulling) -> Self {
+        self.primitive.cull_mode = culling.cull_mode;
+        self.primitive.front_face = culling.front_face;
         self
     }
 }
 
+//--------------------------------------------------
+
+/// Which faces are culled and which winding counts as the front. Can be added to
+/// an entity to override its renderer's default, e.g. for geometry with inverted
+/// winding.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FaceCulling {
+    pub cull_mode: Option<wgpu::Face>,
+    pub front_face: wgpu::FrontFace,
+}
+
+impl Default for FaceCulling {
+    #[inline]
+    fn default() -> Self {
+        Self::BACK
+    }
+}
+
+impl FaceCulling {
+    pub const BACK: Self = Self {
+        cull_mode: Some(wgpu::Face::Back),
+        front_face: wgpu::FrontFace::Ccw,
+    };
+
+    pub const FRONT: Self = Self {
+        cull_mode: Some(wgpu::Face::Front),
+        front_face: wgpu::FrontFace::Ccw,
+    };
+
+    /// Draw both sides.
+    pub const NONE: Self = Self {
+        cull_mode: None,
+        front_face: wgpu::FrontFace::Ccw,
+    };
+
+    /// Treat clockwise triangles as the front.
+    #[inline]
+    pub fn clockwise(mut self) -> Self {
+        self.front_face = wgpu::FrontFace::Cw;
+        self
+    }
+
+    /// Culling of an entity, falling back to the default.
+    #[inline]
+    pub fn of(v_culling: &View<FaceCulling>, id: EntityId) -> Self {
+        v_culling.get(id).copied().unwrap_or_default()
+    }
+}
+
 pub fn create_pipeline(
     device: &wgpu::Device,
     config: &wgpu::SurfaceConfiguration,
@@ -91,7 +156,7 @@ pub fn create_pipeline(
         multisample: desc.multisample,
         fragment: Some(wgpu::FragmentState {
             module: &shader_module,
-            entry_point: "fs_main",
+            entry_point: desc.fragment_entry,
             compilation_options: Default::default(),
             targets: fragment_targets,
         }),
@@ -119,6 +184,24 @@ pub fn bgl_uniform_entry(
     }
 }
 
+/// bind group layout storage buffer entry
+pub fn bgl_storage_entry(
+    binding: u32,
+    visibility: wgpu::ShaderStages,
+    read_only: bool,
+) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
 pub fn bgl_texture_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
     wgpu::BindGroupLayoutEntry {
         binding,
@@ -132,6 +215,19 @@ pub fn bgl_texture_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
     }
 }
 
+pub fn bgl_cube_texture_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::Cube,
+            multisampled: false,
+        },
+        count: None,
+    }
+}
+
 pub fn bgl_sampler_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
     wgpu::BindGroupLayoutEntry {
         binding,
@@ -141,55 +237,228 @@ pub fn bgl_sampler_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
     }
 }
 
+/// Name of the module calling into render_tools, used to label resources
+/// created without one so they can be told apart in graphics debuggers.
+#[track_caller]
+fn caller_owner() -> &'static str {
+    let path = Path::new(Location::caller().file());
+
+    let owner = match path.file_stem().and_then(|stem| stem.to_str()) {
+        Some("mod") | Some("lib") => path
+            .parent()
+            .and_then(|parent| parent.file_name())
+            .and_then(|name| name.to_str()),
+        stem => stem,
+    };
+
+    owner.unwrap_or("cabat")
+}
+
+/// Create a buffer, counting it in the [RenderStats]. Unlabelled buffers are
+/// named after the calling module.
+#[inline]
+#[track_caller]
+pub fn create_buffer(device: &wgpu::Device, desc: &wgpu::BufferDescriptor) -> wgpu::Buffer {
+    RenderStats::record_buffer_allocation(desc.size);
+
+    match desc.label {
+        Some(_) => device.create_buffer(desc),
+        None => device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("{} Buffer", caller_owner())),
+            ..*desc
+        }),
+    }
+}
+
+/// Create a buffer holding the given contents, counting it in the [RenderStats].
+/// Unlabelled buffers are named after the calling module.
+#[inline]
+#[track_caller]
+pub fn create_buffer_init(
+    device: &wgpu::Device,
+    desc: &wgpu::util::BufferInitDescriptor,
+) -> wgpu::Buffer {
+    RenderStats::record_buffer_allocation(desc.contents.len() as wgpu::BufferAddress);
+
+    match desc.label {
+        Some(_) => device.create_buffer_init(desc),
+        None => device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} Buffer", caller_owner())),
+            ..*desc
+        }),
+    }
+}
+
+/// Create a texture, counting it in the [RenderStats]. Unlabelled textures are
+/// named after the calling module.
+#[inline]
+#[track_caller]
+pub fn create_texture(device: &wgpu::Device, desc: &wgpu::TextureDescriptor) -> wgpu::Texture {
+    let created = match desc.label {
+        Some(_) => device.create_texture(desc),
+        None => device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&format!("{} Texture", caller_owner())),
+            ..*desc
+        }),
+    };
+
+    RenderStats::record_texture_allocation(texture::texture_memory(&created) as u64);
+    created
+}
+
 pub fn vertex_buffer<T: Vertex>(device: &wgpu::Device, label: &str, data: &[T]) -> wgpu::Buffer {
-    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some(&format!("{} Vertex Buffer", label)),
-        contents: bytemuck::cast_slice(data),
-        usage: wgpu::BufferUsages::VERTEX,
-    })
+    create_buffer_init(
+        device,
+        &wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} Vertex Buffer", label)),
+            contents: bytemuck::cast_slice(data),
+            usage: wgpu::BufferUsages::VERTEX,
+        },
+    )
 }
 
 pub fn index_buffer(device: &wgpu::Device, label: &str, data: &[u16]) -> wgpu::Buffer {
-    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some(&format!("{} Index Buffer", label)),
-        contents: bytemuck::cast_slice(data),
-        usage: wgpu::BufferUsages::INDEX,
-    })
+    create_buffer_init(
+        device,
+        &wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} Index Buffer", label)),
+            contents: bytemuck::cast_slice(data),
+            usage: wgpu::BufferUsages::INDEX,
+        },
+    )
 }
 
 //====================================================================
 
-pub fn update_instance_buffer<T: bytemuck::Pod>(
-    device: &wgpu::Device,
-    queue: &wgpu::Queue,
+/// Growable instance buffer that tracks its used length separately from its capacity.
+///
+/// Capacity grows geometrically so scenes with fluctuating instance counts
+/// don't reallocate every frame, and is never shrunk implicitly.
+pub struct InstanceBuffer<T: bytemuck::Pod> {
+    label: String,
+    buffer: wgpu::Buffer,
+    count: u32,
+    capacity: u32,
+    phantom: PhantomData<T>,
+}
 
-    label: &str,
-    buffer: &mut wgpu::Buffer,
-    instance_count: &mut u32,
+impl<T: bytemuck::Pod> InstanceBuffer<T> {
+    const MIN_CAPACITY: u32 = 8;
 
-    data: &[T],
-) {
-    if data.len() == 0 {
-        // Nothing to update
-        if *instance_count != 0 {
-            // Empty buffer and reset instance count
-            *buffer = create_instance_buffer(device, label, data);
-            *instance_count = 0;
+    pub fn new(device: &wgpu::Device, label: &str) -> Self {
+        Self::with_capacity(device, label, 0)
+    }
+
+    pub fn with_capacity(device: &wgpu::Device, label: &str, capacity: u32) -> Self {
+        Self {
+            label: label.to_string(),
+            buffer: Self::create_buffer(device, label, capacity),
+            count: 0,
+            capacity,
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn with_data(device: &wgpu::Device, label: &str, data: &[T]) -> Self {
+        Self {
+            label: label.to_string(),
+            buffer: create_instance_buffer(device, label, data),
+            count: data.len() as u32,
+            capacity: data.len() as u32,
+            phantom: PhantomData,
+        }
+    }
+
+    fn create_buffer(device: &wgpu::Device, label: &str, capacity: u32) -> wgpu::Buffer {
+        create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some(&format!("{} Instance Buffer", label)),
+                size: capacity as wgpu::BufferAddress
+                    * std::mem::size_of::<T>() as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        )
+    }
+
+    // Make sure buffer can fit the requested amount of instances, growing if required
+    fn reserve(&mut self, device: &wgpu::Device, required: u32) {
+        if required <= self.capacity {
+            return;
+        }
+
+        let capacity = required.next_power_of_two().max(Self::MIN_CAPACITY);
+
+        log::trace!(
+            "Growing '{}' instance buffer from {} to {}",
+            self.label,
+            self.capacity,
+            capacity
+        );
+
+        self.buffer = Self::create_buffer(device, &self.label, capacity);
+        self.capacity = capacity;
+    }
+
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, data: &[T]) {
+        self.count = data.len() as u32;
+
+        if data.is_empty() {
+            return;
         }
 
-        return;
+        self.reserve(device, self.count);
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(data));
     }
 
-    // We can fit all data inside existing buffer
-    if data.len() <= *instance_count as usize {
-        queue.write_buffer(buffer, 0, bytemuck::cast_slice(data));
-        *instance_count = data.len() as u32; // TODO - add additional variable for buffer size
-        return;
+    /// Upload through a persistent staging belt instead of `Queue::write_buffer`.
+    /// The caller is responsible for calling `finish` on the belt before the
+    /// encoder is submitted and `recall` afterwards.
+    pub fn update_staged(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        belt: &mut wgpu::util::StagingBelt,
+        data: &[T],
+    ) {
+        self.count = data.len() as u32;
+
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        let size = match wgpu::BufferSize::new(bytes.len() as wgpu::BufferAddress) {
+            Some(size) => size,
+            None => return,
+        };
+
+        self.reserve(device, self.count);
+        belt.write_buffer(encoder, &self.buffer, 0, size, device)
+            .copy_from_slice(bytes);
     }
 
-    // Buffer is too small to fit new data. Create a new bigger one.
-    *instance_count = data.len() as u32;
-    *buffer = create_instance_buffer(device, label, data);
+    #[inline]
+    pub fn clear(&mut self) {
+        self.count = 0;
+    }
+
+    #[inline]
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    #[inline]
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
 }
 
 pub fn create_instance_buffer<T: bytemuck::Pod>(
@@ -197,11 +466,436 @@ pub fn create_instance_buffer<T: bytemuck::Pod>(
     label: &str,
     data: &[T],
 ) -> wgpu::Buffer {
-    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some(&format!("{} Instance Buffer", label)),
-        contents: bytemuck::cast_slice(data),
-        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-    })
+    create_buffer_init(
+        device,
+        &wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} Instance Buffer", label)),
+            contents: bytemuck::cast_slice(data),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        },
+    )
+}
+
+//====================================================================
+
+/// Uniform buffer shared by many objects, bound once with a dynamic offset per
+/// object instead of every object owning a buffer and bind group.
+///
+/// Values are pushed each frame, returning the offset to bind them with. The
+/// buffer is reset with `clear` and grows to fit, recreating its bind group.
+pub struct DynamicUniformBuffer<T: bytemuck::Pod> {
+    label: String,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    stride: u32,
+    capacity: u32,
+    data: Vec<u8>,
+    phantom: PhantomData<T>,
+}
+
+impl<T: bytemuck::Pod> DynamicUniformBuffer<T> {
+    const MIN_CAPACITY: u32 = 16;
+
+    /// `layout` should hold a single [Self::bgl_entry] at binding 0.
+    pub fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, label: &str) -> Self {
+        let stride = wgpu::util::align_to(
+            std::mem::size_of::<T>() as u32,
+            device.limits().min_uniform_buffer_offset_alignment,
+        );
+
+        let (buffer, bind_group) =
+            Self::create_buffer(device, layout, label, stride, Self::MIN_CAPACITY);
+
+        Self {
+            label: label.to_string(),
+            buffer,
+            bind_group,
+            stride,
+            capacity: Self::MIN_CAPACITY,
+            data: Vec::new(),
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn bgl_entry(binding: u32, visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<T>() as u64),
+            },
+            count: None,
+        }
+    }
+
+    fn create_buffer(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        label: &str,
+        stride: u32,
+        capacity: u32,
+    ) -> (wgpu::Buffer, wgpu::BindGroup) {
+        let buffer = create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some(&format!("{} Dynamic Uniform Buffer", label)),
+                size: stride as wgpu::BufferAddress * capacity as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{} Dynamic Uniform Bind Group", label)),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<T>() as u64),
+                }),
+            }],
+        });
+
+        (buffer, bind_group)
+    }
+
+    /// Queue a value for upload, returning its dynamic offset.
+    pub fn push(&mut self, value: &T) -> u32 {
+        let offset = self.data.len() as u32;
+
+        self.data.extend_from_slice(bytemuck::bytes_of(value));
+        self.data.resize((offset + self.stride) as usize, 0);
+
+        offset
+    }
+
+    /// Upload every value pushed since the last clear, growing if required.
+    pub fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+    ) {
+        if self.data.is_empty() {
+            return;
+        }
+
+        let required = self.len();
+
+        if required > self.capacity {
+            let capacity = required.next_power_of_two();
+
+            log::trace!(
+                "Growing '{}' dynamic uniform buffer from {} to {}",
+                self.label,
+                self.capacity,
+                capacity
+            );
+
+            (self.buffer, self.bind_group) =
+                Self::create_buffer(device, layout, &self.label, self.stride, capacity);
+            self.capacity = capacity;
+        }
+
+        queue.write_buffer(&self.buffer, 0, &self.data);
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    #[inline]
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    /// Values pushed since the last clear.
+    #[inline]
+    pub fn len(&self) -> u32 {
+        self.data.len() as u32 / self.stride
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+//====================================================================
+
+/// Buffer handed out by the [BufferPool]. Returned to the pool once dropped.
+pub struct PooledBuffer {
+    buffer: Arc<wgpu::Buffer>,
+}
+
+impl PooledBuffer {
+    #[inline]
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    /// Allocated size, which may be larger than requested.
+    #[inline]
+    pub fn size(&self) -> wgpu::BufferAddress {
+        self.buffer.size()
+    }
+}
+
+impl std::ops::Deref for PooledBuffer {
+    type Target = wgpu::Buffer;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.buffer
+    }
+}
+
+//--------------------------------------------------
+
+// Size class and usage
+type PoolKey = (wgpu::BufferAddress, wgpu::BufferUsages);
+
+/// Recycles transient buffers between frames so dynamic renderers don't allocate
+/// new ones every time their data changes.
+///
+/// Buffers are grouped by usage and rounded up to a power of two size. Those no
+/// longer held by a [PooledBuffer] go back into the pool at the end of the frame,
+/// and are freed if they go unused for a while.
+#[derive(Unique, Default)]
+pub struct BufferPool {
+    frame: u64,
+    in_use: Vec<(PoolKey, Arc<wgpu::Buffer>)>,
+    free: HashMap<PoolKey, Vec<(u64, Arc<wgpu::Buffer>)>, BuildHasherDefault<FxHasher>>,
+}
+
+impl BufferPool {
+    const MIN_SIZE: wgpu::BufferAddress = 256;
+    // Frames a free buffer is kept around before being destroyed
+    const MAX_IDLE_FRAMES: u64 = 120;
+
+    /// Buffers are labelled after the module first allocating them, though
+    /// they may be reused by others later on.
+    #[track_caller]
+    pub fn acquire(
+        &mut self,
+        device: &wgpu::Device,
+        usage: wgpu::BufferUsages,
+        size: wgpu::BufferAddress,
+    ) -> PooledBuffer {
+        let size_class = size.next_power_of_two().max(Self::MIN_SIZE);
+        let key = (size_class, usage);
+
+        let buffer = match self.free.get_mut(&key).and_then(|free| free.pop()) {
+            Some((_, buffer)) => buffer,
+            None => {
+                log::trace!(
+                    "Allocating pooled {:?} buffer of {} bytes",
+                    usage,
+                    size_class
+                );
+
+                Arc::new(create_buffer(
+                    device,
+                    &wgpu::BufferDescriptor {
+                        label: Some(&format!("{} Pooled Buffer", caller_owner())),
+                        size: size_class,
+                        usage,
+                        mapped_at_creation: false,
+                    },
+                ))
+            }
+        };
+
+        self.in_use.push((key, buffer.clone()));
+
+        PooledBuffer { buffer }
+    }
+
+    /// Acquire a buffer and fill it with `data`. `COPY_DST` is added to the usage.
+    #[track_caller]
+    pub fn acquire_init(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        usage: wgpu::BufferUsages,
+        data: &[u8],
+    ) -> PooledBuffer {
+        let buffer = self.acquire(
+            device,
+            usage | wgpu::BufferUsages::COPY_DST,
+            data.len() as wgpu::BufferAddress,
+        );
+
+        queue.write_buffer(&buffer, 0, data);
+        buffer
+    }
+
+    /// Buffers allocated and waiting to be reused.
+    pub fn free_count(&self) -> usize {
+        self.free.values().map(|free| free.len()).sum()
+    }
+
+    #[inline]
+    pub fn in_use_count(&self) -> usize {
+        self.in_use.len()
+    }
+
+    fn recycle(&mut self) {
+        self.frame += 1;
+        let frame = self.frame;
+
+        let (in_use, released) = std::mem::take(&mut self.in_use)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(_, buffer)| Arc::strong_count(buffer) > 1);
+
+        self.in_use = in_use;
+
+        released.into_iter().for_each(|(key, buffer)| {
+            self.free.entry(key).or_default().push((frame, buffer));
+        });
+
+        self.free.retain(|_, free| {
+            free.retain(|(last_used, _)| frame - last_used < Self::MAX_IDLE_FRAMES);
+            !free.is_empty()
+        });
+    }
+}
+
+// Writes through the queue are applied before the next submission, so buffers
+// released this frame are safe to reuse next frame.
+pub(crate) fn sys_recycle_buffer_pool(mut pool: ResMut<BufferPool>) {
+    pool.recycle();
+}
+
+//====================================================================
+
+/// Collects writes to a texture so they can be uploaded with a single staging
+/// buffer, rather than a queue write each. Useful for textures written to in many
+/// small pieces, like glyph atlases.
+///
+/// Writes are recorded into the encoder when flushed, so flush before any passes
+/// sampling the texture begin.
+pub struct TextureUploadQueue {
+    bytes_per_pixel: u32,
+    data: Vec<u8>,
+    uploads: Vec<TextureUpload>,
+}
+
+struct TextureUpload {
+    offset: wgpu::BufferAddress,
+    bytes_per_row: u32,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl TextureUploadQueue {
+    pub fn new(format: wgpu::TextureFormat) -> Self {
+        Self {
+            bytes_per_pixel: format
+                .block_copy_size(None)
+                .expect("Texture upload queues only support color formats"),
+            data: Vec::new(),
+            uploads: Vec::new(),
+        }
+    }
+
+    /// Queue tightly packed pixel `data` to be written to the given area.
+    pub fn push(&mut self, data: &[u8], x: u32, y: u32, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let row_size = (width * self.bytes_per_pixel) as usize;
+        debug_assert!(data.len() >= row_size * height as usize);
+
+        // Buffer to texture copies need rows aligned, unlike queue writes
+        let bytes_per_row =
+            wgpu::util::align_to(row_size as u32, wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        let offset = wgpu::util::align_to(
+            self.data.len() as wgpu::BufferAddress,
+            wgpu::COPY_BUFFER_ALIGNMENT,
+        );
+        self.data.resize(offset as usize, 0);
+
+        data.chunks_exact(row_size)
+            .take(height as usize)
+            .for_each(|row| {
+                self.data.extend_from_slice(row);
+                self.data
+                    .resize(self.data.len() + bytes_per_row as usize - row_size, 0);
+            });
+
+        self.uploads.push(TextureUpload {
+            offset,
+            bytes_per_row,
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.uploads.is_empty()
+    }
+
+    /// Record every queued write into the encoder and clear the queue.
+    pub fn flush(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        pool: &mut BufferPool,
+        texture: &wgpu::Texture,
+    ) {
+        if self.uploads.is_empty() {
+            return;
+        }
+
+        let len = wgpu::util::align_to(self.data.len(), wgpu::COPY_BUFFER_ALIGNMENT as usize);
+        self.data.resize(len, 0);
+
+        let staging = pool.acquire_init(device, queue, wgpu::BufferUsages::COPY_SRC, &self.data);
+
+        self.uploads.drain(..).for_each(|upload| {
+            encoder.copy_buffer_to_texture(
+                wgpu::ImageCopyBuffer {
+                    buffer: &staging,
+                    layout: wgpu::ImageDataLayout {
+                        offset: upload.offset,
+                        bytes_per_row: Some(upload.bytes_per_row),
+                        rows_per_image: None,
+                    },
+                },
+                wgpu::ImageCopyTexture {
+                    texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: upload.x,
+                        y: upload.y,
+                        z: 0,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::Extent3d {
+                    width: upload.width,
+                    height: upload.height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        });
+
+        self.data.clear();
+    }
 }
 
 //====================================================================
@@ -0,0 +1,277 @@
+//====================================================================
+
+use cabat_shipyard::Event;
+use pollster::FutureExt;
+use serde::Deserialize;
+use shipyard::Unique;
+
+//====================================================================
+
+/// Insert before the renderer is set up to control which adapter and device
+/// features are requested. Anything that can't be provided falls back to what the
+/// adapter supports, see [GpuInfo] for what was actually obtained.
+#[derive(Unique, Debug, Clone)]
+pub struct GpuSettings {
+    pub backends: wgpu::Backends,
+    pub power_preference: wgpu::PowerPreference,
+    pub force_fallback_adapter: bool,
+
+    /// Features to request. Unsupported features are dropped with an error.
+    pub features: wgpu::Features,
+    pub limits: wgpu::Limits,
+}
+
+impl Default for GpuSettings {
+    fn default() -> Self {
+        Self {
+            backends: wgpu::Backends::PRIMARY,
+            power_preference: wgpu::PowerPreference::default(),
+            force_fallback_adapter: false,
+
+            features: wgpu::Features::empty(),
+            limits: wgpu::Limits::default(),
+        }
+    }
+}
+
+//--------------------------------------------------
+
+/// What the renderer actually obtained from the requested [GpuSettings].
+#[derive(Unique, Debug, Clone)]
+pub struct GpuInfo {
+    pub adapter: wgpu::AdapterInfo,
+    pub features: wgpu::Features,
+    pub limits: wgpu::Limits,
+    /// Capabilities missing from downlevel backends such as WebGL.
+    pub downlevel: wgpu::DownlevelCapabilities,
+
+    /// Requested features the adapter doesn't support.
+    pub missing_features: wgpu::Features,
+    /// Whether the requested limits had to be replaced with the adapter's own.
+    pub limits_fallback: bool,
+}
+
+impl GpuInfo {
+    #[inline]
+    pub fn has_feature(&self, feature: wgpu::Features) -> bool {
+        self.features.contains(feature)
+    }
+}
+
+//====================================================================
+
+/// Surface output settings. Can be inserted before setup or modified at any point,
+/// in which case the surface is reconfigured and a [SurfaceFormatChangedEvent] is
+/// triggered so pipelines can be rebuilt. Loaded from the `renderer` section of
+/// the config file when using the ConfigPlugin.
+#[derive(Unique, Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RendererSettings {
+    /// Surface formats in order of preference. The first one the surface supports
+    /// is used, otherwise falls back to the first sRGB format.
+    pub formats: Vec<wgpu::TextureFormat>,
+    pub present_mode: wgpu::PresentMode,
+}
+
+impl Default for RendererSettings {
+    fn default() -> Self {
+        Self {
+            formats: Vec::new(),
+            present_mode: wgpu::PresentMode::AutoNoVsync,
+        }
+    }
+}
+
+impl RendererSettings {
+    pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+    /// Prefer 16-bit float output on displays that support it.
+    #[inline]
+    pub fn hdr() -> Self {
+        Self {
+            formats: vec![Self::HDR_FORMAT],
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn with_formats(mut self, formats: &[wgpu::TextureFormat]) -> Self {
+        self.formats = formats.to_vec();
+        self
+    }
+
+    #[inline]
+    pub fn with_present_mode(mut self, present_mode: wgpu::PresentMode) -> Self {
+        self.present_mode = present_mode;
+        self
+    }
+
+    pub(crate) fn choose_format(&self, supported: &SurfaceFormats) -> wgpu::TextureFormat {
+        if let Some(format) = self
+            .formats
+            .iter()
+            .find(|format| supported.supports(**format))
+        {
+            return *format;
+        }
+
+        if !self.formats.is_empty() {
+            log::warn!(
+                "None of the requested surface formats {:?} are supported - using default",
+                self.formats
+            );
+        }
+
+        supported
+            .0
+            .iter()
+            .find(|format| format.is_srgb())
+            .copied()
+            .unwrap_or(supported.0[0])
+    }
+}
+
+//--------------------------------------------------
+
+/// Formats supported by the current surface.
+#[derive(Unique, Debug, Clone)]
+pub struct SurfaceFormats(pub(crate) Vec<wgpu::TextureFormat>);
+
+impl SurfaceFormats {
+    #[inline]
+    pub fn formats(&self) -> &[wgpu::TextureFormat] {
+        &self.0
+    }
+
+    #[inline]
+    pub fn supports(&self, format: wgpu::TextureFormat) -> bool {
+        self.0.contains(&format)
+    }
+
+    #[inline]
+    pub fn supports_hdr(&self) -> bool {
+        self.supports(RendererSettings::HDR_FORMAT)
+    }
+}
+
+//--------------------------------------------------
+
+/// Triggered when the surface format changes. Anything created with the old
+/// format (pipelines, render targets) needs to be recreated.
+#[derive(Event)]
+pub struct SurfaceFormatChangedEvent(wgpu::TextureFormat);
+
+impl SurfaceFormatChangedEvent {
+    #[inline]
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.0
+    }
+}
+
+//====================================================================
+
+pub(crate) fn request_instance(settings: &GpuSettings) -> wgpu::Instance {
+    wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: settings.backends,
+        ..Default::default()
+    })
+}
+
+pub(crate) fn request_adapter(
+    instance: &wgpu::Instance,
+    surface: &wgpu::Surface,
+    settings: &GpuSettings,
+) -> wgpu::Adapter {
+    let request = |power_preference, force_fallback_adapter| {
+        instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference,
+                force_fallback_adapter,
+                compatible_surface: Some(surface),
+            })
+            .block_on()
+    };
+
+    if let Some(adapter) = request(settings.power_preference, settings.force_fallback_adapter) {
+        return adapter;
+    }
+
+    log::warn!(
+        "No adapter found for power preference {:?} - trying any adapter",
+        settings.power_preference
+    );
+
+    request(wgpu::PowerPreference::None, false)
+        .or_else(|| {
+            log::warn!("No hardware adapter found - trying fallback adapter");
+            request(wgpu::PowerPreference::None, true)
+        })
+        .expect("Unable to find a compatible graphics adapter")
+}
+
+pub(crate) fn request_device(
+    adapter: &wgpu::Adapter,
+    settings: &GpuSettings,
+) -> (wgpu::Device, wgpu::Queue, GpuInfo) {
+    let supported_features = adapter.features();
+    let missing_features = settings.features - supported_features;
+
+    if !missing_features.is_empty() {
+        log::error!(
+            "Requested gpu features not supported by adapter: {:?}",
+            missing_features
+        );
+    }
+
+    let features = settings.features & supported_features;
+
+    let supported_limits = adapter.limits();
+    let limits_fallback = !settings.limits.check_limits(&supported_limits);
+
+    let limits = match limits_fallback {
+        true => {
+            log::error!("Requested gpu limits not supported by adapter - using adapter limits");
+            supported_limits
+        }
+        false => settings.limits.clone(),
+    };
+
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("Cabat Device"),
+                required_features: features,
+                required_limits: limits.clone(),
+                memory_hints: wgpu::MemoryHints::default(),
+            },
+            None,
+        )
+        .block_on()
+        .expect("Unable to create graphics device");
+
+    // Report validation errors through the log rather than panicking so they
+    // can be seen in app
+    device.on_uncaptured_error(Box::new(|error| {
+        log::error!("Graphics error: {}", error);
+    }));
+
+    let info = GpuInfo {
+        adapter: adapter.get_info(),
+        features: device.features(),
+        limits: device.limits(),
+        downlevel: adapter.get_downlevel_capabilities(),
+        missing_features,
+        limits_fallback,
+    };
+
+    log::info!(
+        "Using adapter '{}' ({:?}) with features {:?}",
+        info.adapter.name,
+        info.adapter.backend,
+        info.features
+    );
+
+    (device, queue, info)
+}
+
+//====================================================================
@@ -1,9 +1,14 @@
 //====================================================================
 
-use shipyard::Unique;
+use std::{cmp::Ordering, collections::HashMap, hash::BuildHasherDefault};
+
+use rustc_hash::FxHasher;
+use shipyard::{EntityId, Unique};
 
 use crate::{
+    lights::Lights,
     render_tools,
+    settings::GpuInfo,
     texture::{RawTexture, Texture},
 };
 
@@ -11,30 +16,130 @@ use super::Vertex;
 
 //====================================================================
 
+/// Bind group layouts used by more than one pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LayoutKey {
+    /// Camera uniform, see [Camera](crate::camera::Camera).
+    Camera,
+    /// Texture and sampler, as used by texture assets.
+    Texture,
+    /// Environment uniform, see [Environment](crate::environment::Environment).
+    Environment,
+    /// Cube texture and sampler, as used by reflection probes.
+    Cubemap,
+    /// Point lights and their screen tiles, see [Lights].
+    Lights,
+    /// Layouts registered outside of the renderer.
+    Named(&'static str),
+}
+
+/// Creates common bind group layouts once so renderers and resources can share
+/// them instead of each defining their own.
 #[derive(Unique)]
-pub struct SharedPipelineResources {
-    texture_bind_group_layout: wgpu::BindGroupLayout,
+pub struct BindGroupLayoutRegistry {
+    layouts: HashMap<LayoutKey, wgpu::BindGroupLayout, BuildHasherDefault<FxHasher>>,
 }
 
-impl SharedPipelineResources {
-    pub fn new(device: &wgpu::Device) -> Self {
-        let texture_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Texture 3d Bind Group Layout"),
-                entries: &[
-                    render_tools::bgl_texture_entry(0),
-                    render_tools::bgl_sampler_entry(1),
-                ],
-            });
-
-        Self {
-            texture_bind_group_layout,
+impl BindGroupLayoutRegistry {
+    pub fn new(device: &wgpu::Device, gpu_info: &GpuInfo) -> Self {
+        let mut registry = Self {
+            layouts: HashMap::default(),
+        };
+
+        registry.register(
+            device,
+            LayoutKey::Camera,
+            &[render_tools::bgl_uniform_entry(
+                0,
+                wgpu::ShaderStages::VERTEX_FRAGMENT,
+            )],
+        );
+
+        registry.register(
+            device,
+            LayoutKey::Texture,
+            &[
+                render_tools::bgl_texture_entry(0),
+                render_tools::bgl_sampler_entry(1),
+            ],
+        );
+
+        registry.register(
+            device,
+            LayoutKey::Environment,
+            &[render_tools::bgl_uniform_entry(
+                0,
+                wgpu::ShaderStages::FRAGMENT,
+            )],
+        );
+
+        registry.register(
+            device,
+            LayoutKey::Cubemap,
+            &[
+                render_tools::bgl_cube_texture_entry(0),
+                render_tools::bgl_sampler_entry(1),
+            ],
+        );
+
+        registry.register(device, LayoutKey::Lights, &Lights::layout_entries(gpu_info));
+
+        registry
+    }
+
+    /// Create a layout under the given key. Keys can only be registered once,
+    /// as pipelines and bind groups already made with a layout can't use a
+    /// replacement. Registering a key again returns the existing layout.
+    pub fn register(
+        &mut self,
+        device: &wgpu::Device,
+        key: LayoutKey,
+        entries: &[wgpu::BindGroupLayoutEntry],
+    ) -> &wgpu::BindGroupLayout {
+        if self.layouts.contains_key(&key) {
+            log::warn!(
+                "{:?} bind group layout is already registered, keeping the existing one",
+                key
+            );
+            return &self.layouts[&key];
         }
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(&format!("{:?} Bind Group Layout", key)),
+            entries,
+        });
+
+        self.layouts.entry(key).or_insert(layout)
+    }
+
+    #[inline]
+    pub fn get(&self, key: LayoutKey) -> Option<&wgpu::BindGroupLayout> {
+        self.layouts.get(&key)
+    }
+
+    #[inline]
+    pub fn camera(&self) -> &wgpu::BindGroupLayout {
+        &self.layouts[&LayoutKey::Camera]
     }
 
     #[inline]
-    pub fn texture_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
-        &self.texture_bind_group_layout
+    pub fn texture(&self) -> &wgpu::BindGroupLayout {
+        &self.layouts[&LayoutKey::Texture]
+    }
+
+    #[inline]
+    pub fn environment(&self) -> &wgpu::BindGroupLayout {
+        &self.layouts[&LayoutKey::Environment]
+    }
+
+    #[inline]
+    pub fn cubemap(&self) -> &wgpu::BindGroupLayout {
+        &self.layouts[&LayoutKey::Cubemap]
+    }
+
+    #[inline]
+    pub fn lights(&self) -> &wgpu::BindGroupLayout {
+        &self.layouts[&LayoutKey::Lights]
     }
 
     pub fn create_bind_group(
@@ -45,7 +150,7 @@ impl SharedPipelineResources {
     ) -> wgpu::BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor {
             label,
-            layout: &self.texture_bind_group_layout,
+            layout: self.texture(),
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
@@ -116,3 +221,40 @@ pub const TEXTURE_RECT_INDICES: [u16; 6] = [0, 1, 3, 0, 3, 2];
 pub const TEXTURE_RECT_INDEX_COUNT: u32 = TEXTURE_RECT_INDICES.len() as u32;
 
 //====================================================================
+
+/// Draw order of screen space elements. Elements are drawn by layer, then by z,
+/// with higher values on top.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SortKey {
+    pub layer: i32,
+    pub z: f32,
+}
+
+impl SortKey {
+    #[inline]
+    pub fn new(layer: i32, z: f32) -> Self {
+        Self { layer, z }
+    }
+
+    #[inline]
+    pub fn layer(layer: i32) -> Self {
+        Self { layer, z: 0. }
+    }
+
+    #[inline]
+    pub fn compare(&self, other: &SortKey) -> Ordering {
+        self.layer
+            .cmp(&other.layer)
+            .then(self.z.total_cmp(&other.z))
+    }
+}
+
+/// Sort elements into draw order. Equal keys fall back to the entity id so the
+/// result doesn't depend on storage iteration order.
+pub(crate) fn sort_draw_order<T>(elements: &mut [(SortKey, EntityId, T)]) {
+    elements.sort_by(|(key_a, id_a, _), (key_b, id_b, _)| {
+        key_a.compare(key_b).then(id_a.inner().cmp(&id_b.inner()))
+    });
+}
+
+//====================================================================
@@ -0,0 +1,400 @@
+//====================================================================
+
+use cabat_common::Size;
+use cabat_shipyard::{prelude::*, UniqueTools};
+use shipyard::{AllStoragesView, IntoWorkload, SystemModificator, Unique, View};
+
+use crate::{
+    camera::{self, MainCamera, SceneCamera},
+    render_graph::{resources, AddRenderPass, RenderGraphNode},
+    render_tools::{self, DynamicUniformBuffer},
+    settings::SurfaceFormatChangedEvent,
+    texture::{DepthTexture, RawTexture},
+    visibility::RenderLayers,
+    Device, DrawCounts, Queue, RenderEncoder, RenderPassDesc, RenderStats, SurfaceConfig,
+};
+
+//====================================================================
+
+/// Screen space ambient occlusion. Darkens creases and the ground around
+/// objects using the main pass depth buffer, with normals rebuilt from depth.
+///
+/// The occlusion is multiplied over the finished scene, so add before plugins
+/// drawing on top of it such as decals, outlines and gizmos.
+pub struct SsaoPlugin;
+
+impl Plugin for SsaoPlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        builder.get_or_insert(SsaoSettings::default);
+
+        builder
+            .add_workload_pre(Stages::Setup, sys_setup_ssao_renderer)
+            .add_workload_pre(
+                Stages::Render,
+                sys_prep_ssao.skip_if_missing_unique::<RenderEncoder>(),
+            )
+            .add_render_pass(
+                RenderGraphNode::new("ssao").writes(resources::SCENE),
+                sys_render_ssao,
+            )
+            .add_event::<SurfaceFormatChangedEvent>(sys_setup_ssao_renderer.into_workload());
+    }
+}
+
+//====================================================================
+
+/// Insert before adding the [SsaoPlugin] to configure it. Can be changed at
+/// any time.
+#[derive(Unique, Debug, Clone)]
+pub struct SsaoSettings {
+    pub enabled: bool,
+    /// World space distance around each pixel searched for occluders.
+    pub radius: f32,
+    /// Multiplier on how dark fully occluded pixels get.
+    pub intensity: f32,
+    /// Minimum height above a surface for something to occlude it, avoiding
+    /// speckles on flat surfaces.
+    pub bias: f32,
+    /// Samples per pixel, up to [SsaoSettings::MAX_SAMPLES].
+    pub samples: u32,
+}
+
+impl Default for SsaoSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            radius: 0.5,
+            intensity: 1.,
+            bias: 0.025,
+            samples: 16,
+        }
+    }
+}
+
+impl SsaoSettings {
+    pub const MAX_SAMPLES: u32 = 64;
+}
+
+// Per camera data used to rebuild and reproject world positions
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+struct SsaoViewRaw {
+    view_projection: [f32; 16],
+    inverse_view_projection: [f32; 16],
+    // x, y, width, height in pixels
+    viewport: [f32; 4],
+    radius: f32,
+    intensity: f32,
+    bias: f32,
+    samples: u32,
+}
+
+//====================================================================
+
+#[derive(Unique)]
+pub struct SsaoRenderer {
+    occlusion_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+
+    depth_bind_group_layout: wgpu::BindGroupLayout,
+    depth_bind_group: wgpu::BindGroup,
+
+    view_bind_group_layout: wgpu::BindGroupLayout,
+    views: DynamicUniformBuffer<SsaoViewRaw>,
+    // Offsets into views, in main pass camera order
+    view_offsets: Vec<u32>,
+
+    // Occlusion is drawn into its own target first so it can be blurred when
+    // composited, hiding the noise used to rotate samples
+    occlusion: RawTexture,
+    composite_bind_group_layout: wgpu::BindGroupLayout,
+    composite_bind_group: wgpu::BindGroup,
+}
+
+impl SsaoRenderer {
+    const OCCLUSION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth: &DepthTexture,
+        size: Size<u32>,
+    ) -> Self {
+        let depth_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Ssao Depth Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                }],
+            });
+
+        let depth_bind_group =
+            Self::create_depth_bind_group(device, &depth_bind_group_layout, depth);
+
+        let view_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Ssao View Bind Group Layout"),
+                entries: &[DynamicUniformBuffer::<SsaoViewRaw>::bgl_entry(
+                    0,
+                    wgpu::ShaderStages::FRAGMENT,
+                )],
+            });
+
+        let views = DynamicUniformBuffer::new(device, &view_bind_group_layout, "Ssao View");
+
+        let occlusion_pipeline = render_tools::create_pipeline(
+            device,
+            config,
+            "Ssao Occlusion Pipeline",
+            &[&depth_bind_group_layout, &view_bind_group_layout],
+            &[],
+            include_str!("../shaders/ssao.wgsl"),
+            render_tools::RenderPipelineDescriptor {
+                fragment_targets: Some(&[Some(wgpu::ColorTargetState {
+                    format: Self::OCCLUSION_FORMAT,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::all(),
+                })]),
+                ..Default::default()
+            },
+        );
+
+        let composite_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Ssao Composite Bind Group Layout"),
+                entries: &[render_tools::bgl_texture_entry(0)],
+            });
+
+        // Multiplies the scene by the blurred occlusion
+        let composite_pipeline = render_tools::create_pipeline(
+            device,
+            config,
+            "Ssao Composite Pipeline",
+            &[&composite_bind_group_layout],
+            &[],
+            include_str!("../shaders/ssao_composite.wgsl"),
+            render_tools::RenderPipelineDescriptor {
+                fragment_targets: Some(&[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState {
+                        color: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::Zero,
+                            dst_factor: wgpu::BlendFactor::Src,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                        alpha: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::Zero,
+                            dst_factor: wgpu::BlendFactor::One,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                    }),
+                    write_mask: wgpu::ColorWrites::all(),
+                })]),
+                ..Default::default()
+            },
+        );
+
+        let occlusion = Self::create_occlusion(device, size);
+        let composite_bind_group =
+            Self::create_composite_bind_group(device, &composite_bind_group_layout, &occlusion);
+
+        Self {
+            occlusion_pipeline,
+            composite_pipeline,
+
+            depth_bind_group_layout,
+            depth_bind_group,
+
+            view_bind_group_layout,
+            views,
+            view_offsets: Vec::new(),
+
+            occlusion,
+            composite_bind_group_layout,
+            composite_bind_group,
+        }
+    }
+
+    fn create_depth_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        depth: &DepthTexture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Ssao Depth Bind Group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&depth.main_texture().view),
+            }],
+        })
+    }
+
+    fn create_occlusion(device: &wgpu::Device, size: Size<u32>) -> RawTexture {
+        let size = Size::new(size.width.max(1), size.height.max(1));
+        RawTexture::create_render_target(device, size, Self::OCCLUSION_FORMAT, "Ssao Occlusion")
+    }
+
+    fn create_composite_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        occlusion: &RawTexture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Ssao Composite Bind Group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&occlusion.view),
+            }],
+        })
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, size: Size<u32>) {
+        self.occlusion = Self::create_occlusion(device, size);
+        self.composite_bind_group = Self::create_composite_bind_group(
+            device,
+            &self.composite_bind_group_layout,
+            &self.occlusion,
+        );
+    }
+}
+
+//====================================================================
+
+fn sys_setup_ssao_renderer(
+    all_storages: AllStoragesView,
+    device: Res<Device>,
+    config: Res<SurfaceConfig>,
+    depth: Res<DepthTexture>,
+) {
+    let renderer = SsaoRenderer::new(device.inner(), config.inner(), &depth, config.size());
+    all_storages.add_unique(renderer);
+}
+
+fn sys_prep_ssao(
+    device: Res<Device>,
+    queue: Res<Queue>,
+    tools: Res<RenderEncoder>,
+    settings: Res<SsaoSettings>,
+    depth: Res<DepthTexture>,
+    mut renderer: ResMut<SsaoRenderer>,
+
+    camera: Res<MainCamera>,
+    v_cameras: View<SceneCamera>,
+    v_layers: View<RenderLayers>,
+) {
+    let renderer = &mut *renderer;
+
+    if depth.is_modified() {
+        renderer.depth_bind_group = SsaoRenderer::create_depth_bind_group(
+            device.inner(),
+            &renderer.depth_bind_group_layout,
+            &depth,
+        );
+    }
+
+    // The occlusion target matches whatever the scene is being drawn into
+    let size = tools.scene_size();
+    let occlusion_size = renderer.occlusion.texture.size();
+
+    if occlusion_size.width != size.width.max(1) || occlusion_size.height != size.height.max(1) {
+        renderer.resize(device.inner(), size);
+    }
+
+    renderer.views.clear();
+
+    if !settings.enabled {
+        renderer.view_offsets.clear();
+        return;
+    }
+
+    renderer.view_offsets = camera::main_pass_cameras(&camera, &v_cameras, &v_layers)
+        .into_iter()
+        .map(|view| {
+            renderer.views.push(&SsaoViewRaw {
+                view_projection: view.view_projection.to_cols_array(),
+                inverse_view_projection: view.view_projection.inverse().to_cols_array(),
+                viewport: view.viewport.to_pixels(size),
+                radius: settings.radius.max(0.),
+                intensity: settings.intensity.max(0.),
+                bias: settings.bias,
+                samples: settings.samples.clamp(1, SsaoSettings::MAX_SAMPLES),
+            })
+        })
+        .collect();
+
+    renderer.views.upload(
+        device.inner(),
+        queue.inner(),
+        &renderer.view_bind_group_layout,
+    );
+}
+
+fn sys_render_ssao(
+    mut tools: ResMut<RenderEncoder>,
+    renderer: Res<SsaoRenderer>,
+    stats: Res<RenderStats>,
+
+    camera: Res<MainCamera>,
+    v_cameras: View<SceneCamera>,
+    v_layers: View<RenderLayers>,
+) {
+    if renderer.view_offsets.is_empty() {
+        return;
+    }
+
+    let size = tools.scene_size();
+
+    {
+        // Unoccluded by default so areas outside of every camera are untouched
+        let mut pass = tools
+            .encoder()
+            .begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Ssao Occlusion Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &renderer.occlusion.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+        pass.set_pipeline(&renderer.occlusion_pipeline);
+        pass.set_bind_group(0, &renderer.depth_bind_group, &[]);
+        stats.record(DrawCounts::pipeline());
+
+        camera::main_pass_cameras(&camera, &v_cameras, &v_layers)
+            .into_iter()
+            .zip(renderer.view_offsets.iter())
+            .for_each(|(view, offset)| {
+                view.viewport.apply(&mut pass, size);
+
+                pass.set_bind_group(1, renderer.views.bind_group(), &[*offset]);
+                pass.draw(0..3, 0..1);
+                stats.record(DrawCounts::default().draw(1));
+            });
+    }
+
+    let mut pass = tools.begin_render_pass(RenderPassDesc::none());
+
+    pass.set_pipeline(&renderer.composite_pipeline);
+    pass.set_bind_group(0, &renderer.composite_bind_group, &[]);
+    pass.draw(0..3, 0..1);
+
+    stats.record(DrawCounts::pipeline().draw(1));
+}
+
+//====================================================================
@@ -0,0 +1,700 @@
+//====================================================================
+
+use cabat_assets::{asset_loader::AssetTypeLoader, handle::Handle, Asset, RegisterAssetLoader};
+use cabat_common::Size;
+use cabat_shipyard::prelude::*;
+use cabat_spatial::Transform;
+use shipyard::{
+    AllStoragesView, Component, IntoIter, IntoWithId, IntoWorkload, Unique, View, ViewMut,
+};
+
+use crate::{
+    camera::{self, MainCamera, SceneCamera},
+    default_assets::DefaultRendererAssets,
+    environment::{self, Environment},
+    lights::Lights,
+    mesh::{Mesh, MeshBuilder, MeshVertex},
+    render_graph::{resources, AddRenderPass, RenderGraphNode},
+    render_tools,
+    settings::SurfaceFormatChangedEvent,
+    shared::BindGroupLayoutRegistry,
+    texture::Texture,
+    visibility::RenderLayers,
+    Device, DrawCounts, Queue, RenderPass, RenderStats, SurfaceConfig, Vertex,
+};
+
+//====================================================================
+
+pub struct TerrainPlugin;
+
+impl Plugin for TerrainPlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        builder.get_or_insert(TerrainLighting::default);
+
+        builder
+            .register_loader(HeightmapLoader)
+            .add_workload_pre(Stages::Setup, sys_setup_terrain_renderer)
+            .add_workload_last(
+                Stages::Update,
+                (sys_build_terrain, sys_prep_terrain).into_sequential_workload(),
+            )
+            .add_render_pass(
+                RenderGraphNode::new("terrain").writes(resources::MAIN_PASS),
+                sys_render_terrain,
+            )
+            .add_event::<SurfaceFormatChangedEvent>(sys_rebuild_terrain_pipeline.into_workload());
+    }
+}
+
+//====================================================================
+
+/// Grayscale height samples, from 0 to 1.
+pub struct Heightmap {
+    size: Size<u32>,
+    heights: Vec<f32>,
+}
+
+impl Asset for Heightmap {
+    #[inline]
+    fn cpu_memory(&self) -> usize {
+        std::mem::size_of::<Self>() + self.heights.capacity() * std::mem::size_of::<f32>()
+    }
+}
+
+impl Heightmap {
+    pub fn new(size: Size<u32>, heights: Vec<f32>) -> Self {
+        assert_eq!(
+            heights.len(),
+            (size.width * size.height) as usize,
+            "Heightmap sample count doesn't match its size"
+        );
+
+        Self { size, heights }
+    }
+
+    pub fn from_image(image: &image::DynamicImage) -> Self {
+        let luma = image.to_luma16();
+
+        let heights = luma
+            .pixels()
+            .map(|pixel| pixel.0[0] as f32 / u16::MAX as f32)
+            .collect();
+
+        Self::new(Size::new(luma.width(), luma.height()), heights)
+    }
+
+    pub fn open(path: &std::path::Path) -> cabat_assets::Result<Self> {
+        let image = image::ImageReader::open(path)?.decode()?;
+        Ok(Self::from_image(&image))
+    }
+
+    #[inline]
+    pub fn size(&self) -> Size<u32> {
+        self.size
+    }
+
+    /// Height at the given sample, clamped to the edges of the map.
+    #[inline]
+    pub fn get(&self, x: i64, z: i64) -> f32 {
+        let x = x.clamp(0, self.size.width as i64 - 1) as u32;
+        let z = z.clamp(0, self.size.height as i64 - 1) as u32;
+
+        self.heights[(z * self.size.width + x) as usize]
+    }
+}
+
+//--------------------------------------------------
+
+pub struct HeightmapLoader;
+
+impl AssetTypeLoader for HeightmapLoader {
+    type AssetType = Heightmap;
+
+    #[inline]
+    fn load(
+        &self,
+        _all_storages: AllStoragesView,
+        path: &std::path::Path,
+    ) -> cabat_assets::Result<Self::AssetType> {
+        Heightmap::open(path)
+    }
+
+    #[inline]
+    fn load_detached(
+        &self,
+        path: &std::path::Path,
+    ) -> Option<cabat_assets::Result<Self::AssetType>> {
+        Some(Heightmap::open(path))
+    }
+
+    #[inline]
+    fn extensions(&self) -> &[&str] {
+        &["png", "jpg"]
+    }
+}
+
+//====================================================================
+
+/// Single directional light used to shade terrain. Ambient light and fog come
+/// from the [EnvironmentSettings](crate::environment::EnvironmentSettings), and
+/// [PointLight](crate::lights::PointLight)s are added on top. Insert before
+/// adding the [TerrainPlugin] to configure it.
+#[derive(Unique, Debug, Clone)]
+pub struct TerrainLighting {
+    pub sun_direction: glam::Vec3,
+}
+
+impl Default for TerrainLighting {
+    fn default() -> Self {
+        Self {
+            sun_direction: glam::vec3(-0.4, -1., -0.3),
+        }
+    }
+}
+
+//--------------------------------------------------
+
+#[derive(Clone)]
+pub struct TerrainMaterial {
+    /// Layer weights in the rgba channels. Without one, layers are blended equally.
+    pub splat_map: Option<Handle<Texture>>,
+    /// Up to four layer textures, matching the splat map channels. Missing layers
+    /// reuse the first one.
+    pub layers: Vec<Handle<Texture>>,
+    /// How many times the layer textures repeat across the terrain.
+    pub layer_tiling: glam::Vec2,
+}
+
+impl Default for TerrainMaterial {
+    fn default() -> Self {
+        Self {
+            splat_map: None,
+            layers: Vec::new(),
+            layer_tiling: glam::Vec2::ONE,
+        }
+    }
+}
+
+//--------------------------------------------------
+
+/// Terrain built from a heightmap, split into chunks that are culled separately.
+/// Requires a [Transform] on the same entity.
+#[derive(Component)]
+pub struct Terrain {
+    heightmap: Handle<Heightmap>,
+    size: glam::Vec3,
+    chunk_size: u32,
+    material: TerrainMaterial,
+
+    gpu: Option<TerrainGpu>,
+}
+
+impl Terrain {
+    pub const DEFAULT_CHUNK_SIZE: u32 = 64;
+
+    /// Size is the world space extents of the terrain, with y being the height of
+    /// the highest sample. The terrain is centered on x and z.
+    pub fn new(heightmap: Handle<Heightmap>, size: glam::Vec3) -> Self {
+        Self {
+            heightmap,
+            size,
+            chunk_size: Self::DEFAULT_CHUNK_SIZE,
+            material: TerrainMaterial::default(),
+
+            gpu: None,
+        }
+    }
+
+    /// Number of heightmap cells along each side of a chunk.
+    #[inline]
+    pub fn with_chunk_size(mut self, chunk_size: u32) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    #[inline]
+    pub fn with_material(mut self, material: TerrainMaterial) -> Self {
+        self.material = material;
+        self
+    }
+
+    #[inline]
+    pub fn heightmap(&self) -> &Handle<Heightmap> {
+        &self.heightmap
+    }
+
+    #[inline]
+    pub fn size(&self) -> glam::Vec3 {
+        self.size
+    }
+
+    #[inline]
+    pub fn material(&self) -> &TerrainMaterial {
+        &self.material
+    }
+
+    #[inline]
+    pub fn set_material(&mut self, material: TerrainMaterial) {
+        self.material = material;
+        self.gpu = None;
+    }
+
+    #[inline]
+    pub fn set_size(&mut self, size: glam::Vec3) {
+        self.size = size;
+        self.gpu = None;
+    }
+
+    #[inline]
+    pub fn chunk_count(&self) -> usize {
+        self.gpu.as_ref().map_or(0, |gpu| gpu.chunks.len())
+    }
+
+    /// Height in local space at the given local x and z, or None if outside the terrain.
+    pub fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        let heightmap = self.heightmap.inner();
+        let map_size = heightmap.size();
+
+        // Same as when building chunks, there's no terrain to sample
+        if map_size.width < 2 || map_size.height < 2 {
+            return None;
+        }
+
+        let fx = (x / self.size.x + 0.5) * (map_size.width - 1) as f32;
+        let fz = (z / self.size.z + 0.5) * (map_size.height - 1) as f32;
+
+        if fx < 0.
+            || fz < 0.
+            || fx > (map_size.width - 1) as f32
+            || fz > (map_size.height - 1) as f32
+        {
+            return None;
+        }
+
+        let (x0, z0) = (fx.floor() as i64, fz.floor() as i64);
+        let (tx, tz) = (fx.fract(), fz.fract());
+
+        let top = heightmap.get(x0, z0) * (1. - tx) + heightmap.get(x0 + 1, z0) * tx;
+        let bottom = heightmap.get(x0, z0 + 1) * (1. - tx) + heightmap.get(x0 + 1, z0 + 1) * tx;
+
+        Some((top * (1. - tz) + bottom * tz) * self.size.y)
+    }
+}
+
+//====================================================================
+
+struct TerrainChunk {
+    mesh: Mesh,
+    min: glam::Vec3,
+    max: glam::Vec3,
+
+    world_min: glam::Vec3,
+    world_max: glam::Vec3,
+}
+
+struct TerrainGpu {
+    chunks: Vec<TerrainChunk>,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl TerrainGpu {
+    fn new(
+        device: &wgpu::Device,
+        renderer: &TerrainRenderer,
+        defaults: &DefaultRendererAssets,
+        terrain: &Terrain,
+    ) -> Self {
+        let chunks = build_chunks(
+            device,
+            terrain.heightmap.inner(),
+            terrain.size,
+            terrain.chunk_size,
+        );
+
+        let uniform_buffer = render_tools::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Terrain Uniform Buffer"),
+                contents: bytemuck::bytes_of(&<TerrainUniformRaw as bytemuck::Zeroable>::zeroed()),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        );
+
+        let material = &terrain.material;
+
+        let splat_map = material
+            .splat_map
+            .as_ref()
+            .unwrap_or(&defaults.white_texture);
+
+        let first_layer = material.layers.first().unwrap_or(&defaults.white_texture);
+        let layers = [0, 1, 2, 3].map(|index| material.layers.get(index).unwrap_or(first_layer));
+
+        let texture_entry = |binding: u32, texture: &Handle<Texture>| wgpu::BindGroupEntry {
+            binding,
+            resource: wgpu::BindingResource::TextureView(&texture.inner().raw().view),
+        };
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Terrain Bind Group"),
+            layout: &renderer.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                texture_entry(1, splat_map),
+                texture_entry(2, layers[0]),
+                texture_entry(3, layers[1]),
+                texture_entry(4, layers[2]),
+                texture_entry(5, layers[3]),
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::Sampler(&renderer.sampler),
+                },
+            ],
+        });
+
+        Self {
+            chunks,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+}
+
+fn build_chunks(
+    device: &wgpu::Device,
+    heightmap: &Heightmap,
+    size: glam::Vec3,
+    chunk_size: u32,
+) -> Vec<TerrainChunk> {
+    let map_size = heightmap.size();
+
+    if map_size.width < 2 || map_size.height < 2 {
+        log::warn!("Heightmap too small to build terrain from: {}", map_size);
+        return Vec::new();
+    }
+
+    let cells_x = map_size.width - 1;
+    let cells_z = map_size.height - 1;
+
+    let step_x = size.x / cells_x as f32;
+    let step_z = size.z / cells_z as f32;
+
+    let height = |x: u32, z: u32| heightmap.get(x as i64, z as i64) * size.y;
+
+    // Normals come from the whole heightmap so chunk edges line up
+    let vertex = |x: u32, z: u32| {
+        let pos = glam::vec3(
+            x as f32 * step_x - size.x / 2.,
+            height(x, z),
+            z as f32 * step_z - size.z / 2.,
+        );
+
+        let (x, z) = (x as i64, z as i64);
+
+        let dx = (heightmap.get(x + 1, z) - heightmap.get(x - 1, z)) * size.y / (2. * step_x);
+        let dz = (heightmap.get(x, z + 1) - heightmap.get(x, z - 1)) * size.y / (2. * step_z);
+        let normal = glam::vec3(-dx, 1., -dz).normalize();
+
+        let uv = glam::vec2(x as f32 / cells_x as f32, z as f32 / cells_z as f32);
+
+        (pos, uv, normal)
+    };
+
+    let mut chunks = Vec::new();
+
+    (0..cells_z)
+        .step_by(chunk_size as usize)
+        .for_each(|start_z| {
+            (0..cells_x)
+                .step_by(chunk_size as usize)
+                .for_each(|start_x| {
+                    let end_x = (start_x + chunk_size).min(cells_x);
+                    let end_z = (start_z + chunk_size).min(cells_z);
+
+                    let row = end_x - start_x + 1;
+                    let mut builder = MeshBuilder::with_capacity(
+                        (row * (end_z - start_z + 1)) as usize,
+                        ((end_x - start_x) * (end_z - start_z) * 6) as usize,
+                    );
+
+                    let mut min = glam::Vec3::MAX;
+                    let mut max = glam::Vec3::MIN;
+
+                    (start_z..=end_z).for_each(|z| {
+                        (start_x..=end_x).for_each(|x| {
+                            let (pos, uv, normal) = vertex(x, z);
+
+                            min = min.min(pos);
+                            max = max.max(pos);
+
+                            builder.vertex_with_normal(pos, uv, normal);
+                        });
+                    });
+
+                    (0..end_z - start_z).for_each(|z| {
+                        (0..end_x - start_x).for_each(|x| {
+                            let a = z * row + x;
+                            let c = a + row;
+
+                            builder.quad(a, c, c + 1, a + 1);
+                        });
+                    });
+
+                    chunks.push(TerrainChunk {
+                        mesh: builder.build_mesh(device, "Terrain Chunk"),
+                        min,
+                        max,
+
+                        world_min: min,
+                        world_max: max,
+                    });
+                });
+        });
+
+    log::debug!("Built terrain with {} chunks", chunks.len());
+
+    chunks
+}
+
+//====================================================================
+
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+struct TerrainUniformRaw {
+    transform: [f32; 16],
+    sun_direction: [f32; 3],
+    _padding: f32,
+    layer_tiling: [f32; 2],
+    _padding2: [f32; 2],
+}
+
+//--------------------------------------------------
+
+#[derive(Unique)]
+pub struct TerrainRenderer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+}
+
+impl TerrainRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        layouts: &BindGroupLayoutRegistry,
+        lights: &Lights,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Terrain Bind Group Layout"),
+            entries: &[
+                render_tools::bgl_uniform_entry(0, wgpu::ShaderStages::VERTEX_FRAGMENT),
+                render_tools::bgl_texture_entry(1),
+                render_tools::bgl_texture_entry(2),
+                render_tools::bgl_texture_entry(3),
+                render_tools::bgl_texture_entry(4),
+                render_tools::bgl_texture_entry(5),
+                render_tools::bgl_sampler_entry(6),
+            ],
+        });
+
+        let pipeline = Self::create_pipeline(device, config, layouts, lights, &bind_group_layout);
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Terrain Sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        layouts: &BindGroupLayoutRegistry,
+        lights: &Lights,
+        bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::RenderPipeline {
+        render_tools::create_pipeline(
+            device,
+            config,
+            "Terrain Pipeline",
+            &[
+                layouts.camera(),
+                bind_group_layout,
+                layouts.environment(),
+                layouts.lights(),
+            ],
+            &[MeshVertex::desc()],
+            &format!(
+                "{}\n{}\n{}\n{}",
+                include_str!("../shaders/terrain.wgsl"),
+                camera::TONEMAP_SHADER,
+                environment::FOG_SHADER,
+                lights.shader_source()
+            ),
+            render_tools::RenderPipelineDescriptor::default()
+                .with_depth_stencil()
+                .with_backface_culling(),
+        )
+    }
+}
+
+//====================================================================
+
+fn sys_setup_terrain_renderer(
+    all_storages: AllStoragesView,
+    device: Res<Device>,
+    config: Res<SurfaceConfig>,
+    layouts: Res<BindGroupLayoutRegistry>,
+    lights: Res<Lights>,
+) {
+    all_storages.add_unique(TerrainRenderer::new(
+        device.inner(),
+        config.inner(),
+        &layouts,
+        &lights,
+    ));
+}
+
+fn sys_rebuild_terrain_pipeline(
+    device: Res<Device>,
+    config: Res<SurfaceConfig>,
+    layouts: Res<BindGroupLayoutRegistry>,
+    lights: Res<Lights>,
+    mut renderer: ResMut<TerrainRenderer>,
+) {
+    renderer.pipeline = TerrainRenderer::create_pipeline(
+        device.inner(),
+        config.inner(),
+        &layouts,
+        &lights,
+        &renderer.bind_group_layout,
+    );
+}
+
+fn sys_build_terrain(
+    device: Res<Device>,
+    renderer: Res<TerrainRenderer>,
+    defaults: Res<DefaultRendererAssets>,
+    mut vm_terrain: ViewMut<Terrain>,
+) {
+    (&mut vm_terrain)
+        .iter()
+        .filter(|terrain| terrain.gpu.is_none())
+        .for_each(|terrain| {
+            terrain.gpu = Some(TerrainGpu::new(
+                device.inner(),
+                &renderer,
+                &defaults,
+                &*terrain,
+            ));
+        });
+}
+
+fn sys_prep_terrain(
+    queue: Res<Queue>,
+    lighting: Res<TerrainLighting>,
+    mut vm_terrain: ViewMut<Terrain>,
+    v_transform: View<Transform>,
+) {
+    (&mut vm_terrain, &v_transform)
+        .iter()
+        .for_each(|(terrain, transform)| {
+            let layer_tiling = terrain.material.layer_tiling.to_array();
+
+            let gpu = match &mut terrain.gpu {
+                Some(gpu) => gpu,
+                None => return,
+            };
+
+            let uniform = TerrainUniformRaw {
+                transform: transform.to_array(),
+                sun_direction: lighting.sun_direction.to_array(),
+                _padding: 0.,
+                layer_tiling,
+                _padding2: [0.; 2],
+            };
+
+            queue
+                .inner()
+                .write_buffer(&gpu.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+            let matrix = glam::Mat4::from_cols_array(&uniform.transform);
+
+            gpu.chunks.iter_mut().for_each(|chunk| {
+                let corners = [0, 1, 2, 3, 4, 5, 6, 7].map(|corner| {
+                    let local = glam::Vec3::select(
+                        glam::BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0),
+                        chunk.max,
+                        chunk.min,
+                    );
+                    matrix.transform_point3(local)
+                });
+
+                chunk.world_min = corners.iter().fold(glam::Vec3::MAX, |acc, c| acc.min(*c));
+                chunk.world_max = corners.iter().fold(glam::Vec3::MIN, |acc, c| acc.max(*c));
+            });
+        });
+}
+
+fn sys_render_terrain(
+    mut pass: ResMut<RenderPass>,
+    renderer: Res<TerrainRenderer>,
+    camera: Res<MainCamera>,
+    v_cameras: View<SceneCamera>,
+    stats: Res<RenderStats>,
+    environment: Res<Environment>,
+    lights: Res<Lights>,
+    v_terrain: View<Terrain>,
+    v_layers: View<RenderLayers>,
+) {
+    let size = pass.size();
+
+    camera::main_pass_cameras(&camera, &v_cameras, &v_layers)
+        .into_iter()
+        .for_each(|view| {
+            let pass = pass.pass();
+
+            view.viewport.apply(pass, size);
+
+            pass.set_pipeline(&renderer.pipeline);
+            pass.set_bind_group(0, view.bind_group, &[]);
+            pass.set_bind_group(2, environment.bind_group(), &[]);
+            pass.set_bind_group(3, lights.bind_group(), &[]);
+            stats.record(DrawCounts::pipeline());
+
+            v_terrain
+                .iter()
+                .with_id()
+                .filter(|(id, _)| RenderLayers::of(&v_layers, *id).intersects(&view.layers))
+                .filter_map(|(_, terrain)| terrain.gpu.as_ref())
+                .for_each(|gpu| {
+                    pass.set_bind_group(1, &gpu.bind_group, &[]);
+
+                    gpu.chunks
+                        .iter()
+                        .filter(|chunk| {
+                            view.frustum
+                                .intersects_aabb(chunk.world_min, chunk.world_max)
+                        })
+                        .for_each(|chunk| {
+                            chunk.mesh.draw(pass, 0, 0..1);
+                            stats.record(DrawCounts::default().draw(1));
+                        });
+                });
+        });
+}
+
+//====================================================================
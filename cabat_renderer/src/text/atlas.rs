@@ -3,13 +3,16 @@
 use std::{collections::HashSet, error::Error, fmt::Display, hash::BuildHasherDefault};
 
 use cabat_common::Size;
-use cosmic_text::{CacheKey, FontSystem, SwashCache, SwashImage};
+use cosmic_text::{CacheKey, FontSystem, SwashCache, SwashContent, SwashImage};
 use etagere::{euclid::Size2D, AllocId, BucketedAtlasAllocator};
 use lru::LruCache;
 use rustc_hash::FxHasher;
 use shipyard::Unique;
 
-use crate::{render_tools, texture::RawTexture};
+use crate::{
+    render_tools::{self, BufferPool, TextureUploadQueue},
+    texture::RawTexture,
+};
 
 //====================================================================
 
@@ -40,6 +43,30 @@ impl Display for CacheGlyphError {
 
 //====================================================================
 
+/// Pixels of distance stored around each glyph in [GlyphMode::Sdf].
+/// Also the widest outline that mode can draw.
+pub const SDF_SPREAD: u32 = 6;
+
+/// How glyphs are stored in the [TextAtlas].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GlyphMode {
+    /// Rasterized coverage. Sharpest at the size the text was laid out at,
+    /// but blurry once scaled up.
+    #[default]
+    Bitmap,
+    /// Signed distance fields, which stay crisp at any scale and can draw
+    /// outlines without extra copies of the text.
+    Sdf,
+}
+
+/// Insert before adding the [crate::text::Text3dPlugin] to configure the [TextAtlas].
+#[derive(Unique, Debug, Clone, Default)]
+pub struct TextAtlasSettings {
+    pub mode: GlyphMode,
+}
+
+//====================================================================
+
 pub struct GlyphData {
     alloc_id: AllocId,
     pub uv_start: [f32; 2],
@@ -54,6 +81,7 @@ type Hasher = BuildHasherDefault<FxHasher>;
 
 #[derive(Unique)]
 pub struct TextAtlas {
+    mode: GlyphMode,
     packer: BucketedAtlasAllocator,
 
     glyphs_in_use: HashSet<CacheKey, Hasher>,
@@ -61,12 +89,13 @@ pub struct TextAtlas {
 
     texture: RawTexture,
     texture_size: Size<u32>,
+    uploads: TextureUploadQueue,
     bind_group_layout: wgpu::BindGroupLayout,
     bind_group: wgpu::BindGroup,
 }
 
 impl TextAtlas {
-    pub fn new(device: &wgpu::Device) -> Self {
+    pub fn new(device: &wgpu::Device, mode: GlyphMode) -> Self {
         const DEFAULT_START_SIZE: u32 = 256;
 
         let packer = BucketedAtlasAllocator::new(Size2D::new(
@@ -77,7 +106,23 @@ impl TextAtlas {
         let cached_glyphs = LruCache::unbounded_with_hasher(Hasher::default());
 
         let texture_size = Size::new(DEFAULT_START_SIZE, DEFAULT_START_SIZE);
-        let texture = RawTexture::from_size(device, texture_size, Some("Text Atlas Texture"), None);
+
+        // Distance fields rely on filtering to reconstruct the edge between texels
+        let sampler = match mode {
+            GlyphMode::Bitmap => None,
+            GlyphMode::Sdf => Some(wgpu::SamplerDescriptor {
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            }),
+        };
+
+        let texture = RawTexture::from_size(
+            device,
+            texture_size,
+            Some("Text Atlas Texture"),
+            sampler.as_ref(),
+        );
 
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("Text Atlas Bind Group Layout"),
@@ -103,16 +148,23 @@ impl TextAtlas {
         });
 
         Self {
+            mode,
             packer,
             glyphs_in_use,
             cached_glyphs,
             texture,
             texture_size,
+            uploads: TextureUploadQueue::new(wgpu::TextureFormat::R8Unorm),
             bind_group_layout,
             bind_group,
         }
     }
 
+    #[inline]
+    pub fn mode(&self) -> GlyphMode {
+        self.mode
+    }
+
     #[inline]
     pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
         &self.bind_group_layout
@@ -131,7 +183,6 @@ impl TextAtlas {
     pub fn use_glyph(
         &mut self,
         device: &wgpu::Device,
-        queue: &wgpu::Queue,
         font_system: &mut FontSystem,
         swash_cache: &mut SwashCache,
         key: &CacheKey,
@@ -149,7 +200,7 @@ impl TextAtlas {
                 .get_image_uncached(font_system, *key)
                 .ok_or(CacheGlyphError::NoGlyphImage)?;
 
-            self.cache_glyph(device, queue, key, &image)?;
+            self.cache_glyph(device, key, &image)?;
 
             self.cached_glyphs.promote(key);
             self.glyphs_in_use.insert(*key);
@@ -157,20 +208,30 @@ impl TextAtlas {
         }
     }
 
+    // Glyphs are promoted when used, so this doesn't need to touch the LRU order
     #[inline]
-    pub fn get_glyph_data(&mut self, key: &CacheKey) -> Option<&GlyphData> {
-        self.cached_glyphs.get(key)
+    pub fn get_glyph_data(&self, key: &CacheKey) -> Option<&GlyphData> {
+        self.cached_glyphs.peek(key)
     }
 
     fn cache_glyph(
         &mut self,
         device: &wgpu::Device,
-        queue: &wgpu::Queue,
         key: &CacheKey,
         image: &SwashImage,
     ) -> Result<(), CacheGlyphError> {
-        let image_width = image.placement.width;
-        let image_height = image.placement.height;
+        let sdf = match self.mode {
+            GlyphMode::Bitmap => None,
+            GlyphMode::Sdf => Some(generate_sdf(image)),
+        };
+
+        let (data, padding) = match &sdf {
+            Some(sdf) => (sdf.as_slice(), SDF_SPREAD),
+            None => (image.data.as_slice(), 0),
+        };
+
+        let image_width = image.placement.width + padding * 2;
+        let image_height = image.placement.height + padding * 2;
 
         let size = etagere::Size::new(image_width.max(1) as i32, image_height.max(1) as i32);
 
@@ -186,8 +247,8 @@ impl TextAtlas {
         let x = allocation.rectangle.min.x as u32;
         let y = allocation.rectangle.min.y as u32;
 
-        self.texture
-            .update_area(queue, &image.data, x, y, image_width, image_height);
+        // Uploaded with the rest of the frame's glyphs before rendering
+        self.uploads.push(data, x, y, image_width, image_height);
 
         let uv_start = [
             allocation.rectangle.min.x as f32 / self.texture_size.width as f32,
@@ -199,10 +260,10 @@ impl TextAtlas {
             allocation.rectangle.max.y as f32 / self.texture_size.height as f32,
         ];
 
-        let left = image.placement.left as f32;
-        let top = image.placement.top as f32;
-        let width = image.placement.width as f32;
-        let height = image.placement.height as f32;
+        let left = image.placement.left as f32 - padding as f32;
+        let top = image.placement.top as f32 + padding as f32;
+        let width = image_width as f32;
+        let height = image_height as f32;
 
         // log::trace!(
         //     "Allocated glyph id {:?}, with size {:?} and uv ({:?}, {:?})",
@@ -249,6 +310,18 @@ impl TextAtlas {
         return Ok(());
     }
 
+    /// Record glyphs cached since the last flush into the encoder.
+    pub fn flush_uploads(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        pool: &mut BufferPool,
+    ) {
+        self.uploads
+            .flush(device, queue, encoder, pool, &self.texture.texture);
+    }
+
     #[inline]
     pub fn post_render_trim(&mut self) {
         self.glyphs_in_use.clear();
@@ -256,3 +329,68 @@ impl TextAtlas {
 }
 
 //====================================================================
+
+// Coverage of a glyph pixel, whatever format it was rasterized in
+fn coverage(image: &SwashImage, x: i32, y: i32) -> u8 {
+    let width = image.placement.width as i32;
+    let height = image.placement.height as i32;
+
+    if x < 0 || y < 0 || x >= width || y >= height {
+        return 0;
+    }
+
+    let index = (y * width + x) as usize;
+
+    match image.content {
+        SwashContent::Mask => image.data[index],
+        SwashContent::SubpixelMask => {
+            let pixel = &image.data[index * 4..index * 4 + 3];
+            ((pixel[0] as u32 + pixel[1] as u32 + pixel[2] as u32) / 3) as u8
+        }
+        SwashContent::Color => image.data[index * 4 + 3],
+    }
+}
+
+/// Distance to the glyph edge for each pixel of the glyph, padded by
+/// [SDF_SPREAD] on every side. 0.5 (127) is the edge, higher values are inside.
+fn generate_sdf(image: &SwashImage) -> Vec<u8> {
+    let spread = SDF_SPREAD as i32;
+    let width = image.placement.width as i32 + spread * 2;
+    let height = image.placement.height as i32 + spread * 2;
+
+    let inside = |x: i32, y: i32| coverage(image, x - spread, y - spread) >= 128;
+
+    // Glyphs are small, so searching the spread around each pixel is fast enough
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let is_inside = inside(x, y);
+            let mut nearest = (spread * spread) as f32;
+
+            (-spread..=spread).for_each(|offset_y| {
+                (-spread..=spread).for_each(|offset_x| {
+                    if inside(x + offset_x, y + offset_y) != is_inside {
+                        let distance = (offset_x * offset_x + offset_y * offset_y) as f32;
+                        nearest = nearest.min(distance);
+                    }
+                });
+            });
+
+            // The edge lies half way between opposing pixels
+            let distance = nearest.sqrt() - 0.5;
+            let signed = match is_inside {
+                true => distance,
+                false => -distance,
+            };
+
+            ((0.5 + signed / (spread as f32 * 2.)).clamp(0., 1.) * 255.) as u8
+        })
+        .collect()
+}
+
+/// Distance field value the edge of an outline `width` pixels wide falls on.
+pub(crate) fn sdf_outline_edge(width: f32) -> f32 {
+    (0.5 - width / (SDF_SPREAD as f32 * 2.)).max(0.)
+}
+
+//====================================================================
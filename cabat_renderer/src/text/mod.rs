@@ -1,6 +1,6 @@
 //====================================================================
 
-use cabat_shipyard::Res;
+use cabat_shipyard::{Res, WrappedUnique};
 use shipyard::{AllStoragesView, Unique};
 
 use crate::Device;
@@ -9,43 +9,154 @@ mod atlas;
 mod text2d;
 mod text3d;
 
-pub use atlas::TextAtlas;
-pub use cosmic_text::{Attrs, Color, Metrics};
+pub use atlas::{GlyphMode, TextAtlas, TextAtlasSettings, SDF_SPREAD};
+pub use cosmic_text::{Attrs, Buffer, Color, Cursor, Metrics};
 pub use text2d::{Text2dBuffer, Text2dBufferDescriptor, Text2dPlugin, Text2dRenderer};
 pub use text3d::{Text3dBuffer, Text3dBufferDescriptor, Text3dPlugin, Text3dRenderer};
 
 //====================================================================
 
-#[derive(Unique)]
-pub struct TextFontSystem(cosmic_text::FontSystem);
-impl TextFontSystem {
-    pub fn inner(&self) -> &cosmic_text::FontSystem {
-        &self.0
+/// Outline drawn around each glyph, for text that needs to stay readable over
+/// busy backgrounds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextOutline {
+    pub color: Color,
+    /// Thickness in logical pixels.
+    pub width: f32,
+}
+
+impl TextOutline {
+    #[inline]
+    pub fn new(color: Color, width: f32) -> Self {
+        Self { color, width }
     }
+}
 
-    pub fn inner_mut(&mut self) -> &mut cosmic_text::FontSystem {
-        &mut self.0
+/// Copy of the text drawn behind it at an offset.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextShadow {
+    pub color: Color,
+    /// Offset in logical pixels. Positive y is down.
+    pub offset: glam::Vec2,
+}
+
+impl TextShadow {
+    #[inline]
+    pub fn new(color: Color, offset: glam::Vec2) -> Self {
+        Self { color, offset }
     }
 }
 
-#[derive(Unique)]
-pub struct TextSwashCache(cosmic_text::SwashCache);
-impl TextSwashCache {
-    pub fn inner(&self) -> &cosmic_text::SwashCache {
-        &self.0
+/// Size of laid out text.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TextMeasurement {
+    pub width: f32,
+    pub height: f32,
+    pub lines: usize,
+}
+
+impl TextMeasurement {
+    fn from_buffer(buffer: &cosmic_text::Buffer) -> Self {
+        buffer
+            .layout_runs()
+            .fold(Self::default(), |measurement, run| Self {
+                width: measurement.width.max(run.line_w),
+                height: measurement.height.max(run.line_top + run.line_height),
+                lines: measurement.lines + 1,
+            })
+    }
+
+    #[inline]
+    fn scaled(self, scale: f32) -> Self {
+        Self {
+            width: self.width * scale,
+            height: self.height * scale,
+            lines: self.lines,
+        }
+    }
+}
+
+/// Measure text without creating a buffer for it, in the same units as the
+/// metrics. Text wraps to `width` if one is given.
+pub fn measure_text(
+    font_system: &mut cosmic_text::FontSystem,
+    text: &str,
+    metrics: Metrics,
+    width: Option<f32>,
+) -> TextMeasurement {
+    let mut buffer = cosmic_text::Buffer::new(font_system, metrics);
+
+    buffer.set_size(font_system, width, None);
+    buffer.set_text(
+        font_system,
+        text,
+        Attrs::new(),
+        cosmic_text::Shaping::Advanced,
+    );
+
+    TextMeasurement::from_buffer(&buffer)
+}
+
+//--------------------------------------------------
+
+// Both outlines and shadows are drawn as extra copies of the text behind it.
+// Returns the offset and color of each copy, back to front.
+fn effect_layers(
+    outline: Option<TextOutline>,
+    shadow: Option<TextShadow>,
+) -> Vec<(glam::Vec2, Color)> {
+    let mut layers = Vec::new();
+
+    if let Some(shadow) = shadow {
+        layers.push((shadow.offset, shadow.color));
     }
 
-    pub fn inner_mut(&mut self) -> &mut cosmic_text::SwashCache {
-        &mut self.0
+    if let Some(outline) = outline {
+        const DIRECTIONS: [(f32, f32); 8] = [
+            (-1., -1.),
+            (0., -1.),
+            (1., -1.),
+            (-1., 0.),
+            (1., 0.),
+            (-1., 1.),
+            (0., 1.),
+            (1., 1.),
+        ];
+
+        layers.extend(DIRECTIONS.iter().map(|(x, y)| {
+            (
+                glam::Vec2::new(*x, *y).normalize() * outline.width,
+                outline.color,
+            )
+        }));
     }
+
+    layers
 }
 
 //====================================================================
 
-fn sys_setup_text_components(all_storages: AllStoragesView, device: Res<Device>) {
+#[derive(Unique, WrappedUnique)]
+pub struct TextFontSystem(cosmic_text::FontSystem);
+
+#[derive(Unique, WrappedUnique)]
+pub struct TextSwashCache(cosmic_text::SwashCache);
+
+//====================================================================
+
+// Shared by the 2d and 3d text plugins - only the first to run creates them
+fn sys_setup_text_components(
+    all_storages: AllStoragesView,
+    device: Res<Device>,
+    settings: Res<TextAtlasSettings>,
+) {
+    if all_storages.borrow::<Res<TextFontSystem>>().is_ok() {
+        return;
+    }
+
     all_storages.add_unique(TextFontSystem(cosmic_text::FontSystem::new()));
     all_storages.add_unique(TextSwashCache(cosmic_text::SwashCache::new()));
-    all_storages.add_unique(TextAtlas::new(device.inner()));
+    all_storages.add_unique(TextAtlas::new(device.inner(), settings.mode));
 }
 
 //====================================================================
@@ -1,19 +1,29 @@
 //====================================================================
 
-use cabat_common::{WindowResizeEvent, WindowSize};
-use cabat_shipyard::prelude::*;
+use cabat_common::{Size, UiPosition, UiVal, WindowResizeEvent, WindowScale, WindowSize};
+use cabat_shipyard::{prelude::*, UniqueTools};
 use glyphon::{
     Attrs, Buffer, Cache, Color, Metrics, Resolution, Shaping, TextArea, TextAtlas, TextBounds,
     TextRenderer, Viewport, Wrap,
 };
 use shipyard::{
-    AllStoragesView, Component, IntoIter, IntoWorkload, SystemModificator, Unique, View,
-    WorkloadModificator,
+    AllStoragesView, Component, IntoIter, IntoWithId, IntoWorkload, SystemModificator, Unique,
+    View, ViewMut, WorkloadModificator,
 };
 
-use crate::{Device, Queue, RenderEncoder, RenderPassDesc, SurfaceConfig};
+use crate::{
+    accessibility::AccessibilitySettings,
+    render_graph::{resources, AddRenderPass, RenderGraphNode},
+    settings::SurfaceFormatChangedEvent,
+    shared::{self, SortKey},
+    visibility::{self, Visibility},
+    Device, DrawCounts, Queue, RenderEncoder, RenderPassDesc, RenderStats, SurfaceConfig,
+};
 
-use super::{sys_setup_text_components, TextFontSystem, TextSwashCache};
+use super::{
+    effect_layers, sys_setup_text_components, TextAtlasSettings, TextFontSystem, TextMeasurement,
+    TextOutline, TextShadow, TextSwashCache,
+};
 
 //====================================================================
 
@@ -21,22 +31,34 @@ pub struct Text2dPlugin;
 
 impl Plugin for Text2dPlugin {
     fn build(self, builder: &WorkloadBuilder) {
+        builder.get_or_insert(TextAtlasSettings::default);
+
+        let setup_systems = (sys_setup_text_components, sys_setup_text_pipeline);
+
         builder
+            .describe_order(Stages::Setup, &setup_systems, &[], &["renderer_setup"])
             .add_workload_first(
                 Stages::Setup,
-                (sys_setup_text_components, sys_setup_text_pipeline)
+                setup_systems
                     .into_sequential_workload()
                     .after_all("renderer_setup"),
             )
-            .add_workload_last(Stages::Update, sys_prep_text)
-            .add_workload_post(
-                Stages::Render,
-                sys_render
-                    .skip_if_missing_unique::<RenderEncoder>()
-                    .after_all(crate::sys_finish_main_render_pass),
+            .add_workload_last(
+                Stages::Update,
+                (sys_layout_text, sys_prep_text).into_sequential_workload(),
+            )
+            .add_render_pass(
+                RenderGraphNode::new("text2d")
+                    .reads(resources::SCENE)
+                    .reads(resources::SCENE_OUTPUT)
+                    .writes(resources::SURFACE),
+                sys_render.skip_if_missing_unique::<RenderEncoder>(),
             )
             .add_workload(Stages::Last, sys_trim_text_pipeline)
-            .add_event::<WindowResizeEvent>((sys_resize_text_pipeline).into_workload());
+            .add_event::<WindowResizeEvent>((sys_resize_text_pipeline).into_workload())
+            .add_event::<SurfaceFormatChangedEvent>(
+                (sys_setup_text_pipeline, sys_resize_text_pipeline).into_sequential_workload(),
+            );
     }
 }
 
@@ -124,25 +146,83 @@ fn sys_resize_text_pipeline(
     text_pipeline.resize(queue.inner(), size.width(), size.height());
 }
 
+// Percentage sizes depend on the window, so buffers are re-laid out whenever it
+// (or the window scale factor or accessibility text scale) changes.
+fn sys_layout_text(
+    size: Res<WindowSize>,
+    scale: Res<WindowScale>,
+    accessibility: Res<AccessibilitySettings>,
+    mut font_system: ResMut<TextFontSystem>,
+    mut vm_buffers: ViewMut<Text2dBuffer>,
+) {
+    let text_scale = accessibility.effective_text_scale();
+
+    (&mut vm_buffers).iter().for_each(|buffer| {
+        buffer.layout(
+            font_system.inner_mut(),
+            size.size(),
+            scale.scale_factor(),
+            text_scale,
+        );
+    });
+}
+
 fn sys_prep_text(
     device: Res<Device>,
     queue: Res<Queue>,
+    size: Res<WindowSize>,
+    accessibility: Res<AccessibilitySettings>,
 
     mut text_pipeline: ResMut<Text2dRenderer>,
     mut font_system: ResMut<TextFontSystem>,
     mut swash_cache: ResMut<TextSwashCache>,
     v_buffers: View<Text2dBuffer>,
+    v_visibility: View<Visibility>,
 ) {
-    let data = v_buffers
+    let window = Size::new(size.width_f32(), size.height_f32());
+    let palette = accessibility.contrast_palette();
+
+    let mut buffers = v_buffers
         .iter()
-        .map(|buffer| TextArea {
-            buffer: &buffer.buffer,
-            left: buffer.pos.0,
-            top: buffer.pos.1,
-            scale: 1.,
-            bounds: buffer.bounds,
-            default_color: buffer.color,
-            custom_glyphs: &[],
+        .with_id()
+        .filter(|(id, _)| visibility::is_visible(&v_visibility, *id))
+        .map(|(id, buffer)| (buffer.sort_key, id, buffer))
+        .collect::<Vec<_>>();
+    shared::sort_draw_order(&mut buffers);
+
+    // Areas are drawn in order, so outline and shadow copies go first
+    let data = buffers
+        .into_iter()
+        .flat_map(|(_, _, buffer)| {
+            let (left, top) = buffer.screen_position(window);
+
+            // High contrast swaps shadows for a solid outline behind the text
+            let (color, outline, shadow) = match palette {
+                Some(palette) => (
+                    palette.text_color(),
+                    Some(TextOutline::new(palette.background_text_color(), 1.)),
+                    None,
+                ),
+                None => (buffer.color, buffer.outline, buffer.shadow),
+            };
+
+            effect_layers(outline, shadow)
+                .into_iter()
+                .chain(std::iter::once((glam::Vec2::ZERO, color)))
+                .map(move |(offset, color)| {
+                    let left = left + offset.x * buffer.scale_factor;
+                    let top = top + offset.y * buffer.scale_factor;
+
+                    TextArea {
+                        buffer: &buffer.buffer,
+                        left,
+                        top,
+                        scale: buffer.scale_factor,
+                        bounds: buffer.screen_bounds(left, top),
+                        default_color: color,
+                        custom_glyphs: &[],
+                    }
+                })
         })
         .collect::<Vec<_>>();
 
@@ -157,9 +237,15 @@ fn sys_prep_text(
         .unwrap();
 }
 
-fn sys_render(mut tools: ResMut<RenderEncoder>, pipeline: Res<Text2dRenderer>) {
+fn sys_render(
+    mut tools: ResMut<RenderEncoder>,
+    pipeline: Res<Text2dRenderer>,
+    stats: Res<RenderStats>,
+) {
     let mut pass = tools.begin_render_pass(RenderPassDesc::none());
     pipeline.render(&mut pass);
+    // All text areas are drawn with a single call
+    stats.record(DrawCounts::pipeline().draw(1));
 }
 
 fn sys_trim_text_pipeline(mut text_pipeline: ResMut<Text2dRenderer>) {
@@ -171,6 +257,7 @@ fn sys_trim_text_pipeline(mut text_pipeline: ResMut<Text2dRenderer>) {
 pub struct Text2dBufferDescriptor<'a> {
     pub metrics: Metrics,
 
+    /// Clipping bounds, in logical pixels relative to the text position.
     pub bounds_top: i32,
     pub bounds_bottom: i32,
     pub bounds_left: i32,
@@ -178,11 +265,18 @@ pub struct Text2dBufferDescriptor<'a> {
     pub word_wrap: Wrap,
 
     pub text: &'a str,
-    pub pos: (f32, f32),
-    pub width: Option<f32>,
-    pub height: Option<f32>,
+    pub position: UiPosition,
+    pub width: Option<UiVal>,
+    pub height: Option<UiVal>,
+    /// Applied on top of the window scale factor. Unlike the accessibility
+    /// text scale this also scales the position.
+    pub scale: f32,
 
     pub color: Color,
+    pub outline: Option<TextOutline>,
+    pub shadow: Option<TextShadow>,
+    /// Text with a higher sort key is drawn on top.
+    pub sort_key: SortKey,
 }
 
 impl Default for Text2dBufferDescriptor<'_> {
@@ -198,11 +292,15 @@ impl Default for Text2dBufferDescriptor<'_> {
             word_wrap: Wrap::WordOrGlyph,
 
             text: "",
-            pos: (0., 0.),
-            width: Some(800.),
+            position: UiPosition::default(),
+            width: Some(UiVal::Px(800.)),
             height: None,
+            scale: 1.,
 
             color: glyphon::Color::rgb(0, 0, 0),
+            outline: None,
+            shadow: None,
+            sort_key: SortKey::default(),
         }
     }
 }
@@ -219,9 +317,24 @@ impl<'a> Text2dBufferDescriptor<'a> {
 #[derive(Component)]
 pub struct Text2dBuffer {
     pub buffer: Buffer,
+    /// Clipping bounds, in logical pixels relative to the text position.
     pub bounds: TextBounds,
-    pub pos: (f32, f32),
+    pub position: UiPosition,
     pub color: glyphon::Color,
+    pub outline: Option<TextOutline>,
+    pub shadow: Option<TextShadow>,
+    pub sort_key: SortKey,
+
+    width: Option<UiVal>,
+    height: Option<UiVal>,
+    scale: f32,
+    // Buffer scale combined with the window scale factor, for positions and sizes
+    position_scale: f32,
+    // Position scale combined with the accessibility text scale, for glyphs
+    scale_factor: f32,
+
+    // Window size and scale factors the buffer size was last resolved against
+    layout: Option<(Size<u32>, f32, f32)>,
 }
 
 impl Text2dBuffer {
@@ -229,9 +342,7 @@ impl Text2dBuffer {
         let mut buffer = Buffer::new(font_system, desc.metrics);
 
         buffer.set_text(font_system, desc.text, Attrs::new(), Shaping::Advanced);
-
         buffer.set_wrap(font_system, desc.word_wrap);
-        buffer.set_size(font_system, desc.width, desc.height);
 
         Self {
             buffer,
@@ -241,8 +352,19 @@ impl Text2dBuffer {
                 right: desc.bounds_right,
                 bottom: desc.bounds_bottom,
             },
-            pos: desc.pos,
+            position: desc.position,
             color: desc.color,
+            outline: desc.outline,
+            shadow: desc.shadow,
+            sort_key: desc.sort_key,
+
+            width: desc.width,
+            height: desc.height,
+            scale: desc.scale,
+            position_scale: desc.scale,
+            scale_factor: desc.scale,
+
+            layout: None,
         }
     }
 
@@ -252,26 +374,114 @@ impl Text2dBuffer {
             .set_text(font_system, text, Attrs::new(), Shaping::Advanced);
     }
 
+    /// Set the layout size. Applied on the next frame, once it can be resolved
+    /// against the window.
+    #[inline]
+    pub fn set_size(&mut self, width: Option<UiVal>, height: Option<UiVal>) {
+        self.width = width;
+        self.height = height;
+        self.layout = None;
+    }
+
     #[inline]
-    pub fn set_size(
+    pub fn set_metrics_and_size(
         &mut self,
         font_system: &mut cosmic_text::FontSystem,
-        width: Option<f32>,
-        height: Option<f32>,
+        metrics: Metrics,
+        width: Option<UiVal>,
+        height: Option<UiVal>,
     ) {
-        self.buffer.set_size(font_system, width, height);
+        self.buffer.set_metrics(font_system, metrics);
+        self.set_size(width, height);
     }
 
     #[inline]
-    pub fn set_metrics_and_size(
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    #[inline]
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale;
+        self.layout = None;
+    }
+
+    /// Final scale the text is rendered at, including the window scale factor
+    /// and accessibility text scale.
+    #[inline]
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+
+    fn layout(
         &mut self,
         font_system: &mut cosmic_text::FontSystem,
-        metrics: Metrics,
-        width: Option<f32>,
-        height: Option<f32>,
+        window: Size<u32>,
+        window_scale: f32,
+        text_scale: f32,
     ) {
-        self.buffer
-            .set_metrics_and_size(font_system, metrics, width, height);
+        let position_scale = self.scale * window_scale;
+        let scale_factor = position_scale * text_scale;
+        let current = Some((window, position_scale, scale_factor));
+
+        if self.layout == current {
+            return;
+        }
+
+        self.position_scale = position_scale;
+        self.scale_factor = scale_factor;
+
+        // Buffer sizes are in logical units as glyphon scales the whole area, so
+        // larger text wraps within the same space
+        let resolve = |val: UiVal, extent: u32| {
+            val.resolve(extent as f32, self.position_scale) / self.scale_factor
+        };
+
+        let width = self.width.map(|width| resolve(width, window.width));
+        let height = self.height.map(|height| resolve(height, window.height));
+
+        self.buffer.set_size(font_system, width, height);
+        self.layout = current;
+    }
+
+    /// Size and line count of the laid out text, in physical pixels.
+    ///
+    /// Percentage sizes and the window scale factor are applied when the text is
+    /// laid out at the end of the update stage, so changes made this frame may not
+    /// be reflected until then.
+    #[inline]
+    pub fn measure(&self) -> TextMeasurement {
+        TextMeasurement::from_buffer(&self.buffer).scaled(self.scale_factor)
+    }
+
+    #[inline]
+    pub fn line_count(&self) -> usize {
+        self.buffer.layout_runs().count()
+    }
+
+    /// Size of the laid out text in physical pixels.
+    #[inline]
+    pub fn content_size(&self) -> Size<f32> {
+        let measurement = self.measure();
+        Size::new(measurement.width, measurement.height)
+    }
+
+    /// Top left corner of the text in physical pixels.
+    #[inline]
+    pub fn screen_position(&self, window: Size<f32>) -> (f32, f32) {
+        self.position
+            .resolve(window, self.content_size(), self.position_scale)
+    }
+
+    fn screen_bounds(&self, left: f32, top: f32) -> TextBounds {
+        let scale = |value: i32| (value as f32 * self.position_scale) as i32;
+
+        TextBounds {
+            left: left as i32 + scale(self.bounds.left),
+            top: top as i32 + scale(self.bounds.top),
+            right: left as i32 + scale(self.bounds.right),
+            bottom: top as i32 + scale(self.bounds.bottom),
+        }
     }
 }
 
@@ -2,19 +2,33 @@
 
 use std::hash::{Hash, Hasher};
 
-use cabat_shipyard::prelude::*;
-use cabat_spatial::Transform;
+use cabat_shipyard::{prelude::*, UniqueTools};
+use cabat_spatial::{SpatialIndex, Transform};
 use cosmic_text::{Attrs, Buffer, CacheKey, Color, FontSystem, Metrics, Shaping, SwashCache, Wrap};
 use rustc_hash::FxHasher;
 use shipyard::{
-    track, AllStoragesView, Component, IntoIter, IntoWorkload, SystemModificator, Unique, View,
-    ViewMut, WorkloadModificator,
+    track, AllStoragesView, Component, IntoIter, IntoWithId, IntoWorkload, SystemModificator,
+    Unique, View, ViewMut, WorkloadModificator,
 };
-use wgpu::util::DeviceExt;
 
-use crate::{camera::MainCamera, render_tools, Device, Queue, RenderPass, SurfaceConfig, Vertex};
+use crate::{
+    accessibility::{AccessibilitySettings, HighContrastPalette},
+    camera::{self, MainCamera, SceneCamera},
+    environment::{self, Environment},
+    render_graph::{resources, AddRenderPass, RenderGraphNode},
+    render_target::RenderTarget,
+    render_tools::{self, BufferPool, DynamicUniformBuffer, InstanceBuffer},
+    settings::SurfaceFormatChangedEvent,
+    shared::BindGroupLayoutRegistry,
+    visibility::{self, RenderLayers, Visibility},
+    Device, DrawCounts, Queue, RenderEncoder, RenderPass, RenderStats, SurfaceConfig, Vertex,
+};
 
-use super::{atlas::TextAtlas, sys_setup_text_components, TextFontSystem, TextSwashCache};
+use super::{
+    atlas::{self, GlyphMode, TextAtlas},
+    effect_layers, sys_setup_text_components, TextAtlasSettings, TextFontSystem, TextMeasurement,
+    TextOutline, TextShadow, TextSwashCache,
+};
 
 //====================================================================
 
@@ -22,19 +36,38 @@ pub struct Text3dPlugin;
 
 impl Plugin for Text3dPlugin {
     fn build(self, builder: &WorkloadBuilder) {
+        builder.get_or_insert(TextAtlasSettings::default);
+
+        let setup_systems = (sys_setup_text_components, sys_setup_text_pipeline);
+
         builder
+            .describe_order(Stages::Setup, &setup_systems, &[], &["renderer_setup"])
             .add_workload_first(
                 Stages::Setup,
-                (sys_setup_text_components, sys_setup_text_pipeline)
+                setup_systems
                     .into_sequential_workload()
                     .after_all("renderer_setup"),
             )
-            .add_workload_last(Stages::Update, (sys_prep_text, sys_prep_text_transform))
-            .add_workload(
+            .add_workload_last(
+                Stages::Update,
+                (sys_cull_text, sys_prep_text, sys_prep_text_transform)
+                    .into_workload()
+                    .after_all("update_spatial_index"),
+            )
+            .add_render_pass(
+                RenderGraphNode::new("text3d").writes(resources::MAIN_PASS),
+                sys_render_text,
+            )
+            .add_render_pass(
+                RenderGraphNode::new("text3d_targets").writes(resources::RENDER_TARGETS),
+                sys_render_text_targets,
+            )
+            .add_workload_pre(
                 Stages::Render,
-                sys_render_text.skip_if_missing_unique::<RenderPass>(),
+                sys_flush_atlas.skip_if_missing_unique::<RenderEncoder>(),
             )
-            .add_workload(Stages::Last, sys_trim_atlas);
+            .add_workload(Stages::Last, sys_trim_atlas)
+            .add_event::<SurfaceFormatChangedEvent>(sys_setup_text_pipeline.into_workload());
     }
 }
 
@@ -42,15 +75,10 @@ fn sys_setup_text_pipeline(
     all_storages: AllStoragesView,
     device: Res<Device>,
     config: Res<SurfaceConfig>,
+    layouts: Res<BindGroupLayoutRegistry>,
     atlas: Res<TextAtlas>,
-    camera: Res<MainCamera>,
 ) {
-    let pipeline = Text3dRenderer::new(
-        device.inner(),
-        config.inner(),
-        &atlas,
-        camera.bind_group_layout(),
-    );
+    let pipeline = Text3dRenderer::new(device.inner(), config.inner(), &layouts, &atlas);
 
     all_storages.add_unique(pipeline);
 }
@@ -63,6 +91,7 @@ fn sys_prep_text(
     mut font_system: ResMut<TextFontSystem>,
     mut swash_cache: ResMut<TextSwashCache>,
     mut text_atlas: ResMut<TextAtlas>,
+    accessibility: Res<AccessibilitySettings>,
 
     mut vm_text_buffer: ViewMut<Text3dBuffer>,
 ) {
@@ -72,37 +101,172 @@ fn sys_prep_text(
         font_system.inner_mut(),
         swash_cache.inner_mut(),
         &mut text_atlas,
+        accessibility.contrast_palette(),
         (&mut vm_text_buffer).iter(),
     )
 }
 
+// Upload glyphs cached this frame before any text is drawn
+fn sys_flush_atlas(
+    device: Res<Device>,
+    queue: Res<Queue>,
+    mut encoder: ResMut<RenderEncoder>,
+    mut pool: ResMut<BufferPool>,
+    mut atlas: ResMut<TextAtlas>,
+) {
+    atlas.flush_uploads(device.inner(), queue.inner(), encoder.encoder(), &mut pool);
+}
+
 fn sys_prep_text_transform(
+    device: Res<Device>,
     queue: Res<Queue>,
+    mut renderer: ResMut<Text3dRenderer>,
 
-    v_text_buffer: View<Text3dBuffer>,
+    mut vm_text_buffer: ViewMut<Text3dBuffer>,
     v_transform: View<Transform, track::All>,
 ) {
-    (v_transform.inserted_or_modified(), &v_text_buffer)
+    (v_transform.inserted_or_modified(), &mut vm_text_buffer)
         .iter()
         .for_each(|(transform, text_buffer)| {
-            text_buffer.update_transform(queue.inner(), transform);
+            text_buffer.update_transform(transform);
         });
+
+    renderer.prep_transforms(device.inner(), queue.inner(), (&mut vm_text_buffer).iter());
+}
+
+fn sys_cull_text(
+    camera: Res<MainCamera>,
+    index: Option<Res<SpatialIndex>>,
+    v_cameras: View<SceneCamera>,
+    v_targets: View<RenderTarget>,
+    v_layers: View<RenderLayers>,
+    v_text_buffer: View<Text3dBuffer>,
+    v_transform: View<Transform>,
+    mut vm_visibility: ViewMut<Visibility>,
+) {
+    let frustums = visibility::active_frustums(&camera, &v_cameras, &v_targets, &v_layers);
+    let candidates = visibility::IndexedCandidates::new(index.as_deref(), &frustums);
+
+    (&v_transform, &v_text_buffer, &mut vm_visibility)
+        .iter()
+        .with_id()
+        .for_each(|(id, (transform, text_buffer, visibility))| {
+            if candidates.excludes(id) {
+                visibility.set_culled(true);
+                return;
+            }
+
+            // Text starts at the transform, so a sphere reaching the far corner
+            // covers it whichever way it is rotated
+            let size = text_buffer.measure();
+            let radius = glam::Vec2::new(size.width, size.height).length()
+                * transform.scale.abs().max_element();
+
+            let visible = visibility::sphere_visible(
+                &frustums,
+                RenderLayers::of(&v_layers, id),
+                transform.translation,
+                radius,
+            );
+
+            visibility.set_culled(!visible);
+        });
+}
+
+fn visible_buffers<'a>(
+    v_text_buffers: &'a View<Text3dBuffer>,
+    v_visibility: &View<Visibility>,
+    v_layers: &View<RenderLayers>,
+) -> Vec<(&'a Text3dBuffer, RenderLayers)> {
+    v_text_buffers
+        .iter()
+        .with_id()
+        .filter(|(id, _)| visibility::is_visible(v_visibility, *id))
+        .map(|(id, buffer)| (buffer, RenderLayers::of(v_layers, id)))
+        .collect()
+}
+
+fn buffers_on_layers<'a>(
+    buffers: &'a [(&'a Text3dBuffer, RenderLayers)],
+    camera_layers: RenderLayers,
+) -> Vec<&'a Text3dBuffer> {
+    buffers
+        .iter()
+        .filter(|(_, layers)| layers.intersects(&camera_layers))
+        .map(|(buffer, _)| *buffer)
+        .collect()
 }
 
 fn sys_render_text(
-    mut render_pass: ResMut<RenderPass>,
+    render_pass: Option<ResMut<RenderPass>>,
     renderer: Res<Text3dRenderer>,
     text_atlas: Res<TextAtlas>,
     v_text_buffers: View<Text3dBuffer>,
+    v_visibility: View<Visibility>,
+    v_layers: View<RenderLayers>,
 
     camera: Res<MainCamera>,
+    v_cameras: View<SceneCamera>,
+    stats: Res<RenderStats>,
+    environment: Res<Environment>,
 ) {
-    renderer.render(
-        render_pass.pass(),
-        &text_atlas,
-        camera.bind_group(),
-        v_text_buffers.iter(),
-    )
+    let mut render_pass = match render_pass {
+        Some(render_pass) => render_pass,
+        None => return,
+    };
+
+    let buffers = visible_buffers(&v_text_buffers, &v_visibility, &v_layers);
+    let size = render_pass.size();
+
+    camera::main_pass_cameras(&camera, &v_cameras, &v_layers)
+        .into_iter()
+        .for_each(|view| {
+            view.viewport.apply(render_pass.pass(), size);
+
+            let buffers = buffers_on_layers(&buffers, view.layers);
+
+            let counts = renderer.render(
+                render_pass.pass(),
+                &text_atlas,
+                view.bind_group,
+                environment.bind_group(),
+                buffers.iter().copied(),
+            );
+            stats.record(counts);
+        });
+}
+
+fn sys_render_text_targets(
+    mut tools: ResMut<RenderEncoder>,
+    renderer: Res<Text3dRenderer>,
+    text_atlas: Res<TextAtlas>,
+    v_text_buffers: View<Text3dBuffer>,
+    v_visibility: View<Visibility>,
+    v_layers: View<RenderLayers>,
+    v_targets: View<RenderTarget>,
+    stats: Res<RenderStats>,
+    environment: Res<Environment>,
+) {
+    let buffers = visible_buffers(&v_text_buffers, &v_visibility, &v_layers);
+
+    v_targets
+        .iter()
+        .with_id()
+        .filter(|(_, target)| target.active)
+        .for_each(|(id, target)| {
+            let mut pass = target.begin_render_pass(tools.encoder(), false);
+
+            let buffers = buffers_on_layers(&buffers, RenderLayers::of(&v_layers, id));
+
+            let counts = renderer.render(
+                &mut pass,
+                &text_atlas,
+                target.camera().bind_group(),
+                environment.bind_group(),
+                buffers.iter().copied(),
+            );
+            stats.record(counts);
+        });
 }
 
 fn sys_trim_atlas(mut atlas: ResMut<TextAtlas>) {
@@ -111,6 +275,14 @@ fn sys_trim_atlas(mut atlas: ResMut<TextAtlas>) {
 
 //====================================================================
 
+// Local depth between outline and shadow copies, so the text in front isn't
+// rejected by the depth test
+const LAYER_DEPTH: f32 = 0.1;
+
+const SDF_EDGE: f32 = 0.5;
+
+//====================================================================
+
 #[repr(C)]
 #[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Debug)]
 pub struct Text3dVertex {
@@ -119,16 +291,21 @@ pub struct Text3dVertex {
     uv_start: [f32; 2],
     uv_end: [f32; 2],
     color: u32,
+    depth: f32,
+    // Distance field value of the glyph edge, unused by bitmap glyphs
+    edge: f32,
 }
 
 impl Vertex for Text3dVertex {
     fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
-        const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+        const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 7] = wgpu::vertex_attr_array![
             0 => Float32x2,
             1 => Float32x2,
             2 => Float32x2,
             3 => Float32x2,
             4 => Uint32,
+            5 => Float32,
+            6 => Float32,
         ];
 
         wgpu::VertexBufferLayout {
@@ -153,36 +330,47 @@ struct LocalGlyphData {
 #[derive(Unique)]
 pub struct Text3dRenderer {
     pipeline: wgpu::RenderPipeline,
-    buffer_bind_group_layout: wgpu::BindGroupLayout,
+    transform_bind_group_layout: wgpu::BindGroupLayout,
+    // Transforms of every text buffer, rewritten each frame
+    transforms: DynamicUniformBuffer<[f32; 16]>,
 }
 
 impl Text3dRenderer {
     pub fn new(
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
+        layouts: &BindGroupLayoutRegistry,
         atlas: &TextAtlas,
-        camera_bind_group_layout: &wgpu::BindGroupLayout,
     ) -> Self {
-        let buffer_bind_group_layout =
+        let transform_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Text 3d Renderer Buffer Bind Group Layout"),
-                entries: &[render_tools::bgl_uniform_entry(
+                label: Some("Text 3d Renderer Transform Bind Group Layout"),
+                entries: &[DynamicUniformBuffer::<[f32; 16]>::bgl_entry(
                     0,
                     wgpu::ShaderStages::VERTEX,
                 )],
             });
 
+        let transforms =
+            DynamicUniformBuffer::new(device, &transform_bind_group_layout, "Text 3d Transform");
+
         let pipeline = render_tools::create_pipeline(
             device,
             config,
             "Text3dRenderer",
             &[
-                camera_bind_group_layout,
+                layouts.camera(),
                 atlas.bind_group_layout(),
-                &buffer_bind_group_layout,
+                &transform_bind_group_layout,
+                layouts.environment(),
             ],
             &[Text3dVertex::desc()],
-            include_str!("../../shaders/text3d.wgsl"),
+            &format!(
+                "{}\n{}\n{}",
+                include_str!("../../shaders/text3d.wgsl"),
+                camera::TONEMAP_SHADER,
+                environment::FOG_SHADER
+            ),
             render_tools::RenderPipelineDescriptor {
                 primitive: wgpu::PrimitiveState {
                     topology: wgpu::PrimitiveTopology::TriangleStrip,
@@ -194,6 +382,10 @@ impl Text3dRenderer {
                     blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                     write_mask: wgpu::ColorWrites::all(),
                 })]),
+                fragment_entry: match atlas.mode() {
+                    GlyphMode::Bitmap => "fs_main",
+                    GlyphMode::Sdf => "fs_sdf",
+                },
                 ..Default::default()
             }
             .with_depth_stencil(),
@@ -201,10 +393,27 @@ impl Text3dRenderer {
 
         Self {
             pipeline,
-            buffer_bind_group_layout,
+            transform_bind_group_layout,
+            transforms,
         }
     }
 
+    pub fn prep_transforms<'a>(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        buffers: impl IntoIterator<Item = &'a mut Text3dBuffer>,
+    ) {
+        self.transforms.clear();
+
+        buffers.into_iter().for_each(|buffer| {
+            buffer.transform_offset = self.transforms.push(&buffer.transform);
+        });
+
+        self.transforms
+            .upload(device, queue, &self.transform_bind_group_layout);
+    }
+
     pub fn prep<'a>(
         &mut self,
         device: &wgpu::Device,
@@ -212,10 +421,53 @@ impl Text3dRenderer {
         font_system: &mut FontSystem,
         swash_cache: &mut SwashCache,
         atlas: &mut TextAtlas,
+        palette: Option<&HighContrastPalette>,
         buffers: impl IntoIterator<Item = &'a mut Text3dBuffer>,
     ) {
         buffers.into_iter().for_each(|text3d_buffer| {
             let mut rebuild_all_lines = false;
+
+            // High contrast swaps shadows for a solid outline behind the text
+            let (text_color, outline, shadow) = match palette {
+                Some(palette) => (
+                    Some(palette.text_color()),
+                    Some(TextOutline::new(palette.background_text_color(), 1.)),
+                    None,
+                ),
+                None => (None, text3d_buffer.outline, text3d_buffer.shadow),
+            };
+
+            let layers = match atlas.mode() {
+                GlyphMode::Bitmap => effect_layers(outline, shadow)
+                    .into_iter()
+                    .map(|(offset, color)| (offset, color, SDF_EDGE))
+                    .collect::<Vec<_>>(),
+
+                // Distance fields draw outlines by moving the edge out instead
+                GlyphMode::Sdf => effect_layers(None, shadow)
+                    .into_iter()
+                    .map(|(offset, color)| (offset, color, SDF_EDGE))
+                    .chain(outline.map(|outline| {
+                        (
+                            glam::Vec2::ZERO,
+                            outline.color,
+                            atlas::sdf_outline_edge(outline.width),
+                        )
+                    }))
+                    .collect::<Vec<_>>(),
+            };
+
+            // Changing the outline or shadow needs every line rebuilt
+            let layers_hash = {
+                let mut hasher = FxHasher::default();
+                layers.iter().for_each(|(offset, color, edge)| {
+                    offset.x.to_bits().hash(&mut hasher);
+                    offset.y.to_bits().hash(&mut hasher);
+                    color.hash(&mut hasher);
+                    edge.to_bits().hash(&mut hasher);
+                });
+                hasher.finish()
+            };
             // let mut rebuild_start_index = 0;
 
             let local_glyph_data = text3d_buffer
@@ -225,6 +477,7 @@ impl Text3dRenderer {
                 .flat_map(|(index, layout_run)| {
                     // Hasher for determining if a line has changed
                     let mut hasher = FxHasher::default();
+                    layers_hash.hash(&mut hasher);
 
                     let mut line_length = 0;
 
@@ -240,7 +493,6 @@ impl Text3dRenderer {
                             // Try to prep glyph in atlas
                             if let Err(_) = atlas.use_glyph(
                                 device,
-                                queue,
                                 font_system,
                                 swash_cache,
                                 &physical.cache_key,
@@ -251,9 +503,9 @@ impl Text3dRenderer {
                             }
 
                             // Check if glyph has specific color to use
-                            let color = match glyph.color_opt {
-                                Some(color) => color,
-                                None => text3d_buffer.color,
+                            let color = match (text_color, glyph.color_opt) {
+                                (Some(color), _) | (None, Some(color)) => color,
+                                (None, None) => text3d_buffer.color,
                             };
 
                             // Hash results to check changes
@@ -325,32 +577,41 @@ impl Text3dRenderer {
 
             // TODO - OPTIMIZE - Only rebuild lines that need rebuilding
             if rebuild_all_lines {
-                let glyph_vertices = local_glyph_data
+                let atlas = &*atlas;
+                let layer_count = layers.len();
+
+                // Outline and shadow copies first (and further back), then the text itself
+                let glyph_vertices = layers
                     .into_iter()
-                    .map(|local_data| {
-                        let data = atlas.get_glyph_data(&local_data.key).unwrap();
-
-                        let x = local_data.x + data.left + data.width / 2.;
-                        let y = local_data.y + data.top; // TODO - Run Line
-
-                        Text3dVertex {
-                            glyph_pos: [x, y],
-                            glyph_size: [data.width, data.height],
-                            uv_start: data.uv_start,
-                            uv_end: data.uv_end,
-                            color: local_data.color,
-                        }
+                    .map(|(offset, color, edge)| (offset, Some(color.0), edge))
+                    .chain(std::iter::once((glam::Vec2::ZERO, None, SDF_EDGE)))
+                    .enumerate()
+                    .flat_map(|(index, (offset, color, edge))| {
+                        let depth = -((layer_count - index) as f32) * LAYER_DEPTH;
+
+                        local_glyph_data.iter().map(move |local_data| {
+                            let data = atlas.get_glyph_data(&local_data.key).unwrap();
+
+                            // Text is laid out with y down but drawn with y up
+                            let x = local_data.x + data.left + data.width / 2. + offset.x;
+                            let y = local_data.y + data.top - offset.y; // TODO - Run Line
+
+                            Text3dVertex {
+                                glyph_pos: [x, y],
+                                glyph_size: [data.width, data.height],
+                                uv_start: data.uv_start,
+                                uv_end: data.uv_end,
+                                color: color.unwrap_or(local_data.color),
+                                depth,
+                                edge,
+                            }
+                        })
                     })
                     .collect::<Vec<_>>();
 
-                render_tools::update_instance_buffer(
-                    device,
-                    queue,
-                    "Text3d Vertex Buffer",
-                    &mut text3d_buffer.vertex_buffer,
-                    &mut text3d_buffer.vertex_count,
-                    &glyph_vertices,
-                );
+                text3d_buffer
+                    .vertex_buffer
+                    .update(device, queue, &glyph_vertices);
             }
         });
     }
@@ -360,19 +621,26 @@ impl Text3dRenderer {
         pass: &mut wgpu::RenderPass,
         atlas: &TextAtlas,
         camera_bind_group: &wgpu::BindGroup,
+        environment_bind_group: &wgpu::BindGroup,
         buffers: B,
-    ) where
+    ) -> DrawCounts
+    where
         B: IntoIterator<Item = &'a Text3dBuffer>,
     {
         pass.set_pipeline(&self.pipeline);
         pass.set_bind_group(0, camera_bind_group, &[]);
         pass.set_bind_group(1, atlas.bind_group(), &[]);
+        pass.set_bind_group(3, environment_bind_group, &[]);
 
-        buffers.into_iter().for_each(|buffer| {
-            pass.set_vertex_buffer(0, buffer.vertex_buffer.slice(..));
-            pass.set_bind_group(2, &buffer.uniform_bind_group, &[]);
-            pass.draw(0..4, 0..buffer.vertex_count);
-        });
+        buffers
+            .into_iter()
+            .fold(DrawCounts::pipeline(), |counts, buffer| {
+                pass.set_vertex_buffer(0, buffer.vertex_buffer.buffer().slice(..));
+                pass.set_bind_group(2, self.transforms.bind_group(), &[buffer.transform_offset]);
+                pass.draw(0..4, 0..buffer.vertex_buffer.count());
+
+                counts.draw(buffer.vertex_buffer.count())
+            })
     }
 }
 
@@ -386,6 +654,8 @@ pub struct Text3dBufferDescriptor<'a> {
     pub width: Option<f32>,
     pub height: Option<f32>,
     pub color: Color,
+    pub outline: Option<TextOutline>,
+    pub shadow: Option<TextShadow>,
 
     pub pos: glam::Vec3,
     pub rotation: glam::Quat,
@@ -402,6 +672,8 @@ impl<'a> Default for Text3dBufferDescriptor<'a> {
             width: Some(800.),
             height: None,
             color: Color::rgb(0, 0, 0),
+            outline: None,
+            shadow: None,
 
             pos: glam::Vec3::ZERO,
             rotation: glam::Quat::IDENTITY,
@@ -418,54 +690,32 @@ struct Text3dBufferLine {
 
 #[derive(Component)]
 pub struct Text3dBuffer {
-    vertex_buffer: wgpu::Buffer,
-    vertex_count: u32,
+    vertex_buffer: InstanceBuffer<Text3dVertex>,
     lines: Vec<Text3dBufferLine>,
 
     // 3d Transform
-    uniform_buffer: wgpu::Buffer,
-    uniform_bind_group: wgpu::BindGroup,
+    transform: [f32; 16],
+    transform_offset: u32,
 
     pub text_buffer: Buffer,
     pub color: Color,
+    pub outline: Option<TextOutline>,
+    pub shadow: Option<TextShadow>,
 }
 
 impl Text3dBuffer {
     pub fn new(
         device: &wgpu::Device,
-        text3d_renderer: &mut Text3dRenderer,
         font_system: &mut FontSystem,
         desc: &Text3dBufferDescriptor,
     ) -> Self {
-        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Text 3d Vertex Buffer"),
-            size: 0,
-            usage: wgpu::BufferUsages::VERTEX,
-            mapped_at_creation: false,
-        });
-
-        let vertex_count = 0;
+        let vertex_buffer = InstanceBuffer::new(device, "Text 3d Vertex");
         let lines = Vec::new();
 
         let transform =
             glam::Mat4::from_scale_rotation_translation(desc.scale, desc.rotation, desc.pos)
                 .to_cols_array();
 
-        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Text 3d Uniform Buffer"),
-            contents: bytemuck::cast_slice(&[transform]),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
-        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Text 3d Uniform Bind Group"),
-            layout: &text3d_renderer.buffer_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::Buffer(uniform_buffer.as_entire_buffer_binding()),
-            }],
-        });
-
         let mut text_buffer = Buffer::new(font_system, desc.metrics);
 
         text_buffer.set_size(font_system, desc.width, desc.height);
@@ -475,23 +725,33 @@ impl Text3dBuffer {
 
         Self {
             vertex_buffer,
-            vertex_count,
             lines,
 
-            uniform_buffer,
-            uniform_bind_group,
+            transform,
+            transform_offset: 0,
 
             text_buffer,
             color: desc.color,
+            outline: desc.outline,
+            shadow: desc.shadow,
         }
     }
 
-    pub fn update_transform(&self, queue: &wgpu::Queue, transform: &Transform) {
-        queue.write_buffer(
-            &self.uniform_buffer,
-            0,
-            bytemuck::cast_slice(&[transform.to_array()]),
-        );
+    /// Size and line count of the laid out text, in local units before the
+    /// transform is applied.
+    #[inline]
+    pub fn measure(&self) -> TextMeasurement {
+        TextMeasurement::from_buffer(&self.text_buffer)
+    }
+
+    #[inline]
+    pub fn line_count(&self) -> usize {
+        self.text_buffer.layout_runs().count()
+    }
+
+    #[inline]
+    pub fn update_transform(&mut self, transform: &Transform) {
+        self.transform = transform.to_array();
     }
 }
 
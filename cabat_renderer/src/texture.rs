@@ -6,7 +6,7 @@ use cabat_shipyard::{Res, ResMut};
 use image::GenericImageView;
 use shipyard::AllStoragesView;
 
-use crate::Device;
+use crate::{render_tools, Device};
 
 //====================================================================
 
@@ -14,13 +14,18 @@ use crate::Device;
 pub struct DepthTexture {
     // Main Depth texture
     depth_texture: RawTexture,
+    // Used instead of the window size when the scene is drawn at a fixed resolution
+    fixed_size: Option<Size<u32>>,
 }
 
 impl DepthTexture {
     pub fn new(device: &wgpu::Device, size: Size<u32>) -> Self {
         let depth_texture = RawTexture::create_depth_texture(&device, size, "Main Depth Texture");
 
-        Self { depth_texture }
+        Self {
+            depth_texture,
+            fixed_size: None,
+        }
     }
 
     #[inline]
@@ -28,6 +33,23 @@ impl DepthTexture {
         &self.depth_texture
     }
 
+    #[inline]
+    pub fn fixed_size(&self) -> Option<Size<u32>> {
+        self.fixed_size
+    }
+
+    /// Keep the depth texture at a size regardless of the window, or follow the
+    /// window again if `None`.
+    pub fn set_fixed_size(
+        &mut self,
+        device: &wgpu::Device,
+        fixed_size: Option<Size<u32>>,
+        window_size: Size<u32>,
+    ) {
+        self.fixed_size = fixed_size;
+        self.resize(device, fixed_size.unwrap_or(window_size));
+    }
+
     fn resize(&mut self, device: &wgpu::Device, size: Size<u32>) {
         self.depth_texture = RawTexture::create_depth_texture(device, size, "Main Depth Texture");
     }
@@ -47,7 +69,8 @@ pub(super) fn sys_resize_depth_texture(
     mut depth_texture: ResMut<DepthTexture>,
     size: Res<WindowSize>,
 ) {
-    depth_texture.resize(device.inner(), size.size());
+    let size = depth_texture.fixed_size.unwrap_or(size.size());
+    depth_texture.resize(device.inner(), size);
 }
 
 //====================================================================
@@ -57,6 +80,13 @@ pub struct Texture {
     binding: wgpu::BindGroup,
 }
 
+impl Asset for Texture {
+    #[inline]
+    fn gpu_memory(&self) -> usize {
+        self.raw.gpu_memory()
+    }
+}
+
 impl Texture {
     #[inline]
     pub fn new(raw: RawTexture, binding: wgpu::BindGroup) -> Self {
@@ -74,8 +104,6 @@ impl Texture {
     }
 }
 
-impl Asset for Texture {}
-
 //====================================================================
 
 pub struct RawTexture {
@@ -87,6 +115,12 @@ pub struct RawTexture {
 impl RawTexture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
+    /// Estimated bytes used by the texture and its mip levels.
+    #[inline]
+    pub fn gpu_memory(&self) -> usize {
+        texture_memory(&self.texture)
+    }
+
     pub fn create_depth_texture(
         device: &wgpu::Device,
         window_size: Size<u32>,
@@ -98,16 +132,20 @@ impl RawTexture {
             depth_or_array_layers: 1,
         };
 
-        let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some(&format!("Depth Texture: {}", label)),
-            size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: Self::DEPTH_FORMAT,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
-            view_formats: &[wgpu::TextureFormat::Depth32Float],
-        });
+        let texture = render_tools::create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label: Some(&format!("Depth Texture: {}", label)),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: Self::DEPTH_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[wgpu::TextureFormat::Depth32Float],
+            },
+        );
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor {
             label: Some(&format!("Depth Texture View: {}", label)),
@@ -135,8 +173,59 @@ impl RawTexture {
 
 //--------------------------------------------------
 
+impl RawTexture {
+    pub fn create_render_target(
+        device: &wgpu::Device,
+        size: Size<u32>,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Self {
+        let texture = render_tools::create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label: Some(&format!("Render Target Texture: {}", label)),
+                size: wgpu::Extent3d {
+                    width: size.width,
+                    height: size.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                // Copy source for reflection probe faces
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            },
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(&format!("Render Target Texture View: {}", label)),
+            ..Default::default()
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(&format!("Render Target Texture Sampler: {}", label)),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+}
+
+//--------------------------------------------------
+
 impl RawTexture {
     // Create a wgpu Texture from given RGB values.
+    #[track_caller]
     pub fn from_color(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
@@ -160,6 +249,7 @@ impl RawTexture {
     /// Try to create a wgpu Texture from an array of bytes.
     /// The image crate will return an error if it cannot determine the format
     /// of the image.
+    #[track_caller]
     pub fn from_bytes(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
@@ -172,12 +262,35 @@ impl RawTexture {
     }
 
     /// Create a wgpu Texture from an existing image::DynamicImage
+    #[inline]
+    #[track_caller]
     pub fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         image: &image::DynamicImage,
         label: Option<&str>,
         sampler: Option<&wgpu::SamplerDescriptor>,
+    ) -> Self {
+        Self::from_image_with_format(
+            device,
+            queue,
+            image,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            label,
+            sampler,
+        )
+    }
+
+    /// Create a wgpu Texture from an existing image::DynamicImage. Format must be
+    /// an 8 bit rgba format, use a non srgb format for data such as normal maps.
+    #[track_caller]
+    pub fn from_image_with_format(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: &image::DynamicImage,
+        format: wgpu::TextureFormat,
+        label: Option<&str>,
+        sampler: Option<&wgpu::SamplerDescriptor>,
     ) -> Self {
         // Convert from generic dynamic image format to usable rgba8 format
         let rgba = image.to_rgba8();
@@ -190,16 +303,22 @@ impl RawTexture {
         };
 
         // Create empty wgpu texture
-        let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label,
-            size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            view_formats: &[],
-        });
+        let texture = render_tools::create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label,
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                // Copy source for packing into texture arrays and atlases
+                usage: wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_DST
+                    | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            },
+        );
 
         // Fill texture with image data
         queue.write_texture(
@@ -229,26 +348,30 @@ impl RawTexture {
         }
     }
 
+    #[track_caller]
     pub fn from_size(
         device: &wgpu::Device,
         size: Size<u32>,
         label: Option<&str>,
         sampler: Option<&wgpu::SamplerDescriptor>,
     ) -> Self {
-        let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label,
-            size: wgpu::Extent3d {
-                width: size.width,
-                height: size.height,
-                depth_or_array_layers: 1,
+        let texture = render_tools::create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label,
+                size: wgpu::Extent3d {
+                    width: size.width,
+                    height: size.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
             },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::R8Unorm,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            view_formats: &[],
-        });
+        );
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         let sampler = device.create_sampler(sampler.unwrap_or(&wgpu::SamplerDescriptor::default()));
@@ -298,3 +421,28 @@ impl RawTexture {
 }
 
 //====================================================================
+
+/// Estimated bytes used by a texture and its mip levels.
+pub fn texture_memory(texture: &wgpu::Texture) -> usize {
+    let format = texture.format();
+
+    let (block_width, block_height) = format.block_dimensions();
+    // Combined depth stencil formats don't have a single block size
+    let block_size = format.block_copy_size(None).unwrap_or(4) as usize;
+
+    let bytes = (0..texture.mip_level_count())
+        .map(|level| {
+            let size = texture.size().mip_level_size(level, texture.dimension());
+
+            let blocks = size.width.div_ceil(block_width) as usize
+                * size.height.div_ceil(block_height) as usize
+                * size.depth_or_array_layers as usize;
+
+            blocks * block_size
+        })
+        .sum::<usize>();
+
+    bytes * texture.sample_count() as usize
+}
+
+//====================================================================
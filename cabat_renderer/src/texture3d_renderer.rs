@@ -9,20 +9,31 @@ use cabat_assets::{
     asset_storage::AssetStorage,
     handle::{Handle, HandleId},
 };
-use cabat_shipyard::prelude::*;
-use cabat_spatial::Transform;
+use cabat_common::{Color, Size};
+use cabat_shipyard::{prelude::*, UniqueTools};
+use cabat_spatial::{SpatialIndex, Transform};
 use rustc_hash::FxHasher;
-use shipyard::{AllStoragesView, Component, IntoIter, Unique, View};
+use shipyard::{
+    AllStoragesView, Component, EntityId, Get, IntoIter, IntoWithId, IntoWorkload, Unique, View,
+    ViewMut, WorkloadModificator,
+};
 
 use crate::{
-    camera::MainCamera,
-    render_tools,
+    camera::{self, Frustum, MainCamera, SceneCamera},
+    environment::{self, Environment},
+    indirect::{IndirectBatch, IndirectCuller},
+    render_graph::{resources, AddRenderPass, RenderGraphNode},
+    render_target::RenderTarget,
+    render_tools::{self, FaceCulling, InstanceBuffer},
+    settings::{GpuInfo, SurfaceFormatChangedEvent},
     shared::{
-        SharedPipelineResources, TextureRectVertex, TEXTURE_RECT_INDEX_COUNT, TEXTURE_RECT_INDICES,
+        BindGroupLayoutRegistry, TextureRectVertex, TEXTURE_RECT_INDEX_COUNT, TEXTURE_RECT_INDICES,
         TEXTURE_RECT_VERTICES,
     },
     texture::{RawTexture, Texture},
-    Device, Queue, RenderPass, SurfaceConfig, Vertex,
+    texture_array::TextureArray,
+    visibility::{self, RenderLayers, Visibility},
+    Device, DrawCounts, Queue, RenderEncoder, RenderPass, RenderStats, SurfaceConfig, Vertex,
 };
 
 //====================================================================
@@ -31,10 +42,31 @@ pub struct Texture3dPlugin;
 
 impl Plugin for Texture3dPlugin {
     fn build(self, builder: &WorkloadBuilder) {
+        builder.get_or_insert(SpriteArraySettings::default);
+
         builder
             .add_workload_pre(Stages::Setup, sys_setup_texture_pipeline)
-            .add_workload_last(Stages::Update, sys_prep_texture3d)
-            .add_workload(Stages::Render, sys_render_texture3d);
+            .add_workload_last(
+                Stages::Update,
+                (sys_cull_sprites, sys_prep_texture3d)
+                    .into_sequential_workload()
+                    .after_all("update_spatial_index"),
+            )
+            // Compute passes can't be recorded while the main pass is open, so culling
+            // runs with the render target passes, before any of them draw
+            .add_render_pass(
+                RenderGraphNode::new("texture3d_gpu_cull").writes(resources::RENDER_TARGETS),
+                sys_cull_texture3d_indirect,
+            )
+            .add_render_pass(
+                RenderGraphNode::new("texture3d_targets").writes(resources::RENDER_TARGETS),
+                sys_render_texture3d_targets,
+            )
+            .add_render_pass(
+                RenderGraphNode::new("texture3d").writes(resources::MAIN_PASS),
+                sys_render_texture3d,
+            )
+            .add_event::<SurfaceFormatChangedEvent>(sys_setup_texture_pipeline.into_workload());
     }
 }
 
@@ -43,26 +75,81 @@ fn sys_setup_texture_pipeline(
     device: Res<Device>,
     queue: Res<Queue>,
     config: Res<SurfaceConfig>,
-    shared: Res<SharedPipelineResources>,
-    camera: Res<MainCamera>,
+    layouts: Res<BindGroupLayoutRegistry>,
+    array_settings: Res<SpriteArraySettings>,
+    gpu_info: Res<GpuInfo>,
 ) {
     let pipeline = Texture3dRenderer::new(
         device.inner(),
         queue.inner(),
         config.inner(),
-        &shared,
-        camera.bind_group_layout(),
+        &layouts,
+        &array_settings,
+        &gpu_info,
     );
 
     all_storages.add_unique(pipeline);
 }
 
+fn sys_cull_sprites(
+    renderer: Res<Texture3dRenderer>,
+    camera: Res<MainCamera>,
+    index: Option<Res<SpatialIndex>>,
+    v_cameras: View<SceneCamera>,
+    v_targets: View<RenderTarget>,
+    v_layers: View<RenderLayers>,
+    v_sprite: View<Sprite>,
+    v_transform: View<Transform>,
+    v_static: View<StaticGeometry>,
+    v_gpu_culled: View<GpuCulled>,
+    mut vm_visibility: ViewMut<Visibility>,
+) {
+    let frustums = visibility::active_frustums(&camera, &v_cameras, &v_targets, &v_layers);
+    let candidates = visibility::IndexedCandidates::new(index.as_deref(), &frustums);
+
+    (&v_transform, &v_sprite, &mut vm_visibility)
+        .iter()
+        .with_id()
+        .filter(|(id, _)| {
+            !v_static.contains(*id) && !(renderer.gpu_culling && v_gpu_culled.contains(*id))
+        })
+        .for_each(|(id, (transform, sprite, visibility))| {
+            if candidates.excludes(id) {
+                visibility.set_culled(true);
+                return;
+            }
+
+            // Bounding sphere of the scaled quad
+            let radius = glam::Vec2::new(sprite.width, sprite.height).length() / 2.
+                * transform.scale.abs().max_element();
+
+            let visible = visibility::sphere_visible(
+                &frustums,
+                RenderLayers::of(&v_layers, id),
+                transform.translation,
+                radius,
+            );
+
+            visibility.set_culled(!visible);
+        });
+}
+
 fn sys_prep_texture3d(
     device: Res<Device>,
     queue: Res<Queue>,
+    config: Res<SurfaceConfig>,
+    layouts: Res<BindGroupLayoutRegistry>,
+    storage: Res<AssetStorage>,
     mut renderer: ResMut<Texture3dRenderer>,
     v_sprite: View<Sprite>,
     v_transform: View<Transform>,
+    v_visibility: View<Visibility>,
+    v_layers: View<RenderLayers>,
+    v_culling: View<FaceCulling>,
+    v_cutout: View<AlphaCutout>,
+    v_static: View<StaticGeometry>,
+    v_gpu_culled: View<GpuCulled>,
+    v_array: View<ArrayBatched>,
 ) {
     #[derive(PartialEq, Eq, Hash)]
     enum InstanceType {
@@ -70,120 +157,367 @@ fn sys_prep_texture3d(
         Default,
     }
 
-    let instances =
-        (&v_transform, &v_sprite)
+    let instance = |id: EntityId, transform: &Transform, sprite: &Sprite| Texture3dInstanceRaw {
+        size: [sprite.width, sprite.height],
+        transform: transform.to_array(),
+        color: sprite.color.into(),
+        alpha_cutoff: v_cutout.get(id).map(|cutout| cutout.0).unwrap_or(0.),
+    };
+
+    // Without gpu culling, GpuCulled sprites are drawn like any other
+    let gpu_culling = renderer.gpu_culling;
+    let gpu_culled = |id: EntityId| gpu_culling && v_gpu_culled.contains(id);
+
+    let rebuild_static = renderer.static_dirty
+        || v_static.inserted().iter().next().is_some()
+        || v_static.removed_or_deleted().next().is_some()
+        || (v_transform.inserted_or_modified(), &v_static)
+            .iter()
+            .next()
+            .is_some();
+
+    if rebuild_static {
+        let batches = (&v_transform, &v_sprite, &v_static)
             .iter()
-            .fold(HashMap::new(), |mut acc, (transform, sprite)| {
-                let instance = Texture3dInstanceRaw {
-                    size: [sprite.width, sprite.height],
-                    transform: transform.to_array(),
-                    color: sprite.color,
-                };
-
-                let instance_type = match &sprite.texture {
-                    Some(texture) => InstanceType::Texture(texture.id()),
-                    None => InstanceType::Default,
-                };
-
-                acc.entry(instance_type)
-                    .or_insert(Vec::new())
-                    .push(instance);
+            .with_id()
+            // Static sprites are never culled, only hidden
+            .filter(|(id, _)| v_visibility.get(*id).map(|v| v.visible).unwrap_or(true))
+            .fold(HashMap::new(), |mut acc, (id, (transform, sprite, _))| {
+                acc.entry((
+                    sprite.texture.as_ref().map(|texture| texture.id()),
+                    RenderLayers::of(&v_layers, id),
+                    FaceCulling::of(&v_culling, id),
+                ))
+                .or_insert(Vec::new())
+                .push(instance(id, transform, sprite));
 
                 acc
             });
 
+        log::debug!("Rebuilding {} static sprite batches", batches.len());
+
+        renderer.static_dirty = false;
+        renderer.static_instances.clear();
+
+        batches.into_iter().for_each(|(key, raw)| {
+            renderer.prep_pipeline(device.inner(), config.inner(), &layouts, key.2);
+            renderer.static_instances.insert(
+                key,
+                InstanceBuffer::with_data(device.inner(), "Static Texture 3d", &raw),
+            );
+        });
+    }
+
+    // Uploaded every frame and culled on the gpu, so only hidden sprites are skipped here
+    let indirect = (&v_transform, &v_sprite, &v_gpu_culled)
+        .iter()
+        .with_id()
+        .filter(|(id, _)| {
+            gpu_culling
+                && !v_static.contains(*id)
+                && v_visibility
+                    .get(*id)
+                    .map(|v| v.visible && !v.occluded())
+                    .unwrap_or(true)
+        })
+        .fold(HashMap::new(), |mut acc, (id, (transform, sprite, _))| {
+            acc.entry((
+                sprite.texture.as_ref().map(|texture| texture.id()),
+                RenderLayers::of(&v_layers, id),
+                FaceCulling::of(&v_culling, id),
+            ))
+            .or_insert(Vec::new())
+            .push(instance(id, transform, sprite));
+
+            acc
+        });
+
+    renderer
+        .indirect_instances
+        .retain(|key, _| indirect.contains_key(key));
+
+    if !indirect.is_empty() && renderer.culler.is_none() {
+        renderer.culler = Some(IndirectCuller::new(
+            device.inner(),
+            "Texture 3d Cull",
+            include_str!("../shaders/texture3d_cull.wgsl"),
+        ));
+    }
+
+    indirect.into_iter().for_each(|(key, raw)| {
+        renderer.prep_pipeline(device.inner(), config.inner(), &layouts, key.2);
+        renderer
+            .indirect_instances
+            .entry(key)
+            .or_insert_with(|| IndirectBatch::new(device.inner(), "Texture 3d"))
+            .update(device.inner(), queue.inner(), raw.as_slice());
+    });
+
+    let array_instances = renderer.prep_array(
+        device.inner(),
+        queue.inner(),
+        &storage,
+        (&v_transform, &v_sprite, &v_array)
+            .iter()
+            .with_id()
+            .filter(|(id, _)| {
+                !v_static.contains(*id)
+                    && !gpu_culled(*id)
+                    && visibility::is_visible(&v_visibility, *id)
+            })
+            .map(|(id, (transform, sprite, _))| (id, transform, sprite)),
+    );
+
+    // Sprites that made it into the array are drawn from there instead
+    let in_array = |id: EntityId, sprite: &Sprite| {
+        v_array.contains(id)
+            && sprite
+                .texture
+                .as_ref()
+                .is_some_and(|texture| renderer.array.get(texture.id()).is_some())
+    };
+
+    let array_instances = array_instances.into_iter().fold(
+        HashMap::new(),
+        |mut acc, (id, layer, transform, sprite)| {
+            let base = instance(id, transform, sprite);
+
+            acc.entry((
+                RenderLayers::of(&v_layers, id),
+                FaceCulling::of(&v_culling, id),
+            ))
+            .or_insert(Vec::new())
+            .push(Texture3dArrayInstanceRaw {
+                size: base.size,
+                transform: base.transform,
+                color: base.color,
+                alpha_cutoff: base.alpha_cutoff,
+                layer,
+            });
+
+            acc
+        },
+    );
+
+    let instances = (&v_transform, &v_sprite)
+        .iter()
+        .with_id()
+        .filter(|(id, (_, sprite))| {
+            !v_static.contains(*id)
+                && !gpu_culled(*id)
+                && !in_array(*id, sprite)
+                && visibility::is_visible(&v_visibility, *id)
+        })
+        .fold(HashMap::new(), |mut acc, (id, (transform, sprite))| {
+            let instance = instance(id, transform, sprite);
+
+            let instance_type = match &sprite.texture {
+                Some(texture) => InstanceType::Texture(texture.id()),
+                None => InstanceType::Default,
+            };
+
+            // Split by layer so each camera can skip the batches it doesn't draw,
+            // and by culling as each needs its own pipeline
+            acc.entry((
+                instance_type,
+                RenderLayers::of(&v_layers, id),
+                FaceCulling::of(&v_culling, id),
+            ))
+            .or_insert(Vec::new())
+            .push(instance);
+
+            acc
+        });
+
     let mut previous = renderer
         .instances
         .keys()
         .map(|id| *id)
         .collect::<HashSet<_>>();
 
-    let mut default_used = false;
+    let mut previous_default = renderer
+        .default_instances
+        .keys()
+        .map(|key| *key)
+        .collect::<HashSet<_>>();
+
+    instances
+        .into_iter()
+        .for_each(|((id, layers, culling), raw)| {
+            renderer.prep_pipeline(device.inner(), config.inner(), &layouts, culling);
 
-    instances.into_iter().for_each(|(id, raw)| {
-        match id {
-            InstanceType::Texture(handle_id) => {
-                previous.remove(&handle_id);
+            match id {
+                InstanceType::Texture(handle_id) => {
+                    previous.remove(&(handle_id, layers, culling));
 
-                renderer
-                    .instances
-                    .entry(handle_id)
-                    .and_modify(|instance| {
-                        instance.update(device.inner(), queue.inner(), raw.as_slice());
-                    })
-                    .or_insert(Texture3dInstance {
-                        instance_buffer: render_tools::create_instance_buffer(
-                            device.inner(),
-                            "Texture 3d",
-                            raw.as_slice(),
-                        ),
-                        instance_count: raw.len() as u32,
-                    });
-            }
+                    renderer
+                        .instances
+                        .entry((handle_id, layers, culling))
+                        .or_insert_with(|| InstanceBuffer::new(device.inner(), "Texture 3d"))
+                        .update(device.inner(), queue.inner(), raw.as_slice());
+                }
 
-            InstanceType::Default => {
-                default_used = true;
+                InstanceType::Default => {
+                    previous_default.remove(&(layers, culling));
 
-                renderer
-                    .default_instances
-                    .update(device.inner(), queue.inner(), raw.as_slice());
-            }
-        };
-    });
+                    renderer
+                        .default_instances
+                        .entry((layers, culling))
+                        .or_insert_with(|| {
+                            InstanceBuffer::new(device.inner(), "Default Texture 3d")
+                        })
+                        .update(device.inner(), queue.inner(), raw.as_slice());
+                }
+            };
+        });
 
     previous.into_iter().for_each(|to_remove| {
         renderer.instances.remove(&to_remove);
     });
 
-    // Reset default instances if not in use
-    if !default_used && renderer.default_instances.instance_count != 0 {
-        renderer.default_instances.instance_buffer =
-            device.inner().create_buffer(&wgpu::BufferDescriptor {
-                label: Some("Default Texture 3d Instance Buffer"),
-                size: 0,
-                usage: wgpu::BufferUsages::VERTEX,
-                mapped_at_creation: false,
-            });
+    previous_default.into_iter().for_each(|to_remove| {
+        renderer.default_instances.remove(&to_remove);
+    });
 
-        renderer.default_instances.instance_count = 0;
-    }
+    renderer
+        .array_instances
+        .retain(|key, _| array_instances.contains_key(key));
+
+    array_instances.into_iter().for_each(|(key, raw)| {
+        renderer.prep_array_pipeline(device.inner(), config.inner(), &layouts, key.1);
+        renderer
+            .array_instances
+            .entry(key)
+            .or_insert_with(|| InstanceBuffer::new(device.inner(), "Texture 3d Array"))
+            .update(device.inner(), queue.inner(), raw.as_slice());
+    });
 }
 
 fn sys_render_texture3d(
     mut pass: ResMut<RenderPass>,
     renderer: Res<Texture3dRenderer>,
     camera: Res<MainCamera>,
+    v_cameras: View<SceneCamera>,
+    v_layers: View<RenderLayers>,
+    stats: Res<RenderStats>,
+    environment: Res<Environment>,
 
     storage: Res<AssetStorage>,
 ) {
-    let use_default = match renderer.default_instances.instance_count != 0 {
-        true => Some((
-            None,
-            &renderer.default_instances.instance_buffer,
-            renderer.default_instances.instance_count,
-        )),
-        false => None,
-    };
+    let instances = renderer.instances_to_render();
+    let size = pass.size();
 
-    let instances = renderer
-        .instances
+    camera::main_pass_cameras(&camera, &v_cameras, &v_layers)
+        .into_iter()
+        .enumerate()
+        .for_each(|(index, view)| {
+            view.viewport.apply(pass.pass(), size);
+
+            let counts = renderer.render_storage(
+                pass.pass(),
+                view.bind_group,
+                environment.bind_group(),
+                view.layers,
+                instances.as_slice(),
+                &storage,
+                None,
+            );
+
+            let indirect_counts = renderer.render_indirect(
+                pass.pass(),
+                view.bind_group,
+                environment.bind_group(),
+                view.layers,
+                index as u32,
+                &storage,
+                None,
+            );
+
+            let array_counts = renderer.render_array(
+                pass.pass(),
+                view.bind_group,
+                environment.bind_group(),
+                view.layers,
+            );
+            stats.record(counts + indirect_counts + array_counts);
+        });
+}
+
+fn sys_render_texture3d_targets(
+    mut tools: ResMut<RenderEncoder>,
+    renderer: Res<Texture3dRenderer>,
+    storage: Res<AssetStorage>,
+    stats: Res<RenderStats>,
+    environment: Res<Environment>,
+    v_targets: View<RenderTarget>,
+    v_layers: View<RenderLayers>,
+) {
+    let instances = renderer.instances_to_render();
+
+    v_targets
         .iter()
-        .map(|(id, instance)| {
-            (
-                Some(*id),
-                &instance.instance_buffer,
-                instance.instance_count,
-            )
-        })
-        .chain(use_default)
+        .with_id()
+        .filter(|(_, target)| target.active)
+        .enumerate()
+        .for_each(|(index, (id, target))| {
+            let mut pass = target.begin_render_pass(tools.encoder(), false);
+            let layers = RenderLayers::of(&v_layers, id);
+
+            let counts = renderer.render_storage(
+                &mut pass,
+                target.camera().bind_group(),
+                environment.bind_group(),
+                layers,
+                instances.as_slice(),
+                &storage,
+                Some(target),
+            );
+
+            // Render targets are culled after the main pass cameras
+            let indirect_counts = renderer.render_indirect(
+                &mut pass,
+                target.camera().bind_group(),
+                environment.bind_group(),
+                layers,
+                renderer.indirect_target_offset + index as u32,
+                &storage,
+                Some(target),
+            );
+
+            let array_counts = renderer.render_array(
+                &mut pass,
+                target.camera().bind_group(),
+                environment.bind_group(),
+                layers,
+            );
+            stats.record(counts + indirect_counts + array_counts);
+        });
+}
+
+fn sys_cull_texture3d_indirect(
+    device: Res<Device>,
+    queue: Res<Queue>,
+    mut tools: ResMut<RenderEncoder>,
+    mut renderer: ResMut<Texture3dRenderer>,
+    camera: Res<MainCamera>,
+    v_cameras: View<SceneCamera>,
+    v_targets: View<RenderTarget>,
+    v_layers: View<RenderLayers>,
+) {
+    if renderer.indirect_instances.is_empty() {
+        return;
+    }
+
+    // Same order as the render systems draw them in
+    let frustums = visibility::active_frustums(&camera, &v_cameras, &v_targets, &v_layers)
+        .into_iter()
+        .map(|(frustum, _)| frustum)
         .collect::<Vec<_>>();
 
-    renderer.render_storage(
-        pass.pass(),
-        camera.bind_group(),
-        instances.as_slice(),
-        &storage,
-    );
+    renderer.indirect_target_offset =
+        camera::main_pass_cameras(&camera, &v_cameras, &v_layers).len() as u32;
+
+    renderer.cull_indirect(device.inner(), queue.inner(), tools.encoder(), &frustums);
 }
 
 //====================================================================
@@ -193,9 +527,71 @@ pub struct Sprite {
     pub texture: Option<Handle<Texture>>,
     pub width: f32,
     pub height: f32,
-    pub color: [f32; 4],
+    pub color: Color,
 }
 
+/// Discard sprite texels with an alpha below the threshold, so the transparent
+/// parts of a texture don't write to the depth buffer and hide sprites behind.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct AlphaCutout(pub f32);
+
+impl Default for AlphaCutout {
+    #[inline]
+    fn default() -> Self {
+        Self(0.5)
+    }
+}
+
+//--------------------------------------------------
+
+/// Marks a sprite that never moves. Static sprites are baked into persistent
+/// batches and skipped by the per frame instance preparation and culling.
+///
+/// Batches are rebuilt when a static sprite is added, removed or has its
+/// transform changed. Changes to its [Sprite] or [Visibility] need a call to
+/// [Texture3dRenderer::invalidate_static].
+#[derive(Component, Debug, Clone, Copy, Default)]
+#[track(All)]
+pub struct StaticGeometry;
+
+/// Draws the sprite from the renderer's shared texture array, so sprites with
+/// different textures can share a draw call. The texture is copied into a layer
+/// of the array, which needs it to be an srgb rgba texture of the size set in
+/// the [SpriteArraySettings]. Other textures fall back to being batched by
+/// texture.
+///
+/// The array has a single sampler, so a texture's own sampler is ignored.
+/// Ignored on [StaticGeometry] and [GpuCulled] sprites.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct ArrayBatched;
+
+/// Insert before adding the [Texture3dPlugin] to configure the texture array
+/// used by [ArrayBatched] sprites.
+#[derive(Unique, Debug, Clone)]
+pub struct SpriteArraySettings {
+    /// Size every texture in the array must be.
+    pub layer_size: Size<u32>,
+    pub max_layers: u32,
+}
+
+impl Default for SpriteArraySettings {
+    fn default() -> Self {
+        Self {
+            layer_size: Size::new(64, 64),
+            max_layers: 256,
+        }
+    }
+}
+
+/// Marks a sprite that is culled on the gpu and drawn indirectly, for very large
+/// instance counts such as grass or crowds. Instances are uploaded each frame
+/// and a compute pass compacts those inside each camera's frustum.
+///
+/// Needs compute shader and indirect draw support, otherwise these are drawn
+/// and culled like other sprites. Ignored on [StaticGeometry] sprites.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct GpuCulled;
+
 //====================================================================
 
 #[repr(C)]
@@ -204,17 +600,19 @@ pub struct Texture3dInstanceRaw {
     pub size: [f32; 2],
     pub transform: [f32; 16],
     pub color: [f32; 4],
+    pub alpha_cutoff: f32,
 }
 
 impl Vertex for Texture3dInstanceRaw {
     fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
-        const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 6] = wgpu::vertex_attr_array![
+        const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 7] = wgpu::vertex_attr_array![
             2 => Float32x2,
             3 => Float32x4,
             4 => Float32x4,
             5 => Float32x4,
             6 => Float32x4,
             7 => Float32x4,
+            8 => Float32,
         ];
 
         wgpu::VertexBufferLayout {
@@ -225,18 +623,60 @@ impl Vertex for Texture3dInstanceRaw {
     }
 }
 
+/// [Texture3dInstanceRaw] along with the texture array layer to sample.
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Debug)]
+pub struct Texture3dArrayInstanceRaw {
+    pub size: [f32; 2],
+    pub transform: [f32; 16],
+    pub color: [f32; 4],
+    pub alpha_cutoff: f32,
+    pub layer: u32,
+}
+
+impl Vertex for Texture3dArrayInstanceRaw {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 8] = wgpu::vertex_attr_array![
+            2 => Float32x2,
+            3 => Float32x4,
+            4 => Float32x4,
+            5 => Float32x4,
+            6 => Float32x4,
+            7 => Float32x4,
+            8 => Float32,
+            9 => Uint32,
+        ];
+
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Texture3dArrayInstanceRaw>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &VERTEX_ATTRIBUTES,
+        }
+    }
+}
+
 impl Default for Texture3dInstanceRaw {
     fn default() -> Self {
         Self {
             size: [1.; 2],
             transform: glam::Mat4::IDENTITY.to_cols_array(),
             color: [1.; 4],
+            alpha_cutoff: 0.,
         }
     }
 }
 
 //====================================================================
 
+/// Instances sharing a texture, layers and culling, drawn with one call.
+pub struct Texture3dBatch<'a> {
+    pub texture: Option<HandleId>,
+    pub layers: RenderLayers,
+    pub culling: FaceCulling,
+    pub instance_buffer: &'a wgpu::Buffer,
+    pub instance_count: u32,
+}
+
 pub struct Texture3dInstanceToRender<'a> {
     pub texture_bind_group: &'a wgpu::BindGroup,
     pub instance_buffer: &'a wgpu::Buffer,
@@ -247,15 +687,52 @@ pub struct Texture3dInstanceToRender<'a> {
 
 #[derive(Unique)]
 pub struct Texture3dRenderer {
-    pipeline: wgpu::RenderPipeline,
+    // Created as sprites request a culling mode
+    pipelines: HashMap<FaceCulling, wgpu::RenderPipeline, BuildHasherDefault<FxHasher>>,
 
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
     index_count: u32,
 
-    instances: HashMap<HandleId, Texture3dInstance, BuildHasherDefault<FxHasher>>,
+    instances: HashMap<
+        (HandleId, RenderLayers, FaceCulling),
+        InstanceBuffer<Texture3dInstanceRaw>,
+        BuildHasherDefault<FxHasher>,
+    >,
     default_texture_bind_group: wgpu::BindGroup,
-    default_instances: Texture3dInstance,
+    default_instances: HashMap<
+        (RenderLayers, FaceCulling),
+        InstanceBuffer<Texture3dInstanceRaw>,
+        BuildHasherDefault<FxHasher>,
+    >,
+
+    // Sprites marked with StaticGeometry, only rebuilt when they change
+    static_instances: HashMap<
+        (Option<HandleId>, RenderLayers, FaceCulling),
+        InstanceBuffer<Texture3dInstanceRaw>,
+        BuildHasherDefault<FxHasher>,
+    >,
+    static_dirty: bool,
+
+    // Sprites marked with GpuCulled, drawn with the draw arguments written by the culler.
+    // The culler is only created once there are sprites to cull.
+    gpu_culling: bool,
+    culler: Option<IndirectCuller>,
+    indirect_instances: HashMap<
+        (Option<HandleId>, RenderLayers, FaceCulling),
+        IndirectBatch<Texture3dInstanceRaw>,
+        BuildHasherDefault<FxHasher>,
+    >,
+    indirect_target_offset: u32,
+
+    // Sprites marked with ArrayBatched, batched regardless of texture
+    array: TextureArray,
+    array_pipelines: HashMap<FaceCulling, wgpu::RenderPipeline, BuildHasherDefault<FxHasher>>,
+    array_instances: HashMap<
+        (RenderLayers, FaceCulling),
+        InstanceBuffer<Texture3dArrayInstanceRaw>,
+        BuildHasherDefault<FxHasher>,
+    >,
 }
 
 impl Texture3dRenderer {
@@ -263,19 +740,19 @@ impl Texture3dRenderer {
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         config: &wgpu::SurfaceConfiguration,
-        shared: &SharedPipelineResources,
-        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        layouts: &BindGroupLayoutRegistry,
+        array_settings: &SpriteArraySettings,
+        gpu_info: &GpuInfo,
     ) -> Self {
-        let pipeline = render_tools::create_pipeline(
-            device,
-            config,
-            "Texture 3d Pipeline",
-            &[camera_bind_group_layout, shared.texture_bind_group_layout()],
-            &[TextureRectVertex::desc(), Texture3dInstanceRaw::desc()],
-            include_str!("../shaders/texture3d.wgsl"),
-            render_tools::RenderPipelineDescriptor::default()
-                .with_depth_stencil()
-                .with_backface_culling(),
+        let gpu_culling = IndirectCuller::is_supported(gpu_info);
+        if !gpu_culling {
+            log::info!("Indirect culling not supported - GpuCulled sprites are drawn directly");
+        }
+
+        let mut pipelines = HashMap::default();
+        pipelines.insert(
+            FaceCulling::default(),
+            Self::create_pipeline(device, config, layouts, FaceCulling::default()),
         );
 
         let vertex_buffer =
@@ -289,22 +766,14 @@ impl Texture3dRenderer {
 
         let default_texture = RawTexture::from_color(device, queue, [255, 255, 255], None, None);
         let default_texture_bind_group =
-            shared.create_bind_group(device, &default_texture, Some("Default Texture"));
-
-        let default_instances = Texture3dInstance {
-            instance_buffer: device.create_buffer(&wgpu::BufferDescriptor {
-                label: Some("Default Texture 3d Instance Buffer"),
-                size: 0,
-                usage: wgpu::BufferUsages::VERTEX,
-                mapped_at_creation: false,
-            }),
-            instance_count: 0,
-        };
+            layouts.create_bind_group(device, &default_texture, Some("Default Texture"));
+
+        let default_instances = HashMap::default();
 
         //--------------------------------------------------
 
         Self {
-            pipeline,
+            pipelines,
 
             vertex_buffer,
             index_buffer,
@@ -313,18 +782,234 @@ impl Texture3dRenderer {
             instances,
             default_texture_bind_group,
             default_instances,
+
+            static_instances: HashMap::default(),
+            static_dirty: true,
+
+            gpu_culling,
+            culler: None,
+            indirect_instances: HashMap::default(),
+            indirect_target_offset: 0,
+
+            array: TextureArray::new(
+                device,
+                "Sprite",
+                array_settings.layer_size,
+                array_settings.max_layers,
+            ),
+            array_pipelines: HashMap::default(),
+            array_instances: HashMap::default(),
         }
     }
 
+    /// Rebuild the static sprite batches next update.
+    #[inline]
+    pub fn invalidate_static(&mut self) {
+        self.static_dirty = true;
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        layouts: &BindGroupLayoutRegistry,
+        culling: FaceCulling,
+    ) -> wgpu::RenderPipeline {
+        render_tools::create_pipeline(
+            device,
+            config,
+            "Texture 3d Pipeline",
+            &[layouts.camera(), layouts.texture(), layouts.environment()],
+            &[TextureRectVertex::desc(), Texture3dInstanceRaw::desc()],
+            &format!(
+                "{}\n{}\n{}",
+                include_str!("../shaders/texture3d.wgsl"),
+                camera::TONEMAP_SHADER,
+                environment::FOG_SHADER
+            ),
+            render_tools::RenderPipelineDescriptor::default()
+                .with_depth_stencil()
+                .with_face_culling(culling),
+        )
+    }
+
+    /// Make sure a pipeline exists for the given culling.
+    pub fn prep_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        layouts: &BindGroupLayoutRegistry,
+        culling: FaceCulling,
+    ) {
+        self.pipelines.entry(culling).or_insert_with(|| {
+            log::trace!("Creating texture 3d pipeline for {:?}", culling);
+            Self::create_pipeline(device, config, layouts, culling)
+        });
+    }
+
+    fn create_array_pipeline(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        layouts: &BindGroupLayoutRegistry,
+        array: &TextureArray,
+        culling: FaceCulling,
+    ) -> wgpu::RenderPipeline {
+        render_tools::create_pipeline(
+            device,
+            config,
+            "Texture 3d Array Pipeline",
+            &[
+                layouts.camera(),
+                array.bind_group_layout(),
+                layouts.environment(),
+            ],
+            &[TextureRectVertex::desc(), Texture3dArrayInstanceRaw::desc()],
+            &format!(
+                "{}\n{}\n{}",
+                include_str!("../shaders/texture3d_array.wgsl"),
+                camera::TONEMAP_SHADER,
+                environment::FOG_SHADER
+            ),
+            render_tools::RenderPipelineDescriptor::default()
+                .with_depth_stencil()
+                .with_face_culling(culling),
+        )
+    }
+
+    /// Make sure an array pipeline exists for the given culling.
+    fn prep_array_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        layouts: &BindGroupLayoutRegistry,
+        culling: FaceCulling,
+    ) {
+        let array = &self.array;
+
+        self.array_pipelines.entry(culling).or_insert_with(|| {
+            log::trace!("Creating texture 3d array pipeline for {:?}", culling);
+            Self::create_array_pipeline(device, config, layouts, array, culling)
+        });
+    }
+
+    /// Copy any new textures into the array, returning the sprites that have a
+    /// layer along with it.
+    fn prep_array<'a>(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        storage: &AssetStorage,
+        sprites: impl Iterator<Item = (EntityId, &'a Transform, &'a Sprite)>,
+    ) -> Vec<(EntityId, u32, &'a Transform, &'a Sprite)> {
+        self.array.trim(storage);
+
+        let sprites = sprites
+            .filter_map(|(id, transform, sprite)| {
+                Some((id, sprite.texture.as_ref()?.id(), transform, sprite))
+            })
+            .collect::<Vec<_>>();
+
+        // Rejected textures are drawn through the regular path instead
+        let pending = sprites
+            .iter()
+            .filter(|(_, texture, _, _)| {
+                self.array.get(*texture).is_none() && !self.array.is_rejected(*texture)
+            })
+            .map(|(_, texture, _, _)| *texture)
+            .collect::<HashSet<_>>();
+
+        if !pending.is_empty() {
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Sprite Texture Array Encoder"),
+            });
+
+            // Textures still loading don't record a copy and are tried again next frame
+            let copied = pending
+                .into_iter()
+                .filter_map(|texture| self.array.layer(device, &mut encoder, storage, texture))
+                .count();
+
+            if copied > 0 {
+                queue.submit(Some(encoder.finish()));
+            }
+        }
+
+        sprites
+            .into_iter()
+            .filter_map(|(id, texture, transform, sprite)| {
+                Some((id, self.array.get(texture)?, transform, sprite))
+            })
+            .collect()
+    }
+
+    fn cull_indirect(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        frustums: &[Frustum],
+    ) {
+        let culler = match &mut self.culler {
+            Some(culler) => culler,
+            None => return,
+        };
+
+        culler.update_views(device, queue, frustums);
+
+        self.indirect_instances.values_mut().for_each(|batch| {
+            batch.prepare(device, queue, culler, self.index_count);
+        });
+
+        culler.dispatch(encoder, self.indirect_instances.values());
+    }
+
+    fn instances_to_render(&self) -> Vec<Texture3dBatch> {
+        let use_default = self
+            .default_instances
+            .iter()
+            .filter(|(_, instance)| !instance.is_empty())
+            .map(|((layers, culling), instance)| Texture3dBatch {
+                texture: None,
+                layers: *layers,
+                culling: *culling,
+                instance_buffer: instance.buffer(),
+                instance_count: instance.count(),
+            });
+
+        self.instances
+            .iter()
+            .map(|((id, layers, culling), instance)| Texture3dBatch {
+                texture: Some(*id),
+                layers: *layers,
+                culling: *culling,
+                instance_buffer: instance.buffer(),
+                instance_count: instance.count(),
+            })
+            .chain(use_default)
+            .chain(
+                self.static_instances
+                    .iter()
+                    .map(|((texture, layers, culling), instance)| Texture3dBatch {
+                        texture: *texture,
+                        layers: *layers,
+                        culling: *culling,
+                        instance_buffer: instance.buffer(),
+                        instance_count: instance.count(),
+                    }),
+            )
+            .collect()
+    }
+
     #[deprecated]
     pub fn render(
         &self,
         pass: &mut wgpu::RenderPass,
         camera_bind_group: &wgpu::BindGroup,
+        environment_bind_group: &wgpu::BindGroup,
         instances: &[Texture3dInstanceToRender],
     ) {
-        pass.set_pipeline(&self.pipeline);
+        pass.set_pipeline(&self.pipelines[&FaceCulling::default()]);
         pass.set_bind_group(0, camera_bind_group, &[]);
+        pass.set_bind_group(2, environment_bind_group, &[]);
 
         pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
         pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
@@ -336,58 +1021,183 @@ impl Texture3dRenderer {
         });
     }
 
+    /// Draw the instances on any of the camera's layers. Returns what was drawn.
     pub fn render_storage(
         &self,
         pass: &mut wgpu::RenderPass,
         camera_bind_group: &wgpu::BindGroup,
-        instances: &[(Option<HandleId>, &wgpu::Buffer, u32)],
+        environment_bind_group: &wgpu::BindGroup,
+        camera_layers: RenderLayers,
+        instances: &[Texture3dBatch],
         storage: &AssetStorage,
-    ) {
-        pass.set_pipeline(&self.pipeline);
-        pass.set_bind_group(0, camera_bind_group, &[]);
+        target: Option<&RenderTarget>,
+    ) -> DrawCounts {
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+
+        self.pipelines
+            .iter()
+            .fold(DrawCounts::default(), |counts, (culling, pipeline)| {
+                let batches = instances
+                    .iter()
+                    .filter(|batch| {
+                        batch.culling == *culling && batch.layers.intersects(&camera_layers)
+                    })
+                    .collect::<Vec<_>>();
+
+                if batches.is_empty() {
+                    return counts;
+                }
+
+                pass.set_pipeline(pipeline);
+                pass.set_bind_group(0, camera_bind_group, &[]);
+                pass.set_bind_group(2, environment_bind_group, &[]);
+
+                batches
+                    .into_iter()
+                    .fold(counts + DrawCounts::pipeline(), |counts, batch| {
+                        let binding = match self.texture_binding(storage, batch.texture, target) {
+                            Some(binding) => binding,
+                            None => return counts,
+                        };
+
+                        pass.set_vertex_buffer(1, batch.instance_buffer.slice(..));
+                        pass.set_bind_group(1, binding, &[]);
+
+                        pass.draw_indexed(0..self.index_count, 0, 0..batch.instance_count);
+                        counts.draw(batch.instance_count)
+                    })
+            })
+    }
+
+    /// Draw the texture array instances on any of the camera's layers, one draw
+    /// per culling mode and layers. Returns what was drawn.
+    pub fn render_array(
+        &self,
+        pass: &mut wgpu::RenderPass,
+        camera_bind_group: &wgpu::BindGroup,
+        environment_bind_group: &wgpu::BindGroup,
+        camera_layers: RenderLayers,
+    ) -> DrawCounts {
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+
+        self.array_pipelines
+            .iter()
+            .fold(DrawCounts::default(), |counts, (culling, pipeline)| {
+                let batches = self
+                    .array_instances
+                    .iter()
+                    .filter(|((layers, batch_culling), instances)| {
+                        batch_culling == culling
+                            && layers.intersects(&camera_layers)
+                            && !instances.is_empty()
+                    })
+                    .collect::<Vec<_>>();
+
+                if batches.is_empty() {
+                    return counts;
+                }
+
+                pass.set_pipeline(pipeline);
+                pass.set_bind_group(0, camera_bind_group, &[]);
+                pass.set_bind_group(1, self.array.bind_group(), &[]);
+                pass.set_bind_group(2, environment_bind_group, &[]);
+
+                batches.into_iter().fold(
+                    counts + DrawCounts::pipeline(),
+                    |counts, (_, instances)| {
+                        pass.set_vertex_buffer(1, instances.buffer().slice(..));
+                        pass.draw_indexed(0..self.index_count, 0, 0..instances.count());
+                        counts.draw(instances.count())
+                    },
+                )
+            })
+    }
+
+    /// Draw the gpu culled instances on any of the camera's layers, using the
+    /// camera's index in the last culling. Returns what was drawn, counting
+    /// instances from before they were culled.
+    pub fn render_indirect(
+        &self,
+        pass: &mut wgpu::RenderPass,
+        camera_bind_group: &wgpu::BindGroup,
+        environment_bind_group: &wgpu::BindGroup,
+        camera_layers: RenderLayers,
+        view: u32,
+        storage: &AssetStorage,
+        target: Option<&RenderTarget>,
+    ) -> DrawCounts {
+        let views = self
+            .culler
+            .as_ref()
+            .map(|culler| culler.views())
+            .unwrap_or(0);
+        if view >= views {
+            return DrawCounts::default();
+        }
 
         pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
         pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
 
-        instances.into_iter().for_each(|instance| {
-            pass.set_vertex_buffer(1, instance.1.slice(..));
+        self.pipelines
+            .iter()
+            .fold(DrawCounts::default(), |counts, (culling, pipeline)| {
+                let batches = self
+                    .indirect_instances
+                    .iter()
+                    .filter(|((_, layers, batch_culling), batch)| {
+                        batch_culling == culling
+                            && layers.intersects(&camera_layers)
+                            && !batch.is_empty()
+                    })
+                    .collect::<Vec<_>>();
 
-            match instance.0 {
-                Some(id) => {
-                    let texture = storage.get_asset::<Texture>(id).unwrap();
-                    pass.set_bind_group(1, texture.binding(), &[]);
+                if batches.is_empty() {
+                    return counts;
                 }
-                None => pass.set_bind_group(1, &self.default_texture_bind_group, &[]),
-            }
 
-            pass.draw_indexed(0..self.index_count, 0, 0..instance.2);
-        });
+                pass.set_pipeline(pipeline);
+                pass.set_bind_group(0, camera_bind_group, &[]);
+                pass.set_bind_group(2, environment_bind_group, &[]);
+
+                batches.into_iter().fold(
+                    counts + DrawCounts::pipeline(),
+                    |counts, ((texture, _, _), batch)| {
+                        let binding = match self.texture_binding(storage, *texture, target) {
+                            Some(binding) => binding,
+                            None => return counts,
+                        };
+
+                        pass.set_bind_group(1, binding, &[]);
+                        batch.draw(pass, 1, view);
+                        counts.draw(batch.count())
+                    },
+                )
+            })
     }
-}
 
-//====================================================================
+    // Bind group of a batch's texture. Batches drawing a render target's own
+    // texture into it are skipped, as it can't be sampled while drawn into.
+    fn texture_binding<'a>(
+        &'a self,
+        storage: &'a AssetStorage,
+        texture: Option<HandleId>,
+        target: Option<&RenderTarget>,
+    ) -> Option<&'a wgpu::BindGroup> {
+        let id = match texture {
+            Some(id) => id,
+            None => return Some(&self.default_texture_bind_group),
+        };
 
-struct Texture3dInstance {
-    instance_buffer: wgpu::Buffer,
-    instance_count: u32,
-}
+        if let Some(target) = target {
+            if let Err(e) = target.check_sampled(id) {
+                target.report(e);
+                return None;
+            }
+        }
 
-impl Texture3dInstance {
-    #[inline]
-    fn update(
-        &mut self,
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        data: &[Texture3dInstanceRaw],
-    ) {
-        render_tools::update_instance_buffer(
-            device,
-            queue,
-            "Texture 3d",
-            &mut self.instance_buffer,
-            &mut self.instance_count,
-            data,
-        )
+        Some(storage.get_asset::<Texture>(id).unwrap().binding())
     }
 }
 
@@ -0,0 +1,333 @@
+//====================================================================
+
+use std::{
+    collections::{HashMap, HashSet},
+    hash::BuildHasherDefault,
+};
+
+use cabat_assets::{asset_storage::AssetStorage, handle::HandleId};
+use cabat_common::Size;
+use rustc_hash::FxHasher;
+
+use crate::{render_tools, texture::Texture};
+
+//====================================================================
+
+/// Copies same sized textures into the layers of one array texture, so
+/// anything drawn with them can share a bind group and be batched together,
+/// picking its layer per instance.
+///
+/// Layers are freed once their texture is unloaded. The array grows as needed
+/// up to its maximum layer count. Textures that don't fit, or arrive once the
+/// array is full, are rejected rather than retried every frame.
+pub struct TextureArray {
+    label: String,
+    layer_size: Size<u32>,
+    max_layers: u32,
+
+    texture: wgpu::Texture,
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    capacity: u32,
+
+    layers: HashMap<HandleId, u32, BuildHasherDefault<FxHasher>>,
+    free: Vec<u32>,
+    next: u32,
+
+    // Textures with the wrong size or format, kept until they're unloaded
+    unfit: HashSet<HandleId, BuildHasherDefault<FxHasher>>,
+    // Textures that arrived while the array was full, retried once layers free up
+    overflow: HashSet<HandleId, BuildHasherDefault<FxHasher>>,
+}
+
+impl TextureArray {
+    /// Textures must be in this format to be added.
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+    const INITIAL_CAPACITY: u32 = 16;
+
+    pub fn new(device: &wgpu::Device, label: &str, layer_size: Size<u32>, max_layers: u32) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(&format!("{} Texture Array Bind Group Layout", label)),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                render_tools::bgl_sampler_entry(1),
+            ],
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(&format!("{} Texture Array Sampler", label)),
+            ..Default::default()
+        });
+
+        let max_layers = max_layers.max(1);
+        let capacity = Self::INITIAL_CAPACITY.min(max_layers);
+
+        let texture = Self::create_texture(device, label, layer_size, capacity);
+        let bind_group =
+            Self::create_bind_group(device, label, &bind_group_layout, &texture, &sampler);
+
+        Self {
+            label: label.to_string(),
+            layer_size,
+            max_layers,
+
+            texture,
+            sampler,
+            bind_group_layout,
+            bind_group,
+            capacity,
+
+            layers: HashMap::default(),
+            free: Vec::new(),
+            next: 0,
+
+            unfit: HashSet::default(),
+            overflow: HashSet::default(),
+        }
+    }
+
+    fn create_texture(
+        device: &wgpu::Device,
+        label: &str,
+        layer_size: Size<u32>,
+        capacity: u32,
+    ) -> wgpu::Texture {
+        render_tools::create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label: Some(&format!("{} Texture Array", label)),
+                size: wgpu::Extent3d {
+                    width: layer_size.width.max(1),
+                    height: layer_size.height.max(1),
+                    depth_or_array_layers: capacity,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: Self::FORMAT,
+                // Copy source for keeping existing layers when growing
+                usage: wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_DST
+                    | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            },
+        )
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        label: &str,
+        layout: &wgpu::BindGroupLayout,
+        texture: &wgpu::Texture,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(&format!("{} Texture Array View", label)),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{} Texture Array Bind Group", label)),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        })
+    }
+
+    /// Layout with the array view at binding 0 and its sampler at binding 1.
+    #[inline]
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    #[inline]
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    #[inline]
+    pub fn layer_size(&self) -> Size<u32> {
+        self.layer_size
+    }
+
+    /// Number of layers in use.
+    #[inline]
+    pub fn len(&self) -> u32 {
+        self.layers.len() as u32
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Layer already holding the texture.
+    #[inline]
+    pub fn get(&self, id: HandleId) -> Option<u32> {
+        self.layers.get(&id).copied()
+    }
+
+    /// Whether the texture was turned away because it doesn't fit or the array
+    /// was full. [TextureArray::layer] won't try it again until it's unloaded
+    /// or layers are freed.
+    #[inline]
+    pub fn is_rejected(&self, id: HandleId) -> bool {
+        self.unfit.contains(&id) || self.overflow.contains(&id)
+    }
+
+    /// Whether a texture has the size and format to be added.
+    pub fn accepts(&self, texture: &Texture) -> bool {
+        let raw = &texture.raw().texture;
+
+        raw.format() == Self::FORMAT
+            && raw.width() == self.layer_size.width
+            && raw.height() == self.layer_size.height
+            && raw.depth_or_array_layers() == 1
+    }
+
+    /// Layer holding the texture, copying it in if it isn't already. Returns
+    /// `None` if the texture isn't loaded yet, or if it's rejected because it
+    /// doesn't fit or the array is full, see [TextureArray::is_rejected].
+    pub fn layer(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        storage: &AssetStorage,
+        id: HandleId,
+    ) -> Option<u32> {
+        if let Some(layer) = self.layers.get(&id) {
+            return Some(*layer);
+        }
+
+        if self.is_rejected(id) {
+            return None;
+        }
+
+        // Not loaded yet, so tried again next time
+        let texture = storage.get_asset::<Texture>(id)?;
+        if !self.accepts(texture) {
+            log::debug!(
+                "Texture doesn't match the '{}' texture array's size or format",
+                self.label
+            );
+            self.unfit.insert(id);
+            return None;
+        }
+
+        let layer = match self.free.pop() {
+            Some(layer) => layer,
+            None => {
+                if self.next >= self.max_layers {
+                    log::debug!("'{}' texture array is full", self.label);
+                    self.overflow.insert(id);
+                    return None;
+                }
+
+                if self.next >= self.capacity {
+                    self.grow(device, encoder);
+                }
+
+                self.next += 1;
+                self.next - 1
+            }
+        };
+
+        encoder.copy_texture_to_texture(
+            texture.raw().texture.as_image_copy(),
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: 0,
+                    y: 0,
+                    z: layer,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::Extent3d {
+                width: self.layer_size.width,
+                height: self.layer_size.height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        self.layers.insert(id, layer);
+        Some(layer)
+    }
+
+    fn grow(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
+        let capacity = (self.capacity * 2).min(self.max_layers);
+
+        log::trace!(
+            "Growing '{}' texture array from {} to {} layers",
+            self.label,
+            self.capacity,
+            capacity
+        );
+
+        let texture = Self::create_texture(device, &self.label, self.layer_size, capacity);
+
+        encoder.copy_texture_to_texture(
+            self.texture.as_image_copy(),
+            texture.as_image_copy(),
+            wgpu::Extent3d {
+                width: self.layer_size.width.max(1),
+                height: self.layer_size.height.max(1),
+                depth_or_array_layers: self.capacity,
+            },
+        );
+
+        self.texture = texture;
+        self.capacity = capacity;
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.label,
+            &self.bind_group_layout,
+            &self.texture,
+            &self.sampler,
+        );
+    }
+
+    /// Free the layers of any textures that have been unloaded.
+    pub fn trim(&mut self, storage: &AssetStorage) {
+        let loaded = |id: &HandleId| storage.get_asset::<Texture>(*id).is_some();
+        let free = &mut self.free;
+
+        self.layers.retain(|id, layer| {
+            let loaded = loaded(id);
+            if !loaded {
+                free.push(*layer);
+            }
+            loaded
+        });
+
+        self.unfit.retain(loaded);
+
+        // Textures turned away while full get another chance at the freed layers
+        match self.free.is_empty() {
+            true => self.overflow.retain(loaded),
+            false => self.overflow.clear(),
+        }
+    }
+}
+
+//====================================================================
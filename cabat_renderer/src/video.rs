@@ -0,0 +1,370 @@
+//====================================================================
+
+use std::{
+    error::Error,
+    fmt::Display,
+    path::Path,
+    sync::{
+        mpsc::{self, Receiver, SyncSender, TryRecvError},
+        Mutex,
+    },
+};
+
+use cabat_assets::{asset_storage::AssetStorage, handle::Handle};
+use cabat_common::Size;
+use cabat_runner::tools::Time;
+use cabat_shipyard::prelude::*;
+use ffmpeg_next as ffmpeg;
+use shipyard::{Component, IntoIter, ViewMut};
+
+use crate::{
+    shared::BindGroupLayoutRegistry,
+    texture::{RawTexture, Texture},
+    Queue,
+};
+
+//====================================================================
+
+/// Streams [VideoPlayer] frames into their textures. Requires the `video`
+/// feature and ffmpeg to be installed.
+pub struct VideoPlugin;
+
+impl Plugin for VideoPlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        builder.add_workload_last(Stages::Update, sys_update_videos);
+    }
+}
+
+//====================================================================
+
+#[derive(Debug)]
+pub enum VideoError {
+    Ffmpeg(ffmpeg::Error),
+    NoVideoStream,
+    Thread(std::io::Error),
+}
+
+impl Error for VideoError {}
+
+impl Display for VideoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            VideoError::Ffmpeg(e) => write!(f, "Unable to decode video: {}", e),
+            VideoError::NoVideoStream => write!(f, "File doesn't contain a video stream."),
+            VideoError::Thread(e) => write!(f, "Unable to spawn video decoding thread: {}", e),
+        }
+    }
+}
+
+impl From<ffmpeg::Error> for VideoError {
+    #[inline]
+    fn from(value: ffmpeg::Error) -> Self {
+        Self::Ffmpeg(value)
+    }
+}
+
+//====================================================================
+
+// Number of decoded frames waiting to be shown before the decoder blocks
+const FRAME_BUFFER: usize = 8;
+
+struct VideoFrame {
+    /// Seconds since playback started, including previous loops.
+    time: f64,
+    stride: u32,
+    data: Vec<u8>,
+}
+
+/// Plays a video into a streaming [Texture], which can be used on sprites and
+/// materials like any other texture. Frames are decoded on a separate thread
+/// and shown at the video's own pace, advanced by the [Time] delta each frame.
+#[derive(Component)]
+pub struct VideoPlayer {
+    texture: Handle<Texture>,
+    size: Size<u32>,
+
+    frames: Mutex<Receiver<VideoFrame>>,
+    pending: Option<VideoFrame>,
+    elapsed: f64,
+    finished: bool,
+
+    pub playing: bool,
+    pub speed: f32,
+}
+
+impl VideoPlayer {
+    /// Open a video relative to the asset storage's load path and start
+    /// playing it.
+    pub fn open(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layouts: &BindGroupLayoutRegistry,
+        storage: &mut AssetStorage,
+        path: impl AsRef<Path>,
+        looping: bool,
+    ) -> Result<Self, VideoError> {
+        ffmpeg::init()?;
+
+        let path = storage.load_path().join(path);
+
+        // Opened here as well as on the decoding thread to know the texture size up front
+        let input = ffmpeg::format::input(&path)?;
+        let stream = input
+            .streams()
+            .best(ffmpeg::media::Type::Video)
+            .ok_or(VideoError::NoVideoStream)?;
+
+        let decoder = ffmpeg::codec::context::Context::from_parameters(stream.parameters())?
+            .decoder()
+            .video()?;
+
+        let size = Size::new(decoder.width().max(1), decoder.height().max(1));
+
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("Video");
+
+        let raw = RawTexture::from_image(
+            device,
+            queue,
+            &image::DynamicImage::new_rgba8(size.width, size.height),
+            Some(name),
+            None,
+        );
+        let texture = storage.insert_asset(layouts.load_texture(device, raw, Some(name)));
+
+        let (sender, receiver) = mpsc::sync_channel(FRAME_BUFFER);
+
+        std::thread::Builder::new()
+            .name(format!("Video Decoder: {}", name))
+            .spawn(move || {
+                if let Err(e) = decode(&path, looping, &sender) {
+                    log::warn!("Stopped decoding video '{}': {}", path.display(), e);
+                }
+            })
+            .map_err(VideoError::Thread)?;
+
+        Ok(Self {
+            texture,
+            size,
+
+            frames: Mutex::new(receiver),
+            pending: None,
+            elapsed: 0.,
+            finished: false,
+
+            playing: true,
+            speed: 1.,
+        })
+    }
+
+    #[inline]
+    pub fn texture(&self) -> &Handle<Texture> {
+        &self.texture
+    }
+
+    #[inline]
+    pub fn size(&self) -> Size<u32> {
+        self.size
+    }
+
+    /// Seconds of video played, including previous loops.
+    #[inline]
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    /// Whether a video that doesn't loop has shown its last frame.
+    #[inline]
+    pub fn finished(&self) -> bool {
+        self.finished && self.pending.is_none()
+    }
+
+    fn update(&mut self, queue: &wgpu::Queue, storage: &AssetStorage, delta: f64) {
+        if self.playing {
+            self.elapsed += delta * self.speed as f64;
+        }
+
+        let frames = self.frames.get_mut().unwrap();
+
+        // Skip to the latest frame that is due, in case several are
+        let mut latest = None;
+        loop {
+            let frame = match self.pending.take() {
+                Some(frame) => frame,
+                None => match frames.try_recv() {
+                    Ok(frame) => frame,
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        self.finished = true;
+                        break;
+                    }
+                },
+            };
+
+            if frame.time > self.elapsed {
+                self.pending = Some(frame);
+                break;
+            }
+
+            latest = Some(frame);
+        }
+
+        let frame = match latest {
+            Some(frame) => frame,
+            None => return,
+        };
+
+        let texture = match storage.get_asset::<Texture>(self.texture.id()) {
+            Some(texture) => texture,
+            None => return,
+        };
+
+        queue.write_texture(
+            texture.raw().texture.as_image_copy(),
+            &frame.data,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(frame.stride),
+                rows_per_image: None,
+            },
+            wgpu::Extent3d {
+                width: self.size.width,
+                height: self.size.height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+//====================================================================
+
+// Runs until the video ends without looping or the player is dropped
+fn decode(path: &Path, looping: bool, sender: &SyncSender<VideoFrame>) -> Result<(), VideoError> {
+    let mut offset = 0.;
+
+    loop {
+        let mut input = ffmpeg::format::input(path)?;
+        let stream = input
+            .streams()
+            .best(ffmpeg::media::Type::Video)
+            .ok_or(VideoError::NoVideoStream)?;
+
+        let index = stream.index();
+        let time_base = f64::from(stream.time_base());
+        // Timestamps count from the stream's start, which isn't always zero.
+        // Streams without a start time report i64::MIN.
+        let start_time = stream.start_time().max(0);
+        let frame_rate = f64::from(stream.avg_frame_rate());
+
+        let mut decoder = ffmpeg::codec::context::Context::from_parameters(stream.parameters())?
+            .decoder()
+            .video()?;
+
+        let mut scaler = ffmpeg::software::scaling::Context::get(
+            decoder.format(),
+            decoder.width(),
+            decoder.height(),
+            ffmpeg::format::Pixel::RGBA,
+            decoder.width(),
+            decoder.height(),
+            ffmpeg::software::scaling::Flags::BILINEAR,
+        )?;
+
+        let mut last = offset;
+
+        for (stream, packet) in input.packets() {
+            if stream.index() != index {
+                continue;
+            }
+
+            decoder.send_packet(&packet)?;
+            match send_frames(
+                &mut decoder,
+                &mut scaler,
+                sender,
+                offset,
+                start_time,
+                time_base,
+            )? {
+                Some(time) => last = last.max(time),
+                None => return Ok(()),
+            }
+        }
+
+        decoder.send_eof()?;
+        match send_frames(
+            &mut decoder,
+            &mut scaler,
+            sender,
+            offset,
+            start_time,
+            time_base,
+        )? {
+            Some(time) => last = last.max(time),
+            None => return Ok(()),
+        }
+
+        if !looping {
+            return Ok(());
+        }
+
+        // Next loop starts a frame after the last one was shown
+        offset = match frame_rate > 0. {
+            true => last + 1. / frame_rate,
+            false => last,
+        };
+    }
+}
+
+// Returns the time of the latest frame sent, or None once the player is gone
+fn send_frames(
+    decoder: &mut ffmpeg::decoder::Video,
+    scaler: &mut ffmpeg::software::scaling::Context,
+    sender: &SyncSender<VideoFrame>,
+    offset: f64,
+    start_time: i64,
+    time_base: f64,
+) -> Result<Option<f64>, VideoError> {
+    let mut decoded = ffmpeg::frame::Video::empty();
+    let mut last = offset;
+
+    while decoder.receive_frame(&mut decoded).is_ok() {
+        let mut rgba = ffmpeg::frame::Video::empty();
+        scaler.run(&decoded, &mut rgba)?;
+
+        let timestamp = decoded.timestamp().unwrap_or(start_time) - start_time;
+        let time = offset + timestamp.max(0) as f64 * time_base;
+        last = last.max(time);
+
+        let frame = VideoFrame {
+            time,
+            stride: rgba.stride(0) as u32,
+            data: rgba.data(0).to_vec(),
+        };
+
+        if sender.send(frame).is_err() {
+            return Ok(None);
+        }
+    }
+
+    Ok(Some(last))
+}
+
+//====================================================================
+
+fn sys_update_videos(
+    queue: Res<Queue>,
+    storage: Res<AssetStorage>,
+    time: Res<Time>,
+    mut vm_players: ViewMut<VideoPlayer>,
+) {
+    let delta = time.delta().as_secs_f64();
+
+    (&mut vm_players)
+        .iter()
+        .for_each(|player| player.update(queue.inner(), &storage, delta));
+}
+
+//====================================================================
@@ -0,0 +1,220 @@
+//====================================================================
+
+use std::collections::HashSet;
+
+use cabat_spatial::{Aabb, SpatialIndex};
+use shipyard::{Component, EntityId, Get, IntoIter, IntoWithId, View};
+
+use crate::{
+    camera::{self, Frustum, MainCamera, SceneCamera},
+    render_target::RenderTarget,
+};
+
+//====================================================================
+
+/// Hide an entity from the sprite and text renderers without removing its
+/// components. Entities without one are always drawn.
+///
+/// Sprites and 3d text with a visibility are also culled when they are outside
+/// of every camera, skipping those whose [Bounds](cabat_spatial::Bounds) in the
+/// [SpatialIndex] are away from all of them. Entities with an
+/// [OcclusionCulled](crate::occlusion::OcclusionCulled) when hidden behind others.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Visibility {
+    pub visible: bool,
+    culled: bool,
+    occluded: bool,
+}
+
+impl Default for Visibility {
+    #[inline]
+    fn default() -> Self {
+        Self::visible()
+    }
+}
+
+impl Visibility {
+    #[inline]
+    pub fn visible() -> Self {
+        Self {
+            visible: true,
+            culled: false,
+            occluded: false,
+        }
+    }
+
+    #[inline]
+    pub fn hidden() -> Self {
+        Self {
+            visible: false,
+            culled: false,
+            occluded: false,
+        }
+    }
+
+    /// Outside of every camera last time culling ran.
+    #[inline]
+    pub fn culled(&self) -> bool {
+        self.culled
+    }
+
+    /// Hidden behind other geometry last time occlusion results came back.
+    #[inline]
+    pub fn occluded(&self) -> bool {
+        self.occluded
+    }
+
+    /// Whether the entity will be drawn.
+    #[inline]
+    pub fn is_visible(&self) -> bool {
+        self.visible && !self.culled && !self.occluded
+    }
+
+    #[inline]
+    pub(crate) fn set_culled(&mut self, culled: bool) {
+        self.culled = culled;
+    }
+
+    #[inline]
+    pub(crate) fn set_occluded(&mut self, occluded: bool) {
+        self.occluded = occluded;
+    }
+}
+
+//--------------------------------------------------
+
+/// Layers an entity is drawn on, or a camera draws. Cameras only draw entities
+/// sharing at least one layer with them. Entities and cameras without one are on
+/// layer 0, including the [MainCamera].
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RenderLayers(pub u32);
+
+impl Default for RenderLayers {
+    #[inline]
+    fn default() -> Self {
+        Self::layer(0)
+    }
+}
+
+impl RenderLayers {
+    pub const ALL: Self = Self(u32::MAX);
+    pub const NONE: Self = Self(0);
+
+    #[inline]
+    pub fn layer(layer: u8) -> Self {
+        Self::NONE.with(layer)
+    }
+
+    #[inline]
+    pub fn with(self, layer: u8) -> Self {
+        assert!(layer < 32, "Render layer {} out of range (0 to 31)", layer);
+        Self(self.0 | 1 << layer)
+    }
+
+    #[inline]
+    pub fn without(self, layer: u8) -> Self {
+        assert!(layer < 32, "Render layer {} out of range (0 to 31)", layer);
+        Self(self.0 & !(1 << layer))
+    }
+
+    #[inline]
+    pub fn intersects(&self, other: &RenderLayers) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Layers of an entity, falling back to the default layer.
+    #[inline]
+    pub fn of(v_layers: &View<RenderLayers>, id: EntityId) -> Self {
+        v_layers.get(id).copied().unwrap_or_default()
+    }
+}
+
+//--------------------------------------------------
+
+/// Whether the entity should be drawn. True for entities without a [Visibility].
+#[inline]
+pub fn is_visible(v_visibility: &View<Visibility>, id: EntityId) -> bool {
+    match v_visibility.get(id) {
+        Ok(visibility) => visibility.is_visible(),
+        Err(_) => true,
+    }
+}
+
+/// Frustums and layers of every camera drawing this frame, including render targets.
+pub(crate) fn active_frustums(
+    main_camera: &MainCamera,
+    v_cameras: &View<SceneCamera>,
+    v_targets: &View<RenderTarget>,
+    v_layers: &View<RenderLayers>,
+) -> Vec<(Frustum, RenderLayers)> {
+    camera::main_pass_cameras(main_camera, v_cameras, v_layers)
+        .into_iter()
+        .map(|view| (view.frustum, view.layers))
+        .chain(
+            v_targets
+                .iter()
+                .with_id()
+                .filter(|(_, target)| target.active)
+                .map(|(id, target)| (target.camera().frustum(), RenderLayers::of(v_layers, id))),
+        )
+        .collect()
+}
+
+/// Entities of the [SpatialIndex] within the box around any of the camera
+/// frustums. Indexed entities that weren't found can be culled without
+/// testing them.
+pub(crate) struct IndexedCandidates<'a> {
+    index: Option<&'a SpatialIndex>,
+    found: HashSet<EntityId>,
+}
+
+impl<'a> IndexedCandidates<'a> {
+    pub(crate) fn new(
+        index: Option<&'a SpatialIndex>,
+        frustums: &[(Frustum, RenderLayers)],
+    ) -> Self {
+        // Frustums reaching infinitely far can't be bounded, so rule nothing out
+        let boxes = frustums
+            .iter()
+            .map(|(frustum, _)| frustum.aabb())
+            .collect::<Option<Vec<_>>>();
+
+        match (index, boxes) {
+            (Some(index), Some(boxes)) if !index.is_empty() => Self {
+                index: Some(index),
+                found: boxes
+                    .into_iter()
+                    .flat_map(|(min, max)| index.query_aabb(&Aabb::new(min, max)))
+                    .collect(),
+            },
+
+            _ => Self {
+                index: None,
+                found: HashSet::new(),
+            },
+        }
+    }
+
+    /// Whether the entity is indexed but away from every camera.
+    #[inline]
+    pub(crate) fn excludes(&self, id: EntityId) -> bool {
+        match self.index {
+            Some(index) => !self.found.contains(&id) && index.aabb(id).is_some(),
+            None => false,
+        }
+    }
+}
+
+/// Whether a bounding sphere on the given layers is seen by any of the cameras.
+pub(crate) fn sphere_visible(
+    frustums: &[(Frustum, RenderLayers)],
+    layers: RenderLayers,
+    center: glam::Vec3,
+    radius: f32,
+) -> bool {
+    frustums.iter().any(|(frustum, camera_layers)| {
+        camera_layers.intersects(&layers) && frustum.intersects_sphere(center, radius)
+    })
+}
+
+//====================================================================
@@ -0,0 +1,79 @@
+//====================================================================
+
+use shipyard::Unique;
+
+//====================================================================
+
+/// Read and write the system clipboard. Falls back to a clipboard private to
+/// the app when the system one can't be opened, such as when running headless.
+///
+/// arboard's clipboard isn't Send or Sync on every platform, so this is a non
+/// send unique borrowed with [cabat_shipyard::NonSendResMut].
+#[derive(Unique)]
+pub struct Clipboard {
+    system: Option<arboard::Clipboard>,
+    local: String,
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        let system = match arboard::Clipboard::new() {
+            Ok(clipboard) => Some(clipboard),
+            Err(e) => {
+                log::warn!(
+                    "System clipboard unavailable - using local clipboard: {}",
+                    e
+                );
+                None
+            }
+        };
+
+        Self {
+            system,
+            local: String::new(),
+        }
+    }
+}
+
+impl Clipboard {
+    /// Whether the system clipboard is being used.
+    #[inline]
+    pub fn is_system(&self) -> bool {
+        self.system.is_some()
+    }
+
+    /// Text on the clipboard, or `None` if it's empty or holds something else.
+    pub fn get_text(&mut self) -> Option<String> {
+        let system = match &mut self.system {
+            Some(system) => system,
+            None => return (!self.local.is_empty()).then(|| self.local.clone()),
+        };
+
+        match system.get_text() {
+            Ok(text) => Some(text),
+            Err(arboard::Error::ContentNotAvailable) => None,
+            Err(e) => {
+                log::warn!("Failed to read clipboard: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        let text = text.into();
+
+        let system = match &mut self.system {
+            Some(system) => system,
+            None => {
+                self.local = text;
+                return;
+            }
+        };
+
+        if let Err(e) = system.set_text(text) {
+            log::warn!("Failed to write clipboard: {}", e);
+        }
+    }
+}
+
+//====================================================================
@@ -0,0 +1,122 @@
+//====================================================================
+
+use std::time::{Duration, Instant};
+
+use cabat_common::{LaunchConfig, Size, WindowScale, WindowSize};
+use cabat_shipyard::{GetWorld, ResMut, WorkloadBuilder};
+
+use crate::{tools::Time, FrameStepper, FIXED_TIMESTEP, TIMESTEP};
+
+//====================================================================
+
+/// Runner without a window or event loop. Frames only advance when [HeadlessRunner::tick]
+/// is called and use a fixed delta, so tests can step the world deterministically
+/// and inspect it between frames.
+///
+/// Plugins that need a window surface (such as the renderer) can't be used.
+pub struct HeadlessRunner {
+    world: shipyard::World,
+    stepper: FrameStepper,
+    frame: u64,
+}
+
+impl HeadlessRunner {
+    #[inline]
+    pub fn new<F>(build_app: F) -> Self
+    where
+        F: FnOnce(&WorkloadBuilder),
+    {
+        Self::with_config(
+            LaunchConfig {
+                headless: true,
+                ..Default::default()
+            },
+            build_app,
+        )
+    }
+
+    /// Build the app with launch options. The config is inserted as a unique
+    /// before the app is built.
+    pub fn with_config<F>(config: LaunchConfig, build_app: F) -> Self
+    where
+        F: FnOnce(&WorkloadBuilder),
+    {
+        let world = shipyard::World::new();
+
+        // Stand in window data for systems that read it
+        let size = config.window_size.unwrap_or(Size::new(800, 600));
+        world.add_unique(WindowSize::new(size));
+        world.add_unique(WindowScale::new(1.));
+        world.add_unique(config);
+
+        let builder = WorkloadBuilder::new(&world);
+        build_app(&builder);
+        builder.build();
+
+        FrameStepper::setup(&world);
+
+        Self {
+            world,
+            stepper: FrameStepper::default(),
+            frame: 0,
+        }
+    }
+
+    #[inline]
+    pub fn world(&self) -> &shipyard::World {
+        &self.world
+    }
+
+    /// Number of frames ticked so far.
+    #[inline]
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Advance one frame by the fixed update timestep.
+    #[inline]
+    pub fn tick(&mut self) {
+        self.tick_with_delta(Duration::from_secs_f32(FIXED_TIMESTEP));
+    }
+
+    pub fn tick_with_delta(&mut self, delta: Duration) {
+        if let Ok(mut time) = self.world.borrow::<ResMut<Time>>() {
+            time.set_manual_delta(delta);
+        }
+
+        self.stepper.tick(&self.world, delta, true);
+        self.frame += 1;
+    }
+
+    #[inline]
+    pub fn tick_frames(&mut self, frames: u32) {
+        (0..frames).for_each(|_| self.tick());
+    }
+
+    /// Tick frames in real time, at the same rate as the windowed runner, until
+    /// the process is stopped. Used for dedicated servers and other apps without
+    /// a window.
+    pub fn run(mut self) -> ! {
+        let timestep = Duration::from_secs_f32(TIMESTEP);
+        let mut last_tick = Instant::now();
+
+        loop {
+            let now = Instant::now();
+            self.tick_with_delta(now - last_tick);
+            last_tick = now;
+
+            if let Some(remaining) = timestep.checked_sub(last_tick.elapsed()) {
+                std::thread::sleep(remaining);
+            }
+        }
+    }
+}
+
+impl GetWorld for HeadlessRunner {
+    #[inline]
+    fn get_world(&self) -> &shipyard::World {
+        &self.world
+    }
+}
+
+//====================================================================
@@ -1,19 +1,35 @@
 //====================================================================
 
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use cabat_common::Size;
-use cabat_shipyard::{Stages, WorkloadBuilder};
+use cabat_shipyard::{
+    trace_span, FrameContext, Res, ResMut, StageTimings, Stages, WorkloadBuilder,
+};
+use replay::InputEvent;
+use tools::ImePreedit;
+use window::BackgroundPolicy;
 use winit::{
     application::ApplicationHandler,
-    event::{DeviceEvent, DeviceId, StartCause, WindowEvent},
-    event_loop::{ActiveEventLoop, EventLoop},
-    window::{WindowAttributes, WindowId},
+    event::{DeviceEvent, DeviceId, Ime, StartCause, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    window::{Fullscreen, WindowAttributes, WindowId},
 };
 
+pub mod clipboard;
+mod headless;
+pub mod monitor;
+pub mod replay;
+pub mod task_pool;
 pub mod tools;
 pub mod window;
 
+pub use cabat_common::LaunchConfig;
+pub use headless::HeadlessRunner;
+
 //====================================================================
 
 enum RunnerState {
@@ -26,11 +42,36 @@ pub struct Runner {
 }
 
 impl Runner {
+    #[inline]
     pub fn run<F>(build_app: F)
     where
         F: FnOnce(&WorkloadBuilder),
     {
+        Self::run_with_config(LaunchConfig::default(), build_app);
+    }
+
+    /// Run with launch options, usually parsed from the command line with
+    /// [LaunchConfig::from_env]. The config is inserted as a unique before the
+    /// app is built so plugins can read it.
+    ///
+    /// Headless configs step the app in real time without a window until the
+    /// process is stopped. Plugins that need a window, such as the renderer, can
+    /// check [LaunchConfig::headless] while building and be left out.
+    pub fn run_with_config<F>(config: LaunchConfig, build_app: F)
+    where
+        F: FnOnce(&WorkloadBuilder),
+    {
+        if let Some(log_level) = config.log_level {
+            log::set_max_level(log_level);
+        }
+
+        if config.headless {
+            HeadlessRunner::with_config(config, build_app).run();
+        }
+
         let world = shipyard::World::new();
+        world.add_unique(config);
+
         let builder = WorkloadBuilder::new(&world);
         build_app(&builder);
         builder.build();
@@ -42,6 +83,16 @@ impl Runner {
         let event_loop = EventLoop::new().unwrap();
         event_loop.run_app(&mut runner).unwrap();
     }
+
+    /// Build the app without a window or event loop, for tests and tools that
+    /// step frames manually.
+    #[inline]
+    pub fn build_headless<F>(build_app: F) -> HeadlessRunner
+    where
+        F: FnOnce(&WorkloadBuilder),
+    {
+        HeadlessRunner::new(build_app)
+    }
 }
 
 impl ApplicationHandler for Runner {
@@ -118,39 +169,56 @@ impl ApplicationHandler for Runner {
 
 //====================================================================
 
-const TIMESTEP: f32 = 1. / 75.;
+pub(crate) const TIMESTEP: f32 = 1. / 75.;
+/// Seconds between each run of [Stages::FixedUpdate].
+pub const FIXED_TIMESTEP: f32 = 1. / 60.;
+// Avoid spiralling when a frame takes longer than the steps it has to catch up on
+const MAX_FIXED_STEPS: u32 = 5;
+// Monitors can be plugged in without the window getting an event
+const MONITOR_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+// Wait for the window to stop moving before checking which monitor it's on
+const MONITOR_MOVE_DEBOUNCE: Duration = Duration::from_millis(250);
 
 pub struct RunnerInner {
     world: shipyard::World,
     timestep: Duration,
+
+    stepper: FrameStepper,
+    last_tick: Instant,
+
+    // Resizes are applied once per frame, using the latest size
+    pending_resize: Option<Size<u32>>,
+    minimized: bool,
+    paused: bool,
+    last_monitor_check: Instant,
+    last_move: Option<Instant>,
 }
 
 impl RunnerInner {
     fn new(event_loop: &ActiveEventLoop, world: shipyard::World) -> Self {
-        let window = Arc::new(
-            event_loop
-                .create_window(WindowAttributes::default())
-                .unwrap(),
-        );
+        let attributes = match world.borrow::<Res<LaunchConfig>>() {
+            Ok(config) => window_attributes(&config),
+            Err(_) => WindowAttributes::default(),
+        };
+
+        let window = Arc::new(event_loop.create_window(attributes).unwrap());
 
         world.run_with_data(window::sys_add_window, window);
 
-        match world.run_workload(Stages::Setup) {
-            Ok(_) => {}
-            Err(e) => match e {
-                shipyard::error::RunWorkload::Run((system, err)) => {
-                    panic!(
-                        "Workload setup failed to run system '{:?}'.\n\tErr: {:?}",
-                        system, err
-                    )
-                }
-                _ => panic!("Workload setup failed to run: {:?}", e),
-            },
-        }
+        FrameStepper::setup(&world);
 
         Self {
             world,
             timestep: Duration::from_secs_f32(TIMESTEP),
+
+            stepper: FrameStepper::default(),
+            last_tick: Instant::now(),
+
+            pending_resize: None,
+            minimized: false,
+            paused: false,
+            last_monitor_check: Instant::now(),
+            last_move: None,
         }
     }
 
@@ -166,6 +234,15 @@ impl RunnerInner {
                 self.resize(Size::new(new_size.width, new_size.height))
             }
 
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                log::info!("Window scale factor changed to {}", scale_factor);
+                self.world
+                    .run_with_data(window::sys_rescale, scale_factor as f32);
+                self.refresh_monitors(true);
+            }
+
+            WindowEvent::Moved(_) => self.last_move = Some(Instant::now()),
+
             WindowEvent::Destroyed => log::error!("Window was destroyed"), // panic!("Window was destroyed"),
             WindowEvent::CloseRequested => {
                 log::info!("Close requested. Closing App.");
@@ -175,32 +252,64 @@ impl RunnerInner {
             WindowEvent::RedrawRequested => {
                 self.tick();
 
-                event_loop
-                    .set_control_flow(winit::event_loop::ControlFlow::wait_duration(self.timestep));
+                let control_flow = match self.frame_interval() {
+                    Some(interval) => ControlFlow::wait_duration(interval),
+                    None => ControlFlow::Wait,
+                };
+
+                event_loop.set_control_flow(control_flow);
+            }
+
+            WindowEvent::Focused(focused) => {
+                self.world.run_with_data(window::sys_set_focused, focused);
+                self.world.run(window::sys_apply_mouse_mode);
+                self.resume_if_paused();
+            }
+
+            WindowEvent::Occluded(occluded) => {
+                self.world.run_with_data(window::sys_set_occluded, occluded);
+                self.resume_if_paused();
             }
 
             WindowEvent::KeyboardInput { event, .. } => {
-                if let winit::keyboard::PhysicalKey::Code(key) = event.physical_key {
-                    self.world.run_with_data(
-                        tools::sys_process_input::<winit::keyboard::KeyCode>,
-                        (key, event.state.is_pressed()),
-                    );
+                if let winit::keyboard::PhysicalKey::Code(code) = event.physical_key {
+                    self.input(InputEvent::Key {
+                        code,
+                        pressed: event.state.is_pressed(),
+                    });
+                }
+
+                if let (true, Some(text)) = (event.state.is_pressed(), &event.text) {
+                    text.chars().for_each(|c| self.input(InputEvent::Char(c)));
                 }
             }
 
-            WindowEvent::MouseInput { state, button, .. } => self.world.run_with_data(
-                tools::sys_process_input::<winit::event::MouseButton>,
-                (button, state.is_pressed()),
-            ),
+            WindowEvent::Ime(ime) => match ime {
+                Ime::Commit(text) => text.chars().for_each(|c| self.input(InputEvent::Char(c))),
+                Ime::Preedit(text, cursor) => {
+                    let preedit = (!text.is_empty()).then_some(ImePreedit { text, cursor });
+                    self.world
+                        .run_with_data(tools::sys_process_ime_preedit, preedit);
+                }
+                Ime::Disabled => self
+                    .world
+                    .run_with_data(tools::sys_process_ime_preedit, None),
+                Ime::Enabled => {}
+            },
 
-            WindowEvent::CursorMoved { position, .. } => self.world.run_with_data(
-                tools::sys_process_mouse_pos,
-                [position.x as f32, position.y as f32],
-            ),
+            WindowEvent::MouseInput { state, button, .. } => self.input(InputEvent::MouseButton {
+                button,
+                pressed: state.is_pressed(),
+            }),
+
+            WindowEvent::CursorMoved { position, .. } => self.input(InputEvent::CursorMoved([
+                position.x as f32,
+                position.y as f32,
+            ])),
 
             WindowEvent::MouseWheel { delta, .. } => match delta {
                 winit::event::MouseScrollDelta::LineDelta(h, v) => {
-                    self.world.run_with_data(tools::sys_process_wheel, [h, v])
+                    self.input(InputEvent::Wheel([h, v]))
                 }
                 winit::event::MouseScrollDelta::PixelDelta(_) => {}
             },
@@ -214,38 +323,241 @@ impl RunnerInner {
             .run(|window: shipyard::UniqueView<window::Window>| window.request_redraw());
     }
 
-    // TODO
     fn device_event(
         &mut self,
-        event_loop: &ActiveEventLoop,
-        device_id: DeviceId,
+        _event_loop: &ActiveEventLoop,
+        _device_id: DeviceId,
         event: DeviceEvent,
     ) {
-        let _ = (event_loop, device_id, event);
+        // Device events arrive even while another window has focus
+        if let DeviceEvent::MouseMotion { delta } = event {
+            if self
+                .world
+                .run(|focus: Res<window::WindowFocus>| focus.focused)
+            {
+                self.input(InputEvent::MouseMotion([delta.0 as f32, delta.1 as f32]));
+            }
+        }
     }
 }
 
 impl RunnerInner {
+    #[inline]
+    fn input(&mut self, event: InputEvent) {
+        self.world.run_with_data(replay::sys_live_input, event);
+    }
+
+    #[inline]
     fn resize(&mut self, new_size: Size<u32>) {
+        self.pending_resize = Some(new_size);
+    }
+
+    fn apply_resize(&mut self) {
+        let new_size = match self.pending_resize.take() {
+            Some(new_size) => new_size,
+            None => return,
+        };
+
+        // Minimized windows report a size of zero
         if new_size.width == 0 || new_size.height == 0 {
-            log::warn!("Resize width or height of '0' provided");
+            if !self.minimized {
+                log::info!("Window minimized - pausing rendering");
+                self.minimized = true;
+            }
             return;
         }
 
+        if self.minimized {
+            log::info!("Window restored - resuming rendering");
+            self.minimized = false;
+        }
+
         self.world.run_with_data(window::sys_resize, new_size);
     }
 
+    // Time until the next frame, or None to wait until the window is in the foreground again
+    fn frame_interval(&mut self) -> Option<Duration> {
+        let in_background = self
+            .world
+            .run(|focus: Res<window::WindowFocus>| focus.in_background());
+
+        let policy = match in_background {
+            true => *self.world.borrow::<Res<BackgroundPolicy>>().unwrap(),
+            false => BackgroundPolicy::Continue,
+        };
+
+        match policy {
+            BackgroundPolicy::Continue => Some(self.timestep),
+            BackgroundPolicy::Throttle(fps) => Some(
+                self.timestep
+                    .max(Duration::from_secs_f32(1. / fps.max(0.001))),
+            ),
+            BackgroundPolicy::Pause => {
+                if !self.paused {
+                    log::info!("Window in background - pausing frames");
+                    self.paused = true;
+                }
+                None
+            }
+        }
+    }
+
+    fn resume_if_paused(&mut self) {
+        if !self.paused {
+            return;
+        }
+
+        if self
+            .world
+            .run(|focus: Res<window::WindowFocus>| focus.in_background())
+        {
+            return;
+        }
+
+        log::info!("Window in foreground - resuming frames");
+        self.paused = false;
+
+        // Don't count the time spent paused as a frame
+        self.last_tick = Instant::now();
+        self.resumed();
+    }
+
+    fn refresh_monitors(&mut self, force: bool) {
+        self.last_monitor_check = Instant::now();
+        self.last_move = None;
+        self.world
+            .run_with_data(monitor::sys_refresh_monitors, force);
+    }
+
     fn tick(&mut self) {
-        self.world.run_workload(Stages::First).unwrap();
+        let moved = self
+            .last_move
+            .is_some_and(|last_move| last_move.elapsed() >= MONITOR_MOVE_DEBOUNCE);
+
+        if moved || self.last_monitor_check.elapsed() >= MONITOR_CHECK_INTERVAL {
+            self.refresh_monitors(false);
+        }
+
+        let now = Instant::now();
+        let delta = now - self.last_tick;
+        self.last_tick = now;
+
+        self.apply_resize();
+        self.world.run(window::sys_apply_mouse_mode);
+
+        self.stepper.tick(&self.world, delta, !self.minimized);
+    }
+}
+
+fn window_attributes(config: &LaunchConfig) -> WindowAttributes {
+    let mut attributes = WindowAttributes::default();
+
+    if let Some(size) = config.window_size {
+        attributes =
+            attributes.with_inner_size(winit::dpi::PhysicalSize::new(size.width, size.height));
+    }
+
+    if config.fullscreen {
+        attributes = attributes.with_fullscreen(Some(Fullscreen::Borderless(None)));
+    }
+
+    attributes
+}
+
+//====================================================================
+
+/// Runs the stages of a frame in order. Shared by the windowed and headless runners.
+pub(crate) struct FrameStepper {
+    fixed_timestep: Duration,
+    fixed_accumulator: Duration,
+}
+
+impl Default for FrameStepper {
+    fn default() -> Self {
+        Self {
+            fixed_timestep: Duration::from_secs_f32(FIXED_TIMESTEP),
+            fixed_accumulator: Duration::ZERO,
+        }
+    }
+}
+
+impl FrameStepper {
+    pub(crate) fn setup(world: &shipyard::World) {
+        trace_span!("stage", name = ?Stages::Setup);
+        FrameContext::set_stage(Some(Stages::Setup));
+
+        match world.run_workload(Stages::Setup) {
+            Ok(_) => {}
+            Err(e) => match e {
+                shipyard::error::RunWorkload::Run((system, err)) => {
+                    panic!(
+                        "Workload setup failed to run system '{:?}'.\n\tErr: {:?}",
+                        system, err
+                    )
+                }
+                _ => panic!("Workload setup failed to run: {:?}", e),
+            },
+        }
+
+        cabat_shipyard::apply_commands(world);
+        cabat_shipyard::report_missing_uniques(world);
+
+        FrameContext::set_stage(None);
+    }
 
-        cabat_shipyard::activate_events(&self.world);
+    pub(crate) fn tick(&mut self, world: &shipyard::World, delta: Duration, render: bool) {
+        trace_span!("frame");
 
-        // TODO
-        // self.world.run_workload(Stages::FixedUpdate).unwrap();
+        Self::run_stage(world, Stages::First);
 
-        self.world.run_workload(Stages::Update).unwrap();
-        self.world.run_workload(Stages::Render).unwrap();
-        self.world.run_workload(Stages::Last).unwrap();
+        cabat_shipyard::activate_events(world);
+        cabat_shipyard::apply_state_transitions(world);
+
+        self.fixed_update(world, delta);
+
+        Self::run_stage(world, Stages::Update);
+
+        if render {
+            Self::run_stage(world, Stages::Render);
+        }
+
+        Self::run_stage(world, Stages::Last);
+
+        FrameContext::set_stage(None);
+        world.run(|mut timings: ResMut<StageTimings>| timings.end_frame());
+    }
+
+    #[inline]
+    fn run_stage(world: &shipyard::World, stage: Stages) {
+        trace_span!("stage", name = ?stage);
+        FrameContext::set_stage(Some(stage));
+        world.run(|mut timings: ResMut<StageTimings>| timings.begin_stage());
+
+        world.run_workload(stage).unwrap();
+        cabat_shipyard::apply_commands(world);
+        cabat_shipyard::flush_events(world, stage);
+
+        world.run(|mut timings: ResMut<StageTimings>| timings.end_stage(stage));
+    }
+
+    fn fixed_update(&mut self, world: &shipyard::World, delta: Duration) {
+        trace_span!("fixed_update");
+        self.fixed_accumulator += delta;
+
+        let mut steps = 0;
+
+        while self.fixed_accumulator >= self.fixed_timestep {
+            if steps == MAX_FIXED_STEPS {
+                log::warn!("Fixed update is falling behind - skipping steps");
+                self.fixed_accumulator = Duration::ZERO;
+                break;
+            }
+
+            Self::run_stage(world, Stages::FixedUpdate);
+
+            self.fixed_accumulator -= self.fixed_timestep;
+            steps += 1;
+        }
     }
 }
 
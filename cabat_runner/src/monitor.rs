@@ -0,0 +1,236 @@
+//====================================================================
+
+use cabat_common::Size;
+use cabat_shipyard::prelude::*;
+use shipyard::{AllStoragesView, Unique};
+use winit::monitor::{MonitorHandle, VideoModeHandle};
+
+use crate::window::Window;
+
+//====================================================================
+
+/// A display connected when the monitors were last checked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorInfo {
+    pub name: Option<String>,
+    /// Resolution in physical pixels.
+    pub size: Size<u32>,
+    /// Top left corner on the desktop in physical pixels.
+    pub position: [i32; 2],
+    /// Refresh rate in hertz, if known.
+    pub refresh_rate: Option<f32>,
+    pub scale_factor: f32,
+    /// Modes usable for exclusive fullscreen.
+    pub video_modes: Vec<VideoMode>,
+    handle: MonitorHandle,
+}
+
+impl MonitorInfo {
+    // Listing video modes can be slow, so they're reused from the previous
+    // query of the same monitor unless its resolution changed
+    fn new(handle: MonitorHandle, previous: Option<&MonitorInfo>) -> Self {
+        let size = handle.size();
+        let size = Size::new(size.width, size.height);
+        let position = handle.position();
+
+        let video_modes = match previous {
+            Some(previous) if previous.size == size => previous.video_modes.clone(),
+            _ => handle.video_modes().map(VideoMode::new).collect(),
+        };
+
+        Self {
+            name: handle.name(),
+            size,
+            position: [position.x, position.y],
+            refresh_rate: handle
+                .refresh_rate_millihertz()
+                .map(|millihertz| millihertz as f32 / 1000.),
+            scale_factor: handle.scale_factor() as f32,
+            video_modes,
+            handle,
+        }
+    }
+
+    #[inline]
+    pub fn handle(&self) -> &MonitorHandle {
+        &self.handle
+    }
+
+    /// Video mode matching the resolution with the highest refresh rate and bit depth.
+    pub fn best_video_mode(&self, size: Size<u32>) -> Option<&VideoMode> {
+        self.video_modes
+            .iter()
+            .filter(|mode| mode.size == size)
+            .max_by(|a, b| {
+                a.refresh_rate
+                    .total_cmp(&b.refresh_rate)
+                    .then(a.bit_depth.cmp(&b.bit_depth))
+            })
+    }
+}
+
+//--------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoMode {
+    pub size: Size<u32>,
+    pub bit_depth: u16,
+    /// Refresh rate in hertz.
+    pub refresh_rate: f32,
+    handle: VideoModeHandle,
+}
+
+impl VideoMode {
+    fn new(handle: VideoModeHandle) -> Self {
+        let size = handle.size();
+
+        Self {
+            size: Size::new(size.width, size.height),
+            bit_depth: handle.bit_depth(),
+            refresh_rate: handle.refresh_rate_millihertz() as f32 / 1000.,
+            handle,
+        }
+    }
+
+    #[inline]
+    pub fn handle(&self) -> &VideoModeHandle {
+        &self.handle
+    }
+}
+
+//====================================================================
+
+/// Connected monitors, filled in when the window is created and kept up to
+/// date by the runner. A [MonitorsChangedEvent] is sent when they change.
+#[derive(Unique, Debug, Default)]
+pub struct Monitors {
+    monitors: Vec<MonitorInfo>,
+    primary: Option<usize>,
+    current: Option<usize>,
+}
+
+impl Monitors {
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &MonitorInfo> {
+        self.monitors.iter()
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&MonitorInfo> {
+        self.monitors.get(index)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.monitors.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.monitors.is_empty()
+    }
+
+    /// Not every platform reports a primary monitor.
+    #[inline]
+    pub fn primary(&self) -> Option<&MonitorInfo> {
+        self.primary.and_then(|index| self.monitors.get(index))
+    }
+
+    /// Monitor the window is mostly on.
+    #[inline]
+    pub fn current(&self) -> Option<&MonitorInfo> {
+        self.current.and_then(|index| self.monitors.get(index))
+    }
+
+    #[inline]
+    pub fn current_index(&self) -> Option<usize> {
+        self.current
+    }
+
+    pub(crate) fn query(window: &winit::window::Window) -> Self {
+        Self::query_cached(window, &[])
+    }
+
+    fn query_cached(window: &winit::window::Window, previous: &[MonitorInfo]) -> Self {
+        let monitors = window
+            .available_monitors()
+            .map(|handle| {
+                let previous = previous.iter().find(|monitor| monitor.handle == handle);
+                MonitorInfo::new(handle, previous)
+            })
+            .collect::<Vec<_>>();
+
+        let index_of = |handle: Option<MonitorHandle>| {
+            let handle = handle?;
+            monitors.iter().position(|monitor| monitor.handle == handle)
+        };
+
+        let primary = index_of(window.primary_monitor());
+        let current = index_of(window.current_monitor());
+
+        Self {
+            monitors,
+            primary,
+            current,
+        }
+    }
+
+    // Cheap check for monitors being connected or disconnected, or the window
+    // moving onto another monitor, without querying each monitor's details
+    fn outdated(&self, window: &winit::window::Window) -> bool {
+        let current = window.current_monitor();
+        if self.current().map(|monitor| &monitor.handle) != current.as_ref() {
+            return true;
+        }
+
+        let mut available = window.available_monitors();
+        let unchanged = self
+            .monitors
+            .iter()
+            .all(|monitor| available.next().as_ref() == Some(&monitor.handle));
+
+        !unchanged || available.next().is_some()
+    }
+}
+
+//--------------------------------------------------
+
+/// Sent when monitors are connected, disconnected or changed, or the window
+/// moves onto a different monitor.
+#[derive(Event, Debug, Clone)]
+pub struct MonitorsChangedEvent {
+    /// Whether the window is on a different monitor than before.
+    pub current_changed: bool,
+}
+
+//====================================================================
+
+/// Query the monitors again if they were connected, disconnected or the window
+/// moved onto another one. `force` queries them regardless, such as when their
+/// scale factor changes.
+pub fn sys_refresh_monitors(force: bool, all_storages: AllStoragesView) {
+    let window = all_storages.borrow::<Res<Window>>().unwrap();
+    let mut monitors = all_storages.borrow::<ResMut<Monitors>>().unwrap();
+
+    if !force && !monitors.outdated(window.inner()) {
+        return;
+    }
+
+    let new = Monitors::query_cached(window.inner(), &monitors.monitors);
+
+    if monitors.monitors == new.monitors && monitors.current == new.current {
+        return;
+    }
+
+    let current_changed = monitors.current() != new.current();
+    *monitors = new;
+
+    log::info!("Monitors changed - {} connected", monitors.len());
+
+    all_storages
+        .borrow::<ResMut<EventHandler>>()
+        .unwrap()
+        .add_event(MonitorsChangedEvent { current_changed });
+}
+
+//====================================================================
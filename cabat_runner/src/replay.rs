@@ -0,0 +1,269 @@
+//====================================================================
+
+use std::{path::Path, time::Duration};
+
+use cabat_common::WindowSize;
+use cabat_shipyard::prelude::*;
+use serde::{Deserialize, Serialize};
+use shipyard::Unique;
+
+use crate::tools::{Input, KeyCode, KeyboardText, MouseButton, MouseInput, Rng, Time};
+
+//====================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum InputEvent {
+    Key {
+        code: KeyCode,
+        pressed: bool,
+    },
+    MouseButton {
+        button: MouseButton,
+        pressed: bool,
+    },
+    CursorMoved([f32; 2]),
+    /// Raw mouse movement, see [MouseInput::motion].
+    MouseMotion([f32; 2]),
+    Wheel([f32; 2]),
+    /// Character typed or committed by an input method.
+    Char(char),
+}
+
+impl InputEvent {
+    fn apply(
+        &self,
+        keys: &mut Input<KeyCode>,
+        buttons: &mut Input<MouseButton>,
+        mouse: &mut MouseInput,
+        text: &mut KeyboardText,
+        size: &WindowSize,
+    ) {
+        match *self {
+            InputEvent::Key { code, pressed } => keys.process(code, pressed),
+            InputEvent::MouseButton { button, pressed } => buttons.process(button, pressed),
+            InputEvent::CursorMoved(pos) => mouse.set_pos(pos, size),
+            InputEvent::MouseMotion(motion) => mouse.add_motion(motion),
+            InputEvent::Wheel(wheel) => mouse.add_scroll(wheel),
+            InputEvent::Char(c) => text.push(c),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RecordedInput {
+    /// Frame the event is applied at the start of.
+    pub frame: u64,
+    pub event: InputEvent,
+}
+
+/// Input events and frame times captured by an [InputRecorder].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InputRecording {
+    /// Delta of each recorded frame in seconds, replayed so timing matches.
+    pub frame_times: Vec<f32>,
+    pub events: Vec<RecordedInput>,
+    /// The [Rng] is reseeded with this when recording and playback start.
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+impl InputRecording {
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let data = serde_json::to_string(self)?;
+        std::fs::write(path, data)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    #[inline]
+    pub fn frames(&self) -> u64 {
+        self.frame_times.len() as u64
+    }
+}
+
+//====================================================================
+
+#[derive(Debug, Default)]
+enum RecorderState {
+    #[default]
+    Idle,
+    Recording(InputRecording),
+    Playing {
+        recording: InputRecording,
+        next_event: usize,
+    },
+}
+
+/// Records keyboard and mouse input with the frame it arrived on, or plays a
+/// recording back into the [Input] and [MouseInput] uniques. Live input is
+/// ignored during playback.
+#[derive(Unique, Debug, Default)]
+pub struct InputRecorder {
+    state: RecorderState,
+    // Frames since recording or playback started
+    frame: u64,
+}
+
+impl InputRecorder {
+    pub fn start_recording(&mut self) {
+        log::info!("Started recording input");
+
+        self.state = RecorderState::Recording(InputRecording::default());
+        self.frame = 0;
+    }
+
+    /// Returns the recording if one was in progress.
+    pub fn stop_recording(&mut self) -> Option<InputRecording> {
+        match std::mem::take(&mut self.state) {
+            RecorderState::Recording(recording) => {
+                log::info!(
+                    "Stopped recording input after {} frames",
+                    recording.frames()
+                );
+                Some(recording)
+            }
+            state => {
+                self.state = state;
+                None
+            }
+        }
+    }
+
+    pub fn play(&mut self, recording: InputRecording) {
+        log::info!("Playing back {} frames of input", recording.frames());
+
+        self.state = RecorderState::Playing {
+            recording,
+            next_event: 0,
+        };
+        self.frame = 0;
+    }
+
+    pub fn stop_playback(&mut self) {
+        if self.is_playing() {
+            self.state = RecorderState::Idle;
+        }
+    }
+
+    #[inline]
+    pub fn is_recording(&self) -> bool {
+        matches!(self.state, RecorderState::Recording(_))
+    }
+
+    #[inline]
+    pub fn is_playing(&self) -> bool {
+        matches!(self.state, RecorderState::Playing { .. })
+    }
+
+    /// Frames since recording or playback started.
+    #[inline]
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+}
+
+//====================================================================
+
+/// Entry point for input from the window. Applied straight away unless a
+/// recording is being played back.
+pub(crate) fn sys_live_input(
+    event: InputEvent,
+    mut recorder: ResMut<InputRecorder>,
+    mut keys: ResMut<Input<KeyCode>>,
+    mut buttons: ResMut<Input<MouseButton>>,
+    mut mouse: ResMut<MouseInput>,
+    mut text: ResMut<KeyboardText>,
+    size: Res<WindowSize>,
+) {
+    let frame = recorder.frame;
+
+    match &mut recorder.state {
+        RecorderState::Playing { .. } => return,
+        RecorderState::Recording(recording) => {
+            recording.events.push(RecordedInput { frame, event });
+        }
+        RecorderState::Idle => {}
+    }
+
+    event.apply(&mut keys, &mut buttons, &mut mouse, &mut text, &size);
+}
+
+// Runs before the time and input are ticked, where live input arrives, so played
+// back frames use their recorded delta and presses are timed as they were live
+pub(crate) fn sys_replay_input(
+    mut recorder: ResMut<InputRecorder>,
+    mut time: ResMut<Time>,
+    mut keys: ResMut<Input<KeyCode>>,
+    mut buttons: ResMut<Input<MouseButton>>,
+    mut mouse: ResMut<MouseInput>,
+    mut text: ResMut<KeyboardText>,
+    size: Res<WindowSize>,
+) {
+    let frame = recorder.frame;
+
+    let (recording, next_event) = match &mut recorder.state {
+        RecorderState::Playing {
+            recording,
+            next_event,
+        } => (recording, next_event),
+        _ => return,
+    };
+
+    if let Some(delta) = recording.frame_times.get(frame as usize) {
+        time.set_manual_delta(Duration::from_secs_f32(*delta));
+    }
+
+    while let Some(recorded) = recording.events.get(*next_event) {
+        if recorded.frame > frame {
+            break;
+        }
+
+        recorded
+            .event
+            .apply(&mut keys, &mut buttons, &mut mouse, &mut text, &size);
+        *next_event += 1;
+    }
+}
+
+pub(crate) fn sys_step_replay(
+    time: Res<Time>,
+    mut recorder: ResMut<InputRecorder>,
+    mut rng: ResMut<Rng>,
+) {
+    let frame = recorder.frame;
+    let mut finished = false;
+
+    match &mut recorder.state {
+        RecorderState::Idle => return,
+
+        RecorderState::Recording(recording) => {
+            if frame == 0 {
+                let seed = rng.next_u64();
+                rng.reseed(seed);
+                recording.seed = Some(seed);
+            }
+
+            recording.frame_times.push(time.delta_seconds());
+        }
+
+        RecorderState::Playing { recording, .. } => {
+            if let (0, Some(seed)) = (frame, recording.seed) {
+                rng.reseed(seed);
+            }
+
+            finished = frame + 1 >= recording.frames();
+        }
+    }
+
+    recorder.frame += 1;
+
+    if finished {
+        log::info!("Finished playing back input");
+        recorder.state = RecorderState::Idle;
+    }
+}
+
+//====================================================================
@@ -0,0 +1,115 @@
+//====================================================================
+
+use std::sync::Arc;
+
+use shipyard::Unique;
+
+//====================================================================
+
+/// Insert before adding the [crate::tools::ToolsPlugin] to configure the [TaskPool].
+#[derive(Unique, Debug, Clone, Default)]
+pub struct TaskPoolSettings {
+    /// Number of worker threads. Uses one per logical core when `None`.
+    pub threads: Option<usize>,
+}
+
+//====================================================================
+
+/// Shared worker threads for heavy work systems want off the main thread or
+/// spread over several cores, such as decompressing assets or pathfinding.
+/// Assets can be loaded on it with `AssetStorage::load_file_background`.
+#[derive(Unique, Clone)]
+pub struct TaskPool {
+    pool: Arc<rayon::ThreadPool>,
+}
+
+impl TaskPool {
+    pub fn new(settings: &TaskPoolSettings) -> Self {
+        let mut builder =
+            rayon::ThreadPoolBuilder::new().thread_name(|index| format!("Cabat Worker {}", index));
+
+        if let Some(threads) = settings.threads {
+            builder = builder.num_threads(threads);
+        }
+
+        let pool = builder
+            .build()
+            .expect("Failed to create task pool worker threads");
+
+        log::info!(
+            "Created task pool with {} threads",
+            pool.current_num_threads()
+        );
+
+        Self {
+            pool: Arc::new(pool),
+        }
+    }
+
+    #[inline]
+    pub fn threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    /// Run a task in the background. Use a channel to get results back.
+    #[inline]
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.pool.spawn(task);
+    }
+
+    /// Run two tasks, potentially in parallel, and wait for both.
+    #[inline]
+    pub fn join<A, B, RA, RB>(&self, a: A, b: B) -> (RA, RB)
+    where
+        A: FnOnce() -> RA + Send,
+        B: FnOnce() -> RB + Send,
+        RA: Send,
+        RB: Send,
+    {
+        self.pool.join(a, b)
+    }
+
+    /// Spawn tasks borrowing local data. Returns once every task has finished.
+    #[inline]
+    pub fn scope<'scope, F, R>(&self, op: F) -> R
+    where
+        F: FnOnce(&rayon::Scope<'scope>) -> R + Send,
+        R: Send,
+    {
+        self.pool.scope(op)
+    }
+
+    /// Run a closure inside the pool so rayon parallel iterators use its threads.
+    #[inline]
+    pub fn install<F, R>(&self, op: F) -> R
+    where
+        F: FnOnce() -> R + Send,
+        R: Send,
+    {
+        self.pool.install(op)
+    }
+
+    pub fn par_map<T, R, F>(&self, items: &[T], map: F) -> Vec<R>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&T) -> R + Send + Sync,
+    {
+        use rayon::prelude::*;
+        self.install(|| items.par_iter().map(map).collect())
+    }
+
+    pub fn par_for_each_mut<T, F>(&self, items: &mut [T], op: F)
+    where
+        T: Send,
+        F: Fn(&mut T) + Send + Sync,
+    {
+        use rayon::prelude::*;
+        self.install(|| items.par_iter_mut().for_each(op))
+    }
+}
+
+//====================================================================
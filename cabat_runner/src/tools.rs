@@ -1,14 +1,20 @@
 //====================================================================
 
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     hash::Hash,
     time::{Duration, Instant},
 };
 
 use cabat_common::WindowSize;
 use cabat_shipyard::{prelude::*, UniqueTools};
-use shipyard::{AllStoragesView, Unique};
+use shipyard::{AllStoragesView, Component, IntoIter, IntoWorkload, Unique, ViewMut};
+
+use crate::{
+    clipboard::Clipboard,
+    replay::{self, InputRecorder},
+    task_pool::{TaskPool, TaskPoolSettings},
+};
 
 //====================================================================
 
@@ -20,15 +26,35 @@ pub struct ToolsPlugin;
 
 impl Plugin for ToolsPlugin {
     fn build(self, builder: &WorkloadBuilder) {
+        let task_pool = TaskPool::new(&builder.get_or_insert(TaskPoolSettings::default));
+
+        builder.get_or_insert(Rng::from_entropy);
+
         builder
+            .insert(task_pool)
+            .insert_non_send(Clipboard::default())
             .add_workload(Stages::Setup, sys_setup_uniques)
-            .add_workload(Stages::First, sys_update_time)
+            .add_workload_first(Stages::First, replay::sys_replay_input)
+            .add_workload(
+                Stages::First,
+                (
+                    sys_update_time,
+                    (
+                        sys_tick_timers,
+                        sys_tick_input::<KeyCode>,
+                        sys_tick_input::<MouseButton>,
+                    ),
+                )
+                    .into_sequential_workload(),
+            )
+            .add_workload_post(Stages::First, replay::sys_step_replay)
             .add_workload(
                 Stages::Last,
                 (
                     sys_reset_input::<KeyCode>,
                     sys_reset_input::<MouseButton>,
                     sys_reset_mouse_input,
+                    sys_reset_keyboard_text,
                 ),
             );
     }
@@ -39,7 +65,9 @@ fn sys_setup_uniques(all_storages: AllStoragesView) {
         .insert(Time::default())
         .insert(Input::<KeyCode>::default())
         .insert(Input::<MouseButton>::default())
-        .insert(MouseInput::default());
+        .insert(MouseInput::default())
+        .insert(KeyboardText::default())
+        .insert(InputRecorder::default());
 }
 
 //====================================================================
@@ -51,6 +79,9 @@ pub struct Time {
     last_frame: Instant,
     delta: Duration,
     delta_seconds: f32,
+
+    // Used instead of the measured frame time when stepping frames manually
+    manual_delta: Option<Duration>,
 }
 
 impl Default for Time {
@@ -60,6 +91,8 @@ impl Default for Time {
             last_frame: Instant::now(),
             delta: Duration::ZERO,
             delta_seconds: 0.,
+
+            manual_delta: None,
         }
     }
 }
@@ -80,10 +113,18 @@ impl Time {
     pub fn delta_seconds(&self) -> f32 {
         self.delta_seconds
     }
+
+    #[inline]
+    pub(crate) fn set_manual_delta(&mut self, delta: Duration) {
+        self.manual_delta = Some(delta);
+    }
 }
 
 fn sys_update_time(mut time: ResMut<Time>) {
-    time.delta = time.last_frame.elapsed();
+    time.delta = match time.manual_delta.take() {
+        Some(delta) => delta,
+        None => time.last_frame.elapsed(),
+    };
     time.delta_seconds = time.delta.as_secs_f32();
 
     time.last_frame = Instant::now();
@@ -91,6 +132,456 @@ fn sys_update_time(mut time: ResMut<Time>) {
 
 //====================================================================
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerMode {
+    Once,
+    Repeating,
+}
+
+/// Counts up to a duration. Timer components (and a Timer unique) are ticked
+/// automatically at the start of every frame.
+#[derive(Component, Unique, Debug, Clone)]
+pub struct Timer {
+    duration: Duration,
+    elapsed: Duration,
+    mode: TimerMode,
+    paused: bool,
+
+    finished: bool,
+    times_finished: u32,
+}
+
+impl Timer {
+    pub fn new(duration: Duration, mode: TimerMode) -> Self {
+        Self {
+            duration,
+            elapsed: Duration::ZERO,
+            mode,
+            paused: false,
+
+            finished: false,
+            times_finished: 0,
+        }
+    }
+
+    #[inline]
+    pub fn once(duration: Duration) -> Self {
+        Self::new(duration, TimerMode::Once)
+    }
+
+    #[inline]
+    pub fn repeating(duration: Duration) -> Self {
+        Self::new(duration, TimerMode::Repeating)
+    }
+
+    #[inline]
+    pub fn from_seconds(seconds: f32, mode: TimerMode) -> Self {
+        Self::new(Duration::from_secs_f32(seconds), mode)
+    }
+
+    pub fn tick(&mut self, delta: Duration) {
+        self.times_finished = 0;
+
+        if self.paused || (self.mode == TimerMode::Once && self.finished) {
+            return;
+        }
+
+        self.elapsed += delta;
+
+        if self.elapsed < self.duration {
+            return;
+        }
+
+        self.finished = true;
+
+        match self.mode {
+            TimerMode::Once => {
+                self.elapsed = self.duration;
+                self.times_finished = 1;
+            }
+
+            TimerMode::Repeating if self.duration.is_zero() => {
+                self.elapsed = Duration::ZERO;
+                self.times_finished = 1;
+            }
+
+            TimerMode::Repeating => {
+                let times = self.elapsed.as_nanos() / self.duration.as_nanos();
+
+                self.times_finished = times as u32;
+                self.elapsed -= self.duration * self.times_finished;
+            }
+        }
+    }
+
+    /// Whether the timer has finished at least once. Once timers stay finished until reset.
+    #[inline]
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Whether the timer finished during the last tick.
+    #[inline]
+    pub fn just_finished(&self) -> bool {
+        self.times_finished > 0
+    }
+
+    /// How many times a repeating timer finished during the last tick.
+    #[inline]
+    pub fn times_finished(&self) -> u32 {
+        self.times_finished
+    }
+
+    #[inline]
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    #[inline]
+    pub fn remaining(&self) -> Duration {
+        self.duration.saturating_sub(self.elapsed)
+    }
+
+    #[inline]
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    #[inline]
+    pub fn set_duration(&mut self, duration: Duration) {
+        self.duration = duration;
+    }
+
+    /// Progress towards the duration, from 0 to 1.
+    #[inline]
+    pub fn fraction(&self) -> f32 {
+        match self.duration.is_zero() {
+            true => 1.,
+            false => self.elapsed.as_secs_f32() / self.duration.as_secs_f32(),
+        }
+    }
+
+    #[inline]
+    pub fn mode(&self) -> TimerMode {
+        self.mode
+    }
+
+    #[inline]
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    #[inline]
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    #[inline]
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    #[inline]
+    pub fn reset(&mut self) {
+        self.elapsed = Duration::ZERO;
+        self.finished = false;
+        self.times_finished = 0;
+    }
+}
+
+//--------------------------------------------------
+
+/// Measures time passed since it was created or last reset. Stopwatch components
+/// (and a Stopwatch unique) are ticked automatically at the start of every frame.
+#[derive(Component, Unique, Debug, Clone, Default)]
+pub struct Stopwatch {
+    elapsed: Duration,
+    paused: bool,
+}
+
+impl Stopwatch {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn tick(&mut self, delta: Duration) {
+        if !self.paused {
+            self.elapsed += delta;
+        }
+    }
+
+    #[inline]
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    #[inline]
+    pub fn elapsed_seconds(&self) -> f32 {
+        self.elapsed.as_secs_f32()
+    }
+
+    #[inline]
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    #[inline]
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    #[inline]
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    #[inline]
+    pub fn reset(&mut self) {
+        self.elapsed = Duration::ZERO;
+    }
+}
+
+//--------------------------------------------------
+
+fn sys_tick_timers(
+    time: Res<Time>,
+    mut vm_timer: ViewMut<Timer>,
+    mut vm_stopwatch: ViewMut<Stopwatch>,
+    timer: Option<ResMut<Timer>>,
+    stopwatch: Option<ResMut<Stopwatch>>,
+) {
+    let delta = *time.delta();
+
+    (&mut vm_timer).iter().for_each(|timer| timer.tick(delta));
+
+    (&mut vm_stopwatch)
+        .iter()
+        .for_each(|stopwatch| stopwatch.tick(delta));
+
+    if let Some(mut timer) = timer {
+        timer.tick(delta);
+    }
+
+    if let Some(mut stopwatch) = stopwatch {
+        stopwatch.tick(delta);
+    }
+}
+
+//====================================================================
+
+/// Seedable random source shared by gameplay systems. Seeded from the system
+/// clock unless inserted with [Rng::new] before the [ToolsPlugin], and
+/// reseeded when an input recording starts or plays back so replays roll the
+/// same numbers.
+///
+/// Dereferences to its main [RngStream]. Systems running in parallel should
+/// each use their own [Rng::stream] rather than sharing the main one, so the
+/// results don't depend on which thread ran first.
+#[derive(Unique, Debug, Clone)]
+pub struct Rng {
+    seed: u64,
+    main: RngStream,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            main: RngStream::new(seed, 0),
+        }
+    }
+
+    /// Seeded from the system clock, for when runs don't need to repeat.
+    pub fn from_entropy() -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64;
+
+        Self::new(splitmix64(nanos))
+    }
+
+    #[inline]
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Start over from a new seed, resetting the main stream.
+    pub fn reseed(&mut self, seed: u64) {
+        log::debug!("Reseeding rng with {}", seed);
+        *self = Self::new(seed);
+    }
+
+    /// Independent stream for the given index, such as a thread, chunk or
+    /// entity index. Always the same sequence for the same seed and index.
+    #[inline]
+    pub fn stream(&self, index: u64) -> RngStream {
+        RngStream::new(self.seed, index.wrapping_add(1))
+    }
+
+    /// Independent stream split off the main one, advancing it.
+    #[inline]
+    pub fn fork(&mut self) -> RngStream {
+        let seed = self.main.next_u64();
+        let stream = self.main.next_u64();
+        RngStream::new(seed, stream)
+    }
+}
+
+impl std::ops::Deref for Rng {
+    type Target = RngStream;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.main
+    }
+}
+
+impl std::ops::DerefMut for Rng {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.main
+    }
+}
+
+//--------------------------------------------------
+
+/// PCG32 generator. Streams created from the same seed with different
+/// indices produce unrelated sequences.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RngStream {
+    state: u64,
+    increment: u64,
+}
+
+impl RngStream {
+    const MULTIPLIER: u64 = 6364136223846793005;
+
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            // Must be odd
+            increment: (stream << 1) | 1,
+        };
+
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(splitmix64(seed));
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old
+            .wrapping_mul(Self::MULTIPLIER)
+            .wrapping_add(self.increment);
+
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rotation = (old >> 59) as u32;
+        xorshifted.rotate_right(rotation)
+    }
+
+    #[inline]
+    pub fn next_u64(&mut self) -> u64 {
+        ((self.next_u32() as u64) << 32) | self.next_u32() as u64
+    }
+
+    /// Between 0 inclusive and 1 exclusive.
+    #[inline]
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    /// Returns `true` with the given probability between 0 and 1.
+    #[inline]
+    pub fn chance(&mut self, probability: f32) -> bool {
+        self.next_f32() < probability
+    }
+
+    #[inline]
+    pub fn range_f32(&mut self, range: std::ops::Range<f32>) -> f32 {
+        range.start + (range.end - range.start) * self.next_f32()
+    }
+
+    /// Returns `range.start` if the range is empty.
+    pub fn range_i32(&mut self, range: std::ops::Range<i32>) -> i32 {
+        if range.is_empty() {
+            return range.start;
+        }
+
+        let span = range.end.wrapping_sub(range.start) as u32;
+        range.start.wrapping_add(self.bounded(span) as i32)
+    }
+
+    /// Random index below `len`, or 0 if `len` is 0.
+    #[inline]
+    pub fn index(&mut self, len: usize) -> usize {
+        self.bounded(len.min(u32::MAX as usize) as u32) as usize
+    }
+
+    #[inline]
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        match items.is_empty() {
+            true => None,
+            false => items.get(self.index(items.len())),
+        }
+    }
+
+    /// Fisher-Yates shuffle.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        (1..items.len()).rev().for_each(|index| {
+            let other = self.index(index + 1);
+            items.swap(index, other);
+        });
+    }
+
+    // Below `bound` without modulo bias
+    fn bounded(&mut self, bound: u32) -> u32 {
+        if bound == 0 {
+            return 0;
+        }
+
+        let threshold = bound.wrapping_neg() % bound;
+        loop {
+            let value = self.next_u32();
+            if value >= threshold {
+                return value % bound;
+            }
+        }
+    }
+}
+
+#[inline]
+fn splitmix64(value: u64) -> u64 {
+    let mut value = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    value ^ (value >> 31)
+}
+
+//====================================================================
+
+/// Order systems get to handle input in, earliest first. Earlier layers can
+/// [Input::consume] inputs so later layers don't react to them as well, such
+/// as gameplay ignoring clicks on the ui. Layers are compared by their value,
+/// so custom layers can be placed between the built in ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InputLayer(pub u8);
+
+impl InputLayer {
+    pub const CONSOLE: Self = Self(0);
+    pub const UI: Self = Self(64);
+    pub const GAME: Self = Self(128);
+}
+
+/// Inputs consumed by a layer stay hidden from later layers until they are
+/// released. Queries directly on the input see everything, use
+/// [Input::layer] to only see what a layer should react to.
+///
+/// Hold durations and double taps are measured in frame time, so they follow
+/// [Time] and replays.
 #[derive(Unique, Debug)]
 pub struct Input<T>
 where
@@ -99,6 +590,16 @@ where
     pressed: HashSet<T>,
     just_pressed: HashSet<T>,
     released: HashSet<T>,
+    consumed: HashMap<T, InputLayer>,
+
+    // Frame time since the input was created, and at the previous frame
+    now: Duration,
+    previous: Duration,
+    pressed_at: HashMap<T, Duration>,
+    // Presses that could still be the first of a double tap
+    last_tap: HashMap<T, Duration>,
+    double_tapped: HashSet<T>,
+    double_tap_window: Duration,
 }
 
 impl<T> Default for Input<T>
@@ -110,6 +611,14 @@ where
             pressed: HashSet::new(),
             just_pressed: HashSet::new(),
             released: HashSet::new(),
+            consumed: HashMap::new(),
+
+            now: Duration::ZERO,
+            previous: Duration::ZERO,
+            pressed_at: HashMap::new(),
+            last_tap: HashMap::new(),
+            double_tapped: HashSet::new(),
+            double_tap_window: Self::DEFAULT_DOUBLE_TAP_WINDOW,
         }
     }
 }
@@ -119,19 +628,54 @@ impl<T> Input<T>
 where
     T: 'static + Send + Sync + Eq + PartialEq + Hash + Clone + Copy,
 {
+    pub const DEFAULT_DOUBLE_TAP_WINDOW: Duration = Duration::from_millis(300);
+
     fn add_pressed(&mut self, input: T) {
-        self.pressed.insert(input);
+        // Key repeats arrive as presses while already held
+        if self.pressed.insert(input) {
+            self.pressed_at.insert(input, self.now);
+
+            match self.last_tap.remove(&input) {
+                Some(last) if self.now - last <= self.double_tap_window => {
+                    self.double_tapped.insert(input);
+                }
+                _ => {
+                    self.last_tap.insert(input, self.now);
+                }
+            }
+        }
+
         self.just_pressed.insert(input);
     }
 
     fn remove_pressed(&mut self, input: T) {
         self.pressed.remove(&input);
+        self.pressed_at.remove(&input);
         self.released.insert(input);
     }
 
+    fn tick(&mut self, delta: Duration) {
+        self.previous = self.now;
+        self.now += delta;
+
+        let (now, window) = (self.now, self.double_tap_window);
+        self.last_tap.retain(|_, last| now - *last <= window);
+    }
+
     fn reset(&mut self) {
         self.just_pressed.clear();
         self.released.clear();
+        self.double_tapped.clear();
+
+        let pressed = &self.pressed;
+        self.consumed.retain(|input, _| pressed.contains(input));
+    }
+
+    pub(crate) fn process(&mut self, input: T, pressed: bool) {
+        match pressed {
+            true => self.add_pressed(input),
+            false => self.remove_pressed(input),
+        }
     }
 
     #[inline]
@@ -148,18 +692,204 @@ where
     pub fn _released(&self, input: T) -> bool {
         self.released.contains(&input)
     }
+
+    #[inline]
+    pub fn any_pressed(&self, inputs: &[T]) -> bool {
+        inputs.iter().any(|input| self.pressed(*input))
+    }
+
+    #[inline]
+    pub fn all_pressed(&self, inputs: &[T]) -> bool {
+        inputs.iter().all(|input| self.pressed(*input))
+    }
+
+    /// Whether `input` was just pressed while all of `held` are down, such as
+    /// the S of Ctrl+S.
+    #[inline]
+    pub fn chord(&self, held: &[T], input: T) -> bool {
+        self.just_pressed(input) && self.all_pressed(held)
+    }
+
+    /// How long the input has been held, or `None` if it isn't.
+    #[inline]
+    pub fn held_for(&self, input: T) -> Option<Duration> {
+        self.pressed_at
+            .get(&input)
+            .map(|pressed_at| self.now - *pressed_at)
+    }
+
+    /// Whether the input has been held for at least `duration` as of this
+    /// frame, but not the last one. True once per hold.
+    pub fn just_held_for(&self, input: T, duration: Duration) -> bool {
+        match self.pressed_at.get(&input) {
+            Some(pressed_at) => {
+                self.now - *pressed_at >= duration && self.previous < *pressed_at + duration
+            }
+            None => false,
+        }
+    }
+
+    /// Pressed this frame within the double tap window of the last press.
+    /// A third tap starts a new pair.
+    #[inline]
+    pub fn double_tapped(&self, input: T) -> bool {
+        self.double_tapped.contains(&input)
+    }
+
+    #[inline]
+    pub fn double_tap_window(&self) -> Duration {
+        self.double_tap_window
+    }
+
+    #[inline]
+    pub fn set_double_tap_window(&mut self, window: Duration) {
+        self.double_tap_window = window;
+    }
+
+    /// Hide an input from layers after this one until it is released.
+    pub fn consume(&mut self, input: T, layer: InputLayer) {
+        self.consumed
+            .entry(input)
+            .and_modify(|consumed| *consumed = (*consumed).min(layer))
+            .or_insert(layer);
+    }
+
+    /// Consume every input that is held or was released this frame, such as
+    /// while a text field has focus.
+    pub fn consume_all(&mut self, layer: InputLayer) {
+        let inputs = self
+            .pressed
+            .iter()
+            .chain(self.released.iter())
+            .copied()
+            .collect::<Vec<_>>();
+
+        inputs
+            .into_iter()
+            .for_each(|input| self.consume(input, layer));
+    }
+
+    /// Earliest layer that consumed the input, if any.
+    #[inline]
+    pub fn consumed_by(&self, input: T) -> Option<InputLayer> {
+        self.consumed.get(&input).copied()
+    }
+
+    /// Queries that ignore inputs consumed by earlier layers.
+    #[inline]
+    pub fn layer(&self, layer: InputLayer) -> LayerInput<'_, T> {
+        LayerInput { input: self, layer }
+    }
 }
 
-pub fn sys_process_input<T>(input_data: (T, bool), mut input: ResMut<Input<T>>)
+/// View of an [Input] from one [InputLayer], see [Input::layer].
+pub struct LayerInput<'a, T>
+where
+    T: 'static + Send + Sync + Eq + PartialEq + Hash + Clone + Copy,
+{
+    input: &'a Input<T>,
+    layer: InputLayer,
+}
+
+impl<'a, T> LayerInput<'a, T>
 where
     T: 'static + Send + Sync + Eq + PartialEq + Hash + Clone + Copy,
 {
-    match input_data.1 {
-        true => input.add_pressed(input_data.0),
-        false => input.remove_pressed(input_data.0),
+    #[inline]
+    fn visible(&self, input: T) -> bool {
+        self.input
+            .consumed_by(input)
+            .map_or(true, |consumed| consumed >= self.layer)
+    }
+
+    #[inline]
+    pub fn pressed(&self, input: T) -> bool {
+        self.input.pressed(input) && self.visible(input)
+    }
+
+    #[inline]
+    pub fn just_pressed(&self, input: T) -> bool {
+        self.input.just_pressed(input) && self.visible(input)
+    }
+
+    #[inline]
+    pub fn released(&self, input: T) -> bool {
+        self.input._released(input) && self.visible(input)
+    }
+
+    #[inline]
+    pub fn chord(&self, held: &[T], input: T) -> bool {
+        self.just_pressed(input) && held.iter().all(|held| self.pressed(*held))
+    }
+
+    #[inline]
+    pub fn held_for(&self, input: T) -> Option<Duration> {
+        self.input.held_for(input).filter(|_| self.visible(input))
+    }
+
+    #[inline]
+    pub fn just_held_for(&self, input: T, duration: Duration) -> bool {
+        self.input.just_held_for(input, duration) && self.visible(input)
+    }
+
+    #[inline]
+    pub fn double_tapped(&self, input: T) -> bool {
+        self.input.double_tapped(input) && self.visible(input)
     }
 }
 
+//--------------------------------------------------
+
+/// Modifier keys, matching either the left or right key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Modifier {
+    Ctrl,
+    Shift,
+    Alt,
+    Super,
+}
+
+impl Modifier {
+    #[inline]
+    pub fn keys(&self) -> [KeyCode; 2] {
+        match self {
+            Modifier::Ctrl => [KeyCode::ControlLeft, KeyCode::ControlRight],
+            Modifier::Shift => [KeyCode::ShiftLeft, KeyCode::ShiftRight],
+            Modifier::Alt => [KeyCode::AltLeft, KeyCode::AltRight],
+            Modifier::Super => [KeyCode::SuperLeft, KeyCode::SuperRight],
+        }
+    }
+}
+
+impl Input<KeyCode> {
+    #[inline]
+    pub fn modifier(&self, modifier: Modifier) -> bool {
+        self.any_pressed(&modifier.keys())
+    }
+
+    /// Whether `key` was just pressed while all of the modifiers are held,
+    /// such as `shortcut(&[Modifier::Ctrl], KeyCode::KeyS)`. Other modifiers
+    /// being held as well doesn't matter.
+    #[inline]
+    pub fn shortcut(&self, modifiers: &[Modifier], key: KeyCode) -> bool {
+        self.just_pressed(key) && modifiers.iter().all(|modifier| self.modifier(*modifier))
+    }
+}
+
+pub fn sys_process_input<T>(input_data: (T, bool), mut input: ResMut<Input<T>>)
+where
+    T: 'static + Send + Sync + Eq + PartialEq + Hash + Clone + Copy,
+{
+    input.process(input_data.0, input_data.1);
+}
+
+fn sys_tick_input<T>(time: Res<Time>, mut input: ResMut<Input<T>>)
+where
+    T: 'static + Send + Sync + Eq + PartialEq + Hash + Clone + Copy,
+{
+    input.tick(*time.delta());
+}
+
 fn sys_reset_input<T>(mut input: ResMut<Input<T>>)
 where
     T: 'static + Send + Sync + Eq + PartialEq + Hash + Clone + Copy,
@@ -174,6 +904,7 @@ pub struct MouseInput {
     pos: glam::Vec2,
     screen_pos: glam::Vec2,
     pos_delta: glam::Vec2,
+    motion: glam::Vec2,
     scroll: glam::Vec2,
 }
 
@@ -184,7 +915,7 @@ impl MouseInput {
     }
 
     #[inline]
-    pub fn _pos(&self) -> glam::Vec2 {
+    pub fn pos(&self) -> glam::Vec2 {
         self.pos
     }
 
@@ -192,20 +923,98 @@ impl MouseInput {
     pub fn screen_pos(&self) -> glam::Vec2 {
         self.screen_pos
     }
+
+    /// Raw mouse movement since the last frame, unaffected by the cursor
+    /// reaching the edge of the window or screen. Only collected while the
+    /// window is focused.
+    #[inline]
+    pub fn motion(&self) -> glam::Vec2 {
+        self.motion
+    }
+}
+
+impl MouseInput {
+    pub(crate) fn add_scroll(&mut self, wheel: [f32; 2]) {
+        self.scroll += glam::Vec2::from(wheel);
+    }
+
+    pub(crate) fn add_motion(&mut self, motion: [f32; 2]) {
+        self.motion += glam::Vec2::from(motion);
+    }
+
+    pub(crate) fn set_pos(&mut self, pos: [f32; 2], size: &WindowSize) {
+        self.pos = pos.into();
+        self.screen_pos = glam::vec2(self.pos.x, size.height_f32() - self.pos.y as f32);
+    }
 }
 
 pub fn sys_process_wheel(wheel: [f32; 2], mut mouse: ResMut<MouseInput>) {
-    mouse.scroll += glam::Vec2::from(wheel);
+    mouse.add_scroll(wheel);
 }
 
 pub fn sys_process_mouse_pos(pos: [f32; 2], mut mouse: ResMut<MouseInput>, size: Res<WindowSize>) {
-    mouse.pos = pos.into();
-    mouse.screen_pos = glam::vec2(mouse.pos.x, size.height_f32() - mouse.pos.y as f32);
+    mouse.set_pos(pos, &size);
 }
 
 fn sys_reset_mouse_input(mut mouse: ResMut<MouseInput>) {
     mouse.pos_delta = glam::Vec2::ZERO;
+    mouse.motion = glam::Vec2::ZERO;
     mouse.scroll = glam::Vec2::ZERO;
 }
 
 //====================================================================
+
+/// Text typed since the last frame, after the keyboard layout and any input
+/// method have been applied. Meant for text fields - controls should use
+/// [Input<KeyCode>] instead.
+#[derive(Unique, Debug, Default)]
+pub struct KeyboardText {
+    text: String,
+    preedit: Option<ImePreedit>,
+}
+
+/// Text an input method is still composing, not yet committed.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ImePreedit {
+    pub text: String,
+    /// Byte range of the input method's cursor within the text.
+    pub cursor: Option<(usize, usize)>,
+}
+
+impl KeyboardText {
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Composition in progress. Kept across frames until the input method
+    /// commits or cancels it.
+    #[inline]
+    pub fn preedit(&self) -> Option<&ImePreedit> {
+        self.preedit.as_ref()
+    }
+}
+
+impl KeyboardText {
+    // Control characters (enter, backspace, tab...) are left to Input<KeyCode>
+    pub(crate) fn push(&mut self, c: char) {
+        if !c.is_control() {
+            self.text.push(c);
+        }
+    }
+}
+
+pub fn sys_process_ime_preedit(preedit: Option<ImePreedit>, mut text: ResMut<KeyboardText>) {
+    text.preedit = preedit;
+}
+
+fn sys_reset_keyboard_text(mut text: ResMut<KeyboardText>) {
+    text.text.clear();
+}
+
+//====================================================================
@@ -2,9 +2,14 @@
 
 use std::sync::Arc;
 
-use cabat_common::{Size, WindowRaw, WindowResizeEvent, WindowSize};
-use cabat_shipyard::{EventHandler, ResMut, UniqueTools};
+use cabat_common::{
+    ScaleFactorChangedEvent, Size, WindowRaw, WindowResizeEvent, WindowScale, WindowSize,
+};
+use cabat_shipyard::{EventHandler, Res, ResMut, UniqueTools};
 use shipyard::{AllStoragesView, Unique};
+use winit::window::{CursorGrabMode, Fullscreen};
+
+use crate::monitor::{MonitorInfo, Monitors, VideoMode};
 
 //====================================================================
 
@@ -21,7 +26,141 @@ impl Window {
         self.0.request_redraw();
     }
 
-    // TODO - Window manipulation stuff here
+    /// Input methods are off by default. Enable them while a text field has focus
+    /// so composed text arrives through [crate::tools::KeyboardText].
+    #[inline]
+    pub fn set_ime_allowed(&self, allowed: bool) {
+        self.0.set_ime_allowed(allowed);
+    }
+
+    /// Area being typed into, in physical pixels, so the input method can place
+    /// its candidate window next to it.
+    #[inline]
+    pub fn set_ime_cursor_area(&self, x: f32, y: f32, width: f32, height: f32) {
+        self.0.set_ime_cursor_area(
+            winit::dpi::PhysicalPosition::new(x, y),
+            winit::dpi::PhysicalSize::new(width, height),
+        );
+    }
+
+    /// Move the top left corner of the window, in physical desktop coordinates.
+    #[inline]
+    pub fn set_position(&self, position: [i32; 2]) {
+        self.0
+            .set_outer_position(winit::dpi::PhysicalPosition::new(position[0], position[1]));
+    }
+
+    /// Centre the window on a monitor.
+    pub fn move_to_monitor(&self, monitor: &MonitorInfo) {
+        let size = self.0.outer_size();
+
+        let x = monitor.position[0] + (monitor.size.width as i32 - size.width as i32) / 2;
+        let y = monitor.position[1] + (monitor.size.height as i32 - size.height as i32) / 2;
+
+        self.set_position([x, y]);
+    }
+
+    /// Borderless fullscreen on the given monitor, or the current one if `None`.
+    #[inline]
+    pub fn set_borderless_fullscreen(&self, monitor: Option<&MonitorInfo>) {
+        self.0.set_fullscreen(Some(Fullscreen::Borderless(
+            monitor.map(|monitor| monitor.handle().clone()),
+        )));
+    }
+
+    /// Exclusive fullscreen using one of a monitor's [MonitorInfo::video_modes].
+    #[inline]
+    pub fn set_exclusive_fullscreen(&self, mode: &VideoMode) {
+        self.0
+            .set_fullscreen(Some(Fullscreen::Exclusive(mode.handle().clone())));
+    }
+
+    #[inline]
+    pub fn set_windowed(&self) {
+        self.0.set_fullscreen(None);
+    }
+
+    #[inline]
+    pub fn is_fullscreen(&self) -> bool {
+        self.0.fullscreen().is_some()
+    }
+}
+
+//--------------------------------------------------
+
+/// Whether the window has keyboard focus and is visible on screen.
+#[derive(Unique, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowFocus {
+    pub focused: bool,
+    pub occluded: bool,
+}
+
+impl WindowFocus {
+    /// Unfocused or hidden behind other windows.
+    #[inline]
+    pub fn in_background(&self) -> bool {
+        !self.focused || self.occluded
+    }
+}
+
+/// How often frames are run while the window is in the background, see
+/// [WindowFocus::in_background]. Insert before running the app to change it.
+#[derive(Unique, Debug, Clone, Copy, PartialEq, Default)]
+pub enum BackgroundPolicy {
+    /// Keep running at the normal rate.
+    #[default]
+    Continue,
+    /// Run at most this many frames per second.
+    Throttle(f32),
+    /// Stop running frames until the window is in the foreground again.
+    Pause,
+}
+
+//--------------------------------------------------
+
+/// How the mouse cursor behaves over the window. Change it at any time and it
+/// is applied at the start of the next frame. The cursor is always released
+/// while the window is unfocused and the mode is restored once focus returns.
+///
+/// Raw mouse movement is available from
+/// [MouseInput::motion](crate::tools::MouseInput::motion) in every mode.
+#[derive(Unique, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MouseMode {
+    /// Visible and free to leave the window.
+    #[default]
+    Absolute,
+    /// Visible but kept inside the window.
+    Confined,
+    /// Hidden and held in place, for mouse look. Falls back to confining the
+    /// cursor on platforms that can't lock it.
+    Relative,
+}
+
+// Mode last applied to the window, which is Absolute while unfocused
+#[derive(Unique, Debug, Default)]
+pub(crate) struct AppliedMouseMode(MouseMode);
+
+impl Window {
+    fn apply_mouse_mode(&self, mode: MouseMode) {
+        // Platforms support either confining or locking, so try the other if one fails
+        let grab = match mode {
+            MouseMode::Absolute => self.0.set_cursor_grab(CursorGrabMode::None),
+            MouseMode::Confined => self
+                .0
+                .set_cursor_grab(CursorGrabMode::Confined)
+                .or_else(|_| self.0.set_cursor_grab(CursorGrabMode::Locked)),
+            MouseMode::Relative => self
+                .0
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| self.0.set_cursor_grab(CursorGrabMode::Confined)),
+        };
+
+        if let Err(e) = grab {
+            log::warn!("Unable to set cursor grab for {:?}: {}", mode, e);
+        }
+
+        self.0.set_cursor_visible(mode != MouseMode::Relative);
+    }
 }
 
 //====================================================================
@@ -31,8 +170,18 @@ pub fn sys_add_window(window: Arc<winit::window::Window>, all_storages: AllStora
 
     all_storages
         .insert(WindowSize::new(size))
+        .insert(WindowScale::new(window.scale_factor() as f32))
+        .insert(WindowFocus {
+            focused: window.has_focus(),
+            occluded: false,
+        })
+        .insert(Monitors::query(&window))
         .insert(Window(window.clone()))
         .insert(WindowRaw::new(window.clone(), size));
+
+    all_storages.get_or_insert(BackgroundPolicy::default);
+    all_storages.get_or_insert(MouseMode::default);
+    all_storages.insert(AppliedMouseMode::default());
 }
 
 pub fn sys_resize(
@@ -45,4 +194,43 @@ pub fn sys_resize(
     event_handler.add_event(WindowResizeEvent::new(new_size));
 }
 
+pub fn sys_rescale(
+    scale_factor: f32,
+    mut scale: ResMut<WindowScale>,
+    mut event_handler: ResMut<EventHandler>,
+) {
+    *scale = WindowScale::new(scale_factor);
+
+    event_handler.add_event(ScaleFactorChangedEvent::new(scale_factor));
+}
+
+pub fn sys_set_focused(focused: bool, mut focus: ResMut<WindowFocus>) {
+    focus.focused = focused;
+}
+
+pub fn sys_set_occluded(occluded: bool, mut focus: ResMut<WindowFocus>) {
+    focus.occluded = occluded;
+}
+
+pub(crate) fn sys_apply_mouse_mode(
+    window: Res<Window>,
+    mode: Res<MouseMode>,
+    focus: Res<WindowFocus>,
+    mut applied: ResMut<AppliedMouseMode>,
+) {
+    let target = match focus.focused {
+        true => *mode,
+        false => MouseMode::Absolute,
+    };
+
+    if applied.0 == target {
+        return;
+    }
+
+    log::debug!("Changing mouse mode from {:?} to {:?}", applied.0, target);
+
+    window.apply_mouse_mode(target);
+    applied.0 = target;
+}
+
 //====================================================================
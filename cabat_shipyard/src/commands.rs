@@ -0,0 +1,91 @@
+//====================================================================
+
+use shipyard::{AllStorages, AllStoragesViewMut, EntityId, TupleAddComponent, TupleDelete, Unique};
+
+use crate::ResMut;
+
+//====================================================================
+
+type Command = Box<dyn FnOnce(&mut AllStorages) + Send + Sync>;
+
+/// Queue of world changes applied once the current stage finishes. Lets systems
+/// spawn and modify entities without borrowing every storage involved.
+#[derive(Unique, Default)]
+pub struct Commands {
+    queue: Vec<Command>,
+}
+
+impl Commands {
+    #[inline]
+    pub fn add<F>(&mut self, command: F) -> &mut Self
+    where
+        F: FnOnce(&mut AllStorages) + Send + Sync + 'static,
+    {
+        self.queue.push(Box::new(command));
+        self
+    }
+
+    #[inline]
+    pub fn spawn<C>(&mut self, components: C) -> &mut Self
+    where
+        C: TupleAddComponent + Send + Sync + 'static,
+    {
+        self.add(move |all_storages| {
+            all_storages.add_entity(components);
+        })
+    }
+
+    #[inline]
+    pub fn despawn(&mut self, entity: EntityId) -> &mut Self {
+        self.add(move |all_storages| {
+            all_storages.delete_entity(entity);
+        })
+    }
+
+    #[inline]
+    pub fn insert<C>(&mut self, entity: EntityId, components: C) -> &mut Self
+    where
+        C: TupleAddComponent + Send + Sync + 'static,
+    {
+        self.add(move |all_storages| {
+            all_storages.add_component(entity, components);
+        })
+    }
+
+    #[inline]
+    pub fn remove<C: TupleDelete + 'static>(&mut self, entity: EntityId) -> &mut Self {
+        self.add(move |all_storages| {
+            all_storages.delete_component::<C>(entity);
+        })
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+//====================================================================
+
+/// Apply every queued command. Called by the runner after each stage.
+pub fn apply_commands(world: &shipyard::World) {
+    crate::trace_span!("apply_commands");
+
+    world.run(|mut all_storages: AllStoragesViewMut| {
+        let queue = match all_storages.borrow::<ResMut<Commands>>() {
+            Ok(mut commands) => std::mem::take(&mut commands.queue),
+            Err(_) => return,
+        };
+
+        queue
+            .into_iter()
+            .for_each(|command| command(&mut all_storages));
+    });
+}
+
+//====================================================================
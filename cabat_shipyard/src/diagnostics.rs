@@ -0,0 +1,248 @@
+//====================================================================
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use shipyard::{AllStoragesView, StorageId, Unique};
+
+use crate::Stages;
+
+//====================================================================
+
+/// A unique borrowed by a system that runs after setup.
+#[derive(Debug, Clone)]
+pub struct UniqueRequirement {
+    /// Storage name of the unique, as recorded by shipyard.
+    pub unique: String,
+    pub system: String,
+    pub workload: String,
+    /// Borrowed from an event or state workload, which may never run.
+    pub gated: bool,
+    storage_id: StorageId,
+}
+
+/// Uniques borrowed by each system, collected when the [crate::WorkloadBuilder]
+/// is built. Checked once setup has run with [report_missing_uniques].
+#[derive(Unique, Debug, Default)]
+pub struct UniqueRequirements(Vec<UniqueRequirement>);
+
+impl UniqueRequirements {
+    pub(crate) fn collect(
+        world: &shipyard::World,
+        workload_names: &HashMap<String, String>,
+        gated_workloads: &HashSet<String>,
+        transient: &HashSet<StorageId>,
+    ) -> Self {
+        let setup = format!("{:?}", Stages::Setup);
+        let mut requirements = Vec::new();
+
+        for (name, workload_info) in world.workloads_info().0.iter() {
+            // Setup is where uniques get inserted
+            if *name == setup {
+                continue;
+            }
+
+            let workload = workload_names.get(name).unwrap_or(name);
+            let gated = gated_workloads.contains(name);
+
+            for batch_info in workload_info.batch_info.iter() {
+                for system in batch_info.systems() {
+                    for type_info in system.borrow.iter() {
+                        if transient.contains(&type_info.storage_id) || !is_unique(&type_info.name)
+                        {
+                            continue;
+                        }
+
+                        requirements.push(UniqueRequirement {
+                            unique: type_info.name.to_string(),
+                            system: system.name.clone(),
+                            workload: workload.clone(),
+                            gated,
+                            storage_id: type_info.storage_id,
+                        });
+                    }
+                }
+            }
+        }
+
+        Self(requirements)
+    }
+
+    #[inline]
+    pub fn requirements(&self) -> &[UniqueRequirement] {
+        &self.0
+    }
+
+    /// Requirements whose unique doesn't currently exist in the world.
+    pub fn missing<'a>(&'a self, all_storages: &'a AllStoragesView) -> Vec<&'a UniqueRequirement> {
+        self.0
+            .iter()
+            .filter(|requirement| {
+                all_storages
+                    .custom_storage_by_id(requirement.storage_id)
+                    .is_err()
+            })
+            .collect()
+    }
+}
+
+// Shipyard records unique borrows, optional or not, as their storage -
+// "shipyard::unique::UniqueStorage<T>"
+fn is_unique(storage_name: &str) -> bool {
+    storage_name.contains("UniqueStorage<")
+}
+
+//--------------------------------------------------
+
+/// Log a warning for every unique that a system borrows but that hasn't been
+/// inserted once setup has run. Returns false if any are missing.
+///
+/// Shipyard records borrows through an `Option` the same as any other, so
+/// these can't be told apart from systems that will panic and are only
+/// warned about. Those borrowed by event or state workloads are listed
+/// separately, as they may be inserted before the workload first runs.
+///
+/// Run by the runners once setup has finished, so missing plugins are reported
+/// before the first frame panics.
+pub fn report_missing_uniques(world: &shipyard::World) -> bool {
+    let requirements = match world.get_unique::<&UniqueRequirements>() {
+        Ok(requirements) => requirements,
+        Err(_) => return true,
+    };
+
+    let all_storages = world.borrow::<AllStoragesView>().unwrap();
+    let missing = requirements.missing(&all_storages);
+
+    if missing.is_empty() {
+        return true;
+    }
+
+    let (gated, required) = missing
+        .into_iter()
+        .partition::<Vec<_>, _>(|requirement| requirement.gated);
+
+    group_by_unique(required)
+        .into_iter()
+        .for_each(|(unique, systems)| {
+            log::warn!(
+                "Unique '{}' isn't inserted after setup but is borrowed by:{}\n\
+                Unless they borrow it as optional, add the plugin that provides it or insert it while building the app.",
+                unique,
+                systems
+            );
+        });
+
+    group_by_unique(gated)
+        .into_iter()
+        .for_each(|(unique, systems)| {
+            log::warn!(
+                "Unique '{}' isn't inserted yet but is borrowed by event or state workloads:{}\n\
+                Unless they borrow it as optional, they will panic if they run before it is added.",
+                unique,
+                systems
+            );
+        });
+
+    false
+}
+
+// Unique names with a line listing each system borrowing them
+fn group_by_unique(requirements: Vec<&UniqueRequirement>) -> BTreeMap<&str, String> {
+    requirements
+        .into_iter()
+        .fold(BTreeMap::new(), |mut by_unique, requirement| {
+            by_unique
+                .entry(requirement.unique.as_str())
+                .or_default()
+                .push_str(&format!(
+                    "\n\t{} ({})",
+                    requirement.system, requirement.workload
+                ));
+            by_unique
+        })
+}
+
+//====================================================================
+
+#[cfg(test)]
+mod tests {
+    use shipyard::IntoWorkload;
+
+    use super::*;
+    use crate::{Event, Res, ResMut, UniqueTools, WorkloadBuilder};
+
+    #[derive(Unique)]
+    struct Missing;
+
+    struct TestEvent;
+    impl Event for TestEvent {}
+
+    fn sys_required(_missing: Res<Missing>) {}
+    fn sys_required_mut(_missing: ResMut<Missing>) {}
+
+    fn build(add: impl FnOnce(&WorkloadBuilder)) -> shipyard::World {
+        let world = shipyard::World::new();
+        let builder = WorkloadBuilder::new(&world);
+        add(&builder);
+        builder.build();
+        world
+    }
+
+    #[test]
+    fn missing_borrow_is_reported() {
+        let world = build(|builder| {
+            builder.add_workload(Stages::Update, sys_required);
+        });
+
+        assert!(!report_missing_uniques(&world));
+    }
+
+    #[test]
+    fn borrow_in_sequential_workload_is_reported() {
+        let world = build(|builder| {
+            builder.add_workload(
+                Stages::Update,
+                (sys_required, sys_required_mut).into_sequential_workload(),
+            );
+        });
+
+        assert!(!report_missing_uniques(&world));
+    }
+
+    #[test]
+    fn inserted_unique_isnt_reported() {
+        let world = build(|builder| {
+            builder
+                .insert(Missing)
+                .add_workload(Stages::Update, sys_required);
+        });
+
+        assert!(report_missing_uniques(&world));
+    }
+
+    #[test]
+    fn transient_unique_isnt_reported() {
+        let world = build(|builder| {
+            builder
+                .transient_unique::<Missing>()
+                .add_workload(Stages::Update, sys_required);
+        });
+
+        assert!(report_missing_uniques(&world));
+    }
+
+    #[test]
+    fn event_borrow_is_gated() {
+        let world = build(|builder| {
+            builder.add_event::<TestEvent>(sys_required.into_workload());
+        });
+
+        let requirements = world.get_unique::<&UniqueRequirements>().unwrap();
+        assert!(!requirements.requirements().is_empty());
+        assert!(requirements
+            .requirements()
+            .iter()
+            .all(|requirement| requirement.gated));
+    }
+}
+
+//====================================================================
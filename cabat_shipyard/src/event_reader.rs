@@ -0,0 +1,196 @@
+//====================================================================
+
+use std::collections::VecDeque;
+
+use shipyard::{
+    atomic_refcell::SharedBorrow,
+    borrow::{Borrow, BorrowInfo},
+    info::TypeInfo,
+    AllStorages, TrackingTimestamp, Unique, UniqueView, UniqueViewMut,
+};
+
+use crate::{Event, GetWorld, WorkloadBuilder};
+
+//====================================================================
+
+const DEFAULT_CAPACITY: usize = 256;
+
+/// Ring buffer of events of type `E`. Unlike events sent through the
+/// [crate::EventHandler], these are polled by regular systems using an
+/// [EventReader], which only yields events sent since that system last ran.
+#[derive(Unique)]
+pub struct Events<E: Event> {
+    buffer: VecDeque<(TrackingTimestamp, E)>,
+    capacity: usize,
+}
+
+impl<E: Event> Default for Events<E> {
+    #[inline]
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+}
+
+impl<E: Event> Events<E> {
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            buffer: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    fn push(&mut self, timestamp: TrackingTimestamp, event: E) {
+        if self.buffer.len() == self.capacity {
+            self.buffer.pop_front();
+        }
+
+        self.buffer.push_back((timestamp, event));
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+//====================================================================
+
+pub struct EventReader<'a, E: Event> {
+    events: UniqueView<'a, Events<E>>,
+    last_run: Option<TrackingTimestamp>,
+    current: TrackingTimestamp,
+}
+
+impl<E: Event> EventReader<'_, E> {
+    /// Events sent since the system reading them last ran, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &E> {
+        let (last_run, current) = (self.last_run, self.current);
+
+        self.events
+            .buffer
+            .iter()
+            .filter(move |(timestamp, _)| match last_run {
+                Some(last_run) => timestamp.is_within(last_run, current),
+                None => true,
+            })
+            .map(|(_, event)| event)
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+}
+
+impl<E: Event> Borrow for EventReader<'_, E> {
+    type View<'a> = EventReader<'a, E>;
+
+    fn borrow<'a>(
+        all_storages: &'a AllStorages,
+        all_borrow: Option<SharedBorrow<'a>>,
+        last_run: Option<TrackingTimestamp>,
+        current: TrackingTimestamp,
+    ) -> Result<Self::View<'a>, shipyard::error::GetStorage> {
+        let events = <UniqueView<'_, Events<E>> as Borrow>::borrow(
+            all_storages,
+            all_borrow,
+            last_run,
+            current,
+        )?;
+
+        Ok(EventReader {
+            events,
+            last_run,
+            current,
+        })
+    }
+}
+
+unsafe impl<E: Event> BorrowInfo for EventReader<'_, E> {
+    fn borrow_info(info: &mut Vec<TypeInfo>) {
+        <UniqueView<'_, Events<E>> as BorrowInfo>::borrow_info(info);
+    }
+
+    fn enable_tracking(
+        _enable_tracking_fn: &mut Vec<fn(&AllStorages) -> Result<(), shipyard::error::GetStorage>>,
+    ) {
+    }
+}
+
+//--------------------------------------------------
+
+pub struct EventWriter<'a, E: Event> {
+    events: UniqueViewMut<'a, Events<E>>,
+    current: TrackingTimestamp,
+}
+
+impl<E: Event> EventWriter<'_, E> {
+    #[inline]
+    pub fn send(&mut self, event: E) {
+        self.events.push(self.current, event);
+    }
+}
+
+impl<E: Event> Borrow for EventWriter<'_, E> {
+    type View<'a> = EventWriter<'a, E>;
+
+    fn borrow<'a>(
+        all_storages: &'a AllStorages,
+        all_borrow: Option<SharedBorrow<'a>>,
+        last_run: Option<TrackingTimestamp>,
+        current: TrackingTimestamp,
+    ) -> Result<Self::View<'a>, shipyard::error::GetStorage> {
+        let events = <UniqueViewMut<'_, Events<E>> as Borrow>::borrow(
+            all_storages,
+            all_borrow,
+            last_run,
+            current,
+        )?;
+
+        Ok(EventWriter { events, current })
+    }
+}
+
+unsafe impl<E: Event> BorrowInfo for EventWriter<'_, E> {
+    fn borrow_info(info: &mut Vec<TypeInfo>) {
+        <UniqueViewMut<'_, Events<E>> as BorrowInfo>::borrow_info(info);
+    }
+
+    fn enable_tracking(
+        _enable_tracking_fn: &mut Vec<fn(&AllStorages) -> Result<(), shipyard::error::GetStorage>>,
+    ) {
+    }
+}
+
+//====================================================================
+
+impl WorkloadBuilder<'_> {
+    /// Create the [Events] buffer used by [EventReader] and [EventWriter] views of `E`.
+    pub fn add_event_buffer<E: Event>(&self) -> &Self {
+        self.log(format!(
+            "Adding event buffer for '{}'",
+            std::any::type_name::<E>()
+        ));
+
+        if let Err(shipyard::error::GetStorage::MissingStorage { .. }) =
+            self.get_world().get_unique::<&Events<E>>()
+        {
+            self.get_world().add_unique(Events::<E>::default());
+        }
+
+        self
+    }
+}
+
+//====================================================================
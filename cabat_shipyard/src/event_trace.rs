@@ -0,0 +1,75 @@
+//====================================================================
+
+use std::{collections::VecDeque, time::Instant};
+
+use shipyard::Unique;
+
+//====================================================================
+
+const TRACE_LIMIT: usize = 256;
+
+#[derive(Debug, Clone, Copy)]
+pub struct EventRecord {
+    pub name: &'static str,
+    pub frame: u64,
+    pub time: Instant,
+}
+
+/// Event types dispatched over the last few frames, oldest first.
+#[derive(Unique, Debug)]
+pub struct EventTrace {
+    records: VecDeque<EventRecord>,
+    frame: u64,
+}
+
+impl Default for EventTrace {
+    fn default() -> Self {
+        Self {
+            records: VecDeque::with_capacity(TRACE_LIMIT),
+            frame: 0,
+        }
+    }
+}
+
+impl EventTrace {
+    #[inline]
+    pub fn records(&self) -> impl DoubleEndedIterator<Item = &EventRecord> {
+        self.records.iter()
+    }
+
+    /// Names of events dispatched during the current frame.
+    pub fn current_frame(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.records
+            .iter()
+            .rev()
+            .take_while(|record| record.frame == self.frame)
+            .map(|record| record.name)
+    }
+
+    #[inline]
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub(crate) fn next_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    pub(crate) fn record(&mut self, names: &[&'static str]) {
+        let time = Instant::now();
+
+        names.iter().for_each(|name| {
+            if self.records.len() == TRACE_LIMIT {
+                self.records.pop_front();
+            }
+
+            self.records.push_back(EventRecord {
+                name,
+                frame: self.frame,
+                time,
+            });
+        });
+    }
+}
+
+//====================================================================
@@ -0,0 +1,123 @@
+//====================================================================
+
+use std::{
+    cell::Cell,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, MutexGuard, TryLockError,
+    },
+};
+
+use shipyard::{IntoWorkload, WorkloadModificator};
+
+use crate::Stages;
+
+//====================================================================
+
+// Number of recent event names kept for crash reports
+const RECENT_EVENTS: usize = 32;
+
+static CONTEXT: Mutex<FrameContext> = Mutex::new(FrameContext {
+    stage: None,
+    system: None,
+    frame: 0,
+    recent_events: Vec::new(),
+});
+
+// Incremented whenever the stage changes, so systems recorded during an earlier
+// stage aren't reported
+static STAGE_RUN: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    // System most recently started on this thread, and the stage run it started in
+    static RUNNING_SYSTEM: Cell<Option<(u64, &'static str)>> = const { Cell::new(None) };
+}
+
+//====================================================================
+
+/// What the runner was doing most recently. Kept outside of the world so it
+/// can still be read from a panic hook while a workload has it borrowed.
+#[derive(Debug, Clone, Default)]
+pub struct FrameContext {
+    /// Stage being run, or `None` between frames.
+    pub stage: Option<Stages>,
+    /// System running on the calling thread, named by its type when it was
+    /// added to the [crate::WorkloadBuilder]. Systems added together as a tuple
+    /// are named by the tuple, and event workloads by their event.
+    pub system: Option<&'static str>,
+    pub frame: u64,
+    /// Names of the most recently dispatched events, oldest first.
+    pub recent_events: Vec<&'static str>,
+}
+
+impl FrameContext {
+    /// Copy of the current context. Returns `None` if it is being written to,
+    /// which only happens if this is called from inside the runner itself.
+    pub fn current() -> Option<Self> {
+        let mut context = match CONTEXT.try_lock() {
+            Ok(context) => context.clone(),
+            Err(TryLockError::Poisoned(e)) => e.into_inner().clone(),
+            Err(TryLockError::WouldBlock) => return None,
+        };
+
+        let stage_run = STAGE_RUN.load(Ordering::Relaxed);
+
+        context.system = RUNNING_SYSTEM
+            .get()
+            .filter(|(run, _)| context.stage.is_some() && *run == stage_run)
+            .map(|(_, system)| system);
+
+        Some(context)
+    }
+
+    /// Called by the runner before and after each stage.
+    pub fn set_stage(stage: Option<Stages>) {
+        lock().stage = stage;
+        STAGE_RUN.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn set_system(system: &'static str) {
+        RUNNING_SYSTEM.set(Some((STAGE_RUN.load(Ordering::Relaxed), system)));
+    }
+
+    pub(crate) fn record_events(frame: u64, names: &[&'static str]) {
+        let mut context = lock();
+        context.frame = frame;
+
+        let events = &mut context.recent_events;
+        events.extend_from_slice(names);
+
+        if events.len() > RECENT_EVENTS {
+            events.drain(..events.len() - RECENT_EVENTS);
+        }
+    }
+}
+
+#[inline]
+fn lock() -> MutexGuard<'static, FrameContext> {
+    CONTEXT.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+//--------------------------------------------------
+
+/// Record the systems as running in the [FrameContext] whenever they start.
+/// Shipyard has no hook around systems, so this is a condition that always
+/// passes, checked on the thread about to run each one.
+pub(crate) fn track_systems<Views, R, Sys: IntoWorkload<Views, R>>(
+    workload: Sys,
+) -> shipyard::Workload {
+    track_workload(workload.into_workload(), std::any::type_name::<Sys>())
+}
+
+pub(crate) fn track_workload(
+    workload: shipyard::Workload,
+    name: &'static str,
+) -> shipyard::Workload {
+    workload.run_if(move || {
+        FrameContext::set_system(name);
+        true
+    })
+}
+
+//====================================================================
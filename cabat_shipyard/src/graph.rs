@@ -0,0 +1,548 @@
+//====================================================================
+
+use std::{
+    any::type_name,
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    path::PathBuf,
+};
+
+use shipyard::IntoWorkload;
+
+use crate::{timings, Stages};
+
+//====================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Graphviz DOT - render with `dot -Tsvg workloads.dot -o workloads.svg`
+    Dot,
+    Json,
+}
+
+/// Systems of every registered workload, grouped by substage and into the
+/// batches shipyard runs them in. Batches run one after another, systems within
+/// a batch may run in parallel.
+///
+/// Shipyard doesn't report tags or `before_all`/`after_all` once workloads are
+/// built, so only those described with [crate::WorkloadBuilder::describe_tag]
+/// and [crate::WorkloadBuilder::describe_order] are included, along with the
+/// ordering of custom substages.
+#[derive(Debug, Clone)]
+pub struct WorkloadGraph {
+    pub workloads: Vec<WorkloadNode>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkloadNode {
+    pub name: String,
+    /// Stages are split into their built in substages, with systems of custom
+    /// substages counting towards the substage that runs after them. Other
+    /// workloads have a single unnamed group.
+    pub substages: Vec<SubstageNode>,
+    pub tags: Vec<TagNode>,
+    pub edges: Vec<OrderEdge>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SubstageNode {
+    /// `None` for systems outside of the built in substages.
+    pub name: Option<String>,
+    pub batches: Vec<Vec<String>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TagNode {
+    pub name: String,
+    /// Tagged systems found in the workload.
+    pub systems: Vec<String>,
+}
+
+/// `from` runs before `to`. Either end is the name of a system, tag or substage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrderEdge {
+    pub from: String,
+    pub to: String,
+}
+
+//--------------------------------------------------
+
+/// Tags and ordering recorded by the builder, keyed by workload name.
+#[derive(Debug, Default)]
+pub(crate) struct GraphDescription {
+    tags: HashMap<String, BTreeMap<String, Vec<String>>>,
+    edges: HashMap<String, Vec<OrderEdge>>,
+}
+
+impl GraphDescription {
+    pub(crate) fn add_tag(&mut self, stage: Stages, tag: &str, systems: Vec<String>) {
+        self.tags
+            .entry(format!("{:?}", stage))
+            .or_default()
+            .entry(tag.to_string())
+            .or_default()
+            .extend(systems);
+    }
+
+    pub(crate) fn add_edge(&mut self, stage: Stages, from: &str, to: &str) {
+        let edge = OrderEdge {
+            from: from.to_string(),
+            to: to.to_string(),
+        };
+
+        let edges = self.edges.entry(format!("{:?}", stage)).or_default();
+        if !edges.contains(&edge) {
+            edges.push(edge);
+        }
+    }
+}
+
+//--------------------------------------------------
+
+impl WorkloadGraph {
+    /// Collect the workloads currently added to the world. Event workloads are
+    /// named after their event when names are given.
+    pub fn from_world(world: &shipyard::World, workload_names: &HashMap<String, String>) -> Self {
+        Self::from_world_described(world, workload_names, &GraphDescription::default())
+    }
+
+    pub(crate) fn from_world_described(
+        world: &shipyard::World,
+        workload_names: &HashMap<String, String>,
+        description: &GraphDescription,
+    ) -> Self {
+        let stage_order = enum_iterator::all::<Stages>()
+            .map(|stage| format!("{:?}", stage))
+            .collect::<Vec<_>>();
+
+        let mut workloads = world
+            .workloads_info()
+            .0
+            .iter()
+            .map(|(name, workload_info)| {
+                let batches = workload_info
+                    .batch_info
+                    .iter()
+                    .map(|batch_info| {
+                        batch_info
+                            .systems()
+                            .map(|system| system.name.clone())
+                            .collect::<Vec<_>>()
+                    })
+                    .filter(|batch| !batch.is_empty())
+                    .collect();
+
+                let substages = match stage_order.contains(name) {
+                    true => split_substages(batches),
+                    false => vec![SubstageNode {
+                        name: None,
+                        batches,
+                    }],
+                };
+
+                let systems = substages
+                    .iter()
+                    .flat_map(|substage| substage.batches.iter().flatten())
+                    .collect::<Vec<_>>();
+
+                let mut tags = description
+                    .tags
+                    .get(name)
+                    .map(|tags| {
+                        tags.iter()
+                            .map(|(tag, tagged)| TagNode {
+                                name: tag.clone(),
+                                systems: tagged
+                                    .iter()
+                                    .filter(|system| systems.contains(system))
+                                    .cloned()
+                                    .collect(),
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+
+                // Substages follow each other
+                let mut edges = substages
+                    .iter()
+                    .filter_map(|substage| substage.name.as_ref())
+                    .collect::<Vec<_>>()
+                    .windows(2)
+                    .map(|pair| OrderEdge {
+                        from: pair[0].clone(),
+                        to: pair[1].clone(),
+                    })
+                    .collect::<Vec<_>>();
+
+                edges.extend(description.edges.get(name).cloned().unwrap_or_default());
+
+                // Ordered against tags that weren't described
+                edges
+                    .iter()
+                    .flat_map(|edge| [&edge.from, &edge.to])
+                    .for_each(|end| {
+                        let known = systems.contains(&end)
+                            || tags.iter().any(|tag| tag.name == *end)
+                            || substages
+                                .iter()
+                                .any(|substage| substage.name.as_ref() == Some(end));
+
+                        if !known {
+                            tags.push(TagNode {
+                                name: end.clone(),
+                                systems: Vec::new(),
+                            });
+                        }
+                    });
+
+                WorkloadNode {
+                    name: workload_names.get(name).unwrap_or(name).clone(),
+                    substages,
+                    tags,
+                    edges,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // Stages in the order they run, followed by everything else
+        workloads.sort_by_key(|workload| {
+            let stage = stage_order.iter().position(|stage| *stage == workload.name);
+            (stage.unwrap_or(usize::MAX), workload.name.clone())
+        });
+
+        Self { workloads }
+    }
+
+    pub fn export(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.to_dot(),
+            GraphFormat::Json => self.to_json(),
+        }
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from(
+            "digraph workloads {\n    compound=true;\n    rankdir=TB;\n    node [shape=box];\n",
+        );
+
+        self.workloads
+            .iter()
+            .enumerate()
+            .for_each(|(workload_index, workload)| {
+                writeln!(out, "    subgraph cluster_{} {{", workload_index).unwrap();
+                writeln!(out, "        label=\"{}\";", escape(&workload.name)).unwrap();
+
+                // Name : (node, cluster it stands in for)
+                let mut nodes = HashMap::<&str, (String, Option<String>)>::new();
+
+                workload
+                    .substages
+                    .iter()
+                    .enumerate()
+                    .for_each(|(substage_index, substage)| {
+                        let prefix = format!("{}_{}", workload_index, substage_index);
+
+                        let indent = match &substage.name {
+                            Some(name) => {
+                                let anchor = format!("w{}_anchor", prefix);
+
+                                writeln!(
+                                    out,
+                                    "        subgraph cluster_{} {{\n            label=\"{}\";\n            style=rounded;\n            {} [shape=point, style=invis];",
+                                    prefix,
+                                    escape(name),
+                                    anchor
+                                )
+                                .unwrap();
+
+                                nodes.insert(name, (anchor, Some(format!("cluster_{}", prefix))));
+                                "    "
+                            }
+                            None => "",
+                        };
+
+                        write_batches(&mut out, &mut nodes, &prefix, indent, &substage.batches);
+
+                        if substage.name.is_some() {
+                            out.push_str("        }\n");
+                        }
+                    });
+
+                workload
+                    .tags
+                    .iter()
+                    .enumerate()
+                    .for_each(|(tag_index, tag)| {
+                        let id = format!("w{}_t{}", workload_index, tag_index);
+
+                        writeln!(
+                            out,
+                            "        {} [label=\"{}\", shape=ellipse, style=filled, fillcolor=lightyellow];",
+                            id,
+                            escape(&tag.name)
+                        )
+                        .unwrap();
+
+                        tag.systems.iter().for_each(|system| {
+                            if let Some((node, _)) = nodes.get(system.as_str()) {
+                                writeln!(
+                                    out,
+                                    "        {} -> {} [style=dashed, arrowhead=none];",
+                                    id, node
+                                )
+                                .unwrap();
+                            }
+                        });
+
+                        nodes.entry(&tag.name).or_insert((id, None));
+                    });
+
+                workload.edges.iter().for_each(|edge| {
+                    let (from, to) = match (nodes.get(edge.from.as_str()), nodes.get(edge.to.as_str())) {
+                        (Some(from), Some(to)) => (from, to),
+                        _ => return,
+                    };
+
+                    let mut attributes = vec!["color=blue".to_string()];
+                    if let Some(cluster) = &from.1 {
+                        attributes.push(format!("ltail={}", cluster));
+                    }
+                    if let Some(cluster) = &to.1 {
+                        attributes.push(format!("lhead={}", cluster));
+                    }
+
+                    writeln!(
+                        out,
+                        "        {} -> {} [{}];",
+                        from.0,
+                        to.0,
+                        attributes.join(", ")
+                    )
+                    .unwrap();
+                });
+
+                out.push_str("    }\n");
+            });
+
+        out.push_str("}\n");
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let workloads = self
+            .workloads
+            .iter()
+            .map(|workload| {
+                let substages = workload
+                    .substages
+                    .iter()
+                    .map(|substage| {
+                        let name = match &substage.name {
+                            Some(name) => format!("\"{}\"", escape(name)),
+                            None => "null".to_string(),
+                        };
+
+                        let batches = substage
+                            .batches
+                            .iter()
+                            .map(|batch| format!("[{}]", json_strings(batch)))
+                            .collect::<Vec<_>>()
+                            .join(", ");
+
+                        format!("{{ \"name\": {}, \"batches\": [{}] }}", name, batches)
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                let tags = workload
+                    .tags
+                    .iter()
+                    .map(|tag| {
+                        format!(
+                            "{{ \"name\": \"{}\", \"systems\": [{}] }}",
+                            escape(&tag.name),
+                            json_strings(&tag.systems)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                let edges = workload
+                    .edges
+                    .iter()
+                    .map(|edge| {
+                        format!(
+                            "{{ \"from\": \"{}\", \"to\": \"{}\" }}",
+                            escape(&edge.from),
+                            escape(&edge.to)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                format!(
+                    "    {{ \"name\": \"{}\", \"substages\": [{}], \"tags\": [{}], \"edges\": [{}] }}",
+                    escape(&workload.name),
+                    substages,
+                    tags,
+                    edges
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",\n");
+
+        format!("{{\n  \"workloads\": [\n{}\n  ]\n}}\n", workloads)
+    }
+}
+
+// Stage batches are split at the markers closing each substage. Systems of a
+// substage all run in batches before its marker, and those of the next after it.
+fn split_substages(batches: Vec<Vec<String>>) -> Vec<SubstageNode> {
+    let mut substages = Vec::new();
+    let mut current = Vec::new();
+
+    batches.into_iter().for_each(|batch| {
+        let (markers, systems) = batch
+            .into_iter()
+            .partition::<Vec<_>, _>(|system| timings::marker_substage(system).is_some());
+
+        markers.iter().for_each(|marker| {
+            substages.push(SubstageNode {
+                name: timings::marker_substage(marker).map(|substage| format!("{:?}", substage)),
+                batches: std::mem::take(&mut current),
+            });
+        });
+
+        if !systems.is_empty() {
+            current.push(systems);
+        }
+    });
+
+    if !current.is_empty() {
+        substages.push(SubstageNode {
+            name: None,
+            batches: current,
+        });
+    }
+
+    substages
+}
+
+fn write_batches<'a>(
+    out: &mut String,
+    nodes: &mut HashMap<&'a str, (String, Option<String>)>,
+    prefix: &str,
+    indent: &str,
+    batches: &'a [Vec<String>],
+) {
+    batches.iter().enumerate().for_each(|(batch_index, batch)| {
+        writeln!(
+            out,
+            "{0}        subgraph cluster_{1}_{2} {{\n{0}            label=\"batch {2}\";\n{0}            style=dashed;",
+            indent, prefix, batch_index
+        )
+        .unwrap();
+
+        batch.iter().enumerate().for_each(|(system_index, system)| {
+            let id = format!("w{}_b{}_s{}", prefix, batch_index, system_index);
+
+            writeln!(
+                out,
+                "{}            {} [label=\"{}\"];",
+                indent,
+                id,
+                escape(system)
+            )
+            .unwrap();
+
+            nodes.entry(system).or_insert((id, None));
+        });
+
+        writeln!(out, "{}        }}", indent).unwrap();
+    });
+
+    // Link batches in execution order
+    (1..batches.len()).for_each(|batch_index| {
+        writeln!(
+            out,
+            "{0}        w{1}_b{2}_s0 -> w{1}_b{3}_s0 [ltail=cluster_{1}_{2}, lhead=cluster_{1}_{3}];",
+            indent,
+            prefix,
+            batch_index - 1,
+            batch_index
+        )
+        .unwrap();
+    });
+}
+
+#[inline]
+fn json_strings(items: &[String]) -> String {
+    items
+        .iter()
+        .map(|item| format!("\"{}\"", escape(item)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Valid for both DOT and JSON strings
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+//--------------------------------------------------
+
+/// Call `f` with the type name of each system, to describe them in the graph.
+/// Systems already turned into a workload can't be seen into and are skipped,
+/// as are any whose type names can't be matched up.
+pub(crate) fn for_each_system<Views, R, Sys: IntoWorkload<Views, R>>(f: &mut dyn FnMut(&str)) {
+    walk_systems(type_name::<Sys>(), type_name::<Views>(), f);
+}
+
+// Tuples of systems have a matching tuple of views, functions borrow a tuple of views
+fn walk_systems(systems: &str, views: &str, f: &mut dyn FnMut(&str)) {
+    match (tuple_items(systems), tuple_items(views)) {
+        (Some(systems), Some(views)) if systems.len() == views.len() => systems
+            .into_iter()
+            .zip(views)
+            .for_each(|(systems, views)| walk_systems(systems, views, f)),
+
+        (None, Some(_)) => f(systems),
+
+        _ => {}
+    }
+}
+
+// Items of a tuple type name, or None if it isn't a tuple
+fn tuple_items(type_name: &str) -> Option<Vec<&str>> {
+    let inner = type_name.strip_prefix('(')?.strip_suffix(')')?;
+
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    inner.char_indices().for_each(|(index, c)| match c {
+        '<' | '(' | '[' => depth += 1,
+        '>' | ')' | ']' => depth -= 1,
+        ',' if depth == 0 => {
+            items.push(inner[start..index].trim());
+            start = index + 1;
+        }
+        _ => {}
+    });
+
+    let last = inner[start..].trim();
+    if !last.is_empty() {
+        items.push(last);
+    }
+
+    Some(items)
+}
+
+//--------------------------------------------------
+
+pub(crate) struct GraphExport {
+    pub format: GraphFormat,
+    pub path: PathBuf,
+}
+
+//====================================================================
@@ -2,16 +2,56 @@
 
 use std::{
     cell::RefCell,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     ops::{Deref, DerefMut},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 
-use shipyard::{info::TypeId, IntoWorkload, Unique, UniqueView, WorkloadModificator};
+use shipyard::{
+    info::TypeId, IntoWorkload, StorageId, Unique, UniqueStorage, UniqueView, WorkloadModificator,
+};
+
+mod commands;
+mod diagnostics;
+mod event_reader;
+mod event_trace;
+mod frame_context;
+mod graph;
+mod name;
+mod plugin_group;
+mod snapshot;
+mod state;
+mod substage;
+mod timings;
+mod trace;
+
+pub use commands::{apply_commands, Commands};
+pub use diagnostics::{report_missing_uniques, UniqueRequirement, UniqueRequirements};
+pub use event_reader::{EventReader, EventWriter, Events};
+pub use event_trace::{EventRecord, EventTrace};
+pub use frame_context::FrameContext;
+pub use graph::{GraphFormat, OrderEdge, SubstageNode, TagNode, WorkloadGraph, WorkloadNode};
+pub use name::{find_all_by_name, find_by_name, find_by_tag, EntityLabel, Name, Tags};
+pub use plugin_group::{PluginGroup, PluginGroupBuilder};
+pub use snapshot::{SnapshotRegistry, WorldSnapshot};
+pub use state::{apply_state_transitions, AppState, State};
+pub use substage::{CustomSubStage, SubStageRef};
+pub use timings::{FrameTimings, StageTimings};
+
+#[cfg(feature = "tracing")]
+pub use tracing;
 
 //====================================================================
 
 pub mod prelude {
-    pub use crate::{Event, EventHandler, Plugin, Res, ResMut, Stages, SubStages, WorkloadBuilder};
+    pub use crate::{
+        Commands, Event, EventHandler, EventReader, EventWriter, NonSendRes, NonSendResMut, Plugin,
+        PluginGroup, Res, ResMut, Stages, State, SubStages, WorkloadBuilder,
+    };
 }
 
 //====================================================================
@@ -19,6 +59,12 @@ pub mod prelude {
 pub type Res<'a, T> = shipyard::UniqueView<'a, T>;
 pub type ResMut<'a, T> = shipyard::UniqueViewMut<'a, T>;
 
+/// Borrow a unique added with [UniqueTools::insert_non_send]. Systems borrowing
+/// one are always run on the thread running the workload, which is the main
+/// thread for the runner, instead of on the thread pool.
+pub type NonSendRes<'a, T> = shipyard::NonSendSync<shipyard::UniqueView<'a, T>>;
+pub type NonSendResMut<'a, T> = shipyard::NonSendSync<shipyard::UniqueViewMut<'a, T>>;
+
 //====================================================================
 
 pub trait GetWorld {
@@ -43,6 +89,11 @@ pub trait UniqueTools {
         self.insert(U::default())
     }
 
+    /// Insert a unique that doesn't need to be Send or Sync, such as one holding
+    /// raw platform handles. It can only be borrowed through [NonSendRes] and
+    /// [NonSendResMut], from the thread it was inserted on.
+    fn insert_non_send<U: shipyard::Unique>(&self, unique: U) -> &Self;
+
     fn get_or_insert<U, F>(&self, insert: F) -> UniqueView<'_, U>
     where
         U: shipyard::Unique + Send + Sync,
@@ -83,6 +134,12 @@ impl<T: GetWorld> UniqueTools for T {
         self
     }
 
+    #[inline]
+    fn insert_non_send<U: shipyard::Unique>(&self, unique: U) -> &Self {
+        self.get_world().add_unique_non_send_sync(unique);
+        self
+    }
+
     fn get_or_insert<U, F>(&self, insert: F) -> UniqueView<'_, U>
     where
         U: shipyard::Unique + Send + Sync,
@@ -111,6 +168,12 @@ impl UniqueTools for shipyard::AllStoragesView<'_> {
         self
     }
 
+    #[inline]
+    fn insert_non_send<U: shipyard::Unique>(&self, unique: U) -> &Self {
+        self.add_unique_non_send_sync(unique);
+        self
+    }
+
     fn get_or_insert<U, F>(&self, insert: F) -> UniqueView<'_, U>
     where
         U: shipyard::Unique + Send + Sync,
@@ -182,6 +245,13 @@ struct WorkloadBuilderInner {
     event_workloads: HashMap<TypeId, shipyard::Workload>,
     event_workload_names: HashMap<String, String>, // Type ID : Event name
 
+    state_workloads: HashMap<String, shipyard::Workload>,
+    state_transitions: Vec<fn(&shipyard::World)>,
+    flush_stages: Vec<Stages>,
+    transient_uniques: HashSet<StorageId>,
+    graph_export: Option<graph::GraphExport>,
+    graph_description: graph::GraphDescription,
+
     build_tabs: u8,
     build_text: String,
 }
@@ -189,6 +259,8 @@ struct WorkloadBuilderInner {
 struct WorkloadToBuild {
     main: shipyard::Workload,
     substages: HashMap<SubStages, shipyard::Workload>,
+    custom_order: HashMap<&'static str, CustomSubStage>,
+    custom_substages: HashMap<&'static str, shipyard::Workload>,
 }
 
 impl WorkloadToBuild {
@@ -196,6 +268,8 @@ impl WorkloadToBuild {
         Self {
             main: shipyard::Workload::new(stage),
             substages: HashMap::new(),
+            custom_order: HashMap::new(),
+            custom_substages: HashMap::new(),
         }
     }
 }
@@ -210,6 +284,13 @@ impl<'a> WorkloadBuilder<'a> {
             event_workloads: HashMap::new(),
             event_workload_names: HashMap::new(),
 
+            state_workloads: HashMap::new(),
+            state_transitions: Vec::new(),
+            flush_stages: Vec::new(),
+            transient_uniques: HashSet::new(),
+            graph_export: None,
+            graph_description: graph::GraphDescription::default(),
+
             build_tabs: 0,
             build_text: "Setting up Workload Builder".to_string(),
         };
@@ -221,25 +302,73 @@ impl<'a> WorkloadBuilder<'a> {
     }
 
     pub fn build(self) {
-        let inner = self.inner.into_inner();
+        let mut inner = self.inner.into_inner();
 
         log::trace!("{}", inner.build_text);
 
-        inner.workloads.into_iter().for_each(|(_, mut to_build)| {
-            enum_iterator::all::<SubStages>()
+        // Custom substages are ordered by tags, which shipyard doesn't report
+        inner.workloads.iter().for_each(|(stage, to_build)| {
+            to_build.custom_order.iter().for_each(|(name, order)| {
+                order.after.iter().for_each(|after| {
+                    inner
+                        .graph_description
+                        .add_edge(*stage, &after.label(), name)
+                });
+                order.before.iter().for_each(|before| {
+                    inner
+                        .graph_description
+                        .add_edge(*stage, name, &before.label())
+                });
+            });
+        });
+
+        inner.workloads.into_iter().for_each(|(stage, mut to_build)| {
+            let workload = enum_iterator::all::<SubStages>()
                 .into_iter()
                 .fold(to_build.main, |acc, substage| {
                     // Check and Add substage if it exists
                     match to_build.substages.remove(&substage) {
                         Some(workload) => {
                             let workload = workload.tag(substage);
+                            // Marks the end of the substage for the stage timings
+                            let marker = timings::substage_marker(substage).after_all(substage);
+
                             // Go through substages and add before all
-                            acc.merge(substage.into_iter().fold(workload, |acc, substage_after| {
-                                acc.before_all(substage_after)
-                            }))
+                            let (workload, marker) = substage.into_iter().fold(
+                                (workload, marker),
+                                |(workload, marker), substage_after| {
+                                    (
+                                        workload.before_all(substage_after),
+                                        marker.before_all(substage_after),
+                                    )
+                                },
+                            );
+
+                            acc.merge(workload).merge(marker)
                         }
                         None => acc,
                     }
+                });
+
+            let custom_order = to_build.custom_order;
+
+            to_build
+                .custom_substages
+                .into_iter()
+                .fold(workload, |acc, (name, workload)| {
+                    let workload = workload.tag(name);
+
+                    match custom_order.get(name) {
+                        Some(order) => acc.merge(order.apply(workload)),
+                        None => {
+                            log::warn!(
+                                "Substage '{}' in stage '{:?}' was never registered - it has no ordering",
+                                name,
+                                stage
+                            );
+                            acc.merge(workload)
+                        }
+                    }
                 })
                 .add_to_world(&self.world)
                 .unwrap();
@@ -267,10 +396,43 @@ impl<'a> WorkloadBuilder<'a> {
 
         let event_handler = EventHandler {
             event_subscribers: ids,
+            flush_stages: inner.flush_stages,
             ..Default::default()
         };
 
         self.world.add_unique(event_handler);
+        self.world.add_unique(EventTrace::default());
+        self.world.add_unique(StageTimings::default());
+        self.world.add_unique(Commands::default());
+
+        // Event and state workloads may never run, so their borrows aren't required
+        let gated_workloads = inner
+            .event_workload_names
+            .keys()
+            .cloned()
+            .chain(
+                inner
+                    .state_workloads
+                    .keys()
+                    .map(|label| format!("{:?}", label)),
+            )
+            .collect::<HashSet<_>>();
+
+        // Process states
+        inner
+            .state_workloads
+            .into_iter()
+            .for_each(|(_, workload)| workload.add_to_world(&self.world).unwrap());
+
+        self.world
+            .add_unique(state::StateTransitions(inner.state_transitions));
+
+        self.world.add_unique(UniqueRequirements::collect(
+            &self.world,
+            &inner.event_workload_names,
+            &gated_workloads,
+            &inner.transient_uniques,
+        ));
 
         // Print debug data
         let data = self.world.workloads_info().0.iter().fold(
@@ -295,6 +457,23 @@ impl<'a> WorkloadBuilder<'a> {
         );
 
         log::debug!("{data}");
+
+        if let Some(export) = inner.graph_export {
+            let graph = WorkloadGraph::from_world_described(
+                &self.world,
+                &inner.event_workload_names,
+                &inner.graph_description,
+            );
+
+            match std::fs::write(&export.path, graph.export(export.format)) {
+                Ok(_) => log::info!("Exported workload graph to {:?}", export.path),
+                Err(e) => log::error!(
+                    "Failed to export workload graph to {:?}: {}",
+                    export.path,
+                    e
+                ),
+            }
+        }
     }
 }
 
@@ -331,6 +510,17 @@ impl<'a> WorkloadBuilder<'a> {
         inner.workloads.insert(stage, old_workload);
     }
 
+    fn with_stage(&self, stage: Stages, f: impl FnOnce(&mut WorkloadToBuild)) {
+        let mut inner = self.inner.borrow_mut();
+
+        let to_build = inner
+            .workloads
+            .entry(stage)
+            .or_insert_with(|| WorkloadToBuild::new(stage));
+
+        f(to_build);
+    }
+
     //--------------------------------------------------
 
     pub fn add_workload_first<Views, R, Sys>(&self, stage: Stages, workload: Sys) -> &Self
@@ -338,7 +528,11 @@ impl<'a> WorkloadBuilder<'a> {
         Sys: IntoWorkload<Views, R>,
         R: 'static,
     {
-        self.add_workload_sub(stage, SubStages::First, workload.into_workload());
+        self.add_workload_sub(
+            stage,
+            SubStages::First,
+            frame_context::track_systems(workload),
+        );
         self
     }
 
@@ -347,7 +541,11 @@ impl<'a> WorkloadBuilder<'a> {
         Sys: IntoWorkload<Views, R>,
         R: 'static,
     {
-        self.add_workload_sub(stage, SubStages::Pre, workload.into_workload());
+        self.add_workload_sub(
+            stage,
+            SubStages::Pre,
+            frame_context::track_systems(workload),
+        );
         self
     }
 
@@ -356,7 +554,11 @@ impl<'a> WorkloadBuilder<'a> {
         Sys: IntoWorkload<Views, R>,
         R: 'static,
     {
-        self.add_workload_sub(stage, SubStages::Main, workload.into_workload());
+        self.add_workload_sub(
+            stage,
+            SubStages::Main,
+            frame_context::track_systems(workload),
+        );
         self
     }
 
@@ -365,7 +567,11 @@ impl<'a> WorkloadBuilder<'a> {
         Sys: IntoWorkload<Views, R>,
         R: 'static,
     {
-        self.add_workload_sub(stage, SubStages::Post, workload.into_workload());
+        self.add_workload_sub(
+            stage,
+            SubStages::Post,
+            frame_context::track_systems(workload),
+        );
         self
     }
 
@@ -374,7 +580,80 @@ impl<'a> WorkloadBuilder<'a> {
         Sys: IntoWorkload<Views, R>,
         R: 'static,
     {
-        self.add_workload_sub(stage, SubStages::Last, workload.into_workload());
+        self.add_workload_sub(
+            stage,
+            SubStages::Last,
+            frame_context::track_systems(workload),
+        );
+        self
+    }
+
+    /// Register a named substage, ordered relative to the built in [SubStages] or
+    /// other custom substages of the same stage.
+    pub fn register_substage(
+        &self,
+        stage: Stages,
+        name: &'static str,
+        order: CustomSubStage,
+    ) -> &Self {
+        self.log(format!(
+            "Registering substage '{}' for stage '{:?}'",
+            name, stage
+        ));
+
+        self.with_stage(stage, |to_build| {
+            to_build.custom_order.insert(name, order);
+        });
+        self
+    }
+
+    /// Add a workload to a custom substage registered with [WorkloadBuilder::register_substage].
+    pub fn add_workload_to_substage<Views, R, Sys>(
+        &self,
+        stage: Stages,
+        name: &'static str,
+        workload: Sys,
+    ) -> &Self
+    where
+        Sys: IntoWorkload<Views, R>,
+        R: 'static,
+    {
+        self.log(format!(
+            "Adding workload for stage '{:?}' - substage '{}'",
+            stage, name
+        ));
+
+        self.describe_tag(stage, name, &workload);
+        let workload = frame_context::track_systems(workload);
+
+        self.with_stage(stage, |to_build| {
+            let new_substage = match to_build.custom_substages.remove(name) {
+                Some(old_substage) => old_substage.merge(workload),
+                None => workload,
+            };
+
+            to_build.custom_substages.insert(name, new_substage);
+        });
+        self
+    }
+
+    /// Add a workload that only runs while the condition system returns true.
+    pub fn add_workload_with_condition<Views, R, Sys, RunB, Run>(
+        &self,
+        stage: Stages,
+        condition: Run,
+        workload: Sys,
+    ) -> &Self
+    where
+        Sys: IntoWorkload<Views, R>,
+        R: 'static,
+        Run: shipyard::IntoWorkloadRunIf<RunB>,
+    {
+        self.add_workload_sub(
+            stage,
+            SubStages::Main,
+            frame_context::track_systems(workload).run_if(condition),
+        );
         self
     }
 
@@ -383,6 +662,7 @@ impl<'a> WorkloadBuilder<'a> {
     // TODO - Find way to convert to use IntoWorkload
     pub fn add_event<E: Event>(&self, workload: shipyard::Workload) -> &Self {
         let id = TypeId::of::<E>();
+        let workload = frame_context::track_workload(workload, std::any::type_name::<E>());
 
         self.log(format!(
             "Adding workload for event '{}'",
@@ -412,6 +692,86 @@ impl<'a> WorkloadBuilder<'a> {
         self
     }
 
+    /// Dispatch events sent during a stage once it finishes, allowing same frame reactions.
+    pub fn flush_events_after(&self, stage: Stages) -> &Self {
+        self.log(format!("Flushing events after stage '{:?}'", stage));
+
+        {
+            let mut inner = self.inner.borrow_mut();
+            if !inner.flush_stages.contains(&stage) {
+                inner.flush_stages.push(stage);
+            }
+        }
+
+        self
+    }
+
+    /// Write a description of every workload and the order its systems run in
+    /// to `path` once the builder is built.
+    pub fn export_workload_graph(&self, format: GraphFormat, path: impl Into<PathBuf>) -> &Self {
+        self.inner.borrow_mut().graph_export = Some(graph::GraphExport {
+            format,
+            path: path.into(),
+        });
+        self
+    }
+
+    /// Describe a tag given to systems added to a stage, for the graph written by
+    /// [WorkloadBuilder::export_workload_graph]. Shipyard doesn't report tags
+    /// once workloads are built. Systems are only found when passed before
+    /// being turned into a workload.
+    pub fn describe_tag<Views, R, Sys>(&self, stage: Stages, tag: &str, systems: &Sys) -> &Self
+    where
+        Sys: IntoWorkload<Views, R>,
+    {
+        let mut names = Vec::new();
+        graph::for_each_system::<Views, R, Sys>(&mut |system| names.push(system.to_string()));
+
+        self.inner
+            .borrow_mut()
+            .graph_description
+            .add_tag(stage, tag, names);
+        self
+    }
+
+    /// Describe the `before_all`/`after_all` tags systems added to a stage are
+    /// ordered against, for the graph written by
+    /// [WorkloadBuilder::export_workload_graph]. Systems are only found when
+    /// passed before being turned into a workload.
+    pub fn describe_order<Views, R, Sys>(
+        &self,
+        stage: Stages,
+        systems: &Sys,
+        before: &[&str],
+        after: &[&str],
+    ) -> &Self
+    where
+        Sys: IntoWorkload<Views, R>,
+    {
+        let mut inner = self.inner.borrow_mut();
+
+        graph::for_each_system::<Views, R, Sys>(&mut |system| {
+            before
+                .iter()
+                .for_each(|tag| inner.graph_description.add_edge(stage, system, tag));
+            after
+                .iter()
+                .for_each(|tag| inner.graph_description.add_edge(stage, tag, system));
+        });
+
+        self
+    }
+
+    /// Mark a unique that only exists for part of a frame (such as one added and
+    /// removed during rendering) so systems borrowing it aren't reported as missing it.
+    pub fn transient_unique<U: Unique + Send + Sync>(&self) -> &Self {
+        self.inner
+            .borrow_mut()
+            .transient_uniques
+            .insert(StorageId::of::<UniqueStorage<U>>());
+        self
+    }
+
     // TODO - Add tracking to make sure plugin can't be added multiple times
     pub fn add_plugin<T: Plugin>(&self, plugin: T) -> &Self {
         self.log(format!("Adding plugin '{}'", std::any::type_name::<T>()));
@@ -434,21 +794,35 @@ impl GetWorld for WorkloadBuilder<'_> {
 
 //====================================================================
 
-pub use cabat_proc::Event;
+/// Condition for [WorkloadBuilder::add_workload_with_condition] that is only true
+/// the first time it is checked.
+pub fn run_once() -> impl Fn() -> bool + Clone + Send + Sync + 'static {
+    let has_run = Arc::new(AtomicBool::new(false));
+    move || !has_run.swap(true, Ordering::Relaxed)
+}
+
+//====================================================================
+
+pub use cabat_proc::{Event, WrappedUnique};
 pub trait Event: Send + Sync + downcast::AnySync {}
 
 #[derive(Unique, Default)]
 pub struct EventHandler {
     pending: HashMap<TypeId, Box<dyn Event>>,
     active: HashMap<TypeId, Box<dyn Event>>,
+    names: HashMap<TypeId, &'static str>,
 
     event_subscribers: Vec<TypeId>,
+    flush_stages: Vec<Stages>,
 }
 
 impl EventHandler {
     pub fn add_event<E: 'static + Event>(&mut self, event: E) {
         let id = TypeId::of::<E>();
 
+        self.names
+            .entry(id)
+            .or_insert_with(std::any::type_name::<E>);
         self.pending.insert(id, Box::new(event));
     }
 
@@ -462,11 +836,16 @@ impl EventHandler {
 }
 
 pub fn activate_events(world: &shipyard::World) {
+    trace_span!("activate_events");
+
     let mut handler = world.borrow::<ResMut<EventHandler>>().unwrap();
 
     match handler.pending.is_empty() {
         true => {
             handler.active.clear();
+            std::mem::drop(handler);
+
+            trace_events(world, Vec::new(), true);
             return;
         }
         false => {
@@ -480,20 +859,92 @@ pub fn activate_events(world: &shipyard::World) {
         .active
         .keys()
         .filter_map(|key| match handler.event_subscribers.contains(key) {
-            true => Some(*key),
+            true => Some((*key, handler.event_name(key))),
             false => None,
         })
         .collect::<Vec<_>>();
 
+    let names = handler.event_names(handler.active.keys());
+
     std::mem::drop(handler);
 
-    keys.iter()
-        .for_each(|key| world.run_workload(*key).unwrap());
+    trace_events(world, names, true);
+    run_event_workloads(world, keys);
+}
+
+impl EventHandler {
+    fn event_name(&self, key: &TypeId) -> &'static str {
+        self.names.get(key).copied().unwrap_or("unknown event")
+    }
+
+    fn event_names<'a>(&self, keys: impl Iterator<Item = &'a TypeId>) -> Vec<&'static str> {
+        keys.map(|key| self.event_name(key)).collect()
+    }
+}
+
+fn run_event_workloads(world: &shipyard::World, keys: Vec<(TypeId, &'static str)>) {
+    keys.into_iter().for_each(|(key, _name)| {
+        trace_span!("event", name = _name);
+        world.run_workload(key).unwrap();
+    });
+}
+
+fn trace_events(world: &shipyard::World, names: Vec<&'static str>, new_frame: bool) {
+    let mut trace = world.borrow::<ResMut<EventTrace>>().unwrap();
+
+    if new_frame {
+        trace.next_frame();
+    }
+
+    if !names.is_empty() {
+        log::trace!("Triggering events {:?}", names);
+        trace.record(&names);
+    }
+
+    FrameContext::record_events(trace.frame(), &names);
+}
+
+/// Dispatch events sent during the given stage straight away instead of at the
+/// start of the next frame, if the stage was registered with
+/// [WorkloadBuilder::flush_events_after]. Flushed events stay active for the rest
+/// of the frame.
+pub fn flush_events(world: &shipyard::World, stage: Stages) {
+    let mut handler = world.borrow::<ResMut<EventHandler>>().unwrap();
+
+    if handler.pending.is_empty() || !handler.flush_stages.contains(&stage) {
+        return;
+    }
+
+    trace_span!("flush_events", stage = ?stage);
+
+    let names = handler.event_names(handler.pending.keys());
+
+    let keys = {
+        let handler = handler.deref_mut();
+
+        handler
+            .pending
+            .drain()
+            .filter_map(|(key, event)| {
+                handler.active.insert(key, event);
+
+                match handler.event_subscribers.contains(&key) {
+                    true => Some(key),
+                    false => None,
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let keys = keys
+        .into_iter()
+        .map(|key| (key, handler.event_name(&key)))
+        .collect::<Vec<_>>();
+
+    std::mem::drop(handler);
 
-    // // TODO - log event names instead of IDs
-    // if !keys.is_empty() {
-    //     log::trace!("Triggering events for {:?}", keys);
-    // }
+    trace_events(world, names, false);
+    run_event_workloads(world, keys);
 }
 
 //====================================================================
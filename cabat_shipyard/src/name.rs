@@ -0,0 +1,160 @@
+//====================================================================
+
+use std::{borrow::Cow, fmt};
+
+use shipyard::{Component, EntityId, Get, IntoIter, IntoWithId, View};
+
+//====================================================================
+
+/// Human readable name for an entity, shown by tooling in place of its id.
+/// Names don't have to be unique.
+#[derive(Component, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Name(Cow<'static, str>);
+
+impl Name {
+    #[inline]
+    pub fn new(name: impl Into<Cow<'static, str>>) -> Self {
+        Self(name.into())
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    #[inline]
+    pub fn set(&mut self, name: impl Into<Cow<'static, str>>) {
+        self.0 = name.into();
+    }
+}
+
+impl fmt::Display for Name {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&'static str> for Name {
+    #[inline]
+    fn from(name: &'static str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<String> for Name {
+    #[inline]
+    fn from(name: String) -> Self {
+        Self::new(name)
+    }
+}
+
+//--------------------------------------------------
+
+/// Set of labels for grouping entities, e.g. "enemy" or "pickup".
+#[derive(Component, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tags(Vec<Cow<'static, str>>);
+
+impl Tags {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn with(mut self, tag: impl Into<Cow<'static, str>>) -> Self {
+        self.insert(tag);
+        self
+    }
+
+    pub fn insert(&mut self, tag: impl Into<Cow<'static, str>>) {
+        let tag = tag.into();
+        if !self.contains(&tag) {
+            self.0.push(tag);
+        }
+    }
+
+    #[inline]
+    pub fn remove(&mut self, tag: &str) {
+        self.0.retain(|existing| existing != tag);
+    }
+
+    #[inline]
+    pub fn contains(&self, tag: &str) -> bool {
+        self.0.iter().any(|existing| existing == tag)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(|tag| tag.as_ref())
+    }
+}
+
+//====================================================================
+
+/// First entity found with the given name.
+pub fn find_by_name(v_name: &View<Name>, name: &str) -> Option<EntityId> {
+    v_name
+        .iter()
+        .with_id()
+        .find(|(_, entity_name)| entity_name.as_str() == name)
+        .map(|(id, _)| id)
+}
+
+/// Every entity with the given name.
+pub fn find_all_by_name<'a>(
+    v_name: &'a View<Name>,
+    name: &'a str,
+) -> impl Iterator<Item = EntityId> + 'a {
+    v_name
+        .iter()
+        .with_id()
+        .filter(move |(_, entity_name)| entity_name.as_str() == name)
+        .map(|(id, _)| id)
+}
+
+/// Every entity with the given tag.
+pub fn find_by_tag<'a>(
+    v_tags: &'a View<Tags>,
+    tag: &'a str,
+) -> impl Iterator<Item = EntityId> + 'a {
+    v_tags
+        .iter()
+        .with_id()
+        .filter(move |(_, tags)| tags.contains(tag))
+        .map(|(id, _)| id)
+}
+
+//--------------------------------------------------
+
+/// Displays an entity by its [Name] when it has one, falling back to its id.
+/// Meant for log messages and debug tooling.
+///
+/// ```ignore
+/// log::info!("{} picked up a key", EntityLabel::new(&v_name, id));
+/// ```
+pub struct EntityLabel<'a> {
+    name: Option<&'a Name>,
+    id: EntityId,
+}
+
+impl<'a> EntityLabel<'a> {
+    #[inline]
+    pub fn new(v_name: &'a View<Name>, id: EntityId) -> Self {
+        Self {
+            name: v_name.get(id).ok(),
+            id,
+        }
+    }
+}
+
+impl fmt::Display for EntityLabel<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name {
+            Some(name) => write!(f, "'{}' ({}:{})", name, self.id.index(), self.id.gen()),
+            None => write!(f, "Entity ({}:{})", self.id.index(), self.id.gen()),
+        }
+    }
+}
+
+//====================================================================
@@ -0,0 +1,147 @@
+//====================================================================
+
+use std::any::TypeId;
+
+use crate::{Plugin, WorkloadBuilder};
+
+//====================================================================
+
+/// Set of plugins added together. Use [PluginGroup::build] to get a
+/// [PluginGroupBuilder] when individual plugins need to be disabled or replaced.
+pub trait PluginGroup: Sized {
+    fn build(self) -> PluginGroupBuilder;
+}
+
+//====================================================================
+
+struct PluginEntry {
+    id: TypeId,
+    name: &'static str,
+    enabled: bool,
+    add: Box<dyn FnOnce(&WorkloadBuilder)>,
+}
+
+impl PluginEntry {
+    fn new<T: Plugin + 'static>(plugin: T) -> Self {
+        Self {
+            id: TypeId::of::<T>(),
+            name: std::any::type_name::<T>(),
+            enabled: true,
+            add: Box::new(move |builder| {
+                builder.add_plugin(plugin);
+            }),
+        }
+    }
+}
+
+pub struct PluginGroupBuilder {
+    group: &'static str,
+    plugins: Vec<PluginEntry>,
+}
+
+impl PluginGroupBuilder {
+    pub fn start<G: PluginGroup>() -> Self {
+        Self {
+            group: std::any::type_name::<G>(),
+            plugins: Vec::new(),
+        }
+    }
+
+    fn index_of<T: Plugin + 'static>(&self) -> Option<usize> {
+        let id = TypeId::of::<T>();
+        self.plugins.iter().position(|entry| entry.id == id)
+    }
+
+    fn expect_index_of<T: Plugin + 'static>(&self) -> usize {
+        self.index_of::<T>().unwrap_or_else(|| {
+            panic!(
+                "Plugin '{}' does not exist in group '{}'",
+                std::any::type_name::<T>(),
+                self.group
+            )
+        })
+    }
+
+    // Existing entries of the same type are replaced so each plugin is only added once
+    fn insert<T: Plugin + 'static>(&mut self, index: usize, plugin: T) {
+        let index = match self.index_of::<T>() {
+            Some(existing) => {
+                self.plugins.remove(existing);
+                match existing < index {
+                    true => index - 1,
+                    false => index,
+                }
+            }
+            None => index,
+        };
+
+        self.plugins.insert(index, PluginEntry::new(plugin));
+    }
+
+    /// Add a plugin to the end of the group, replacing it if it already exists.
+    pub fn add<T: Plugin + 'static>(mut self, plugin: T) -> Self {
+        match self.index_of::<T>() {
+            Some(index) => self.plugins[index] = PluginEntry::new(plugin),
+            None => self.plugins.push(PluginEntry::new(plugin)),
+        }
+        self
+    }
+
+    pub fn add_group(mut self, group: impl PluginGroup) -> Self {
+        self.plugins.extend(group.build().plugins);
+        self
+    }
+
+    pub fn add_before<Target: Plugin + 'static, T: Plugin + 'static>(mut self, plugin: T) -> Self {
+        let index = self.expect_index_of::<Target>();
+        self.insert(index, plugin);
+        self
+    }
+
+    pub fn add_after<Target: Plugin + 'static, T: Plugin + 'static>(mut self, plugin: T) -> Self {
+        let index = self.expect_index_of::<Target>();
+        self.insert(index + 1, plugin);
+        self
+    }
+
+    pub fn disable<T: Plugin + 'static>(mut self) -> Self {
+        let index = self.expect_index_of::<T>();
+        self.plugins[index].enabled = false;
+        self
+    }
+
+    pub fn enable<T: Plugin + 'static>(mut self) -> Self {
+        let index = self.expect_index_of::<T>();
+        self.plugins[index].enabled = true;
+        self
+    }
+
+    fn finish(self, builder: &WorkloadBuilder) {
+        builder.log(format!("Adding plugin group '{}'", self.group));
+
+        self.plugins
+            .into_iter()
+            .for_each(|entry| match entry.enabled {
+                true => (entry.add)(builder),
+                false => builder.log(format!("Skipping disabled plugin '{}'", entry.name)),
+            });
+    }
+}
+
+impl PluginGroup for PluginGroupBuilder {
+    #[inline]
+    fn build(self) -> PluginGroupBuilder {
+        self
+    }
+}
+
+//====================================================================
+
+impl WorkloadBuilder<'_> {
+    pub fn add_plugins<G: PluginGroup>(&self, group: G) -> &Self {
+        group.build().finish(self);
+        self
+    }
+}
+
+//====================================================================
@@ -0,0 +1,207 @@
+//====================================================================
+
+use std::any::Any;
+
+use shipyard::{
+    Component, EntitiesViewMut, EntityId, IntoIter, IntoWithId, Unique, UniqueView, UniqueViewMut,
+    View, ViewMut,
+};
+
+use crate::{GetWorld, UniqueTools, WorkloadBuilder};
+
+//====================================================================
+
+type SnapshotData = Box<dyn Any + Send + Sync>;
+
+struct SnapshotEntry {
+    name: &'static str,
+    capture: fn(&shipyard::World) -> SnapshotData,
+    restore: fn(&shipyard::World, &SnapshotData),
+}
+
+/// Component and unique types that are captured by a [WorldSnapshot].
+#[derive(Unique, Default)]
+pub struct SnapshotRegistry {
+    entries: Vec<SnapshotEntry>,
+}
+
+impl SnapshotRegistry {
+    fn register(&mut self, entry: SnapshotEntry) {
+        if self
+            .entries
+            .iter()
+            .any(|existing| existing.name == entry.name)
+        {
+            return;
+        }
+
+        self.entries.push(entry);
+    }
+
+    /// Type names of everything captured.
+    pub fn registered(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.entries.iter().map(|entry| entry.name)
+    }
+}
+
+//--------------------------------------------------
+
+impl WorkloadBuilder<'_> {
+    /// Include every `T` component in world snapshots.
+    pub fn register_snapshot_component<T>(&self) -> &Self
+    where
+        T: Component + Clone + Send + Sync,
+    {
+        self.snapshot_registry().register(SnapshotEntry {
+            name: std::any::type_name::<T>(),
+            capture: capture_component::<T>,
+            restore: restore_component::<T>,
+        });
+        self
+    }
+
+    /// Include the `U` unique in world snapshots.
+    pub fn register_snapshot_unique<U>(&self) -> &Self
+    where
+        U: Unique + Clone + Send + Sync,
+    {
+        self.snapshot_registry().register(SnapshotEntry {
+            name: std::any::type_name::<U>(),
+            capture: capture_unique::<U>,
+            restore: restore_unique::<U>,
+        });
+        self
+    }
+
+    fn snapshot_registry(&self) -> UniqueViewMut<SnapshotRegistry> {
+        self.get_or_insert(SnapshotRegistry::default);
+
+        self.get_world()
+            .borrow::<UniqueViewMut<SnapshotRegistry>>()
+            .unwrap()
+    }
+}
+
+//====================================================================
+
+/// Copy of the registered component storages and uniques, taken with
+/// [WorldSnapshot::capture] and applied again with [WorldSnapshot::restore].
+/// Useful for rollback and save states.
+///
+/// Entities created after the snapshot are kept on restore but lose their
+/// registered components. Entities deleted since are respawned with the same id.
+/// Registered uniques that were missing when captured are removed on restore.
+pub struct WorldSnapshot {
+    data: Vec<(&'static str, SnapshotData)>,
+}
+
+impl WorldSnapshot {
+    pub fn capture(world: &shipyard::World) -> Self {
+        let registry = match world.borrow::<UniqueView<SnapshotRegistry>>() {
+            Ok(registry) => registry,
+            Err(_) => return Self { data: Vec::new() },
+        };
+
+        let data = registry
+            .entries
+            .iter()
+            .map(|entry| (entry.name, (entry.capture)(world)))
+            .collect();
+
+        Self { data }
+    }
+
+    pub fn restore(&self, world: &shipyard::World) {
+        // Copy the restore functions out so the registry isn't borrowed while they run
+        let entries = {
+            let registry = match world.borrow::<UniqueView<SnapshotRegistry>>() {
+                Ok(registry) => registry,
+                Err(_) => return,
+            };
+
+            registry
+                .entries
+                .iter()
+                .map(|entry| (entry.name, entry.restore))
+                .collect::<Vec<_>>()
+        };
+
+        self.data.iter().for_each(|(name, data)| {
+            match entries.iter().find(|(entry_name, _)| entry_name == name) {
+                Some((_, restore)) => restore(world, data),
+                None => log::warn!("Snapshot contains unregistered type '{}'", name),
+            }
+        });
+    }
+
+    /// Type names captured in this snapshot.
+    pub fn types(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.data.iter().map(|(name, _)| *name)
+    }
+}
+
+//====================================================================
+
+fn capture_component<T: Component + Clone + Send + Sync>(world: &shipyard::World) -> SnapshotData {
+    let components = world.run(|v_component: View<T>| {
+        v_component
+            .iter()
+            .with_id()
+            .map(|(id, component)| (id, component.clone()))
+            .collect::<Vec<_>>()
+    });
+
+    Box::new(components)
+}
+
+fn restore_component<T: Component + Clone + Send + Sync>(
+    world: &shipyard::World,
+    data: &SnapshotData,
+) {
+    let components = match data.downcast_ref::<Vec<(EntityId, T)>>() {
+        Some(components) => components,
+        None => return,
+    };
+
+    world.run(
+        |mut entities: EntitiesViewMut, mut vm_component: ViewMut<T>| {
+            vm_component.clear();
+
+            components.iter().for_each(|(id, component)| {
+                if !entities.is_alive(*id) {
+                    entities.spawn(*id);
+                }
+
+                entities.add_component(*id, &mut vm_component, component.clone());
+            });
+        },
+    );
+}
+
+// Absent uniques are captured as None so restoring can remove them again
+fn capture_unique<U: Unique + Clone + Send + Sync>(world: &shipyard::World) -> SnapshotData {
+    let unique = world
+        .borrow::<UniqueView<U>>()
+        .ok()
+        .map(|unique| unique.clone());
+
+    Box::new(unique)
+}
+
+fn restore_unique<U: Unique + Clone + Send + Sync>(world: &shipyard::World, data: &SnapshotData) {
+    let unique = match data.downcast_ref::<Option<U>>() {
+        Some(Some(unique)) => unique.clone(),
+        Some(None) => {
+            world.remove_unique::<U>().ok();
+            return;
+        }
+        None => return,
+    };
+
+    match world.borrow::<UniqueViewMut<U>>() {
+        Ok(mut existing) => *existing = unique,
+        Err(_) => world.add_unique(unique),
+    }
+}
+
+//====================================================================
@@ -0,0 +1,202 @@
+//====================================================================
+
+use std::{fmt::Debug, hash::Hash};
+
+use shipyard::{IntoWorkload, Unique};
+
+use crate::{frame_context, GetWorld, Res, Stages, WorkloadBuilder};
+
+//====================================================================
+
+pub trait AppState: 'static + Send + Sync + Clone + Copy + PartialEq + Eq + Hash + Debug {}
+
+impl<T> AppState for T where T: 'static + Send + Sync + Clone + Copy + PartialEq + Eq + Hash + Debug {}
+
+//====================================================================
+
+/// Current state of type `S`. Changes requested with [State::set] are applied at
+/// the start of the next frame, running the `OnExit` workloads of the old state
+/// followed by the `OnEnter` workloads of the new one.
+#[derive(Unique)]
+pub struct State<S: AppState> {
+    current: S,
+    previous: Option<S>,
+    next: Option<S>,
+    entered: bool,
+}
+
+impl<S: AppState> State<S> {
+    #[inline]
+    fn new(initial: S) -> Self {
+        Self {
+            current: initial,
+            previous: None,
+            next: None,
+            entered: false,
+        }
+    }
+
+    #[inline]
+    pub fn get(&self) -> S {
+        self.current
+    }
+
+    #[inline]
+    pub fn previous(&self) -> Option<S> {
+        self.previous
+    }
+
+    #[inline]
+    pub fn is(&self, state: S) -> bool {
+        self.current == state
+    }
+
+    #[inline]
+    pub fn set(&mut self, next: S) {
+        self.next = Some(next);
+    }
+}
+
+//====================================================================
+
+#[inline]
+fn on_enter_label<S: AppState>(state: S) -> String {
+    format!("{}::OnEnter({:?})", std::any::type_name::<S>(), state)
+}
+
+#[inline]
+fn on_exit_label<S: AppState>(state: S) -> String {
+    format!("{}::OnExit({:?})", std::any::type_name::<S>(), state)
+}
+
+/// Transition functions for every registered state type, run by [apply_state_transitions].
+#[derive(Unique, Default)]
+pub(crate) struct StateTransitions(pub(crate) Vec<fn(&shipyard::World)>);
+
+fn run_state_workload(world: &shipyard::World, label: String) {
+    match world.run_workload(label) {
+        Ok(_) | Err(shipyard::error::RunWorkload::MissingWorkload) => {}
+        Err(e) => panic!("Failed to run state workload: {:?}", e),
+    }
+}
+
+fn apply_state<S: AppState>(world: &shipyard::World) {
+    let (exit, enter) = {
+        let mut state = world.borrow::<crate::ResMut<State<S>>>().unwrap();
+
+        if !state.entered {
+            state.entered = true;
+            (None, state.current)
+        } else {
+            match state.next.take() {
+                Some(next) if next != state.current => {
+                    let previous = state.current;
+                    state.previous = Some(previous);
+                    state.current = next;
+                    (Some(previous), next)
+                }
+                _ => return,
+            }
+        }
+    };
+
+    if let Some(exit) = exit {
+        log::debug!("Exiting state {:?}", exit);
+        run_state_workload(world, on_exit_label(exit));
+    }
+
+    log::debug!("Entering state {:?}", enter);
+    run_state_workload(world, on_enter_label(enter));
+}
+
+pub fn apply_state_transitions(world: &shipyard::World) {
+    let transitions = world
+        .borrow::<Res<StateTransitions>>()
+        .map(|transitions| transitions.0.clone())
+        .unwrap_or_default();
+
+    transitions
+        .into_iter()
+        .for_each(|transition| transition(world));
+}
+
+//====================================================================
+
+impl WorkloadBuilder<'_> {
+    pub fn add_state<S: AppState>(&self, initial: S) -> &Self {
+        self.log(format!(
+            "Adding state '{}' - initial {:?}",
+            std::any::type_name::<S>(),
+            initial
+        ));
+
+        self.get_world().add_unique(State::new(initial));
+        self.inner
+            .borrow_mut()
+            .state_transitions
+            .push(apply_state::<S>);
+
+        self
+    }
+
+    /// Add a workload to a stage that only runs while in the given state.
+    pub fn add_workload_in_state<S, Views, R, Sys>(
+        &self,
+        stage: Stages,
+        state: S,
+        workload: Sys,
+    ) -> &Self
+    where
+        S: AppState,
+        Sys: IntoWorkload<Views, R>,
+        R: 'static,
+    {
+        self.add_workload_with_condition(
+            stage,
+            move |current: Res<State<S>>| current.is(state),
+            workload,
+        )
+    }
+
+    pub fn add_on_enter<S, Views, R, Sys>(&self, state: S, workload: Sys) -> &Self
+    where
+        S: AppState,
+        Sys: IntoWorkload<Views, R>,
+        R: 'static,
+    {
+        self.add_state_workload(
+            on_enter_label(state),
+            frame_context::track_systems(workload),
+        )
+    }
+
+    pub fn add_on_exit<S, Views, R, Sys>(&self, state: S, workload: Sys) -> &Self
+    where
+        S: AppState,
+        Sys: IntoWorkload<Views, R>,
+        R: 'static,
+    {
+        self.add_state_workload(on_exit_label(state), frame_context::track_systems(workload))
+    }
+
+    fn add_state_workload(&self, label: String, workload: shipyard::Workload) -> &Self {
+        self.log(format!("Adding workload for '{}'", label));
+
+        {
+            let mut inner = self.inner.borrow_mut();
+
+            let old_workload = inner
+                .state_workloads
+                .remove(&label)
+                .unwrap_or(shipyard::Workload::new(label.clone()));
+
+            inner
+                .state_workloads
+                .insert(label, old_workload.merge(workload));
+        }
+
+        self
+    }
+}
+
+//====================================================================
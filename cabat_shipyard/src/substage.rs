@@ -0,0 +1,92 @@
+//====================================================================
+
+use crate::SubStages;
+
+//====================================================================
+
+/// A built in or custom substage, used to order custom substages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SubStageRef {
+    Builtin(SubStages),
+    Custom(&'static str),
+}
+
+impl From<SubStages> for SubStageRef {
+    #[inline]
+    fn from(value: SubStages) -> Self {
+        Self::Builtin(value)
+    }
+}
+
+impl From<&'static str> for SubStageRef {
+    #[inline]
+    fn from(value: &'static str) -> Self {
+        Self::Custom(value)
+    }
+}
+
+impl SubStageRef {
+    /// Name of the tag given to the substage's workload.
+    pub(crate) fn label(&self) -> String {
+        match self {
+            SubStageRef::Builtin(substage) => format!("{:?}", substage),
+            SubStageRef::Custom(name) => name.to_string(),
+        }
+    }
+}
+
+//--------------------------------------------------
+
+/// Ordering of a custom substage relative to the others in its stage. Register
+/// with [crate::WorkloadBuilder::register_substage].
+///
+/// ```ignore
+/// builder.register_substage(
+///     Stages::Update,
+///     "physics_sync",
+///     CustomSubStage::new().after(SubStages::Main).before(SubStages::Post),
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CustomSubStage {
+    pub(crate) after: Vec<SubStageRef>,
+    pub(crate) before: Vec<SubStageRef>,
+}
+
+impl CustomSubStage {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn after(mut self, substage: impl Into<SubStageRef>) -> Self {
+        self.after.push(substage.into());
+        self
+    }
+
+    #[inline]
+    pub fn before(mut self, substage: impl Into<SubStageRef>) -> Self {
+        self.before.push(substage.into());
+        self
+    }
+
+    pub(crate) fn apply(&self, workload: shipyard::Workload) -> shipyard::Workload {
+        let workload = self
+            .after
+            .iter()
+            .fold(workload, |workload, substage| match substage {
+                SubStageRef::Builtin(substage) => workload.after_all(*substage),
+                SubStageRef::Custom(name) => workload.after_all(*name),
+            });
+
+        self.before
+            .iter()
+            .fold(workload, |workload, substage| match substage {
+                SubStageRef::Builtin(substage) => workload.before_all(*substage),
+                SubStageRef::Custom(name) => workload.before_all(*name),
+            })
+    }
+}
+
+//====================================================================
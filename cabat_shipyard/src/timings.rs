@@ -0,0 +1,252 @@
+//====================================================================
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use shipyard::{IntoWorkload, Unique};
+
+use crate::{ResMut, Stages, SubStages};
+
+//====================================================================
+
+const DEFAULT_CAPACITY: usize = 120;
+
+// Setup only runs once so isn't tracked
+const STAGE_COUNT: usize = 5;
+const SUBSTAGE_COUNT: usize = 5;
+
+#[inline]
+fn stage_index(stage: Stages) -> Option<usize> {
+    match stage {
+        Stages::Setup => None,
+        Stages::First => Some(0),
+        Stages::FixedUpdate => Some(1),
+        Stages::Update => Some(2),
+        Stages::Render => Some(3),
+        Stages::Last => Some(4),
+    }
+}
+
+#[inline]
+fn substage_index(substage: SubStages) -> usize {
+    match substage {
+        SubStages::First => 0,
+        SubStages::Pre => 1,
+        SubStages::Main => 2,
+        SubStages::Post => 3,
+        SubStages::Last => 4,
+    }
+}
+
+//====================================================================
+
+/// Wall clock time spent in each stage and substage over a single frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameTimings {
+    stages: [Duration; STAGE_COUNT],
+    substages: [[Duration; SUBSTAGE_COUNT]; STAGE_COUNT],
+}
+
+impl FrameTimings {
+    /// Stages that are timed, in the order they run.
+    pub const STAGES: [Stages; STAGE_COUNT] = [
+        Stages::First,
+        Stages::FixedUpdate,
+        Stages::Update,
+        Stages::Render,
+        Stages::Last,
+    ];
+
+    pub const SUBSTAGES: [SubStages; SUBSTAGE_COUNT] = [
+        SubStages::First,
+        SubStages::Pre,
+        SubStages::Main,
+        SubStages::Post,
+        SubStages::Last,
+    ];
+
+    /// Time spent in a stage, including applying commands and flushing events
+    /// afterwards. Fixed update runs are added together.
+    #[inline]
+    pub fn stage(&self, stage: Stages) -> Duration {
+        match stage_index(stage) {
+            Some(index) => self.stages[index],
+            None => Duration::ZERO,
+        }
+    }
+
+    /// Time spent in one of a stage's substages. Custom substages count
+    /// towards the built in substage that runs after them.
+    #[inline]
+    pub fn substage(&self, stage: Stages, substage: SubStages) -> Duration {
+        match stage_index(stage) {
+            Some(index) => self.substages[index][substage_index(substage)],
+            None => Duration::ZERO,
+        }
+    }
+
+    /// Time spent in a stage outside of its substages.
+    pub fn overhead(&self, stage: Stages) -> Duration {
+        let substages = Self::SUBSTAGES
+            .iter()
+            .map(|substage| self.substage(stage, *substage))
+            .sum::<Duration>();
+
+        self.stage(stage).saturating_sub(substages)
+    }
+
+    /// Time spent in every stage.
+    #[inline]
+    pub fn total(&self) -> Duration {
+        self.stages.iter().sum()
+    }
+}
+
+//====================================================================
+
+/// Time spent in each stage and substage over recent frames, recorded by the
+/// runner. Oldest frames are dropped once the capacity is reached.
+#[derive(Unique, Debug)]
+pub struct StageTimings {
+    frames: VecDeque<FrameTimings>,
+    capacity: usize,
+
+    current: FrameTimings,
+    stage_start: Option<Instant>,
+    // Set as each substage of the running stage finishes
+    marks: [Option<Instant>; SUBSTAGE_COUNT],
+}
+
+impl Default for StageTimings {
+    fn default() -> Self {
+        Self {
+            frames: VecDeque::with_capacity(DEFAULT_CAPACITY),
+            capacity: DEFAULT_CAPACITY,
+
+            current: FrameTimings::default(),
+            stage_start: None,
+            marks: [None; SUBSTAGE_COUNT],
+        }
+    }
+}
+
+impl StageTimings {
+    /// Recorded frames, oldest first.
+    #[inline]
+    pub fn frames(&self) -> impl DoubleEndedIterator<Item = &FrameTimings> {
+        self.frames.iter()
+    }
+
+    #[inline]
+    pub fn latest(&self) -> Option<&FrameTimings> {
+        self.frames.back()
+    }
+
+    /// Mean of every recorded frame.
+    pub fn average(&self) -> FrameTimings {
+        let count = self.frames.len().max(1) as u32;
+
+        let total = self
+            .frames
+            .iter()
+            .fold(FrameTimings::default(), |mut total, frame| {
+                (0..STAGE_COUNT).for_each(|stage| {
+                    total.stages[stage] += frame.stages[stage];
+
+                    (0..SUBSTAGE_COUNT).for_each(|substage| {
+                        total.substages[stage][substage] += frame.substages[stage][substage];
+                    });
+                });
+                total
+            });
+
+        FrameTimings {
+            stages: total.stages.map(|duration| duration / count),
+            substages: total
+                .substages
+                .map(|substages| substages.map(|duration| duration / count)),
+        }
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+
+        if self.frames.len() > self.capacity {
+            self.frames.drain(..self.frames.len() - self.capacity);
+        }
+    }
+
+    /// Called by the runner before each stage.
+    pub fn begin_stage(&mut self) {
+        self.stage_start = Some(Instant::now());
+        self.marks = [None; SUBSTAGE_COUNT];
+    }
+
+    /// Called by the runner after each stage.
+    pub fn end_stage(&mut self, stage: Stages) {
+        let (index, start) = match (stage_index(stage), self.stage_start.take()) {
+            (Some(index), Some(start)) => (index, start),
+            _ => return,
+        };
+
+        self.current.stages[index] += start.elapsed();
+
+        // Each substage runs from the end of the one before it
+        let mut last = start;
+        self.marks.iter().enumerate().for_each(|(substage, mark)| {
+            if let Some(mark) = mark {
+                self.current.substages[index][substage] += mark.saturating_duration_since(last);
+                last = *mark;
+            }
+        });
+    }
+
+    /// Called by the runner once every stage of a frame has run.
+    pub fn end_frame(&mut self) {
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+
+        self.frames.push_back(std::mem::take(&mut self.current));
+    }
+}
+
+//--------------------------------------------------
+
+// Runs once the systems in a substage have finished. Each substage gets its own
+// copy so they can be told apart in the workload.
+pub(crate) fn substage_marker(substage: SubStages) -> shipyard::Workload {
+    match substage {
+        SubStages::First => sys_mark_substage::<0>.into_workload(),
+        SubStages::Pre => sys_mark_substage::<1>.into_workload(),
+        SubStages::Main => sys_mark_substage::<2>.into_workload(),
+        SubStages::Post => sys_mark_substage::<3>.into_workload(),
+        SubStages::Last => sys_mark_substage::<4>.into_workload(),
+    }
+}
+
+/// Substage a marker system closes, from its system name.
+pub(crate) fn marker_substage(system: &str) -> Option<SubStages> {
+    let index = system
+        .rsplit("::")
+        .next()?
+        .strip_prefix("sys_mark_substage<")?
+        .strip_suffix('>')?
+        .parse::<usize>()
+        .ok()?;
+
+    FrameTimings::SUBSTAGES.get(index).copied()
+}
+
+fn sys_mark_substage<const SUBSTAGE: usize>(mut timings: ResMut<StageTimings>) {
+    timings.marks[SUBSTAGE] = Some(Instant::now());
+}
+
+//====================================================================
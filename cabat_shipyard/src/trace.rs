@@ -0,0 +1,31 @@
+//====================================================================
+
+// Spans are emitted with the `tracing` crate when the `tracing` feature is
+// enabled, which also turns on shipyard's own spans for every workload and
+// system, including each render pass. Any tracing subscriber can collect them,
+// such as tracing-tracy or tracing-chrome. Without the feature the macros
+// expand to nothing.
+
+//====================================================================
+
+/// Enter an info level span until the end of the current scope. Takes the same
+/// arguments as `tracing::info_span!`.
+///
+/// ```ignore
+/// trace_span!("stage", name = ?stage);
+/// ```
+#[cfg(feature = "tracing")]
+#[macro_export]
+macro_rules! trace_span {
+    ($($args:tt)*) => {
+        let _span = $crate::tracing::info_span!($($args)*).entered();
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+#[macro_export]
+macro_rules! trace_span {
+    ($($args:tt)*) => {};
+}
+
+//====================================================================
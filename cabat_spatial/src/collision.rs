@@ -0,0 +1,268 @@
+//====================================================================
+
+use std::collections::{HashMap, HashSet};
+
+use cabat_shipyard::{prelude::*, UniqueTools};
+use shipyard::{Component, EntityId, IntoIter, IntoWithId, Unique, View};
+
+use crate::{Aabb, SpatialIndex, Transform};
+
+//====================================================================
+
+pub struct CollisionPlugin;
+
+impl Plugin for CollisionPlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        builder
+            .insert_default::<CollisionTracker>()
+            .add_workload_first(Stages::First, sys_reset_collisions)
+            .add_workload_last(Stages::FixedUpdate, sys_detect_collisions);
+    }
+}
+
+//====================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColliderShape {
+    Aabb { half_extents: glam::Vec3 },
+    Sphere { radius: f32 },
+}
+
+/// Overlap volume attached to an entity with a [Transform]. Rotation is ignored and
+/// scale is applied to the shape (spheres use the largest axis).
+#[derive(Component, Debug, Clone)]
+pub struct Collider {
+    pub shape: ColliderShape,
+    pub offset: glam::Vec3,
+    pub active: bool,
+}
+
+impl Collider {
+    #[inline]
+    pub fn aabb(half_extents: glam::Vec3) -> Self {
+        Self {
+            shape: ColliderShape::Aabb { half_extents },
+            offset: glam::Vec3::ZERO,
+            active: true,
+        }
+    }
+
+    #[inline]
+    pub fn sphere(radius: f32) -> Self {
+        Self {
+            shape: ColliderShape::Sphere { radius },
+            offset: glam::Vec3::ZERO,
+            active: true,
+        }
+    }
+
+    #[inline]
+    pub fn with_offset(mut self, offset: glam::Vec3) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    fn world_shape(&self, transform: &Transform) -> WorldShape {
+        let center = transform.translation + self.offset * transform.scale;
+
+        match self.shape {
+            ColliderShape::Aabb { half_extents } => WorldShape::Aabb {
+                min: center - half_extents * transform.scale.abs(),
+                max: center + half_extents * transform.scale.abs(),
+            },
+            ColliderShape::Sphere { radius } => WorldShape::Sphere {
+                center,
+                radius: radius * transform.scale.abs().max_element(),
+            },
+        }
+    }
+}
+
+//--------------------------------------------------
+
+#[derive(Debug, Clone, Copy)]
+enum WorldShape {
+    Aabb { min: glam::Vec3, max: glam::Vec3 },
+    Sphere { center: glam::Vec3, radius: f32 },
+}
+
+impl WorldShape {
+    fn bounds(&self) -> Aabb {
+        match *self {
+            WorldShape::Aabb { min, max } => Aabb::new(min, max),
+            WorldShape::Sphere { center, radius } => {
+                Aabb::from_center(center, glam::Vec3::splat(radius))
+            }
+        }
+    }
+
+    fn overlaps(&self, other: &WorldShape) -> bool {
+        match (*self, *other) {
+            (
+                WorldShape::Aabb { min, max },
+                WorldShape::Aabb {
+                    min: o_min,
+                    max: o_max,
+                },
+            ) => min.cmple(o_max).all() && max.cmpge(o_min).all(),
+
+            (
+                WorldShape::Sphere { center, radius },
+                WorldShape::Sphere {
+                    center: o_center,
+                    radius: o_radius,
+                },
+            ) => center.distance_squared(o_center) <= (radius + o_radius).powi(2),
+
+            (WorldShape::Aabb { min, max }, WorldShape::Sphere { center, radius })
+            | (WorldShape::Sphere { center, radius }, WorldShape::Aabb { min, max }) => {
+                center.clamp(min, max).distance_squared(center) <= radius * radius
+            }
+        }
+    }
+}
+
+//====================================================================
+
+/// Pair of overlapping entities. The entity with the lower id is always first.
+pub type CollisionPair = (EntityId, EntityId);
+
+#[inline]
+fn collision_pair(a: EntityId, b: EntityId) -> CollisionPair {
+    match a.inner() < b.inner() {
+        true => (a, b),
+        false => (b, a),
+    }
+}
+
+macro_rules! collision_event {
+    ($name:ident) => {
+        #[derive(Event, Debug, Clone)]
+        pub struct $name(Vec<CollisionPair>);
+
+        impl $name {
+            #[inline]
+            pub fn pairs(&self) -> &[CollisionPair] {
+                &self.0
+            }
+
+            /// Entities paired with the given entity in this event.
+            pub fn with(&self, entity: EntityId) -> impl Iterator<Item = EntityId> + '_ {
+                self.0.iter().filter_map(move |(a, b)| {
+                    if *a == entity {
+                        Some(*b)
+                    } else if *b == entity {
+                        Some(*a)
+                    } else {
+                        None
+                    }
+                })
+            }
+        }
+    };
+}
+
+collision_event!(CollisionStartedEvent);
+collision_event!(CollisionEndedEvent);
+
+//====================================================================
+
+/// Contacts between [Collider]s, found each fixed step by testing only the
+/// colliders sharing cells of a [SpatialIndex] of their bounds.
+#[derive(Unique, Default)]
+pub struct CollisionTracker {
+    contacts: HashSet<CollisionPair>,
+    broad_phase: SpatialIndex,
+
+    // Accumulated over every fixed step in a frame, as the event handler keeps a
+    // single event of each type
+    started: Vec<CollisionPair>,
+    ended: Vec<CollisionPair>,
+}
+
+impl CollisionTracker {
+    #[inline]
+    pub fn contacts(&self) -> impl Iterator<Item = &CollisionPair> {
+        self.contacts.iter()
+    }
+
+    #[inline]
+    pub fn is_colliding(&self, a: EntityId, b: EntityId) -> bool {
+        self.contacts.contains(&collision_pair(a, b))
+    }
+
+    /// Change the cell size of the broad phase, which should be around the
+    /// size of a typical collider.
+    #[inline]
+    pub fn set_cell_size(&mut self, cell_size: f32) {
+        self.broad_phase.set_cell_size(cell_size);
+    }
+}
+
+fn sys_reset_collisions(mut tracker: ResMut<CollisionTracker>) {
+    tracker.started.clear();
+    tracker.ended.clear();
+}
+
+fn sys_detect_collisions(
+    mut tracker: ResMut<CollisionTracker>,
+    mut event_handler: ResMut<EventHandler>,
+    v_collider: View<Collider>,
+    v_transform: View<Transform>,
+) {
+    let shapes = (&v_collider, &v_transform)
+        .iter()
+        .with_id()
+        .filter(|(_, (collider, _))| collider.active)
+        .map(|(id, (collider, transform))| (id, collider.world_shape(transform)))
+        .collect::<HashMap<_, _>>();
+
+    let tracker = &mut *tracker;
+
+    // Broad phase - rebuilt every step as colliders aren't tracked
+    tracker.broad_phase.clear();
+    shapes
+        .iter()
+        .for_each(|(id, shape)| tracker.broad_phase.insert(*id, shape.bounds()));
+
+    let broad_phase = &tracker.broad_phase;
+    let shapes = &shapes;
+
+    let contacts = shapes
+        .iter()
+        .flat_map(|(id, shape)| {
+            broad_phase
+                .query_aabb(&shape.bounds())
+                .into_iter()
+                // Each pair is found from both sides, only test it from the first
+                .filter(move |other_id| id.inner() < other_id.inner())
+                .filter(move |other_id| shape.overlaps(&shapes[other_id]))
+                .map(move |other_id| collision_pair(*id, other_id))
+        })
+        .collect::<HashSet<_>>();
+
+    let started = contacts
+        .difference(&tracker.contacts)
+        .copied()
+        .collect::<Vec<_>>();
+
+    let ended = tracker
+        .contacts
+        .difference(&contacts)
+        .copied()
+        .collect::<Vec<_>>();
+
+    tracker.contacts = contacts;
+
+    if !started.is_empty() {
+        tracker.started.extend(started);
+        event_handler.add_event(CollisionStartedEvent(tracker.started.clone()));
+    }
+
+    if !ended.is_empty() {
+        tracker.ended.extend(ended);
+        event_handler.add_event(CollisionEndedEvent(tracker.ended.clone()));
+    }
+}
+
+//====================================================================
@@ -0,0 +1,74 @@
+//====================================================================
+
+use cabat_runner::FIXED_TIMESTEP;
+use cabat_shipyard::prelude::*;
+use serde::{Deserialize, Serialize};
+use shipyard::{Component, IntoIter, IntoWorkload, View, ViewMut};
+
+use crate::Transform;
+
+//====================================================================
+
+/// Simple movement for games that don't need a physics engine. Applies
+/// [Acceleration], [Velocity] and [AngularVelocity] to the [Transform] each fixed
+/// step, before collisions are detected.
+pub struct KinematicsPlugin;
+
+impl Plugin for KinematicsPlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        builder.add_workload(
+            Stages::FixedUpdate,
+            (sys_apply_acceleration, sys_apply_velocity).into_sequential_workload(),
+        );
+    }
+}
+
+//====================================================================
+
+/// World space units per second.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Velocity(pub glam::Vec3);
+
+/// World space units per second squared, added to the [Velocity].
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Acceleration(pub glam::Vec3);
+
+/// World space rotation axis scaled by radians per second.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct AngularVelocity(pub glam::Vec3);
+
+impl AngularVelocity {
+    #[inline]
+    pub fn from_axis_angle(axis: glam::Vec3, radians_per_second: f32) -> Self {
+        Self(axis.normalize_or_zero() * radians_per_second)
+    }
+}
+
+//====================================================================
+
+fn sys_apply_acceleration(v_acceleration: View<Acceleration>, mut vm_velocity: ViewMut<Velocity>) {
+    (&v_acceleration, &mut vm_velocity)
+        .iter()
+        .for_each(|(acceleration, velocity)| velocity.0 += acceleration.0 * FIXED_TIMESTEP);
+}
+
+fn sys_apply_velocity(
+    v_velocity: View<Velocity>,
+    v_angular: View<AngularVelocity>,
+    mut vm_transform: ViewMut<Transform>,
+) {
+    // Only touch moving entities so unchanged transforms aren't flagged as modified
+    (&v_velocity, &mut vm_transform)
+        .iter()
+        .filter(|(velocity, _)| velocity.0 != glam::Vec3::ZERO)
+        .for_each(|(velocity, mut transform)| transform.translation += velocity.0 * FIXED_TIMESTEP);
+
+    (&v_angular, &mut vm_transform)
+        .iter()
+        .filter(|(angular, _)| angular.0 != glam::Vec3::ZERO)
+        .for_each(|(angular, mut transform)| {
+            transform.rotate(glam::Quat::from_scaled_axis(angular.0 * FIXED_TIMESTEP))
+        });
+}
+
+//====================================================================
@@ -1,10 +1,29 @@
 //====================================================================
 
+use serde::{Deserialize, Serialize};
 use shipyard::Component;
 
+mod collision;
+mod kinematics;
+mod spatial_index;
+mod transform2d;
+mod tween;
+
+pub use collision::{
+    Collider, ColliderShape, CollisionEndedEvent, CollisionPair, CollisionPlugin,
+    CollisionStartedEvent, CollisionTracker,
+};
+pub use kinematics::{Acceleration, AngularVelocity, KinematicsPlugin, Velocity};
+pub use spatial_index::{Aabb, Bounds, Ray, RayHit, SpatialIndex, SpatialIndexPlugin};
+pub use transform2d::{Transform2d, Transform2dPlugin};
+pub use tween::{
+    sys_update_tweens, Ease, FnLens, Lens, RotationLens, ScaleLens, TranslationLens, Tween,
+    TweenCompletedEvent, TweenPlugin,
+};
+
 //====================================================================
 
-#[derive(Component)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 #[track(All)]
 pub struct Transform {
     pub translation: glam::Vec3,
@@ -13,16 +32,19 @@ pub struct Transform {
 }
 
 impl Default for Transform {
+    #[inline]
     fn default() -> Self {
-        Self {
-            translation: glam::Vec3::ZERO,
-            rotation: glam::Quat::IDENTITY,
-            scale: glam::Vec3::ONE,
-        }
+        Self::IDENTITY
     }
 }
 
 impl Transform {
+    pub const IDENTITY: Self = Self {
+        translation: glam::Vec3::ZERO,
+        rotation: glam::Quat::IDENTITY,
+        scale: glam::Vec3::ONE,
+    };
+
     #[inline]
     pub fn from_translation(translation: glam::Vec3) -> Self {
         Self {
@@ -48,7 +70,7 @@ impl Transform {
     }
 
     #[inline]
-    pub fn from_translation_rotatation(translation: glam::Vec3, rotation: glam::Quat) -> Self {
+    pub fn from_translation_rotation(translation: glam::Vec3, rotation: glam::Quat) -> Self {
         Self {
             translation,
             rotation,
@@ -56,6 +78,12 @@ impl Transform {
         }
     }
 
+    #[deprecated(note = "use from_translation_rotation")]
+    #[inline]
+    pub fn from_translation_rotatation(translation: glam::Vec3, rotation: glam::Quat) -> Self {
+        Self::from_translation_rotation(translation, rotation)
+    }
+
     #[inline]
     pub fn from_translation_scale(translation: glam::Vec3, scale: glam::Vec3) -> Self {
         Self {
@@ -74,41 +102,221 @@ impl Transform {
         }
     }
 
+    #[inline]
+    pub fn from_translation_rotation_scale(
+        translation: glam::Vec3,
+        rotation: glam::Quat,
+        scale: glam::Vec3,
+    ) -> Self {
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    #[deprecated(note = "use from_translation_rotation_scale")]
     #[inline]
     pub fn from_translation_rotatation_scale(
         translation: glam::Vec3,
         rotation: glam::Quat,
         scale: glam::Vec3,
     ) -> Self {
+        Self::from_translation_rotation_scale(translation, rotation, scale)
+    }
+
+    /// Decompose an affine matrix. Shear and perspective are lost.
+    #[inline]
+    pub fn from_matrix(matrix: glam::Mat4) -> Self {
+        let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+
         Self {
             translation,
             rotation,
             scale,
         }
     }
+
+    /// Transform at the given position with its forward axis pointing at the target.
+    #[inline]
+    pub fn looking_at(translation: glam::Vec3, target: glam::Vec3, up: glam::Vec3) -> Self {
+        let mut transform = Self::from_translation(translation);
+        transform.look_at(target, up);
+        transform
+    }
 }
 
+//--------------------------------------------------
+
+// Local axes, +Z is forward, +X right and +Y up
 impl Transform {
     #[inline]
     pub fn forward(&self) -> glam::Vec3 {
         self.rotation * glam::Vec3::Z
     }
 
+    #[inline]
+    pub fn back(&self) -> glam::Vec3 {
+        self.rotation * glam::Vec3::NEG_Z
+    }
+
     #[inline]
     pub fn right(&self) -> glam::Vec3 {
         self.rotation * glam::Vec3::X
     }
 
+    #[inline]
+    pub fn left(&self) -> glam::Vec3 {
+        self.rotation * glam::Vec3::NEG_X
+    }
+
+    #[inline]
+    pub fn up(&self) -> glam::Vec3 {
+        self.rotation * glam::Vec3::Y
+    }
+
+    #[inline]
+    pub fn down(&self) -> glam::Vec3 {
+        self.rotation * glam::Vec3::NEG_Y
+    }
+}
+
+impl Transform {
+    /// Rotate so the forward axis points at the target, keeping the right axis
+    /// perpendicular to `up`. Does nothing if the target is on this transform.
+    pub fn look_at(&mut self, target: glam::Vec3, up: glam::Vec3) {
+        self.look_to(target - self.translation, up);
+    }
+
+    /// Rotate so the forward axis points along the direction.
+    pub fn look_to(&mut self, direction: glam::Vec3, up: glam::Vec3) {
+        let forward = match direction.try_normalize() {
+            Some(forward) => forward,
+            None => return,
+        };
+
+        // Fall back to any perpendicular axis when looking straight along up
+        let right = up
+            .cross(forward)
+            .try_normalize()
+            .unwrap_or_else(|| forward.any_orthonormal_vector());
+        let up = forward.cross(right);
+
+        self.rotation = glam::Quat::from_mat3(&glam::Mat3::from_cols(right, up, forward));
+    }
+
+    /// Rotate in world space.
+    #[inline]
+    pub fn rotate(&mut self, rotation: glam::Quat) {
+        self.rotation = (rotation * self.rotation).normalize();
+    }
+
+    /// Rotate relative to the current rotation.
+    #[inline]
+    pub fn rotate_local(&mut self, rotation: glam::Quat) {
+        self.rotation = (self.rotation * rotation).normalize();
+    }
+
+    /// Orbit a world space point, rotating both the position and orientation.
+    #[inline]
+    pub fn rotate_around(&mut self, point: glam::Vec3, rotation: glam::Quat) {
+        self.translation = point + rotation * (self.translation - point);
+        self.rotate(rotation);
+    }
+
+    /// Move towards the target, linearly interpolating the rotation.
     pub fn lerp(&mut self, target: &Transform, s: f32) {
         self.translation = self.translation.lerp(target.translation, s);
         self.rotation = self.rotation.lerp(target.rotation, s);
         self.scale = self.scale.lerp(target.scale, s);
     }
 
+    /// Move towards the target, spherically interpolating the rotation. Keeps a
+    /// constant angular speed, unlike [Self::lerp].
+    pub fn slerp(&mut self, target: &Transform, s: f32) {
+        self.translation = self.translation.lerp(target.translation, s);
+        self.rotation = self.rotation.slerp(target.rotation, s);
+        self.scale = self.scale.lerp(target.scale, s);
+    }
+
+    /// Apply this transform to a child transform, as if parented to it.
+    pub fn mul_transform(&self, child: &Transform) -> Self {
+        Self {
+            translation: self.transform_point(child.translation),
+            rotation: self.rotation * child.rotation,
+            scale: self.scale * child.scale,
+        }
+    }
+
+    /// Move a local space point into world space.
     #[inline]
-    pub fn to_array(&self) -> [f32; 16] {
+    pub fn transform_point(&self, point: glam::Vec3) -> glam::Vec3 {
+        self.translation + self.rotation * (point * self.scale)
+    }
+
+    /// Rotate and scale a local space direction, ignoring translation.
+    #[inline]
+    pub fn transform_vector(&self, vector: glam::Vec3) -> glam::Vec3 {
+        self.rotation * (vector * self.scale)
+    }
+
+    /// Inverse of this transform. Exact as long as scale is uniform.
+    pub fn inverse(&self) -> Self {
+        let rotation = self.rotation.inverse();
+        let scale = self.scale.recip();
+
+        Self {
+            translation: rotation * -self.translation * scale,
+            rotation,
+            scale,
+        }
+    }
+
+    #[inline]
+    pub fn to_matrix(&self) -> glam::Mat4 {
         glam::Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
-            .to_cols_array()
+    }
+
+    #[inline]
+    pub fn to_array(&self) -> [f32; 16] {
+        self.to_matrix().to_cols_array()
+    }
+}
+
+//--------------------------------------------------
+
+impl From<glam::Vec3> for Transform {
+    #[inline]
+    fn from(translation: glam::Vec3) -> Self {
+        Self::from_translation(translation)
+    }
+}
+
+impl From<glam::Quat> for Transform {
+    #[inline]
+    fn from(rotation: glam::Quat) -> Self {
+        Self::from_rotation(rotation)
+    }
+}
+
+impl From<glam::Mat4> for Transform {
+    #[inline]
+    fn from(matrix: glam::Mat4) -> Self {
+        Self::from_matrix(matrix)
+    }
+}
+
+impl From<Transform> for glam::Mat4 {
+    #[inline]
+    fn from(transform: Transform) -> Self {
+        transform.to_matrix()
+    }
+}
+
+impl From<&Transform> for glam::Mat4 {
+    #[inline]
+    fn from(transform: &Transform) -> Self {
+        transform.to_matrix()
     }
 }
 
@@ -0,0 +1,528 @@
+//====================================================================
+
+use std::collections::HashMap;
+
+use cabat_shipyard::prelude::*;
+use shipyard::{
+    Component, EntityId, Get, IntoIter, IntoWithId, IntoWorkload, Unique, View, WorkloadModificator,
+};
+
+use crate::Transform;
+
+//====================================================================
+
+/// Keeps the [SpatialIndex] up to date. Systems in the last substage of
+/// [Stages::Update] querying it should run `after_all("update_spatial_index")`.
+pub struct SpatialIndexPlugin;
+
+impl Plugin for SpatialIndexPlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        builder
+            .insert_default::<SpatialIndex>()
+            // After any synced transforms have been updated
+            .add_workload_last(
+                Stages::Update,
+                sys_update_spatial_index
+                    .into_workload()
+                    .tag("update_spatial_index"),
+            );
+    }
+}
+
+//====================================================================
+
+/// Local space box used to place an entity with a [Transform] in the
+/// [SpatialIndex]. Rotation and scale are applied to the box.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+#[track(All)]
+pub struct Bounds {
+    pub half_extents: glam::Vec3,
+    pub offset: glam::Vec3,
+}
+
+impl Bounds {
+    #[inline]
+    pub fn new(half_extents: glam::Vec3) -> Self {
+        Self {
+            half_extents,
+            offset: glam::Vec3::ZERO,
+        }
+    }
+
+    #[inline]
+    pub fn with_offset(mut self, offset: glam::Vec3) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Smallest world space box containing the transformed bounds.
+    pub fn world_aabb(&self, transform: &Transform) -> Aabb {
+        let center = transform.translation + transform.rotation * (self.offset * transform.scale);
+
+        let rotation = glam::Mat3::from_quat(transform.rotation);
+        let extents = self.half_extents * transform.scale.abs();
+        let half_extents = glam::Vec3::new(
+            rotation.row(0).abs().dot(extents),
+            rotation.row(1).abs().dot(extents),
+            rotation.row(2).abs().dot(extents),
+        );
+
+        Aabb::from_center(center, half_extents)
+    }
+}
+
+//--------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: glam::Vec3,
+    pub max: glam::Vec3,
+}
+
+impl Aabb {
+    #[inline]
+    pub fn new(min: glam::Vec3, max: glam::Vec3) -> Self {
+        Self { min, max }
+    }
+
+    #[inline]
+    pub fn from_center(center: glam::Vec3, half_extents: glam::Vec3) -> Self {
+        Self {
+            min: center - half_extents,
+            max: center + half_extents,
+        }
+    }
+
+    #[inline]
+    pub fn center(&self) -> glam::Vec3 {
+        (self.min + self.max) / 2.
+    }
+
+    #[inline]
+    pub fn contains_point(&self, point: glam::Vec3) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    #[inline]
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.cmple(other.max).all() && self.max.cmpge(other.min).all()
+    }
+
+    #[inline]
+    pub fn intersects_sphere(&self, center: glam::Vec3, radius: f32) -> bool {
+        center.clamp(self.min, self.max).distance_squared(center) <= radius * radius
+    }
+
+    /// Distance along the ray to where it enters the box, or 0 if the ray starts inside.
+    pub fn ray_distance(&self, ray: &Ray) -> Option<f32> {
+        let inverse = ray.direction.recip();
+
+        let t1 = (self.min - ray.origin) * inverse;
+        let t2 = (self.max - ray.origin) * inverse;
+
+        let t_enter = t1.min(t2).max_element().max(0.);
+        let t_exit = t1.max(t2).min_element();
+
+        (t_enter <= t_exit).then_some(t_enter)
+    }
+}
+
+//--------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: glam::Vec3,
+    /// Normalized direction of the ray.
+    pub direction: glam::Vec3,
+}
+
+impl Ray {
+    #[inline]
+    pub fn new(origin: glam::Vec3, direction: glam::Vec3) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize_or(glam::Vec3::Z),
+        }
+    }
+
+    #[inline]
+    pub fn at(&self, distance: f32) -> glam::Vec3 {
+        self.origin + self.direction * distance
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub entity: EntityId,
+    pub distance: f32,
+}
+
+//====================================================================
+
+#[derive(Debug, Clone, Copy)]
+struct SpatialEntry {
+    aabb: Aabb,
+    // Inclusive range of cells covered, None for entities too large to be gridded
+    cells: Option<(glam::IVec3, glam::IVec3)>,
+}
+
+/// Uniform grid of every entity with a [Transform] and [Bounds], updated each
+/// frame at the end of [Stages::Update]. Queries only test entities in nearby cells
+/// instead of scanning the whole world. The renderer uses it to cull indexed
+/// sprites and 3d text away from every camera.
+///
+/// The cell size should be around the size of a typical entity. Entities
+/// covering too many cells are kept in a separate list tested by every query.
+#[derive(Unique)]
+pub struct SpatialIndex {
+    cell_size: f32,
+    cells: HashMap<glam::IVec3, Vec<EntityId>>,
+    oversized: Vec<EntityId>,
+    entries: HashMap<EntityId, SpatialEntry>,
+}
+
+impl Default for SpatialIndex {
+    #[inline]
+    fn default() -> Self {
+        Self::new(8.)
+    }
+}
+
+impl SpatialIndex {
+    const MAX_ENTRY_CELLS: i32 = 64;
+    const MAX_RAY_CELLS: u32 = 4096;
+
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size: cell_size.max(f32::EPSILON),
+            cells: HashMap::new(),
+            oversized: Vec::new(),
+            entries: HashMap::new(),
+        }
+    }
+
+    #[inline]
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Change the cell size, regridding every entity.
+    pub fn set_cell_size(&mut self, cell_size: f32) {
+        let entries = std::mem::take(&mut self.entries);
+
+        self.cell_size = cell_size.max(f32::EPSILON);
+        self.cells.clear();
+        self.oversized.clear();
+
+        entries
+            .into_iter()
+            .for_each(|(id, entry)| self.insert(id, entry.aabb));
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// World space box the entity was last indexed with.
+    #[inline]
+    pub fn aabb(&self, entity: EntityId) -> Option<Aabb> {
+        self.entries.get(&entity).map(|entry| entry.aabb)
+    }
+
+    #[inline]
+    fn cell(&self, point: glam::Vec3) -> glam::IVec3 {
+        (point / self.cell_size).floor().as_ivec3()
+    }
+
+    #[inline]
+    fn cell_range(&self, aabb: &Aabb) -> (glam::IVec3, glam::IVec3) {
+        (self.cell(aabb.min), self.cell(aabb.max))
+    }
+
+    //--------------------------------------------------
+
+    /// Add or move an entity. Entities are normally kept up to date from their
+    /// [Transform] and [Bounds], this is for anything indexed by hand.
+    pub fn insert(&mut self, entity: EntityId, aabb: Aabb) {
+        self.remove(entity);
+
+        let (min, max) = self.cell_range(&aabb);
+        let span = max - min + glam::IVec3::ONE;
+
+        let cells = match span.cmple(glam::IVec3::splat(Self::MAX_ENTRY_CELLS)).all()
+            && span.x * span.y * span.z <= Self::MAX_ENTRY_CELLS
+        {
+            true => {
+                for_each_cell(min, max, |cell| {
+                    self.cells.entry(cell).or_default().push(entity);
+                });
+                Some((min, max))
+            }
+            false => {
+                self.oversized.push(entity);
+                None
+            }
+        };
+
+        self.entries.insert(entity, SpatialEntry { aabb, cells });
+    }
+
+    pub fn remove(&mut self, entity: EntityId) {
+        let entry = match self.entries.remove(&entity) {
+            Some(entry) => entry,
+            None => return,
+        };
+
+        match entry.cells {
+            Some((min, max)) => for_each_cell(min, max, |cell| {
+                if let Some(entities) = self.cells.get_mut(&cell) {
+                    entities.retain(|id| *id != entity);
+                    if entities.is_empty() {
+                        self.cells.remove(&cell);
+                    }
+                }
+            }),
+            None => self.oversized.retain(|id| *id != entity),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.oversized.clear();
+        self.entries.clear();
+    }
+
+    //--------------------------------------------------
+
+    // Every entity whose cells overlap the range, each visited once
+    fn visit_range(
+        &self,
+        min: glam::IVec3,
+        max: glam::IVec3,
+        mut visit: impl FnMut(EntityId, &Aabb),
+    ) {
+        let mut visit_cell = |cell: glam::IVec3, entities: &[EntityId]| {
+            entities.iter().for_each(|id| {
+                let entry = &self.entries[id];
+                let (entry_min, _) = entry.cells.unwrap();
+
+                // Only visit in the first cell shared with the range
+                if entry_min.max(min) == cell {
+                    visit(*id, &entry.aabb);
+                }
+            });
+        };
+
+        let span = max.as_i64vec3() - min.as_i64vec3() + glam::I64Vec3::ONE;
+
+        // Large ranges are cheaper to check against the occupied cells
+        match span.x.saturating_mul(span.y).saturating_mul(span.z) > self.cells.len() as i64 {
+            true => self
+                .cells
+                .iter()
+                .filter(|(cell, _)| cell.cmpge(min).all() && cell.cmple(max).all())
+                .for_each(|(cell, entities)| visit_cell(*cell, entities)),
+
+            false => for_each_cell(min, max, |cell| {
+                if let Some(entities) = self.cells.get(&cell) {
+                    visit_cell(cell, entities);
+                }
+            }),
+        }
+
+        self.oversized
+            .iter()
+            .for_each(|id| visit(*id, &self.entries[id].aabb));
+    }
+
+    /// Entities whose box overlaps the given box.
+    pub fn query_aabb(&self, aabb: &Aabb) -> Vec<EntityId> {
+        let (min, max) = self.cell_range(aabb);
+        let mut found = Vec::new();
+
+        self.visit_range(min, max, |id, entry| {
+            if entry.intersects(aabb) {
+                found.push(id);
+            }
+        });
+
+        found
+    }
+
+    /// Entities whose box overlaps the given sphere.
+    pub fn query_sphere(&self, center: glam::Vec3, radius: f32) -> Vec<EntityId> {
+        let (min, max) = self.cell_range(&Aabb::from_center(center, glam::Vec3::splat(radius)));
+        let mut found = Vec::new();
+
+        self.visit_range(min, max, |id, entry| {
+            if entry.intersects_sphere(center, radius) {
+                found.push(id);
+            }
+        });
+
+        found
+    }
+
+    #[inline]
+    fn ray_hit(&self, ray: &Ray, max_distance: f32, entity: EntityId) -> Option<RayHit> {
+        self.entries[&entity]
+            .aabb
+            .ray_distance(ray)
+            .filter(|distance| *distance <= max_distance)
+            .map(|distance| RayHit { entity, distance })
+    }
+
+    /// Closest entity box hit by the ray within the given distance.
+    pub fn raycast(&self, ray: &Ray, max_distance: f32) -> Option<RayHit> {
+        let mut closest = self
+            .oversized
+            .iter()
+            .filter_map(|id| self.ray_hit(ray, max_distance, *id))
+            .min_by(compare_hits);
+
+        self.traverse_ray(ray, max_distance, |entities, cell_exit| {
+            closest = entities
+                .iter()
+                .filter_map(|id| self.ray_hit(ray, max_distance, *id))
+                .chain(closest)
+                .min_by(compare_hits);
+
+            // Anything only in later cells is further than a hit inside this one
+            closest.map_or(true, |hit| hit.distance > cell_exit)
+        });
+
+        closest
+    }
+
+    /// Every entity box hit by the ray within the given distance, closest first.
+    pub fn raycast_all(&self, ray: &Ray, max_distance: f32) -> Vec<RayHit> {
+        let mut hits = self
+            .oversized
+            .iter()
+            .filter_map(|id| self.ray_hit(ray, max_distance, *id))
+            .collect::<Vec<_>>();
+
+        self.traverse_ray(ray, max_distance, |entities, _| {
+            hits.extend(
+                entities
+                    .iter()
+                    .filter_map(|id| self.ray_hit(ray, max_distance, *id)),
+            );
+            true
+        });
+
+        // Entities covering several cells are hit once per cell
+        hits.sort_by(compare_hits);
+        hits.dedup_by_key(|hit| hit.entity);
+        hits
+    }
+
+    // Walk the cells along the ray in order, passing each cell's entities and the
+    // distance the ray leaves it at. Stops once the visitor returns false, or after
+    // MAX_RAY_CELLS for rays with no practical length limit.
+    fn traverse_ray(
+        &self,
+        ray: &Ray,
+        max_distance: f32,
+        mut visit: impl FnMut(&[EntityId], f32) -> bool,
+    ) {
+        let mut cell = self.cell(ray.origin);
+        let step = ray.direction.signum().as_ivec3();
+
+        let t_delta = (self.cell_size / ray.direction.abs()).to_array();
+        let mut t_max = [0, 1, 2].map(|axis| {
+            let direction = ray.direction[axis];
+            let origin = ray.origin[axis];
+
+            if direction > 0. {
+                ((cell[axis] + 1) as f32 * self.cell_size - origin) / direction
+            } else if direction < 0. {
+                (cell[axis] as f32 * self.cell_size - origin) / direction
+            } else {
+                f32::INFINITY
+            }
+        });
+
+        for _ in 0..Self::MAX_RAY_CELLS {
+            let axis = match t_max[0] < t_max[1] {
+                true if t_max[0] < t_max[2] => 0,
+                false if t_max[1] < t_max[2] => 1,
+                _ => 2,
+            };
+            let cell_exit = t_max[axis];
+
+            if let Some(entities) = self.cells.get(&cell) {
+                if !visit(entities, cell_exit) {
+                    return;
+                }
+            }
+
+            if cell_exit > max_distance || !cell_exit.is_finite() {
+                return;
+            }
+
+            cell[axis] += step[axis];
+            t_max[axis] += t_delta[axis];
+        }
+    }
+}
+
+// Closest first, ties ordered by entity so duplicate hits end up next to each other
+#[inline]
+fn compare_hits(a: &RayHit, b: &RayHit) -> std::cmp::Ordering {
+    a.distance
+        .total_cmp(&b.distance)
+        .then(a.entity.inner().cmp(&b.entity.inner()))
+}
+
+#[inline]
+fn for_each_cell(min: glam::IVec3, max: glam::IVec3, mut f: impl FnMut(glam::IVec3)) {
+    (min.z..=max.z).for_each(|z| {
+        (min.y..=max.y).for_each(|y| {
+            (min.x..=max.x).for_each(|x| f(glam::IVec3::new(x, y, z)));
+        });
+    });
+}
+
+//====================================================================
+
+fn sys_update_spatial_index(
+    mut index: ResMut<SpatialIndex>,
+    v_transform: View<Transform>,
+    v_bounds: View<Bounds>,
+) {
+    v_bounds
+        .removed_or_deleted()
+        .chain(v_transform.removed_or_deleted())
+        .for_each(|id| index.remove(id));
+
+    // Bounds changes aren't seen when iterating the modified transforms
+    let changed = (v_transform.inserted_or_modified(), &v_bounds)
+        .iter()
+        .with_id()
+        .map(|(id, _)| id)
+        .chain(
+            (&v_transform, v_bounds.inserted_or_modified())
+                .iter()
+                .with_id()
+                .map(|(id, _)| id),
+        )
+        .collect::<Vec<_>>();
+
+    changed.into_iter().for_each(|id| {
+        let (transform, bounds) = match (v_transform.get(id), v_bounds.get(id)) {
+            (Ok(transform), Ok(bounds)) => (transform, bounds),
+            _ => return,
+        };
+
+        index.insert(id, bounds.world_aabb(transform));
+    });
+}
+
+//====================================================================
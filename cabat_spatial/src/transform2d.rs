@@ -0,0 +1,184 @@
+//====================================================================
+
+use cabat_shipyard::prelude::*;
+use serde::{Deserialize, Serialize};
+use shipyard::{Component, EntitiesView, Get, IntoIter, IntoWithId, View, ViewMut};
+
+use crate::Transform;
+
+//====================================================================
+
+pub struct Transform2dPlugin;
+
+impl Plugin for Transform2dPlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        // Renderers prepare their instances at the end of the update
+        builder.add_workload_post(Stages::Update, sys_sync_transform2d);
+    }
+}
+
+//====================================================================
+
+/// Position, rotation and scale on the XY plane, for 2d games that would rather
+/// not deal with quaternions. Entities with a [Transform2d] get a [Transform]
+/// kept in sync with it, so anything drawing or querying transforms accepts it.
+///
+/// Changes made directly to the synced [Transform] are overwritten the next
+/// time the [Transform2d] changes.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[track(All)]
+pub struct Transform2d {
+    pub position: glam::Vec2,
+    /// Counter clockwise rotation around the Z axis, in radians.
+    pub rotation: f32,
+    pub scale: glam::Vec2,
+    /// Depth on the Z axis, used to order overlapping sprites.
+    pub layer: f32,
+}
+
+impl Default for Transform2d {
+    #[inline]
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform2d {
+    pub const IDENTITY: Self = Self {
+        position: glam::Vec2::ZERO,
+        rotation: 0.,
+        scale: glam::Vec2::ONE,
+        layer: 0.,
+    };
+
+    #[inline]
+    pub fn from_position(position: glam::Vec2) -> Self {
+        Self {
+            position,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn from_rotation(rotation: f32) -> Self {
+        Self {
+            rotation,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn from_scale(scale: glam::Vec2) -> Self {
+        Self {
+            scale,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn with_layer(mut self, layer: f32) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    //--------------------------------------------------
+
+    #[inline]
+    pub fn right(&self) -> glam::Vec2 {
+        glam::Vec2::from_angle(self.rotation)
+    }
+
+    #[inline]
+    pub fn up(&self) -> glam::Vec2 {
+        glam::Vec2::from_angle(self.rotation).perp()
+    }
+
+    #[inline]
+    pub fn rotate(&mut self, angle: f32) {
+        self.rotation += angle;
+    }
+
+    /// Rotate so the right axis points at the target.
+    #[inline]
+    pub fn look_at(&mut self, target: glam::Vec2) {
+        let direction = target - self.position;
+        if direction != glam::Vec2::ZERO {
+            self.rotation = direction.to_angle();
+        }
+    }
+
+    pub fn lerp(&mut self, target: &Transform2d, s: f32) {
+        self.position = self.position.lerp(target.position, s);
+        self.rotation += (target.rotation - self.rotation) * s;
+        self.scale = self.scale.lerp(target.scale, s);
+        self.layer += (target.layer - self.layer) * s;
+    }
+
+    /// Move a local space point onto the plane.
+    #[inline]
+    pub fn transform_point(&self, point: glam::Vec2) -> glam::Vec2 {
+        self.position + glam::Vec2::from_angle(self.rotation).rotate(point * self.scale)
+    }
+
+    //--------------------------------------------------
+
+    #[inline]
+    pub fn to_transform(&self) -> Transform {
+        Transform {
+            translation: self.position.extend(self.layer),
+            rotation: glam::Quat::from_rotation_z(self.rotation),
+            scale: self.scale.extend(1.),
+        }
+    }
+
+    #[inline]
+    pub fn to_matrix(&self) -> glam::Mat4 {
+        self.to_transform().to_matrix()
+    }
+}
+
+//--------------------------------------------------
+
+impl From<glam::Vec2> for Transform2d {
+    #[inline]
+    fn from(position: glam::Vec2) -> Self {
+        Self::from_position(position)
+    }
+}
+
+impl From<Transform2d> for Transform {
+    #[inline]
+    fn from(transform: Transform2d) -> Self {
+        transform.to_transform()
+    }
+}
+
+impl From<Transform2d> for glam::Mat4 {
+    #[inline]
+    fn from(transform: Transform2d) -> Self {
+        transform.to_matrix()
+    }
+}
+
+//====================================================================
+
+fn sys_sync_transform2d(
+    entities: EntitiesView,
+    v_transform2d: View<Transform2d>,
+    mut vm_transform: ViewMut<Transform>,
+) {
+    v_transform2d
+        .inserted_or_modified()
+        .iter()
+        .with_id()
+        .for_each(|(id, transform2d)| {
+            let transform = transform2d.to_transform();
+
+            match vm_transform.contains(id) {
+                true => *(&mut vm_transform).get(id).unwrap() = transform,
+                false => entities.add_component(id, &mut vm_transform, transform),
+            }
+        });
+}
+
+//====================================================================
@@ -0,0 +1,315 @@
+//====================================================================
+
+use std::{collections::VecDeque, f32::consts::PI, time::Duration};
+
+use cabat_runner::tools::Time;
+use cabat_shipyard::prelude::*;
+use shipyard::{Component, EntityId, IntoIter, IntoWithId, ViewMut};
+
+use crate::Transform;
+
+//====================================================================
+
+pub struct TweenPlugin;
+
+impl Plugin for TweenPlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        builder.add_workload(Stages::Update, sys_update_tweens::<Transform>);
+    }
+}
+
+//====================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Ease {
+    #[default]
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+    ExpoIn,
+    ExpoOut,
+    BackOut,
+    ElasticOut,
+    BounceOut,
+}
+
+impl Ease {
+    /// Map linear progress (0 to 1) onto the easing curve.
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0., 1.);
+
+        match self {
+            Ease::Linear => t,
+
+            Ease::QuadIn => t * t,
+            Ease::QuadOut => 1. - (1. - t).powi(2),
+            Ease::QuadInOut => match t < 0.5 {
+                true => 2. * t * t,
+                false => 1. - (-2. * t + 2.).powi(2) / 2.,
+            },
+
+            Ease::CubicIn => t.powi(3),
+            Ease::CubicOut => 1. - (1. - t).powi(3),
+            Ease::CubicInOut => match t < 0.5 {
+                true => 4. * t.powi(3),
+                false => 1. - (-2. * t + 2.).powi(3) / 2.,
+            },
+
+            Ease::SineIn => 1. - (t * PI / 2.).cos(),
+            Ease::SineOut => (t * PI / 2.).sin(),
+            Ease::SineInOut => -((PI * t).cos() - 1.) / 2.,
+
+            Ease::ExpoIn => match t == 0. {
+                true => 0.,
+                false => 2_f32.powf(10. * t - 10.),
+            },
+            Ease::ExpoOut => match t == 1. {
+                true => 1.,
+                false => 1. - 2_f32.powf(-10. * t),
+            },
+
+            Ease::BackOut => {
+                const C1: f32 = 1.70158;
+                const C3: f32 = C1 + 1.;
+                1. + C3 * (t - 1.).powi(3) + C1 * (t - 1.).powi(2)
+            }
+
+            Ease::ElasticOut => match t == 0. || t == 1. {
+                true => t,
+                false => 2_f32.powf(-10. * t) * ((t * 10. - 0.75) * (2. * PI / 3.)).sin() + 1.,
+            },
+
+            Ease::BounceOut => {
+                const N1: f32 = 7.5625;
+                const D1: f32 = 2.75;
+
+                if t < 1. / D1 {
+                    N1 * t * t
+                } else if t < 2. / D1 {
+                    let t = t - 1.5 / D1;
+                    N1 * t * t + 0.75
+                } else if t < 2.5 / D1 {
+                    let t = t - 2.25 / D1;
+                    N1 * t * t + 0.9375
+                } else {
+                    let t = t - 2.625 / D1;
+                    N1 * t * t + 0.984375
+                }
+            }
+        }
+    }
+}
+
+//====================================================================
+
+/// Property of a component a tween animates.
+pub trait Lens<T>: Send + Sync + 'static {
+    /// Set the property to the given (eased) progress between its start and end.
+    fn lerp(&mut self, target: &mut T, ratio: f32);
+}
+
+pub struct TranslationLens {
+    pub start: glam::Vec3,
+    pub end: glam::Vec3,
+}
+
+impl Lens<Transform> for TranslationLens {
+    #[inline]
+    fn lerp(&mut self, target: &mut Transform, ratio: f32) {
+        target.translation = self.start.lerp(self.end, ratio);
+    }
+}
+
+pub struct RotationLens {
+    pub start: glam::Quat,
+    pub end: glam::Quat,
+}
+
+impl Lens<Transform> for RotationLens {
+    #[inline]
+    fn lerp(&mut self, target: &mut Transform, ratio: f32) {
+        target.rotation = self.start.slerp(self.end, ratio);
+    }
+}
+
+pub struct ScaleLens {
+    pub start: glam::Vec3,
+    pub end: glam::Vec3,
+}
+
+impl Lens<Transform> for ScaleLens {
+    #[inline]
+    fn lerp(&mut self, target: &mut Transform, ratio: f32) {
+        target.scale = self.start.lerp(self.end, ratio);
+    }
+}
+
+/// Lens from a closure, for one off properties.
+pub struct FnLens<F>(pub F);
+
+impl<T, F> Lens<T> for FnLens<F>
+where
+    F: FnMut(&mut T, f32) + Send + Sync + 'static,
+{
+    #[inline]
+    fn lerp(&mut self, target: &mut T, ratio: f32) {
+        (self.0)(target, ratio)
+    }
+}
+
+//====================================================================
+
+struct TweenStep<T> {
+    lens: Box<dyn Lens<T>>,
+    duration: Duration,
+    ease: Ease,
+    elapsed: Duration,
+}
+
+/// Animates a component of type `T` on the same entity through a chain of steps.
+/// Each component type needs [sys_update_tweens] registered; [TweenPlugin] does so
+/// for [Transform].
+#[derive(Component)]
+pub struct Tween<T: 'static> {
+    steps: VecDeque<TweenStep<T>>,
+    remaining: usize,
+    looping: bool,
+    tag: u32,
+
+    pub paused: bool,
+}
+
+impl<T: 'static> Tween<T> {
+    pub fn new(duration: Duration, ease: Ease, lens: impl Lens<T>) -> Self {
+        Self {
+            steps: VecDeque::new(),
+            remaining: 0,
+            looping: false,
+            tag: 0,
+
+            paused: false,
+        }
+        .then(duration, ease, lens)
+    }
+
+    /// Add a step that starts once the previous ones have finished.
+    pub fn then(mut self, duration: Duration, ease: Ease, lens: impl Lens<T>) -> Self {
+        self.steps.push_back(TweenStep {
+            lens: Box::new(lens),
+            duration,
+            ease,
+            elapsed: Duration::ZERO,
+        });
+        self.remaining += 1;
+        self
+    }
+
+    /// Restart from the first step after the last one finishes. Looping tweens never complete.
+    #[inline]
+    pub fn looping(mut self) -> Self {
+        self.looping = true;
+        self
+    }
+
+    /// User value sent with the [TweenCompletedEvent].
+    #[inline]
+    pub fn with_tag(mut self, tag: u32) -> Self {
+        self.tag = tag;
+        self
+    }
+
+    #[inline]
+    pub fn tag(&self) -> u32 {
+        self.tag
+    }
+
+    #[inline]
+    pub fn finished(&self) -> bool {
+        self.remaining == 0
+    }
+
+    // Returns true when the final step completes
+    fn tick(&mut self, target: &mut T, mut delta: Duration) -> bool {
+        while self.remaining > 0 {
+            let step = &mut self.steps[self.steps.len() - self.remaining];
+
+            let available = step.duration.saturating_sub(step.elapsed);
+            let used = delta.min(available);
+
+            step.elapsed += used;
+            delta -= used;
+
+            let ratio = match step.duration.is_zero() {
+                true => 1.,
+                false => step.elapsed.as_secs_f32() / step.duration.as_secs_f32(),
+            };
+            step.lens.lerp(target, step.ease.apply(ratio));
+
+            if step.elapsed < step.duration {
+                return false;
+            }
+
+            self.remaining -= 1;
+
+            if self.remaining == 0 && self.looping {
+                self.steps
+                    .iter_mut()
+                    .for_each(|step| step.elapsed = Duration::ZERO);
+                self.remaining = self.steps.len();
+
+                // Zero length loops would never consume the delta
+                if delta.is_zero() || self.steps.iter().all(|step| step.duration.is_zero()) {
+                    return false;
+                }
+            }
+        }
+
+        !self.looping
+    }
+}
+
+//====================================================================
+
+/// Tweens that finished this frame, with their entity and tag.
+#[derive(Event, Debug, Clone)]
+pub struct TweenCompletedEvent(Vec<(EntityId, u32)>);
+
+impl TweenCompletedEvent {
+    #[inline]
+    pub fn completed(&self) -> &[(EntityId, u32)] {
+        &self.0
+    }
+}
+
+pub fn sys_update_tweens<T: Component + Send + Sync>(
+    time: Res<Time>,
+    mut event_handler: ResMut<EventHandler>,
+    mut vm_tween: ViewMut<Tween<T>>,
+    mut vm_target: ViewMut<T>,
+) {
+    let delta = *time.delta();
+
+    let completed = (&mut vm_tween, &mut vm_target)
+        .iter()
+        .with_id()
+        .filter_map(|(id, (tween, target))| {
+            match !tween.paused && !tween.finished() && tween.tick(target, delta) {
+                true => Some((id, tween.tag)),
+                false => None,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if !completed.is_empty() {
+        event_handler.add_event(TweenCompletedEvent(completed));
+    }
+}
+
+//====================================================================
@@ -0,0 +1,162 @@
+//====================================================================
+
+use cabat_runner::tools::{Input, InputLayer, MouseButton, MouseInput};
+use cabat_shipyard::prelude::*;
+use shipyard::{Component, EntityId, Get, IntoIter, IntoWithId, Unique, View, ViewMut};
+
+use crate::node::UiNode;
+
+//====================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interaction {
+    #[default]
+    None,
+    Hovered,
+    Pressed,
+}
+
+/// Tracks the mouse over a [UiNode]. Only the deepest shown node under the
+/// cursor is hovered.
+#[derive(Component, Debug, Default)]
+pub struct UiInteraction {
+    state: Interaction,
+    clicked: bool,
+}
+
+impl UiInteraction {
+    #[inline]
+    pub fn state(&self) -> Interaction {
+        self.state
+    }
+
+    #[inline]
+    pub fn hovered(&self) -> bool {
+        self.state != Interaction::None
+    }
+
+    #[inline]
+    pub fn pressed(&self) -> bool {
+        self.state == Interaction::Pressed
+    }
+
+    /// Pressed and then released over the node this frame.
+    #[inline]
+    pub fn clicked(&self) -> bool {
+        self.clicked
+    }
+}
+
+//--------------------------------------------------
+
+#[derive(Unique, Debug, Default)]
+pub struct UiPointer {
+    hovered: Option<EntityId>,
+}
+
+impl UiPointer {
+    /// Interactive node currently under the cursor.
+    #[inline]
+    pub fn hovered(&self) -> Option<EntityId> {
+        self.hovered
+    }
+
+    #[inline]
+    pub fn over_ui(&self) -> bool {
+        self.hovered.is_some()
+    }
+}
+
+/// Node receiving keyboard input. Clicking an interactive node focuses it and
+/// clicking anywhere else clears the focus. Hidden nodes lose focus.
+#[derive(Unique, Debug, Default)]
+pub struct UiFocus {
+    focused: Option<EntityId>,
+}
+
+impl UiFocus {
+    #[inline]
+    pub fn focused(&self) -> Option<EntityId> {
+        self.focused
+    }
+
+    #[inline]
+    pub fn is_focused(&self, id: EntityId) -> bool {
+        self.focused == Some(id)
+    }
+
+    #[inline]
+    pub fn focus(&mut self, id: EntityId) {
+        self.focused = Some(id);
+    }
+
+    #[inline]
+    pub fn blur(&mut self) {
+        self.focused = None;
+    }
+}
+
+//====================================================================
+
+pub(crate) fn sys_update_interaction(
+    mouse: Res<MouseInput>,
+    mut buttons: ResMut<Input<MouseButton>>,
+    mut pointer: ResMut<UiPointer>,
+    mut focus: ResMut<UiFocus>,
+    v_node: View<UiNode>,
+    mut vm_interaction: ViewMut<UiInteraction>,
+) {
+    let pos = mouse.pos();
+
+    let hovered = (&v_node, &vm_interaction)
+        .iter()
+        .with_id()
+        .filter(|(_, (node, _))| node.shown() && node.rect().contains(pos.x, pos.y))
+        .max_by_key(|(_, (node, _))| node.depth())
+        .map(|(id, _)| id);
+
+    pointer.hovered = hovered;
+
+    let held = buttons.pressed(MouseButton::Left);
+    let just_pressed = buttons.just_pressed(MouseButton::Left);
+
+    if just_pressed {
+        focus.focused = hovered;
+
+        // Presses on the ui stay hidden from gameplay until released
+        if hovered.is_some() {
+            buttons.consume(MouseButton::Left, InputLayer::UI);
+        }
+    }
+
+    if let Some(focused) = focus.focused {
+        if !v_node.get(focused).map_or(false, |node| node.shown()) {
+            focus.focused = None;
+        }
+    }
+
+    (&mut vm_interaction)
+        .iter()
+        .with_id()
+        .for_each(|(id, interaction)| {
+            let is_hovered = hovered == Some(id);
+            interaction.clicked = false;
+
+            interaction.state = match interaction.state {
+                // Stay pressed while the button is held, even if the cursor leaves the node
+                Interaction::Pressed if held => Interaction::Pressed,
+                Interaction::Pressed => {
+                    interaction.clicked = is_hovered;
+                    match is_hovered {
+                        true => Interaction::Hovered,
+                        false => Interaction::None,
+                    }
+                }
+                _ if is_hovered && just_pressed => Interaction::Pressed,
+                _ if is_hovered => Interaction::Hovered,
+                _ => Interaction::None,
+            };
+        });
+}
+
+//====================================================================
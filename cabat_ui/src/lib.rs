@@ -0,0 +1,54 @@
+//====================================================================
+
+use cabat_shipyard::{prelude::*, UniqueTools};
+use shipyard::IntoWorkload;
+
+pub mod interaction;
+pub mod node;
+pub mod text_input;
+
+pub use interaction::{Interaction, UiFocus, UiInteraction, UiPointer};
+pub use node::{SizeConstraints, UiAlign, UiContainer, UiDirection, UiNode, UiRect};
+pub use text_input::{TextInput, TextInputChanged, TextInputSubmitted};
+
+//====================================================================
+
+/// Lays out [UiNode] trees each frame and tracks mouse interaction with them.
+/// Any NineSlice or Text2dBuffer on a node is placed over its computed rect.
+/// Keyboard input goes to the [UiFocus] node, which is how [TextInput]s are edited.
+pub struct UiPlugin;
+
+impl Plugin for UiPlugin {
+    fn build(self, builder: &WorkloadBuilder) {
+        builder
+            .insert_default::<UiPointer>()
+            .insert_default::<UiFocus>()
+            .insert_default::<text_input::TextInputState>()
+            .add_event_buffer::<TextInputChanged>()
+            .add_event_buffer::<TextInputSubmitted>()
+            // Interaction uses last frame's layout - the one currently on screen
+            .add_workload_pre(
+                Stages::Update,
+                (
+                    interaction::sys_update_interaction,
+                    text_input::sys_focus_text_input,
+                    text_input::sys_place_text_cursor,
+                    text_input::sys_edit_text_input,
+                )
+                    .into_sequential_workload(),
+            )
+            // Layout must finish before the renderers prepare in Update last
+            .add_workload_post(
+                Stages::Update,
+                (
+                    node::sys_layout_ui,
+                    node::sys_sync_ui_renderers,
+                    text_input::sys_sync_text_input,
+                    text_input::sys_update_text_caret,
+                )
+                    .into_sequential_workload(),
+            );
+    }
+}
+
+//====================================================================
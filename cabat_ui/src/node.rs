@@ -0,0 +1,520 @@
+//====================================================================
+
+use std::collections::HashSet;
+
+use cabat_common::{Anchor, Color, Size, UiPosition, UiVal, WindowScale, WindowSize};
+use cabat_renderer::{
+    accessibility::AccessibilitySettings, nine_slice::NineSlice, shared::SortKey,
+    text::Text2dBuffer, visibility::Visibility,
+};
+use cabat_shipyard::prelude::*;
+use shipyard::{Component, EntityId, Get, IntoIter, IntoWithId, View, ViewMut};
+
+//====================================================================
+
+// Guards against containers that (indirectly) contain themselves
+const MAX_DEPTH: u32 = 64;
+
+//====================================================================
+
+/// Screen space rectangle in physical pixels, from the top left of the window.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct UiRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl UiRect {
+    #[inline]
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[inline]
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height
+    }
+
+    #[inline]
+    pub fn size(&self) -> Size<f32> {
+        Size::new(self.width, self.height)
+    }
+
+    #[inline]
+    fn shrink(&self, amount: f32) -> Self {
+        Self {
+            x: self.x + amount,
+            y: self.y + amount,
+            width: (self.width - amount * 2.).max(0.),
+            height: (self.height - amount * 2.).max(0.),
+        }
+    }
+}
+
+//--------------------------------------------------
+
+/// Limits applied to a node's size after it has been resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SizeConstraints {
+    pub min_width: Option<UiVal>,
+    pub max_width: Option<UiVal>,
+    pub min_height: Option<UiVal>,
+    pub max_height: Option<UiVal>,
+}
+
+impl SizeConstraints {
+    fn apply(&self, size: Size<f32>, parent: Size<f32>, scale_factor: f32) -> Size<f32> {
+        let clamp = |value: f32, min: Option<UiVal>, max: Option<UiVal>, extent: f32| {
+            let value = match max {
+                Some(max) => value.min(max.resolve(extent, scale_factor)),
+                None => value,
+            };
+
+            match min {
+                Some(min) => value.max(min.resolve(extent, scale_factor)),
+                None => value,
+            }
+        };
+
+        Size::new(
+            clamp(size.width, self.min_width, self.max_width, parent.width),
+            clamp(size.height, self.min_height, self.max_height, parent.height),
+        )
+    }
+}
+
+//====================================================================
+
+/// Element of the UI tree. Nodes that aren't the child of a [UiContainer] are
+/// placed relative to the window using their position, children are placed by
+/// their container.
+///
+/// Hidden nodes (and their children) are skipped by interaction and their panels
+/// and text aren't drawn. In high contrast mode panels are drawn with the palette
+/// background instead of their own color.
+#[derive(Component, Debug, Clone)]
+pub struct UiNode {
+    pub position: UiPosition,
+    pub width: UiVal,
+    pub height: UiVal,
+    pub constraints: SizeConstraints,
+    /// Share of the space left over along the parent container's direction.
+    pub grow: f32,
+    pub visible: bool,
+
+    rect: UiRect,
+    depth: u32,
+    shown: bool,
+    // Panel color to restore once high contrast is turned off
+    panel_color: Option<Color>,
+}
+
+impl UiNode {
+    pub fn new(width: impl Into<UiVal>, height: impl Into<UiVal>) -> Self {
+        Self {
+            position: UiPosition::default(),
+            width: width.into(),
+            height: height.into(),
+            constraints: SizeConstraints::default(),
+            grow: 0.,
+            visible: true,
+
+            rect: UiRect::default(),
+            depth: 0,
+            shown: false,
+            panel_color: None,
+        }
+    }
+
+    #[inline]
+    pub fn with_position(mut self, position: UiPosition) -> Self {
+        self.position = position;
+        self
+    }
+
+    #[inline]
+    pub fn with_constraints(mut self, constraints: SizeConstraints) -> Self {
+        self.constraints = constraints;
+        self
+    }
+
+    #[inline]
+    pub fn with_grow(mut self, grow: f32) -> Self {
+        self.grow = grow;
+        self
+    }
+
+    /// Layout computed this frame.
+    #[inline]
+    pub fn rect(&self) -> UiRect {
+        self.rect
+    }
+
+    /// How deeply nested the node is. Deeper nodes are drawn on top.
+    #[inline]
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Whether the node and all of its parents are visible.
+    #[inline]
+    pub fn shown(&self) -> bool {
+        self.shown
+    }
+
+    fn resolve_size(&self, parent: Size<f32>, scale_factor: f32) -> Size<f32> {
+        let size = Size::new(
+            self.width.resolve(parent.width, scale_factor),
+            self.height.resolve(parent.height, scale_factor),
+        );
+
+        self.constraints.apply(size, parent, scale_factor)
+    }
+}
+
+//--------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UiDirection {
+    #[default]
+    Vertical,
+    Horizontal,
+}
+
+/// Placement of children across the container direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UiAlign {
+    #[default]
+    Start,
+    Center,
+    End,
+    Stretch,
+}
+
+/// Lays its children out one after another, similar to a flexbox.
+#[derive(Component, Debug, Clone, Default)]
+pub struct UiContainer {
+    pub children: Vec<EntityId>,
+    pub direction: UiDirection,
+    pub align: UiAlign,
+    pub spacing: UiVal,
+    pub padding: UiVal,
+}
+
+impl UiContainer {
+    #[inline]
+    pub fn vertical(children: Vec<EntityId>) -> Self {
+        Self {
+            children,
+            direction: UiDirection::Vertical,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn horizontal(children: Vec<EntityId>) -> Self {
+        Self {
+            children,
+            direction: UiDirection::Horizontal,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn with_align(mut self, align: UiAlign) -> Self {
+        self.align = align;
+        self
+    }
+
+    #[inline]
+    pub fn with_spacing(mut self, spacing: impl Into<UiVal>) -> Self {
+        self.spacing = spacing.into();
+        self
+    }
+
+    #[inline]
+    pub fn with_padding(mut self, padding: impl Into<UiVal>) -> Self {
+        self.padding = padding.into();
+        self
+    }
+}
+
+//====================================================================
+
+struct LayoutContext<'a, 'v> {
+    v_container: &'a View<'v, UiContainer>,
+    scale_factor: f32,
+}
+
+impl LayoutContext<'_, '_> {
+    fn layout(
+        &self,
+        vm_node: &mut ViewMut<UiNode>,
+        id: EntityId,
+        rect: UiRect,
+        depth: u32,
+        parent_shown: bool,
+    ) {
+        let shown = {
+            let mut node = match (&mut *vm_node).get(id) {
+                Ok(node) => node,
+                Err(_) => return,
+            };
+
+            node.rect = rect;
+            node.depth = depth;
+            node.shown = parent_shown && node.visible;
+            node.shown
+        };
+
+        let container = match self.v_container.get(id) {
+            Ok(container) => container,
+            Err(_) => return,
+        };
+
+        if depth >= MAX_DEPTH {
+            log::warn!(
+                "Ui tree is nested too deeply - ignoring children of {:?}",
+                id
+            );
+            return;
+        }
+
+        let padding = container.padding.resolve(rect.width, self.scale_factor);
+        let content = rect.shrink(padding);
+
+        let vertical = container.direction == UiDirection::Vertical;
+        let main_extent = match vertical {
+            true => content.height,
+            false => content.width,
+        };
+
+        let spacing = container.spacing.resolve(main_extent, self.scale_factor);
+
+        // Resolve child sizes first so left over space can be shared out
+        let nodes = &*vm_node;
+        let mut children = container
+            .children
+            .iter()
+            .filter_map(|child| {
+                let node = nodes.get(*child).ok()?;
+                let size = node.resolve_size(content.size(), self.scale_factor);
+                Some((*child, size, node.grow.max(0.)))
+            })
+            .collect::<Vec<_>>();
+
+        let used = children
+            .iter()
+            .map(|(_, size, _)| match vertical {
+                true => size.height,
+                false => size.width,
+            })
+            .sum::<f32>()
+            + spacing * children.len().saturating_sub(1) as f32;
+
+        let leftover = (main_extent - used).max(0.);
+        let total_grow = children.iter().map(|(_, _, grow)| grow).sum::<f32>();
+
+        if total_grow > 0. {
+            children.iter_mut().for_each(|(_, size, grow)| {
+                let extra = leftover * *grow / total_grow;
+                match vertical {
+                    true => size.height += extra,
+                    false => size.width += extra,
+                }
+            });
+        }
+
+        let mut offset = 0.;
+
+        children.into_iter().for_each(|(child, size, _)| {
+            let (main, cross, cross_extent) = match vertical {
+                true => (size.height, size.width, content.width),
+                false => (size.width, size.height, content.height),
+            };
+
+            let (cross_offset, cross) = match container.align {
+                UiAlign::Start => (0., cross),
+                UiAlign::Center => ((cross_extent - cross) / 2., cross),
+                UiAlign::End => (cross_extent - cross, cross),
+                UiAlign::Stretch => (0., cross_extent),
+            };
+
+            let child_rect = match vertical {
+                true => UiRect::new(content.x + cross_offset, content.y + offset, cross, main),
+                false => UiRect::new(content.x + offset, content.y + cross_offset, main, cross),
+            };
+
+            offset += main + spacing;
+
+            self.layout(vm_node, child, child_rect, depth + 1, shown);
+        });
+    }
+}
+
+pub(crate) fn sys_layout_ui(
+    size: Res<WindowSize>,
+    scale: Res<WindowScale>,
+    mut vm_node: ViewMut<UiNode>,
+    v_container: View<UiContainer>,
+) {
+    let window = Size::new(size.width_f32(), size.height_f32());
+    let scale_factor = scale.scale_factor();
+
+    let children = v_container
+        .iter()
+        .flat_map(|container| container.children.iter().copied())
+        .collect::<HashSet<_>>();
+
+    let roots = vm_node
+        .iter()
+        .with_id()
+        .filter(|(id, _)| !children.contains(id))
+        .map(|(id, node)| {
+            let size = node.resolve_size(window, scale_factor);
+            let (x, y) = node.position.resolve(window, size, scale_factor);
+
+            (id, UiRect::new(x, y, size.width, size.height))
+        })
+        .collect::<Vec<_>>();
+
+    let context = LayoutContext {
+        v_container: &v_container,
+        scale_factor,
+    };
+
+    roots.into_iter().for_each(|(id, rect)| {
+        context.layout(&mut vm_node, id, rect, 0, true);
+    });
+}
+
+//--------------------------------------------------
+
+// Place panels and text belonging to a node over its computed rect
+pub(crate) fn sys_sync_ui_renderers(
+    scale: Res<WindowScale>,
+    accessibility: Res<AccessibilitySettings>,
+    mut vm_node: ViewMut<UiNode>,
+    mut vm_nine_slice: ViewMut<NineSlice>,
+    mut vm_text: ViewMut<Text2dBuffer>,
+    mut vm_visibility: ViewMut<Visibility>,
+) {
+    let scale_factor = scale.scale_factor();
+    let palette = accessibility.contrast_palette();
+
+    let position = |rect: UiRect| {
+        UiPosition::new(
+            Anchor::TopLeft,
+            rect.x / scale_factor,
+            rect.y / scale_factor,
+        )
+    };
+
+    (&mut vm_node, &mut vm_nine_slice)
+        .iter()
+        .for_each(|(node, panel)| {
+            let rect = node.rect();
+
+            panel.position = position(rect);
+            panel.width = UiVal::Px(rect.width / scale_factor);
+            panel.height = UiVal::Px(rect.height / scale_factor);
+            panel.sort_key = SortKey::layer(node.depth() as i32);
+            panel.visible = node.shown();
+
+            match palette {
+                Some(palette) => {
+                    if node.panel_color.is_none() {
+                        node.panel_color = Some(panel.color);
+                    }
+                    panel.color = palette.background;
+                }
+                None => {
+                    if let Some(color) = node.panel_color.take() {
+                        panel.color = color;
+                    }
+                }
+            }
+        });
+
+    let text_shown = (&vm_node, &mut vm_text)
+        .iter()
+        .with_id()
+        .map(|(id, (node, text))| {
+            let rect = node.rect();
+
+            text.position = position(rect);
+            text.sort_key = SortKey::layer(node.depth() as i32);
+            text.set_size(
+                Some(UiVal::Px(rect.width / scale_factor)),
+                Some(UiVal::Px(rect.height / scale_factor)),
+            );
+
+            (id, node.shown())
+        })
+        .collect::<Vec<_>>();
+
+    // Text is hidden through its visibility, only added once a node is hidden
+    text_shown
+        .into_iter()
+        .for_each(|(id, shown)| match (&mut vm_visibility).get(id) {
+            Ok(mut visibility) => visibility.visible = shown,
+            Err(_) => {
+                if !shown {
+                    vm_visibility.add_component_unchecked(id, Visibility::hidden());
+                }
+            }
+        });
+}
+
+//====================================================================
+
+#[cfg(test)]
+mod tests {
+    use cabat_renderer::text::Text2dBufferDescriptor;
+    use shipyard::World;
+
+    use super::*;
+
+    fn text_visible(world: &World, id: EntityId) -> bool {
+        world.run(|v_visibility: View<Visibility>| {
+            v_visibility
+                .get(id)
+                .map_or(true, |visibility| visibility.is_visible())
+        })
+    }
+
+    #[test]
+    fn hidden_node_hides_text() {
+        let world = World::new();
+        world.add_unique(WindowScale::new(1.));
+        world.add_unique(AccessibilitySettings::default());
+
+        let mut font_system = cosmic_text::FontSystem::new_with_locale_and_db(
+            "en-US".to_string(),
+            cosmic_text::fontdb::Database::new(),
+        );
+        let text = Text2dBuffer::new(&mut font_system, &Text2dBufferDescriptor::default());
+
+        // Nodes aren't shown until they have been laid out
+        let id = world.add_entity((UiNode::new(UiVal::Px(100.), UiVal::Px(50.)), text));
+
+        world.run(sys_sync_ui_renderers);
+        assert!(!text_visible(&world, id));
+
+        world.run(|mut vm_node: ViewMut<UiNode>| {
+            (&mut vm_node).get(id).unwrap().shown = true;
+        });
+
+        world.run(sys_sync_ui_renderers);
+        assert!(text_visible(&world, id));
+    }
+}
+
+//====================================================================
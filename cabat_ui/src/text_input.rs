@@ -0,0 +1,665 @@
+//====================================================================
+
+use std::{borrow::Cow, ops::Range};
+
+use cabat_common::{Anchor, Color, UiPosition, UiVal, WindowScale};
+use cabat_renderer::{
+    accessibility::AccessibilitySettings,
+    default_assets::DefaultRendererAssets,
+    nine_slice::{NineSlice, NineSliceMargins},
+    shared::SortKey,
+    text::{self, Buffer, Cursor, Text2dBuffer, TextFontSystem},
+};
+use cabat_runner::{
+    clipboard::Clipboard,
+    tools::{Input, InputLayer, KeyCode, KeyboardText, MouseButton, MouseInput, Time},
+    window::Window,
+};
+use cabat_shipyard::prelude::*;
+use shipyard::{Component, EntitiesViewMut, EntityId, Get, IntoIter, Unique, View, ViewMut};
+
+use crate::{
+    interaction::{UiFocus, UiInteraction},
+    node::{UiNode, UiRect},
+};
+
+//====================================================================
+
+// Seconds for the caret to blink on and off once
+const CARET_BLINK: f32 = 1.;
+
+//====================================================================
+
+/// Editable single line of text. Needs a [UiNode] to place it, a [UiInteraction]
+/// so it can be clicked into and a [Text2dBuffer] to show it.
+///
+/// Typing goes to the input holding [UiFocus]. Edits are sent as
+/// [TextInputChanged] events and pressing enter sends a [TextInputSubmitted].
+#[derive(Component, Debug, Clone, PartialEq)]
+#[track(All)]
+pub struct TextInput {
+    /// Shown while the input is empty.
+    pub placeholder: String,
+    /// Maximum length in characters.
+    pub max_chars: Option<usize>,
+    pub color: text::Color,
+    pub placeholder_color: text::Color,
+    pub caret_color: Color,
+    pub selection_color: Color,
+
+    value: String,
+    // Byte offsets into the value, always on a char boundary
+    cursor: usize,
+    anchor: Option<usize>,
+    // Text the input method is composing, shown at the cursor
+    preedit: String,
+}
+
+impl Default for TextInput {
+    fn default() -> Self {
+        Self {
+            placeholder: String::new(),
+            max_chars: None,
+            color: text::Color::rgb(0, 0, 0),
+            placeholder_color: text::Color::rgba(0, 0, 0, 128),
+            caret_color: Color::BLACK,
+            selection_color: Color::linear(0.2, 0.4, 1., 0.4),
+
+            value: String::new(),
+            cursor: 0,
+            anchor: None,
+            preedit: String::new(),
+        }
+    }
+}
+
+impl TextInput {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn with_value(mut self, value: impl Into<String>) -> Self {
+        self.set_value(value);
+        self
+    }
+
+    #[inline]
+    pub fn with_placeholder(mut self, placeholder: impl Into<String>) -> Self {
+        self.placeholder = placeholder.into();
+        self
+    }
+
+    #[inline]
+    pub fn with_max_chars(mut self, max_chars: usize) -> Self {
+        self.max_chars = Some(max_chars);
+        self.set_value(std::mem::take(&mut self.value));
+        self
+    }
+
+    #[inline]
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Replace the value, moving the cursor to the end. Doesn't send a
+    /// [TextInputChanged] event.
+    pub fn set_value(&mut self, value: impl Into<String>) {
+        let mut value = value.into();
+
+        if let Some((end, _)) = self
+            .max_chars
+            .and_then(|max_chars| value.char_indices().nth(max_chars))
+        {
+            value.truncate(end);
+        }
+
+        self.cursor = value.len();
+        self.anchor = None;
+        self.value = value;
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.set_value(String::new());
+    }
+
+    /// Byte offset of the cursor in the value.
+    #[inline]
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Byte range of the selected text, if anything is selected.
+    #[inline]
+    pub fn selection(&self) -> Option<Range<usize>> {
+        match self.anchor {
+            Some(anchor) if anchor < self.cursor => Some(anchor..self.cursor),
+            Some(anchor) if anchor > self.cursor => Some(self.cursor..anchor),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn selected_text(&self) -> &str {
+        self.selection().map_or("", |range| &self.value[range])
+    }
+
+    #[inline]
+    pub fn select_all(&mut self) {
+        self.anchor = Some(0);
+        self.cursor = self.value.len();
+    }
+
+    // Value with any composing text inserted at the cursor
+    fn display_text(&self) -> Cow<str> {
+        match self.preedit.is_empty() {
+            true => Cow::Borrowed(&self.value),
+            false => {
+                let mut text = self.value.clone();
+                text.insert_str(self.cursor, &self.preedit);
+                Cow::Owned(text)
+            }
+        }
+    }
+
+    //--------------------------------------------------
+
+    // Move the cursor, extending the selection from where it was when selecting
+    fn move_to(&mut self, index: usize, select: bool) {
+        match select {
+            true => {
+                self.anchor.get_or_insert(self.cursor);
+            }
+            false => self.anchor = None,
+        }
+
+        self.cursor = index;
+    }
+
+    fn delete_selection(&mut self) -> bool {
+        let selection = self.selection();
+        self.anchor = None;
+
+        match selection {
+            Some(range) => {
+                self.cursor = range.start;
+                self.value.replace_range(range, "");
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn insert(&mut self, text: &str) -> bool {
+        let deleted = self.delete_selection();
+
+        let text = match self.max_chars {
+            Some(max_chars) => {
+                let room = max_chars.saturating_sub(self.value.chars().count());
+                match text.char_indices().nth(room) {
+                    Some((end, _)) => &text[..end],
+                    None => text,
+                }
+            }
+            None => text,
+        };
+
+        self.value.insert_str(self.cursor, text);
+        self.cursor += text.len();
+
+        deleted || !text.is_empty()
+    }
+
+    fn backspace(&mut self) -> bool {
+        if self.delete_selection() {
+            return true;
+        }
+
+        if self.cursor == 0 {
+            return false;
+        }
+
+        let start = prev_char(&self.value, self.cursor);
+        self.value.replace_range(start..self.cursor, "");
+        self.cursor = start;
+
+        true
+    }
+
+    fn delete(&mut self) -> bool {
+        if self.delete_selection() {
+            return true;
+        }
+
+        let end = next_char(&self.value, self.cursor);
+        if end == self.cursor {
+            return false;
+        }
+
+        self.value.replace_range(self.cursor..end, "");
+
+        true
+    }
+}
+
+#[inline]
+fn prev_char(text: &str, index: usize) -> usize {
+    text[..index]
+        .char_indices()
+        .next_back()
+        .map_or(0, |(index, _)| index)
+}
+
+#[inline]
+fn next_char(text: &str, index: usize) -> usize {
+    text[index..]
+        .chars()
+        .next()
+        .map_or(index, |c| index + c.len_utf8())
+}
+
+//--------------------------------------------------
+
+#[derive(Event, Debug, Clone)]
+pub struct TextInputChanged {
+    pub entity: EntityId,
+    pub value: String,
+}
+
+#[derive(Event, Debug, Clone)]
+pub struct TextInputSubmitted {
+    pub entity: EntityId,
+    pub value: String,
+}
+
+//--------------------------------------------------
+
+#[derive(Unique, Default)]
+pub(crate) struct TextInputState {
+    // Caret and selection panels, shared by whichever input has focus
+    panels: Option<(EntityId, EntityId)>,
+    // Input the window's input method is currently enabled for
+    ime_target: Option<EntityId>,
+    blink: f32,
+}
+
+//====================================================================
+
+pub(crate) fn sys_focus_text_input(
+    keys: Res<Input<KeyCode>>,
+    window: Option<Res<Window>>,
+    mut focus: ResMut<UiFocus>,
+    mut state: ResMut<TextInputState>,
+    mut vm_input: ViewMut<TextInput>,
+) {
+    if let Some(focused) = focus.focused() {
+        if vm_input.contains(focused) && keys.layer(InputLayer::UI).just_pressed(KeyCode::Escape) {
+            focus.blur();
+        }
+    }
+
+    let focused = focus.focused().filter(|id| vm_input.contains(*id));
+
+    if focused == state.ime_target {
+        return;
+    }
+
+    // Leaving an input drops its selection and any half composed text
+    if let Some(previous) = state.ime_target {
+        if let Ok(mut input) = (&mut vm_input).get(previous) {
+            input.anchor = None;
+            input.preedit.clear();
+        }
+    }
+
+    if let Some(window) = &window {
+        window.set_ime_allowed(focused.is_some());
+    }
+
+    state.ime_target = focused;
+    state.blink = 0.;
+}
+
+// Click to move the cursor, drag to select
+pub(crate) fn sys_place_text_cursor(
+    mouse: Res<MouseInput>,
+    buttons: Res<Input<MouseButton>>,
+    focus: Res<UiFocus>,
+    mut state: ResMut<TextInputState>,
+    v_node: View<UiNode>,
+    v_interaction: View<UiInteraction>,
+    v_text: View<Text2dBuffer>,
+    mut vm_input: ViewMut<TextInput>,
+) {
+    let id = match focus.focused() {
+        Some(id) => id,
+        None => return,
+    };
+
+    let select = !buttons.just_pressed(MouseButton::Left);
+
+    let index = match (&v_node, &v_interaction, &v_text, &vm_input).get(id) {
+        Ok((node, interaction, text, input))
+            if interaction.pressed() && input.preedit.is_empty() =>
+        {
+            let rect = node.rect();
+            let pos = mouse.pos();
+            let scale = text.scale_factor();
+
+            let index = match input.value.is_empty() {
+                true => Some(0),
+                false => text
+                    .buffer
+                    .hit((pos.x - rect.x) / scale, (pos.y - rect.y) / scale)
+                    .map(|cursor| cursor.index.min(input.value.len()))
+                    .filter(|index| input.value.is_char_boundary(*index)),
+            };
+
+            match index {
+                Some(index) if index != input.cursor || (!select && input.anchor.is_some()) => {
+                    index
+                }
+                _ => return,
+            }
+        }
+        _ => return,
+    };
+
+    if let Ok(mut input) = (&mut vm_input).get(id) {
+        input.move_to(index, select);
+        state.blink = 0.;
+    }
+}
+
+pub(crate) fn sys_edit_text_input(
+    time: Res<Time>,
+    mut keys: ResMut<Input<KeyCode>>,
+    keyboard: Res<KeyboardText>,
+    focus: Res<UiFocus>,
+    mut clipboard: NonSendResMut<Clipboard>,
+    mut state: ResMut<TextInputState>,
+    mut vm_input: ViewMut<TextInput>,
+    mut changed: EventWriter<TextInputChanged>,
+    mut submitted: EventWriter<TextInputSubmitted>,
+) {
+    state.blink += time.delta_seconds();
+
+    let id = match focus.focused() {
+        Some(id) => id,
+        None => return,
+    };
+
+    // Edit a copy so untouched inputs aren't flagged as modified
+    let mut input = match vm_input.get(id) {
+        Ok(input) => input.clone(),
+        Err(_) => return,
+    };
+
+    // Typing shouldn't also trigger gameplay shortcuts
+    keys.consume_all(InputLayer::UI);
+
+    // Keys consumed by earlier layers, such as the console, aren't typed here
+    let keys = keys.layer(InputLayer::UI);
+
+    let shift = keys.pressed(KeyCode::ShiftLeft) || keys.pressed(KeyCode::ShiftRight);
+    let ctrl = keys.pressed(KeyCode::ControlLeft) || keys.pressed(KeyCode::ControlRight);
+
+    if ctrl && keys.just_pressed(KeyCode::KeyA) {
+        input.select_all();
+    }
+
+    if keys.just_pressed(KeyCode::ArrowLeft) {
+        match (shift, input.selection()) {
+            (false, Some(range)) => input.move_to(range.start, false),
+            _ => input.move_to(prev_char(&input.value, input.cursor), shift),
+        }
+    }
+
+    if keys.just_pressed(KeyCode::ArrowRight) {
+        match (shift, input.selection()) {
+            (false, Some(range)) => input.move_to(range.end, false),
+            _ => input.move_to(next_char(&input.value, input.cursor), shift),
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Home) {
+        input.move_to(0, shift);
+    }
+
+    if keys.just_pressed(KeyCode::End) {
+        input.move_to(input.value.len(), shift);
+    }
+
+    let mut edited = false;
+
+    if ctrl && (keys.just_pressed(KeyCode::KeyC) || keys.just_pressed(KeyCode::KeyX)) {
+        if input.selection().is_some() {
+            clipboard.set_text(input.selected_text());
+
+            if keys.just_pressed(KeyCode::KeyX) {
+                edited |= input.delete_selection();
+            }
+        }
+    }
+
+    if ctrl && keys.just_pressed(KeyCode::KeyV) {
+        if let Some(text) = clipboard.get_text() {
+            // Inputs are a single line
+            let text = text
+                .chars()
+                .map(|c| if c == '\n' { ' ' } else { c })
+                .filter(|c| !c.is_control())
+                .collect::<String>();
+
+            edited |= input.insert(&text);
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Backspace) {
+        edited |= input.backspace();
+    }
+
+    if keys.just_pressed(KeyCode::Delete) {
+        edited |= input.delete();
+    }
+
+    // Shortcuts can produce text on some platforms
+    if !ctrl && !keyboard.is_empty() {
+        edited |= input.insert(keyboard.as_str());
+    }
+
+    input.preedit = keyboard
+        .preedit()
+        .map(|preedit| preedit.text.clone())
+        .unwrap_or_default();
+
+    if edited {
+        changed.send(TextInputChanged {
+            entity: id,
+            value: input.value.clone(),
+        });
+    }
+
+    if keys.just_pressed(KeyCode::Enter) || keys.just_pressed(KeyCode::NumpadEnter) {
+        submitted.send(TextInputSubmitted {
+            entity: id,
+            value: input.value.clone(),
+        });
+    }
+
+    if let Ok(mut current) = (&mut vm_input).get(id) {
+        if *current != input {
+            if current.cursor != input.cursor || current.value != input.value {
+                state.blink = 0.;
+            }
+
+            *current = input;
+        }
+    }
+}
+
+//--------------------------------------------------
+
+pub(crate) fn sys_sync_text_input(
+    font_system: Option<ResMut<TextFontSystem>>,
+    v_input: View<TextInput>,
+    mut vm_text: ViewMut<Text2dBuffer>,
+) {
+    let mut font_system = match font_system {
+        Some(font_system) => font_system,
+        None => return,
+    };
+
+    (v_input.inserted_or_modified(), &mut vm_text)
+        .iter()
+        .for_each(
+            |(input, text)| match input.value.is_empty() && input.preedit.is_empty() {
+                true => {
+                    text.set_text(font_system.inner_mut(), &input.placeholder);
+                    text.color = input.placeholder_color;
+                }
+                false => {
+                    text.set_text(font_system.inner_mut(), &input.display_text());
+                    text.color = input.color;
+                }
+            },
+        );
+}
+
+// Runs after the text has been placed over its node
+pub(crate) fn sys_update_text_caret(
+    scale: Res<WindowScale>,
+    accessibility: Res<AccessibilitySettings>,
+    focus: Res<UiFocus>,
+    window: Option<Res<Window>>,
+    assets: Option<Res<DefaultRendererAssets>>,
+    mut state: ResMut<TextInputState>,
+    mut entities: EntitiesViewMut,
+    v_node: View<UiNode>,
+    v_text: View<Text2dBuffer>,
+    v_input: View<TextInput>,
+    mut vm_nine_slice: ViewMut<NineSlice>,
+) {
+    // Panels are created once the default white texture is available
+    if state.panels.is_none() {
+        let assets = match &assets {
+            Some(assets) => assets,
+            None => return,
+        };
+
+        let mut panel = || {
+            let panel = NineSlice::new(
+                assets.white_texture.clone(),
+                NineSliceMargins::default(),
+                UiPosition::default(),
+                0.,
+                0.,
+            );
+
+            entities.add_entity(
+                &mut vm_nine_slice,
+                NineSlice {
+                    visible: false,
+                    ..panel
+                },
+            )
+        };
+
+        state.panels = Some((panel(), panel()));
+    }
+
+    let (caret, selection) = state.panels.unwrap();
+    let scale_factor = scale.scale_factor();
+
+    let target = focus
+        .focused()
+        .and_then(|id| (&v_node, &v_text, &v_input).get(id).ok())
+        .filter(|(node, ..)| node.shown());
+
+    let (caret_rect, selection_rect, depth, input) = match target {
+        Some((node, text, input)) => {
+            let rect = node.rect();
+            let text_scale = text.scale_factor();
+
+            let to_screen = |span: UiRect| {
+                UiRect::new(
+                    rect.x + span.x * text_scale,
+                    rect.y + span.y * text_scale,
+                    span.width * text_scale,
+                    span.height * text_scale,
+                )
+            };
+
+            let caret_index = input.cursor + input.preedit.len();
+            let mut caret_rect = to_screen(text_span(&text.buffer, caret_index, caret_index));
+            caret_rect.width = scale_factor;
+
+            if let Some(window) = &window {
+                window.set_ime_cursor_area(
+                    caret_rect.x,
+                    caret_rect.y,
+                    caret_rect.width,
+                    caret_rect.height,
+                );
+            }
+
+            let selection_rect = input
+                .selection()
+                .filter(|_| input.preedit.is_empty())
+                .map(|range| to_screen(text_span(&text.buffer, range.start, range.end)));
+
+            let caret_rect = (state.blink % CARET_BLINK < CARET_BLINK / 2.).then_some(caret_rect);
+
+            (caret_rect, selection_rect, node.depth(), Some(input))
+        }
+        None => (None, None, 0, None),
+    };
+
+    let mut place = |id: EntityId, rect: Option<UiRect>, color: Option<Color>| {
+        if let Ok(mut panel) = (&mut vm_nine_slice).get(id) {
+            panel.visible = rect.is_some();
+
+            if let (Some(rect), Some(color)) = (rect, color) {
+                panel.position = UiPosition::new(
+                    Anchor::TopLeft,
+                    rect.x / scale_factor,
+                    rect.y / scale_factor,
+                );
+                panel.width = UiVal::Px(rect.width / scale_factor);
+                panel.height = UiVal::Px(rect.height / scale_factor);
+                panel.color = color;
+                panel.sort_key = SortKey::new(depth as i32, 1.);
+            }
+        }
+    };
+
+    let (caret_color, selection_color) = match accessibility.contrast_palette() {
+        Some(palette) => (
+            input.map(|_| palette.text),
+            input.map(|_| palette.accent.with_alpha(0.5)),
+        ),
+        None => (
+            input.map(|input| input.caret_color),
+            input.map(|input| input.selection_color),
+        ),
+    };
+
+    place(caret, caret_rect, caret_color);
+    place(selection, selection_rect, selection_color);
+}
+
+// Area covered by the text between two byte offsets, relative to the buffer in
+// its own units. Falls back to an empty span at the start of the first line.
+fn text_span(buffer: &Buffer, start: usize, end: usize) -> UiRect {
+    buffer
+        .layout_runs()
+        .find_map(|run| {
+            run.highlight(Cursor::new(run.line_i, start), Cursor::new(run.line_i, end))
+                .map(|(x, width)| UiRect::new(x, run.line_top, width, run.line_height))
+        })
+        .unwrap_or_else(|| UiRect::new(0., 0., 0., buffer.metrics().line_height))
+}
+
+//====================================================================
@@ -1,13 +1,13 @@
 //====================================================================
 
 use cabat::DefaultPlugins;
-use cabat_runner::Runner;
+use cabat_runner::{LaunchConfig, Runner};
 
 //====================================================================
 
 fn main() {
-    Runner::run(|builder| {
-        builder.add_plugin(DefaultPlugins);
+    Runner::run_with_config(LaunchConfig::from_env().unwrap(), |builder| {
+        builder.add_plugins(DefaultPlugins);
     });
 }
 
@@ -3,7 +3,7 @@
 use std::path::PathBuf;
 
 use cabat::{assets::AssetStorage, DefaultPlugins};
-use cabat_runner::Runner;
+use cabat_runner::{LaunchConfig, Runner};
 use cabat_shipyard::prelude::*;
 use shipyard::AllStoragesView;
 
@@ -16,9 +16,9 @@ fn main() {
         .format_timestamp(None)
         .init();
 
-    Runner::run(|builder| {
+    Runner::run_with_config(LaunchConfig::from_env().unwrap(), |builder| {
         builder
-            .add_plugin(DefaultPlugins)
+            .add_plugins(DefaultPlugins)
             .add_workload(Stages::Setup, sys_load_stuff);
     });
 }
@@ -11,7 +11,7 @@ use cabat::{
 use cabat_common::{WindowResizeEvent, WindowSize};
 use cabat_renderer::{
     camera::MainCamera,
-    text::{Text3dBuffer, Text3dBufferDescriptor, Text3dRenderer, TextFontSystem},
+    text::{Text3dBuffer, Text3dBufferDescriptor, TextFontSystem},
     Device, Queue,
 };
 use cabat_shipyard::{prelude::*, UniqueTools};
@@ -28,11 +28,11 @@ fn main() {
         .format_timestamp(None)
         .init();
 
-    Runner::run(|builder| {
+    Runner::run_with_config(LaunchConfig::from_env().unwrap(), |builder| {
         builder.insert(Camera::default());
 
         builder
-            .add_plugin(DefaultPlugins)
+            .add_plugins(DefaultPlugins)
             .add_workload(Stages::Setup, sys_setup_entities)
             .add_workload(
                 Stages::Update,
@@ -62,7 +62,6 @@ struct RotatePoint {
 fn sys_setup_entities(
     mut entities: EntitiesViewMut,
     device: Res<Device>,
-    mut renderer: ResMut<Text3dRenderer>,
     mut font_system: ResMut<TextFontSystem>,
 
     mut vm_pos: ViewMut<Transform>,
@@ -75,7 +74,6 @@ fn sys_setup_entities(
             Transform::from_translation(glam::Vec3::ZERO),
             Text3dBuffer::new(
                 device.inner(),
-                &mut renderer,
                 font_system.inner_mut(),
                 &Text3dBufferDescriptor {
                     text: "Hello World! 12345 \nABCDE",
@@ -1,8 +1,8 @@
 //====================================================================
 
-use cabat::{assets::AssetStorage, DefaultPlugins};
+use cabat::{assets::AssetStorage, common::Color, DefaultPlugins};
 use cabat_renderer::texture3d_renderer::Sprite;
-use cabat_runner::{tools::Time, Runner};
+use cabat_runner::{tools::Time, LaunchConfig, Runner};
 use cabat_shipyard::{Res, ResMut, Stages};
 use cabat_spatial::Transform;
 use shipyard::{AllStoragesView, Component, EntitiesViewMut, IntoIter, ViewMut};
@@ -16,9 +16,9 @@ fn main() {
         .format_timestamp(None)
         .init();
 
-    Runner::run(|builder| {
+    Runner::run_with_config(LaunchConfig::from_env().unwrap(), |builder| {
         builder
-            .add_plugin(DefaultPlugins)
+            .add_plugins(DefaultPlugins)
             .add_workload(Stages::Setup, sys_spawn_entities)
             .add_workload(Stages::Update, sys_spin);
     });
@@ -51,7 +51,7 @@ fn sys_spawn_entities(
                 texture: None,
                 width: 40.,
                 height: 40.,
-                color: [1., 0., 0., 1.],
+                color: Color::RED,
             },
             Transform::from_translation(glam::Vec3::new(0., 0., 50.)),
             Spin::default(),
@@ -65,7 +65,7 @@ fn sys_spawn_entities(
                 texture: Some(handle),
                 width: 20.,
                 height: 20.,
-                color: [1., 1., 1., 1.],
+                color: Color::WHITE,
             },
             Transform::from_translation(glam::Vec3::new(0., 0., 35.)),
             Spin { progress: 0.6 },
@@ -1,61 +1,159 @@
 //====================================================================
 
-use cabat_shipyard::Plugin;
+use cabat_shipyard::{PluginGroup, PluginGroupBuilder};
 
 //====================================================================
 
+pub mod audio {
+    pub use cabat_audio::{
+        AudioEmitter, AudioListener, AudioLoader, AudioManager, AudioPlugin, AudioSource,
+        PlaybackSettings, SoundId,
+    };
+}
+
 pub mod common {
-    pub use cabat_common::{Size, WindowResizeEvent, WindowSize};
+    pub use cabat_common::{
+        Anchor, Color, LaunchConfig, LaunchConfigError, ScaleFactorChangedEvent, Size, UiPosition,
+        UiVal, WindowResizeEvent, WindowScale, WindowSize,
+    };
+}
+
+pub mod debug {
+    pub use cabat_debug::{
+        AddConsoleCommand, CommandResult, Console, ConsoleArgs, ConsolePlugin, ConsoleSettings,
+        DebugOverlayPlugin, DebugOverlaySettings, DiagnosticsPlugin, FrameSpikeEvent, FrameStats,
+        LogCapture, Logger,
+    };
+
+    #[cfg(feature = "renderdoc")]
+    pub use cabat_debug::{
+        CaptureNextFrame, FrameCapture, FrameCapturePlugin, FrameCaptureSettings,
+    };
+}
+
+pub mod net {
+    pub use cabat_net::{
+        Interpolate, Interpolated, MessageReceivedEvent, NetMessage, NetPlugin, NetRole, NetSender,
+        NetSettings, NetSocket, PeerConnectedEvent, PeerDisconnectedEvent, PeerId,
+        RegisterNetMessage, RegisterReplicated, Replicated, ReplicatedEntities, ReplicationMessage,
+        ReplicationPlugin, ReplicationSettings, ServerEntity,
+    };
 }
 
 pub mod renderer {
     pub use cabat_renderer::{
-        camera::{Camera, CameraUniform, OrthographicCamera, PerspectiveCamera},
-        crates, plugins, render_tools, shared, text, texture, texture3d_renderer, ClearColor,
-        Device, FullRendererPlugin, Queue, RenderEncoder, RenderPass, RenderPassDesc, Surface,
-        SurfaceConfig, Vertex,
+        accessibility::{AccessibilityChangedEvent, AccessibilitySettings, HighContrastPalette},
+        atlas::{AtlasRect, RuntimeAtlas},
+        camera::{
+            Camera, CameraProjection, CameraUniform, Frustum, OrthographicCamera,
+            PerspectiveCamera, SceneCamera, Tonemapping, Viewport,
+        },
+        crates,
+        decal::Decal,
+        default_assets::DefaultRendererAssets,
+        environment::{Environment, EnvironmentSettings, FogMode},
+        gizmo::{GizmoPlugin, GizmoSettings, Gizmos},
+        letterbox::{FixedAspect, LetterboxPlugin},
+        lights::{Lights, PointLight},
+        mesh::{Mesh, MeshBuilder, MeshData, MeshVertex},
+        nine_slice::{NineSlice, NineSliceMargins, NineSlicePlugin},
+        occlusion::OcclusionCulled,
+        outline::{OutlineSettings, Outlined},
+        pixel_perfect::{PixelPerfect, PixelPerfectPlugin},
+        plugins,
+        procedural::{GradientDirection, NoiseKind, Pattern, ProceduralTexture},
+        reflection_probe::{ProbeRefresh, ReflectionProbe},
+        render_graph, render_target, render_tools,
+        settings::{
+            GpuInfo, GpuSettings, RendererSettings, SurfaceFormatChangedEvent, SurfaceFormats,
+        },
+        shared,
+        ssao::{SsaoPlugin, SsaoSettings},
+        terrain::{Heightmap, Terrain, TerrainLighting, TerrainMaterial, TerrainPlugin},
+        text, texture,
+        texture3d_renderer::{self, ArrayBatched, SpriteArraySettings},
+        texture_array::TextureArray,
+        visibility::{RenderLayers, Visibility},
+        ClearColor, Device, DrawCounts, FullRendererPlugin, Queue, RenderEncoder, RenderFrameStats,
+        RenderPass, RenderPassDesc, RenderStats, Surface, SurfaceConfig, Vertex,
     };
+
+    #[cfg(feature = "video")]
+    pub use cabat_renderer::video::{VideoError, VideoPlayer, VideoPlugin};
 }
 
 pub mod runner {
     pub use cabat_runner::{
+        clipboard::Clipboard,
+        monitor::{MonitorInfo, Monitors, MonitorsChangedEvent, VideoMode},
+        replay::{InputEvent, InputRecorder, InputRecording, RecordedInput},
+        task_pool::{TaskPool, TaskPoolSettings},
         tools,
-        tools::ToolsPlugin,
-        window::{sys_add_window, sys_resize, Window},
-        Runner,
+        tools::{
+            ImePreedit, InputLayer, KeyboardText, LayerInput, Modifier, Rng, RngStream, Stopwatch,
+            Timer, TimerMode, ToolsPlugin,
+        },
+        window::{
+            sys_add_window, sys_rescale, sys_resize, BackgroundPolicy, MouseMode, Window,
+            WindowFocus,
+        },
+        HeadlessRunner, LaunchConfig, Runner,
     };
 }
 
 pub mod shipyard_tools {
     pub use cabat_shipyard::{
-        prelude, Event, EventHandler, Plugin, Res, ResMut, Stages, SubStages, UniqueTools,
-        WorkloadBuilder, WorldTools,
+        find_all_by_name, find_by_name, find_by_tag, prelude, report_missing_uniques, run_once,
+        trace_span, AppState, Commands, CustomSubStage, EntityLabel, Event, EventHandler,
+        EventReader, EventRecord, EventTrace, EventWriter, Events, FrameContext, FrameTimings,
+        GraphFormat, Name, NonSendRes, NonSendResMut, Plugin, PluginGroup, PluginGroupBuilder, Res,
+        ResMut, SnapshotRegistry, Stages, State, SubStageRef, SubStages, Tags, UniqueTools,
+        WorkloadBuilder, WorkloadGraph, WorldSnapshot, WorldTools, WrappedUnique,
     };
 }
 
 pub mod spatial {
-    pub use cabat_spatial::Transform;
+    pub use cabat_spatial::{
+        sys_update_tweens, Aabb, Acceleration, AngularVelocity, Bounds, Collider, ColliderShape,
+        CollisionEndedEvent, CollisionPair, CollisionPlugin, CollisionStartedEvent,
+        CollisionTracker, Ease, FnLens, KinematicsPlugin, Lens, Ray, RayHit, RotationLens,
+        ScaleLens, SpatialIndex, SpatialIndexPlugin, Transform, Transform2d, Transform2dPlugin,
+        TranslationLens, Tween, TweenCompletedEvent, TweenPlugin, Velocity,
+    };
 }
 
 pub mod assets {
     pub use cabat_assets::{
         asset_loader::AssetTypeLoader,
         asset_storage::AssetStorage,
+        config::{
+            Config, ConfigPlugin, ConfigRegistry, ConfigReloadedEvent, RegisterConfigSection,
+        },
         handle::{Handle, HandleId},
+        stats::{AssetChange, AssetChangeKind, AssetStats, AssetTypeStats},
         Asset, AssetStoragePlugin,
     };
 }
 
+pub mod ui {
+    pub use cabat_ui::{
+        Interaction, SizeConstraints, TextInput, TextInputChanged, TextInputSubmitted, UiAlign,
+        UiContainer, UiDirection, UiFocus, UiInteraction, UiNode, UiPlugin, UiPointer, UiRect,
+    };
+}
+
 //====================================================================
 
 pub struct DefaultPlugins;
 
-impl Plugin for DefaultPlugins {
-    fn build(self, workload_builder: &cabat_shipyard::WorkloadBuilder) {
-        workload_builder
-            .add_plugin(runner::ToolsPlugin)
-            .add_plugin(assets::AssetStoragePlugin)
-            .add_plugin(renderer::FullRendererPlugin);
+impl PluginGroup for DefaultPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(runner::ToolsPlugin)
+            .add(assets::AssetStoragePlugin)
+            .add(audio::AudioPlugin)
+            .add_group(renderer::FullRendererPlugin)
+            .add(ui::UiPlugin)
     }
 }
 